@@ -75,6 +75,10 @@ error: the `wasm32-wasi` target is not installed
     );
     build_wasm_test_program("redis-rust.wasm", "crates/redis/tests/rust");
     build_wasm_test_program("wagi-test.wasm", "crates/trigger-http/tests/wagi-test");
+    build_wasm_test_program(
+        "context-test.wasm",
+        "crates/trigger-http/tests/context-test",
+    );
 
     build_wasm_test_program(
         "spin-http-benchmark.wasm",
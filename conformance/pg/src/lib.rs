@@ -0,0 +1,436 @@
+//! Conformance cases for the `fermyon:spin/postgres` interface.
+//!
+//! Each case is a route which responds `200 OK` if the host behaves as the
+//! interface requires, and `500` with a description of the difference if
+//! not. The database to use is given by the runner in the
+//! `x-conformance-db-url` header, so the suite doesn't depend on how a host
+//! supplies configuration.
+
+use anyhow::{bail, ensure, Context, Result};
+use spin_sdk::{
+    http_component,
+    pg::{Connection, DbDataType, DbValue, Decode, ParameterValue, PgError},
+};
+
+const DB_URL_HEADER: &str = "x-conformance-db-url";
+const REQUEST_ID_HEADER: &str = "x-conformance-request-id";
+
+type Case = fn(&Connection, &http::Request<()>) -> Result<()>;
+
+const CASES: &[(&str, Case)] = &[
+    ("/types/boolean", boolean),
+    ("/types/integer", integer),
+    ("/types/floating", floating),
+    ("/types/character", character),
+    ("/types/binary", binary),
+    ("/types/columns", columns),
+    ("/nulls/results", null_results),
+    ("/nulls/parameters", null_parameters),
+    ("/execute/rows-affected", rows_affected),
+    ("/execute/returning", execute_returning),
+    ("/execute/batch", batch_execute),
+    ("/execute/many", execute_many),
+    ("/copy", copy),
+    ("/errors/server", server_error),
+    ("/errors/bad-parameter", bad_parameter),
+    ("/errors/timeout", timeout),
+    ("/errors/connection", connection_error),
+    ("/concurrency", concurrency),
+];
+
+#[http_component]
+fn handle(req: http::Request<()>) -> Result<http::Response<String>> {
+    let path = req.uri().path();
+    if path == "/cases" {
+        let names = CASES.iter().map(|(name, _)| *name).collect::<Vec<_>>();
+        return Ok(http::Response::builder()
+            .status(200)
+            .body(names.join("\n"))?);
+    }
+    let Some((_, case)) = CASES.iter().find(|(name, _)| *name == path) else {
+        return Ok(http::Response::builder()
+            .status(404)
+            .body("No such case".into())?);
+    };
+    let outcome = db_url(&req)
+        .and_then(|url| Connection::open(&url).context("Failed to connect"))
+        .and_then(|conn| case(&conn, &req));
+    Ok(match outcome {
+        Ok(()) => http::Response::builder().status(200).body("OK".into())?,
+        Err(e) => http::Response::builder()
+            .status(500)
+            .body(format!("{e:#}"))?,
+    })
+}
+
+fn db_url(req: &http::Request<()>) -> Result<String> {
+    let url = req
+        .headers()
+        .get(DB_URL_HEADER)
+        .with_context(|| format!("The runner must set the {DB_URL_HEADER} header"))?;
+    Ok(url.to_str()?.to_owned())
+}
+
+/// Queries a single value.
+fn select_one<T: Decode>(conn: &Connection, sql: &str, params: &[ParameterValue]) -> Result<T> {
+    let rowset = conn.query(sql, params)?;
+    ensure!(
+        rowset.rows.len() == 1,
+        "{sql}: expected one row, got {}",
+        rowset.rows.len()
+    );
+    let value = rowset.rows[0].first().context("row has no columns")?;
+    Ok(T::decode(value)?)
+}
+
+fn round_trip<T: Decode + PartialEq + std::fmt::Debug>(
+    conn: &Connection,
+    cast: &str,
+    param: ParameterValue,
+    expected: T,
+) -> Result<()> {
+    let sql = format!("SELECT $1::{cast}");
+    let actual: T = select_one(conn, &sql, &[param])?;
+    ensure!(
+        actual == expected,
+        "{sql}: expected {expected:?}, got {actual:?}"
+    );
+    Ok(())
+}
+
+fn boolean(conn: &Connection, _: &http::Request<()>) -> Result<()> {
+    round_trip(conn, "bool", ParameterValue::Boolean(true), true)?;
+    round_trip(conn, "bool", ParameterValue::Boolean(false), false)?;
+    let literal: bool = select_one(conn, "SELECT 1 < 2", &[])?;
+    ensure!(literal, "SELECT 1 < 2 should be true");
+    Ok(())
+}
+
+fn integer(conn: &Connection, _: &http::Request<()>) -> Result<()> {
+    for n in [i16::MIN, -1, 0, 1, i16::MAX] {
+        round_trip(conn, "int2", ParameterValue::Int16(n), n)?;
+    }
+    for n in [i32::MIN, -1, 0, 1, i32::MAX] {
+        round_trip(conn, "int4", ParameterValue::Int32(n), n)?;
+    }
+    for n in [i64::MIN, -1, 0, 1, i64::MAX] {
+        round_trip(conn, "int8", ParameterValue::Int64(n), n)?;
+    }
+    conn.execute("CREATE TEMPORARY TABLE t_serial (id serial)", &[])?;
+    let serial: i32 = select_one(
+        conn,
+        "INSERT INTO t_serial DEFAULT VALUES RETURNING id",
+        &[],
+    )?;
+    ensure!(
+        serial == 1,
+        "the first serial value should be 1, got {serial}"
+    );
+    Ok(())
+}
+
+fn floating(conn: &Connection, _: &http::Request<()>) -> Result<()> {
+    for n in [f32::MIN, -1.5, 0.0, 1.5, f32::MAX] {
+        round_trip(conn, "float4", ParameterValue::Floating32(n), n)?;
+    }
+    for n in [f64::MIN, -1.5, 0.0, 1.5, f64::MAX] {
+        round_trip(conn, "float8", ParameterValue::Floating64(n), n)?;
+    }
+    let infinity: f64 = select_one(conn, "SELECT 'Infinity'::float8", &[])?;
+    ensure!(
+        infinity == f64::INFINITY,
+        "expected infinity, got {infinity}"
+    );
+    let nan: f64 = select_one(conn, "SELECT 'NaN'::float8", &[])?;
+    ensure!(nan.is_nan(), "expected NaN, got {nan}");
+    Ok(())
+}
+
+fn character(conn: &Connection, _: &http::Request<()>) -> Result<()> {
+    for s in [
+        "",
+        "hello",
+        "O'Brien",
+        "emoji 🦀 and ünïcödé",
+        "line\nbreak",
+    ] {
+        for cast in ["text", "varchar"] {
+            round_trip(conn, cast, ParameterValue::Str(s.into()), s.to_owned())?;
+        }
+    }
+    // CHAR(n) pads with spaces.
+    round_trip(
+        conn,
+        "char(5)",
+        ParameterValue::Str("ab".into()),
+        "ab   ".to_owned(),
+    )?;
+    Ok(())
+}
+
+fn binary(conn: &Connection, _: &http::Request<()>) -> Result<()> {
+    let all_bytes = (0..=255).collect::<Vec<u8>>();
+    round_trip(conn, "bytea", ParameterValue::Binary(vec![]), vec![])?;
+    round_trip(
+        conn,
+        "bytea",
+        ParameterValue::Binary(all_bytes.clone()),
+        all_bytes,
+    )?;
+    Ok(())
+}
+
+fn columns(conn: &Connection, _: &http::Request<()>) -> Result<()> {
+    let rowset = conn.query(
+        "SELECT true AS b, 1::int2 AS i2, 1::int4 AS i4, 1::int8 AS i8, 1::float4 AS f4, 1::float8 AS f8, 'x'::text AS s, '\\x00'::bytea AS bin, now() AS other",
+        &[],
+    )?;
+    let expected = [
+        ("b", DbDataType::Boolean),
+        ("i2", DbDataType::Int16),
+        ("i4", DbDataType::Int32),
+        ("i8", DbDataType::Int64),
+        ("f4", DbDataType::Floating32),
+        ("f8", DbDataType::Floating64),
+        ("s", DbDataType::Str),
+        ("bin", DbDataType::Binary),
+        ("other", DbDataType::Other),
+    ];
+    ensure!(
+        rowset.columns.len() == expected.len(),
+        "expected {} columns, got {}",
+        expected.len(),
+        rowset.columns.len()
+    );
+    for (column, (name, data_type)) in rowset.columns.iter().zip(expected) {
+        ensure!(
+            column.name == name && column.data_type == data_type,
+            "expected column {name}: {data_type:?}, got {}: {:?}",
+            column.name,
+            column.data_type
+        );
+    }
+    // Values of types the interface doesn't support are reported as such,
+    // rather than failing the query.
+    let other = rowset.rows[0].last().context("row has no columns")?;
+    ensure!(
+        matches!(other, DbValue::Unsupported),
+        "expected an unsupported value, got {other:?}"
+    );
+    Ok(())
+}
+
+fn null_results(conn: &Connection, _: &http::Request<()>) -> Result<()> {
+    for cast in [
+        "bool", "int2", "int4", "int8", "float4", "float8", "text", "bytea",
+    ] {
+        let sql = format!("SELECT NULL::{cast}");
+        let rowset = conn.query(&sql, &[])?;
+        let value = &rowset.rows[0][0];
+        ensure!(
+            matches!(value, DbValue::DbNull),
+            "{sql}: expected NULL, got {value:?}"
+        );
+    }
+    let value: Option<i32> = select_one(conn, "SELECT NULL::int4", &[])?;
+    ensure!(value.is_none(), "expected None, got {value:?}");
+    Ok(())
+}
+
+fn null_parameters(conn: &Connection, _: &http::Request<()>) -> Result<()> {
+    let typed_nulls = [
+        ParameterValue::null_boolean(),
+        ParameterValue::null_int16(),
+        ParameterValue::null_int32(),
+        ParameterValue::null_int64(),
+        ParameterValue::null_floating32(),
+        ParameterValue::null_floating64(),
+        ParameterValue::null_str(),
+        ParameterValue::null_binary(),
+    ];
+    for null in typed_nulls {
+        // The type of `$1` can only be known from the parameter.
+        let is_null: bool = select_one(conn, "SELECT $1 IS NULL", &[null.clone()])?;
+        ensure!(is_null, "{null:?} should be NULL");
+    }
+    let is_null: bool = select_one(conn, "SELECT $1::int4 IS NULL", &[ParameterValue::DbNull])?;
+    ensure!(is_null, "DbNull should be NULL");
+
+    conn.execute("CREATE TEMPORARY TABLE t_nulls (n int4)", &[])?;
+    conn.execute("INSERT INTO t_nulls VALUES ($1)", &[ParameterValue::DbNull])?;
+    let value: Option<i32> = select_one(conn, "SELECT n FROM t_nulls", &[])?;
+    ensure!(value.is_none(), "inserted NULL read back as {value:?}");
+    Ok(())
+}
+
+fn rows_affected(conn: &Connection, _: &http::Request<()>) -> Result<()> {
+    conn.execute("CREATE TEMPORARY TABLE t_rows (n int4)", &[])?;
+    let inserted = conn.execute("INSERT INTO t_rows VALUES (1), (2), (3)", &[])?;
+    ensure!(inserted == 3, "INSERT of 3 rows reported {inserted}");
+    let updated = conn.execute(
+        "UPDATE t_rows SET n = n + 1 WHERE n > $1",
+        &[ParameterValue::Int32(1)],
+    )?;
+    ensure!(updated == 2, "UPDATE of 2 rows reported {updated}");
+    let deleted = conn.execute("DELETE FROM t_rows", &[])?;
+    ensure!(deleted == 3, "DELETE of 3 rows reported {deleted}");
+    Ok(())
+}
+
+fn execute_returning(conn: &Connection, _: &http::Request<()>) -> Result<()> {
+    conn.execute(
+        "CREATE TEMPORARY TABLE t_returning (id serial, s text)",
+        &[],
+    )?;
+    let result = conn.execute_returning(
+        "INSERT INTO t_returning (s) VALUES ($1), ($2) RETURNING id, s",
+        &[
+            ParameterValue::Str("a".into()),
+            ParameterValue::Str("b".into()),
+        ],
+    )?;
+    ensure!(
+        result.rows_affected == 2,
+        "reported {} rows affected",
+        result.rows_affected
+    );
+    ensure!(
+        result.returning.rows.len() == 2,
+        "returned {} rows",
+        result.returning.rows.len()
+    );
+    let s = String::decode(&result.returning.rows[1][1])?;
+    ensure!(s == "b", "returned {s:?} for the second row");
+    Ok(())
+}
+
+fn batch_execute(conn: &Connection, _: &http::Request<()>) -> Result<()> {
+    conn.batch_execute(
+        "CREATE TEMPORARY TABLE t_batch (n int4); INSERT INTO t_batch VALUES (1); INSERT INTO t_batch VALUES (2);",
+    )?;
+    let count: i64 = select_one(conn, "SELECT count(*) FROM t_batch", &[])?;
+    ensure!(count == 2, "batch inserted {count} rows");
+    Ok(())
+}
+
+fn execute_many(conn: &Connection, _: &http::Request<()>) -> Result<()> {
+    conn.execute("CREATE TEMPORARY TABLE t_many (n int4 UNIQUE)", &[])?;
+    let sets = (1..=5)
+        .map(|n| vec![ParameterValue::Int32(n)])
+        .collect::<Vec<_>>();
+    let affected = conn.execute_many("INSERT INTO t_many VALUES ($1)", &sets)?;
+    ensure!(affected == 5, "reported {affected} rows affected");
+
+    // The sets are applied in one transaction, so a failure applies none.
+    let sets = [6, 7, 1].map(|n| vec![ParameterValue::Int32(n)]).to_vec();
+    ensure!(
+        conn.execute_many("INSERT INTO t_many VALUES ($1)", &sets)
+            .is_err(),
+        "inserting a duplicate should fail"
+    );
+    let count: i64 = select_one(conn, "SELECT count(*) FROM t_many", &[])?;
+    ensure!(count == 5, "a failed execute-many left {count} rows");
+    Ok(())
+}
+
+fn copy(conn: &Connection, _: &http::Request<()>) -> Result<()> {
+    conn.execute("CREATE TEMPORARY TABLE t_copy (n int4, s text)", &[])?;
+    let sink = conn.copy_in("COPY t_copy FROM STDIN WITH (FORMAT csv)")?;
+    sink.write(b"1,one\n2,")?;
+    sink.write(b"two\n")?;
+    let loaded = sink.finish()?;
+    ensure!(loaded == 2, "COPY FROM reported {loaded} rows");
+
+    let stream =
+        conn.copy_out("COPY (SELECT * FROM t_copy ORDER BY n) TO STDOUT WITH (FORMAT csv)")?;
+    let mut data = vec![];
+    while let Some(chunk) = stream.read()? {
+        data.extend(chunk);
+    }
+    ensure!(
+        data == b"1,one\n2,two\n",
+        "COPY TO produced {:?}",
+        String::from_utf8_lossy(&data)
+    );
+    Ok(())
+}
+
+fn server_error(conn: &Connection, _: &http::Request<()>) -> Result<()> {
+    match conn.query("SELECT * FROM conformance_no_such_table", &[]) {
+        Err(PgError::ServerError(e)) => {
+            ensure!(
+                e.code == "42P01",
+                "expected SQLSTATE 42P01 (undefined_table), got {}",
+                e.code
+            );
+            ensure!(!e.message.is_empty(), "the server error has no message");
+        }
+        other => bail!("expected a server error, got {other:?}"),
+    }
+    // The connection is still usable after an error.
+    let n: i32 = select_one(conn, "SELECT 1", &[])?;
+    ensure!(n == 1, "SELECT 1 returned {n}");
+    Ok(())
+}
+
+fn bad_parameter(conn: &Connection, _: &http::Request<()>) -> Result<()> {
+    let result = conn.query("SELECT $1::int4", &[ParameterValue::Str("one".into())]);
+    ensure!(
+        result.is_err(),
+        "a text parameter for an int4 placeholder should fail, got {result:?}"
+    );
+    let result = conn.query("SELECT $1::int4, $2::int4", &[ParameterValue::Int32(1)]);
+    ensure!(
+        result.is_err(),
+        "too few parameters should fail, got {result:?}"
+    );
+    Ok(())
+}
+
+fn timeout(conn: &Connection, _: &http::Request<()>) -> Result<()> {
+    conn.set_timeout(Some(100));
+    let result = conn.execute("SELECT pg_sleep(5)", &[]);
+    ensure!(result.is_err(), "a query over the timeout should fail");
+    conn.set_timeout(None);
+    conn.execute("SELECT pg_sleep(0.2)", &[])
+        .context("a query with the timeout removed should succeed")?;
+    Ok(())
+}
+
+fn connection_error(_: &Connection, req: &http::Request<()>) -> Result<()> {
+    let url = db_url(req)?;
+    let bad_url = if url.contains("://") {
+        format!(
+            "{}_conformance_missing",
+            url.split('?').next().unwrap_or_default()
+        )
+    } else {
+        format!("{url} dbname=conformance_missing")
+    };
+    match Connection::open(&bad_url) {
+        Err(PgError::ConnectionFailed(_) | PgError::ServerError(_)) => Ok(()),
+        Err(other) => bail!("expected a connection error, got {other:?}"),
+        Ok(_) => bail!("connecting to a missing database should fail"),
+    }
+}
+
+fn concurrency(conn: &Connection, req: &http::Request<()>) -> Result<()> {
+    // The runner sends many of these at once; each must see only its own
+    // session's temporary table.
+    let id = req
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|id| id.to_str().ok()?.parse::<i32>().ok())
+        .unwrap_or_default();
+    conn.execute("CREATE TEMPORARY TABLE t_concurrency (id int4)", &[])?;
+    conn.execute(
+        "INSERT INTO t_concurrency VALUES ($1)",
+        &[ParameterValue::Int32(id)],
+    )?;
+    conn.execute("SELECT pg_sleep(0.05)", &[])?;
+    let ids = conn.query("SELECT id FROM t_concurrency", &[])?;
+    ensure!(ids.rows.len() == 1, "session saw {} rows", ids.rows.len());
+    let seen = i32::decode(&ids.rows[0][0])?;
+    ensure!(seen == id, "request {id} saw the row of request {seen}");
+    Ok(())
+}
@@ -0,0 +1,237 @@
+//! State shared in memory by the components and instances of a running
+//! application, for data such as feature flags and counters which is read
+//! and written too often for a key-value store round trip.
+//!
+//! The state is not persisted: it starts out empty and is lost when the
+//! application stops, and it is not shared between replicas.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use anyhow::Result;
+use spin_core::{async_trait, HostComponent};
+use spin_world::v3::app_state;
+
+pub use app_state::Error;
+
+/// An application's shared state, holding up to a limited number of bytes of
+/// keys and values.
+pub struct AppState {
+    max_size: usize,
+    state: Mutex<State>,
+}
+
+#[derive(Default)]
+struct State {
+    entries: HashMap<String, Vec<u8>>,
+    /// The total size of the keys and values of the entries
+    size: usize,
+}
+
+impl State {
+    /// Sets `key` to `value` if the state stays within `max_size`.
+    fn set(&mut self, max_size: usize, key: &str, value: Vec<u8>) -> Result<(), Error> {
+        let old_size = self.entries.get(key).map_or(0, |old| key.len() + old.len());
+        let size = self.size - old_size + key.len() + value.len();
+        if size > max_size {
+            return Err(Error::TooLarge);
+        }
+        self.size = size;
+        self.entries.insert(key.to_owned(), value);
+        Ok(())
+    }
+}
+
+impl AppState {
+    /// An empty state which may hold up to `max_size` bytes of keys and
+    /// values.
+    pub fn new(max_size: usize) -> Self {
+        Self {
+            max_size,
+            state: Default::default(),
+        }
+    }
+
+    /// Returns the value of `key`, if it is set.
+    pub fn get(&self, key: &str) -> Option<Vec<u8>> {
+        self.state.lock().unwrap().entries.get(key).cloned()
+    }
+
+    /// Sets `key` to `value`, unless the state would exceed its size limit.
+    pub fn set(&self, key: &str, value: Vec<u8>) -> Result<(), Error> {
+        self.state.lock().unwrap().set(self.max_size, key, value)
+    }
+
+    /// Removes `key`, returning whether it was set.
+    pub fn delete(&self, key: &str) -> bool {
+        let mut state = self.state.lock().unwrap();
+        match state.entries.remove(key) {
+            Some(value) => {
+                state.size -= key.len() + value.len();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Adds `delta` to the counter at `key`, stored as a decimal string,
+    /// returning its new value.
+    pub fn increment(&self, key: &str, delta: i64) -> Result<i64, Error> {
+        let mut state = self.state.lock().unwrap();
+        let current = match state.entries.get(key) {
+            Some(value) => std::str::from_utf8(value)
+                .ok()
+                .and_then(|value| value.parse::<i64>().ok())
+                .ok_or(Error::NotACounter)?,
+            None => 0,
+        };
+        let new = current
+            .checked_add(delta)
+            .ok_or_else(|| Error::Other(format!("counter {key:?} overflowed")))?;
+        state.set(self.max_size, key, new.to_string().into_bytes())?;
+        Ok(new)
+    }
+
+    /// Sets `key` to `new` if its value is `expected`, or it is not set and
+    /// `expected` is `None`, returning whether it was set.
+    pub fn compare_and_swap(
+        &self,
+        key: &str,
+        expected: Option<&[u8]>,
+        new: Vec<u8>,
+    ) -> Result<bool, Error> {
+        let mut state = self.state.lock().unwrap();
+        if state.entries.get(key).map(Vec::as_slice) != expected {
+            return Ok(false);
+        }
+        state.set(self.max_size, key, new)?;
+        Ok(true)
+    }
+
+    /// Returns the keys which are set.
+    pub fn keys(&self) -> Vec<String> {
+        self.state.lock().unwrap().entries.keys().cloned().collect()
+    }
+}
+
+pub struct AppStateComponent {
+    state: Arc<AppState>,
+}
+
+impl AppStateComponent {
+    /// A component whose state may hold up to `max_size` bytes of keys and
+    /// values.
+    pub fn new(max_size: usize) -> Self {
+        Self {
+            state: Arc::new(AppState::new(max_size)),
+        }
+    }
+}
+
+impl HostComponent for AppStateComponent {
+    type Data = AppStateDispatch;
+
+    fn add_to_linker<T: Send>(
+        linker: &mut spin_core::Linker<T>,
+        get: impl Fn(&mut spin_core::Data<T>) -> &mut Self::Data + Send + Sync + Copy + 'static,
+    ) -> anyhow::Result<()> {
+        app_state::add_to_linker(linker, get)
+    }
+
+    fn build_data(&self) -> Self::Data {
+        AppStateDispatch {
+            state: self.state.clone(),
+        }
+    }
+}
+
+pub struct AppStateDispatch {
+    state: Arc<AppState>,
+}
+
+#[async_trait]
+impl app_state::Host for AppStateDispatch {
+    async fn get(&mut self, key: String) -> Result<Result<Option<Vec<u8>>, Error>> {
+        Ok(Ok(self.state.get(&key)))
+    }
+
+    async fn set(&mut self, key: String, value: Vec<u8>) -> Result<Result<(), Error>> {
+        Ok(self.state.set(&key, value))
+    }
+
+    async fn delete(&mut self, key: String) -> Result<Result<bool, Error>> {
+        Ok(Ok(self.state.delete(&key)))
+    }
+
+    async fn increment(&mut self, key: String, delta: i64) -> Result<Result<i64, Error>> {
+        Ok(self.state.increment(&key, delta))
+    }
+
+    async fn compare_and_swap(
+        &mut self,
+        key: String,
+        expected: Option<Vec<u8>>,
+        new: Vec<u8>,
+    ) -> Result<Result<bool, Error>> {
+        Ok(self.state.compare_and_swap(&key, expected.as_deref(), new))
+    }
+
+    async fn get_keys(&mut self) -> Result<Result<Vec<String>, Error>> {
+        Ok(Ok(self.state.keys()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn state_stays_within_its_size_limit() {
+        let state = AppState::new(10);
+        state.set("a", b"1234".to_vec()).unwrap();
+        assert!(matches!(
+            state.set("b", b"12345678".to_vec()),
+            Err(Error::TooLarge)
+        ));
+        // Replacing a value frees its old size.
+        state.set("a", b"123456789".to_vec()).unwrap();
+
+        assert!(state.delete("a"));
+        assert!(!state.delete("a"));
+        state.set("b", b"12345678".to_vec()).unwrap();
+        assert_eq!(vec!["b".to_owned()], state.keys());
+    }
+
+    #[test]
+    fn counters_are_decimal_strings() {
+        let state = AppState::new(100);
+        assert!(matches!(state.increment("hits", 5), Ok(5)));
+        assert!(matches!(state.increment("hits", -2), Ok(3)));
+        assert_eq!(Some(b"3".to_vec()), state.get("hits"));
+
+        state.set("flag", b"on".to_vec()).unwrap();
+        assert!(matches!(
+            state.increment("flag", 1),
+            Err(Error::NotACounter)
+        ));
+        state.set("max", i64::MAX.to_string().into_bytes()).unwrap();
+        assert!(matches!(state.increment("max", 1), Err(Error::Other(_))));
+    }
+
+    #[test]
+    fn values_are_swapped_if_unchanged() {
+        let state = AppState::new(100);
+        assert!(state
+            .compare_and_swap("flag", None, b"on".to_vec())
+            .unwrap());
+        assert!(!state
+            .compare_and_swap("flag", None, b"off".to_vec())
+            .unwrap());
+        assert!(state
+            .compare_and_swap("flag", Some(b"on"), b"off".to_vec())
+            .unwrap());
+        assert_eq!(Some(b"off".to_vec()), state.get("flag"));
+    }
+}
@@ -31,6 +31,9 @@ pub const APP_VERSION_KEY: MetadataKey = MetadataKey::new("version");
 pub const APP_DESCRIPTION_KEY: MetadataKey = MetadataKey::new("description");
 /// MetadataKey for extracting the OCI image digest.
 pub const OCI_IMAGE_DIGEST_KEY: MetadataKey = MetadataKey::new("oci_image_digest");
+/// MetadataKey for extracting the optional host capabilities a component is
+/// granted, such as `image`.
+pub const CAPABILITIES_KEY: MetadataKey<Vec<String>> = MetadataKey::new("capabilities");
 
 /// A trait for implementing the low-level operations needed to load an [`App`].
 // TODO(lann): Should this migrate to spin-loader?
@@ -298,6 +301,12 @@ impl<'a, L: MaybeLoader> AppComponent<'a, L> {
     pub fn config(&self) -> impl Iterator<Item = (&String, &String)> {
         self.locked.config.iter()
     }
+
+    /// Returns an iterator of the environment variables set for this
+    /// component.
+    pub fn environment(&self) -> impl Iterator<Item = (&String, &String)> {
+        self.locked.env.iter()
+    }
 }
 
 impl<'a> AppComponent<'a> {
@@ -331,9 +340,15 @@ impl<'a> AppComponent<'a> {
     ///
     /// In particular, the WASI 'env' and "preloaded dirs" are set up, and any
     /// [`DynamicHostComponent`]s associated with the source [`AppLoader`] are
-    /// configured.
+    /// configured. In a replay, the WASI 'env' is the recorded one.
     pub async fn apply_store_config(&self, builder: &mut StoreBuilder) -> Result<()> {
-        builder.env(&self.locked.env).map_err(Error::CoreError)?;
+        match spin_core::replay::replayed_environment() {
+            Some(env) => builder.env(env).map_err(Error::CoreError)?,
+            None => {
+                spin_core::replay::record_environment(&self.locked.env);
+                builder.env(&self.locked.env).map_err(Error::CoreError)?;
+            }
+        }
 
         let loader = self.app.loader;
         loader
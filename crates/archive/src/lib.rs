@@ -0,0 +1,263 @@
+//! Packaging Spin applications as single-file archives.
+//!
+//! An archive is a gzipped tar file, conventionally named `*.spin`, which
+//! holds everything needed to run an application without network access:
+//!
+//! - `spin.toml`, the manifest the archive was made from, for reference;
+//! - `spin.lock`, the locked application, whose content sources are paths
+//!   within the archive;
+//! - `components/<id>/component.wasm`, each component's Wasm, whose digest
+//!   is recorded in the locked application;
+//! - `components/<id>/files/<n>/`, each of a component's file mounts.
+#![deny(missing_docs)]
+
+use std::{
+    fs::File,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, bail, ensure, Context, Result};
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use spin_common::{sha256::hex_digest_from_file, url::parse_file_url};
+use spin_locked_app::locked::{ContentRef, LockedApp};
+use tar::{Header, HeaderMode};
+use url::Url;
+
+/// The extension archive files are recognised by.
+pub const ARCHIVE_EXTENSION: &str = "spin";
+
+const MANIFEST_PATH: &str = "spin.toml";
+const LOCKED_PATH: &str = "spin.lock";
+
+/// Returns whether `path` names an archive rather than a manifest.
+pub fn is_archive(path: &Path) -> bool {
+    path.is_file() && path.extension().is_some_and(|ext| ext == ARCHIVE_EXTENSION)
+}
+
+/// Writes an archive of `locked`, whose component and file sources must be
+/// local files, and of the manifest it was loaded from to `dest`.
+pub fn write(mut locked: LockedApp, manifest: &Path, dest: &Path) -> Result<()> {
+    let file = File::create(dest).with_context(|| format!("cannot create {dest:?}"))?;
+    let mut tar = tar::Builder::new(GzEncoder::new(file, Compression::default()));
+    // Archives of the same application are identical, whoever makes them.
+    tar.mode(HeaderMode::Deterministic);
+
+    tar.append_path_with_name(manifest, MANIFEST_PATH)
+        .with_context(|| format!("cannot add {manifest:?} to archive"))?;
+
+    for c in &mut locked.components {
+        let component_dir = format!("components/{}", c.id);
+        let wasm = local_source(&c.source.content)?;
+        let wasm_path = format!("{component_dir}/component.wasm");
+        let digest = hex_digest_from_file(&wasm)
+            .with_context(|| format!("cannot read Wasm for component {:?}", c.id))?;
+        tar.append_path_with_name(&wasm, &wasm_path)
+            .with_context(|| format!("cannot add {wasm:?} to archive"))?;
+        c.source.content = ContentRef {
+            source: Some(wasm_path),
+            digest: Some(format!("sha256:{digest}")),
+            ..Default::default()
+        };
+
+        for (index, f) in c.files.iter_mut().enumerate() {
+            if f.content.inline.is_some() {
+                continue;
+            }
+            let source = local_source(&f.content)?;
+            let files_dir = format!("{component_dir}/files/{index}");
+            tar.append_dir_all(&files_dir, &source)
+                .with_context(|| format!("cannot add {source:?} to archive"))?;
+            f.content = ContentRef {
+                source: Some(files_dir),
+                ..Default::default()
+            };
+        }
+    }
+    locked.metadata.remove("origin");
+
+    let locked_json = locked.to_json().context("could not serialize locked app")?;
+    let mut header = Header::new_gnu();
+    header.set_size(locked_json.len() as u64);
+    header.set_mode(0o644);
+    header.set_cksum();
+    tar.append_data(&mut header, LOCKED_PATH, &locked_json[..])
+        .context("cannot add spin.lock to archive")?;
+
+    tar.into_inner()?.finish()?;
+    Ok(())
+}
+
+/// ArchiveLoader loads an archived app in preparation for running with Spin.
+pub struct ArchiveLoader {
+    working_dir: PathBuf,
+}
+
+impl ArchiveLoader {
+    /// Creates a new ArchiveLoader which unpacks archives into the given
+    /// working_dir.
+    pub fn new(working_dir: impl Into<PathBuf>) -> Self {
+        let working_dir = working_dir.into();
+        Self { working_dir }
+    }
+
+    /// Unpacks the archive at `archive` and returns its LockedApp, whose
+    /// content sources point at the unpacked files.
+    pub fn load_app(&self, archive: &Path) -> Result<LockedApp> {
+        let dir = self.working_dir.join("archive");
+        std::fs::create_dir_all(&dir)
+            .with_context(|| format!("cannot create archive directory {dir:?}"))?;
+        let file = File::open(archive).with_context(|| format!("cannot open {archive:?}"))?;
+        // Entries which would unpack outside `dir` are skipped.
+        tar::Archive::new(GzDecoder::new(file))
+            .unpack(&dir)
+            .with_context(|| format!("cannot unpack {archive:?}"))?;
+
+        let locked_path = dir.join(LOCKED_PATH);
+        let locked_content = std::fs::read(&locked_path)
+            .with_context(|| format!("{archive:?} is not a Spin archive: it has no spin.lock"))?;
+        let mut locked = LockedApp::from_json(&locked_content)
+            .with_context(|| format!("failed to decode locked app from {archive:?}"))?;
+
+        for c in &mut locked.components {
+            let wasm = resolve(&dir, &mut c.source.content)?;
+            verify_digest(&wasm, &c.source.content)
+                .with_context(|| format!("invalid Wasm for component {:?}", c.id))?;
+            for f in &mut c.files {
+                if f.content.inline.is_none() {
+                    resolve(&dir, &mut f.content)?;
+                }
+            }
+        }
+        Ok(locked)
+    }
+}
+
+/// Points `content`, whose source is a path within the archive unpacked in
+/// `dir`, at the unpacked file, returning its path.
+fn resolve(dir: &Path, content: &mut ContentRef) -> Result<PathBuf> {
+    let source = content
+        .source
+        .as_deref()
+        .context("archived content should contain a source")?;
+    ensure!(is_safe_to_join(source), "invalid archive path {source:?}");
+    let path = dir.join(source);
+    ensure!(path.exists(), "archive is missing {source:?}");
+    let url = Url::from_file_path(&path).map_err(|_| anyhow!("couldn't build file URL"))?;
+    content.source = Some(url.to_string());
+    Ok(path)
+}
+
+fn verify_digest(path: &Path, content: &ContentRef) -> Result<()> {
+    let Some(expected) = content.digest.as_deref() else {
+        return Ok(());
+    };
+    let actual = format!("sha256:{}", hex_digest_from_file(path)?);
+    if actual != expected {
+        bail!("expected digest {expected}, but {path:?} has digest {actual}");
+    }
+    Ok(())
+}
+
+fn local_source(content: &ContentRef) -> Result<PathBuf> {
+    let source = content
+        .source
+        .as_deref()
+        .context("content loaded from disk should contain a file source")?;
+    parse_file_url(source)
+}
+
+fn is_safe_to_join(path: impl AsRef<Path>) -> bool {
+    path.as_ref()
+        .components()
+        .all(|c| matches!(c, std::path::Component::Normal(_)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file_url(path: &Path) -> String {
+        Url::from_file_path(path).unwrap().to_string()
+    }
+
+    fn locked_app(wasm: &Path, files: &Path) -> LockedApp {
+        let json = serde_json::json!({
+            "spin_lock_version": 0,
+            "metadata": { "name": "hello", "origin": "file:///src/spin.toml" },
+            "triggers": [],
+            "components": [{
+                "id": "hello",
+                "source": { "content_type": "application/wasm", "source": file_url(wasm) },
+                "files": [{ "source": file_url(files), "path": "/" }],
+            }],
+        });
+        serde_json::from_value(json).unwrap()
+    }
+
+    #[test]
+    fn archives_round_trip() -> Result<()> {
+        let src = tempfile::tempdir()?;
+        let manifest = src.path().join("spin.toml");
+        std::fs::write(&manifest, "spin_manifest_version = 2")?;
+        let wasm = src.path().join("hello.wasm");
+        std::fs::write(&wasm, b"\0asm")?;
+        let files = src.path().join("static");
+        std::fs::create_dir_all(files.join("css"))?;
+        std::fs::write(files.join("css/site.css"), "body {}")?;
+
+        let archive = src.path().join("hello.spin");
+        write(locked_app(&wasm, &files), &manifest, &archive)?;
+        assert!(is_archive(&archive));
+        assert!(!is_archive(&manifest));
+
+        let working_dir = tempfile::tempdir()?;
+        let locked = ArchiveLoader::new(working_dir.path()).load_app(&archive)?;
+        assert!(!locked.metadata.contains_key("origin"));
+
+        let component = &locked.components[0];
+        let wasm = parse_file_url(component.source.content.source.as_deref().unwrap())?;
+        assert!(wasm.starts_with(working_dir.path()));
+        assert_eq!(b"\0asm", &std::fs::read(wasm)?[..]);
+
+        let files = parse_file_url(component.files[0].content.source.as_deref().unwrap())?;
+        assert_eq!(
+            "body {}",
+            std::fs::read_to_string(files.join("css/site.css"))?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn tampered_wasm_is_rejected() -> Result<()> {
+        let src = tempfile::tempdir()?;
+        let manifest = src.path().join("spin.toml");
+        std::fs::write(&manifest, "")?;
+        let wasm = src.path().join("hello.wasm");
+        std::fs::write(&wasm, b"\0asm")?;
+        let mut locked = locked_app(&wasm, src.path());
+        locked.components[0].files.clear();
+        let archive = src.path().join("hello.spin");
+        write(locked, &manifest, &archive)?;
+
+        // Rewrite the archive with different Wasm under the recorded digest.
+        let unpacked = tempfile::tempdir()?;
+        tar::Archive::new(GzDecoder::new(File::open(&archive)?)).unpack(unpacked.path())?;
+        std::fs::write(
+            unpacked.path().join("components/hello/component.wasm"),
+            b"evil",
+        )?;
+        let mut tar = tar::Builder::new(GzEncoder::new(
+            File::create(&archive)?,
+            Compression::default(),
+        ));
+        tar.append_dir_all(".", unpacked.path())?;
+        tar.into_inner()?.finish()?;
+
+        let working_dir = tempfile::tempdir()?;
+        let err = ArchiveLoader::new(working_dir.path())
+            .load_app(&archive)
+            .unwrap_err();
+        assert!(format!("{err:#}").contains("digest"), "{err:#}");
+        Ok(())
+    }
+}
@@ -3,12 +3,17 @@
 //! A library for building Spin components.
 
 mod manifest;
+mod optimize;
+mod pre_init;
+mod size_report;
 
 use anyhow::{anyhow, bail, Context, Result};
 use manifest::ComponentBuildInfo;
 use spin_common::paths::parent_dir;
+use spin_manifest::schema::v2::Install;
 use std::{
     collections::HashSet,
+    ffi::OsString,
     path::{Path, PathBuf},
 };
 use subprocess::{Exec, Redirection};
@@ -17,12 +22,52 @@ use crate::manifest::component_build_configs;
 
 /// If present, run the build command of each component.
 pub async fn build(manifest_file: &Path, component_ids: &[String]) -> Result<()> {
+    let components_to_build = selected_components(manifest_file, component_ids).await?;
+    let app_dir = parent_dir(manifest_file)?;
+
+    if components_to_build.iter().all(|c| c.build.is_none()) {
+        println!("None of the components have a build command.");
+        println!("For information on specifying a build command, see https://developer.fermyon.com/spin/build#setting-up-for-spin-build.");
+        return Ok(());
+    }
+
+    components_to_build
+        .into_iter()
+        .map(|c| build_component(c, &app_dir))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    terminal::step!("Finished", "building all Spin components");
+    Ok(())
+}
+
+/// Pre-initialize each component which exports a `wizer.initialize`
+/// function, by running it and snapshotting the result into the component's
+/// source. This requires the `wizer` command.
+pub async fn pre_initialize(manifest_file: &Path, component_ids: &[String]) -> Result<()> {
+    let components = selected_components(manifest_file, component_ids).await?;
+    let app_dir = parent_dir(manifest_file)?;
+    pre_init::pre_initialize(&components, &app_dir)
+}
+
+/// Print a report of each component's size, broken down by section and by
+/// crate, with suggestions for making it smaller.
+pub async fn size_report(manifest_file: &Path, component_ids: &[String]) -> Result<()> {
+    let components = selected_components(manifest_file, component_ids).await?;
+    let app_dir = parent_dir(manifest_file)?;
+    size_report::print_size_reports(&components, &app_dir)
+}
+
+/// Returns the components with the given IDs, or all components if none are
+/// given.
+async fn selected_components(
+    manifest_file: &Path,
+    component_ids: &[String],
+) -> Result<Vec<ComponentBuildInfo>> {
     let components = component_build_configs(manifest_file)
         .await
         .with_context(|| format!("Cannot read manifest file from {}", manifest_file.display()))?;
-    let app_dir = parent_dir(manifest_file)?;
 
-    let components_to_build = if component_ids.is_empty() {
+    Ok(if component_ids.is_empty() {
         components
     } else {
         let all_ids: HashSet<_> = components.iter().map(|c| &c.id).collect();
@@ -40,26 +85,12 @@ pub async fn build(manifest_file: &Path, component_ids: &[String]) -> Result<()>
             .into_iter()
             .filter(|c| component_ids.contains(&c.id))
             .collect()
-    };
-
-    if components_to_build.iter().all(|c| c.build.is_none()) {
-        println!("None of the components have a build command.");
-        println!("For information on specifying a build command, see https://developer.fermyon.com/spin/build#setting-up-for-spin-build.");
-        return Ok(());
-    }
-
-    components_to_build
-        .into_iter()
-        .map(|c| build_component(c, &app_dir))
-        .collect::<Result<Vec<_>, _>>()?;
-
-    terminal::step!("Finished", "building all Spin components");
-    Ok(())
+    })
 }
 
 /// Run the build command of the component.
 fn build_component(build_info: ComponentBuildInfo, app_dir: &Path) -> Result<()> {
-    match build_info.build {
+    match &build_info.build {
         Some(b) => {
             terminal::step!(
                 "Building",
@@ -72,11 +103,25 @@ fn build_component(build_info: ComponentBuildInfo, app_dir: &Path) -> Result<()>
                 println!("Working directory: {:?}", workdir);
             }
 
-            let exit_status = Exec::shell(&b.command)
-                .cwd(workdir)
+            match b.install {
+                Some(Install::Npm) => install_npm_dependencies(&workdir, &build_info.id)?,
+                Some(Install::Python) => install_python_dependencies(&workdir, &build_info.id)?,
+                None => {}
+            }
+
+            let mut command = Exec::shell(&b.command)
+                .cwd(&workdir)
                 .stdout(Redirection::None)
                 .stderr(Redirection::None)
-                .stdin(Redirection::None)
+                .stdin(Redirection::None);
+            if b.install == Some(Install::Python) {
+                if let Some(path) = python_venv_path(&workdir)? {
+                    command = command
+                        .env("PATH", path)
+                        .env("VIRTUAL_ENV", workdir.join(PYTHON_VENV_DIR));
+                }
+            }
+            let exit_status = command
                 .popen()
                 .map_err(|err| {
                     anyhow!(
@@ -96,12 +141,163 @@ fn build_component(build_info: ComponentBuildInfo, app_dir: &Path) -> Result<()>
                 );
             }
 
+            if let Some(passes) = optimize::passes(&b.optimize) {
+                match build_info.local_source() {
+                    Some(source) => {
+                        optimize::optimize(&build_info.id, &app_dir.join(source), &passes)?
+                    }
+                    None => println!(
+                        "Not optimizing component {}: its source is not a local file.",
+                        build_info.id
+                    ),
+                }
+            }
+
             Ok(())
         }
         _ => Ok(()),
     }
 }
 
+/// JavaScript and TypeScript components are bundled and compiled to Wasm by
+/// their build command, which needs the component's npm dependencies. For
+/// components with `install = "npm"`, these are installed on the first
+/// build, so that a fresh project or checkout builds with just `spin build`.
+fn install_npm_dependencies(workdir: &Path, component_id: &str) -> Result<()> {
+    if !workdir.join("package.json").exists() || workdir.join("node_modules").exists() {
+        return Ok(());
+    }
+
+    terminal::step!(
+        "Installing",
+        "npm dependencies for component {}",
+        component_id
+    );
+    run_install(
+        Exec::shell("npm install"),
+        workdir,
+        "npm install",
+        component_id,
+        "JavaScript components require Node.js and npm.",
+    )
+}
+
+/// The directory, relative to a Python component's working directory, of
+/// the virtual environment its requirements are installed in.
+const PYTHON_VENV_DIR: &str = ".venv";
+
+/// The file, in a Python component's virtual environment, which is written
+/// once its requirements are installed.
+const PYTHON_INSTALLED_MARKER: &str = "spin-requirements-installed";
+
+/// Python components are compiled to Wasm by `componentize-py`, which needs
+/// the Spin SDK and the component's other requirements. For components with
+/// `install = "python"`, these are installed into a virtual environment
+/// which the build command then runs in: on the first build, and again
+/// whenever `requirements.txt` has changed since they were installed.
+fn install_python_dependencies(workdir: &Path, component_id: &str) -> Result<()> {
+    let requirements = workdir.join("requirements.txt");
+    let venv = workdir.join(PYTHON_VENV_DIR);
+    let marker = venv.join(PYTHON_INSTALLED_MARKER);
+    if !requirements.exists() || !modified_since(&requirements, &marker)? {
+        return Ok(());
+    }
+
+    terminal::step!(
+        "Installing",
+        "Python requirements for component {}",
+        component_id
+    );
+    let python = if cfg!(windows) { "python" } else { "python3" };
+    if !venv.exists() {
+        run_install(
+            Exec::cmd(python).args(&["-m", "venv", PYTHON_VENV_DIR]),
+            workdir,
+            "python3 -m venv",
+            component_id,
+            "Python components require Python 3.10 or later.",
+        )?;
+    }
+    let venv_python = python_venv_bin_dir(workdir).join(python);
+    run_install(
+        Exec::cmd(venv_python).args(&["-m", "pip", "install", "-r", "requirements.txt"]),
+        workdir,
+        "pip install",
+        component_id,
+        "Python components require pip.",
+    )?;
+    std::fs::write(&marker, "").with_context(|| format!("Cannot write {}", marker.display()))
+}
+
+/// Whether `path` was modified after `since` was, or `since` doesn't exist.
+fn modified_since(path: &Path, since: &Path) -> Result<bool> {
+    let modified = |p: &Path| std::fs::metadata(p).and_then(|m| m.modified());
+    let Ok(since) = modified(since) else {
+        return Ok(true);
+    };
+    let path_modified =
+        modified(path).with_context(|| format!("Cannot read {}", path.display()))?;
+    Ok(path_modified > since)
+}
+
+fn python_venv_bin_dir(workdir: &Path) -> PathBuf {
+    let venv = workdir.join(PYTHON_VENV_DIR);
+    if cfg!(windows) {
+        venv.join("Scripts")
+    } else {
+        venv.join("bin")
+    }
+}
+
+/// The `PATH` with the component's Python virtual environment, if it has
+/// one, ahead of everything else.
+fn python_venv_path(workdir: &Path) -> Result<Option<OsString>> {
+    let bin_dir = python_venv_bin_dir(workdir);
+    if !bin_dir.exists() {
+        return Ok(None);
+    }
+    let path = std::env::var_os("PATH").unwrap_or_default();
+    let paths = std::iter::once(bin_dir).chain(std::env::split_paths(&path));
+    Ok(Some(std::env::join_paths(paths)?))
+}
+
+/// Runs a command which installs a component's dependencies in `workdir`.
+fn run_install(
+    exec: Exec,
+    workdir: &Path,
+    description: &str,
+    component_id: &str,
+    requires: &str,
+) -> Result<()> {
+    let exit_status = exec
+        .cwd(workdir)
+        .stdout(Redirection::None)
+        .stderr(Redirection::None)
+        .stdin(Redirection::None)
+        .popen()
+        .map_err(|err| {
+            anyhow!(
+                "Cannot run `{}` for component {}: {}. {}",
+                description,
+                component_id,
+                err,
+                requires
+            )
+        })?
+        .wait()?;
+
+    if !exit_status.success() {
+        bail!(
+            "`{}` for component {} failed with status {:?}",
+            description,
+            component_id,
+            exit_status,
+        );
+    }
+
+    Ok(())
+}
+
 /// Constructs the absolute working directory in which to run the build command.
 fn construct_workdir(app_dir: &Path, workdir: Option<impl AsRef<Path>>) -> Result<PathBuf> {
     let mut cwd = app_dir.to_owned();
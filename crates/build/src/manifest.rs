@@ -33,6 +33,15 @@ pub struct ComponentBuildInfo {
     #[serde(default)]
     pub id: String,
     pub build: Option<v2::ComponentBuildConfig>,
+    pub source: Option<toml::Value>,
+}
+
+impl ComponentBuildInfo {
+    /// The path of the component's source, relative to the manifest, unless
+    /// it is fetched from elsewhere.
+    pub fn local_source(&self) -> Option<&str> {
+        self.source.as_ref()?.as_str()
+    }
 }
 
 #[derive(Deserialize)]
@@ -0,0 +1,134 @@
+//! Optimization of built components with `wasm-opt`.
+//!
+//! A component whose build config sets `optimize` has its Wasm run through
+//! Binaryen's `wasm-opt` after its build command, so that it is smaller and
+//! faster without each language's build script having to do it. `wasm-opt`
+//! works on modules, so a source which is already a component is left as
+//! it is.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, bail, Context, Result};
+use spin_manifest::schema::v2::Optimize;
+use subprocess::{Exec, Redirection};
+use wasmparser::{Encoding, Parser, Payload};
+
+/// The `wasm-opt` arguments used by `optimize = true`: optimize for size,
+/// and strip debug info.
+const DEFAULT_PASSES: &[&str] = &["-Oz", "--strip-debug"];
+
+/// Returns the `wasm-opt` arguments for `optimize`, or `None` if
+/// optimization is off.
+pub(crate) fn passes(optimize: &Optimize) -> Option<Vec<String>> {
+    match optimize {
+        Optimize::Enabled(false) => None,
+        Optimize::Enabled(true) => Some(DEFAULT_PASSES.iter().map(|&p| p.to_owned()).collect()),
+        Optimize::Passes(passes) => Some(passes.clone()),
+    }
+}
+
+/// Optimizes the module at `path` in place with the given `wasm-opt`
+/// arguments.
+pub(crate) fn optimize(component_id: &str, path: &Path, passes: &[String]) -> Result<()> {
+    let wasm = std::fs::read(path).with_context(|| {
+        format!(
+            "Cannot read the source of component {}: {}",
+            component_id,
+            path.display()
+        )
+    })?;
+    if is_component(&wasm)? {
+        println!(
+            "Not optimizing component {component_id}: only modules, not components, can be optimized with wasm-opt."
+        );
+        return Ok(());
+    }
+
+    terminal::step!(
+        "Optimizing",
+        "component {component_id} with `wasm-opt {}`",
+        passes.join(" ")
+    );
+    let output = optimized_path(path);
+    let exit_status = Exec::cmd("wasm-opt")
+        .args(passes)
+        .arg(path)
+        .arg("-o")
+        .arg(&output)
+        .stdout(Redirection::None)
+        .stderr(Redirection::None)
+        .stdin(Redirection::None)
+        .popen()
+        .map_err(|err| {
+            anyhow!(
+                "Cannot run `wasm-opt` for component {}: {}. Install it from Binaryen (https://github.com/WebAssembly/binaryen/releases).",
+                component_id,
+                err
+            )
+        })?
+        .wait()?;
+
+    if !exit_status.success() {
+        _ = std::fs::remove_file(&output);
+        bail!(
+            "Optimizing component {} failed with status {:?}",
+            component_id,
+            exit_status,
+        );
+    }
+
+    let before = wasm.len();
+    let after = std::fs::metadata(&output)?.len();
+    std::fs::rename(&output, path)
+        .with_context(|| format!("Cannot replace {} with its optimized build", path.display()))?;
+    println!("Optimized component {component_id} from {before} to {after} bytes.");
+    Ok(())
+}
+
+/// The path at which `wasm-opt` writes the optimized module, before it
+/// replaces the original.
+fn optimized_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_owned();
+    name.push(".opt");
+    path.with_file_name(name)
+}
+
+fn is_component(wasm: &[u8]) -> Result<bool> {
+    match Parser::new(0).parse_all(wasm).next() {
+        Some(payload) => Ok(matches!(
+            payload.context("Invalid Wasm binary")?,
+            Payload::Version {
+                encoding: Encoding::Component,
+                ..
+            }
+        )),
+        None => bail!("Invalid Wasm binary: it is empty"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn passes_default_when_enabled() {
+        assert_eq!(None, passes(&Optimize::Enabled(false)));
+        assert_eq!(
+            Some(vec!["-Oz".to_owned(), "--strip-debug".to_owned()]),
+            passes(&Optimize::Enabled(true))
+        );
+        assert_eq!(
+            Some(vec!["-O3".to_owned()]),
+            passes(&Optimize::Passes(vec!["-O3".to_owned()]))
+        );
+    }
+
+    #[test]
+    fn components_are_told_from_modules() {
+        let empty_module = [0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00];
+        assert!(!is_component(&empty_module).unwrap());
+
+        let empty_component = [0x00, 0x61, 0x73, 0x6d, 0x0d, 0x00, 0x01, 0x00];
+        assert!(is_component(&empty_component).unwrap());
+    }
+}
@@ -0,0 +1,157 @@
+//! Pre-initialization of built components with Wizer.
+//!
+//! A module which exports `wizer.initialize` has that function run at build
+//! time, and its memory and globals afterwards are snapshotted into the
+//! module, so that the work it does (such as parsing templates or compiling
+//! regular expressions) isn't repeated each time an instance starts.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, bail, Context, Result};
+use subprocess::{Exec, Redirection};
+use wasmparser::{Encoding, ExternalKind, Parser, Payload};
+
+use crate::manifest::ComponentBuildInfo;
+
+/// The export which Wizer runs to initialize a module.
+const INIT_FUNC: &str = "wizer.initialize";
+
+/// Pre-initializes each component whose source is a local module which
+/// exports `wizer.initialize`, replacing the module with the snapshot.
+pub(crate) fn pre_initialize(components: &[ComponentBuildInfo], app_dir: &Path) -> Result<()> {
+    for component in components {
+        let Some(source) = component.local_source() else {
+            continue;
+        };
+        let path = app_dir.join(source);
+        let wasm = std::fs::read(&path).with_context(|| {
+            format!(
+                "Cannot read the source of component {}: {}",
+                component.id,
+                path.display()
+            )
+        })?;
+        match init_func_status(&wasm)? {
+            InitFunc::Exported => pre_initialize_module(&component.id, &path)?,
+            InitFunc::Missing => println!(
+                "Not pre-initializing component {}: it does not export `{INIT_FUNC}`, or has already been pre-initialized.",
+                component.id
+            ),
+            InitFunc::NotAModule => println!(
+                "Not pre-initializing component {}: only modules, not components, can be pre-initialized.",
+                component.id
+            ),
+        }
+    }
+    Ok(())
+}
+
+fn pre_initialize_module(component_id: &str, path: &Path) -> Result<()> {
+    terminal::step!("Pre-initializing", "component {component_id}");
+    let output = snapshot_path(path);
+    let exit_status = Exec::cmd("wizer")
+        .arg(path)
+        .arg("-o")
+        .arg(&output)
+        .arg("--init-func")
+        .arg(INIT_FUNC)
+        .arg("--allow-wasi")
+        .arg("--wasm-bulk-memory")
+        .arg("true")
+        .stdout(Redirection::None)
+        .stderr(Redirection::None)
+        .stdin(Redirection::None)
+        .popen()
+        .map_err(|err| {
+            anyhow!(
+                "Cannot run `wizer` for component {}: {}. Install it with `cargo install wizer --all-features`.",
+                component_id,
+                err
+            )
+        })?
+        .wait()?;
+
+    if !exit_status.success() {
+        _ = std::fs::remove_file(&output);
+        bail!(
+            "Pre-initializing component {} failed with status {:?}",
+            component_id,
+            exit_status,
+        );
+    }
+
+    std::fs::rename(&output, path)
+        .with_context(|| format!("Cannot replace {} with its snapshot", path.display()))
+}
+
+/// The path at which Wizer writes the snapshot of a module, before it
+/// replaces the module.
+fn snapshot_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_owned();
+    name.push(".pre-init");
+    path.with_file_name(name)
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum InitFunc {
+    Exported,
+    Missing,
+    NotAModule,
+}
+
+fn init_func_status(wasm: &[u8]) -> Result<InitFunc> {
+    for payload in Parser::new(0).parse_all(wasm) {
+        match payload.context("Invalid Wasm binary")? {
+            Payload::Version {
+                encoding: Encoding::Component,
+                ..
+            } => return Ok(InitFunc::NotAModule),
+            Payload::ExportSection(exports) => {
+                for export in exports {
+                    let export = export.context("Invalid Wasm binary")?;
+                    if export.name == INIT_FUNC && export.kind == ExternalKind::Func {
+                        return Ok(InitFunc::Exported);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(InitFunc::Missing)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn init_func_is_found_in_modules_only() {
+        // (module (func (export "wizer.initialize")))
+        let exported = [
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // header
+            0x01, 0x04, 0x01, 0x60, 0x00, 0x00, // type section: () -> ()
+            0x03, 0x02, 0x01, 0x00, // function section
+            0x07, 0x14, 0x01, 0x10, b'w', b'i', b'z', b'e', b'r', b'.', b'i', b'n', b'i', b't',
+            b'i', b'a', b'l', b'i', b'z', b'e', 0x00, 0x00, // export section
+            0x0a, 0x04, 0x01, 0x02, 0x00, 0x0b, // code section
+        ];
+        assert_eq!(InitFunc::Exported, init_func_status(&exported).unwrap());
+
+        let empty_module = [0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00];
+        assert_eq!(InitFunc::Missing, init_func_status(&empty_module).unwrap());
+
+        let empty_component = [0x00, 0x61, 0x73, 0x6d, 0x0d, 0x00, 0x01, 0x00];
+        assert_eq!(
+            InitFunc::NotAModule,
+            init_func_status(&empty_component).unwrap()
+        );
+    }
+
+    #[test]
+    fn snapshots_are_written_beside_the_module() {
+        assert_eq!(
+            Path::new("target/app.wasm.pre-init"),
+            snapshot_path(Path::new("target/app.wasm"))
+        );
+    }
+}
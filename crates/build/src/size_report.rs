@@ -0,0 +1,358 @@
+//! Reports of where the bytes of built components go.
+//!
+//! Each component's Wasm is broken down by section and, where its core
+//! modules carry a `name` section, its code by the crate (or other top-level
+//! namespace) each function belongs to, in the manner of `twiggy`. Large
+//! components are slow to start cold and to push to registries, so the
+//! report ends with suggestions for making the component smaller.
+
+use std::{collections::BTreeMap, path::Path};
+
+use anyhow::{Context, Result};
+use wasmparser::{Encoding, Name, NameSectionReader, Parser, Payload, TypeRef};
+
+use crate::manifest::ComponentBuildInfo;
+
+/// How many crates to list for each component.
+const TOP_CRATES: usize = 10;
+
+/// Prints a size report for each component whose source is a local file.
+pub(crate) fn print_size_reports(components: &[ComponentBuildInfo], app_dir: &Path) -> Result<()> {
+    for component in components {
+        let Some(source) = component.local_source() else {
+            continue;
+        };
+        let path = app_dir.join(source);
+        let wasm = std::fs::read(&path).with_context(|| {
+            format!(
+                "Cannot read the source of component {}: {}",
+                component.id,
+                path.display()
+            )
+        })?;
+        let report = SizeReport::analyze(&wasm)
+            .with_context(|| format!("Cannot analyze component {}", component.id))?;
+        println!();
+        report.print(&component.id, &path);
+    }
+    Ok(())
+}
+
+#[derive(Debug, Default)]
+struct SizeReport {
+    total: usize,
+    /// Section name -> bytes, across all core modules.
+    sections: BTreeMap<String, usize>,
+    /// Crate name -> bytes of function bodies.
+    crates: BTreeMap<String, usize>,
+    /// Bytes of custom sections holding debug info.
+    debug_info: usize,
+    /// Bytes of `name` sections.
+    names: usize,
+    /// Whether the producers section records that wasm-opt has run.
+    optimized: bool,
+}
+
+/// A core module being analyzed.
+#[derive(Default)]
+struct ModuleSizes {
+    imported_funcs: u32,
+    /// The size of each defined function's body, in order.
+    bodies: Vec<usize>,
+    /// Function index -> name.
+    names: BTreeMap<u32, String>,
+}
+
+impl SizeReport {
+    fn analyze(wasm: &[u8]) -> Result<Self> {
+        let mut report = Self {
+            total: wasm.len(),
+            ..Default::default()
+        };
+        // One entry per enclosing module or component; components are `None`.
+        let mut stack: Vec<Option<ModuleSizes>> = vec![];
+
+        for payload in Parser::new(0).parse_all(wasm) {
+            let payload = payload.context("Invalid Wasm binary")?;
+            match &payload {
+                Payload::Version { encoding, .. } => {
+                    stack.push((*encoding == Encoding::Module).then(ModuleSizes::default));
+                    continue;
+                }
+                Payload::End(_) => {
+                    if let Some(Some(module)) = stack.pop() {
+                        report.add_module(module);
+                    }
+                    continue;
+                }
+                _ => {}
+            }
+            let Some(Some(module)) = stack.last_mut() else {
+                // Component-level sections are mostly the modules they nest.
+                continue;
+            };
+
+            if let Payload::CustomSection(custom) = &payload {
+                let size = custom.range().len();
+                let name = custom.name();
+                if name.starts_with(".debug_") {
+                    report.debug_info += size;
+                } else if name == "name" {
+                    report.names += size;
+                    read_function_names(custom.data(), custom.data_offset(), module);
+                } else if name == "producers" {
+                    let data = String::from_utf8_lossy(custom.data());
+                    report.optimized |= data.contains("wasm-opt");
+                }
+                *report.sections.entry(format!("custom {name}")).or_default() += size;
+            } else if let Some((id, range)) = payload.as_section() {
+                *report.sections.entry(section_name(id)).or_default() += range.len();
+            }
+
+            match &payload {
+                Payload::ImportSection(imports) => {
+                    for import in imports.clone() {
+                        if matches!(import?.ty, TypeRef::Func(_)) {
+                            module.imported_funcs += 1;
+                        }
+                    }
+                }
+                Payload::CodeSectionEntry(body) => module.bodies.push(body.range().len()),
+                _ => {}
+            }
+        }
+        Ok(report)
+    }
+
+    fn add_module(&mut self, module: ModuleSizes) {
+        for (index, size) in module.bodies.iter().enumerate() {
+            let func_index = module.imported_funcs + index as u32;
+            let crate_name = match module.names.get(&func_index) {
+                Some(name) => crate_of(name),
+                None => "(unnamed)".to_owned(),
+            };
+            *self.crates.entry(crate_name).or_default() += size;
+        }
+    }
+
+    /// Returns suggestions for making the component smaller.
+    fn suggestions(&self) -> Vec<String> {
+        let mut suggestions = vec![];
+        if self.debug_info > 0 {
+            suggestions.push(format!(
+                "Strip debug info to save {}: e.g. set `strip = true` in Cargo's release profile, or run `wasm-tools strip`.",
+                format_size(self.debug_info)
+            ));
+        }
+        if self.names > self.total / 20 {
+            suggestions.push(format!(
+                "The function names take {}; stripping them saves the space, at the cost of less readable backtraces.",
+                format_size(self.names)
+            ));
+        }
+        if !self.optimized {
+            suggestions.push(
+                "Run `wasm-opt -Oz` (from Binaryen) on the module before componentizing it; this often saves 10-30%.".to_owned(),
+            );
+        }
+        let std_size: usize = ["std", "core", "alloc"]
+            .iter()
+            .filter_map(|name| self.crates.get(*name))
+            .sum();
+        if std_size > self.total / 4 {
+            suggestions.push(
+                "Much of the code is from the standard library; `opt-level = \"z\"`, `lto = true` and `codegen-units = 1` in Cargo's release profile help to shrink it.".to_owned(),
+            );
+        }
+        suggestions
+    }
+
+    fn print(&self, component_id: &str, path: &Path) {
+        terminal::step!(
+            "Size",
+            "of component {component_id}: {} ({})",
+            format_size(self.total),
+            path.display()
+        );
+
+        println!("  Sections:");
+        let mut sections: Vec<_> = self.sections.iter().collect();
+        sections.sort_by(|a, b| b.1.cmp(a.1));
+        for (name, size) in sections {
+            println!("    {name:<30} {}", self.share(*size));
+        }
+
+        if self.crates.keys().any(|name| name != "(unnamed)") {
+            println!("  Code by crate:");
+            let mut crates: Vec<_> = self.crates.iter().collect();
+            crates.sort_by(|a, b| b.1.cmp(a.1));
+            for (name, size) in crates.iter().take(TOP_CRATES) {
+                println!("    {name:<30} {}", self.share(**size));
+            }
+            if crates.len() > TOP_CRATES {
+                let rest: usize = crates[TOP_CRATES..].iter().map(|(_, size)| **size).sum();
+                let others = format!("({} others)", crates.len() - TOP_CRATES);
+                println!("    {others:<30} {}", self.share(rest));
+            }
+        }
+
+        let suggestions = self.suggestions();
+        if !suggestions.is_empty() {
+            println!("  Suggestions:");
+            for suggestion in suggestions {
+                println!("    - {suggestion}");
+            }
+        }
+    }
+
+    fn share(&self, size: usize) -> String {
+        let percent = size as f64 * 100.0 / self.total.max(1) as f64;
+        format!("{:>10} {percent:>5.1}%", format_size(size))
+    }
+}
+
+fn read_function_names(data: &[u8], offset: usize, module: &mut ModuleSizes) {
+    // A malformed name section only loses the names.
+    for name in NameSectionReader::new(data, offset).flatten() {
+        if let Name::Function(names) = name {
+            for naming in names.into_iter().flatten() {
+                module.names.insert(naming.index, naming.name.to_owned());
+            }
+        }
+    }
+}
+
+fn section_name(id: u8) -> String {
+    match id {
+        1 => "type",
+        2 => "import",
+        3 => "function",
+        4 => "table",
+        5 => "memory",
+        6 => "global",
+        7 => "export",
+        8 => "start",
+        9 => "element",
+        10 => "code",
+        11 => "data",
+        12 => "data count",
+        13 => "tag",
+        _ => return format!("section {id}"),
+    }
+    .to_owned()
+}
+
+/// Returns the crate a function belongs to, judging by its name, which may
+/// be a legacy-mangled or demangled Rust path; for other languages, this is
+/// the first component of a `::`-separated name.
+fn crate_of(name: &str) -> String {
+    let name = demangle_legacy(name).unwrap_or_else(|| name.to_owned());
+    // The first path segment, e.g. `core` in `<T as core::convert::From<T>>::from`.
+    let Some(end) = name.find("::") else {
+        return "(other)".to_owned();
+    };
+    let start = name[..end]
+        .rfind(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .map_or(0, |index| index + 1);
+    match &name[start..end] {
+        "" => "(other)".to_owned(),
+        crate_name => crate_name.to_owned(),
+    }
+}
+
+/// Demangles a Rust legacy-mangled (`_ZN...E`) name, without its hash.
+fn demangle_legacy(name: &str) -> Option<String> {
+    let mut rest = name.strip_prefix("_ZN")?;
+    let mut segments = vec![];
+    while let Some(digits) = rest.find(|c: char| !c.is_ascii_digit()).filter(|&d| d > 0) {
+        let len: usize = rest[..digits].parse().ok()?;
+        let segment = rest.get(digits..digits + len)?;
+        rest = &rest[digits + len..];
+        // The trailing hash, e.g. `h0123456789abcdef`.
+        if segment.len() == 17 && segment.starts_with('h') && rest.starts_with('E') {
+            break;
+        }
+        // Segments which start with `$` are prefixed with `_`.
+        let segment = segment
+            .strip_prefix('_')
+            .filter(|s| s.starts_with('$'))
+            .unwrap_or(segment);
+        segments.push(unescape(segment));
+    }
+    (!segments.is_empty()).then(|| segments.join("::"))
+}
+
+fn unescape(segment: &str) -> String {
+    [
+        ("$LT$", "<"),
+        ("$GT$", ">"),
+        ("$u20$", " "),
+        ("$RF$", "&"),
+        ("$BP$", "*"),
+        ("$C$", ","),
+        ("..", "::"),
+    ]
+    .iter()
+    .fold(segment.to_owned(), |s, (from, to)| s.replace(from, to))
+}
+
+fn format_size(bytes: usize) -> String {
+    const KIB: f64 = 1024.0;
+    let bytes_f = bytes as f64;
+    if bytes_f >= KIB * KIB {
+        format!("{:.1} MiB", bytes_f / (KIB * KIB))
+    } else if bytes_f >= KIB {
+        format!("{:.1} KiB", bytes_f / KIB)
+    } else {
+        format!("{bytes} B")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn functions_are_attributed_to_crates() {
+        assert_eq!("core", crate_of("core::fmt::Formatter::pad"));
+        assert_eq!(
+            "core",
+            crate_of("_ZN4core3fmt9Formatter3pad17h0123456789abcdefE")
+        );
+        assert_eq!(
+            "serde_json",
+            crate_of("_ZN63_$LT$serde_json..value..Value$u20$as$u20$core..clone..Clone$GT$5clone17h0123456789abcdefE")
+        );
+        assert_eq!("core", crate_of("<T as core::convert::From<T>>::from"));
+        assert_eq!("(other)", crate_of("malloc"));
+    }
+
+    #[test]
+    fn sections_and_debug_info_are_measured() {
+        let module = [
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // header
+            0x01, 0x04, 0x01, 0x60, 0x00, 0x00, // type section: () -> ()
+            0x03, 0x02, 0x01, 0x00, // function section
+            0x0a, 0x04, 0x01, 0x02, 0x00, 0x0b, // code section
+            0x00, 0x10, 0x0b, b'.', b'd', b'e', b'b', b'u', b'g', b'_', b'i', b'n', b'f', b'o',
+            0x01, 0x02, 0x03, 0x04, // custom section: .debug_info
+        ];
+        let report = SizeReport::analyze(&module).unwrap();
+        assert_eq!(module.len(), report.total);
+        assert!(report.sections.contains_key("code"));
+        assert!(report.sections.contains_key("custom .debug_info"));
+        assert!(report.debug_info > 0);
+        assert_eq!(Some(&2), report.crates.get("(unnamed)"));
+
+        let suggestions = report.suggestions();
+        assert!(suggestions.iter().any(|s| s.contains("debug info")));
+        assert!(suggestions.iter().any(|s| s.contains("wasm-opt")));
+    }
+
+    #[test]
+    fn sizes_are_readable() {
+        assert_eq!("512 B", format_size(512));
+        assert_eq!("1.5 KiB", format_size(1536));
+        assert_eq!("2.0 MiB", format_size(2 * 1024 * 1024));
+    }
+}
@@ -0,0 +1,81 @@
+use anyhow::{Context, Result};
+use redis::{aio::Connection, parse_redis_url, AsyncCommands};
+use spin_cache::{log_error, Cache, Error};
+use spin_core::async_trait;
+use std::{sync::Arc, time::Duration};
+use tokio::sync::{Mutex, OnceCell};
+use url::Url;
+
+/// A cache stored in Redis, whose own eviction policy (`maxmemory-policy`)
+/// applies to entries.
+pub struct RedisCache {
+    database_url: Url,
+    key_prefix: String,
+    connection: OnceCell<Arc<Mutex<Connection>>>,
+}
+
+impl RedisCache {
+    /// A cache whose entries are stored under `key_prefix`, so that one Redis
+    /// database can hold several caches.
+    pub fn new(address: String, key_prefix: String) -> Result<Self> {
+        let database_url = parse_redis_url(&address).context("Invalid Redis URL")?;
+
+        Ok(Self {
+            database_url,
+            key_prefix,
+            connection: OnceCell::new(),
+        })
+    }
+
+    async fn connection(&self) -> Result<&Arc<Mutex<Connection>>, Error> {
+        self.connection
+            .get_or_try_init(|| async {
+                redis::Client::open(self.database_url.clone())?
+                    .get_async_connection()
+                    .await
+                    .map(Mutex::new)
+                    .map(Arc::new)
+            })
+            .await
+            .map_err(log_error)
+    }
+
+    fn key(&self, key: &str) -> String {
+        format!("{}{key}", self.key_prefix)
+    }
+}
+
+#[async_trait]
+impl Cache for RedisCache {
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, Error> {
+        let mut conn = self.connection().await?.lock().await;
+        conn.get(self.key(key)).await.map_err(log_error)
+    }
+
+    async fn set(&self, key: &str, value: &[u8], ttl: Option<Duration>) -> Result<(), Error> {
+        let mut conn = self.connection().await?.lock().await;
+        let key = self.key(key);
+        match ttl {
+            // Redis rejects an expiry of zero, so the entry is removed instead.
+            Some(ttl) if ttl.as_secs() == 0 => conn.del(key).await,
+            Some(ttl) => {
+                let seconds = ttl.as_secs().try_into().unwrap_or(usize::MAX);
+                conn.set_ex(key, value, seconds).await
+            }
+            None => conn.set(key, value).await,
+        }
+        .map_err(log_error)
+    }
+
+    async fn ttl(&self, key: &str) -> Result<Option<Duration>, Error> {
+        let mut conn = self.connection().await?.lock().await;
+        // `TTL` returns -2 for a missing key, and -1 for one without an expiry.
+        let seconds: i64 = conn.ttl(self.key(key)).await.map_err(log_error)?;
+        Ok(u64::try_from(seconds).ok().map(Duration::from_secs))
+    }
+
+    async fn invalidate(&self, key: &str) -> Result<(), Error> {
+        let mut conn = self.connection().await?.lock().await;
+        conn.del(self.key(key)).await.map_err(log_error)
+    }
+}
@@ -0,0 +1,70 @@
+use crate::{Cache, CacheDispatch, CACHES_KEY};
+use anyhow::anyhow;
+use spin_app::{AppComponent, DynamicHostComponent};
+use spin_core::HostComponent;
+use std::{collections::HashMap, sync::Arc};
+
+pub struct CacheComponent {
+    caches: Arc<HashMap<String, Arc<dyn Cache>>>,
+}
+
+impl CacheComponent {
+    /// A component providing the given caches, by label.
+    pub fn new(caches: HashMap<String, Arc<dyn Cache>>) -> Self {
+        Self {
+            caches: Arc::new(caches),
+        }
+    }
+}
+
+impl HostComponent for CacheComponent {
+    type Data = CacheDispatch;
+
+    fn add_to_linker<T: Send>(
+        linker: &mut spin_core::Linker<T>,
+        get: impl Fn(&mut spin_core::Data<T>) -> &mut Self::Data + Send + Sync + Copy + 'static,
+    ) -> anyhow::Result<()> {
+        spin_world::v3::cache::add_to_linker(linker, get)
+    }
+
+    fn build_data(&self) -> Self::Data {
+        CacheDispatch::new()
+    }
+}
+
+impl DynamicHostComponent for CacheComponent {
+    fn update_data(&self, data: &mut Self::Data, component: &AppComponent) -> anyhow::Result<()> {
+        let caches = component.get_metadata(CACHES_KEY)?.unwrap_or_default();
+        data.init(caches, self.caches.clone());
+        Ok(())
+    }
+
+    fn validate_app(&self, app: &spin_app::App) -> anyhow::Result<()> {
+        let mut errors = vec![];
+
+        for component in app.components() {
+            for allowed in component.get_metadata(CACHES_KEY)?.unwrap_or_default() {
+                if !self.caches.contains_key(&allowed) {
+                    let err = format!("- Component {} uses cache '{allowed}'", component.id());
+                    errors.push(err);
+                }
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            let prologue = vec![
+                "One or more components use caches which are not defined.",
+                "Check the spelling, or pass a runtime configuration file that defines these caches.",
+                "Details:",
+            ];
+            let lines: Vec<_> = prologue
+                .into_iter()
+                .map(|s| s.to_owned())
+                .chain(errors)
+                .collect();
+            Err(anyhow!(lines.join("\n")))
+        }
+    }
+}
@@ -0,0 +1,142 @@
+use anyhow::{Context, Result};
+use spin_app::MetadataKey;
+use spin_core::{async_trait, wasmtime::component::Resource};
+use spin_world::v3::cache;
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+    time::Duration,
+};
+use table::Table;
+
+mod host_component;
+mod memory;
+
+pub use host_component::CacheComponent;
+pub use memory::MemoryCache;
+
+pub const CACHES_KEY: MetadataKey<HashSet<String>> = MetadataKey::new("caches");
+
+const DEFAULT_CACHE_TABLE_CAPACITY: u32 = 256;
+
+pub use cache::Error;
+
+/// A cache backend.
+///
+/// Unlike a key-value store, a cache may drop entries at any time.
+#[async_trait]
+pub trait Cache: Sync + Send {
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, Error>;
+    async fn set(&self, key: &str, value: &[u8], ttl: Option<Duration>) -> Result<(), Error>;
+    /// Return the time until the entry for `key` expires, or `None` if it isn't cached or doesn't expire.
+    async fn ttl(&self, key: &str) -> Result<Option<Duration>, Error>;
+    async fn invalidate(&self, key: &str) -> Result<(), Error>;
+}
+
+pub struct CacheDispatch {
+    allowed_caches: HashSet<String>,
+    caches: Arc<HashMap<String, Arc<dyn Cache>>>,
+    open: Table<Arc<dyn Cache>>,
+}
+
+impl CacheDispatch {
+    pub fn new() -> Self {
+        Self {
+            allowed_caches: HashSet::new(),
+            caches: Default::default(),
+            open: Table::new(DEFAULT_CACHE_TABLE_CAPACITY),
+        }
+    }
+
+    pub fn init(
+        &mut self,
+        allowed_caches: HashSet<String>,
+        caches: Arc<HashMap<String, Arc<dyn Cache>>>,
+    ) {
+        self.allowed_caches = allowed_caches;
+        self.caches = caches;
+    }
+
+    fn get_cache(&self, cache: Resource<cache::Cache>) -> Result<&Arc<dyn Cache>> {
+        self.open.get(cache.rep()).context("invalid cache")
+    }
+}
+
+impl Default for CacheDispatch {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl cache::Host for CacheDispatch {}
+
+#[async_trait]
+impl cache::HostCache for CacheDispatch {
+    async fn open(&mut self, label: String) -> Result<Result<Resource<cache::Cache>, Error>> {
+        if !self.allowed_caches.contains(&label) {
+            return Ok(Err(Error::AccessDenied));
+        }
+        let Some(cache) = self.caches.get(&label) else {
+            return Ok(Err(Error::NoSuchCache));
+        };
+        Ok(self
+            .open
+            .push(cache.clone())
+            .map(Resource::new_own)
+            .map_err(|()| Error::Other("too many caches opened".into())))
+    }
+
+    async fn get(
+        &mut self,
+        cache: Resource<cache::Cache>,
+        key: String,
+    ) -> Result<Result<Option<Vec<u8>>, Error>> {
+        let cache = self.get_cache(cache)?;
+        Ok(cache.get(&key).await)
+    }
+
+    async fn set(
+        &mut self,
+        cache: Resource<cache::Cache>,
+        key: String,
+        value: Vec<u8>,
+        ttl_seconds: Option<u64>,
+    ) -> Result<Result<(), Error>> {
+        let cache = self.get_cache(cache)?;
+        Ok(cache
+            .set(&key, &value, ttl_seconds.map(Duration::from_secs))
+            .await)
+    }
+
+    async fn ttl(
+        &mut self,
+        cache: Resource<cache::Cache>,
+        key: String,
+    ) -> Result<Result<Option<u64>, Error>> {
+        let cache = self.get_cache(cache)?;
+        Ok(cache
+            .ttl(&key)
+            .await
+            .map(|ttl| ttl.map(|ttl| ttl.as_secs())))
+    }
+
+    async fn invalidate(
+        &mut self,
+        cache: Resource<cache::Cache>,
+        key: String,
+    ) -> Result<Result<(), Error>> {
+        let cache = self.get_cache(cache)?;
+        Ok(cache.invalidate(&key).await)
+    }
+
+    fn drop(&mut self, cache: Resource<cache::Cache>) -> Result<()> {
+        self.open.remove(cache.rep());
+        Ok(())
+    }
+}
+
+pub fn log_error(err: impl std::fmt::Debug) -> Error {
+    tracing::warn!("cache error: {err:?}");
+    Error::Other(format!("{err:?}"))
+}
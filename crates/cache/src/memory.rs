@@ -0,0 +1,151 @@
+use crate::{Cache, Error};
+use lru::LruCache;
+use spin_core::async_trait;
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+/// An in-process cache which evicts the least recently used entries to stay
+/// within a size limit.
+pub struct MemoryCache {
+    max_size: usize,
+    state: Mutex<State>,
+}
+
+struct State {
+    entries: LruCache<String, Entry>,
+    /// The total size of the keys and values of the entries
+    size: usize,
+}
+
+struct Entry {
+    value: Vec<u8>,
+    expires: Option<Instant>,
+}
+
+impl Entry {
+    fn expired(&self, now: Instant) -> bool {
+        self.expires.map_or(false, |expires| expires <= now)
+    }
+}
+
+impl MemoryCache {
+    /// A cache holding up to `max_size` bytes of keys and values.
+    pub fn new(max_size: usize) -> Self {
+        Self {
+            max_size,
+            state: Mutex::new(State {
+                entries: LruCache::unbounded(),
+                size: 0,
+            }),
+        }
+    }
+}
+
+impl State {
+    fn remove(&mut self, key: &str) {
+        if let Some(entry) = self.entries.pop(key) {
+            self.size -= key.len() + entry.value.len();
+        }
+    }
+
+    /// Returns the entry for `key` if it is live, removing it if it has expired.
+    fn live(&mut self, key: &str, now: Instant) -> Option<&Entry> {
+        if self.entries.peek(key)?.expired(now) {
+            self.remove(key);
+            return None;
+        }
+        self.entries.get(key)
+    }
+}
+
+#[async_trait]
+impl Cache for MemoryCache {
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, Error> {
+        let mut state = self.state.lock().unwrap();
+        Ok(state
+            .live(key, Instant::now())
+            .map(|entry| entry.value.clone()))
+    }
+
+    async fn set(&self, key: &str, value: &[u8], ttl: Option<Duration>) -> Result<(), Error> {
+        let mut state = self.state.lock().unwrap();
+        state.remove(key);
+        let size = key.len() + value.len();
+        // An entry larger than the whole cache is dropped straight away, as
+        // if it had been evicted.
+        if size > self.max_size {
+            return Ok(());
+        }
+        while state.size + size > self.max_size {
+            match state.entries.pop_lru() {
+                Some((key, entry)) => state.size -= key.len() + entry.value.len(),
+                None => break,
+            }
+        }
+        let entry = Entry {
+            value: value.to_vec(),
+            expires: ttl.map(|ttl| Instant::now() + ttl),
+        };
+        state.entries.put(key.to_owned(), entry);
+        state.size += size;
+        Ok(())
+    }
+
+    async fn ttl(&self, key: &str) -> Result<Option<Duration>, Error> {
+        let mut state = self.state.lock().unwrap();
+        let now = Instant::now();
+        Ok(state
+            .live(key, now)
+            .and_then(|entry| entry.expires)
+            .map(|expires| expires - now))
+    }
+
+    async fn invalidate(&self, key: &str) -> Result<(), Error> {
+        self.state.lock().unwrap().remove(key);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[tokio::test]
+    async fn least_recently_used_entries_are_evicted() -> Result<(), Error> {
+        let cache = MemoryCache::new(10);
+        cache.set("a", b"1234", None).await?;
+        cache.set("b", b"1234", None).await?;
+        // Using `a` makes `b` the least recently used.
+        assert!(cache.get("a").await?.is_some());
+        cache.set("c", b"1234", None).await?;
+
+        assert_eq!(Some(b"1234".to_vec()), cache.get("a").await?);
+        assert_eq!(None, cache.get("b").await?);
+        assert_eq!(Some(b"1234".to_vec()), cache.get("c").await?);
+
+        cache.set("d", b"too large for the cache", None).await?;
+        assert_eq!(None, cache.get("d").await?);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn entries_expire() -> Result<(), Error> {
+        let cache = MemoryCache::new(100);
+        cache.set("gone", b"1", Some(Duration::ZERO)).await?;
+        cache
+            .set("soon", b"1", Some(Duration::from_secs(60)))
+            .await?;
+        cache.set("never", b"1", None).await?;
+
+        assert_eq!(None, cache.get("gone").await?);
+        assert!(cache.ttl("soon").await?.unwrap() <= Duration::from_secs(60));
+        assert_eq!(None, cache.ttl("never").await?);
+        assert!(cache.get("never").await?.is_some());
+
+        cache.invalidate("never").await?;
+        assert_eq!(None, cache.get("never").await?);
+        Ok(())
+    }
+}
@@ -0,0 +1,179 @@
+//! Replacement WASI clocks.
+//!
+//! Coarse clocks make timing side channels harder to exploit. Both clocks
+//! only advance in steps of the configured resolution, and each reading has a
+//! random offset of up to one step added, so that a guest can neither measure
+//! short intervals nor line its readings up with the step boundaries.
+//!
+//! Frozen and offset clocks let a component see a chosen time, e.g. to make
+//! tests reproducible.
+
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+    sync::atomic::{AtomicU64, Ordering},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use wasmtime_wasi::preview2::{HostMonotonicClock, HostWallClock};
+
+/// A random number of nanoseconds below `bound`.
+fn jitter(bound: u64) -> u64 {
+    // Each `RandomState` is randomly keyed, which is all the randomness
+    // needed here.
+    let random = RandomState::new().build_hasher().finish();
+    random % bound.max(1)
+}
+
+fn coarsen(nanos: u64, resolution: u64) -> u64 {
+    let resolution = resolution.max(1);
+    nanos - nanos % resolution + jitter(resolution)
+}
+
+pub(crate) struct CoarseWallClock {
+    resolution: Duration,
+}
+
+impl CoarseWallClock {
+    pub(crate) fn new(resolution: Duration) -> Self {
+        Self { resolution }
+    }
+}
+
+impl HostWallClock for CoarseWallClock {
+    fn resolution(&self) -> Duration {
+        self.resolution
+    }
+
+    fn now(&self) -> Duration {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        let nanos = coarsen(now.as_nanos() as u64, self.resolution.as_nanos() as u64);
+        Duration::from_nanos(nanos)
+    }
+}
+
+pub(crate) struct CoarseMonotonicClock {
+    start: Instant,
+    resolution: u64,
+    // The jitter must never make the clock go backwards.
+    last: AtomicU64,
+}
+
+impl CoarseMonotonicClock {
+    pub(crate) fn new(resolution: Duration) -> Self {
+        Self {
+            start: Instant::now(),
+            resolution: resolution.as_nanos() as u64,
+            last: AtomicU64::new(0),
+        }
+    }
+}
+
+impl HostMonotonicClock for CoarseMonotonicClock {
+    fn resolution(&self) -> u64 {
+        self.resolution
+    }
+
+    fn now(&self) -> u64 {
+        let nanos = coarsen(self.start.elapsed().as_nanos() as u64, self.resolution);
+        let last = self.last.fetch_max(nanos, Ordering::Relaxed);
+        nanos.max(last)
+    }
+}
+
+/// A clock which always reads the same time.
+pub(crate) struct FrozenClock {
+    since_epoch: Duration,
+}
+
+impl FrozenClock {
+    pub(crate) fn new(since_epoch: Duration) -> Self {
+        Self { since_epoch }
+    }
+}
+
+impl HostWallClock for FrozenClock {
+    fn resolution(&self) -> Duration {
+        Duration::from_nanos(1)
+    }
+
+    fn now(&self) -> Duration {
+        self.since_epoch
+    }
+}
+
+impl HostMonotonicClock for FrozenClock {
+    fn resolution(&self) -> u64 {
+        1
+    }
+
+    fn now(&self) -> u64 {
+        0
+    }
+}
+
+/// A wall clock offset from the host's by a whole number of seconds.
+pub(crate) struct OffsetWallClock {
+    offset_secs: i64,
+}
+
+impl OffsetWallClock {
+    pub(crate) fn new(offset_secs: i64) -> Self {
+        Self { offset_secs }
+    }
+}
+
+impl HostWallClock for OffsetWallClock {
+    fn resolution(&self) -> Duration {
+        Duration::from_nanos(1)
+    }
+
+    fn now(&self) -> Duration {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        let offset = Duration::from_secs(self.offset_secs.unsigned_abs());
+        if self.offset_secs < 0 {
+            now.saturating_sub(offset)
+        } else {
+            now.saturating_add(offset)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn coarse_readings_stay_within_one_step() {
+        let resolution = 1_000_000;
+        for nanos in [0, 1, 999_999, 1_000_000, 5_432_100] {
+            let coarse = coarsen(nanos, resolution);
+            let step = nanos - nanos % resolution;
+            assert!((step..step + resolution).contains(&coarse));
+        }
+    }
+
+    #[test]
+    fn coarse_monotonic_clock_never_goes_backwards() {
+        let clock = CoarseMonotonicClock::new(Duration::from_millis(1));
+        let mut previous = 0;
+        for _ in 0..1000 {
+            let now = clock.now();
+            assert!(now >= previous);
+            previous = now;
+        }
+    }
+
+    #[test]
+    fn offset_wall_clock_is_offset_from_the_host() {
+        let host = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+        let behind = HostWallClock::now(&OffsetWallClock::new(-3600));
+        let ahead = HostWallClock::now(&OffsetWallClock::new(3600));
+        assert!(behind < host && host < ahead);
+        assert!(ahead - behind >= Duration::from_secs(7200));
+    }
+}
@@ -0,0 +1,307 @@
+//! Injecting faults into outbound calls, for testing how components behave
+//! when their dependencies are slow or failing.
+//!
+//! Faults are described by [`FaultRule`]s, set once per process with
+//! [`configure`]. Host components call [`inject`] before each outbound HTTP
+//! request or database operation: it delays the call by any latency the
+//! matching rules add, and returns the [`Fault`] the call should fail with,
+//! if any. Each matching rule applies to a given call with its own
+//! probability; the first fault to apply wins.
+
+use std::{fmt, sync::OnceLock, time::Duration};
+
+use anyhow::{bail, Result};
+use serde::Deserialize;
+
+static RULES: OnceLock<Vec<FaultRule>> = OnceLock::new();
+
+/// The services faults can be injected into.
+const SERVICES: &[&str] = &["http", "postgres", "redis"];
+
+/// The contents of a fault injection file: a list of `[[rule]]` tables.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FaultConfig {
+    /// The rules, in the order they are matched.
+    #[serde(default, rename = "rule")]
+    pub rules: Vec<FaultRule>,
+}
+
+/// Faults to inject into the calls to some service or host.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FaultRule {
+    /// One of "http", "postgres" or "redis"; every service if unset.
+    #[serde(default)]
+    pub service: Option<String>,
+    /// A host, with or without a port, such as "api.example.com" or
+    /// "db:5432"; a leading "*." matches subdomains. Every host if unset.
+    #[serde(default)]
+    pub host: Option<String>,
+    /// The percentage of matching calls the rule applies to.
+    #[serde(default = "default_percent")]
+    pub percent: f64,
+    /// Latency to add to the call, in milliseconds.
+    #[serde(default)]
+    pub latency_ms: Option<u64>,
+    /// Fails the call with this error message.
+    #[serde(default)]
+    pub error: Option<String>,
+    /// Answers an HTTP request with this status code, without sending it.
+    #[serde(default)]
+    pub status: Option<u16>,
+    /// Fails the call as if the connection had been dropped.
+    #[serde(default)]
+    pub drop: bool,
+}
+
+fn default_percent() -> f64 {
+    100.0
+}
+
+/// How an outbound call should fail.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Fault {
+    /// The call fails with the given error message.
+    Error(String),
+    /// The HTTP request gets a response with the given status code.
+    Status(u16),
+    /// The call fails as if the connection had been dropped.
+    Drop,
+}
+
+impl fmt::Display for Fault {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Error(message) => write!(f, "{message} (injected fault)"),
+            Self::Status(status) => write!(f, "HTTP status {status} (injected fault)"),
+            Self::Drop => f.write_str("connection dropped (injected fault)"),
+        }
+    }
+}
+
+impl FaultRule {
+    fn validate(&self) -> Result<()> {
+        if let Some(service) = &self.service {
+            if !SERVICES.contains(&service.as_str()) {
+                bail!(
+                    "unknown service {service:?}: expected one of {}",
+                    SERVICES.join(", ")
+                );
+            }
+        }
+        if !(0.0..=100.0).contains(&self.percent) {
+            bail!("percent must be between 0 and 100");
+        }
+        let faults = [self.error.is_some(), self.status.is_some(), self.drop];
+        match faults.iter().filter(|&&f| f).count() {
+            0 if self.latency_ms.is_none() => {
+                bail!("a rule must set at least one of latency_ms, error, status or drop")
+            }
+            0 | 1 => {}
+            _ => bail!("a rule may set only one of error, status or drop"),
+        }
+        if let Some(status) = self.status {
+            if self.service.as_deref() != Some("http") {
+                bail!("status can only be set for service = \"http\"");
+            }
+            if !(100..=599).contains(&status) {
+                bail!("status must be between 100 and 599");
+            }
+        }
+        Ok(())
+    }
+
+    fn matches(&self, service: &str, host: &str) -> bool {
+        if self.service.as_deref().is_some_and(|s| s != service) {
+            return false;
+        }
+        let Some(pattern) = &self.host else {
+            return true;
+        };
+        let pattern = pattern.to_ascii_lowercase();
+        let host = host.to_ascii_lowercase();
+        // A pattern without a port matches the host on any port.
+        let host_only = host.rsplit_once(':').map_or(host.as_str(), |(h, _)| h);
+        [host.as_str(), host_only]
+            .iter()
+            .any(|host| match pattern.strip_prefix("*.") {
+                Some(domain) => host.strip_suffix(domain).is_some_and(|p| p.ends_with('.')),
+                None => *host == pattern,
+            })
+    }
+
+    fn fault(&self) -> Option<Fault> {
+        if let Some(message) = &self.error {
+            Some(Fault::Error(message.clone()))
+        } else if let Some(status) = self.status {
+            Some(Fault::Status(status))
+        } else {
+            self.drop.then_some(Fault::Drop)
+        }
+    }
+}
+
+/// Starts injecting the faults described by `config`. This can only be done
+/// once per process.
+pub fn configure(config: FaultConfig) -> Result<()> {
+    for (index, rule) in config.rules.iter().enumerate() {
+        rule.validate()
+            .map_err(|e| anyhow::anyhow!("invalid fault rule {}: {e}", index + 1))?;
+    }
+    RULES
+        .set(config.rules)
+        .map_err(|_| anyhow::anyhow!("fault injection has already been configured"))
+}
+
+/// Applies the faults for a call to `address`, which may be a URL or, for
+/// databases, a `key=value` connection string, with `default_scheme` used
+/// if it has none. Returns once any injected latency has passed, with the
+/// fault the call should fail with, if any.
+pub async fn inject(default_scheme: &str, address: &str) -> Option<Fault> {
+    let rules = RULES.get()?;
+    let (scheme, host) = crate::audit::outbound_host(default_scheme, address);
+    let service = service_of(scheme);
+    let (latency, fault) = roll(rules, service, &host, rand::random::<f64>);
+    if latency > Duration::ZERO {
+        tracing::info!("Injecting {latency:?} of latency into a {service} call to {host}");
+        tokio::time::sleep(latency).await;
+    }
+    if let Some(fault) = &fault {
+        tracing::info!("Injecting a fault into a {service} call to {host}: {fault}");
+    }
+    fault
+}
+
+/// Decides which of the matching rules apply to a call, given a source of
+/// uniform random numbers in `[0, 1)`.
+fn roll(
+    rules: &[FaultRule],
+    service: &str,
+    host: &str,
+    mut random: impl FnMut() -> f64,
+) -> (Duration, Option<Fault>) {
+    let mut latency = Duration::ZERO;
+    let mut fault = None;
+    for rule in rules.iter().filter(|rule| rule.matches(service, host)) {
+        if random() * 100.0 >= rule.percent {
+            continue;
+        }
+        latency += Duration::from_millis(rule.latency_ms.unwrap_or_default());
+        if fault.is_none() {
+            fault = rule.fault();
+        }
+    }
+    (latency, fault)
+}
+
+fn service_of(scheme: &str) -> &str {
+    match scheme {
+        "http" | "https" => "http",
+        "postgres" | "postgresql" => "postgres",
+        "redis" | "rediss" | "redis+unix" => "redis",
+        other => other,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(host: &str) -> FaultRule {
+        FaultRule {
+            host: Some(host.to_owned()),
+            percent: 100.0,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn rules_match_services_and_hosts() {
+        let mut any_port = rule("*.example.com");
+        assert!(any_port.matches("http", "api.example.com"));
+        assert!(any_port.matches("redis", "cache.example.com:6379"));
+        assert!(!any_port.matches("http", "example.com"));
+        assert!(!any_port.matches("http", "badexample.com"));
+
+        any_port.service = Some("postgres".into());
+        assert!(!any_port.matches("http", "api.example.com"));
+
+        let one_port = rule("db:5432");
+        assert!(one_port.matches("postgres", "db:5432"));
+        assert!(!one_port.matches("postgres", "db:5433"));
+    }
+
+    #[test]
+    fn matching_rules_apply_by_percentage() {
+        let slow = FaultRule {
+            latency_ms: Some(200),
+            percent: 50.0,
+            ..rule("api.example.com")
+        };
+        let failing = FaultRule {
+            drop: true,
+            percent: 10.0,
+            ..rule("api.example.com")
+        };
+        let rules = [slow, failing];
+
+        let (latency, fault) = roll(&rules, "http", "api.example.com", || 0.05);
+        assert_eq!(Duration::from_millis(200), latency);
+        assert_eq!(Some(Fault::Drop), fault);
+
+        let (latency, fault) = roll(&rules, "http", "api.example.com", || 0.3);
+        assert_eq!(Duration::from_millis(200), latency);
+        assert_eq!(None, fault);
+
+        let (latency, fault) = roll(&rules, "http", "other.example.com", || 0.0);
+        assert_eq!(Duration::ZERO, latency);
+        assert_eq!(None, fault);
+    }
+
+    #[test]
+    fn rules_are_validated() {
+        rule("a").validate().unwrap_err();
+        FaultRule {
+            status: Some(503),
+            ..rule("a")
+        }
+        .validate()
+        .unwrap_err();
+        FaultRule {
+            service: Some("http".into()),
+            status: Some(503),
+            ..rule("a")
+        }
+        .validate()
+        .unwrap();
+        FaultRule {
+            error: Some("boom".into()),
+            drop: true,
+            ..rule("a")
+        }
+        .validate()
+        .unwrap_err();
+        FaultRule {
+            drop: true,
+            percent: 150.0,
+            ..rule("a")
+        }
+        .validate()
+        .unwrap_err();
+    }
+
+    #[test]
+    fn config_files_list_rules() {
+        let config: FaultConfig = serde_json::from_value(serde_json::json!({
+            "rule": [
+                { "service": "http", "host": "api.example.com", "status": 503, "percent": 25 },
+                { "service": "redis", "latency_ms": 500 },
+            ]
+        }))
+        .unwrap();
+        assert_eq!(2, config.rules.len());
+        assert_eq!(25.0, config.rules[0].percent);
+        assert_eq!(100.0, config.rules[1].percent);
+    }
+}
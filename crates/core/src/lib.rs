@@ -7,11 +7,17 @@
 
 #![deny(missing_docs)]
 
+pub mod audit;
+mod clocks;
+pub mod faults;
 mod host_component;
 mod io;
 mod limits;
 mod preview1;
+pub mod profiling;
+pub mod replay;
 mod store;
+pub mod usage;
 
 use std::{path::PathBuf, sync::Arc, time::Duration};
 
@@ -36,7 +42,7 @@ pub use host_component::{
     AnyHostComponentDataHandle, HostComponent, HostComponentDataHandle, HostComponentsData,
 };
 pub use io::OutputBuffer;
-pub use store::{Store, StoreBuilder, Wasi, WasiVersion};
+pub use store::{FilesystemAccess, Store, StoreBuilder, Wasi, WasiVersion};
 
 /// The default [`EngineBuilder::epoch_tick_interval`].
 pub const DEFAULT_EPOCH_TICK_INTERVAL: Duration = Duration::from_millis(10);
@@ -81,6 +87,47 @@ impl Config {
             .allocation_strategy(wasmtime::InstanceAllocationStrategy::OnDemand);
         self
     }
+
+    /// Set whether linear memories are initialized by mapping a
+    /// copy-on-write image of their initial contents, so that instances of
+    /// the same component share the pages they don't write to. This is
+    /// enabled by default.
+    ///
+    /// See [`wasmtime::Config::memory_init_cow`].
+    pub fn memory_init_cow(&mut self, enable: bool) -> &mut Self {
+        self.inner.memory_init_cow(enable);
+        self
+    }
+
+    /// Set the size in bytes up to which a sparsely initialized linear
+    /// memory is given a dense image, so that it can be initialized
+    /// copy-on-write.
+    ///
+    /// See [`wasmtime::Config::memory_guaranteed_dense_image_size`].
+    pub fn memory_guaranteed_dense_image_size(&mut self, size: u64) -> &mut Self {
+        self.inner.memory_guaranteed_dense_image_size(size);
+        self
+    }
+
+    /// Prepare guest code for debugging.
+    ///
+    /// Guest DWARF debug info is kept and translated into the compiled code,
+    /// so that native debuggers which support Wasmtime's JIT interface (such
+    /// as LLDB and GDB) can set breakpoints in component source code.
+    /// Backtraces of guest traps, such as Rust panics, include source file
+    /// and line information. Optimizations are disabled, so that variables
+    /// and line steps match the source.
+    ///
+    /// Spin doesn't run a debug adapter (DAP) server of its own: to debug from
+    /// an editor, run Spin under a native debugger through the editor's
+    /// debugger integration, such as LLDB through CodeLLDB in VS Code.
+    pub fn enable_guest_debugging(&mut self) -> &mut Self {
+        self.inner
+            .debug_info(true)
+            .wasm_backtrace_details(wasmtime::WasmBacktraceDetails::Enable)
+            .cranelift_opt_level(wasmtime::OptLevel::None);
+        self
+    }
 }
 
 impl Default for Config {
@@ -146,6 +193,11 @@ pub struct Data<T> {
     host_components_data: HostComponentsData,
     store_limits: limits::StoreLimitsAsync,
     table: Table,
+    // The execution deadline, when it is checked by the epoch callback
+    // rather than by Wasmtime's epoch deadline.
+    deadline: Option<std::time::Instant>,
+    // The label of the stack samples taken from now on.
+    sample_label: Option<String>,
 }
 
 impl<T> Data<T> {
@@ -153,6 +205,11 @@ impl<T> Data<T> {
     pub fn memory_consumed(&self) -> u64 {
         self.store_limits.memory_consumed()
     }
+
+    /// Returns the associated [`HostComponentsData`] for the store.
+    pub fn host_components_data(&mut self) -> &mut HostComponentsData {
+        &mut self.host_components_data
+    }
 }
 
 impl<T> AsRef<T> for Data<T> {
@@ -399,6 +456,17 @@ impl<T: OutboundWasiHttpHandler + Send + Sync> Engine<T> {
         )
     }
 
+    /// Adds definition(s) to this [`Engine`], such as those which can only
+    /// be made once the components they are for have been loaded.
+    ///
+    /// See [`EngineBuilder::link_import`].
+    pub fn link_import(
+        &mut self,
+        f: impl FnOnce(&mut Linker<T>, fn(&mut Data<T>) -> &mut T) -> Result<()>,
+    ) -> Result<()> {
+        f(&mut self.linker, Data::as_mut)
+    }
+
     /// Creates a new [`InstancePre`] for the given [`Component`].
     #[instrument(skip_all)]
     pub fn instantiate_pre(&self, component: &Component) -> Result<InstancePre<T>> {
@@ -27,6 +27,7 @@ impl ResourceLimiterAsync for StoreLimitsAsync {
         if can_grow {
             self.memory_consumed =
                 (self.memory_consumed as i64 + (desired as i64 - current as i64)) as u64;
+            crate::usage::record_memory(self.memory_consumed);
         }
         Ok(can_grow)
     }
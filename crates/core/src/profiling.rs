@@ -0,0 +1,156 @@
+//! Sampling guest stacks, for profiling components.
+//!
+//! When a [`StoreBuilder`](crate::StoreBuilder) is given [`StackSamples`],
+//! the guest's stack is captured at every epoch tick while it runs. The
+//! samples are aggregated in the "folded" format read by flamegraph tools
+//! such as `inferno-flamegraph` and `flamegraph.pl`: one line per distinct
+//! stack, outermost frame first, followed by the number of samples.
+//!
+//! Samples are kept apart by the label of the store they were taken in (see
+//! [`Store::set_sample_label`](crate::Store::set_sample_label)), so that
+//! e.g. each route a component serves can have a flamegraph of its own.
+
+use std::{
+    collections::HashMap,
+    io::{self, Write},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+};
+
+use wasmtime::WasmBacktrace;
+
+/// Aggregated stack samples from any number of stores.
+#[derive(Default)]
+pub struct StackSamples {
+    // Sample counts by label, then by stack.
+    stacks: Mutex<HashMap<Option<String>, HashMap<String, u64>>>,
+    total: AtomicU64,
+}
+
+impl StackSamples {
+    /// Creates an empty set of samples.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The number of samples taken so far.
+    pub fn total(&self) -> u64 {
+        self.total.load(Ordering::Relaxed)
+    }
+
+    /// The labels which samples have been taken under, with `None` for
+    /// samples from stores without a label.
+    pub fn labels(&self) -> Vec<Option<String>> {
+        let mut labels = self
+            .stacks
+            .lock()
+            .unwrap()
+            .keys()
+            .cloned()
+            .collect::<Vec<_>>();
+        labels.sort();
+        labels
+    }
+
+    /// Writes the samples taken under `label` in folded format.
+    pub fn write_folded(&self, label: Option<&str>, mut out: impl Write) -> io::Result<()> {
+        let stacks = self.stacks.lock().unwrap();
+        let Some(stacks) = stacks.get(&label.map(str::to_owned)) else {
+            return Ok(());
+        };
+        let mut lines = stacks.iter().collect::<Vec<_>>();
+        lines.sort();
+        for (stack, count) in lines {
+            writeln!(out, "{stack} {count}")?;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn sample(&self, label: Option<&str>, backtrace: &WasmBacktrace) {
+        // Frames are captured innermost first.
+        let frames = backtrace
+            .frames()
+            .iter()
+            .rev()
+            .map(|frame| match frame.func_name() {
+                Some(name) => frame_name(name),
+                None => format!("wasm-function[{}]", frame.func_index()),
+            })
+            .collect::<Vec<_>>();
+        if frames.is_empty() {
+            return;
+        }
+        self.add(label, frames.join(";"));
+    }
+
+    fn add(&self, label: Option<&str>, stack: String) {
+        *self
+            .stacks
+            .lock()
+            .unwrap()
+            .entry(label.map(str::to_owned))
+            .or_default()
+            .entry(stack)
+            .or_default() += 1;
+        self.total.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Demangles a function name, and removes the characters that the folded
+/// format uses as separators.
+fn frame_name(name: &str) -> String {
+    format!("{:#}", rustc_demangle::demangle(name)).replace([';', ' '], "_")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn samples_are_folded() {
+        let samples = StackSamples::new();
+        samples.add(None, "main;handle;parse".into());
+        samples.add(None, "main;handle".into());
+        samples.add(None, "main;handle;parse".into());
+
+        let mut out = vec![];
+        samples.write_folded(None, &mut out).unwrap();
+        assert_eq!(
+            "main;handle 1\nmain;handle;parse 2\n",
+            String::from_utf8(out).unwrap()
+        );
+        assert_eq!(3, samples.total());
+    }
+
+    #[test]
+    fn samples_are_kept_apart_by_label() {
+        let samples = StackSamples::new();
+        samples.add(Some("/users"), "main;list_users".into());
+        samples.add(Some("/orders"), "main;list_orders".into());
+        samples.add(Some("/users"), "main;list_users".into());
+
+        assert_eq!(
+            vec![Some("/orders".to_owned()), Some("/users".to_owned())],
+            samples.labels()
+        );
+        let mut out = vec![];
+        samples.write_folded(Some("/users"), &mut out).unwrap();
+        assert_eq!("main;list_users 2\n", String::from_utf8(out).unwrap());
+
+        let mut out = vec![];
+        samples.write_folded(None, &mut out).unwrap();
+        assert!(out.is_empty());
+        assert_eq!(3, samples.total());
+    }
+
+    #[test]
+    fn frame_names_are_demangled() {
+        assert_eq!(
+            "spin_sdk::http::handle",
+            frame_name("_ZN8spin_sdk4http6handle17h0123456789abcdefE")
+        );
+        assert_eq!("<T_as_Trait>::f", frame_name("<T as Trait>::f"));
+    }
+}
@@ -0,0 +1,282 @@
+//! Recording what an invocation learned from the outside world, so that it
+//! can be replayed.
+//!
+//! A trigger runs an invocation inside [`record`] to capture the variable
+//! values it resolved and the responses to its outbound HTTP requests. Later
+//! the invocation can be run again inside [`replay`] with the same
+//! [`Recording`]: host components then return the recorded values instead
+//! of contacting providers or the network, so the component sees exactly
+//! what it saw the first time.
+//!
+//! Host components call [`replayed_variable`] and [`replayed_response`]
+//! before doing real work, and [`record_variable`] and [`record_response`]
+//! after. The store's environment variables are captured and replayed in
+//! the same way, through [`replayed_environment`] and
+//! [`record_environment`].
+//!
+//! Unless a recording is made with `include_secrets`, the values of secret
+//! variables are left out of it. A replay resolves those variables again
+//! from the providers it is run with.
+//!
+//! Only the Spin outbound HTTP interface is recorded; `wasi:http` requests,
+//! which stream their bodies, are made for real during a replay.
+
+use std::{
+    collections::{BTreeMap, VecDeque},
+    future::Future,
+    sync::{Arc, Mutex},
+};
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+
+tokio::task_local! {
+    static SESSION: Arc<Session>;
+}
+
+enum Session {
+    Record {
+        recording: Mutex<Recording>,
+        include_secrets: bool,
+    },
+    Replay(Mutex<Recording>),
+}
+
+/// What an invocation learned from the outside world.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Recording {
+    /// The variables resolved, in order.
+    #[serde(default)]
+    pub variables: Vec<RecordedVariable>,
+    /// The environment variables the component was given.
+    #[serde(default)]
+    pub environment: BTreeMap<String, String>,
+    /// The outbound HTTP requests made, in order, with their responses.
+    #[serde(default)]
+    pub outbound_http: VecDeque<RecordedExchange>,
+}
+
+/// A variable value resolved by an invocation.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RecordedVariable {
+    /// The variable's name.
+    pub name: String,
+    /// The resolved value, or `None` if the variable is secret and its value
+    /// was left out of the recording.
+    pub value: Option<String>,
+}
+
+/// An outbound HTTP request and its response.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RecordedExchange {
+    /// The request method, e.g. "GET".
+    pub method: String,
+    /// The absolute request URL.
+    pub url: String,
+    /// The response status code.
+    pub status: u16,
+    /// The response headers.
+    pub headers: Vec<(String, String)>,
+    /// The response body.
+    pub body: Vec<u8>,
+}
+
+/// Runs `f`, returning its output and a recording of what it learned. The
+/// values of secret variables are recorded only if `include_secrets` is set.
+pub async fn record<F: Future>(include_secrets: bool, f: F) -> (F::Output, Recording) {
+    let session = Arc::new(Session::Record {
+        recording: Default::default(),
+        include_secrets,
+    });
+    let output = SESSION.scope(session.clone(), f).await;
+    let recording = match &*session {
+        Session::Record { recording, .. } | Session::Replay(recording) => {
+            std::mem::take(&mut *recording.lock().unwrap())
+        }
+    };
+    (output, recording)
+}
+
+/// Runs `f`, answering its variable lookups and outbound HTTP requests from
+/// `recording`.
+pub async fn replay<F: Future>(recording: Recording, f: F) -> F::Output {
+    let session = Arc::new(Session::Replay(Mutex::new(recording)));
+    SESSION.scope(session, f).await
+}
+
+/// Whether the current invocation is being recorded. Host components may use
+/// this to avoid the work of building a record that would be discarded.
+pub fn is_recording() -> bool {
+    SESSION
+        .try_with(|session| matches!(**session, Session::Record { .. }))
+        .unwrap_or(false)
+}
+
+/// In a replay, returns the recorded value of the variable. It is an error
+/// if the variable was not resolved in the recorded invocation. A secret
+/// variable whose value was left out of the recording is not replayed, and
+/// must be resolved as usual.
+pub fn replayed_variable(name: &str) -> Option<Result<String>> {
+    let value = SESSION.try_with(|session| match &**session {
+        Session::Replay(recording) => {
+            let recording = recording.lock().unwrap();
+            match recording.variables.iter().find(|v| v.name == name) {
+                Some(variable) => variable.value.clone().map(Ok),
+                None => Some(Err(anyhow!(
+                    "variable '{name}' was not resolved in the recording"
+                ))),
+            }
+        }
+        Session::Record { .. } => None,
+    });
+    value.ok().flatten()
+}
+
+/// In a recording, records the value a variable resolved to. The value of a
+/// `secret` variable is left out unless the recording includes secrets.
+pub fn record_variable(name: &str, value: &str, secret: bool) {
+    let _ = SESSION.try_with(|session| {
+        if let Session::Record {
+            recording,
+            include_secrets,
+        } = &**session
+        {
+            let value = (!secret || *include_secrets).then(|| value.to_owned());
+            recording.lock().unwrap().variables.push(RecordedVariable {
+                name: name.to_owned(),
+                value,
+            });
+        }
+    });
+}
+
+/// In a replay, returns the recorded environment variables, to be given to
+/// the component in place of its own.
+pub fn replayed_environment() -> Option<BTreeMap<String, String>> {
+    let environment = SESSION.try_with(|session| match &**session {
+        Session::Replay(recording) => Some(recording.lock().unwrap().environment.clone()),
+        Session::Record { .. } => None,
+    });
+    environment.ok().flatten()
+}
+
+/// In a recording, records the environment variables given to the component.
+pub fn record_environment<'a>(vars: impl IntoIterator<Item = (&'a String, &'a String)>) {
+    let _ = SESSION.try_with(|session| {
+        if let Session::Record { recording, .. } = &**session {
+            recording.lock().unwrap().environment.extend(
+                vars.into_iter()
+                    .map(|(name, value)| (name.clone(), value.clone())),
+            );
+        }
+    });
+}
+
+/// In a replay, returns the recorded response to the next outbound request,
+/// which must have the same method and URL as the one recorded.
+pub fn replayed_response(method: &str, url: &str) -> Option<Result<RecordedExchange>> {
+    let exchange = SESSION.try_with(|session| match &**session {
+        Session::Replay(recording) => {
+            let next = recording.lock().unwrap().outbound_http.pop_front();
+            Some(match next {
+                Some(exchange) if exchange.method == method && exchange.url == url => Ok(exchange),
+                Some(exchange) => Err(anyhow!(
+                    "expected a recorded {} request to {}, but the component sent {method} {url}",
+                    exchange.method,
+                    exchange.url
+                )),
+                None => Err(anyhow!(
+                    "the component sent {method} {url}, which is not in the recording"
+                )),
+            })
+        }
+        Session::Record { .. } => None,
+    });
+    exchange.ok().flatten()
+}
+
+/// In a recording, records an outbound request and its response.
+pub fn record_response(exchange: RecordedExchange) {
+    let _ = SESSION.try_with(|session| {
+        if let Session::Record { recording, .. } = &**session {
+            recording.lock().unwrap().outbound_http.push_back(exchange);
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn exchange(url: &str) -> RecordedExchange {
+        RecordedExchange {
+            method: "GET".into(),
+            url: url.into(),
+            status: 200,
+            headers: vec![],
+            body: b"hello".to_vec(),
+        }
+    }
+
+    #[tokio::test]
+    async fn recordings_replay() {
+        let env = BTreeMap::from([("MODE".to_owned(), "test".to_owned())]);
+        let ((), recording) = record(true, async {
+            assert!(replayed_variable("api_key").is_none());
+            record_variable("api_key", "secret", true);
+            record_environment(&env);
+            record_response(exchange("https://example.com/"));
+        })
+        .await;
+
+        replay(recording, async {
+            assert_eq!("secret", replayed_variable("api_key").unwrap().unwrap());
+            assert_eq!(Some(env), replayed_environment());
+            replayed_variable("other").unwrap().unwrap_err();
+            let replayed = replayed_response("GET", "https://example.com/").unwrap();
+            assert_eq!(b"hello", replayed.unwrap().body.as_slice());
+            replayed_response("GET", "https://example.com/")
+                .unwrap()
+                .unwrap_err();
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn replays_reject_different_requests() {
+        let recording = Recording {
+            outbound_http: [exchange("https://example.com/a")].into(),
+            ..Default::default()
+        };
+        replay(recording, async {
+            replayed_response("GET", "https://example.com/b")
+                .unwrap()
+                .unwrap_err();
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn secrets_are_left_out_by_default() {
+        let ((), recording) = record(false, async {
+            record_variable("api_key", "secret", true);
+            record_variable("region", "eu", false);
+        })
+        .await;
+        let json = serde_json::to_string(&recording).unwrap();
+        assert!(!json.contains("secret"), "{json}");
+
+        replay(recording, async {
+            assert!(replayed_variable("api_key").is_none());
+            assert_eq!("eu", replayed_variable("region").unwrap().unwrap());
+        })
+        .await;
+    }
+
+    #[test]
+    fn nothing_is_recorded_outside_a_session() {
+        record_variable("api_key", "secret", false);
+        assert!(replayed_variable("api_key").is_none());
+        assert!(replayed_environment().is_none());
+    }
+}
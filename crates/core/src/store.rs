@@ -1,5 +1,6 @@
 use anyhow::{anyhow, Result};
 use bytes::Bytes;
+use cap_rand::{rngs::StdRng, SeedableRng};
 use std::{
     io::{Read, Write},
     path::{Path, PathBuf},
@@ -18,10 +19,13 @@ use wasmtime_wasi_http::types::WasiHttpCtx;
 
 use crate::{
     async_trait,
+    clocks::{CoarseMonotonicClock, CoarseWallClock, FrozenClock, OffsetWallClock},
     host_component::{HostComponents, HostComponentsData},
     io::OutputBuffer,
     limits::StoreLimitsAsync,
-    preview1, Data,
+    preview1,
+    profiling::StackSamples,
+    usage, Data,
 };
 
 #[cfg(doc)]
@@ -71,6 +75,8 @@ pub enum WasiVersion {
 pub struct Store<T> {
     inner: wasmtime::Store<Data<T>>,
     epoch_tick_interval: Duration,
+    // Whether the deadline is checked by the epoch callback.
+    epoch_callback: bool,
 }
 
 impl<T> Store<T> {
@@ -87,6 +93,12 @@ impl<T> Store<T> {
     ///
     /// See [`wasmtime::Store::set_epoch_deadline`](https://docs.rs/wasmtime/latest/wasmtime/struct.Store.html#method.set_epoch_deadline).
     pub fn set_deadline(&mut self, deadline: Instant) {
+        if self.epoch_callback {
+            // The epoch callback samples or yields, and checks the deadline
+            // itself.
+            self.inner.data_mut().deadline = Some(deadline);
+            return;
+        }
         let now = Instant::now();
         let duration = deadline - now;
         let ticks = if duration.is_zero() {
@@ -99,6 +111,15 @@ impl<T> Store<T> {
         };
         self.inner.set_epoch_deadline(ticks);
     }
+
+    /// Labels the stack samples taken from now on, e.g. with the route
+    /// being handled, so that they can be told apart from the samples of
+    /// the component's other invocations.
+    ///
+    /// See [`StoreBuilder::sample_stacks`].
+    pub fn set_sample_label(&mut self, label: impl Into<String>) {
+        self.inner.data_mut().sample_label = Some(label.into());
+    }
 }
 
 impl<T> AsRef<wasmtime::Store<Data<T>>> for Store<T> {
@@ -127,6 +148,18 @@ impl<T> wasmtime::AsContextMut for Store<T> {
     }
 }
 
+/// How much of the directories mounted into a [`Store`] the guest can see.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum FilesystemAccess {
+    /// Directories are mounted as requested.
+    #[default]
+    ReadWrite,
+    /// Directories are always mounted read-only.
+    ReadOnly,
+    /// No directories are mounted.
+    None,
+}
+
 /// A builder interface for configuring a new [`Store`].
 ///
 /// A new [`StoreBuilder`] can be obtained with [`crate::Engine::store_builder`].
@@ -136,6 +169,9 @@ pub struct StoreBuilder {
     wasi: std::result::Result<WasiCtxBuilder, String>,
     host_components_data: HostComponentsData,
     store_limits: StoreLimitsAsync,
+    stack_samples: Option<Arc<StackSamples>>,
+    max_slice: Option<Duration>,
+    filesystem_access: FilesystemAccess,
 }
 
 impl StoreBuilder {
@@ -152,6 +188,9 @@ impl StoreBuilder {
             wasi: Ok(wasi.into()),
             host_components_data: host_components.new_data(),
             store_limits: StoreLimitsAsync::default(),
+            stack_samples: None,
+            max_slice: None,
+            filesystem_access: FilesystemAccess::default(),
         }
     }
 
@@ -163,6 +202,111 @@ impl StoreBuilder {
         self.store_limits = StoreLimitsAsync::new(Some(max_memory_size), None);
     }
 
+    /// Samples the guest's stack into `samples` at every epoch tick, under
+    /// the label given to [`Store::set_sample_label`], if any.
+    ///
+    /// See [`EngineBuilder::epoch_tick_interval`] for the tick interval.
+    pub fn sample_stacks(&mut self, samples: Arc<StackSamples>) {
+        self.stack_samples = Some(samples);
+    }
+
+    /// Makes the guest yield to the async executor whenever it has run for
+    /// `slice` without yielding, so that CPU-heavy guests don't starve other
+    /// tasks on the same thread.
+    ///
+    /// The slice is rounded up to a whole number of
+    /// [`EngineBuilder::epoch_tick_interval`]s. Forced yields are counted in
+    /// [`crate::usage::Usage::forced_yields`].
+    pub fn max_slice(&mut self, slice: Duration) {
+        self.max_slice = Some(slice);
+    }
+
+    /// Replaces the WASI wall and monotonic clocks with ones which only
+    /// advance in steps of `resolution`, each reading offset by a random
+    /// amount of up to one step.
+    ///
+    /// This is only supported with WASI Preview 2.
+    pub fn coarse_clocks(&mut self, resolution: Duration) -> Result<()> {
+        self.try_with_wasi(|wasi| match wasi {
+            WasiCtxBuilder::Preview1(_) => Err(anyhow!(
+                "coarse clocks are only supported with WASI Preview 2"
+            )),
+            WasiCtxBuilder::Preview2(ctx) => {
+                ctx.wall_clock(CoarseWallClock::new(resolution))
+                    .monotonic_clock(CoarseMonotonicClock::new(resolution));
+                Ok(())
+            }
+        })
+    }
+
+    /// Replaces the WASI wall and monotonic clocks with ones which always
+    /// read `since_epoch` and zero respectively.
+    ///
+    /// This is only supported with WASI Preview 2.
+    pub fn frozen_clocks(&mut self, since_epoch: Duration) -> Result<()> {
+        self.try_with_wasi(|wasi| match wasi {
+            WasiCtxBuilder::Preview1(_) => Err(anyhow!(
+                "frozen clocks are only supported with WASI Preview 2"
+            )),
+            WasiCtxBuilder::Preview2(ctx) => {
+                ctx.wall_clock(FrozenClock::new(since_epoch))
+                    .monotonic_clock(FrozenClock::new(since_epoch));
+                Ok(())
+            }
+        })
+    }
+
+    /// Replaces the WASI wall clock with one which reads the host's time
+    /// plus `offset_secs` seconds, which may be negative.
+    ///
+    /// This is only supported with WASI Preview 2.
+    pub fn offset_wall_clock(&mut self, offset_secs: i64) -> Result<()> {
+        self.try_with_wasi(|wasi| match wasi {
+            WasiCtxBuilder::Preview1(_) => Err(anyhow!(
+                "offset clocks are only supported with WASI Preview 2"
+            )),
+            WasiCtxBuilder::Preview2(ctx) => {
+                ctx.wall_clock(OffsetWallClock::new(offset_secs));
+                Ok(())
+            }
+        })
+    }
+
+    /// Replaces the WASI random sources with ones seeded from `seed`, so
+    /// that the guest sees the same "random" numbers on every run.
+    ///
+    /// This is only supported with WASI Preview 2, and must never be used
+    /// for components which rely on randomness for security.
+    pub fn random_seed(&mut self, seed: u64) -> Result<()> {
+        self.try_with_wasi(|wasi| match wasi {
+            WasiCtxBuilder::Preview1(_) => Err(anyhow!(
+                "seeded random is only supported with WASI Preview 2"
+            )),
+            WasiCtxBuilder::Preview2(ctx) => {
+                ctx.secure_random(StdRng::seed_from_u64(seed))
+                    .insecure_random(StdRng::seed_from_u64(seed))
+                    .insecure_random_seed(seed.into());
+                Ok(())
+            }
+        })
+    }
+
+    /// Restricts the directories which may be mounted from then on with
+    /// [`Self::read_only_preopened_dir`] and
+    /// [`Self::read_write_preopened_dir`].
+    ///
+    /// Restrictions only ever tighten: once directories are read-only they
+    /// can't be made writable again.
+    pub fn restrict_filesystem(&mut self, access: FilesystemAccess) {
+        self.filesystem_access = match (self.filesystem_access, access) {
+            (FilesystemAccess::None, _) | (_, FilesystemAccess::None) => FilesystemAccess::None,
+            (FilesystemAccess::ReadOnly, _) | (_, FilesystemAccess::ReadOnly) => {
+                FilesystemAccess::ReadOnly
+            }
+            _ => FilesystemAccess::ReadWrite,
+        };
+    }
+
     /// Inherit stdin from the host process.
     pub fn inherit_stdin(&mut self) {
         self.with_wasi(|wasi| match wasi {
@@ -328,6 +472,11 @@ impl StoreBuilder {
         guest_path: PathBuf,
         writable: bool,
     ) -> Result<()> {
+        let writable = match self.filesystem_access {
+            FilesystemAccess::None => return Ok(()),
+            FilesystemAccess::ReadOnly => false,
+            FilesystemAccess::ReadWrite => writable,
+        };
         let cap_std_dir =
             cap_std::fs::Dir::open_ambient_dir(host_path.as_ref(), cap_std::ambient_authority())?;
         let path = guest_path
@@ -378,20 +527,55 @@ impl StoreBuilder {
                 host_components_data: self.host_components_data,
                 store_limits: self.store_limits,
                 table: wasi_preview2::Table::new(),
+                deadline: None,
+                sample_label: None,
             },
         );
 
         inner.limiter_async(move |data| &mut data.store_limits);
 
-        // With epoch interruption enabled, there must be _some_ deadline set
-        // or execution will trap immediately. Since this is a delta, we need
-        // to avoid overflow so we'll use 2^63 which is still "practically
-        // forever" for any plausible tick interval.
-        inner.set_epoch_deadline(u64::MAX / 2);
+        let slice_ticks = self
+            .max_slice
+            .map(|slice| ticks_in(slice, self.epoch_tick_interval));
+        let epoch_callback = self.stack_samples.is_some() || slice_ticks.is_some();
+        if epoch_callback {
+            // When sampling, the callback runs on every tick; otherwise only
+            // at the end of each slice.
+            let interval = match (&self.stack_samples, slice_ticks) {
+                (None, Some(slice_ticks)) => slice_ticks,
+                _ => 1,
+            };
+            let samples = self.stack_samples;
+            let mut ticks_run = 0;
+            inner.set_epoch_deadline(interval);
+            inner.epoch_deadline_callback(move |store| {
+                if let Some(samples) = &samples {
+                    let backtrace = wasmtime::WasmBacktrace::capture(&store);
+                    samples.sample(store.data().sample_label.as_deref(), &backtrace);
+                }
+                if store.data().deadline.is_some_and(|d| d <= Instant::now()) {
+                    return Err(wasmtime::Trap::Interrupt.into());
+                }
+                ticks_run += interval;
+                if slice_ticks.is_some_and(|slice_ticks| ticks_run >= slice_ticks) {
+                    ticks_run = 0;
+                    usage::record_forced_yield();
+                    return Ok(wasmtime::UpdateDeadline::Yield(interval));
+                }
+                Ok(wasmtime::UpdateDeadline::Continue(interval))
+            });
+        } else {
+            // With epoch interruption enabled, there must be _some_ deadline
+            // set or execution will trap immediately. Since this is a delta,
+            // we need to avoid overflow so we'll use 2^63 which is still
+            // "practically forever" for any plausible tick interval.
+            inner.set_epoch_deadline(u64::MAX / 2);
+        }
 
         Ok(Store {
             inner,
             epoch_tick_interval: self.epoch_tick_interval,
+            epoch_callback,
         })
     }
 
@@ -423,6 +607,13 @@ impl StoreBuilder {
     }
 }
 
+/// The number of epoch ticks in `duration`, rounded up and at least one.
+fn ticks_in(duration: Duration, epoch_tick_interval: Duration) -> u64 {
+    let interval = epoch_tick_interval.as_micros().max(1);
+    let ticks = (duration.as_micros() + interval - 1) / interval;
+    ticks.clamp(1, u64::MAX as u128) as u64
+}
+
 struct PipeStdinStream<T> {
     buffer: Vec<u8>,
     inner: Arc<Mutex<T>>,
@@ -0,0 +1,158 @@
+//! Measuring the resources used by an invocation of a component.
+//!
+//! [`measure`] runs a future, such as a trigger's handling of one request,
+//! and reports the CPU time spent polling it, the peak linear memory of the
+//! instances created while it ran, and how often they were made to yield.
+//! It also reports how much of the invocation's time went on instantiating
+//! components, as recorded by the trigger with [`record_instantiation`], and
+//! on outbound calls made inside [`outbound`].
+
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
+
+tokio::task_local! {
+    static COUNTERS: Arc<Counters>;
+}
+
+#[derive(Default)]
+struct Counters {
+    peak_memory: AtomicU64,
+    forced_yields: AtomicU64,
+    instantiation_us: AtomicU64,
+    outbound_us: AtomicU64,
+}
+
+/// The resources used by an invocation.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Usage {
+    /// The time spent running the invocation's guest and host code. This
+    /// does not include time spent waiting, e.g. for outbound requests.
+    pub cpu_time: Duration,
+    /// The largest linear memory, in bytes, of any instance created by the
+    /// invocation.
+    pub peak_memory_bytes: u64,
+    /// The number of times the invocation's instances were made to yield
+    /// because they had run for longer than their time slice.
+    pub forced_yields: u64,
+    /// The time spent instantiating components.
+    pub instantiation_time: Duration,
+    /// The time spent waiting on outbound calls.
+    pub outbound_time: Duration,
+}
+
+/// Runs `f`, returning its output and the resources it used.
+pub async fn measure<F: Future>(f: F) -> (F::Output, Usage) {
+    let counters = Arc::new(Counters::default());
+    let busy = Busy {
+        inner: Box::pin(f),
+        cpu_time: Duration::ZERO,
+    };
+    let (output, cpu_time) = COUNTERS.scope(counters.clone(), busy).await;
+    let usage = Usage {
+        cpu_time,
+        peak_memory_bytes: counters.peak_memory.load(Ordering::Relaxed),
+        forced_yields: counters.forced_yields.load(Ordering::Relaxed),
+        instantiation_time: Duration::from_micros(
+            counters.instantiation_us.load(Ordering::Relaxed),
+        ),
+        outbound_time: Duration::from_micros(counters.outbound_us.load(Ordering::Relaxed)),
+    };
+    (output, usage)
+}
+
+/// Records that an instance's linear memory has grown to `bytes`.
+pub(crate) fn record_memory(bytes: u64) {
+    let _ = COUNTERS.try_with(|c| c.peak_memory.fetch_max(bytes, Ordering::Relaxed));
+}
+
+/// Records that an instance was made to yield at the end of its time slice.
+pub(crate) fn record_forced_yield() {
+    let _ = COUNTERS.try_with(|c| c.forced_yields.fetch_add(1, Ordering::Relaxed));
+}
+
+/// Records that the invocation spent `elapsed` instantiating a component.
+pub fn record_instantiation(elapsed: Duration) {
+    let us = elapsed.as_micros() as u64;
+    let _ = COUNTERS.try_with(|c| c.instantiation_us.fetch_add(us, Ordering::Relaxed));
+}
+
+/// Runs `f`, an outbound call such as an HTTP request, counting the time it
+/// takes towards the invocation's outbound time.
+pub async fn outbound<F: Future>(f: F) -> F::Output {
+    let start = Instant::now();
+    let output = f.await;
+    let us = start.elapsed().as_micros() as u64;
+    let _ = COUNTERS.try_with(|c| c.outbound_us.fetch_add(us, Ordering::Relaxed));
+    output
+}
+
+/// Counts the time spent polling the inner future.
+struct Busy<F> {
+    inner: Pin<Box<F>>,
+    cpu_time: Duration,
+}
+
+impl<F: Future> Future for Busy<F> {
+    type Output = (F::Output, Duration);
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let start = Instant::now();
+        let poll = self.inner.as_mut().poll(cx);
+        self.cpu_time += start.elapsed();
+        poll.map(|output| (output, self.cpu_time))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn measures_peak_memory_of_the_invocation() {
+        let ((), usage) = measure(async {
+            record_memory(65536);
+            record_memory(131072);
+            record_memory(65536);
+        })
+        .await;
+        assert_eq!(131072, usage.peak_memory_bytes);
+
+        // Outside an invocation, memory isn't attributed to anything
+        record_memory(65536);
+    }
+
+    #[tokio::test]
+    async fn counts_forced_yields() {
+        let ((), usage) = measure(async {
+            record_forced_yield();
+            record_forced_yield();
+        })
+        .await;
+        assert_eq!(2, usage.forced_yields);
+    }
+
+    #[tokio::test]
+    async fn times_instantiation_and_outbound_calls() {
+        let ((), usage) = measure(async {
+            record_instantiation(Duration::from_millis(3));
+            outbound(tokio::time::sleep(Duration::from_millis(20))).await;
+        })
+        .await;
+        assert_eq!(Duration::from_millis(3), usage.instantiation_time);
+        assert!(usage.outbound_time >= Duration::from_millis(20));
+    }
+
+    #[tokio::test]
+    async fn waiting_is_not_cpu_time() {
+        let ((), usage) = measure(tokio::time::sleep(Duration::from_millis(50))).await;
+        assert!(usage.cpu_time < Duration::from_millis(50));
+    }
+}
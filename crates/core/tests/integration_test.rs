@@ -131,6 +131,33 @@ async fn test_set_deadline_violated() {
     assert_eq!(trap, Trap::Interrupt);
 }
 
+#[tokio::test(flavor = "multi_thread")]
+async fn test_set_deadline_violated_with_max_slice() {
+    let err = run_core_wasi_test_engine(
+        &test_engine(),
+        ["sleep", "100"],
+        |store_builder| store_builder.max_slice(Duration::from_millis(20)),
+        |store| {
+            store.set_deadline(Instant::now() + Duration::from_millis(10));
+        },
+    )
+    .await
+    .unwrap_err();
+    let trap = err.downcast::<Trap>().expect("trap");
+    assert_eq!(trap, Trap::Interrupt);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_max_slice_forces_yields() {
+    let (res, usage) =
+        spin_core::usage::measure(run_core_wasi_test(["sleep", "50"], |store_builder| {
+            store_builder.max_slice(Duration::from_millis(10))
+        }))
+        .await;
+    res.unwrap();
+    assert!(usage.forced_yields > 0);
+}
+
 #[tokio::test(flavor = "multi_thread")]
 async fn test_host_component() {
     let stdout = run_core_wasi_test(["multiply", "5"], |_| {}).await.unwrap();
@@ -167,6 +194,30 @@ async fn test_panic() {
     assert_eq!(trap, Trap::UnreachableCodeReached);
 }
 
+#[tokio::test(flavor = "multi_thread")]
+async fn test_guest_debugging_runs_components() {
+    let mut config = test_config();
+    config.enable_guest_debugging();
+    let mut builder = Engine::builder(&config).unwrap();
+    builder.add_host_component(MultiplierHostComponent).unwrap();
+    builder
+        .link_import(|l, _| wasmtime_wasi::preview2::command::add_to_linker(l))
+        .unwrap();
+    let engine = builder.build();
+
+    let stdout = run_core_wasi_test_engine(
+        &engine,
+        ["echo"],
+        |store_builder| {
+            store_builder.stdin_pipe(Cursor::new(b"DEBUG"));
+        },
+        |_| {},
+    )
+    .await
+    .unwrap();
+    assert_eq!(stdout, "DEBUG");
+}
+
 fn test_config() -> Config {
     let mut config = Config::default();
     config
@@ -0,0 +1,165 @@
+//! Cryptography with keys held by the host, so that components can verify
+//! webhooks, sign tokens and encrypt data without handling key material.
+
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
+
+use anyhow::{anyhow, Result};
+use spin_app::{AppComponent, DynamicHostComponent, MetadataKey};
+use spin_core::{async_trait, HostComponent};
+use spin_world::v3::crypto;
+
+mod key;
+
+pub use crypto::Error;
+pub use key::Key;
+
+pub const CRYPTO_KEYS_KEY: MetadataKey<HashSet<String>> = MetadataKey::new("crypto_keys");
+
+pub struct CryptoComponent {
+    keys: Arc<HashMap<String, Arc<Key>>>,
+}
+
+impl CryptoComponent {
+    /// A component providing the given keys, by label.
+    pub fn new(keys: HashMap<String, Arc<Key>>) -> Self {
+        Self {
+            keys: Arc::new(keys),
+        }
+    }
+
+    /// The keys this component provides, by label.
+    pub fn keys(&self) -> Arc<HashMap<String, Arc<Key>>> {
+        self.keys.clone()
+    }
+}
+
+impl HostComponent for CryptoComponent {
+    type Data = CryptoDispatch;
+
+    fn add_to_linker<T: Send>(
+        linker: &mut spin_core::Linker<T>,
+        get: impl Fn(&mut spin_core::Data<T>) -> &mut Self::Data + Send + Sync + Copy + 'static,
+    ) -> anyhow::Result<()> {
+        crypto::add_to_linker(linker, get)
+    }
+
+    fn build_data(&self) -> Self::Data {
+        CryptoDispatch {
+            allowed_keys: HashSet::new(),
+            keys: self.keys.clone(),
+        }
+    }
+}
+
+impl DynamicHostComponent for CryptoComponent {
+    fn update_data(&self, data: &mut Self::Data, component: &AppComponent) -> anyhow::Result<()> {
+        data.allowed_keys = component.get_metadata(CRYPTO_KEYS_KEY)?.unwrap_or_default();
+        Ok(())
+    }
+
+    fn validate_app(&self, app: &spin_app::App) -> anyhow::Result<()> {
+        let mut errors = vec![];
+
+        for component in app.components() {
+            for allowed in component.get_metadata(CRYPTO_KEYS_KEY)?.unwrap_or_default() {
+                if !self.keys.contains_key(&allowed) {
+                    let err = format!("- Component {} uses crypto key '{allowed}'", component.id());
+                    errors.push(err);
+                }
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            let prologue = vec![
+                "One or more components use crypto keys which are not defined.",
+                "Check the spelling, or pass a runtime configuration file that defines these keys.",
+                "Details:",
+            ];
+            let lines: Vec<_> = prologue
+                .into_iter()
+                .map(|s| s.to_owned())
+                .chain(errors)
+                .collect();
+            Err(anyhow!(lines.join("\n")))
+        }
+    }
+}
+
+pub struct CryptoDispatch {
+    allowed_keys: HashSet<String>,
+    keys: Arc<HashMap<String, Arc<Key>>>,
+}
+
+impl CryptoDispatch {
+    fn key(&self, label: &str) -> Result<&Key, Error> {
+        if !self.allowed_keys.contains(label) {
+            return Err(Error::AccessDenied);
+        }
+        self.keys
+            .get(label)
+            .map(|key| &**key)
+            .ok_or(Error::NoSuchKey)
+    }
+}
+
+#[async_trait]
+impl crypto::Host for CryptoDispatch {
+    async fn hmac(&mut self, key: String, data: Vec<u8>) -> Result<Result<Vec<u8>, Error>> {
+        Ok(self.key(&key).and_then(|key| key.hmac(&data)))
+    }
+
+    async fn verify_hmac(
+        &mut self,
+        key: String,
+        data: Vec<u8>,
+        tag: Vec<u8>,
+    ) -> Result<Result<bool, Error>> {
+        Ok(self.key(&key).and_then(|key| key.verify_hmac(&data, &tag)))
+    }
+
+    async fn sign(&mut self, key: String, message: Vec<u8>) -> Result<Result<Vec<u8>, Error>> {
+        Ok(self.key(&key).and_then(|key| key.sign(&message)))
+    }
+
+    async fn verify(
+        &mut self,
+        key: String,
+        message: Vec<u8>,
+        signature: Vec<u8>,
+    ) -> Result<Result<bool, Error>> {
+        Ok(self
+            .key(&key)
+            .and_then(|key| key.verify(&message, &signature)))
+    }
+
+    async fn public_key(&mut self, key: String) -> Result<Result<Vec<u8>, Error>> {
+        Ok(self.key(&key).and_then(Key::public_key))
+    }
+
+    async fn encrypt(
+        &mut self,
+        key: String,
+        plaintext: Vec<u8>,
+        associated_data: Vec<u8>,
+    ) -> Result<Result<Vec<u8>, Error>> {
+        Ok(self
+            .key(&key)
+            .and_then(|key| key.encrypt(&plaintext, &associated_data)))
+    }
+
+    async fn decrypt(
+        &mut self,
+        key: String,
+        ciphertext: Vec<u8>,
+        associated_data: Vec<u8>,
+    ) -> Result<Result<Vec<u8>, Error>> {
+        Ok(self
+            .key(&key)
+            .and_then(|key| key.decrypt(&ciphertext, &associated_data)))
+    }
+}
@@ -0,0 +1,119 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock, Weak},
+    time::{Duration, SystemTime},
+};
+
+use anyhow::{Context as _, Result};
+use spin_core::async_trait;
+
+use crate::{Context, Flag, FlagProvider};
+
+/// How often the flags file is checked for changes.
+const RELOAD_INTERVAL: Duration = Duration::from_secs(1);
+
+type Flags = HashMap<String, Flag>;
+
+/// Flags defined in a TOML file, as a `[<name>]` table for each flag, which
+/// are reloaded when the file changes.
+pub struct FileFlags {
+    flags: Arc<RwLock<Flags>>,
+}
+
+impl FileFlags {
+    /// Loads the flags in the file at `path`, then watches it for changes.
+    /// Must be called within a Tokio runtime.
+    pub fn new(path: impl Into<PathBuf>) -> Result<Self> {
+        let path = path.into();
+        let (flags, modified) = load(&path)?;
+        let flags = Arc::new(RwLock::new(flags));
+        tokio::spawn(watch(path, modified, Arc::downgrade(&flags)));
+        Ok(Self { flags })
+    }
+}
+
+#[async_trait]
+impl FlagProvider for FileFlags {
+    async fn is_enabled(&self, name: &str, context: &Context) -> Result<bool> {
+        let flags = self.flags.read().unwrap();
+        Ok(flags
+            .get(name)
+            .map_or(false, |flag| flag.evaluate(name, context)))
+    }
+}
+
+fn load(path: &Path) -> Result<(Flags, Option<SystemTime>)> {
+    let modified = modified(path);
+    let contents = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read flags file {path:?}"))?;
+    let flags =
+        toml::from_str(&contents).with_context(|| format!("Invalid flags file {path:?}"))?;
+    Ok((flags, modified))
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// Reloads the flags when the file's modification time changes, until the
+/// provider is dropped. A file which fails to load leaves the flags as they
+/// were.
+async fn watch(path: PathBuf, mut loaded: Option<SystemTime>, flags: Weak<RwLock<Flags>>) {
+    loop {
+        tokio::time::sleep(RELOAD_INTERVAL).await;
+        let Some(flags) = flags.upgrade() else {
+            return;
+        };
+        let modified = modified(&path);
+        if modified == loaded {
+            continue;
+        }
+        loaded = modified;
+        match load(&path) {
+            Ok((new, _)) => {
+                *flags.write().unwrap() = new;
+                tracing::info!("Reloaded flags from {path:?}");
+            }
+            Err(e) => tracing::warn!("Failed to reload flags, keeping the old ones: {e:?}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[tokio::test]
+    async fn flags_are_read_from_the_file() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("flags.toml");
+        std::fs::write(
+            &path,
+            r#"
+            [new-checkout]
+            keys = ["tester"]
+
+            [dark-mode]
+            enabled = false
+            "#,
+        )?;
+        let flags = FileFlags::new(&path)?;
+
+        let tester = Context {
+            key: "tester".into(),
+            ..Default::default()
+        };
+        assert!(flags.is_enabled("new-checkout", &tester).await?);
+        assert!(!flags.is_enabled("dark-mode", &tester).await?);
+        assert!(!flags.is_enabled("unknown", &tester).await?);
+
+        std::fs::write(&path, "[unknown]")?;
+        let (reloaded, _) = load(&path)?;
+        assert!(reloaded["unknown"].evaluate("unknown", &tester));
+
+        std::fs::write(&path, "not toml")?;
+        load(&path).unwrap_err();
+        Ok(())
+    }
+}
@@ -0,0 +1,84 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, Context as _, Result};
+use spin_core::async_trait;
+use spin_key_value::StoreManager;
+
+use crate::{Context, Flag, FlagProvider};
+
+/// Flags stored as JSON in a key-value store, at `<key_prefix><name>`. Each
+/// flag is read at most once per time to live, so that changes to it take
+/// effect within that time.
+pub struct KeyValueFlags {
+    manager: Arc<dyn StoreManager>,
+    store: String,
+    key_prefix: String,
+    ttl: Duration,
+    // Flag name -> when it was read, and its definition, if any.
+    cache: Mutex<HashMap<String, (Instant, Option<Flag>)>>,
+}
+
+impl KeyValueFlags {
+    /// Flags read from the store `store` of `manager`, and kept for `ttl`.
+    pub fn new(
+        manager: Arc<dyn StoreManager>,
+        store: String,
+        key_prefix: String,
+        ttl: Duration,
+    ) -> Self {
+        Self {
+            manager,
+            store,
+            key_prefix,
+            ttl,
+            cache: Default::default(),
+        }
+    }
+
+    fn cached(&self, name: &str) -> Option<Option<Flag>> {
+        let cache = self.cache.lock().unwrap();
+        let (read, flag) = cache.get(name)?;
+        (read.elapsed() < self.ttl).then(|| flag.clone())
+    }
+
+    async fn read(&self, name: &str) -> Result<Option<Flag>> {
+        let store = self
+            .manager
+            .get(&self.store)
+            .await
+            .map_err(|e| anyhow!("Failed to open key-value store {:?}: {e:?}", self.store))?;
+        let key = format!("{}{name}", self.key_prefix);
+        let Some(json) = store
+            .get(&key)
+            .await
+            .map_err(|e| anyhow!("Failed to read flag {name:?}: {e:?}"))?
+        else {
+            return Ok(None);
+        };
+        let flag = serde_json::from_slice(&json)
+            .with_context(|| format!("Invalid flag {name:?} at key {key:?}"))?;
+        Ok(Some(flag))
+    }
+}
+
+#[async_trait]
+impl FlagProvider for KeyValueFlags {
+    async fn is_enabled(&self, name: &str, context: &Context) -> Result<bool> {
+        let flag = match self.cached(name) {
+            Some(flag) => flag,
+            None => {
+                let flag = self.read(name).await?;
+                self.cache
+                    .lock()
+                    .unwrap()
+                    .insert(name.to_owned(), (Instant::now(), flag.clone()));
+                flag
+            }
+        };
+        Ok(flag.map_or(false, |flag| flag.evaluate(name, context)))
+    }
+}
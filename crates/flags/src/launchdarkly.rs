@@ -0,0 +1,438 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock, Weak},
+    time::Duration,
+};
+
+use anyhow::{Context as _, Result};
+use futures::StreamExt;
+use reqwest::header::{ACCEPT, AUTHORIZATION};
+use serde::Deserialize;
+use serde_json::Value;
+use sha1::{Digest, Sha1};
+use spin_core::async_trait;
+
+use crate::{Context, FlagProvider};
+
+const MIN_RECONNECT_DELAY: Duration = Duration::from_secs(1);
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(30);
+
+type Flags = HashMap<String, LdFlag>;
+
+/// Flags streamed from a LaunchDarkly-compatible service, such as
+/// LaunchDarkly itself or a relay proxy.
+///
+/// Flags are evaluated on the host: individual targets, rules whose clauses
+/// use the `in`, `startsWith`, `endsWith` and `contains` operators, and
+/// percentage rollouts are supported, with the flag's boolean variations.
+/// Prerequisites and segments are not.
+pub struct LaunchDarklyFlags {
+    flags: Arc<RwLock<Flags>>,
+}
+
+impl LaunchDarklyFlags {
+    /// Streams flags from `stream_url`, e.g. "https://stream.launchdarkly.com",
+    /// with the server-side SDK key `sdk_key`, reconnecting whenever the
+    /// stream fails. Every flag is disabled until the first flags arrive.
+    /// Must be called within a Tokio runtime.
+    pub fn new(stream_url: &str, sdk_key: String) -> Result<Self> {
+        let url = format!("{}/all", stream_url.trim_end_matches('/'));
+        let url = reqwest::Url::parse(&url)
+            .with_context(|| format!("Invalid flag stream URL {stream_url:?}"))?;
+        let flags = Arc::new(RwLock::new(Flags::new()));
+        tokio::spawn(stream(url, sdk_key, Arc::downgrade(&flags)));
+        Ok(Self { flags })
+    }
+}
+
+#[async_trait]
+impl FlagProvider for LaunchDarklyFlags {
+    async fn is_enabled(&self, name: &str, context: &Context) -> Result<bool> {
+        let flags = self.flags.read().unwrap();
+        Ok(flags
+            .get(name)
+            .map_or(false, |flag| flag.is_enabled(name, context)))
+    }
+}
+
+/// Keeps `flags` up to date with the stream at `url` until the provider is
+/// dropped.
+async fn stream(url: reqwest::Url, sdk_key: String, flags: Weak<RwLock<Flags>>) {
+    let client = reqwest::Client::new();
+    let mut delay = MIN_RECONNECT_DELAY;
+    loop {
+        match follow(&client, &url, &sdk_key, &flags).await {
+            Ok(()) => delay = MIN_RECONNECT_DELAY,
+            Err(e) => tracing::warn!("Flag stream failed, reconnecting in {delay:?}: {e:?}"),
+        }
+        if flags.strong_count() == 0 {
+            return;
+        }
+        tokio::time::sleep(delay).await;
+        delay = (delay * 2).min(MAX_RECONNECT_DELAY);
+    }
+}
+
+async fn follow(
+    client: &reqwest::Client,
+    url: &reqwest::Url,
+    sdk_key: &str,
+    flags: &Weak<RwLock<Flags>>,
+) -> Result<()> {
+    let response = client
+        .get(url.clone())
+        .header(AUTHORIZATION, sdk_key)
+        .header(ACCEPT, "text/event-stream")
+        .send()
+        .await?
+        .error_for_status()?;
+    let mut body = response.bytes_stream();
+    let mut parser = EventParser::default();
+    while let Some(chunk) = body.next().await {
+        for (event, data) in parser.push(&chunk?) {
+            let Some(flags) = flags.upgrade() else {
+                return Ok(());
+            };
+            if let Err(e) = apply(&flags, &event, &data) {
+                tracing::warn!("Ignoring a malformed {event:?} flag event: {e:?}");
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Splits a server-sent event stream into (event, data) pairs.
+#[derive(Default)]
+struct EventParser {
+    buffer: Vec<u8>,
+    event: String,
+    data: String,
+}
+
+impl EventParser {
+    fn push(&mut self, chunk: &[u8]) -> Vec<(String, String)> {
+        self.buffer.extend_from_slice(chunk);
+        let mut events = vec![];
+        while let Some(end) = self.buffer.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = self.buffer.drain(..=end).collect();
+            let line = String::from_utf8_lossy(&line);
+            let line = line.trim_end_matches(['\n', '\r']);
+            if line.is_empty() {
+                let event = std::mem::take(&mut self.event);
+                if !self.data.is_empty() {
+                    events.push((event, std::mem::take(&mut self.data)));
+                }
+                continue;
+            }
+            let (field, value) = line.split_once(':').unwrap_or((line, ""));
+            let value = value.strip_prefix(' ').unwrap_or(value);
+            match field {
+                "event" => self.event = value.to_owned(),
+                "data" => {
+                    if !self.data.is_empty() {
+                        self.data.push('\n');
+                    }
+                    self.data.push_str(value);
+                }
+                // Comments, such as keep-alives, and other fields.
+                _ => (),
+            }
+        }
+        events
+    }
+}
+
+/// Applies a `put` (all flags), `patch` (one flag) or `delete` event.
+fn apply(flags: &RwLock<Flags>, event: &str, data: &str) -> Result<()> {
+    #[derive(Deserialize)]
+    struct Put {
+        data: PutData,
+    }
+    #[derive(Deserialize)]
+    struct PutData {
+        #[serde(default)]
+        flags: Flags,
+    }
+    #[derive(Deserialize)]
+    struct Patch {
+        path: String,
+        data: LdFlag,
+    }
+    #[derive(Deserialize)]
+    struct Delete {
+        path: String,
+        version: u64,
+    }
+
+    match event {
+        "put" => {
+            let put: Put = serde_json::from_str(data)?;
+            *flags.write().unwrap() = put.data.flags;
+        }
+        "patch" => {
+            let patch: Patch = serde_json::from_str(data)?;
+            if let Some(name) = patch.path.strip_prefix("/flags/") {
+                let mut flags = flags.write().unwrap();
+                // Events may arrive out of order.
+                if flags
+                    .get(name)
+                    .map_or(true, |f| f.version < patch.data.version)
+                {
+                    flags.insert(name.to_owned(), patch.data);
+                }
+            }
+        }
+        "delete" => {
+            let delete: Delete = serde_json::from_str(data)?;
+            if let Some(name) = delete.path.strip_prefix("/flags/") {
+                let mut flags = flags.write().unwrap();
+                if flags
+                    .get(name)
+                    .map_or(false, |f| f.version < delete.version)
+                {
+                    flags.remove(name);
+                }
+            }
+        }
+        _ => (),
+    }
+    Ok(())
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct LdFlag {
+    #[serde(default)]
+    on: bool,
+    #[serde(default)]
+    variations: Vec<Value>,
+    #[serde(default)]
+    off_variation: Option<usize>,
+    #[serde(default)]
+    fallthrough: Serve,
+    #[serde(default)]
+    targets: Vec<Target>,
+    #[serde(default)]
+    rules: Vec<Rule>,
+    #[serde(default)]
+    salt: String,
+    #[serde(default)]
+    version: u64,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+struct Serve {
+    #[serde(default)]
+    variation: Option<usize>,
+    #[serde(default)]
+    rollout: Option<Rollout>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Rollout {
+    variations: Vec<WeightedVariation>,
+    #[serde(default)]
+    bucket_by: Option<String>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+struct WeightedVariation {
+    variation: usize,
+    /// In thousandths of a percent.
+    weight: u64,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+struct Target {
+    values: Vec<String>,
+    variation: usize,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+struct Rule {
+    #[serde(default)]
+    clauses: Vec<Clause>,
+    #[serde(flatten)]
+    serve: Serve,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+struct Clause {
+    attribute: String,
+    op: String,
+    #[serde(default)]
+    values: Vec<Value>,
+    #[serde(default)]
+    negate: bool,
+}
+
+impl LdFlag {
+    fn is_enabled(&self, name: &str, context: &Context) -> bool {
+        self.variation(name, context)
+            .and_then(|index| self.variations.get(index))
+            .and_then(Value::as_bool)
+            .unwrap_or(false)
+    }
+
+    fn variation(&self, name: &str, context: &Context) -> Option<usize> {
+        if !self.on {
+            return self.off_variation;
+        }
+        if let Some(target) = self
+            .targets
+            .iter()
+            .find(|t| t.values.contains(&context.key))
+        {
+            return Some(target.variation);
+        }
+        let serve = self
+            .rules
+            .iter()
+            .find(|rule| rule.clauses.iter().all(|clause| clause.matches(context)))
+            .map_or(&self.fallthrough, |rule| &rule.serve);
+        serve.variation(name, &self.salt, context)
+    }
+}
+
+impl Serve {
+    fn variation(&self, name: &str, salt: &str, context: &Context) -> Option<usize> {
+        if let Some(variation) = self.variation {
+            return Some(variation);
+        }
+        let rollout = self.rollout.as_ref()?;
+        let bucket_by = rollout.bucket_by.as_deref().unwrap_or("key");
+        let bucket = bucket(name, salt, attribute(context, bucket_by)?);
+        let mut sum = 0.0;
+        for weighted in &rollout.variations {
+            sum += weighted.weight as f64 / 100_000.0;
+            if bucket < sum {
+                return Some(weighted.variation);
+            }
+        }
+        rollout.variations.last().map(|weighted| weighted.variation)
+    }
+}
+
+impl Clause {
+    fn matches(&self, context: &Context) -> bool {
+        // A clause on a missing attribute never matches, even if negated.
+        let Some(value) = attribute(context, &self.attribute) else {
+            return false;
+        };
+        let matched =
+            self.values
+                .iter()
+                .filter_map(Value::as_str)
+                .any(|v| match self.op.as_str() {
+                    "in" => value == v,
+                    "startsWith" => value.starts_with(v),
+                    "endsWith" => value.ends_with(v),
+                    "contains" => value.contains(v),
+                    _ => false,
+                });
+        matched != self.negate
+    }
+}
+
+fn attribute<'a>(context: &'a Context, name: &str) -> Option<&'a str> {
+    match name {
+        "key" => Some(&context.key),
+        _ => context.attributes.get(name).map(String::as_str),
+    }
+}
+
+/// Places a value in `[0, 1)` as LaunchDarkly's SDKs do, so that rollouts
+/// enable a flag for the same contexts as other services using the flag.
+fn bucket(name: &str, salt: &str, value: &str) -> f64 {
+    let hash = Sha1::digest(format!("{name}.{salt}.{value}"));
+    let hex: String = hash.iter().map(|b| format!("{b:02x}")).collect();
+    let value = u64::from_str_radix(&hex[..15], 16).unwrap();
+    value as f64 / 0xFFF_FFFF_FFFF_FFFF_u64 as f64
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn context(key: &str, attributes: &[(&str, &str)]) -> Context {
+        Context {
+            key: key.into(),
+            attributes: attributes
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn events_are_parsed_from_chunks() {
+        let mut parser = EventParser::default();
+        assert!(parser.push(b":keep-alive\n\nevent: put\nda").is_empty());
+        let events = parser.push(b"ta: {}\r\n\r\nevent: patch\ndata: 1\ndata: 2\n\n");
+        assert_eq!(
+            vec![
+                ("put".to_owned(), "{}".to_owned()),
+                ("patch".to_owned(), "1\n2".to_owned())
+            ],
+            events
+        );
+    }
+
+    #[test]
+    fn flags_are_put_patched_and_deleted() {
+        let flags = RwLock::new(Flags::new());
+        apply(
+            &flags,
+            "put",
+            r#"{"path": "/", "data": {"flags": {
+                "beta": {"on": true, "variations": [true, false], "fallthrough": {"variation": 0}, "version": 1}
+            }, "segments": {}}}"#,
+        )
+        .unwrap();
+        let user = context("user", &[]);
+        assert!(flags.read().unwrap()["beta"].is_enabled("beta", &user));
+
+        let patch = |version: u64| {
+            format!(
+                r#"{{"path": "/flags/beta", "data": {{"on": false, "offVariation": 1, "variations": [true, false], "version": {version}}}}}"#
+            )
+        };
+        apply(&flags, "patch", &patch(2)).unwrap();
+        assert!(!flags.read().unwrap()["beta"].is_enabled("beta", &user));
+        // A stale patch is ignored.
+        apply(&flags, "patch", &patch(1)).unwrap();
+        assert_eq!(2, flags.read().unwrap()["beta"].version);
+
+        apply(&flags, "delete", r#"{"path": "/flags/beta", "version": 3}"#).unwrap();
+        assert!(flags.read().unwrap().is_empty());
+    }
+
+    #[test]
+    fn flags_serve_targets_rules_then_rollouts() {
+        let flag: LdFlag = serde_json::from_str(
+            r#"{
+                "on": true,
+                "variations": [true, false],
+                "salt": "abc",
+                "targets": [{"values": ["tester"], "variation": 0}],
+                "rules": [{
+                    "clauses": [{"attribute": "email", "op": "endsWith", "values": ["@example.com"]}],
+                    "variation": 0
+                }],
+                "fallthrough": {"rollout": {"variations": [
+                    {"variation": 0, "weight": 25000},
+                    {"variation": 1, "weight": 75000}
+                ]}}
+            }"#,
+        )
+        .unwrap();
+        assert!(flag.is_enabled("beta", &context("tester", &[])));
+        assert!(flag.is_enabled("beta", &context("someone", &[("email", "a@example.com")])));
+
+        let enabled = (0..1000)
+            .filter(|i| flag.is_enabled("beta", &context(&format!("user-{i}"), &[])))
+            .count();
+        assert!((200..300).contains(&enabled), "{enabled} of 1000 enabled");
+    }
+}
@@ -0,0 +1,209 @@
+//! Feature flags, so that features can be rolled out and toggled without
+//! redeploying the application.
+//!
+//! Flags come from a [`FlagProvider`]: a flags file which is reloaded when
+//! it changes, a key-value store, or a LaunchDarkly-compatible streaming
+//! service. Each provider keeps the flags in memory, so that evaluating a
+//! flag doesn't wait on the backend.
+
+use std::{collections::HashMap, sync::Arc};
+
+use anyhow::Result;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use spin_core::{async_trait, HostComponent};
+use spin_world::v3::feature_flags as flags;
+
+mod file;
+mod key_value;
+mod launchdarkly;
+
+pub use file::FileFlags;
+pub use flags::Error;
+pub use key_value::KeyValueFlags;
+pub use launchdarkly::LaunchDarklyFlags;
+
+/// The subject a flag is evaluated for, such as a user.
+#[derive(Clone, Debug, Default)]
+pub struct Context {
+    pub key: String,
+    pub attributes: HashMap<String, String>,
+}
+
+/// A source of feature flags.
+#[async_trait]
+pub trait FlagProvider: Sync + Send {
+    /// Returns whether the flag `name` is enabled for `context`; flags the
+    /// provider doesn't know are disabled.
+    async fn is_enabled(&self, name: &str, context: &Context) -> Result<bool>;
+}
+
+/// Provides no flags, so that every flag is disabled.
+pub struct NoFlags;
+
+#[async_trait]
+impl FlagProvider for NoFlags {
+    async fn is_enabled(&self, _name: &str, _context: &Context) -> Result<bool> {
+        Ok(false)
+    }
+}
+
+/// A flag, as defined in a flags file or key-value store.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Flag {
+    /// Whether the flag is enabled at all.
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    /// Context keys the flag is always enabled for, if it is enabled.
+    #[serde(default)]
+    pub keys: Vec<String>,
+    /// Attribute -> values, one of which every context must have for the
+    /// flag to be enabled for it.
+    #[serde(default)]
+    pub attributes: HashMap<String, Vec<String>>,
+    /// The percentage of the remaining contexts the flag is enabled for.
+    #[serde(default)]
+    pub percent: Option<f64>,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+impl Flag {
+    /// Returns whether the flag `name`, defined as `self`, is enabled for
+    /// `context`.
+    pub fn evaluate(&self, name: &str, context: &Context) -> bool {
+        if !self.enabled {
+            return false;
+        }
+        if self.keys.contains(&context.key) {
+            return true;
+        }
+        let targeted = self.attributes.iter().all(|(attribute, values)| {
+            context
+                .attributes
+                .get(attribute)
+                .is_some_and(|value| values.contains(value))
+        });
+        match self.percent {
+            _ if !targeted => false,
+            Some(percent) => bucket(name, &context.key) * 100.0 < percent,
+            None => true,
+        }
+    }
+}
+
+/// Places a context key in `[0, 1)` for a flag, the same way every time and
+/// on every replica.
+fn bucket(name: &str, key: &str) -> f64 {
+    let hash = Sha256::digest(format!("{name}.{key}"));
+    let value = u64::from_be_bytes(hash[..8].try_into().unwrap());
+    (value >> 11) as f64 / (1u64 << 53) as f64
+}
+
+pub struct FlagsComponent {
+    provider: Arc<dyn FlagProvider>,
+}
+
+impl FlagsComponent {
+    /// A component whose flags come from the given provider.
+    pub fn new(provider: Arc<dyn FlagProvider>) -> Self {
+        Self { provider }
+    }
+}
+
+impl HostComponent for FlagsComponent {
+    type Data = FlagsDispatch;
+
+    fn add_to_linker<T: Send>(
+        linker: &mut spin_core::Linker<T>,
+        get: impl Fn(&mut spin_core::Data<T>) -> &mut Self::Data + Send + Sync + Copy + 'static,
+    ) -> anyhow::Result<()> {
+        flags::add_to_linker(linker, get)
+    }
+
+    fn build_data(&self) -> Self::Data {
+        FlagsDispatch {
+            provider: self.provider.clone(),
+        }
+    }
+}
+
+pub struct FlagsDispatch {
+    provider: Arc<dyn FlagProvider>,
+}
+
+#[async_trait]
+impl flags::Host for FlagsDispatch {
+    async fn is_enabled(
+        &mut self,
+        name: String,
+        context: flags::Context,
+    ) -> Result<Result<bool, Error>> {
+        let context = Context {
+            key: context.key,
+            attributes: context.attributes.into_iter().collect(),
+        };
+        Ok(self
+            .provider
+            .is_enabled(&name, &context)
+            .await
+            .map_err(log_error))
+    }
+}
+
+fn log_error(err: impl std::fmt::Debug) -> Error {
+    tracing::warn!("flags error: {err:?}");
+    Error::Other(format!("{err:?}"))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn context(key: &str, attributes: &[(&str, &str)]) -> Context {
+        Context {
+            key: key.into(),
+            attributes: attributes
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn flags_target_keys_and_attributes() {
+        let flag: Flag = toml::from_str(
+            r#"
+            keys = ["tester"]
+            attributes = { country = ["NZ", "AU"] }
+            "#,
+        )
+        .unwrap();
+        assert!(flag.evaluate("beta", &context("tester", &[])));
+        assert!(flag.evaluate("beta", &context("someone", &[("country", "NZ")])));
+        assert!(!flag.evaluate("beta", &context("someone", &[("country", "US")])));
+        assert!(!flag.evaluate("beta", &context("someone", &[])));
+
+        let disabled = Flag {
+            enabled: false,
+            ..flag
+        };
+        assert!(!disabled.evaluate("beta", &context("tester", &[])));
+    }
+
+    #[test]
+    fn rollouts_are_consistent_per_key() {
+        let flag: Flag = toml::from_str("percent = 25").unwrap();
+        let enabled = (0..1000)
+            .filter(|i| flag.evaluate("new-checkout", &context(&format!("user-{i}"), &[])))
+            .count();
+        assert!((200..300).contains(&enabled), "{enabled} of 1000 enabled");
+
+        let user = context("user-1", &[]);
+        let first = flag.evaluate("new-checkout", &user);
+        assert!((0..10).all(|_| flag.evaluate("new-checkout", &user) == first));
+    }
+}
@@ -0,0 +1,84 @@
+//! Client metadata, such as the country and network a client address is in,
+//! from MaxMind DB files such as the GeoLite2 databases. Looking these up on
+//! the host saves every component shipping its own copy of a database.
+
+use std::net::IpAddr;
+
+use anyhow::Result;
+
+mod mmdb;
+
+pub use mmdb::{Database, Value};
+
+/// Looks up client metadata in a country (or city) database and an ASN
+/// database, either of which may be absent.
+pub struct GeoIp {
+    country: Option<Database>,
+    asn: Option<Database>,
+}
+
+/// What is known about a client address.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ClientMetadata {
+    /// The ISO 3166-1 alpha-2 code of the country, e.g. "NZ".
+    pub country: Option<String>,
+    /// The number of the autonomous system.
+    pub asn: Option<u32>,
+    /// The organization the autonomous system belongs to.
+    pub as_org: Option<String>,
+}
+
+impl GeoIp {
+    pub fn new(country: Option<Database>, asn: Option<Database>) -> Self {
+        Self { country, asn }
+    }
+
+    /// Returns what the databases know about `ip`.
+    pub fn lookup(&self, ip: IpAddr) -> Result<ClientMetadata> {
+        let mut metadata = ClientMetadata::default();
+        if let Some(record) = lookup(&self.country, ip)? {
+            metadata.country = record
+                .get("country")
+                .or_else(|| record.get("registered_country"))
+                .and_then(|country| country.get("iso_code"))
+                .and_then(Value::as_str)
+                .map(Into::into);
+        }
+        if let Some(record) = lookup(&self.asn, ip)? {
+            metadata.asn = record
+                .get("autonomous_system_number")
+                .and_then(Value::as_u64)
+                .and_then(|asn| asn.try_into().ok());
+            metadata.as_org = record
+                .get("autonomous_system_organization")
+                .and_then(Value::as_str)
+                .map(Into::into);
+        }
+        Ok(metadata)
+    }
+}
+
+fn lookup(db: &Option<Database>, ip: IpAddr) -> Result<Option<Value>> {
+    match db {
+        Some(db) => db.lookup(ip),
+        None => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn metadata_is_looked_up_in_each_database() -> Result<()> {
+        let (data, offset) = mmdb::test::country_record("NZ");
+        let country = Database::new(mmdb::test::database([1, 2, 3, 0], 24, &data, offset))?;
+        let geoip = GeoIp::new(Some(country), None);
+
+        let metadata = geoip.lookup("1.2.3.4".parse()?)?;
+        assert_eq!(Some("NZ"), metadata.country.as_deref());
+        assert_eq!(None, metadata.asn);
+        assert_eq!(ClientMetadata::default(), geoip.lookup("5.6.7.8".parse()?)?);
+        Ok(())
+    }
+}
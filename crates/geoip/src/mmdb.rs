@@ -0,0 +1,503 @@
+//! A reader for the MaxMind DB format, as described at
+//! <https://maxmind.github.io/MaxMind-DB/>.
+//!
+//! Database files aren't trusted: every read is bounds-checked, and how
+//! deeply and how much a record decodes is limited, so that a malformed
+//! file is rejected rather than panicking or hanging a lookup.
+
+use std::{net::IpAddr, path::Path};
+
+use anyhow::{bail, ensure, Context, Result};
+
+const METADATA_MARKER: &[u8] = b"\xAB\xCD\xEFMaxMind.com";
+/// The zero bytes between the search tree and the data section.
+const DATA_SEPARATOR: usize = 16;
+/// How deeply values may nest, so that a malformed file can't exhaust the
+/// stack.
+const MAX_DEPTH: usize = 64;
+/// How many values one record may be decoded into, so that a malformed file
+/// whose pointers fan out can't make a lookup take forever.
+const MAX_VALUES: usize = 1 << 16;
+
+/// A MaxMind DB file, held in memory.
+pub struct Database {
+    data: Vec<u8>,
+    node_count: usize,
+    record_size: usize,
+    ipv6: bool,
+    // The node IPv4 lookups start at in an IPv6 tree: that of `::/96`.
+    ipv4_start: usize,
+    data_start: usize,
+    data_end: usize,
+}
+
+/// A value in a MaxMind DB data section.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    String(String),
+    Double(f64),
+    Bytes(Vec<u8>),
+    Uint(u128),
+    Int(i32),
+    Map(Vec<(String, Value)>),
+    Array(Vec<Value>),
+    Bool(bool),
+    Float(f32),
+}
+
+impl Value {
+    /// Returns the value of `key`, if this is a map which has it.
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        match self {
+            Self::Map(entries) => entries.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Self::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_u64(&self) -> Option<u64> {
+        match self {
+            Self::Uint(n) => (*n).try_into().ok(),
+            _ => None,
+        }
+    }
+}
+
+impl Database {
+    /// Reads the database at `path`.
+    pub fn open(path: &Path) -> Result<Self> {
+        let data = std::fs::read(path)
+            .with_context(|| format!("Failed to read GeoIP database {path:?}"))?;
+        Self::new(data).with_context(|| format!("Invalid GeoIP database {path:?}"))
+    }
+
+    /// Parses a database from its contents.
+    pub fn new(data: Vec<u8>) -> Result<Self> {
+        let marker = data
+            .windows(METADATA_MARKER.len())
+            .rposition(|window| window == METADATA_MARKER)
+            .context("not a MaxMind DB file: no metadata")?;
+        let metadata_start = marker + METADATA_MARKER.len();
+        let (metadata, _) = Decoder::new(&data[metadata_start..]).decode(0, 0)?;
+        let uint = |key: &str| {
+            metadata
+                .get(key)
+                .and_then(Value::as_u64)
+                .and_then(|n| usize::try_from(n).ok())
+                .with_context(|| format!("invalid metadata: no {key}"))
+        };
+        let node_count = uint("node_count")?;
+        let record_size = uint("record_size")?;
+        let ip_version = uint("ip_version")?;
+        ensure!(
+            matches!(record_size, 24 | 28 | 32),
+            "unsupported record size {record_size}"
+        );
+        ensure!(
+            matches!(ip_version, 4 | 6),
+            "unsupported IP version {ip_version}"
+        );
+        let data_start = node_count
+            .checked_mul(record_size / 4)
+            .and_then(|tree_size| tree_size.checked_add(DATA_SEPARATOR))
+            .filter(|data_start| *data_start <= marker)
+            .context("truncated search tree")?;
+
+        let mut db = Self {
+            data,
+            node_count,
+            record_size,
+            ipv6: ip_version == 6,
+            ipv4_start: 0,
+            data_start,
+            data_end: marker,
+        };
+        if db.ipv6 {
+            let mut node = 0;
+            for _ in 0..96 {
+                if node >= node_count {
+                    break;
+                }
+                node = db.record(node, 0)?;
+            }
+            db.ipv4_start = node;
+        }
+        Ok(db)
+    }
+
+    /// Returns the record for the network `ip` is in, if any.
+    pub fn lookup(&self, ip: IpAddr) -> Result<Option<Value>> {
+        let ip = match ip {
+            IpAddr::V6(v6) if !self.ipv6 => match v6.to_ipv4_mapped() {
+                Some(v4) => IpAddr::V4(v4),
+                None => return Ok(None),
+            },
+            ip => ip,
+        };
+        let (bits, len, mut node) = match ip {
+            IpAddr::V4(v4) => (u32::from(v4) as u128, 32, self.ipv4_start),
+            IpAddr::V6(v6) => (u128::from(v6), 128, 0),
+        };
+        for i in (0..len).rev() {
+            if node >= self.node_count {
+                break;
+            }
+            node = self.record(node, ((bits >> i) & 1) as usize)?;
+        }
+        if node <= self.node_count {
+            // Either the address isn't in the tree, or the tree is deeper
+            // than addresses are long.
+            return Ok(None);
+        }
+        let offset = (node - self.node_count)
+            .checked_sub(DATA_SEPARATOR)
+            .context("invalid search tree")?;
+        let (value, _) =
+            Decoder::new(&self.data[self.data_start..self.data_end]).decode(offset, 0)?;
+        Ok(Some(value))
+    }
+
+    /// Returns the left (`bit` 0) or right record of `node`.
+    fn record(&self, node: usize, bit: usize) -> Result<usize> {
+        let node_size = self.record_size / 4;
+        let start = node * node_size;
+        let b = self
+            .data
+            .get(start..start + node_size)
+            .context("truncated search tree")?;
+        Ok(match (self.record_size, bit) {
+            (24, 0) => be(&b[0..3]),
+            (24, _) => be(&b[3..6]),
+            (28, 0) => ((b[3] as usize & 0xF0) << 20) | be(&b[0..3]),
+            (28, _) => ((b[3] as usize & 0x0F) << 24) | be(&b[4..7]),
+            (_, 0) => be(&b[0..4]),
+            _ => be(&b[4..8]),
+        })
+    }
+}
+
+/// Decodes values from a data section, or the metadata.
+struct Decoder<'a> {
+    data: &'a [u8],
+    values: usize,
+}
+
+impl<'a> Decoder<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data, values: 0 }
+    }
+
+    /// Returns the value at `offset`, and the offset after it.
+    fn decode(&mut self, offset: usize, depth: usize) -> Result<(Value, usize)> {
+        ensure!(depth < MAX_DEPTH, "values are nested too deeply");
+        self.values += 1;
+        ensure!(self.values <= MAX_VALUES, "too many values in one record");
+        let ctrl = self.bytes(offset, 1)?[0];
+        let mut pos = offset + 1;
+        let mut kind = ctrl >> 5;
+
+        if kind == 1 {
+            let high = (ctrl & 0x07) as usize;
+            let (pointer, len) = match (ctrl >> 3) & 0x03 {
+                0 => ((high << 8) | be(self.bytes(pos, 1)?), 1),
+                1 => (((high << 16) | be(self.bytes(pos, 2)?)) + 2048, 2),
+                2 => (((high << 24) | be(self.bytes(pos, 3)?)) + 526336, 3),
+                _ => (be(self.bytes(pos, 4)?), 4),
+            };
+            let (value, _) = self.decode(pointer, depth + 1)?;
+            return Ok((value, pos + len));
+        }
+        if kind == 0 {
+            kind = self.bytes(pos, 1)?[0]
+                .checked_add(7)
+                .context("invalid data type")?;
+            pos += 1;
+        }
+        let mut size = (ctrl & 0x1F) as usize;
+        if size >= 29 {
+            let len = size - 28;
+            size = [29, 285, 65821][len - 1] + be(self.bytes(pos, len)?);
+            pos += len;
+        }
+
+        let value = match kind {
+            2 => {
+                let s = std::str::from_utf8(self.bytes(pos, size)?).context("invalid string")?;
+                pos += size;
+                Value::String(s.to_owned())
+            }
+            3 => {
+                ensure!(size == 8, "invalid double size {size}");
+                let bytes = self.bytes(pos, 8)?;
+                pos += 8;
+                Value::Double(f64::from_be_bytes(bytes.try_into().unwrap()))
+            }
+            4 => {
+                let bytes = self.bytes(pos, size)?;
+                pos += size;
+                Value::Bytes(bytes.to_vec())
+            }
+            5 | 6 | 9 | 10 => {
+                ensure!(size <= 16, "invalid integer size {size}");
+                let bytes = self.bytes(pos, size)?;
+                pos += size;
+                Value::Uint(bytes.iter().fold(0, |n, b| (n << 8) | *b as u128))
+            }
+            7 => {
+                let mut entries = Vec::with_capacity(size.min(1024));
+                for _ in 0..size {
+                    let (key, next) = self.decode(pos, depth + 1)?;
+                    let Value::String(key) = key else {
+                        bail!("invalid map key {key:?}");
+                    };
+                    let (value, next) = self.decode(next, depth + 1)?;
+                    entries.push((key, value));
+                    pos = next;
+                }
+                Value::Map(entries)
+            }
+            8 => {
+                ensure!(size <= 4, "invalid integer size {size}");
+                let bytes = self.bytes(pos, size)?;
+                pos += size;
+                Value::Int(be(bytes) as u32 as i32)
+            }
+            11 => {
+                let mut items = Vec::with_capacity(size.min(1024));
+                for _ in 0..size {
+                    let (item, next) = self.decode(pos, depth + 1)?;
+                    items.push(item);
+                    pos = next;
+                }
+                Value::Array(items)
+            }
+            14 => Value::Bool(size != 0),
+            15 => {
+                ensure!(size == 4, "invalid float size {size}");
+                let bytes = self.bytes(pos, 4)?;
+                pos += 4;
+                Value::Float(f32::from_be_bytes(bytes.try_into().unwrap()))
+            }
+            _ => bail!("unsupported data type {kind}"),
+        };
+        Ok((value, pos))
+    }
+
+    fn bytes(&self, pos: usize, len: usize) -> Result<&'a [u8]> {
+        self.data
+            .get(pos..pos.saturating_add(len))
+            .context("truncated data section")
+    }
+}
+
+/// Reads a big-endian unsigned integer of up to `usize` bytes.
+fn be(bytes: &[u8]) -> usize {
+    bytes.iter().fold(0, |n, b| (n << 8) | *b as usize)
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::*;
+
+    fn ctrl(kind: u8, size: usize) -> Vec<u8> {
+        assert!(size < 29);
+        match kind {
+            1..=7 => vec![(kind << 5) | size as u8],
+            _ => vec![size as u8, kind - 7],
+        }
+    }
+
+    fn string(s: &str) -> Vec<u8> {
+        [ctrl(2, s.len()), s.as_bytes().to_vec()].concat()
+    }
+
+    fn uint(n: u32) -> Vec<u8> {
+        [ctrl(6, 4), n.to_be_bytes().to_vec()].concat()
+    }
+
+    fn map(entries: &[(&str, Vec<u8>)]) -> Vec<u8> {
+        let mut map = ctrl(7, entries.len());
+        for (key, value) in entries {
+            map.extend(string(key));
+            map.extend(value);
+        }
+        map
+    }
+
+    /// Builds an IPv4 database with a single network, whose record is at
+    /// `offset` in `data`.
+    pub(crate) fn database(
+        network: [u8; 4],
+        prefix_len: usize,
+        data: &[u8],
+        offset: usize,
+    ) -> Vec<u8> {
+        let network = u32::from_be_bytes(network);
+        let node_count = prefix_len;
+        let mut file = vec![];
+        for node in 0..node_count {
+            let next = if node + 1 == node_count {
+                node_count + DATA_SEPARATOR + offset
+            } else {
+                node + 1
+            };
+            let (left, right) = match (network >> (31 - node)) & 1 {
+                0 => (next, node_count),
+                _ => (node_count, next),
+            };
+            file.extend(&(left as u32).to_be_bytes()[1..]);
+            file.extend(&(right as u32).to_be_bytes()[1..]);
+        }
+        file.extend([0; DATA_SEPARATOR]);
+        file.extend(data);
+        file.extend(METADATA_MARKER);
+        file.extend(map(&[
+            ("node_count", uint(node_count as u32)),
+            ("record_size", [ctrl(5, 1), vec![24]].concat()),
+            ("ip_version", [ctrl(5, 1), vec![4]].concat()),
+            ("description", map(&[("en", string("Test"))])),
+        ]));
+        file
+    }
+
+    /// A record whose country is `iso_code`, which is at offset 0 and
+    /// pointed to from the record at the returned offset.
+    pub(crate) fn country_record(iso_code: &str) -> (Vec<u8>, usize) {
+        let mut data = string(iso_code);
+        let offset = data.len();
+        data.extend(map(&[
+            ("country", map(&[("iso_code", vec![0x20, 0x00])])),
+            ("is_anycast", ctrl(14, 1)),
+        ]));
+        (data, offset)
+    }
+
+    #[test]
+    fn networks_are_looked_up() -> Result<()> {
+        let (data, offset) = country_record("NZ");
+        let db = Database::new(database([1, 2, 3, 0], 24, &data, offset))?;
+
+        let record = db.lookup("1.2.3.4".parse()?)?.unwrap();
+        let iso_code = record.get("country").and_then(|c| c.get("iso_code"));
+        assert_eq!(Some("NZ"), iso_code.and_then(Value::as_str));
+        assert_eq!(Some(&Value::Bool(true)), record.get("is_anycast"));
+        assert_eq!(Some(record), db.lookup("::ffff:1.2.3.255".parse()?)?);
+
+        assert_eq!(None, db.lookup("1.2.4.1".parse()?)?);
+        assert_eq!(None, db.lookup("::1".parse()?)?);
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_databases_are_rejected() {
+        assert!(Database::new(b"not a database".to_vec()).is_err());
+
+        let (data, offset) = country_record("NZ");
+        let mut file = database([1, 2, 3, 0], 24, &data, offset);
+        file.drain(..100);
+        assert!(Database::new(file).is_err());
+    }
+
+    /// Opens `file` and looks up a few addresses in it. A malformed file may
+    /// be rejected, or answer lookups wrongly, but mustn't panic or hang.
+    fn open_and_look_up(file: Vec<u8>) {
+        if let Ok(db) = Database::new(file) {
+            for ip in ["1.2.3.4", "1.2.4.1", "::1", "::ffff:1.2.3.4"] {
+                let _ = db.lookup(ip.parse().unwrap());
+            }
+        }
+    }
+
+    #[test]
+    fn truncated_databases_are_handled() {
+        let (data, offset) = country_record("NZ");
+        let file = database([1, 2, 3, 0], 24, &data, offset);
+        for len in 0..file.len() {
+            open_and_look_up(file[..len].to_vec());
+        }
+    }
+
+    #[test]
+    fn corrupted_databases_are_handled() {
+        let (data, offset) = country_record("NZ");
+        let file = database([1, 2, 3, 0], 24, &data, offset);
+        // A fixed xorshift sequence, so that a failure can be reproduced.
+        let mut state = 0x2545_f491_4f6c_dd1d_u64;
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state as usize
+        };
+        for _ in 0..2_000 {
+            let mut file = file.clone();
+            for _ in 0..=next() % 4 {
+                let i = next() % file.len();
+                file[i] = next() as u8;
+            }
+            open_and_look_up(file);
+        }
+    }
+
+    #[test]
+    fn pointer_loops_are_rejected() -> Result<()> {
+        // A record which points to itself.
+        let db = Database::new(database([1, 2, 3, 0], 24, &[0x20, 0x00], 0))?;
+        assert!(db.lookup("1.2.3.4".parse()?).is_err());
+
+        // An array of two pointers to itself, which would decode into 2^32
+        // values before reaching the depth limit.
+        let data = [ctrl(11, 2), vec![0x20, 0x00, 0x20, 0x00]].concat();
+        let db = Database::new(database([1, 2, 3, 0], 24, &data, 0))?;
+        assert!(db.lookup("1.2.3.4".parse()?).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn oversized_values_are_rejected() -> Result<()> {
+        let rejected = |data: Vec<u8>| -> Result<bool> {
+            let db = Database::new(database([1, 2, 3, 0], 24, &data, 0))?;
+            Ok(db.lookup("1.2.3.4".parse()?).is_err())
+        };
+        // A string, and a map, longer than the data section.
+        assert!(rejected(
+            [vec![(2 << 5) | 31, 0xFF, 0xFF, 0xFF], string("NZ")].concat()
+        )?);
+        assert!(rejected(vec![(7 << 5) | 31, 0xFF, 0xFF, 0xFF])?);
+        // Numbers wider than their type.
+        assert!(rejected([ctrl(8, 5), vec![0; 5]].concat())?);
+        assert!(rejected([ctrl(3, 9), vec![0; 9]].concat())?);
+        assert!(rejected([ctrl(10, 17), vec![0; 17]].concat())?);
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_metadata_is_rejected() {
+        let file = |entries: &[(&str, Vec<u8>)]| {
+            [vec![0; 32], METADATA_MARKER.to_vec(), map(entries)].concat()
+        };
+        let small = |n: u8| [ctrl(5, 1), vec![n]].concat();
+        let metadata = |node_count: u32, record_size: u8, ip_version: u8| {
+            file(&[
+                ("node_count", uint(node_count)),
+                ("record_size", small(record_size)),
+                ("ip_version", small(ip_version)),
+            ])
+        };
+
+        assert!(Database::new(metadata(1, 24, 4)).is_ok());
+        assert!(Database::new(metadata(1, 20, 4)).is_err());
+        assert!(Database::new(metadata(1, 24, 5)).is_err());
+        // A search tree longer than the file.
+        assert!(Database::new(metadata(3, 24, 4)).is_err());
+        assert!(Database::new(metadata(u32::MAX, 32, 6)).is_err());
+        assert!(Database::new(file(&[("node_count", uint(1))])).is_err());
+        assert!(Database::new([METADATA_MARKER.to_vec(), string("x")].concat()).is_err());
+    }
+}
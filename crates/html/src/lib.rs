@@ -0,0 +1,181 @@
+//! CommonMark rendering and HTML sanitization by the host, so that content
+//! components needn't embed a Markdown parser and an HTML5 parser.
+
+use std::collections::{HashMap, HashSet};
+
+use anyhow::Result;
+use pulldown_cmark::{Options, Parser};
+use spin_core::{async_trait, HostComponent};
+use spin_world::v3::html;
+
+pub use html::{Error, MarkdownOptions, Policy};
+
+#[derive(Default)]
+pub struct HtmlComponent;
+
+impl HostComponent for HtmlComponent {
+    type Data = HtmlDispatch;
+
+    fn add_to_linker<T: Send>(
+        linker: &mut spin_core::Linker<T>,
+        get: impl Fn(&mut spin_core::Data<T>) -> &mut Self::Data + Send + Sync + Copy + 'static,
+    ) -> anyhow::Result<()> {
+        html::add_to_linker(linker, get)
+    }
+
+    fn build_data(&self) -> Self::Data {
+        HtmlDispatch
+    }
+}
+
+pub struct HtmlDispatch;
+
+#[async_trait]
+impl html::Host for HtmlDispatch {
+    async fn render_markdown(
+        &mut self,
+        markdown: String,
+        options: MarkdownOptions,
+    ) -> Result<String> {
+        Ok(render_markdown(&markdown, options))
+    }
+
+    async fn sanitize(
+        &mut self,
+        html: String,
+        policy: Option<Policy>,
+    ) -> Result<Result<String, Error>> {
+        Ok(sanitize(&html, policy.as_ref()))
+    }
+}
+
+/// Renders the CommonMark document `markdown` as HTML.
+pub fn render_markdown(markdown: &str, options: MarkdownOptions) -> String {
+    let mut extensions = Options::empty();
+    extensions.set(Options::ENABLE_TABLES, options.tables);
+    extensions.set(Options::ENABLE_STRIKETHROUGH, options.strikethrough);
+    extensions.set(Options::ENABLE_TASKLISTS, options.task_lists);
+    extensions.set(Options::ENABLE_FOOTNOTES, options.footnotes);
+    extensions.set(Options::ENABLE_SMART_PUNCTUATION, options.smart_punctuation);
+    let mut html = String::with_capacity(markdown.len() * 3 / 2);
+    pulldown_cmark::html::push_html(&mut html, Parser::new_ext(markdown, extensions));
+    html
+}
+
+/// Removes what `policy`, or the default policy, doesn't allow from `html`.
+pub fn sanitize(html: &str, policy: Option<&Policy>) -> Result<String, Error> {
+    let Some(policy) = policy else {
+        return Ok(ammonia::clean(html));
+    };
+    let tags: HashSet<&str> = policy.tags.iter().map(String::as_str).collect();
+    let generic_attributes: HashSet<&str> = policy
+        .generic_attributes
+        .iter()
+        .map(String::as_str)
+        .collect();
+    let mut tag_attributes: HashMap<&str, HashSet<&str>> = HashMap::new();
+    for (tag, attribute) in &policy.tag_attributes {
+        tag_attributes
+            .entry(tag.as_str())
+            .or_default()
+            .insert(attribute.as_str());
+    }
+    // Ammonia panics on contradictory policies, so they are rejected here.
+    if policy.link_rel.is_some()
+        && (generic_attributes.contains("rel")
+            || tag_attributes.get("a").is_some_and(|a| a.contains("rel")))
+    {
+        return Err(Error::InvalidPolicy(
+            "links' `rel` attribute is both allowed and set by `link-rel`".into(),
+        ));
+    }
+    // Elements whose content is removed with them, unless they are allowed.
+    let clean_content_tags = ["script", "style"]
+        .into_iter()
+        .filter(|tag| !tags.contains(tag))
+        .collect();
+
+    Ok(ammonia::Builder::empty()
+        .tags(tags)
+        .clean_content_tags(clean_content_tags)
+        .generic_attributes(generic_attributes)
+        .tag_attributes(tag_attributes)
+        .url_schemes(policy.url_schemes.iter().map(String::as_str).collect())
+        .link_rel(policy.link_rel.as_deref())
+        .clean(html)
+        .to_string())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn options() -> MarkdownOptions {
+        MarkdownOptions {
+            tables: false,
+            strikethrough: false,
+            task_lists: false,
+            footnotes: false,
+            smart_punctuation: false,
+        }
+    }
+
+    #[test]
+    fn markdown_is_rendered() {
+        assert_eq!(
+            "<h1>Fish &amp; chips</h1>\n<p><em>Hot</em> <a href=\"/menu\">menu</a></p>\n",
+            render_markdown("# Fish & chips\n*Hot* [menu](/menu)", options())
+        );
+        let table = "| a |\n|---|\n| 1 |";
+        assert!(!render_markdown(table, options()).contains("<table>"));
+        let with_tables = MarkdownOptions {
+            tables: true,
+            ..options()
+        };
+        assert!(render_markdown(table, with_tables).contains("<table>"));
+        assert_eq!("<p>~~gone~~</p>\n", render_markdown("~~gone~~", options()));
+        let with_strikethrough = MarkdownOptions {
+            strikethrough: true,
+            ..options()
+        };
+        assert_eq!(
+            "<p><del>gone</del></p>\n",
+            render_markdown("~~gone~~", with_strikethrough)
+        );
+    }
+
+    #[test]
+    fn html_is_sanitized_by_default() {
+        let html = r#"<p onclick="steal()">Hi<script>steal()</script> <a href="javascript:steal()">there</a> <a href="https://example.com">friend</a></p>"#;
+        assert_eq!(
+            r#"<p>Hi <a rel="noopener noreferrer">there</a> <a href="https://example.com" rel="noopener noreferrer">friend</a></p>"#,
+            sanitize(html, None).unwrap()
+        );
+    }
+
+    #[test]
+    fn html_is_sanitized_with_policy() {
+        let policy = Policy {
+            tags: vec!["p".into(), "a".into()],
+            generic_attributes: vec!["title".into()],
+            tag_attributes: vec![("a".into(), "href".into())],
+            url_schemes: vec!["mailto".into()],
+            link_rel: None,
+        };
+        let html = r#"<p title="t" class="c"><b>Mail</b> <a href="mailto:me@example.com">me</a> <a href="https://example.com">or not</a><style>p {}</style></p>"#;
+        assert_eq!(
+            r#"<p title="t">Mail <a href="mailto:me@example.com">me</a> <a>or not</a></p>"#,
+            sanitize(html, Some(&policy)).unwrap()
+        );
+
+        let contradictory = Policy {
+            link_rel: Some("nofollow".into()),
+            tag_attributes: vec![("a".into(), "rel".into())],
+            ..policy
+        };
+        assert!(matches!(
+            sanitize(html, Some(&contradictory)),
+            Err(Error::InvalidPolicy(_))
+        ));
+    }
+}
@@ -1,4 +1,6 @@
-use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use serde::{de::Error as _, Deserialize, Deserializer, Serialize};
 
 /// Configuration for the HTTP trigger
 #[derive(Clone, Debug, Default, Deserialize, Serialize)]
@@ -11,6 +13,131 @@ pub struct HttpTriggerConfig {
     /// The HTTP executor the component requires
     #[serde(default)]
     pub executor: Option<HttpExecutorType>,
+    /// Headers to set on every response for this route, replacing any of
+    /// the same name set by the component
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub response_headers: BTreeMap<String, String>,
+    /// A preset of security headers to add to responses for this route which
+    /// do not already have them
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub security_headers: Option<SecurityHeaders>,
+    /// A JSON Schema which request bodies for this route must match before
+    /// the component is invoked
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request_schema: Option<serde_json::Value>,
+    /// Cache responses for this route in the host, so that repeated requests
+    /// are served without invoking the component
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub response_cache: Option<ResponseCacheConfig>,
+    /// Limit how many requests for this route the component handles at once,
+    /// queuing or rejecting the rest
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub concurrency: Option<ConcurrencyConfig>,
+    /// Other components, such as new versions being rolled out, which serve
+    /// a share of the requests for this route
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub canary: Vec<CanaryConfig>,
+    /// The longest the component may take to handle a request for this
+    /// route, in seconds, after which it is stopped
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<u64>,
+}
+
+/// A component which serves a share of a route's requests instead of the
+/// route's own component.
+///
+/// In the manifest, e.g. `canary = [{ component = "api-v2", weight = 10 }]`.
+/// Each request is sent to a canary with a probability of its `weight` as a
+/// percentage; the rest are served by the route's `component`.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct CanaryConfig {
+    /// Component ID to invoke
+    pub component: String,
+    /// The percentage of requests the component serves
+    pub weight: u32,
+}
+
+/// How many requests to a route are handled at once.
+///
+/// In the manifest, e.g. `concurrency = { max_concurrent = 10, max_queued = 100 }`.
+/// Requests beyond `max_concurrent` wait for one in progress to complete;
+/// once `max_queued` are waiting, further requests are rejected with a 503.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConcurrencyConfig {
+    /// The most requests the component handles at once
+    pub max_concurrent: usize,
+    /// The most requests waiting to be handled
+    #[serde(default)]
+    pub max_queued: usize,
+}
+
+/// How responses to a route are cached.
+///
+/// In the manifest, e.g. `response_cache = { max_age = 60, vary = ["accept-language"] }`.
+/// A component may also give or override these per response with an
+/// `x-spin-cache` header, e.g. `x-spin-cache: max-age=60, key=path`.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct ResponseCacheConfig {
+    /// How long a response is served from the cache, in seconds
+    pub max_age: u64,
+    /// What identifies a request, beyond its method and the `vary` headers
+    #[serde(default)]
+    pub key: ResponseCacheKey,
+    /// Request headers whose values are part of the cache key
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub vary: Vec<String>,
+}
+
+/// The part of a request's URI which identifies it in a response cache.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ResponseCacheKey {
+    /// The path and query string
+    #[default]
+    Uri,
+    /// The path alone, so that requests differing only in their query string
+    /// share a response
+    Path,
+}
+
+/// A preset of security-related response headers.
+///
+/// In the manifest, e.g. `security_headers = "strict"`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SecurityHeaders {
+    /// Headers suitable for an application served only over HTTPS which is
+    /// not framed by other sites and loads resources only from its own origin.
+    Strict,
+}
+
+impl SecurityHeaders {
+    /// The names and values of the preset's headers.
+    pub fn headers(&self) -> &'static [(&'static str, &'static str)] {
+        match self {
+            Self::Strict => &[
+                (
+                    "strict-transport-security",
+                    "max-age=63072000; includeSubDomains",
+                ),
+                ("x-content-type-options", "nosniff"),
+                ("x-frame-options", "DENY"),
+                (
+                    "content-security-policy",
+                    "default-src 'self'; object-src 'none'; base-uri 'self'; frame-ancestors 'none'",
+                ),
+                ("referrer-policy", "strict-origin-when-cross-origin"),
+                ("cross-origin-opener-policy", "same-origin"),
+                (
+                    "permissions-policy",
+                    "camera=(), geolocation=(), microphone=()",
+                ),
+            ],
+        }
+    }
 }
 
 /// The executor for the HTTP component.
@@ -18,7 +145,11 @@ pub struct HttpTriggerConfig {
 /// the `wasi-http` interface, or the Wagi CGI interface.
 ///
 /// If an executor is not specified, the inferred default is `HttpExecutor::Spin`.
-#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+///
+/// In the manifest, an executor may be given either as a table, e.g.
+/// `executor = { type = "wagi", entrypoint = "main" }`, or by name alone,
+/// e.g. `executor = "wagi"`, in which case the defaults are used.
+#[derive(Clone, Debug, Default, Serialize)]
 #[serde(deny_unknown_fields, rename_all = "lowercase", tag = "type")]
 pub enum HttpExecutorType {
     /// The component implements an HTTP based interface.
@@ -28,9 +159,43 @@ pub enum HttpExecutorType {
     #[serde(alias = "spin")]
     Http,
     /// The component implements the Wagi CGI interface.
+    ///
+    /// It may be a core module, whose `entrypoint` is called with WASI
+    /// Preview 1, or a WASI CLI command component, whose `wasi:cli/run` is
+    /// called with WASI Preview 2. Either way it reads the request from its
+    /// arguments, environment and stdin, and writes the response to stdout.
     Wagi(WagiTriggerConfig),
 }
 
+impl<'de> Deserialize<'de> for HttpExecutorType {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(deny_unknown_fields, rename_all = "lowercase", tag = "type")]
+        enum Tagged {
+            #[serde(alias = "spin")]
+            Http,
+            Wagi(WagiTriggerConfig),
+        }
+
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Repr {
+            Name(String),
+            Tagged(Tagged),
+        }
+
+        match Repr::deserialize(deserializer)? {
+            Repr::Name(name) => match name.as_str() {
+                "http" | "spin" => Ok(Self::Http),
+                "wagi" => Ok(Self::Wagi(Default::default())),
+                other => Err(D::Error::unknown_variant(other, &["http", "spin", "wagi"])),
+            },
+            Repr::Tagged(Tagged::Http) => Ok(Self::Http),
+            Repr::Tagged(Tagged::Wagi(config)) => Ok(Self::Wagi(config)),
+        }
+    }
+}
+
 /// Wagi specific configuration for the http executor.
 #[derive(Clone, Debug, Deserialize, Serialize)]
 #[serde(default, deny_unknown_fields)]
@@ -76,4 +241,81 @@ mod tests {
         assert_eq!(config.entrypoint, "_start");
         assert_eq!(config.argv, "${SCRIPT_NAME} ${ARGS}");
     }
+
+    #[test]
+    fn executor_can_be_given_by_name() {
+        let config: HttpTriggerConfig = toml::toml! {
+            component = "cgi"
+            route = "/..."
+            executor = "wagi"
+        }
+        .try_into()
+        .unwrap();
+        let Some(HttpExecutorType::Wagi(config)) = config.executor else {
+            panic!("wrong type");
+        };
+        assert_eq!(config.entrypoint, "_start");
+
+        let executor: HttpExecutorType = toml::Value::from("spin").try_into().unwrap();
+        assert!(matches!(executor, HttpExecutorType::Http));
+
+        toml::Value::from("cgi")
+            .try_into::<HttpExecutorType>()
+            .unwrap_err();
+    }
+
+    #[test]
+    fn response_headers_and_preset_can_be_given() {
+        let config: HttpTriggerConfig = toml::toml! {
+            component = "site"
+            route = "/..."
+            security_headers = "strict"
+            response_headers = { "cache-control" = "no-store" }
+        }
+        .try_into()
+        .unwrap();
+        assert_eq!(Some(SecurityHeaders::Strict), config.security_headers);
+        assert_eq!("no-store", config.response_headers["cache-control"]);
+
+        toml::toml! {
+            component = "site"
+            route = "/..."
+            security_headers = "lax"
+        }
+        .try_into::<HttpTriggerConfig>()
+        .unwrap_err();
+    }
+
+    #[test]
+    fn request_schema_can_be_given_inline() {
+        let config: HttpTriggerConfig = toml::toml! {
+            component = "orders"
+            route = "/orders"
+            request_schema = { type = "object", required = ["sku"] }
+        }
+        .try_into()
+        .unwrap();
+        assert_eq!(
+            Some(serde_json::json!({ "type": "object", "required": ["sku"] })),
+            config.request_schema
+        );
+    }
+
+    #[test]
+    fn concurrency_can_be_limited() {
+        let config: HttpTriggerConfig = toml::toml! {
+            component = "orders"
+            route = "/orders"
+            concurrency = { max_concurrent = 10 }
+        }
+        .try_into()
+        .unwrap();
+        assert_eq!(
+            Some(ConcurrencyConfig {
+                max_concurrent: 10,
+                max_queued: 0
+            }),
+            config.concurrency
+        );
+    }
 }
@@ -5,9 +5,13 @@
 use anyhow::{anyhow, Result};
 use http::Uri;
 use indexmap::IndexMap;
-use std::{borrow::Cow, fmt};
+use std::{borrow::Cow, cmp::Reverse, fmt};
 
 /// Router for the HTTP trigger.
+///
+/// When more than one route matches a path, an exact route takes precedence
+/// over wildcard routes, and otherwise the wildcard with the longest prefix
+/// is used. The order in which routes are declared doesn't matter.
 #[derive(Clone, Debug)]
 pub struct Router {
     /// Ordered map between a path and the component ID that should handle it.
@@ -56,6 +60,38 @@ impl Router {
         self.routes.iter()
     }
 
+    /// Returns the routes in order of precedence: exact routes, then wildcard
+    /// routes from the longest prefix to the shortest. Each path is handled
+    /// by the first of these routes which matches it.
+    pub fn routes_by_precedence(&self) -> Vec<(&RoutePattern, &String)> {
+        let mut routes: Vec<_> = self.routes.iter().collect();
+        routes.sort_by_key(|&(route, _)| {
+            let is_wildcard = matches!(route, RoutePattern::Wildcard(_));
+            let path = route.path_or_prefix();
+            (is_wildcard, Reverse(path.len()), path)
+        });
+        routes
+    }
+
+    /// Returns the routes which take precedence over `route` for some of the
+    /// paths it matches, such as an exact route inside a wildcard route.
+    pub fn overriding<'a>(&'a self, route: &RoutePattern) -> Vec<(&'a RoutePattern, &'a String)> {
+        let RoutePattern::Wildcard(prefix) = route else {
+            return vec![];
+        };
+        self.routes_by_precedence()
+            .into_iter()
+            .filter(|(other, _)| {
+                *other != route
+                    && route.matches(other.path_or_prefix())
+                    && match other {
+                        RoutePattern::Exact(_) => true,
+                        RoutePattern::Wildcard(other) => other.len() > prefix.len(),
+                    }
+            })
+            .collect()
+    }
+
     /// This returns the component id and route pattern for a matched route.
     pub fn route_full(&self, p: &str) -> Result<(&str, &RoutePattern)> {
         let matches = self.routes.iter().filter(|(rp, _)| rp.matches(p));
@@ -482,4 +518,60 @@ mod route_tests {
         assert_eq!("first /foo", duplicates[0].replaced_id);
         assert_eq!("second /foo", duplicates[0].effective_id);
     }
+
+    #[test]
+    fn routes_by_precedence_match_routing() {
+        let (router, _) = Router::build(
+            "/",
+            vec![
+                ("fallback", "/..."),
+                ("api", "/api/..."),
+                ("users", "/api/users"),
+                ("admin", "/api/admin/..."),
+            ],
+        )
+        .unwrap();
+
+        let ordered: Vec<_> = router
+            .routes_by_precedence()
+            .into_iter()
+            .map(|(_, id)| id.as_str())
+            .collect();
+        assert_eq!(vec!["users", "admin", "api", "fallback"], ordered);
+
+        for path in ["/api/users", "/api/admin/x", "/api/other", "/other"] {
+            let (first, _) = router
+                .routes_by_precedence()
+                .into_iter()
+                .find(|(route, _)| route.matches(path))
+                .unwrap();
+            assert_eq!(router.route(path).unwrap(), first.as_str());
+        }
+    }
+
+    #[test]
+    fn overriding_routes_are_those_inside_a_wildcard() {
+        let (router, _) = Router::build(
+            "/",
+            vec![
+                ("fallback", "/..."),
+                ("api", "/api/..."),
+                ("users", "/api/users"),
+                ("static", "/static/..."),
+            ],
+        )
+        .unwrap();
+
+        let overriding = |route: &str| -> Vec<String> {
+            router
+                .overriding(&RoutePattern::from("/", route))
+                .into_iter()
+                .map(|(_, id)| id.clone())
+                .collect()
+        };
+        assert_eq!(vec!["users"], overriding("/api/..."));
+        assert_eq!(vec!["users", "api", "static"], overriding("/..."));
+        assert!(overriding("/api/users").is_empty());
+        assert!(overriding("/static/...").is_empty());
+    }
 }
@@ -12,6 +12,10 @@ pub struct Metadata {
     // The based url
     #[serde(default = "default_base")]
     pub base: String,
+    // Hosts whose requests are routed as though their paths began with the
+    // first label of the host, e.g. `hello.example.com/x` as `/hello/x`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub hosts: Vec<String>,
 }
 
 pub fn default_base() -> String {
@@ -0,0 +1,133 @@
+//! Image processing on the host, as decoding, resampling and re-encoding
+//! images inside a component is slow and bloats it with codecs.
+//!
+//! Only components granted the `image` capability in the manifest may use
+//! it, as processing large images takes a lot of host CPU and memory. For the
+//! same reason, images are processed on blocking threads, as many at once as
+//! there are CPUs.
+
+use std::sync::Arc;
+
+use anyhow::Result;
+use spin_app::{AppComponent, DynamicHostComponent, CAPABILITIES_KEY};
+use spin_core::{async_trait, HostComponent};
+use spin_world::v3::image;
+use tokio::sync::Semaphore;
+
+mod process;
+
+pub use image::{Error, Fit, Format, ImageInfo};
+
+/// The capability components must be granted to use images.
+pub const IMAGE_CAPABILITY: &str = "image";
+
+pub struct ImageComponent {
+    permits: Arc<Semaphore>,
+}
+
+impl ImageComponent {
+    pub fn new() -> Self {
+        let cpus = std::thread::available_parallelism().map_or(1, |n| n.get());
+        Self {
+            permits: Arc::new(Semaphore::new(cpus)),
+        }
+    }
+}
+
+impl Default for ImageComponent {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl HostComponent for ImageComponent {
+    type Data = ImageDispatch;
+
+    fn add_to_linker<T: Send>(
+        linker: &mut spin_core::Linker<T>,
+        get: impl Fn(&mut spin_core::Data<T>) -> &mut Self::Data + Send + Sync + Copy + 'static,
+    ) -> anyhow::Result<()> {
+        image::add_to_linker(linker, get)
+    }
+
+    fn build_data(&self) -> Self::Data {
+        ImageDispatch {
+            allowed: false,
+            permits: self.permits.clone(),
+        }
+    }
+}
+
+impl DynamicHostComponent for ImageComponent {
+    fn update_data(&self, data: &mut Self::Data, component: &AppComponent) -> anyhow::Result<()> {
+        let capabilities = component
+            .get_metadata(CAPABILITIES_KEY)?
+            .unwrap_or_default();
+        data.allowed = capabilities.iter().any(|c| c == IMAGE_CAPABILITY);
+        Ok(())
+    }
+}
+
+pub struct ImageDispatch {
+    allowed: bool,
+    permits: Arc<Semaphore>,
+}
+
+impl ImageDispatch {
+    /// Runs `f` on a blocking thread once one of the processing permits is
+    /// free, if the component may use images.
+    async fn run<T: Send + 'static>(
+        &self,
+        f: impl FnOnce() -> Result<T, Error> + Send + 'static,
+    ) -> Result<Result<T, Error>> {
+        if !self.allowed {
+            return Ok(Err(Error::AccessDenied));
+        }
+        let _permit = self.permits.acquire().await?;
+        Ok(tokio::task::spawn_blocking(f).await?)
+    }
+}
+
+#[async_trait]
+impl image::Host for ImageDispatch {
+    async fn info(&mut self, image: Vec<u8>) -> Result<Result<ImageInfo, Error>> {
+        self.run(move || process::info(&image)).await
+    }
+
+    async fn resize(
+        &mut self,
+        image: Vec<u8>,
+        width: u32,
+        height: u32,
+        fit: Fit,
+    ) -> Result<Result<Vec<u8>, Error>> {
+        self.run(move || process::resize(&image, width, height, fit))
+            .await
+    }
+
+    async fn crop(
+        &mut self,
+        image: Vec<u8>,
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+    ) -> Result<Result<Vec<u8>, Error>> {
+        self.run(move || process::crop(&image, x, y, width, height))
+            .await
+    }
+
+    async fn convert(
+        &mut self,
+        image: Vec<u8>,
+        format: Format,
+        quality: Option<u8>,
+    ) -> Result<Result<Vec<u8>, Error>> {
+        self.run(move || process::convert(&image, format, quality))
+            .await
+    }
+
+    async fn strip_metadata(&mut self, image: Vec<u8>) -> Result<Result<Vec<u8>, Error>> {
+        self.run(move || process::strip_metadata(&image)).await
+    }
+}
@@ -0,0 +1,338 @@
+use std::{io::Cursor, num::NonZeroU32};
+
+use fast_image_resize as fr;
+use image::{
+    io::{Limits, Reader},
+    DynamicImage, ImageError, ImageFormat, ImageOutputFormat, RgbaImage,
+};
+
+use crate::{Error, Fit, Format, ImageInfo};
+
+/// The widest or tallest image which is read or produced.
+const MAX_DIMENSION: u32 = 16384;
+/// The most pixels a resized image may have, so that it takes at most
+/// 128 MiB as RGBA.
+const MAX_PIXELS: u64 = 32 * 1024 * 1024;
+/// The most memory decoding an image may allocate.
+const MAX_ALLOC: u64 = 256 * 1024 * 1024;
+const DEFAULT_JPEG_QUALITY: u8 = 80;
+
+pub(crate) fn info(bytes: &[u8]) -> Result<ImageInfo, Error> {
+    let reader = reader(bytes)?;
+    let format = format(reader.format())?;
+    let (width, height) = reader.into_dimensions().map_err(decode_error)?;
+    Ok(ImageInfo {
+        width,
+        height,
+        format,
+    })
+}
+
+pub(crate) fn resize(bytes: &[u8], width: u32, height: u32, fit: Fit) -> Result<Vec<u8>, Error> {
+    if width == 0 || height == 0 {
+        return Err(Error::InvalidDimensions);
+    }
+    if width > MAX_DIMENSION || height > MAX_DIMENSION || width as u64 * height as u64 > MAX_PIXELS
+    {
+        return Err(Error::TooLarge);
+    }
+    let (image, format) = decode(bytes)?;
+    let image = match fit {
+        Fit::Fill => scale(&image, width, height)?,
+        Fit::Contain => {
+            let (width, height) = contain(image.width(), image.height(), width, height);
+            scale(&image, width, height)?
+        }
+        Fit::Cover => {
+            let (x, y, crop_width, crop_height) =
+                cover(image.width(), image.height(), width, height);
+            let cropped = image.crop_imm(x, y, crop_width, crop_height);
+            scale(&cropped, width, height)?
+        }
+    };
+    encode(&image, same_format(format), None)
+}
+
+pub(crate) fn crop(
+    bytes: &[u8],
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+) -> Result<Vec<u8>, Error> {
+    let (image, format) = decode(bytes)?;
+    let within = |start: u32, len: u32, max: u32| {
+        len > 0 && start.checked_add(len).is_some_and(|end| end <= max)
+    };
+    if !within(x, width, image.width()) || !within(y, height, image.height()) {
+        return Err(Error::InvalidDimensions);
+    }
+    encode(
+        &image.crop_imm(x, y, width, height),
+        same_format(format),
+        None,
+    )
+}
+
+pub(crate) fn convert(bytes: &[u8], format: Format, quality: Option<u8>) -> Result<Vec<u8>, Error> {
+    let (image, _) = decode(bytes)?;
+    encode(&image, format, quality)
+}
+
+pub(crate) fn strip_metadata(bytes: &[u8]) -> Result<Vec<u8>, Error> {
+    // Only the pixels are decoded, so the re-encoded image has no metadata.
+    let (image, format) = decode(bytes)?;
+    encode(&image, same_format(format), None)
+}
+
+/// Returns the format to re-encode an image read in `format` in. WebP can't
+/// be written, so WebP images are re-encoded as PNG, which loses none of
+/// their pixels.
+fn same_format(format: Format) -> Format {
+    match format {
+        Format::Webp => Format::Png,
+        format => format,
+    }
+}
+
+/// Returns the largest size within `max_width` by `max_height` with the
+/// aspect ratio of `width` by `height`.
+fn contain(width: u32, height: u32, max_width: u32, max_height: u32) -> (u32, u32) {
+    let (width, height, max_width, max_height) = (
+        width as u64,
+        height as u64,
+        max_width as u64,
+        max_height as u64,
+    );
+    if width * max_height <= height * max_width {
+        (
+            (width * max_height / height).max(1) as u32,
+            max_height as u32,
+        )
+    } else {
+        (max_width as u32, (height * max_width / width).max(1) as u32)
+    }
+}
+
+/// Returns the centred region of a `width` by `height` image with the aspect
+/// ratio of `target_width` by `target_height`, as (x, y, width, height).
+fn cover(width: u32, height: u32, target_width: u32, target_height: u32) -> (u32, u32, u32, u32) {
+    let (w, h, tw, th) = (
+        width as u64,
+        height as u64,
+        target_width as u64,
+        target_height as u64,
+    );
+    if w * th > h * tw {
+        let crop_width = (h * tw / th).max(1) as u32;
+        ((width - crop_width) / 2, 0, crop_width, height)
+    } else {
+        let crop_height = (w * th / tw).max(1) as u32;
+        (0, (height - crop_height) / 2, width, crop_height)
+    }
+}
+
+/// Resamples `image` to `width` by `height`, using the CPU's SIMD
+/// instructions where it has them.
+fn scale(image: &DynamicImage, width: u32, height: u32) -> Result<DynamicImage, Error> {
+    if (image.width(), image.height()) == (width, height) {
+        return Ok(image.clone());
+    }
+    let rgba = image.to_rgba8();
+    let mut src = fr::Image::from_vec_u8(
+        nonzero(rgba.width())?,
+        nonzero(rgba.height())?,
+        rgba.into_raw(),
+        fr::PixelType::U8x4,
+    )
+    .map_err(other)?;
+    // Premultiplying alpha keeps transparent pixels' colours from bleeding
+    // into their neighbours.
+    let mul_div = fr::MulDiv::default();
+    mul_div
+        .multiply_alpha_inplace(&mut src.view_mut())
+        .map_err(other)?;
+
+    let mut dst = fr::Image::new(nonzero(width)?, nonzero(height)?, src.pixel_type());
+    let mut dst_view = dst.view_mut();
+    let mut resizer = fr::Resizer::new(fr::ResizeAlg::Convolution(fr::FilterType::Lanczos3));
+    resizer.resize(&src.view(), &mut dst_view).map_err(other)?;
+    mul_div.divide_alpha_inplace(&mut dst_view).map_err(other)?;
+
+    let rgba = RgbaImage::from_raw(width, height, dst.into_vec())
+        .ok_or_else(|| Error::Other("resized image has the wrong size".into()))?;
+    Ok(DynamicImage::ImageRgba8(rgba))
+}
+
+fn reader(bytes: &[u8]) -> Result<Reader<Cursor<&[u8]>>, Error> {
+    let mut reader = Reader::new(Cursor::new(bytes))
+        .with_guessed_format()
+        .map_err(other)?;
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(MAX_DIMENSION);
+    limits.max_image_height = Some(MAX_DIMENSION);
+    limits.max_alloc = Some(MAX_ALLOC);
+    reader.limits(limits);
+    Ok(reader)
+}
+
+fn decode(bytes: &[u8]) -> Result<(DynamicImage, Format), Error> {
+    let reader = reader(bytes)?;
+    let format = format(reader.format())?;
+    let image = reader.decode().map_err(decode_error)?;
+    Ok((image, format))
+}
+
+fn encode(image: &DynamicImage, format: Format, quality: Option<u8>) -> Result<Vec<u8>, Error> {
+    let output = match format {
+        Format::Png => ImageOutputFormat::Png,
+        Format::Jpeg => {
+            ImageOutputFormat::Jpeg(quality.unwrap_or(DEFAULT_JPEG_QUALITY).clamp(1, 100))
+        }
+        Format::Gif => ImageOutputFormat::Gif,
+        Format::Webp => return Err(Error::UnsupportedFormat),
+    };
+    // JPEG has neither an alpha channel nor 16-bit samples.
+    let rgb;
+    let image = match (format, image) {
+        (Format::Jpeg, DynamicImage::ImageRgb8(_) | DynamicImage::ImageLuma8(_)) => image,
+        (Format::Jpeg, _) => {
+            rgb = DynamicImage::ImageRgb8(image.to_rgb8());
+            &rgb
+        }
+        _ => image,
+    };
+    let mut bytes = Cursor::new(vec![]);
+    image.write_to(&mut bytes, output).map_err(|e| match e {
+        ImageError::Unsupported(_) => Error::UnsupportedFormat,
+        ImageError::Limits(_) => Error::TooLarge,
+        e => Error::Other(e.to_string()),
+    })?;
+    Ok(bytes.into_inner())
+}
+
+fn format(format: Option<ImageFormat>) -> Result<Format, Error> {
+    match format {
+        Some(ImageFormat::Png) => Ok(Format::Png),
+        Some(ImageFormat::Jpeg) => Ok(Format::Jpeg),
+        Some(ImageFormat::Gif) => Ok(Format::Gif),
+        Some(ImageFormat::WebP) => Ok(Format::Webp),
+        _ => Err(Error::InvalidImage("unrecognized image format".into())),
+    }
+}
+
+fn decode_error(err: ImageError) -> Error {
+    match err {
+        ImageError::Limits(_) => Error::TooLarge,
+        err => Error::InvalidImage(err.to_string()),
+    }
+}
+
+fn nonzero(n: u32) -> Result<NonZeroU32, Error> {
+    NonZeroU32::new(n).ok_or(Error::InvalidDimensions)
+}
+
+fn other(err: impl std::fmt::Debug) -> Error {
+    Error::Other(format!("{err:?}"))
+}
+
+#[cfg(test)]
+mod test {
+    use image::Rgba;
+
+    use super::*;
+
+    fn png(width: u32, height: u32) -> Vec<u8> {
+        let image = RgbaImage::from_pixel(width, height, Rgba([200, 100, 50, 128]));
+        let mut bytes = Cursor::new(vec![]);
+        DynamicImage::ImageRgba8(image)
+            .write_to(&mut bytes, ImageOutputFormat::Png)
+            .unwrap();
+        bytes.into_inner()
+    }
+
+    fn dimensions(bytes: &[u8]) -> (u32, u32) {
+        let info = info(bytes).unwrap();
+        (info.width, info.height)
+    }
+
+    #[test]
+    fn images_are_resized_to_fit() {
+        let image = png(40, 20);
+        assert!(matches!(info(&image).unwrap().format, Format::Png));
+
+        let contained = resize(&image, 10, 10, Fit::Contain).unwrap();
+        assert_eq!((10, 5), dimensions(&contained));
+        let covered = resize(&image, 10, 10, Fit::Cover).unwrap();
+        assert_eq!((10, 10), dimensions(&covered));
+        let filled = resize(&image, 7, 30, Fit::Fill).unwrap();
+        assert_eq!((7, 30), dimensions(&filled));
+
+        assert!(matches!(
+            resize(&image, 0, 10, Fit::Fill),
+            Err(Error::InvalidDimensions)
+        ));
+        assert!(matches!(
+            resize(&image, 100_000, 10, Fit::Fill),
+            Err(Error::TooLarge)
+        ));
+        assert!(matches!(
+            resize(&image, MAX_DIMENSION, MAX_DIMENSION, Fit::Fill),
+            Err(Error::TooLarge)
+        ));
+    }
+
+    #[test]
+    fn images_are_cropped_within_bounds() {
+        let image = png(40, 20);
+        let cropped = crop(&image, 30, 10, 10, 10).unwrap();
+        assert_eq!((10, 10), dimensions(&cropped));
+        assert!(matches!(
+            crop(&image, 31, 0, 10, 10),
+            Err(Error::InvalidDimensions)
+        ));
+        assert!(matches!(
+            crop(&image, u32::MAX, 0, 10, 10),
+            Err(Error::InvalidDimensions)
+        ));
+    }
+
+    #[test]
+    fn images_are_converted() {
+        let jpeg = convert(&png(8, 8), Format::Jpeg, Some(90)).unwrap();
+        assert!(matches!(info(&jpeg).unwrap().format, Format::Jpeg));
+        assert!(matches!(
+            info(&strip_metadata(&jpeg).unwrap()).unwrap().format,
+            Format::Jpeg
+        ));
+        assert!(matches!(
+            convert(&jpeg, Format::Webp, None),
+            Err(Error::UnsupportedFormat)
+        ));
+        assert!(matches!(
+            convert(b"not an image", Format::Png, None),
+            Err(Error::InvalidImage(_))
+        ));
+    }
+
+    #[test]
+    fn webp_images_are_written_as_png() {
+        // A lossless 1x1 WebP image.
+        let webp = [
+            0x52, 0x49, 0x46, 0x46, 0x1a, 0x00, 0x00, 0x00, 0x57, 0x45, 0x42, 0x50, 0x56, 0x50,
+            0x38, 0x4c, 0x0d, 0x00, 0x00, 0x00, 0x2f, 0x00, 0x00, 0x00, 0x10, 0x07, 0x10, 0x11,
+            0x11, 0x88, 0x88, 0xfe, 0x07, 0x00,
+        ];
+        assert!(matches!(info(&webp).unwrap().format, Format::Webp));
+        for processed in [
+            resize(&webp, 2, 2, Fit::Fill),
+            crop(&webp, 0, 0, 1, 1),
+            strip_metadata(&webp),
+        ] {
+            assert!(matches!(
+                info(&processed.unwrap()).unwrap().format,
+                Format::Png
+            ));
+        }
+    }
+}
@@ -0,0 +1,183 @@
+use anyhow::{Context, Result};
+use native_tls::TlsConnector;
+use postgres_native_tls::MakeTlsConnector;
+use spin_core::async_trait;
+use spin_jobs::{Job, JobStore, StepStore};
+use tokio::{sync::OnceCell, task};
+use tokio_postgres::{config::SslMode, Client, NoTls, Row};
+
+/// Jobs stored in a Postgres table.
+///
+/// Due jobs are claimed with `FOR UPDATE SKIP LOCKED`, so that replicas of an
+/// application sharing the database don't claim the same jobs.
+pub struct PostgresJobStore {
+    config: tokio_postgres::Config,
+    client: OnceCell<Client>,
+}
+
+impl PostgresJobStore {
+    pub fn new(connection_string: &str) -> Result<Self> {
+        let config = connection_string
+            .parse()
+            .context("Invalid Postgres connection string")?;
+        Ok(Self {
+            config,
+            client: OnceCell::new(),
+        })
+    }
+
+    async fn client(&self) -> Result<&Client> {
+        self.client.get_or_try_init(|| self.connect()).await
+    }
+
+    async fn connect(&self) -> Result<Client> {
+        let client = if self.config.get_ssl_mode() == SslMode::Disable {
+            let (client, connection) = self.config.connect(NoTls).await?;
+            task::spawn(connection);
+            client
+        } else {
+            let connector = MakeTlsConnector::new(TlsConnector::builder().build()?);
+            let (client, connection) = self.config.connect(connector).await?;
+            task::spawn(connection);
+            client
+        };
+
+        client
+            .batch_execute(
+                r#"CREATE TABLE IF NOT EXISTS spin_jobs (
+                     id           TEXT PRIMARY KEY,
+                     component_id TEXT NOT NULL,
+                     payload      BYTEA NOT NULL,
+                     due          BIGINT NOT NULL,
+                     attempts     INTEGER NOT NULL
+                   );
+                   CREATE INDEX IF NOT EXISTS spin_jobs_due ON spin_jobs (due);
+                   CREATE TABLE IF NOT EXISTS spin_workflow_steps (
+                     run    TEXT NOT NULL,
+                     step   TEXT NOT NULL,
+                     output BYTEA NOT NULL,
+
+                     PRIMARY KEY (run, step)
+                   )"#,
+            )
+            .await?;
+        Ok(client)
+    }
+}
+
+fn seconds(secs: u64) -> i64 {
+    secs.try_into().unwrap_or(i64::MAX)
+}
+
+fn job(row: &Row) -> Job {
+    Job {
+        id: row.get(0),
+        component_id: row.get(1),
+        payload: row.get(2),
+        due: row.get::<_, i64>(3).try_into().unwrap_or_default(),
+        attempts: row.get::<_, i32>(4).try_into().unwrap_or_default(),
+    }
+}
+
+#[async_trait]
+impl JobStore for PostgresJobStore {
+    async fn put(&self, job: &Job) -> Result<()> {
+        let attempts = i32::try_from(job.attempts).unwrap_or(i32::MAX);
+        self.client()
+            .await?
+            .execute(
+                "INSERT INTO spin_jobs (id, component_id, payload, due, attempts)
+                 VALUES ($1, $2, $3, $4, $5)
+                 ON CONFLICT (id) DO UPDATE
+                 SET component_id=$2, payload=$3, due=$4, attempts=$5",
+                &[
+                    &job.id,
+                    &job.component_id,
+                    &job.payload,
+                    &seconds(job.due),
+                    &attempts,
+                ],
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn cancel(&self, id: &str) -> Result<bool> {
+        let deleted = self
+            .client()
+            .await?
+            .execute("DELETE FROM spin_jobs WHERE id=$1", &[&id])
+            .await?;
+        Ok(deleted > 0)
+    }
+
+    async fn claim_due(&self, now: u64, lease: u64, limit: usize) -> Result<Vec<Job>> {
+        let limit = i64::try_from(limit).unwrap_or(i64::MAX);
+        let rows = self
+            .client()
+            .await?
+            .query(
+                "UPDATE spin_jobs SET due=$2
+                 WHERE id IN (
+                   SELECT id FROM spin_jobs WHERE due <= $1
+                   ORDER BY due LIMIT $3
+                   FOR UPDATE SKIP LOCKED
+                 )
+                 RETURNING id, component_id, payload, due, attempts",
+                &[&seconds(now), &seconds(now.saturating_add(lease)), &limit],
+            )
+            .await?;
+        Ok(rows.iter().map(job).collect())
+    }
+}
+
+/// Workflow progress stored in a Postgres table.
+pub struct PostgresStepStore {
+    jobs: PostgresJobStore,
+}
+
+impl PostgresStepStore {
+    pub fn new(connection_string: &str) -> Result<Self> {
+        Ok(Self {
+            jobs: PostgresJobStore::new(connection_string)?,
+        })
+    }
+}
+
+#[async_trait]
+impl StepStore for PostgresStepStore {
+    async fn get_step(&self, run: &str, step: &str) -> Result<Option<Vec<u8>>> {
+        let row = self
+            .jobs
+            .client()
+            .await?
+            .query_opt(
+                "SELECT output FROM spin_workflow_steps WHERE run=$1 AND step=$2",
+                &[&run, &step],
+            )
+            .await?;
+        Ok(row.map(|row| row.get(0)))
+    }
+
+    async fn record_step(&self, run: &str, step: &str, output: &[u8]) -> Result<()> {
+        self.jobs
+            .client()
+            .await?
+            .execute(
+                "INSERT INTO spin_workflow_steps (run, step, output) VALUES ($1, $2, $3)
+                 ON CONFLICT (run, step) DO NOTHING",
+                &[&run, &step, &output],
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn finish(&self, run: &str) -> Result<()> {
+        self.jobs
+            .client()
+            .await?
+            .execute("DELETE FROM spin_workflow_steps WHERE run=$1", &[&run])
+            .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,76 @@
+use crate::{JobStore, JobsDispatch, JOB_TARGETS_KEY};
+use anyhow::anyhow;
+use spin_app::{AppComponent, DynamicHostComponent};
+use spin_core::HostComponent;
+use std::{collections::HashSet, sync::Arc};
+
+pub struct JobsComponent {
+    store: Arc<dyn JobStore>,
+}
+
+impl JobsComponent {
+    /// A component which schedules jobs in the given store.
+    pub fn new(store: Arc<dyn JobStore>) -> Self {
+        Self { store }
+    }
+}
+
+impl HostComponent for JobsComponent {
+    type Data = JobsDispatch;
+
+    fn add_to_linker<T: Send>(
+        linker: &mut spin_core::Linker<T>,
+        get: impl Fn(&mut spin_core::Data<T>) -> &mut Self::Data + Send + Sync + Copy + 'static,
+    ) -> anyhow::Result<()> {
+        spin_world::v3::jobs::add_to_linker(linker, get)
+    }
+
+    fn build_data(&self) -> Self::Data {
+        JobsDispatch::new()
+    }
+}
+
+impl DynamicHostComponent for JobsComponent {
+    fn update_data(&self, data: &mut Self::Data, component: &AppComponent) -> anyhow::Result<()> {
+        let targets = component.get_metadata(JOB_TARGETS_KEY)?.unwrap_or_default();
+        let app_components: HashSet<_> = component
+            .app
+            .components()
+            .map(|c| c.id().to_owned())
+            .collect();
+        data.init(targets, Arc::new(app_components), self.store.clone());
+        Ok(())
+    }
+
+    fn validate_app(&self, app: &spin_app::App) -> anyhow::Result<()> {
+        let mut errors = vec![];
+
+        for component in app.components() {
+            for target in component.get_metadata(JOB_TARGETS_KEY)?.unwrap_or_default() {
+                if app.get_component(&target).is_none() {
+                    let err = format!(
+                        "- Component {} schedules jobs for '{target}'",
+                        component.id()
+                    );
+                    errors.push(err);
+                }
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            let prologue = vec![
+                "One or more components schedule jobs for components which are not defined.",
+                "Check the spelling of the component IDs in `job_targets`.",
+                "Details:",
+            ];
+            let lines: Vec<_> = prologue
+                .into_iter()
+                .map(|s| s.to_owned())
+                .chain(errors)
+                .collect();
+            Err(anyhow!(lines.join("\n")))
+        }
+    }
+}
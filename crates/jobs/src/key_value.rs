@@ -0,0 +1,541 @@
+use crate::{Job, JobStore, StepStore};
+use anyhow::{anyhow, Context, Result};
+use spin_core::async_trait;
+use spin_key_value::{Scan, Store, StoreManager};
+use std::{sync::Arc, time::Duration};
+use tokio::sync::{Mutex, OnceCell};
+
+/// Jobs are stored under `<prefix>due:<bucket>:<due>:<id>`, where `<bucket>`
+/// is the minute the job is due in, with the times padded so that the keys
+/// sort in due order, and `<prefix>id:<id>` holds the due key of each job. A
+/// claim of a job while it is due at `<due>` is stored under
+/// `<prefix>claim:<bucket>:<due>:<id>` for the lease.
+const DEFAULT_KEY_PREFIX: &str = "spin-jobs:";
+
+/// How long a bucket of due keys covers, in seconds.
+const BUCKET_SECS: u64 = 60;
+
+/// The output of each step of a workflow run is stored under
+/// `<prefix><len>:<run>:<step>`, where `<len>` is the length of the run's
+/// key, so that no run's keys start with another's.
+const DEFAULT_STEP_KEY_PREFIX: &str = "spin-workflows:";
+
+/// A key-value store which is only opened once it is used.
+struct LazyStore {
+    manager: Arc<dyn StoreManager>,
+    name: String,
+    store: OnceCell<Arc<dyn Store>>,
+}
+
+impl LazyStore {
+    fn new(manager: Arc<dyn StoreManager>, name: String) -> Self {
+        Self {
+            manager,
+            name,
+            store: OnceCell::new(),
+        }
+    }
+
+    async fn get(&self) -> Result<&Arc<dyn Store>> {
+        self.store
+            .get_or_try_init(|| async {
+                self.manager
+                    .get(&self.name)
+                    .await
+                    .map_err(|e| anyhow!("Failed to open key-value store {:?}: {e:?}", self.name))
+            })
+            .await
+    }
+}
+
+/// Jobs stored in a key-value store.
+///
+/// Jobs are claimed with [`Store::set_if_absent`], so replicas of an
+/// application sharing the store don't both run a job which falls due, as
+/// long as the store sets keys atomically.
+pub struct KeyValueJobStore {
+    store: LazyStore,
+    key_prefix: String,
+    // Serializes updates, which each touch two keys.
+    lock: Mutex<()>,
+    // The earliest bucket which may hold due jobs, once the store has been
+    // scanned for it.
+    earliest_bucket: Mutex<Option<u64>>,
+}
+
+impl KeyValueJobStore {
+    /// Jobs stored in the store `store_name` of `manager`, which is only
+    /// opened once jobs are used.
+    pub fn new(manager: Arc<dyn StoreManager>, store_name: String) -> Self {
+        Self {
+            store: LazyStore::new(manager, store_name),
+            key_prefix: DEFAULT_KEY_PREFIX.into(),
+            lock: Mutex::new(()),
+            earliest_bucket: Mutex::new(None),
+        }
+    }
+
+    async fn store(&self) -> Result<&Arc<dyn Store>> {
+        self.store.get().await
+    }
+
+    fn due_key(&self, job: &Job) -> String {
+        format!(
+            "{}{:020}:{}",
+            self.bucket_prefix(job.due / BUCKET_SECS),
+            job.due,
+            job.id
+        )
+    }
+
+    fn id_key(&self, id: &str) -> String {
+        format!("{}id:{id}", self.key_prefix)
+    }
+
+    fn due_prefix(&self) -> String {
+        format!("{}due:", self.key_prefix)
+    }
+
+    fn bucket_prefix(&self, bucket: u64) -> String {
+        format!("{}{bucket:020}:", self.due_prefix())
+    }
+
+    /// The due time of the job stored under `due_key`, and the key of its
+    /// claim while it is due then.
+    fn parse_due_key(&self, due_key: &str) -> Option<(u64, String)> {
+        let rest = due_key.strip_prefix(&self.due_prefix())?;
+        let due = rest.split(':').nth(1)?.parse().ok()?;
+        Some((due, format!("{}claim:{rest}", self.key_prefix)))
+    }
+
+    /// The keys of the jobs due at `now`, in due order.
+    ///
+    /// All the jobs are scanned on first use, to find the earliest which is
+    /// due. After that, only the buckets from the earliest with due jobs to
+    /// the current one are scanned. The previous bucket is always scanned
+    /// too, for jobs put by replicas whose clocks are a little behind.
+    async fn due_keys(&self, store: &dyn Store, now: u64) -> Result<Vec<String>> {
+        let current = now / BUCKET_SECS;
+        let mut earliest = self.earliest_bucket.lock().await;
+        let first = match *earliest {
+            Some(bucket) => bucket,
+            None => scan(store, &self.due_prefix())
+                .await?
+                .iter()
+                .filter_map(|key| self.parse_due_key(key))
+                .map(|(due, _)| due / BUCKET_SECS)
+                .min()
+                .unwrap_or(current),
+        }
+        .min(current.saturating_sub(1));
+
+        let mut keys = vec![];
+        for bucket in first..=current {
+            keys.extend(
+                scan(store, &self.bucket_prefix(bucket))
+                    .await?
+                    .into_iter()
+                    .filter(|key| self.parse_due_key(key).map_or(false, |(due, _)| due <= now)),
+            );
+        }
+        // Jobs which aren't claimed now are due again on the next tick.
+        *earliest = Some(
+            keys.first()
+                .and_then(|key| self.parse_due_key(key))
+                .map_or(current, |(due, _)| due / BUCKET_SECS),
+        );
+        Ok(keys)
+    }
+
+    async fn put_locked(&self, store: &dyn Store, job: &Job) -> Result<()> {
+        self.remove_locked(store, &job.id).await?;
+        if let Some(earliest) = self.earliest_bucket.lock().await.as_mut() {
+            *earliest = (*earliest).min(job.due / BUCKET_SECS);
+        }
+        let due_key = self.due_key(job);
+        store
+            .set(&due_key, &serde_json::to_vec(job)?)
+            .await
+            .map_err(kv_error)?;
+        store
+            .set(&self.id_key(&job.id), due_key.as_bytes())
+            .await
+            .map_err(kv_error)
+    }
+
+    async fn remove_locked(&self, store: &dyn Store, id: &str) -> Result<bool> {
+        let id_key = self.id_key(id);
+        let Some(due_key) = store.get(&id_key).await.map_err(kv_error)? else {
+            return Ok(false);
+        };
+        let due_key = String::from_utf8(due_key).context("Invalid job key")?;
+        store
+            .delete_many(&[due_key, id_key])
+            .await
+            .map_err(kv_error)?;
+        Ok(true)
+    }
+}
+
+#[async_trait]
+impl JobStore for KeyValueJobStore {
+    async fn put(&self, job: &Job) -> Result<()> {
+        let store = self.store().await?;
+        let _lock = self.lock.lock().await;
+        self.put_locked(store.as_ref(), job).await
+    }
+
+    async fn cancel(&self, id: &str) -> Result<bool> {
+        let store = self.store().await?;
+        let _lock = self.lock.lock().await;
+        self.remove_locked(store.as_ref(), id).await
+    }
+
+    async fn claim_due(&self, now: u64, lease: u64, limit: usize) -> Result<Vec<Job>> {
+        let store = self.store().await?;
+        let claim_ttl = Duration::from_secs(lease.max(1));
+
+        let mut claimed = vec![];
+        for key in self.due_keys(store.as_ref(), now).await? {
+            if claimed.len() == limit {
+                break;
+            }
+            let Some((_, claim_key)) = self.parse_due_key(&key) else {
+                continue;
+            };
+            // Whoever sets the claim first runs the job; until the claim
+            // expires, it is rescheduled under another key.
+            if !store
+                .set_if_absent(&claim_key, &now.to_be_bytes(), Some(claim_ttl))
+                .await
+                .map_err(kv_error)?
+            {
+                continue;
+            }
+            // The job may have been claimed and rescheduled, or cancelled,
+            // since the scan.
+            let Some(value) = store.get(&key).await.map_err(kv_error)? else {
+                continue;
+            };
+            let mut job: Job = serde_json::from_slice(&value).context("Invalid job")?;
+            job.due = now.saturating_add(lease);
+            let _lock = self.lock.lock().await;
+            self.put_locked(store.as_ref(), &job).await?;
+            claimed.push(job);
+        }
+        Ok(claimed)
+    }
+}
+
+/// Workflow progress stored in a key-value store.
+///
+/// Each step is stored under a key of its own with [`Store::set_if_absent`],
+/// so that replicas recording steps of the same run don't overwrite each
+/// other's, as long as the store sets keys atomically.
+pub struct KeyValueStepStore {
+    store: LazyStore,
+    key_prefix: String,
+}
+
+impl KeyValueStepStore {
+    /// Steps stored in the store `store_name` of `manager`, which is only
+    /// opened once workflows are used.
+    pub fn new(manager: Arc<dyn StoreManager>, store_name: String) -> Self {
+        Self {
+            store: LazyStore::new(manager, store_name),
+            key_prefix: DEFAULT_STEP_KEY_PREFIX.into(),
+        }
+    }
+
+    fn run_prefix(&self, run: &str) -> String {
+        format!("{}{}:{run}:", self.key_prefix, run.len())
+    }
+
+    fn step_key(&self, run: &str, step: &str) -> String {
+        format!("{}{step}", self.run_prefix(run))
+    }
+}
+
+#[async_trait]
+impl StepStore for KeyValueStepStore {
+    async fn get_step(&self, run: &str, step: &str) -> Result<Option<Vec<u8>>> {
+        let store = self.store.get().await?;
+        store.get(&self.step_key(run, step)).await.map_err(kv_error)
+    }
+
+    async fn record_step(&self, run: &str, step: &str, output: &[u8]) -> Result<()> {
+        let store = self.store.get().await?;
+        store
+            .set_if_absent(&self.step_key(run, step), output, None)
+            .await
+            .map_err(kv_error)?;
+        Ok(())
+    }
+
+    async fn finish(&self, run: &str) -> Result<()> {
+        let store = self.store.get().await?;
+        let keys = scan(store.as_ref(), &self.run_prefix(run)).await?;
+        store.delete_many(&keys).await.map_err(kv_error)
+    }
+}
+
+/// All the keys starting with `prefix`, in order.
+///
+/// As stores needn't scan in key order, and may return empty pages before
+/// the end of a scan, this always scans to the end.
+async fn scan(store: &dyn Store, prefix: &str) -> Result<Vec<String>> {
+    let mut keys = vec![];
+    let mut cursor = None;
+    loop {
+        let scan = store
+            .scan(prefix, cursor.as_deref(), Scan::MAX_LIMIT)
+            .await
+            .map_err(kv_error)?;
+        keys.extend(scan.keys);
+        match scan.cursor {
+            Some(next) => cursor = Some(next),
+            None => break,
+        }
+    }
+    // A scan may return a key more than once.
+    keys.sort();
+    keys.dedup();
+    Ok(keys)
+}
+
+fn kv_error(err: spin_key_value::Error) -> anyhow::Error {
+    anyhow!("key-value store error: {err:?}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use spin_key_value::Error;
+    use std::{collections::BTreeMap, sync::Mutex as StdMutex};
+
+    /// The entries, and the prefixes scanned.
+    #[derive(Default)]
+    struct MemoryStore(StdMutex<BTreeMap<String, Vec<u8>>>, StdMutex<Vec<String>>);
+
+    #[async_trait]
+    impl Store for MemoryStore {
+        async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, Error> {
+            Ok(self.0.lock().unwrap().get(key).cloned())
+        }
+        async fn set(&self, key: &str, value: &[u8]) -> Result<(), Error> {
+            self.0.lock().unwrap().insert(key.into(), value.into());
+            Ok(())
+        }
+        async fn delete(&self, key: &str) -> Result<(), Error> {
+            self.0.lock().unwrap().remove(key);
+            Ok(())
+        }
+        async fn exists(&self, key: &str) -> Result<bool, Error> {
+            Ok(self.0.lock().unwrap().contains_key(key))
+        }
+        async fn get_keys(&self) -> Result<Vec<String>, Error> {
+            Ok(self.0.lock().unwrap().keys().cloned().collect())
+        }
+        // Claims outlive the tests, so they never expire.
+        async fn set_with_ttl(&self, key: &str, value: &[u8], _ttl: Duration) -> Result<(), Error> {
+            self.set(key, value).await
+        }
+        // Like Redis' `SCAN`, returns the keys out of order, with empty pages
+        // before the end.
+        async fn scan(
+            &self,
+            prefix: &str,
+            cursor: Option<&str>,
+            _limit: u32,
+        ) -> Result<Scan, Error> {
+            self.1.lock().unwrap().push(prefix.into());
+            let keys: Vec<_> = self
+                .0
+                .lock()
+                .unwrap()
+                .keys()
+                .rev()
+                .filter(|key| key.starts_with(prefix))
+                .cloned()
+                .collect();
+            let position: usize = cursor.map_or(0, |c| c.parse().unwrap());
+            let page = if position % 2 == 0 {
+                vec![]
+            } else {
+                keys.get(position / 2).cloned().into_iter().collect()
+            };
+            let next = position + 1;
+            Ok(Scan {
+                keys: page,
+                cursor: (next < 2 * keys.len()).then(|| next.to_string()),
+            })
+        }
+    }
+
+    struct MemoryStoreManager(Arc<MemoryStore>);
+
+    #[async_trait]
+    impl StoreManager for MemoryStoreManager {
+        async fn get(&self, _name: &str) -> Result<Arc<dyn Store>, Error> {
+            Ok(self.0.clone())
+        }
+        fn is_defined(&self, _store_name: &str) -> bool {
+            true
+        }
+    }
+
+    fn job_store() -> KeyValueJobStore {
+        replica_job_store(Default::default())
+    }
+
+    fn replica_job_store(store: Arc<MemoryStore>) -> KeyValueJobStore {
+        let manager = MemoryStoreManager(store);
+        KeyValueJobStore::new(Arc::new(manager), "default".into())
+    }
+
+    fn step_store() -> KeyValueStepStore {
+        replica_step_store(Default::default())
+    }
+
+    fn replica_step_store(store: Arc<MemoryStore>) -> KeyValueStepStore {
+        let manager = MemoryStoreManager(store);
+        KeyValueStepStore::new(Arc::new(manager), "default".into())
+    }
+
+    fn job(id: &str, due: u64) -> Job {
+        Job {
+            id: id.into(),
+            component_id: "reminder".into(),
+            payload: id.as_bytes().to_vec(),
+            due,
+            attempts: 0,
+        }
+    }
+
+    #[tokio::test]
+    async fn due_jobs_are_claimed_in_order() -> Result<()> {
+        let store = job_store();
+        store.put(&job("later", 200)).await?;
+        store.put(&job("second", 100)).await?;
+        store.put(&job("first", 50)).await?;
+
+        let claimed = store.claim_due(150, 60, 10).await?;
+        let ids: Vec<_> = claimed.iter().map(|job| job.id.as_str()).collect();
+        assert_eq!(vec!["first", "second"], ids);
+        assert!(claimed.iter().all(|job| job.due == 210));
+
+        // Claimed jobs aren't due again until their lease runs out.
+        assert!(store.claim_due(150, 60, 10).await?.is_empty());
+        store.complete("first").await?;
+        let ids: Vec<_> = store
+            .claim_due(210, 60, 10)
+            .await?
+            .into_iter()
+            .map(|job| job.id)
+            .collect();
+        assert_eq!(vec!["later", "second"], ids);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn only_one_replica_claims_a_job() -> Result<()> {
+        let shared = Arc::new(MemoryStore::default());
+        let replica = replica_job_store(shared.clone());
+        let other_replica = replica_job_store(shared);
+        replica.put(&job("first", 50)).await?;
+        replica.put(&job("second", 100)).await?;
+
+        let claimed = replica.claim_due(150, 60, 1).await?;
+        assert_eq!("first", claimed[0].id);
+        let claimed = other_replica.claim_due(150, 60, 10).await?;
+        let ids: Vec<_> = claimed.iter().map(|job| job.id.as_str()).collect();
+        assert_eq!(vec!["second"], ids);
+        assert!(replica.claim_due(150, 60, 10).await?.is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn only_buckets_up_to_now_are_scanned() -> Result<()> {
+        let shared = Arc::new(MemoryStore::default());
+        let store = replica_job_store(shared.clone());
+        store.put(&job("overdue", 65)).await?;
+        store.put(&job("next-year", 60 * 60 * 24 * 365)).await?;
+
+        // The first claim scans all the jobs, to find the earliest due.
+        let claimed = store.claim_due(600, 60, 10).await?;
+        assert_eq!("overdue", claimed[0].id);
+        assert!(store.claim_due(601, 60, 10).await?.is_empty());
+
+        shared.1.lock().unwrap().clear();
+        assert!(store.claim_due(602, 60, 10).await?.is_empty());
+        let scanned = std::mem::take(&mut *shared.1.lock().unwrap());
+        let buckets = [store.bucket_prefix(9), store.bucket_prefix(10)];
+        assert!(scanned.iter().all(|prefix| buckets.contains(prefix)));
+
+        // Jobs put since are still found.
+        store.put(&job("soon", 610)).await?;
+        let ids: Vec<_> = store
+            .claim_due(660, 60, 10)
+            .await?
+            .into_iter()
+            .map(|job| job.id)
+            .collect();
+        assert_eq!(vec!["soon", "overdue"], ids);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn jobs_can_be_cancelled() -> Result<()> {
+        let store = job_store();
+        let job = Job::new("reminder".into(), vec![], Duration::ZERO);
+        store.put(&job).await?;
+
+        assert!(store.cancel(&job.id).await?);
+        assert!(!store.cancel(&job.id).await?);
+        assert!(store.claim_due(u64::MAX, 60, 10).await?.is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn workflow_steps_are_recorded_until_the_run_finishes() -> Result<()> {
+        let store = step_store();
+        assert_eq!(None, store.get_step("order-1", "charge").await?);
+
+        store.record_step("order-1", "charge", b"ch_123").await?;
+        store.record_step("order-1", "reserve", b"").await?;
+        store.record_step("order-12", "charge", b"ch_456").await?;
+        assert_eq!(
+            Some(b"ch_123".to_vec()),
+            store.get_step("order-1", "charge").await?
+        );
+        assert_eq!(Some(vec![]), store.get_step("order-1", "reserve").await?);
+        assert_eq!(None, store.get_step("order-2", "charge").await?);
+
+        store.finish("order-1").await?;
+        assert_eq!(None, store.get_step("order-1", "charge").await?);
+        assert_eq!(
+            Some(b"ch_456".to_vec()),
+            store.get_step("order-12", "charge").await?
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn replicas_keep_the_first_output_of_a_step() -> Result<()> {
+        let shared = Arc::new(MemoryStore::default());
+        let replica = replica_step_store(shared.clone());
+        let other_replica = replica_step_store(shared);
+
+        replica.record_step("order-1", "charge", b"ch_123").await?;
+        other_replica.record_step("order-1", "reserve", b"").await?;
+        other_replica
+            .record_step("order-1", "charge", b"ch_456")
+            .await?;
+        assert_eq!(
+            Some(b"ch_123".to_vec()),
+            replica.get_step("order-1", "charge").await?
+        );
+        assert_eq!(Some(vec![]), replica.get_step("order-1", "reserve").await?);
+        Ok(())
+    }
+}
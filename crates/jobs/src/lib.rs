@@ -0,0 +1,190 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use spin_app::MetadataKey;
+use spin_core::async_trait;
+use spin_world::v3::jobs;
+use std::{
+    collections::HashSet,
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+mod host_component;
+mod key_value;
+mod workflow;
+
+pub use host_component::JobsComponent;
+pub use key_value::{KeyValueJobStore, KeyValueStepStore};
+pub use workflow::{StepStore, WorkflowsComponent};
+
+/// The components for which a component may schedule jobs.
+pub const JOB_TARGETS_KEY: MetadataKey<HashSet<String>> = MetadataKey::new("job_targets");
+
+/// The interface which components export to handle jobs.
+pub const INBOUND_JOB_EXPORT: &str = "fermyon:spin/inbound-job@3.0.0";
+
+/// How many times a job is attempted before it is abandoned.
+pub const MAX_ATTEMPTS: u32 = 10;
+
+pub use jobs::Error;
+
+/// A job scheduled for a component.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Job {
+    pub id: String,
+    pub component_id: String,
+    pub payload: Vec<u8>,
+    /// When the job is next due, in seconds since the Unix epoch.
+    pub due: u64,
+    /// How many times the job has been attempted and failed.
+    pub attempts: u32,
+}
+
+impl Job {
+    /// A job with a new random ID, due after `delay`.
+    pub fn new(component_id: String, payload: Vec<u8>, delay: Duration) -> Self {
+        Self {
+            id: format!("{:032x}", rand::random::<u128>()),
+            component_id,
+            payload,
+            due: now().saturating_add(delay.as_secs()),
+            attempts: 0,
+        }
+    }
+
+    /// Records a failed attempt, rescheduling the job with exponential backoff
+    /// from `now`. Returns `false` if the job has run out of attempts.
+    pub fn retry(&mut self, now: u64) -> bool {
+        self.attempts += 1;
+        if self.attempts >= MAX_ATTEMPTS {
+            return false;
+        }
+        let backoff = 10u64.saturating_mul(1 << self.attempts.min(10)).min(3600);
+        self.due = now.saturating_add(backoff);
+        true
+    }
+}
+
+/// The current time in seconds since the Unix epoch.
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+/// Durable storage for scheduled jobs.
+///
+/// A job is claimed by the dispatcher when it is due, and then completed, or
+/// put back to be retried. A job whose dispatcher goes away before either is
+/// claimed again once its lease runs out, so jobs are delivered at least once.
+#[async_trait]
+pub trait JobStore: Sync + Send {
+    /// Store a job, replacing any job with the same ID.
+    async fn put(&self, job: &Job) -> Result<()>;
+    /// Remove a job, returning whether it existed.
+    async fn cancel(&self, id: &str) -> Result<bool>;
+    /// Claim up to `limit` jobs which are due at `now`, so that they aren't
+    /// claimed again for `lease` seconds.
+    async fn claim_due(&self, now: u64, lease: u64, limit: usize) -> Result<Vec<Job>>;
+    /// Remove a job which has run.
+    async fn complete(&self, id: &str) -> Result<()> {
+        self.cancel(id).await.map(|_| ())
+    }
+}
+
+pub struct JobsDispatch {
+    allowed_targets: HashSet<String>,
+    app_components: Arc<HashSet<String>>,
+    store: Option<Arc<dyn JobStore>>,
+}
+
+impl JobsDispatch {
+    pub fn new() -> Self {
+        Self {
+            allowed_targets: HashSet::new(),
+            app_components: Default::default(),
+            store: None,
+        }
+    }
+
+    pub fn init(
+        &mut self,
+        allowed_targets: HashSet<String>,
+        app_components: Arc<HashSet<String>>,
+        store: Arc<dyn JobStore>,
+    ) {
+        self.allowed_targets = allowed_targets;
+        self.app_components = app_components;
+        self.store = Some(store);
+    }
+
+    fn store(&self) -> Result<&Arc<dyn JobStore>, Error> {
+        self.store
+            .as_ref()
+            .ok_or_else(|| Error::Other("jobs are not available".into()))
+    }
+}
+
+impl Default for JobsDispatch {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl jobs::Host for JobsDispatch {
+    async fn enqueue(
+        &mut self,
+        component_id: String,
+        payload: Vec<u8>,
+        delay_seconds: u64,
+    ) -> Result<Result<String, Error>> {
+        if !self.app_components.contains(&component_id) {
+            return Ok(Err(Error::NoSuchComponent));
+        }
+        if !self.allowed_targets.contains(&component_id) {
+            return Ok(Err(Error::AccessDenied));
+        }
+        let store = match self.store() {
+            Ok(store) => store,
+            Err(e) => return Ok(Err(e)),
+        };
+        let job = Job::new(component_id, payload, Duration::from_secs(delay_seconds));
+        Ok(store.put(&job).await.map(|()| job.id).map_err(log_error))
+    }
+
+    async fn cancel(&mut self, id: String) -> Result<Result<bool, Error>> {
+        let store = match self.store() {
+            Ok(store) => store,
+            Err(e) => return Ok(Err(e)),
+        };
+        Ok(store.cancel(&id).await.map_err(log_error))
+    }
+}
+
+pub fn log_error(err: impl std::fmt::Debug) -> Error {
+    tracing::warn!("jobs error: {err:?}");
+    Error::Other(format!("{err:?}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn failed_jobs_back_off_until_abandoned() {
+        let mut job = Job::new("reminder".into(), vec![], Duration::from_secs(60));
+        assert!(job.due >= now() + 60);
+
+        assert!(job.retry(1000));
+        assert_eq!(1020, job.due);
+        assert!(job.retry(1000));
+        assert_eq!(1040, job.due);
+        for _ in 2..MAX_ATTEMPTS - 1 {
+            assert!(job.retry(1000));
+        }
+        assert_eq!(4600, job.due);
+        assert!(!job.retry(1000));
+    }
+}
@@ -0,0 +1,153 @@
+//! Durable progress of multi-step processes.
+//!
+//! A component records the output of each step of a run as it completes. If
+//! the run is retried, e.g. because the job driving it is delivered again
+//! after a crash, completed steps return their recorded output instead of
+//! being repeated.
+
+use anyhow::{Context, Result};
+use spin_app::{AppComponent, DynamicHostComponent};
+use spin_core::{async_trait, wasmtime::component::Resource, HostComponent};
+use spin_world::v3::workflows;
+use std::sync::Arc;
+use table::Table;
+
+pub use workflows::Error as WorkflowError;
+
+const DEFAULT_RUN_TABLE_CAPACITY: u32 = 256;
+
+/// Durable storage for the steps of workflow runs.
+#[async_trait]
+pub trait StepStore: Sync + Send {
+    /// Return the recorded output of `step` in `run`.
+    async fn get_step(&self, run: &str, step: &str) -> Result<Option<Vec<u8>>>;
+    /// Record the output of `step` in `run`, unless `step` has already been
+    /// recorded, e.g. by another replica running the same run.
+    async fn record_step(&self, run: &str, step: &str, output: &[u8]) -> Result<()>;
+    /// Remove the record of `run`.
+    async fn finish(&self, run: &str) -> Result<()>;
+}
+
+pub struct WorkflowsComponent {
+    store: Arc<dyn StepStore>,
+}
+
+impl WorkflowsComponent {
+    /// A component which records workflow progress in the given store.
+    pub fn new(store: Arc<dyn StepStore>) -> Self {
+        Self { store }
+    }
+}
+
+impl HostComponent for WorkflowsComponent {
+    type Data = WorkflowsDispatch;
+
+    fn add_to_linker<T: Send>(
+        linker: &mut spin_core::Linker<T>,
+        get: impl Fn(&mut spin_core::Data<T>) -> &mut Self::Data + Send + Sync + Copy + 'static,
+    ) -> anyhow::Result<()> {
+        workflows::add_to_linker(linker, get)
+    }
+
+    fn build_data(&self) -> Self::Data {
+        WorkflowsDispatch::new()
+    }
+}
+
+impl DynamicHostComponent for WorkflowsComponent {
+    fn update_data(&self, data: &mut Self::Data, component: &AppComponent) -> anyhow::Result<()> {
+        data.init(component.id().to_owned(), self.store.clone());
+        Ok(())
+    }
+}
+
+pub struct WorkflowsDispatch {
+    component_id: String,
+    store: Option<Arc<dyn StepStore>>,
+    // The store key of each open run
+    runs: Table<String>,
+}
+
+impl WorkflowsDispatch {
+    pub fn new() -> Self {
+        Self {
+            component_id: String::new(),
+            store: None,
+            runs: Table::new(DEFAULT_RUN_TABLE_CAPACITY),
+        }
+    }
+
+    pub fn init(&mut self, component_id: String, store: Arc<dyn StepStore>) {
+        self.component_id = component_id;
+        self.store = Some(store);
+    }
+
+    fn get_run(&self, run: Resource<workflows::Run>) -> Result<(&String, &Arc<dyn StepStore>)> {
+        let key = self.runs.get(run.rep()).context("invalid workflow run")?;
+        let store = self.store.as_ref().context("workflows are not available")?;
+        Ok((key, store))
+    }
+}
+
+impl Default for WorkflowsDispatch {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl workflows::Host for WorkflowsDispatch {}
+
+#[async_trait]
+impl workflows::HostRun for WorkflowsDispatch {
+    async fn open(
+        &mut self,
+        key: String,
+    ) -> Result<Result<Resource<workflows::Run>, WorkflowError>> {
+        // Runs are private to the component which opens them. The component
+        // ID can't contain a slash, so keys can't collide.
+        let run = format!("{}/{key}", self.component_id);
+        Ok(self
+            .runs
+            .push(run)
+            .map(Resource::new_own)
+            .map_err(|()| WorkflowError::Other("too many workflow runs opened".into())))
+    }
+
+    async fn get_step(
+        &mut self,
+        run: Resource<workflows::Run>,
+        step: String,
+    ) -> Result<Result<Option<Vec<u8>>, WorkflowError>> {
+        let (run, store) = self.get_run(run)?;
+        Ok(store.get_step(run, &step).await.map_err(log_error))
+    }
+
+    async fn record_step(
+        &mut self,
+        run: Resource<workflows::Run>,
+        step: String,
+        output: Vec<u8>,
+    ) -> Result<Result<(), WorkflowError>> {
+        let (run, store) = self.get_run(run)?;
+        Ok(store
+            .record_step(run, &step, &output)
+            .await
+            .map_err(log_error))
+    }
+
+    async fn finish(&mut self, run: Resource<workflows::Run>) -> Result<Result<(), WorkflowError>> {
+        let (run, store) = self.get_run(run)?;
+        Ok(store.finish(run).await.map_err(log_error))
+    }
+
+    fn drop(&mut self, run: Resource<workflows::Run>) -> Result<()> {
+        self.runs.remove(run.rep());
+        Ok(())
+    }
+}
+
+fn log_error(err: impl std::fmt::Debug) -> WorkflowError {
+    tracing::warn!("workflows error: {err:?}");
+    WorkflowError::Other(format!("{err:?}"))
+}
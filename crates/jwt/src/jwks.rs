@@ -0,0 +1,334 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use anyhow::{ensure, Context, Result};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD as BASE64URL, Engine};
+use ring::signature::{self, RsaPublicKeyComponents, UnparsedPublicKey};
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::{token::Unverified, Error};
+
+/// How long a key set is used before it is fetched again.
+const TTL: Duration = Duration::from_secs(10 * 60);
+/// How soon a key set may be fetched again when a token is signed by a key
+/// it doesn't have, e.g. because the keys have been rotated.
+const MIN_REFRESH_INTERVAL: Duration = Duration::from_secs(30);
+/// How long fetching a key set may take, from connecting to the end of its
+/// body.
+const FETCH_TIMEOUT: Duration = Duration::from_secs(10);
+/// The largest key set read. Sets hold a handful of keys, so are a few
+/// kilobytes at most.
+const MAX_JWKS_SIZE: usize = 1024 * 1024;
+
+/// JSON Web Key Sets, by URL, shared by every component of the app.
+///
+/// Key sets are fetched through the shared outbound HTTP pool.
+pub(crate) struct JwksCache {
+    sets: Mutex<HashMap<String, Fetched>>,
+}
+
+struct Fetched {
+    keys: Arc<Vec<Jwk>>,
+    at: Instant,
+}
+
+impl JwksCache {
+    pub fn new() -> Self {
+        Self {
+            sets: Default::default(),
+        }
+    }
+
+    /// Checks that `token` is signed by a key of the key set at `url`.
+    pub async fn verify(&self, url: &str, token: &Unverified<'_>) -> Result<(), Error> {
+        let keys = self.keys(url, TTL).await?;
+        let valid = match verify_with_any(&keys, token) {
+            Some(valid) => valid,
+            None => {
+                let keys = self.keys(url, MIN_REFRESH_INTERVAL).await?;
+                verify_with_any(&keys, token).unwrap_or(false)
+            }
+        };
+        if valid {
+            Ok(())
+        } else {
+            Err(Error::InvalidSignature)
+        }
+    }
+
+    /// Returns the key set at `url`, fetching it if it was fetched more than
+    /// `max_age` ago. If it can't be fetched, any earlier copy is used.
+    async fn keys(&self, url: &str, max_age: Duration) -> Result<Arc<Vec<Jwk>>, Error> {
+        let cached = self
+            .sets
+            .lock()
+            .unwrap()
+            .get(url)
+            .map(|fetched| (fetched.keys.clone(), fetched.at.elapsed() < max_age));
+        if let Some((keys, true)) = cached {
+            return Ok(keys);
+        }
+        match self.fetch(url).await {
+            Ok(keys) => {
+                let keys = Arc::new(keys);
+                let fetched = Fetched {
+                    keys: keys.clone(),
+                    at: Instant::now(),
+                };
+                self.sets.lock().unwrap().insert(url.to_owned(), fetched);
+                Ok(keys)
+            }
+            Err(e) => match cached {
+                Some((keys, _)) => {
+                    tracing::warn!("Failed to refresh key set {url}, using the cached keys: {e:#}");
+                    Ok(keys)
+                }
+                None => Err(Error::JwksUnavailable(format!("{e:#}"))),
+            },
+        }
+    }
+
+    async fn fetch(&self, url: &str) -> Result<Vec<Jwk>> {
+        let url = reqwest::Url::parse(url).with_context(|| format!("Invalid key set URL {url}"))?;
+        let _guard = outbound_http::pool::acquire(url.host_str().unwrap_or_default()).await;
+        let mut response = outbound_http::pool::client()
+            .get(url.clone())
+            .timeout(FETCH_TIMEOUT)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .with_context(|| format!("Failed to fetch key set {url}"))?;
+        let too_large = || format!("Key set {url} is larger than {MAX_JWKS_SIZE} bytes");
+        ensure!(
+            response
+                .content_length()
+                .map_or(true, |len| len <= MAX_JWKS_SIZE as u64),
+            too_large()
+        );
+        let mut body = Vec::new();
+        while let Some(chunk) = response
+            .chunk()
+            .await
+            .with_context(|| format!("Failed to fetch key set {url}"))?
+        {
+            ensure!(body.len() + chunk.len() <= MAX_JWKS_SIZE, too_large());
+            body.extend_from_slice(&chunk);
+        }
+        parse(&body)
+    }
+}
+
+/// Returns whether `token` is signed by one of `keys`, or `None` if none of
+/// them could have signed it.
+fn verify_with_any(keys: &[Jwk], token: &Unverified) -> Option<bool> {
+    let mut candidates = keys
+        .iter()
+        .filter(|key| key.could_have_signed(token))
+        .peekable();
+    candidates.peek()?;
+    Some(candidates.any(|key| key.verify(token)))
+}
+
+/// A public key from a JSON Web Key Set.
+#[derive(Debug)]
+pub(crate) struct Jwk {
+    kid: Option<String>,
+    alg: Option<String>,
+    key: PublicKey,
+}
+
+#[derive(Debug)]
+enum PublicKey {
+    Rsa { n: Vec<u8>, e: Vec<u8> },
+    P256(Vec<u8>),
+    P384(Vec<u8>),
+    Ed25519(Vec<u8>),
+}
+
+#[derive(Deserialize)]
+struct RawJwk {
+    kty: String,
+    #[serde(default)]
+    kid: Option<String>,
+    #[serde(default)]
+    alg: Option<String>,
+    #[serde(default, rename = "use")]
+    key_use: Option<String>,
+    #[serde(default)]
+    crv: Option<String>,
+    #[serde(default)]
+    n: Option<String>,
+    #[serde(default)]
+    e: Option<String>,
+    #[serde(default)]
+    x: Option<String>,
+    #[serde(default)]
+    y: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct RawJwks {
+    keys: Vec<Value>,
+}
+
+/// Parses a JSON Web Key Set, skipping keys which aren't signature keys of
+/// a supported type.
+pub(crate) fn parse(body: &[u8]) -> Result<Vec<Jwk>> {
+    let jwks: RawJwks = serde_json::from_slice(body).context("Invalid JSON Web Key Set")?;
+    Ok(jwks
+        .keys
+        .into_iter()
+        .filter_map(|key| Jwk::parse(serde_json::from_value(key).ok()?))
+        .collect())
+}
+
+impl Jwk {
+    fn parse(raw: RawJwk) -> Option<Self> {
+        if raw
+            .key_use
+            .as_deref()
+            .is_some_and(|key_use| key_use != "sig")
+        {
+            return None;
+        }
+        let decode = |part: Option<String>| BASE64URL.decode(part?).ok();
+        let point = |x, y| Some([&[4][..], &decode(x)?, &decode(y)?].concat());
+        let key = match (raw.kty.as_str(), raw.crv.as_deref()) {
+            ("RSA", _) => PublicKey::Rsa {
+                n: decode(raw.n)?,
+                e: decode(raw.e)?,
+            },
+            ("EC", Some("P-256")) => PublicKey::P256(point(raw.x, raw.y)?),
+            ("EC", Some("P-384")) => PublicKey::P384(point(raw.x, raw.y)?),
+            ("OKP", Some("Ed25519")) => PublicKey::Ed25519(decode(raw.x)?),
+            _ => return None,
+        };
+        Some(Self {
+            kid: raw.kid,
+            alg: raw.alg,
+            key,
+        })
+    }
+
+    fn could_have_signed(&self, token: &Unverified) -> bool {
+        let header = &token.header;
+        (header.kid.is_none() || self.kid == header.kid)
+            && self.alg.as_ref().map_or(true, |alg| *alg == header.alg)
+            && self.algorithm(&header.alg).is_some()
+    }
+
+    fn verify(&self, token: &Unverified) -> bool {
+        let (message, signature) = (token.signing_input.as_bytes(), &token.signature);
+        match (self.algorithm(&token.header.alg), &self.key) {
+            (Some(Algorithm::Rsa(parameters)), PublicKey::Rsa { n, e }) => {
+                RsaPublicKeyComponents { n, e }
+                    .verify(parameters, message, signature)
+                    .is_ok()
+            }
+            (
+                Some(Algorithm::Other(algorithm)),
+                PublicKey::P256(key) | PublicKey::P384(key) | PublicKey::Ed25519(key),
+            ) => UnparsedPublicKey::new(algorithm, key)
+                .verify(message, signature)
+                .is_ok(),
+            _ => false,
+        }
+    }
+
+    /// Returns how this key verifies the JWS algorithm `alg`, if it can.
+    fn algorithm(&self, alg: &str) -> Option<Algorithm> {
+        let algorithm = match (&self.key, alg) {
+            (PublicKey::Rsa { .. }, "RS256") => {
+                Algorithm::Rsa(&signature::RSA_PKCS1_2048_8192_SHA256)
+            }
+            (PublicKey::Rsa { .. }, "RS384") => {
+                Algorithm::Rsa(&signature::RSA_PKCS1_2048_8192_SHA384)
+            }
+            (PublicKey::Rsa { .. }, "RS512") => {
+                Algorithm::Rsa(&signature::RSA_PKCS1_2048_8192_SHA512)
+            }
+            (PublicKey::Rsa { .. }, "PS256") => {
+                Algorithm::Rsa(&signature::RSA_PSS_2048_8192_SHA256)
+            }
+            (PublicKey::Rsa { .. }, "PS384") => {
+                Algorithm::Rsa(&signature::RSA_PSS_2048_8192_SHA384)
+            }
+            (PublicKey::Rsa { .. }, "PS512") => {
+                Algorithm::Rsa(&signature::RSA_PSS_2048_8192_SHA512)
+            }
+            (PublicKey::P256(_), "ES256") => Algorithm::Other(&signature::ECDSA_P256_SHA256_FIXED),
+            (PublicKey::P384(_), "ES384") => Algorithm::Other(&signature::ECDSA_P384_SHA384_FIXED),
+            (PublicKey::Ed25519(_), "EdDSA") => Algorithm::Other(&signature::ED25519),
+            _ => return None,
+        };
+        Some(algorithm)
+    }
+}
+
+enum Algorithm {
+    Rsa(&'static signature::RsaParameters),
+    Other(&'static dyn signature::VerificationAlgorithm),
+}
+
+#[cfg(test)]
+mod test {
+    use ring::{
+        rand::SystemRandom,
+        signature::{EcdsaKeyPair, KeyPair},
+    };
+
+    use super::*;
+    use crate::token;
+
+    #[test]
+    fn tokens_are_verified_with_key_sets() {
+        let rng = SystemRandom::new();
+        let pkcs8 = EcdsaKeyPair::generate_pkcs8(&signature::ECDSA_P256_SHA256_FIXED_SIGNING, &rng)
+            .unwrap();
+        let key = spin_crypto::Key::ecdsa_p256(pkcs8.as_ref()).unwrap();
+        let key_pair =
+            EcdsaKeyPair::from_pkcs8(&signature::ECDSA_P256_SHA256_FIXED_SIGNING, pkcs8.as_ref())
+                .unwrap();
+        let point = key_pair.public_key().as_ref();
+        let jwks = serde_json::json!({
+            "keys": [
+                { "kty": "EC", "use": "enc", "crv": "P-256", "kid": "key-1",
+                  "x": BASE64URL.encode(&point[1..33]), "y": BASE64URL.encode(&point[33..]) },
+                { "kty": "EC", "crv": "P-256", "kid": "key-1", "alg": "ES256",
+                  "x": BASE64URL.encode(&point[1..33]), "y": BASE64URL.encode(&point[33..]) },
+                { "kty": "oct", "k": "c2VjcmV0" },
+            ]
+        });
+        let keys = parse(jwks.to_string().as_bytes()).unwrap();
+        assert_eq!(1, keys.len());
+
+        let signed = token::sign(&key, "{}", Some("key-1".into())).unwrap();
+        assert_eq!(
+            Some(true),
+            verify_with_any(&keys, &token::parse(&signed).unwrap())
+        );
+
+        let (header, rest) = signed.split_once('.').unwrap();
+        let (_, signature) = rest.split_once('.').unwrap();
+        let claims = BASE64URL.encode(r#"{"admin":true}"#);
+        let tampered = format!("{header}.{claims}.{signature}");
+        assert_eq!(
+            Some(false),
+            verify_with_any(&keys, &token::parse(&tampered).unwrap())
+        );
+
+        // Tokens signed by a key the set doesn't have cause it to be fetched
+        // again.
+        let rotated = token::sign(&key, "{}", Some("key-2".into())).unwrap();
+        assert_eq!(
+            None,
+            verify_with_any(&keys, &token::parse(&rotated).unwrap())
+        );
+        let hmac = token::sign(&spin_crypto::Key::hmac_sha256(b"secret"), "{}", None).unwrap();
+        assert_eq!(None, verify_with_any(&keys, &token::parse(&hmac).unwrap()));
+    }
+}
@@ -0,0 +1,145 @@
+//! JSON Web Tokens signed and verified by the host, with the keys of the
+//! crypto interface or JSON Web Key Sets which the host fetches and caches.
+
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{Context, Result};
+use spin_app::{AppComponent, DynamicHostComponent};
+use spin_core::{async_trait, HostComponent};
+use spin_crypto::{Key, CRYPTO_KEYS_KEY};
+use spin_outbound_networking::{AllowedHostsConfig, ALLOWED_HOSTS_KEY};
+use spin_world::v3::jwt;
+
+mod jwks;
+mod token;
+
+use jwks::JwksCache;
+
+pub use jwt::{Error, Verifier, VerifyOptions};
+
+pub struct JwtComponent {
+    keys: Arc<HashMap<String, Arc<Key>>>,
+    jwks: Arc<JwksCache>,
+}
+
+impl JwtComponent {
+    /// A component which signs and verifies tokens with the given crypto
+    /// keys, by label.
+    pub fn new(keys: Arc<HashMap<String, Arc<Key>>>) -> Self {
+        Self {
+            keys,
+            jwks: Arc::new(JwksCache::new()),
+        }
+    }
+}
+
+impl HostComponent for JwtComponent {
+    type Data = JwtDispatch;
+
+    fn add_to_linker<T: Send>(
+        linker: &mut spin_core::Linker<T>,
+        get: impl Fn(&mut spin_core::Data<T>) -> &mut Self::Data + Send + Sync + Copy + 'static,
+    ) -> anyhow::Result<()> {
+        jwt::add_to_linker(linker, get)
+    }
+
+    fn build_data(&self) -> Self::Data {
+        JwtDispatch {
+            allowed_keys: HashSet::new(),
+            allowed_hosts: AllowedHostsConfig::default(),
+            keys: self.keys.clone(),
+            jwks: self.jwks.clone(),
+        }
+    }
+}
+
+impl DynamicHostComponent for JwtComponent {
+    fn update_data(&self, data: &mut Self::Data, component: &AppComponent) -> anyhow::Result<()> {
+        data.allowed_keys = component.get_metadata(CRYPTO_KEYS_KEY)?.unwrap_or_default();
+        let hosts = component
+            .get_metadata(ALLOWED_HOSTS_KEY)?
+            .unwrap_or_default();
+        data.allowed_hosts = AllowedHostsConfig::parse(&hosts)
+            .context("`allowed_outbound_hosts` contained an invalid url")?;
+        Ok(())
+    }
+}
+
+pub struct JwtDispatch {
+    allowed_keys: HashSet<String>,
+    allowed_hosts: AllowedHostsConfig,
+    keys: Arc<HashMap<String, Arc<Key>>>,
+    jwks: Arc<JwksCache>,
+}
+
+impl JwtDispatch {
+    fn key(&self, label: &str) -> Result<&Key, Error> {
+        if !self.allowed_keys.contains(label) {
+            return Err(Error::AccessDenied);
+        }
+        self.keys
+            .get(label)
+            .map(|key| &**key)
+            .ok_or(Error::NoSuchKey)
+    }
+
+    async fn verify_token(
+        &self,
+        token: &str,
+        verifier: Verifier,
+        options: VerifyOptions,
+    ) -> Result<String, Error> {
+        let token = token::parse(token)?;
+        match verifier {
+            Verifier::Key(label) => token::verify_with_key(&token, self.key(&label)?)?,
+            Verifier::Jwks(url) => {
+                if !spin_outbound_networking::check_url(&url, "https", &self.allowed_hosts) {
+                    return Err(Error::AccessDenied);
+                }
+                self.jwks.verify(&url, &token).await?
+            }
+        }
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_err(|e| Error::Other(e.to_string()))?
+            .as_secs();
+        token::check_claims(token, &options, now)
+    }
+}
+
+#[async_trait]
+impl jwt::Host for JwtDispatch {
+    async fn sign(
+        &mut self,
+        key: String,
+        claims: String,
+        key_id: Option<String>,
+    ) -> Result<Result<String, Error>> {
+        Ok(self
+            .key(&key)
+            .and_then(|key| token::sign(key, &claims, key_id)))
+    }
+
+    async fn verify(
+        &mut self,
+        token: String,
+        verifier: Verifier,
+        options: VerifyOptions,
+    ) -> Result<Result<String, Error>> {
+        Ok(self.verify_token(&token, verifier, options).await)
+    }
+}
+
+fn crypto_error(err: spin_crypto::Error) -> Error {
+    match err {
+        spin_crypto::Error::AccessDenied => Error::AccessDenied,
+        spin_crypto::Error::NoSuchKey => Error::NoSuchKey,
+        spin_crypto::Error::WrongKeyType => Error::WrongKeyType,
+        spin_crypto::Error::DecryptionFailed => Error::Other("decryption failed".into()),
+        spin_crypto::Error::Other(e) => Error::Other(e),
+    }
+}
@@ -0,0 +1,245 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD as BASE64URL, Engine};
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use spin_crypto::Key;
+
+use crate::{crypto_error, Error, VerifyOptions};
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct Header {
+    pub alg: String,
+    #[serde(default)]
+    pub kid: Option<String>,
+}
+
+/// A token split into its parts, whose signature is not yet verified.
+pub(crate) struct Unverified<'a> {
+    pub header: Header,
+    pub signing_input: &'a str,
+    pub claims: Vec<u8>,
+    pub signature: Vec<u8>,
+}
+
+pub(crate) fn sign(key: &Key, claims: &str, key_id: Option<String>) -> Result<String, Error> {
+    let alg = key.jws_algorithm().ok_or(Error::WrongKeyType)?;
+    let claims: Map<String, Value> = serde_json::from_str(claims).map_err(|_| Error::Malformed)?;
+    let mut header = Map::new();
+    header.insert("alg".into(), alg.into());
+    header.insert("typ".into(), "JWT".into());
+    if let Some(kid) = key_id {
+        header.insert("kid".into(), kid.into());
+    }
+    let signing_input = format!("{}.{}", encode(header), encode(claims));
+    let signature = if is_hmac(alg) {
+        key.hmac(signing_input.as_bytes())
+    } else {
+        key.sign(signing_input.as_bytes())
+    }
+    .map_err(crypto_error)?;
+    Ok(format!("{signing_input}.{}", BASE64URL.encode(signature)))
+}
+
+pub(crate) fn parse(token: &str) -> Result<Unverified<'_>, Error> {
+    let mut parts = token.split('.');
+    let (Some(header), Some(claims), Some(signature), None) =
+        (parts.next(), parts.next(), parts.next(), parts.next())
+    else {
+        return Err(Error::Malformed);
+    };
+    let signing_input = &token[..header.len() + 1 + claims.len()];
+    Ok(Unverified {
+        header: serde_json::from_slice(&decode(header)?).map_err(|_| Error::Malformed)?,
+        signing_input,
+        claims: decode(claims)?,
+        signature: decode(signature)?,
+    })
+}
+
+/// Checks that `token` is signed by `key`, with the key's algorithm.
+pub(crate) fn verify_with_key(token: &Unverified, key: &Key) -> Result<(), Error> {
+    let alg = key.jws_algorithm().ok_or(Error::WrongKeyType)?;
+    if token.header.alg != alg {
+        return Err(Error::InvalidSignature);
+    }
+    let message = token.signing_input.as_bytes();
+    let valid = if is_hmac(alg) {
+        key.verify_hmac(message, &token.signature)
+    } else {
+        key.verify(message, &token.signature)
+    }
+    .map_err(crypto_error)?;
+    if valid {
+        Ok(())
+    } else {
+        Err(Error::InvalidSignature)
+    }
+}
+
+/// Checks the claims of a token whose signature is verified against
+/// `options` at `now`, in seconds since the epoch, and returns them.
+pub(crate) fn check_claims(
+    token: Unverified,
+    options: &VerifyOptions,
+    now: u64,
+) -> Result<String, Error> {
+    let claims: Map<String, Value> =
+        serde_json::from_slice(&token.claims).map_err(|_| Error::Malformed)?;
+    if let Some(exp) = claims.get("exp") {
+        if now >= numeric_date(exp)?.saturating_add(options.leeway) {
+            return Err(Error::Expired);
+        }
+    }
+    if let Some(nbf) = claims.get("nbf") {
+        if now.saturating_add(options.leeway) < numeric_date(nbf)? {
+            return Err(Error::NotYetValid);
+        }
+    }
+    if let Some(issuer) = &options.issuer {
+        if claims.get("iss").and_then(Value::as_str) != Some(issuer) {
+            return Err(Error::InvalidClaims(format!(
+                "the token was not issued by {issuer:?}"
+            )));
+        }
+    }
+    if let Some(audience) = &options.audience {
+        let matches = match claims.get("aud") {
+            Some(Value::String(aud)) => aud == audience,
+            Some(Value::Array(auds)) => auds.iter().any(|aud| aud.as_str() == Some(audience)),
+            _ => false,
+        };
+        if !matches {
+            return Err(Error::InvalidClaims(format!(
+                "the token is not for the audience {audience:?}"
+            )));
+        }
+    }
+    String::from_utf8(token.claims).map_err(|_| Error::Malformed)
+}
+
+fn is_hmac(alg: &str) -> bool {
+    alg.starts_with("HS")
+}
+
+/// Returns a JWT NumericDate, rounded down to whole seconds.
+fn numeric_date(value: &Value) -> Result<u64, Error> {
+    match value.as_u64() {
+        Some(seconds) => Ok(seconds),
+        None => value
+            .as_f64()
+            .filter(|seconds| *seconds >= 0.0)
+            .map(|seconds| seconds as u64)
+            .ok_or(Error::Malformed),
+    }
+}
+
+fn encode(object: Map<String, Value>) -> String {
+    BASE64URL.encode(Value::Object(object).to_string())
+}
+
+fn decode(part: &str) -> Result<Vec<u8>, Error> {
+    BASE64URL.decode(part).map_err(|_| Error::Malformed)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const NOW: u64 = 1_700_000_000;
+
+    fn options() -> VerifyOptions {
+        VerifyOptions {
+            issuer: None,
+            audience: None,
+            leeway: 0,
+        }
+    }
+
+    fn verify(token: &str, key: &Key, options: &VerifyOptions) -> Result<String, Error> {
+        let token = parse(token)?;
+        verify_with_key(&token, key)?;
+        check_claims(token, options, NOW)
+    }
+
+    #[test]
+    fn tokens_are_signed_and_verified() {
+        let key = Key::hmac_sha256(b"secret");
+        let token = sign(&key, r#"{"sub":"user-1"}"#, Some("key-1".into())).unwrap();
+        let header = parse(&token).unwrap().header;
+        assert_eq!(
+            ("HS256", Some("key-1")),
+            (&*header.alg, header.kid.as_deref())
+        );
+        assert_eq!(
+            r#"{"sub":"user-1"}"#,
+            verify(&token, &key, &options()).unwrap()
+        );
+
+        let other = Key::hmac_sha256(b"other secret");
+        assert!(matches!(
+            verify(&token, &other, &options()),
+            Err(Error::InvalidSignature)
+        ));
+        // The same secret with another algorithm is not accepted either.
+        let other = Key::hmac_sha512(b"secret");
+        assert!(matches!(
+            verify(&token, &other, &options()),
+            Err(Error::InvalidSignature)
+        ));
+        assert!(matches!(
+            verify("not.a token", &key, &options()),
+            Err(Error::Malformed)
+        ));
+        assert!(matches!(sign(&key, "[]", None), Err(Error::Malformed)));
+    }
+
+    #[test]
+    fn unsigned_tokens_are_rejected() {
+        let key = Key::hmac_sha256(b"secret");
+        let header = BASE64URL.encode(r#"{"alg":"none"}"#);
+        let claims = BASE64URL.encode(r#"{"sub":"user-1"}"#);
+        assert!(matches!(
+            verify(&format!("{header}.{claims}."), &key, &options()),
+            Err(Error::InvalidSignature)
+        ));
+    }
+
+    #[test]
+    fn claims_are_checked() {
+        let key = Key::hmac_sha256(b"secret");
+        let token = |claims: &str| sign(&key, claims, None).unwrap();
+
+        let expiring = token(&format!(r#"{{"exp":{NOW}}}"#));
+        assert!(matches!(
+            verify(&expiring, &key, &options()),
+            Err(Error::Expired)
+        ));
+        let leeway = VerifyOptions {
+            leeway: 30,
+            ..options()
+        };
+        assert!(verify(&expiring, &key, &leeway).is_ok());
+
+        let future = token(&format!(r#"{{"nbf":{}}}"#, NOW + 10));
+        assert!(matches!(
+            verify(&future, &key, &options()),
+            Err(Error::NotYetValid)
+        ));
+        assert!(verify(&future, &key, &leeway).is_ok());
+
+        let scoped = token(r#"{"iss":"https://auth.example.com","aud":["api","web"]}"#);
+        let required = VerifyOptions {
+            issuer: Some("https://auth.example.com".into()),
+            audience: Some("api".into()),
+            leeway: 0,
+        };
+        assert!(verify(&scoped, &key, &required).is_ok());
+        let elsewhere = VerifyOptions {
+            audience: Some("admin".into()),
+            ..required
+        };
+        assert!(matches!(
+            verify(&scoped, &key, &elsewhere),
+            Err(Error::InvalidClaims(_))
+        ));
+    }
+}
@@ -0,0 +1,499 @@
+use std::{
+    collections::{hash_map::RandomState, HashMap, HashSet},
+    hash::{BuildHasher, Hasher},
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::Result;
+use aws_sdk_dynamodb::{
+    primitives::Blob,
+    types::{AttributeValue, DeleteRequest, KeysAndAttributes, PutRequest, WriteRequest},
+    Client,
+};
+use spin_core::async_trait;
+use spin_key_value::{log_error, Error, Scan, Store, StoreManager};
+use tokio::sync::OnceCell;
+
+/// The partition key of the table, a string attribute holding the key.
+const KEY: &str = "key";
+/// A binary attribute holding the value.
+const VALUE: &str = "value";
+/// A numeric attribute holding the expiry time in seconds since the Unix epoch.
+///
+/// This may be configured as the table's TTL attribute so that DynamoDB deletes expired items, but as that
+/// can happen up to days later, expired items are also ignored on read.
+const EXPIRES_AT: &str = "expires_at";
+
+/// The most keys DynamoDB accepts in a `BatchGetItem` request.
+const BATCH_GET_SIZE: usize = 100;
+/// The most items DynamoDB accepts in a `BatchWriteItem` request.
+const BATCH_WRITE_SIZE: usize = 25;
+/// How many times unprocessed batch items are retried before giving up.
+const MAX_BATCH_RETRIES: u32 = 8;
+/// The longest wait before the first retry of unprocessed items; each retry
+/// may wait up to twice as long as the one before.
+const BASE_BACKOFF: Duration = Duration::from_millis(50);
+/// The longest wait before any retry.
+const MAX_BACKOFF: Duration = Duration::from_secs(5);
+
+type Item = HashMap<String, AttributeValue>;
+
+pub struct KeyValueAwsDynamo {
+    table: String,
+    region: Option<String>,
+    endpoint: Option<String>,
+    consistent_read: bool,
+    client: OnceCell<Client>,
+}
+
+impl KeyValueAwsDynamo {
+    /// Credentials, and the region if it isn't given, are found by the AWS SDK's standard provider chain:
+    /// environment variables, the shared config and credentials files, then the container or instance role.
+    pub fn new(
+        table: String,
+        region: Option<String>,
+        endpoint: Option<String>,
+        consistent_read: bool,
+    ) -> Self {
+        Self {
+            table,
+            region,
+            endpoint,
+            consistent_read,
+            client: OnceCell::new(),
+        }
+    }
+}
+
+#[async_trait]
+impl StoreManager for KeyValueAwsDynamo {
+    async fn get(&self, _name: &str) -> Result<Arc<dyn Store>, Error> {
+        let client = self
+            .client
+            .get_or_init(|| async {
+                let mut loader = aws_config::from_env();
+                if let Some(region) = &self.region {
+                    loader = loader.region(aws_config::Region::new(region.clone()));
+                }
+                if let Some(endpoint) = &self.endpoint {
+                    loader = loader.endpoint_url(endpoint);
+                }
+                Client::new(&loader.load().await)
+            })
+            .await;
+
+        Ok(Arc::new(AwsDynamoStore {
+            client: client.clone(),
+            table: self.table.clone(),
+            consistent_read: self.consistent_read,
+        }))
+    }
+
+    fn is_defined(&self, _store_name: &str) -> bool {
+        true
+    }
+}
+
+struct AwsDynamoStore {
+    client: Client,
+    table: String,
+    consistent_read: bool,
+}
+
+#[async_trait]
+impl Store for AwsDynamoStore {
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, Error> {
+        let item = self.get_item(key).await?;
+        item.as_ref().map(item_value).transpose()
+    }
+
+    async fn set(&self, key: &str, value: &[u8]) -> Result<(), Error> {
+        self.put(key, value, None).await
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), Error> {
+        self.client
+            .delete_item()
+            .table_name(&self.table)
+            .key(KEY, key_attribute(key))
+            .send()
+            .await
+            .map_err(log_error)?;
+        Ok(())
+    }
+
+    async fn exists(&self, key: &str) -> Result<bool, Error> {
+        Ok(self.get_item(key).await?.is_some())
+    }
+
+    async fn get_keys(&self) -> Result<Vec<String>, Error> {
+        let mut keys = Vec::new();
+        let mut start = None;
+        loop {
+            let page = self.scan_page(None, start, None).await?;
+            keys.extend(page.0);
+            match page.1 {
+                Some(next) => start = Some(next),
+                None => return Ok(keys),
+            }
+        }
+    }
+
+    async fn set_with_ttl(&self, key: &str, value: &[u8], ttl: Duration) -> Result<(), Error> {
+        self.put(key, value, Some(ttl)).await
+    }
+
+    async fn expire(&self, key: &str, ttl: Duration) -> Result<bool, Error> {
+        if ttl.is_zero() {
+            let existed = self.exists(key).await?;
+            self.delete(key).await?;
+            return Ok(existed);
+        }
+        let result = self
+            .client
+            .update_item()
+            .table_name(&self.table)
+            .key(KEY, key_attribute(key))
+            .update_expression("SET #e = :e")
+            .condition_expression(
+                "attribute_exists(#k) AND (attribute_not_exists(#e) OR #e > :now)",
+            )
+            .expression_attribute_names("#k", KEY)
+            .expression_attribute_names("#e", EXPIRES_AT)
+            .expression_attribute_values(":e", number(now() + ttl.as_secs()))
+            .expression_attribute_values(":now", number(now()))
+            .send()
+            .await;
+        match result {
+            Ok(_) => Ok(true),
+            Err(e) => {
+                let e = e.into_service_error();
+                if e.is_conditional_check_failed_exception() {
+                    Ok(false)
+                } else {
+                    Err(log_error(e))
+                }
+            }
+        }
+    }
+
+    async fn set_if_absent(
+        &self,
+        key: &str,
+        value: &[u8],
+        ttl: Option<Duration>,
+    ) -> Result<bool, Error> {
+        let result = self
+            .client
+            .put_item()
+            .table_name(&self.table)
+            .set_item(Some(item(key, value, ttl)))
+            .condition_expression("attribute_not_exists(#k) OR #e <= :now")
+            .expression_attribute_names("#k", KEY)
+            .expression_attribute_names("#e", EXPIRES_AT)
+            .expression_attribute_values(":now", number(now()))
+            .send()
+            .await;
+        match result {
+            Ok(_) => Ok(true),
+            Err(e) => {
+                let e = e.into_service_error();
+                if e.is_conditional_check_failed_exception() {
+                    Ok(false)
+                } else {
+                    Err(log_error(e))
+                }
+            }
+        }
+    }
+
+    async fn get_many(&self, keys: &[String]) -> Result<Vec<(String, Option<Vec<u8>>)>, Error> {
+        let mut values = HashMap::new();
+        for chunk in keys.chunks(BATCH_GET_SIZE) {
+            let mut pending = KeysAndAttributes::builder()
+                .set_keys(Some(
+                    chunk
+                        .iter()
+                        .map(|key| HashMap::from([(KEY.to_owned(), key_attribute(key))]))
+                        .collect(),
+                ))
+                .consistent_read(self.consistent_read)
+                .build()
+                .map_err(log_error)?;
+            for attempt in 0.. {
+                let output = self
+                    .client
+                    .batch_get_item()
+                    .request_items(&self.table, pending)
+                    .send()
+                    .await
+                    .map_err(log_error)?;
+                if let Some(items) = output.responses().and_then(|r| r.get(&self.table)) {
+                    for item in items.iter().filter(|item| live(item)) {
+                        values.insert(item_key(item)?, item_value(item)?);
+                    }
+                }
+                match output
+                    .unprocessed_keys()
+                    .and_then(|u| u.get(&self.table))
+                    .filter(|u| !u.keys().is_empty())
+                {
+                    Some(unprocessed) if attempt < MAX_BATCH_RETRIES => {
+                        pending = unprocessed.clone();
+                        backoff(attempt).await;
+                    }
+                    Some(unprocessed) => {
+                        return Err(log_error(format!(
+                            "DynamoDB left {} keys unprocessed after {MAX_BATCH_RETRIES} retries",
+                            unprocessed.keys().len()
+                        )));
+                    }
+                    None => break,
+                }
+            }
+        }
+        Ok(keys
+            .iter()
+            .map(|key| (key.clone(), values.remove(key)))
+            .collect())
+    }
+
+    async fn set_many(&self, key_values: &[(String, Vec<u8>)]) -> Result<(), Error> {
+        // DynamoDB rejects a batch which writes a key twice, so only the last
+        // value given for each key is written.
+        let requests = last_for_each_key(key_values, |(key, _)| key)
+            .into_iter()
+            .map(|(key, value)| {
+                let put = PutRequest::builder()
+                    .set_item(Some(item(key, value, None)))
+                    .build()
+                    .map_err(log_error)?;
+                Ok(WriteRequest::builder().put_request(put).build())
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+        self.batch_write(requests).await
+    }
+
+    async fn delete_many(&self, keys: &[String]) -> Result<(), Error> {
+        let requests = last_for_each_key(keys, |key| key)
+            .into_iter()
+            .map(|key| {
+                let delete = DeleteRequest::builder()
+                    .key(KEY, key_attribute(key))
+                    .build()
+                    .map_err(log_error)?;
+                Ok(WriteRequest::builder().delete_request(delete).build())
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+        self.batch_write(requests).await
+    }
+
+    /// Scans the table with a `begins_with` filter, using the last evaluated key as the cursor. As DynamoDB
+    /// applies `limit` before filtering, pages may have fewer keys than `limit`, or none, before the end.
+    async fn scan(&self, prefix: &str, cursor: Option<&str>, limit: u32) -> Result<Scan, Error> {
+        let limit = limit.try_into().unwrap_or(i32::MAX);
+        let (keys, cursor) = self
+            .scan_page(Some(prefix), cursor.map(str::to_owned), Some(limit))
+            .await?;
+        Ok(Scan { keys, cursor })
+    }
+}
+
+impl AwsDynamoStore {
+    async fn get_item(&self, key: &str) -> Result<Option<Item>, Error> {
+        let output = self
+            .client
+            .get_item()
+            .table_name(&self.table)
+            .key(KEY, key_attribute(key))
+            .consistent_read(self.consistent_read)
+            .send()
+            .await
+            .map_err(log_error)?;
+        Ok(output.item.filter(live))
+    }
+
+    async fn put(&self, key: &str, value: &[u8], ttl: Option<Duration>) -> Result<(), Error> {
+        self.client
+            .put_item()
+            .table_name(&self.table)
+            .set_item(Some(item(key, value, ttl)))
+            .send()
+            .await
+            .map_err(log_error)?;
+        Ok(())
+    }
+
+    /// Returns the live keys of one page of a scan, and the key to start the next page from.
+    async fn scan_page(
+        &self,
+        prefix: Option<&str>,
+        start: Option<String>,
+        limit: Option<i32>,
+    ) -> Result<(Vec<String>, Option<String>), Error> {
+        let mut request = self
+            .client
+            .scan()
+            .table_name(&self.table)
+            .projection_expression("#k, #e")
+            .expression_attribute_names("#k", KEY)
+            .expression_attribute_names("#e", EXPIRES_AT)
+            .consistent_read(self.consistent_read)
+            .set_exclusive_start_key(
+                start.map(|key| HashMap::from([(KEY.to_owned(), key_attribute(&key))])),
+            )
+            .set_limit(limit);
+        if let Some(prefix) = prefix.filter(|p| !p.is_empty()) {
+            request = request
+                .filter_expression("begins_with(#k, :prefix)")
+                .expression_attribute_values(":prefix", key_attribute(prefix));
+        }
+        let output = request.send().await.map_err(log_error)?;
+        let keys = output
+            .items()
+            .iter()
+            .filter(|item| live(item))
+            .map(item_key)
+            .collect::<Result<_, _>>()?;
+        let next = output.last_evaluated_key().map(item_key).transpose()?;
+        Ok((keys, next))
+    }
+
+    async fn batch_write(&self, requests: Vec<WriteRequest>) -> Result<(), Error> {
+        for chunk in requests.chunks(BATCH_WRITE_SIZE) {
+            let mut pending = chunk.to_vec();
+            let mut attempt = 0;
+            while !pending.is_empty() {
+                let output = self
+                    .client
+                    .batch_write_item()
+                    .request_items(&self.table, pending)
+                    .send()
+                    .await
+                    .map_err(log_error)?;
+                pending = output
+                    .unprocessed_items()
+                    .and_then(|u| u.get(&self.table))
+                    .cloned()
+                    .unwrap_or_default();
+                if pending.is_empty() {
+                    break;
+                }
+                if attempt == MAX_BATCH_RETRIES {
+                    return Err(log_error(format!(
+                        "DynamoDB left {} items unprocessed after {MAX_BATCH_RETRIES} retries",
+                        pending.len()
+                    )));
+                }
+                backoff(attempt).await;
+                attempt += 1;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn item(key: &str, value: &[u8], ttl: Option<Duration>) -> Item {
+    let mut item = HashMap::from([
+        (KEY.to_owned(), key_attribute(key)),
+        (VALUE.to_owned(), AttributeValue::B(Blob::new(value))),
+    ]);
+    if let Some(ttl) = ttl {
+        item.insert(EXPIRES_AT.to_owned(), number(now() + ttl.as_secs()));
+    }
+    item
+}
+
+fn key_attribute(key: &str) -> AttributeValue {
+    AttributeValue::S(key.to_owned())
+}
+
+fn number(n: u64) -> AttributeValue {
+    AttributeValue::N(n.to_string())
+}
+
+fn item_key(item: &Item) -> Result<String, Error> {
+    match item.get(KEY) {
+        Some(AttributeValue::S(key)) => Ok(key.clone()),
+        other => Err(log_error(format!("item has an invalid key: {other:?}"))),
+    }
+}
+
+fn item_value(item: &Item) -> Result<Vec<u8>, Error> {
+    match item.get(VALUE) {
+        Some(AttributeValue::B(value)) => Ok(value.as_ref().to_vec()),
+        other => Err(log_error(format!("item has an invalid value: {other:?}"))),
+    }
+}
+
+/// Whether an item has not expired.
+fn live(item: &Item) -> bool {
+    match item.get(EXPIRES_AT) {
+        Some(AttributeValue::N(expires_at)) => {
+            expires_at.parse::<u64>().map_or(true, |e| e > now())
+        }
+        _ => true,
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+/// The last of the items with each key, in the order of their last
+/// occurrence.
+fn last_for_each_key<'a, T>(items: &'a [T], key: impl Fn(&T) -> &String) -> Vec<&'a T> {
+    let mut seen = HashSet::new();
+    let mut last = items
+        .iter()
+        .rev()
+        .filter(|item| seen.insert(key(item)))
+        .collect::<Vec<_>>();
+    last.reverse();
+    last
+}
+
+/// Waits before retrying unprocessed items, which DynamoDB returns when throttling.
+async fn backoff(attempt: u32) {
+    // A random fraction, so that clients throttled together don't retry together.
+    let jitter = RandomState::new().build_hasher().finish() as f64 / u64::MAX as f64;
+    tokio::time::sleep(backoff_delay(attempt, jitter)).await;
+}
+
+/// The wait before retry `attempt`: a `jitter` fraction of a limit which
+/// doubles with each attempt ("full jitter").
+fn backoff_delay(attempt: u32, jitter: f64) -> Duration {
+    BASE_BACKOFF
+        .saturating_mul(1 << attempt.min(16))
+        .min(MAX_BACKOFF)
+        .mul_f64(jitter.clamp(0.0, 1.0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backoff_grows_to_a_limit() {
+        assert_eq!(Duration::from_millis(50), backoff_delay(0, 1.0));
+        assert_eq!(Duration::from_millis(200), backoff_delay(2, 1.0));
+        assert_eq!(Duration::from_millis(100), backoff_delay(2, 0.5));
+        assert_eq!(MAX_BACKOFF, backoff_delay(MAX_BATCH_RETRIES, 1.0));
+        assert_eq!(MAX_BACKOFF, backoff_delay(u32::MAX, 1.0));
+        assert_eq!(Duration::ZERO, backoff_delay(3, 0.0));
+    }
+
+    #[test]
+    fn the_last_value_for_a_key_wins() {
+        let key_values = [
+            ("a".to_owned(), b"1".to_vec()),
+            ("b".to_owned(), b"2".to_vec()),
+            ("a".to_owned(), b"3".to_vec()),
+        ];
+        let last = last_for_each_key(&key_values, |(key, _)| key);
+        assert_eq!(vec![&key_values[1], &key_values[2]], last);
+    }
+}
@@ -0,0 +1,313 @@
+use std::{
+    sync::{Arc, Weak},
+    time::Duration,
+};
+
+use anyhow::{Context, Result};
+use native_tls::TlsConnector;
+use postgres_native_tls::MakeTlsConnector;
+use spin_core::async_trait;
+use spin_key_value::{log_error, Error, Scan, Store, StoreManager};
+use tokio::{sync::OnceCell, task};
+use tokio_postgres::{config::SslMode, Client, NoTls};
+
+/// How often expired tuples are deleted from the database.
+const SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Tuples with an expiry are filtered by this condition. Expiry times are in
+/// seconds since the Unix epoch, by the database's clock.
+const NOT_EXPIRED: &str = "(expires_at IS NULL OR expires_at > extract(epoch FROM now()))";
+
+/// The expiry time of a tuple set now, with `$ttl` the time to live in seconds.
+const EXPIRES_AT: &str = "extract(epoch FROM now())::bigint + $ttl";
+
+pub struct KeyValuePostgres {
+    config: tokio_postgres::Config,
+    client: OnceCell<Arc<Client>>,
+}
+
+impl KeyValuePostgres {
+    pub fn new(connection_string: &str) -> Result<Self> {
+        let config = connection_string
+            .parse()
+            .context("Invalid Postgres connection string")?;
+        Ok(Self {
+            config,
+            client: OnceCell::new(),
+        })
+    }
+
+    async fn connect(&self) -> Result<Client> {
+        let client = if self.config.get_ssl_mode() == SslMode::Disable {
+            let (client, connection) = self.config.connect(NoTls).await?;
+            task::spawn(connection);
+            client
+        } else {
+            let connector = MakeTlsConnector::new(TlsConnector::builder().build()?);
+            let (client, connection) = self.config.connect(connector).await?;
+            task::spawn(connection);
+            client
+        };
+
+        // Keys are compared bytewise so that scans are ordered as in other stores.
+        client
+            .batch_execute(
+                r#"CREATE TABLE IF NOT EXISTS spin_key_value (
+                     store      TEXT NOT NULL,
+                     key        TEXT COLLATE "C" NOT NULL,
+                     value      BYTEA NOT NULL,
+                     expires_at BIGINT,
+
+                     PRIMARY KEY (store, key)
+                   )"#,
+            )
+            .await?;
+        Ok(client)
+    }
+}
+
+#[async_trait]
+impl StoreManager for KeyValuePostgres {
+    async fn get(&self, name: &str) -> Result<Arc<dyn Store>, Error> {
+        let client = self
+            .client
+            .get_or_try_init(|| async {
+                let client = Arc::new(self.connect().await?);
+                spawn_sweeper(Arc::downgrade(&client));
+                anyhow::Ok(client)
+            })
+            .await
+            .map_err(log_error)?;
+
+        Ok(Arc::new(PostgresStore {
+            name: name.to_owned(),
+            client: client.clone(),
+        }))
+    }
+
+    fn is_defined(&self, _store_name: &str) -> bool {
+        true
+    }
+}
+
+/// Periodically deletes expired tuples, until the client is dropped.
+fn spawn_sweeper(client: Weak<Client>) {
+    task::spawn(async move {
+        let mut interval = tokio::time::interval(SWEEP_INTERVAL);
+        loop {
+            interval.tick().await;
+            let Some(client) = client.upgrade() else {
+                break;
+            };
+            if let Err(e) = client
+                .execute(
+                    "DELETE FROM spin_key_value WHERE expires_at <= extract(epoch FROM now())",
+                    &[],
+                )
+                .await
+            {
+                log_error(e);
+            }
+        }
+    });
+}
+
+fn seconds(ttl: Duration) -> i64 {
+    ttl.as_secs().try_into().unwrap_or(i64::MAX)
+}
+
+/// The [`EXPIRES_AT`] expression with the time to live in the given parameter.
+fn expires_at(ttl_param: &str) -> String {
+    EXPIRES_AT.replace("$ttl", ttl_param)
+}
+
+struct PostgresStore {
+    name: String,
+    client: Arc<Client>,
+}
+
+#[async_trait]
+impl Store for PostgresStore {
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, Error> {
+        let row = self
+            .client
+            .query_opt(
+                &format!(
+                    "SELECT value FROM spin_key_value WHERE store=$1 AND key=$2 AND {NOT_EXPIRED}"
+                ),
+                &[&self.name, &key],
+            )
+            .await
+            .map_err(log_error)?;
+        Ok(row.map(|row| row.get(0)))
+    }
+
+    async fn set(&self, key: &str, value: &[u8]) -> Result<(), Error> {
+        self.client
+            .execute(
+                "INSERT INTO spin_key_value (store, key, value) VALUES ($1, $2, $3)
+                 ON CONFLICT (store, key) DO UPDATE SET value=$3, expires_at=NULL",
+                &[&self.name, &key, &value],
+            )
+            .await
+            .map_err(log_error)
+            .map(drop)
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), Error> {
+        self.client
+            .execute(
+                "DELETE FROM spin_key_value WHERE store=$1 AND key=$2",
+                &[&self.name, &key],
+            )
+            .await
+            .map_err(log_error)
+            .map(drop)
+    }
+
+    async fn exists(&self, key: &str) -> Result<bool, Error> {
+        Ok(self.get(key).await?.is_some())
+    }
+
+    async fn get_keys(&self) -> Result<Vec<String>, Error> {
+        let rows = self
+            .client
+            .query(
+                &format!("SELECT key FROM spin_key_value WHERE store=$1 AND {NOT_EXPIRED}"),
+                &[&self.name],
+            )
+            .await
+            .map_err(log_error)?;
+        Ok(rows.iter().map(|row| row.get(0)).collect())
+    }
+
+    async fn set_with_ttl(&self, key: &str, value: &[u8], ttl: Duration) -> Result<(), Error> {
+        self.client
+            .execute(
+                &format!(
+                    "INSERT INTO spin_key_value (store, key, value, expires_at)
+                     VALUES ($1, $2, $3, {expires_at})
+                     ON CONFLICT (store, key) DO UPDATE SET value=$3, expires_at={expires_at}",
+                    expires_at = expires_at("$4")
+                ),
+                &[&self.name, &key, &value, &seconds(ttl)],
+            )
+            .await
+            .map_err(log_error)
+            .map(drop)
+    }
+
+    async fn expire(&self, key: &str, ttl: Duration) -> Result<bool, Error> {
+        self.client
+            .execute(
+                &format!(
+                    "UPDATE spin_key_value SET expires_at={}
+                     WHERE store=$1 AND key=$2 AND {NOT_EXPIRED}",
+                    expires_at("$3")
+                ),
+                &[&self.name, &key, &seconds(ttl)],
+            )
+            .await
+            .map_err(log_error)
+            .map(|updated| updated > 0)
+    }
+
+    /// Inserts the tuple, or replaces it only if it has expired, in a single
+    /// statement, so atomically.
+    async fn set_if_absent(
+        &self,
+        key: &str,
+        value: &[u8],
+        ttl: Option<Duration>,
+    ) -> Result<bool, Error> {
+        self.client
+            .execute(
+                &format!(
+                    "INSERT INTO spin_key_value (store, key, value, expires_at)
+                     VALUES ($1, $2, $3, {expires_at})
+                     ON CONFLICT (store, key) DO UPDATE SET value=$3, expires_at={expires_at}
+                     WHERE spin_key_value.expires_at <= extract(epoch FROM now())",
+                    expires_at = expires_at("$4::bigint")
+                ),
+                &[&self.name, &key, &value, &ttl.map(seconds)],
+            )
+            .await
+            .map_err(log_error)
+            .map(|set| set > 0)
+    }
+
+    async fn get_many(&self, keys: &[String]) -> Result<Vec<(String, Option<Vec<u8>>)>, Error> {
+        let rows = self
+            .client
+            .query(
+                &format!(
+                    "SELECT key, value FROM spin_key_value
+                     WHERE store=$1 AND key=ANY($2) AND {NOT_EXPIRED}"
+                ),
+                &[&self.name, &keys],
+            )
+            .await
+            .map_err(log_error)?;
+        let mut values: std::collections::HashMap<String, Vec<u8>> =
+            rows.iter().map(|row| (row.get(0), row.get(1))).collect();
+        Ok(keys
+            .iter()
+            .map(|key| (key.clone(), values.remove(key)))
+            .collect())
+    }
+
+    /// Sets all the keys in a single statement, so atomically.
+    async fn set_many(&self, key_values: &[(String, Vec<u8>)]) -> Result<(), Error> {
+        let (keys, values): (Vec<&str>, Vec<&[u8]>) = key_values
+            .iter()
+            .map(|(key, value)| (key.as_str(), value.as_slice()))
+            .unzip();
+        self.client
+            .execute(
+                "INSERT INTO spin_key_value (store, key, value)
+                 SELECT $1, * FROM UNNEST($2::text[], $3::bytea[])
+                 ON CONFLICT (store, key) DO UPDATE SET value=EXCLUDED.value, expires_at=NULL",
+                &[&self.name, &keys, &values],
+            )
+            .await
+            .map_err(log_error)
+            .map(drop)
+    }
+
+    async fn delete_many(&self, keys: &[String]) -> Result<(), Error> {
+        self.client
+            .execute(
+                "DELETE FROM spin_key_value WHERE store=$1 AND key=ANY($2)",
+                &[&self.name, &keys],
+            )
+            .await
+            .map_err(log_error)
+            .map(drop)
+    }
+
+    async fn scan(&self, prefix: &str, cursor: Option<&str>, limit: u32) -> Result<Scan, Error> {
+        // Fetch one more key than asked for, to find out whether any remain.
+        let rows = self
+            .client
+            .query(
+                &format!(
+                    "SELECT key FROM spin_key_value
+                     WHERE store=$1 AND left(key, length($2))=$2
+                       AND ($3::text IS NULL OR key > $3) AND {NOT_EXPIRED}
+                     ORDER BY key LIMIT $4"
+                ),
+                &[
+                    &self.name,
+                    &prefix,
+                    &cursor,
+                    &(i64::from(limit.clamp(1, Scan::MAX_LIMIT)) + 1),
+                ],
+            )
+            .await
+            .map_err(log_error)?;
+        Ok(Scan::page(
+            rows.iter().map(|row| row.get(0)).collect(),
+            limit,
+        ))
+    }
+}
@@ -1,8 +1,8 @@
 use anyhow::{Context, Result};
 use redis::{aio::Connection, parse_redis_url, AsyncCommands};
 use spin_core::async_trait;
-use spin_key_value::{log_error, Error, Store, StoreManager};
-use std::sync::Arc;
+use spin_key_value::{log_error, Error, Scan, Store, StoreManager};
+use std::{sync::Arc, time::Duration};
 use tokio::sync::{Mutex, OnceCell};
 use url::Url;
 
@@ -93,4 +93,111 @@ impl Store for RedisStore {
             .await
             .map_err(log_error)
     }
+
+    async fn set_with_ttl(&self, key: &str, value: &[u8], ttl: Duration) -> Result<(), Error> {
+        self.connection
+            .lock()
+            .await
+            .set_ex(key, value, seconds(ttl))
+            .await
+            .map_err(log_error)
+    }
+
+    async fn expire(&self, key: &str, ttl: Duration) -> Result<bool, Error> {
+        self.connection
+            .lock()
+            .await
+            .expire(key, seconds(ttl))
+            .await
+            .map_err(log_error)
+    }
+
+    async fn set_if_absent(
+        &self,
+        key: &str,
+        value: &[u8],
+        ttl: Option<Duration>,
+    ) -> Result<bool, Error> {
+        let mut command = redis::cmd("SET");
+        command.arg(key).arg(value).arg("NX");
+        if let Some(ttl) = ttl {
+            command.arg("EX").arg(seconds(ttl));
+        }
+        let set: Option<String> = command
+            .query_async(&mut *self.connection.lock().await)
+            .await
+            .map_err(log_error)?;
+        Ok(set.is_some())
+    }
+
+    async fn get_many(&self, keys: &[String]) -> Result<Vec<(String, Option<Vec<u8>>)>, Error> {
+        if keys.is_empty() {
+            return Ok(Vec::new());
+        }
+        let values: Vec<Option<Vec<u8>>> = redis::cmd("MGET")
+            .arg(keys)
+            .query_async(&mut *self.connection.lock().await)
+            .await
+            .map_err(log_error)?;
+        Ok(keys.iter().cloned().zip(values).collect())
+    }
+
+    async fn set_many(&self, key_values: &[(String, Vec<u8>)]) -> Result<(), Error> {
+        if key_values.is_empty() {
+            return Ok(());
+        }
+        self.connection
+            .lock()
+            .await
+            .set_multiple(key_values)
+            .await
+            .map_err(log_error)
+    }
+
+    async fn delete_many(&self, keys: &[String]) -> Result<(), Error> {
+        if keys.is_empty() {
+            return Ok(());
+        }
+        self.connection
+            .lock()
+            .await
+            .del(keys)
+            .await
+            .map_err(log_error)
+    }
+
+    /// Scans with `SCAN`, whose cursor is passed through. Redis may return
+    /// fewer or, rarely, more keys than `limit`, and may repeat keys across
+    /// pages if the keyspace changes during the scan.
+    async fn scan(&self, prefix: &str, cursor: Option<&str>, limit: u32) -> Result<Scan, Error> {
+        let (cursor, keys): (String, Vec<String>) = redis::cmd("SCAN")
+            .arg(cursor.unwrap_or("0"))
+            .arg("MATCH")
+            .arg(format!("{}*", escape_glob(prefix)))
+            .arg("COUNT")
+            .arg(limit)
+            .query_async(&mut *self.connection.lock().await)
+            .await
+            .map_err(log_error)?;
+        Ok(Scan {
+            keys,
+            cursor: (cursor != "0").then_some(cursor),
+        })
+    }
+}
+
+/// Escapes the characters which are special in Redis glob patterns.
+fn escape_glob(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        if matches!(c, '*' | '?' | '[' | ']' | '\\') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+fn seconds(ttl: Duration) -> usize {
+    ttl.as_secs().try_into().unwrap_or(usize::MAX)
 }
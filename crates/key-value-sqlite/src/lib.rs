@@ -2,13 +2,21 @@ use anyhow::Result;
 use once_cell::sync::OnceCell;
 use rusqlite::Connection;
 use spin_core::async_trait;
-use spin_key_value::{log_error, Error, Store, StoreManager};
+use spin_key_value::{log_error, Error, Scan, Store, StoreManager};
 use std::{
     path::PathBuf,
-    sync::{Arc, Mutex},
+    sync::{Arc, Mutex, Weak},
+    time::{Duration, SystemTime, UNIX_EPOCH},
 };
 use tokio::task;
 
+/// How often expired tuples are deleted from the database.
+const SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Tuples with an expiry are filtered by this condition, with `$now` the
+/// current time in seconds since the Unix epoch.
+const NOT_EXPIRED: &str = "(expires_at IS NULL OR expires_at > $now)";
+
 pub enum DatabaseLocation {
     InMemory,
     Path(PathBuf),
@@ -42,17 +50,21 @@ impl StoreManager for KeyValueSqlite {
                 connection
                     .execute(
                         "CREATE TABLE IF NOT EXISTS spin_key_value (
-                           store TEXT NOT NULL,
-                           key   TEXT NOT NULL,
-                           value BLOB NOT NULL,
+                           store      TEXT NOT NULL,
+                           key        TEXT NOT NULL,
+                           value      BLOB NOT NULL,
+                           expires_at INTEGER,
 
                            PRIMARY KEY (store, key)
                         )",
                         [],
                     )
                     .map_err(log_error)?;
+                add_expiry_column(&connection).map_err(log_error)?;
 
-                Ok(Arc::new(Mutex::new(connection)))
+                let connection = Arc::new(Mutex::new(connection));
+                spawn_sweeper(Arc::downgrade(&connection));
+                Ok(connection)
             })
         })?;
 
@@ -67,6 +79,53 @@ impl StoreManager for KeyValueSqlite {
     }
 }
 
+/// Adds the `expires_at` column to databases created before it existed.
+fn add_expiry_column(connection: &Connection) -> rusqlite::Result<()> {
+    let exists = connection
+        .prepare("SELECT 1 FROM pragma_table_info('spin_key_value') WHERE name = 'expires_at'")?
+        .exists([])?;
+    if !exists {
+        connection.execute(
+            "ALTER TABLE spin_key_value ADD COLUMN expires_at INTEGER",
+            [],
+        )?;
+    }
+    Ok(())
+}
+
+/// Periodically deletes expired tuples, until the connection is dropped.
+fn spawn_sweeper(connection: Weak<Mutex<Connection>>) {
+    task::spawn(async move {
+        let mut interval = tokio::time::interval(SWEEP_INTERVAL);
+        loop {
+            interval.tick().await;
+            let Some(connection) = connection.upgrade() else {
+                break;
+            };
+            let swept = task::spawn_blocking(move || {
+                connection
+                    .lock()
+                    .unwrap()
+                    .execute("DELETE FROM spin_key_value WHERE expires_at <= $1", [now()])
+            })
+            .await;
+            if let Ok(Err(e)) = swept {
+                log_error(e);
+            }
+        }
+    });
+}
+
+fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs() as i64)
+}
+
+fn expires_at(ttl: Duration) -> i64 {
+    now().saturating_add(ttl.as_secs().try_into().unwrap_or(i64::MAX))
+}
+
 struct SqliteStore {
     name: String,
     connection: Arc<Mutex<Connection>>,
@@ -79,9 +138,14 @@ impl Store for SqliteStore {
             self.connection
                 .lock()
                 .unwrap()
-                .prepare_cached("SELECT value FROM spin_key_value WHERE store=$1 AND key=$2")
+                .prepare_cached(&format!(
+                    "SELECT value FROM spin_key_value WHERE store=$1 AND key=$2 AND {NOT_EXPIRED}"
+                ))
                 .map_err(log_error)?
-                .query_map([&self.name, key], |row| row.get(0))
+                .query_map(
+                    rusqlite::named_params! {"$1": &self.name, "$2": key, "$now": now()},
+                    |row| row.get(0),
+                )
                 .map_err(log_error)?
                 .next()
                 .transpose()
@@ -96,7 +160,7 @@ impl Store for SqliteStore {
                 .unwrap()
                 .prepare_cached(
                     "INSERT INTO spin_key_value (store, key, value) VALUES ($1, $2, $3)
-                     ON CONFLICT(store, key) DO UPDATE SET value=$3",
+                     ON CONFLICT(store, key) DO UPDATE SET value=$3, expires_at=NULL",
                 )
                 .map_err(log_error)?
                 .execute(rusqlite::params![&self.name, key, value])
@@ -127,14 +191,174 @@ impl Store for SqliteStore {
             self.connection
                 .lock()
                 .unwrap()
-                .prepare_cached("SELECT key FROM spin_key_value WHERE store=$1")
+                .prepare_cached(&format!(
+                    "SELECT key FROM spin_key_value WHERE store=$1 AND {NOT_EXPIRED}"
+                ))
                 .map_err(log_error)?
-                .query_map([&self.name], |row| row.get(0))
+                .query_map(
+                    rusqlite::named_params! {"$1": &self.name, "$now": now()},
+                    |row| row.get(0),
+                )
                 .map_err(log_error)?
                 .map(|r| r.map_err(log_error))
                 .collect()
         })
     }
+
+    async fn set_with_ttl(&self, key: &str, value: &[u8], ttl: Duration) -> Result<(), Error> {
+        task::block_in_place(|| {
+            self.connection
+                .lock()
+                .unwrap()
+                .prepare_cached(
+                    "INSERT INTO spin_key_value (store, key, value, expires_at) VALUES ($1, $2, $3, $4)
+                     ON CONFLICT(store, key) DO UPDATE SET value=$3, expires_at=$4",
+                )
+                .map_err(log_error)?
+                .execute(rusqlite::params![&self.name, key, value, expires_at(ttl)])
+                .map_err(log_error)
+                .map(drop)
+        })
+    }
+
+    async fn expire(&self, key: &str, ttl: Duration) -> Result<bool, Error> {
+        task::block_in_place(|| {
+            self.connection
+                .lock()
+                .unwrap()
+                .prepare_cached(&format!(
+                    "UPDATE spin_key_value SET expires_at=$expires_at
+                     WHERE store=$1 AND key=$2 AND {NOT_EXPIRED}"
+                ))
+                .map_err(log_error)?
+                .execute(rusqlite::named_params! {
+                    "$1": &self.name,
+                    "$2": key,
+                    "$expires_at": expires_at(ttl),
+                    "$now": now(),
+                })
+                .map_err(log_error)
+                .map(|updated| updated > 0)
+        })
+    }
+
+    async fn set_if_absent(
+        &self,
+        key: &str,
+        value: &[u8],
+        ttl: Option<Duration>,
+    ) -> Result<bool, Error> {
+        task::block_in_place(|| {
+            let mut connection = self.connection.lock().unwrap();
+            let tx = connection.transaction().map_err(log_error)?;
+            tx.execute(
+                "DELETE FROM spin_key_value WHERE store=$1 AND key=$2 AND expires_at <= $3",
+                rusqlite::params![&self.name, key, now()],
+            )
+            .map_err(log_error)?;
+            let inserted = tx
+                .execute(
+                    "INSERT INTO spin_key_value (store, key, value, expires_at) VALUES ($1, $2, $3, $4)
+                     ON CONFLICT(store, key) DO NOTHING",
+                    rusqlite::params![&self.name, key, value, ttl.map(expires_at)],
+                )
+                .map_err(log_error)?;
+            tx.commit().map_err(log_error)?;
+            Ok(inserted > 0)
+        })
+    }
+
+    async fn set_many(&self, key_values: &[(String, Vec<u8>)]) -> Result<(), Error> {
+        task::block_in_place(|| {
+            let mut connection = self.connection.lock().unwrap();
+            let tx = connection.transaction().map_err(log_error)?;
+            {
+                let mut statement = tx
+                    .prepare_cached(
+                        "INSERT INTO spin_key_value (store, key, value) VALUES ($1, $2, $3)
+                         ON CONFLICT(store, key) DO UPDATE SET value=$3, expires_at=NULL",
+                    )
+                    .map_err(log_error)?;
+                for (key, value) in key_values {
+                    statement
+                        .execute(rusqlite::params![&self.name, key, value])
+                        .map_err(log_error)?;
+                }
+            }
+            tx.commit().map_err(log_error)
+        })
+    }
+
+    async fn delete_many(&self, keys: &[String]) -> Result<(), Error> {
+        task::block_in_place(|| {
+            let mut connection = self.connection.lock().unwrap();
+            let tx = connection.transaction().map_err(log_error)?;
+            {
+                let mut statement = tx
+                    .prepare_cached("DELETE FROM spin_key_value WHERE store=$1 AND key=$2")
+                    .map_err(log_error)?;
+                for key in keys {
+                    statement.execute([&self.name, key]).map_err(log_error)?;
+                }
+            }
+            tx.commit().map_err(log_error)
+        })
+    }
+
+    async fn scan(&self, prefix: &str, cursor: Option<&str>, limit: u32) -> Result<Scan, Error> {
+        // Fetch one more key than asked for, to find out whether any remain.
+        let keys = task::block_in_place(|| {
+            self.connection
+                .lock()
+                .unwrap()
+                .prepare_cached(&format!(
+                    "SELECT key FROM spin_key_value
+                     WHERE store=$1 AND substr(key, 1, length($prefix))=$prefix
+                       AND ($cursor IS NULL OR key > $cursor) AND {NOT_EXPIRED}
+                     ORDER BY key LIMIT $limit"
+                ))
+                .map_err(log_error)?
+                .query_map(
+                    rusqlite::named_params! {
+                        "$1": &self.name,
+                        "$prefix": prefix,
+                        "$cursor": cursor,
+                        "$now": now(),
+                        "$limit": limit.clamp(1, Scan::MAX_LIMIT) + 1,
+                    },
+                    |row| row.get(0),
+                )
+                .map_err(log_error)?
+                .map(|r| r.map_err(log_error))
+                .collect::<Result<Vec<String>, Error>>()
+        })?;
+        Ok(Scan::page(keys, limit))
+    }
+
+    /// Patches with SQLite's `json_patch`, which implements RFC 7396, in a
+    /// single transaction.
+    async fn merge_patch(&self, key: &str, patch: &[u8]) -> Result<(), Error> {
+        let patch = std::str::from_utf8(patch)
+            .map_err(|_| Error::Other("patch is not JSON: not UTF-8".into()))?;
+        task::block_in_place(|| {
+            let mut connection = self.connection.lock().unwrap();
+            let tx = connection.transaction().map_err(log_error)?;
+            tx.execute(
+                "DELETE FROM spin_key_value WHERE store=$1 AND key=$2 AND expires_at <= $3",
+                rusqlite::params![&self.name, key, now()],
+            )
+            .map_err(log_error)?;
+            tx.execute(
+                "INSERT INTO spin_key_value (store, key, value)
+                 VALUES ($1, $2, CAST(json_patch('null', $3) AS BLOB))
+                 ON CONFLICT(store, key) DO UPDATE
+                 SET value=CAST(json_patch(CAST(value AS TEXT), $3) AS BLOB)",
+                rusqlite::params![&self.name, key, patch],
+            )
+            .map_err(|e| Error::Other(format!("failed to apply JSON merge patch: {e}")))?;
+            tx.commit().map_err(log_error)
+        })
+    }
 }
 
 #[cfg(test)]
@@ -232,4 +456,119 @@ mod test {
 
         Ok(())
     }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn expiry() -> Result<()> {
+        let manager = KeyValueSqlite::new(DatabaseLocation::InMemory);
+        let store = manager.get("default").await?;
+
+        store
+            .set_with_ttl("bar", b"baz", Duration::from_secs(60))
+            .await?;
+        assert_eq!(Some(b"baz" as &[_]), store.get("bar").await?.as_deref());
+        assert!(store.expire("bar", Duration::from_secs(120)).await?);
+        assert!(!store.expire("missing", Duration::from_secs(120)).await?);
+
+        // Move the expiry into the past rather than waiting for it.
+        manager.connection.get().unwrap().lock().unwrap().execute(
+            "UPDATE spin_key_value SET expires_at = expires_at - 1000",
+            [],
+        )?;
+        assert_eq!(None, store.get("bar").await?);
+        assert!(!store.exists("bar").await?);
+        assert!(store.get_keys().await?.is_empty());
+        assert!(!store.expire("bar", Duration::from_secs(120)).await?);
+
+        // A plain `set` clears the expiry.
+        store.set("bar", b"wow").await?;
+        assert_eq!(Some(b"wow" as &[_]), store.get("bar").await?.as_deref());
+
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn set_if_absent() -> Result<()> {
+        let manager = KeyValueSqlite::new(DatabaseLocation::InMemory);
+        let store = manager.get("default").await?;
+
+        assert!(store.set_if_absent("bar", b"baz", None).await?);
+        assert!(!store.set_if_absent("bar", b"wow", None).await?);
+        assert_eq!(Some(b"baz" as &[_]), store.get("bar").await?.as_deref());
+
+        assert!(
+            store
+                .set_if_absent("claim", b"1", Some(Duration::from_secs(60)))
+                .await?
+        );
+        // Move the expiry into the past rather than waiting for it.
+        manager.connection.get().unwrap().lock().unwrap().execute(
+            "UPDATE spin_key_value SET expires_at = expires_at - 1000",
+            [],
+        )?;
+        assert!(store.set_if_absent("claim", b"2", None).await?);
+        assert_eq!(Some(b"2" as &[_]), store.get("claim").await?.as_deref());
+
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn bulk_and_scan() -> Result<()> {
+        let store = KeyValueSqlite::new(DatabaseLocation::InMemory)
+            .get("default")
+            .await?;
+
+        let pairs = ["user:1", "user:2", "user:3", "order:1"]
+            .map(|k| (k.to_owned(), k.as_bytes().to_vec()));
+        store.set_many(&pairs).await?;
+        let values = store
+            .get_many(&["user:2".to_owned(), "missing".to_owned()])
+            .await?;
+        assert_eq!(
+            vec![
+                ("user:2".to_owned(), Some(b"user:2".to_vec())),
+                ("missing".to_owned(), None)
+            ],
+            values
+        );
+
+        let first = store.scan("user:", None, 2).await?;
+        assert_eq!(vec!["user:1", "user:2"], first.keys);
+        let second = store.scan("user:", first.cursor.as_deref(), 2).await?;
+        assert_eq!(
+            Scan {
+                keys: vec!["user:3".to_owned()],
+                cursor: None
+            },
+            second
+        );
+
+        store
+            .delete_many(&["user:1".to_owned(), "order:1".to_owned()])
+            .await?;
+        let mut keys = store.get_keys().await?;
+        keys.sort();
+        assert_eq!(vec!["user:2", "user:3"], keys);
+
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn merge_patch() -> Result<()> {
+        let store = KeyValueSqlite::new(DatabaseLocation::InMemory)
+            .get("default")
+            .await?;
+
+        store.merge_patch("doc", br#"{"a":1,"b":{"c":2}}"#).await?;
+        store
+            .merge_patch("doc", br#"{"b":{"c":null,"d":3}}"#)
+            .await?;
+        let value: serde_json::Value = serde_json::from_slice(&store.get("doc").await?.unwrap())?;
+        assert_eq!(serde_json::json!({"a": 1, "b": {"d": 3}}), value);
+
+        store.set("text", b"not json").await?;
+        assert!(store.merge_patch("text", br#"{"a":1}"#).await.is_err());
+        assert!(store.merge_patch("doc", b"not json").await.is_err());
+
+        Ok(())
+    }
 }
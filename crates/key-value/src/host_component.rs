@@ -48,7 +48,8 @@ impl HostComponent for KeyValueComponent {
         get: impl Fn(&mut spin_core::Data<T>) -> &mut Self::Data + Send + Sync + Copy + 'static,
     ) -> anyhow::Result<()> {
         super::key_value::add_to_linker(linker, get)?;
-        spin_world::v1::key_value::add_to_linker(linker, get)
+        spin_world::v1::key_value::add_to_linker(linker, get)?;
+        spin_world::v3::key_value::add_to_linker(linker, get)
     }
 
     fn build_data(&self) -> Self::Data {
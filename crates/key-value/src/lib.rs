@@ -1,8 +1,13 @@
 use anyhow::{Context, Result};
 use spin_app::MetadataKey;
-use spin_core::{async_trait, wasmtime::component::Resource};
+use spin_core::{async_trait, audit::AuditEvent, wasmtime::component::Resource};
 use spin_world::v2::key_value;
-use std::{collections::HashSet, sync::Arc};
+use spin_world::v3::key_value as v3;
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+    time::Duration,
+};
 use table::Table;
 
 mod host_component;
@@ -30,12 +35,127 @@ pub trait Store: Sync + Send {
     async fn delete(&self, key: &str) -> Result<(), Error>;
     async fn exists(&self, key: &str) -> Result<bool, Error>;
     async fn get_keys(&self) -> Result<Vec<String>, Error>;
+
+    /// Set `value` for `key`, expiring the tuple after `ttl`, which is never zero.
+    async fn set_with_ttl(&self, _key: &str, _value: &[u8], _ttl: Duration) -> Result<(), Error> {
+        Err(expiry_unsupported())
+    }
+
+    /// Expire the tuple for `key` after `ttl`, which is never zero, returning whether it existed.
+    async fn expire(&self, _key: &str, _ttl: Duration) -> Result<bool, Error> {
+        Err(expiry_unsupported())
+    }
+
+    /// Set `value` for `key` only if the key has no value, expiring the tuple after `ttl` if given, which is
+    /// never zero, and returning whether it was set.
+    ///
+    /// The default implementation checks for the key and then sets it, so is not atomic.
+    async fn set_if_absent(
+        &self,
+        key: &str,
+        value: &[u8],
+        ttl: Option<Duration>,
+    ) -> Result<bool, Error> {
+        if self.exists(key).await? {
+            return Ok(false);
+        }
+        match ttl {
+            Some(ttl) => self.set_with_ttl(key, value, ttl).await?,
+            None => self.set(key, value).await?,
+        }
+        Ok(true)
+    }
+
+    async fn get_many(&self, keys: &[String]) -> Result<Vec<(String, Option<Vec<u8>>)>, Error> {
+        let mut values = Vec::with_capacity(keys.len());
+        for key in keys {
+            values.push((key.clone(), self.get(key).await?));
+        }
+        Ok(values)
+    }
+
+    async fn set_many(&self, key_values: &[(String, Vec<u8>)]) -> Result<(), Error> {
+        for (key, value) in key_values {
+            self.set(key, value).await?;
+        }
+        Ok(())
+    }
+
+    async fn delete_many(&self, keys: &[String]) -> Result<(), Error> {
+        for key in keys {
+            self.delete(key).await?;
+        }
+        Ok(())
+    }
+
+    /// Return up to `limit` keys beginning with `prefix`, continuing from `cursor`.
+    ///
+    /// The default implementation pages through the sorted result of `get_keys`, using the last key of each
+    /// page as the cursor.
+    async fn scan(&self, prefix: &str, cursor: Option<&str>, limit: u32) -> Result<Scan, Error> {
+        let mut keys = self
+            .get_keys()
+            .await?
+            .into_iter()
+            .filter(|k| k.starts_with(prefix) && cursor.map_or(true, |c| k.as_str() > c))
+            .collect::<Vec<_>>();
+        keys.sort();
+        Ok(Scan::page(keys, limit))
+    }
+
+    /// Apply a JSON merge patch to the value of `key`.
+    ///
+    /// The default implementation reads, patches and writes the value, so is not atomic, and doesn't keep the
+    /// tuple's expiry.
+    async fn merge_patch(&self, key: &str, patch: &[u8]) -> Result<(), Error> {
+        let patch = serde_json::from_slice(patch)
+            .map_err(|e| Error::Other(format!("patch is not JSON: {e}")))?;
+        let mut value = match self.get(key).await? {
+            Some(value) => serde_json::from_slice(&value)
+                .map_err(|e| Error::Other(format!("value of {key:?} is not JSON: {e}")))?,
+            None => serde_json::Value::Null,
+        };
+        merge_patch(&mut value, patch);
+        self.set(key, &serde_json::to_vec(&value).map_err(log_error)?)
+            .await
+    }
+}
+
+/// A page of keys returned by [`Store::scan`].
+#[derive(Debug, Default, PartialEq)]
+pub struct Scan {
+    /// The keys of the page.
+    pub keys: Vec<String>,
+    /// The cursor from which to continue, or `None` if there are no more keys.
+    pub cursor: Option<String>,
+}
+
+impl Scan {
+    /// The most keys returned by a scan.
+    pub const MAX_LIMIT: u32 = 1000;
+
+    /// Takes the first `limit` of the sorted `keys`, with the last of them as the cursor if any remain.
+    pub fn page(mut keys: Vec<String>, limit: u32) -> Self {
+        let limit = limit.clamp(1, Self::MAX_LIMIT) as usize;
+        if keys.len() <= limit {
+            return Self { keys, cursor: None };
+        }
+        keys.truncate(limit);
+        let cursor = keys.last().cloned();
+        Self { keys, cursor }
+    }
+}
+
+fn expiry_unsupported() -> Error {
+    Error::Other("this key-value store does not support expiry".into())
 }
 
 pub struct KeyValueDispatch {
     allowed_stores: HashSet<String>,
     manager: Arc<dyn StoreManager>,
     stores: Table<Arc<dyn Store>>,
+    // The name each open store was opened with, for the audit log
+    store_names: HashMap<u32, String>,
 }
 
 impl KeyValueDispatch {
@@ -48,6 +168,7 @@ impl KeyValueDispatch {
             allowed_stores: HashSet::new(),
             manager: Arc::new(EmptyStoreManager),
             stores: Table::new(capacity),
+            store_names: HashMap::new(),
         }
     }
 
@@ -59,6 +180,16 @@ impl KeyValueDispatch {
     pub fn get_store(&self, store: Resource<key_value::Store>) -> anyhow::Result<&Arc<dyn Store>> {
         self.stores.get(store.rep()).context("invalid store")
     }
+
+    fn audit(&self, store: &Resource<key_value::Store>, operation: &str, key: Option<&str>) {
+        if let Some(name) = self.store_names.get(&store.rep()) {
+            spin_core::audit::record(AuditEvent::KeyValue {
+                store: name,
+                operation,
+                key,
+            });
+        }
+    }
 }
 
 impl Default for KeyValueDispatch {
@@ -79,6 +210,12 @@ impl key_value::HostStore for KeyValueDispatch {
                     .stores
                     .push(self.manager.get(&name).await?)
                     .map_err(|()| Error::StoreTableFull)?;
+                spin_core::audit::record(AuditEvent::KeyValue {
+                    store: &name,
+                    operation: "open",
+                    key: None,
+                });
+                self.store_names.insert(store, name);
                 Ok(Resource::new_own(store))
             } else {
                 Err(Error::AccessDenied)
@@ -92,6 +229,7 @@ impl key_value::HostStore for KeyValueDispatch {
         store: Resource<key_value::Store>,
         key: String,
     ) -> Result<Result<Option<Vec<u8>>, Error>> {
+        self.audit(&store, "get", Some(&key));
         let store = self.get_store(store)?;
         Ok(store.get(&key).await)
     }
@@ -102,6 +240,7 @@ impl key_value::HostStore for KeyValueDispatch {
         key: String,
         value: Vec<u8>,
     ) -> Result<Result<(), Error>> {
+        self.audit(&store, "set", Some(&key));
         let store = self.get_store(store)?;
         Ok(store.set(&key, &value).await)
     }
@@ -111,6 +250,7 @@ impl key_value::HostStore for KeyValueDispatch {
         store: Resource<key_value::Store>,
         key: String,
     ) -> Result<Result<(), Error>> {
+        self.audit(&store, "delete", Some(&key));
         let store = self.get_store(store)?;
         Ok(store.delete(&key).await)
     }
@@ -120,6 +260,7 @@ impl key_value::HostStore for KeyValueDispatch {
         store: Resource<key_value::Store>,
         key: String,
     ) -> Result<Result<bool, Error>> {
+        self.audit(&store, "exists", Some(&key));
         let store = self.get_store(store)?;
         Ok(store.exists(&key).await)
     }
@@ -128,16 +269,219 @@ impl key_value::HostStore for KeyValueDispatch {
         &mut self,
         store: Resource<key_value::Store>,
     ) -> Result<Result<Vec<String>, Error>> {
+        self.audit(&store, "get-keys", None);
         let store = self.get_store(store)?;
         Ok(store.get_keys().await)
     }
 
     fn drop(&mut self, store: Resource<key_value::Store>) -> Result<()> {
         self.stores.remove(store.rep());
+        self.store_names.remove(&store.rep());
         Ok(())
     }
 }
 
+#[async_trait]
+impl spin_world::v3::key_value::Host for KeyValueDispatch {}
+
+/// Applies a JSON merge patch (RFC 7396) to `target`.
+pub fn merge_patch(target: &mut serde_json::Value, patch: serde_json::Value) {
+    use serde_json::Value;
+    let patch = match patch {
+        Value::Object(patch) => patch,
+        patch => {
+            *target = patch;
+            return;
+        }
+    };
+    if !target.is_object() {
+        *target = Value::Object(Default::default());
+    }
+    if let Value::Object(target) = target {
+        for (name, value) in patch {
+            if value.is_null() {
+                target.remove(&name);
+            } else {
+                merge_patch(target.entry(name).or_insert(Value::Null), value);
+            }
+        }
+    }
+}
+
+/// The `fermyon:spin/key-value@3.0.0` store shares its table with the 2.0.0
+/// one, to which the functions which both have delegate.
+#[async_trait]
+impl spin_world::v3::key_value::HostStore for KeyValueDispatch {
+    async fn open(&mut self, name: String) -> Result<Result<Resource<v3::Store>, v3::Error>> {
+        let result = <Self as key_value::HostStore>::open(self, name).await?;
+        Ok(result
+            .map(|s| Resource::new_own(s.rep()))
+            .map_err(Into::into))
+    }
+
+    async fn get(
+        &mut self,
+        store: Resource<v3::Store>,
+        key: String,
+    ) -> Result<Result<Option<Vec<u8>>, v3::Error>> {
+        let this = Resource::new_borrow(store.rep());
+        let result = <Self as key_value::HostStore>::get(self, this, key).await?;
+        Ok(result.map_err(Into::into))
+    }
+
+    async fn set(
+        &mut self,
+        store: Resource<v3::Store>,
+        key: String,
+        value: Vec<u8>,
+    ) -> Result<Result<(), v3::Error>> {
+        let this = Resource::new_borrow(store.rep());
+        let result = <Self as key_value::HostStore>::set(self, this, key, value).await?;
+        Ok(result.map_err(Into::into))
+    }
+
+    async fn delete(
+        &mut self,
+        store: Resource<v3::Store>,
+        key: String,
+    ) -> Result<Result<(), v3::Error>> {
+        let this = Resource::new_borrow(store.rep());
+        let result = <Self as key_value::HostStore>::delete(self, this, key).await?;
+        Ok(result.map_err(Into::into))
+    }
+
+    async fn exists(
+        &mut self,
+        store: Resource<v3::Store>,
+        key: String,
+    ) -> Result<Result<bool, v3::Error>> {
+        let this = Resource::new_borrow(store.rep());
+        let result = <Self as key_value::HostStore>::exists(self, this, key).await?;
+        Ok(result.map_err(Into::into))
+    }
+
+    async fn get_keys(
+        &mut self,
+        store: Resource<v3::Store>,
+    ) -> Result<Result<Vec<String>, v3::Error>> {
+        let this = Resource::new_borrow(store.rep());
+        let result = <Self as key_value::HostStore>::get_keys(self, this).await?;
+        Ok(result.map_err(Into::into))
+    }
+
+    async fn set_with_ttl(
+        &mut self,
+        store: Resource<v3::Store>,
+        key: String,
+        value: Vec<u8>,
+        seconds: u64,
+    ) -> Result<Result<(), v3::Error>> {
+        let this = Resource::new_borrow(store.rep());
+        self.audit(&this, "set-with-ttl", Some(&key));
+        let store = self.get_store(this)?;
+        let result = if seconds == 0 {
+            store.delete(&key).await
+        } else {
+            store
+                .set_with_ttl(&key, &value, Duration::from_secs(seconds))
+                .await
+        };
+        Ok(result.map_err(Into::into))
+    }
+
+    async fn expire(
+        &mut self,
+        store: Resource<v3::Store>,
+        key: String,
+        seconds: u64,
+    ) -> Result<Result<bool, v3::Error>> {
+        let this = Resource::new_borrow(store.rep());
+        self.audit(&this, "expire", Some(&key));
+        let store = self.get_store(this)?;
+        let result = async {
+            if seconds == 0 {
+                let existed = store.exists(&key).await?;
+                store.delete(&key).await?;
+                Ok(existed)
+            } else {
+                store.expire(&key, Duration::from_secs(seconds)).await
+            }
+        }
+        .await;
+        Ok(result.map_err(Into::into))
+    }
+
+    async fn get_many(
+        &mut self,
+        store: Resource<v3::Store>,
+        keys: Vec<String>,
+    ) -> Result<Result<Vec<(String, Option<Vec<u8>>)>, v3::Error>> {
+        let this = Resource::new_borrow(store.rep());
+        self.audit(&this, "get-many", None);
+        let store = self.get_store(this)?;
+        Ok(store.get_many(&keys).await.map_err(Into::into))
+    }
+
+    async fn set_many(
+        &mut self,
+        store: Resource<v3::Store>,
+        key_values: Vec<(String, Vec<u8>)>,
+    ) -> Result<Result<(), v3::Error>> {
+        let this = Resource::new_borrow(store.rep());
+        self.audit(&this, "set-many", None);
+        let store = self.get_store(this)?;
+        Ok(store.set_many(&key_values).await.map_err(Into::into))
+    }
+
+    async fn delete_many(
+        &mut self,
+        store: Resource<v3::Store>,
+        keys: Vec<String>,
+    ) -> Result<Result<(), v3::Error>> {
+        let this = Resource::new_borrow(store.rep());
+        self.audit(&this, "delete-many", None);
+        let store = self.get_store(this)?;
+        Ok(store.delete_many(&keys).await.map_err(Into::into))
+    }
+
+    async fn scan(
+        &mut self,
+        store: Resource<v3::Store>,
+        prefix: String,
+        cursor: Option<String>,
+        limit: u32,
+    ) -> Result<Result<v3::ScanResult, v3::Error>> {
+        let this = Resource::new_borrow(store.rep());
+        self.audit(&this, "scan", Some(&prefix));
+        let store = self.get_store(this)?;
+        let limit = limit.clamp(1, Scan::MAX_LIMIT);
+        Ok(store
+            .scan(&prefix, cursor.as_deref(), limit)
+            .await
+            .map(|scan| v3::ScanResult {
+                keys: scan.keys,
+                cursor: scan.cursor,
+            })
+            .map_err(Into::into))
+    }
+
+    async fn merge_patch(
+        &mut self,
+        store: Resource<v3::Store>,
+        key: String,
+        patch: Vec<u8>,
+    ) -> Result<Result<(), v3::Error>> {
+        let this = Resource::new_borrow(store.rep());
+        self.audit(&this, "merge-patch", Some(&key));
+        let store = self.get_store(this)?;
+        Ok(store.merge_patch(&key, &patch).await.map_err(Into::into))
+    }
+
+    fn drop(&mut self, store: Resource<v3::Store>) -> Result<()> {
+        <Self as key_value::HostStore>::drop(self, Resource::new_own(store.rep()))
+    }
+}
+
 pub fn log_error(err: impl std::fmt::Debug) -> Error {
     tracing::warn!("key-value error: {err:?}");
     Error::Other(format!("{err:?}"))
@@ -203,3 +547,24 @@ impl spin_world::v1::key_value::Host for KeyValueDispatch {
         <Self as key_value::HostStore>::drop(self, this)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::merge_patch;
+    use serde_json::json;
+
+    #[test]
+    fn merge_patch_follows_rfc_7396() {
+        let mut value = json!({"a": "b", "c": {"d": "e", "f": "g"}});
+        merge_patch(&mut value, json!({"a": "z", "c": {"f": null}}));
+        assert_eq!(json!({"a": "z", "c": {"d": "e"}}), value);
+
+        let mut value = json!(["a"]);
+        merge_patch(&mut value, json!({"b": {"c": null, "d": 1}}));
+        assert_eq!(json!({"b": {"d": 1}}), value);
+
+        let mut value = json!({"a": 1});
+        merge_patch(&mut value, json!(["x"]));
+        assert_eq!(json!(["x"]), value);
+    }
+}
@@ -1,4 +1,4 @@
-use crate::{Error, Store, StoreManager};
+use crate::{Error, Scan, Store, StoreManager};
 use lru::LruCache;
 use spin_core::async_trait;
 use std::{
@@ -6,6 +6,7 @@ use std::{
     future::Future,
     num::NonZeroUsize,
     sync::Arc,
+    time::Duration,
 };
 use tokio::{
     sync::Mutex as AsyncMutex,
@@ -202,6 +203,78 @@ impl Store for CachingStore {
         Ok(self.get(key).await?.is_some())
     }
 
+    async fn set_with_ttl(&self, key: &str, value: &[u8], ttl: Duration) -> Result<(), Error> {
+        // As with `set`, but note that the cached value outlives its expiry if the guest instance does.  Guest
+        // instances are expected to be short-lived relative to the TTLs guests use.
+
+        let mut state = self.state.lock().await;
+
+        state.cache.put(key.to_owned(), Some(value.to_owned()));
+
+        let inner = self.inner.clone();
+        let key = key.to_owned();
+        let value = value.to_owned();
+        state.spawn(async move { inner.set_with_ttl(&key, &value, ttl).await });
+
+        Ok(())
+    }
+
+    async fn expire(&self, key: &str, ttl: Duration) -> Result<bool, Error> {
+        // The result depends on whether the tuple exists in the backing store, so flush any outstanding writes
+        // and call it synchronously.
+
+        let mut state = self.state.lock().await;
+
+        state.flush().await?;
+
+        self.inner.expire(key, ttl).await
+    }
+
+    async fn set_if_absent(
+        &self,
+        key: &str,
+        value: &[u8],
+        ttl: Option<Duration>,
+    ) -> Result<bool, Error> {
+        // The result depends on the backing store, which also makes it atomic if it can, so flush any outstanding
+        // writes and call it synchronously, dropping the cached value so that the next read sees the result.
+
+        let mut state = self.state.lock().await;
+
+        state.flush().await?;
+
+        state.cache.pop(key);
+
+        self.inner.set_if_absent(key, value, ttl).await
+    }
+
+    // `get_many`, `set_many` and `delete_many` use the default implementations, which go through the cache one
+    // key at a time.
+
+    async fn scan(&self, prefix: &str, cursor: Option<&str>, limit: u32) -> Result<Scan, Error> {
+        // Unlike `get_keys`, the result isn't merged with the cache, since that couldn't be done consistently
+        // across pages.  Flushing outstanding writes first means the backing store reflects them anyway.
+
+        let mut state = self.state.lock().await;
+
+        state.flush().await?;
+
+        self.inner.scan(prefix, cursor, limit).await
+    }
+
+    async fn merge_patch(&self, key: &str, patch: &[u8]) -> Result<(), Error> {
+        // Let the backing store apply the patch, atomically if it can, and drop the cached value so that the
+        // next read sees the result.
+
+        let mut state = self.state.lock().await;
+
+        state.flush().await?;
+
+        state.cache.pop(key);
+
+        self.inner.merge_patch(key, patch).await
+    }
+
     async fn get_keys(&self) -> Result<Vec<String>, Error> {
         // Get the keys from the backing store, remove any which are `None` in the cache, and add any which are
         // `Some` in the cache, returning the result.
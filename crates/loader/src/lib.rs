@@ -20,6 +20,9 @@ use spin_locked_app::locked::LockedApp;
 pub mod cache;
 mod http;
 mod local;
+mod secrets;
+
+pub use secrets::ALLOW_MANIFEST_SECRETS_ENV;
 
 /// Maximum number of files to copy (or download) concurrently
 pub(crate) const MAX_FILE_LOADING_CONCURRENCY: usize = 16;
@@ -30,11 +33,22 @@ pub(crate) const MAX_FILE_LOADING_CONCURRENCY: usize = 16;
 pub async fn from_file(
     manifest_path: impl AsRef<Path>,
     files_mount_strategy: FilesMountStrategy,
+) -> Result<LockedApp> {
+    from_file_with_overlays(manifest_path, &[] as &[PathBuf], files_mount_strategy).await
+}
+
+/// Load a Spin locked app from a spin.toml manifest file, after merging the
+/// given overlay files into the manifest in order. See
+/// [`spin_manifest::overlay`].
+pub async fn from_file_with_overlays(
+    manifest_path: impl AsRef<Path>,
+    overlays: &[impl AsRef<Path>],
+    files_mount_strategy: FilesMountStrategy,
 ) -> Result<LockedApp> {
     let path = manifest_path.as_ref();
     let app_root = parent_dir(path)?;
     let loader = LocalLoader::new(&app_root, files_mount_strategy).await?;
-    loader.load_file(path).await
+    loader.load_file(path, overlays).await
 }
 
 /// The strategy to use for mounting WASI files into a guest.
@@ -40,10 +40,14 @@ impl LocalLoader {
 
     // Load the manifest file (spin.toml) at the given path into a LockedApp,
     // preparing all its content for execution.
-    pub async fn load_file(&self, path: impl AsRef<Path>) -> Result<LockedApp> {
+    pub async fn load_file(
+        &self,
+        path: impl AsRef<Path>,
+        overlays: &[impl AsRef<Path>],
+    ) -> Result<LockedApp> {
         // Parse manifest
         let path = path.as_ref();
-        let manifest = spin_manifest::manifest_from_file(path)
+        let manifest = spin_manifest::manifest_from_file_with_overlays(path, overlays)
             .with_context(|| format!("Failed to read Spin app manifest from {path:?}"))?;
         let mut locked = self
             .load_manifest(manifest)
@@ -61,6 +65,8 @@ impl LocalLoader {
     // Load the given manifest into a LockedApp, ready for execution.
     async fn load_manifest(&self, mut manifest: AppManifest) -> Result<LockedApp> {
         spin_manifest::normalize::normalize_manifest(&mut manifest);
+        crate::secrets::check_manifest(&manifest)?;
+        check_component_imports(&manifest)?;
 
         let AppManifest {
             spin_manifest_version: _,
@@ -115,6 +121,25 @@ impl LocalLoader {
             .context("`allowed_http_hosts` is malformed")?;
         let _ = spin_outbound_networking::AllowedHostsConfig::parse(&allowed_outbound_hosts)
             .context("`allowed_outbound_hosts` is malformed")?;
+        ensure!(
+            component.max_slice_ms != Some(0),
+            "`max_slice_ms` must be greater than zero"
+        );
+        if let Some(wasi) = &component.wasi {
+            ensure!(
+                wasi.frozen_time.is_none() || wasi.clock_offset.is_none(),
+                "`wasi.frozen_time` and `wasi.clock_offset` cannot both be set"
+            );
+            ensure!(
+                wasi.filesystem != v2::FilesystemVisibility::None || component.files.is_empty(),
+                "`files` cannot be mounted when `wasi.filesystem` is \"none\""
+            );
+        }
+
+        let migrations = match &component.migrations {
+            Some(migrations) => Some(self.load_migrations(migrations).await?),
+            None => None,
+        };
 
         let metadata = ValuesMapBuilder::new()
             .string("description", component.description)
@@ -122,6 +147,18 @@ impl LocalLoader {
             .string_array("key_value_stores", component.key_value_stores)
             .string_array("databases", component.sqlite_databases)
             .string_array("ai_models", component.ai_models)
+            .string_array("caches", component.caches)
+            .string_array("crypto_keys", component.crypto_keys)
+            .string_array("job_targets", component.job_targets)
+            .string_array("capabilities", component.capabilities)
+            .string_array("template_dirs", component.template_dirs)
+            .serializable(
+                "component_imports",
+                (!component.component_imports.is_empty()).then_some(component.component_imports),
+            )?
+            .serializable("max_slice_ms", component.max_slice_ms)?
+            .serializable("wasi", component.wasi)?
+            .serializable("migrations", migrations)?
             .serializable("build", component.build)?
             .take();
 
@@ -181,6 +218,43 @@ impl LocalLoader {
         })
     }
 
+    // Read a component's migration files, so that they can be applied
+    // without the app directory. The result is read by `spin-migrations`.
+    async fn load_migrations(
+        &self,
+        migrations: &v2::ComponentMigrations,
+    ) -> Result<serde_json::Value> {
+        let database = match (&migrations.sqlite, &migrations.postgres, &migrations.mysql) {
+            (Some(label), None, None) => serde_json::json!({ "sqlite": label }),
+            (None, Some(name), None) => serde_json::json!({ "postgres": name }),
+            (None, None, Some(url)) => serde_json::json!({ "mysql": url }),
+            _ => bail!("`migrations` must set exactly one of `sqlite`, `postgres` or `mysql`"),
+        };
+
+        let dir = self.app_root.join(&migrations.dir);
+        let mut entries = fs::read_dir(&dir)
+            .await
+            .with_context(|| format!("Failed to read migrations directory {dir:?}"))?;
+        let mut paths = vec![];
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            if path.extension().is_some_and(|ext| ext == "sql") {
+                paths.push(path);
+            }
+        }
+        paths.sort();
+
+        let mut files = vec![];
+        for path in paths {
+            let sql = fs::read_to_string(&path)
+                .await
+                .with_context(|| format!("Failed to read migration {path:?}"))?;
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            files.push(serde_json::json!({ "name": name, "sql": sql }));
+        }
+        Ok(serde_json::json!({ "database": database, "files": files }))
+    }
+
     // Load a Wasm source from the given ContentRef and update the source
     // URL with an absolute path to the content.
     async fn load_component_source(
@@ -415,6 +489,52 @@ fn locked_metadata(
     Ok(builder.build())
 }
 
+/// Checks that `component_imports` name interfaces exported by other
+/// components of the app, and that no component imports from itself, however
+/// indirectly.
+fn check_component_imports(manifest: &AppManifest) -> Result<()> {
+    for (id, component) in &manifest.components {
+        for (interface, target) in &component.component_imports {
+            let (package, name) = interface.split_once('/').unwrap_or_default();
+            ensure!(
+                package.contains(':') && !name.is_empty(),
+                "Component `{id}` has an invalid `component_imports` interface {interface:?}: \
+                expected a name like \"acme:orders/api\""
+            );
+            ensure!(
+                manifest.components.contains_key(target),
+                "Component `{id}` imports {interface:?} from `{target}`, which is not a component of this app"
+            );
+        }
+    }
+
+    // Depth-first, tracking the chain of imports from `id`.
+    fn check_cycles<'a>(
+        manifest: &'a AppManifest,
+        id: &'a KebabId,
+        chain: &mut Vec<&'a KebabId>,
+    ) -> Result<()> {
+        if chain.contains(&id) {
+            chain.push(id);
+            let chain = chain.iter().map(|id| id.as_ref()).collect::<Vec<&str>>();
+            bail!(
+                "Components can't import from themselves: {}",
+                chain.join(" -> ")
+            );
+        }
+        chain.push(id);
+        for target in manifest.components[id].component_imports.values() {
+            check_cycles(manifest, target, chain)?;
+        }
+        chain.pop();
+        Ok(())
+    }
+    for id in manifest.components.keys() {
+        check_cycles(manifest, id, &mut vec![])?;
+    }
+    Ok(())
+}
+
 fn locked_variable(variable: v2::Variable) -> Result<locked::Variable> {
     ensure!(
         variable.required ^ variable.default.is_some(),
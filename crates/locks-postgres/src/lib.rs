@@ -0,0 +1,217 @@
+use anyhow::{Context, Result};
+use native_tls::TlsConnector;
+use postgres_native_tls::MakeTlsConnector;
+use spin_core::async_trait;
+use spin_locks::LockManager;
+use std::{collections::HashMap, sync::Arc, time::Duration};
+use tokio::{sync::Mutex, task};
+use tokio_postgres::{config::SslMode, Client, NoTls};
+
+/// Locks held as Postgres advisory locks.
+///
+/// Advisory locks belong to a database session, so they are released if this
+/// process loses its connection, e.g. because it crashed. Otherwise, each
+/// lock is unlocked once its time to live runs out. Once the connection is
+/// lost, the next call reconnects, holding none of the locks.
+pub struct PostgresLocks {
+    config: tokio_postgres::Config,
+    session: Mutex<Option<Session>>,
+}
+
+/// A connection, and the locks this process holds through it.
+#[derive(Clone)]
+struct Session {
+    client: Arc<Client>,
+    // The token holding each lock held by this process. A session may take
+    // an advisory lock it already holds, so holders within the process are
+    // told apart here.
+    held: Arc<Mutex<HashMap<String, String>>>,
+}
+
+impl PostgresLocks {
+    pub fn new(connection_string: &str) -> Result<Self> {
+        let config = connection_string
+            .parse()
+            .context("Invalid Postgres connection string")?;
+        Ok(Self {
+            config,
+            session: Mutex::new(None),
+        })
+    }
+
+    /// The current session, connecting if there is none or its connection
+    /// has closed.
+    async fn session(&self) -> Result<Session> {
+        let mut session = self.session.lock().await;
+        if let Some(current) = &*session {
+            if !current.client.is_closed() {
+                return Ok(current.clone());
+            }
+        }
+        // Postgres released the old session's locks when its connection
+        // closed, so the new one holds none.
+        let new = Session {
+            client: self.connect().await?,
+            held: Default::default(),
+        };
+        *session = Some(new.clone());
+        Ok(new)
+    }
+
+    async fn connect(&self) -> Result<Arc<Client>> {
+        let client = if self.config.get_ssl_mode() == SslMode::Disable {
+            let (client, connection) = self.config.connect(NoTls).await?;
+            task::spawn(connection);
+            client
+        } else {
+            let connector = MakeTlsConnector::new(TlsConnector::builder().build()?);
+            let (client, connection) = self.config.connect(connector).await?;
+            task::spawn(connection);
+            client
+        };
+        Ok(Arc::new(client))
+    }
+}
+
+// Advisory locks are identified by a number.
+const LOCK_ID: &str = "hashtextextended('spin-lock:' || $1, 0)";
+
+#[async_trait]
+impl LockManager for PostgresLocks {
+    async fn acquire(&self, name: &str, token: &str, ttl: Duration) -> Result<bool> {
+        let session = self.session().await?;
+        let mut held = session.held.lock().await;
+        if held.contains_key(name) {
+            return Ok(false);
+        }
+        let row = session
+            .client
+            .query_one(&format!("SELECT pg_try_advisory_lock({LOCK_ID})"), &[&name])
+            .await?;
+        if !row.get::<_, bool>(0) {
+            return Ok(false);
+        }
+        held.insert(name.to_owned(), token.to_owned());
+        drop(held);
+
+        let (name, token) = (name.to_owned(), token.to_owned());
+        task::spawn(async move {
+            tokio::time::sleep(ttl).await;
+            _ = unlock(&session, &name, &token).await;
+        });
+        Ok(true)
+    }
+
+    async fn release(&self, name: &str, token: &str) -> Result<bool> {
+        let session = self.session().await?;
+        unlock(&session, name, token).await
+    }
+}
+
+/// Unlocks the lock `name` if it is held by `token` in `session`, returning
+/// whether it was.
+async fn unlock(session: &Session, name: &str, token: &str) -> Result<bool> {
+    let mut held = session.held.lock().await;
+    if held.get(name).map(String::as_str) != Some(token) {
+        return Ok(false);
+    }
+    held.remove(name);
+    session
+        .client
+        .query_one(&format!("SELECT pg_advisory_unlock({LOCK_ID})"), &[&name])
+        .await?;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::{TcpListener, TcpStream},
+    };
+
+    /// Speaks just enough of the Postgres protocol to answer every query
+    /// with `true`, closing the connection after `queries` queries if given.
+    async fn serve(mut stream: TcpStream, queries: Option<usize>) -> std::io::Result<()> {
+        // The startup message has no type byte.
+        let len = stream.read_i32().await?;
+        stream.read_exact(&mut vec![0; len as usize - 4]).await?;
+        stream
+            .write_all(&message(b'R', &0i32.to_be_bytes()))
+            .await?;
+        stream.write_all(&message(b'Z', b"I")).await?;
+
+        let mut executed = 0;
+        loop {
+            let kind = stream.read_u8().await?;
+            let len = stream.read_i32().await?;
+            stream.read_exact(&mut vec![0; len as usize - 4]).await?;
+            let reply = match kind {
+                b'P' => message(b'1', b""),
+                b'D' => {
+                    // One `text` parameter, and one `bool` column.
+                    let params = [&1i16.to_be_bytes()[..], &25i32.to_be_bytes()].concat();
+                    let column = [
+                        &b"locked\0"[..],
+                        &0i32.to_be_bytes(),
+                        &0i16.to_be_bytes(),
+                        &16i32.to_be_bytes(),
+                        &1i16.to_be_bytes(),
+                        &(-1i32).to_be_bytes(),
+                        &0i16.to_be_bytes(),
+                    ]
+                    .concat();
+                    let columns = [&1i16.to_be_bytes()[..], &column].concat();
+                    [message(b't', &params), message(b'T', &columns)].concat()
+                }
+                b'B' => message(b'2', b""),
+                b'E' => {
+                    executed += 1;
+                    let row = [&1i16.to_be_bytes()[..], &1i32.to_be_bytes(), &[1]].concat();
+                    [message(b'D', &row), message(b'C', b"SELECT 1\0")].concat()
+                }
+                b'C' => message(b'3', b""),
+                b'S' => message(b'Z', b"I"),
+                _ => return Ok(()),
+            };
+            stream.write_all(&reply).await?;
+            if kind == b'S' && queries.is_some_and(|queries| executed >= queries) {
+                return Ok(());
+            }
+        }
+    }
+
+    fn message(kind: u8, body: &[u8]) -> Vec<u8> {
+        let len = body.len() as i32 + 4;
+        [&[kind][..], &len.to_be_bytes(), body].concat()
+    }
+
+    #[tokio::test]
+    async fn locks_are_forgotten_when_the_connection_drops() -> Result<()> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let port = listener.local_addr()?.port();
+        task::spawn(async move {
+            let (first, _) = listener.accept().await?;
+            serve(first, Some(1)).await?;
+            let (second, _) = listener.accept().await?;
+            serve(second, None).await
+        });
+        let locks = PostgresLocks::new(&format!(
+            "host=127.0.0.1 port={port} user=spin sslmode=disable"
+        ))?;
+        let ttl = Duration::from_secs(60);
+
+        assert!(locks.acquire("migrations", "first", ttl).await?);
+        let client = locks.session.lock().await.as_ref().unwrap().client.clone();
+        while !client.is_closed() {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        // Postgres released the lock along with the session.
+        assert!(locks.acquire("migrations", "second", ttl).await?);
+        assert!(!locks.release("migrations", "first").await?);
+        assert!(locks.release("migrations", "second").await?);
+        Ok(())
+    }
+}
@@ -0,0 +1,84 @@
+use anyhow::{Context, Result};
+use redis::{aio::Connection, parse_redis_url, Script};
+use spin_core::async_trait;
+use spin_locks::LockManager;
+use std::{sync::Arc, time::Duration};
+use tokio::sync::{Mutex, OnceCell};
+use url::Url;
+
+/// Deletes a lock only if it is still held by the given token, so that a
+/// holder whose lock has expired can't release its successor's.
+const RELEASE_SCRIPT: &str = r#"
+if redis.call("GET", KEYS[1]) == ARGV[1] then
+    return redis.call("DEL", KEYS[1])
+else
+    return 0
+end
+"#;
+
+/// Locks stored as Redis keys, which expire with their time to live.
+pub struct RedisLocks {
+    database_url: Url,
+    key_prefix: String,
+    connection: OnceCell<Arc<Mutex<Connection>>>,
+}
+
+impl RedisLocks {
+    /// Locks stored under `key_prefix`, so that they can share a Redis
+    /// database with other data.
+    pub fn new(address: String, key_prefix: String) -> Result<Self> {
+        let database_url = parse_redis_url(&address).context("Invalid Redis URL")?;
+
+        Ok(Self {
+            database_url,
+            key_prefix,
+            connection: OnceCell::new(),
+        })
+    }
+
+    async fn connection(&self) -> Result<&Arc<Mutex<Connection>>> {
+        self.connection
+            .get_or_try_init(|| async {
+                redis::Client::open(self.database_url.clone())?
+                    .get_async_connection()
+                    .await
+                    .map(Mutex::new)
+                    .map(Arc::new)
+            })
+            .await
+            .context("Failed to connect to Redis")
+    }
+
+    fn key(&self, name: &str) -> String {
+        format!("{}{name}", self.key_prefix)
+    }
+}
+
+#[async_trait]
+impl LockManager for RedisLocks {
+    async fn acquire(&self, name: &str, token: &str, ttl: Duration) -> Result<bool> {
+        let mut conn = self.connection().await?.lock().await;
+        // Redis rejects an expiry of zero.
+        let millis = ttl.as_millis().clamp(1, u64::MAX as u128) as u64;
+        // `SET NX` replies `OK` if the key was set, and nil if it exists.
+        let set: Option<String> = redis::cmd("SET")
+            .arg(self.key(name))
+            .arg(token)
+            .arg("NX")
+            .arg("PX")
+            .arg(millis)
+            .query_async(&mut *conn)
+            .await?;
+        Ok(set.is_some())
+    }
+
+    async fn release(&self, name: &str, token: &str) -> Result<bool> {
+        let mut conn = self.connection().await?.lock().await;
+        let deleted: i64 = Script::new(RELEASE_SCRIPT)
+            .key(self.key(name))
+            .arg(token)
+            .invoke_async(&mut *conn)
+            .await?;
+        Ok(deleted > 0)
+    }
+}
@@ -0,0 +1,105 @@
+//! Locks shared by the instances of an application, so that only one replica
+//! performs a task such as a migration at a time.
+
+use anyhow::Result;
+use spin_app::{AppComponent, DynamicHostComponent, APP_NAME_KEY};
+use spin_core::{async_trait, HostComponent};
+use spin_world::v3::lock;
+use std::{sync::Arc, time::Duration};
+
+mod memory;
+
+pub use lock::Error;
+pub use memory::MemoryLocks;
+
+/// A lock backend.
+///
+/// Each lock is held by one token at a time, until it is released or its
+/// time to live runs out.
+#[async_trait]
+pub trait LockManager: Sync + Send {
+    /// Acquire the lock `name` for `token` if it isn't held, returning
+    /// whether it was acquired.
+    async fn acquire(&self, name: &str, token: &str, ttl: Duration) -> Result<bool>;
+    /// Release the lock `name` if it is held by `token`, returning whether
+    /// it was.
+    async fn release(&self, name: &str, token: &str) -> Result<bool>;
+}
+
+/// Returns a token which identifies a new holder of a lock.
+pub fn new_token() -> String {
+    format!("{:032x}", rand::random::<u128>())
+}
+
+pub struct LocksComponent {
+    manager: Arc<dyn LockManager>,
+}
+
+impl LocksComponent {
+    /// A component whose locks are held by the given manager.
+    pub fn new(manager: Arc<dyn LockManager>) -> Self {
+        Self { manager }
+    }
+}
+
+impl HostComponent for LocksComponent {
+    type Data = LocksDispatch;
+
+    fn add_to_linker<T: Send>(
+        linker: &mut spin_core::Linker<T>,
+        get: impl Fn(&mut spin_core::Data<T>) -> &mut Self::Data + Send + Sync + Copy + 'static,
+    ) -> anyhow::Result<()> {
+        lock::add_to_linker(linker, get)
+    }
+
+    fn build_data(&self) -> Self::Data {
+        LocksDispatch {
+            name_prefix: String::new(),
+            manager: self.manager.clone(),
+        }
+    }
+}
+
+impl DynamicHostComponent for LocksComponent {
+    fn update_data(&self, data: &mut Self::Data, component: &AppComponent) -> anyhow::Result<()> {
+        // Locks are shared by the components of an application, but not
+        // with other applications using the same backend.
+        let app_name: String = component.app.require_metadata(APP_NAME_KEY)?;
+        data.name_prefix = format!("{app_name}/");
+        Ok(())
+    }
+}
+
+pub struct LocksDispatch {
+    name_prefix: String,
+    manager: Arc<dyn LockManager>,
+}
+
+#[async_trait]
+impl lock::Host for LocksDispatch {
+    async fn acquire(
+        &mut self,
+        name: String,
+        ttl_milliseconds: u64,
+    ) -> Result<Result<Option<String>, Error>> {
+        let token = new_token();
+        let name = format!("{}{name}", self.name_prefix);
+        let ttl = Duration::from_millis(ttl_milliseconds);
+        Ok(self
+            .manager
+            .acquire(&name, &token, ttl)
+            .await
+            .map(|acquired| acquired.then_some(token))
+            .map_err(log_error))
+    }
+
+    async fn release(&mut self, name: String, token: String) -> Result<Result<bool, Error>> {
+        let name = format!("{}{name}", self.name_prefix);
+        Ok(self.manager.release(&name, &token).await.map_err(log_error))
+    }
+}
+
+fn log_error(err: impl std::fmt::Debug) -> Error {
+    tracing::warn!("lock error: {err:?}");
+    Error::Other(format!("{err:?}"))
+}
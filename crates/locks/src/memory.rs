@@ -0,0 +1,81 @@
+use crate::LockManager;
+use anyhow::Result;
+use spin_core::async_trait;
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+/// Locks held in memory, which are only shared by the instances in this
+/// process.
+#[derive(Default)]
+pub struct MemoryLocks {
+    // The holder of each lock, and when its hold runs out
+    held: Mutex<HashMap<String, (String, Instant)>>,
+}
+
+impl MemoryLocks {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl LockManager for MemoryLocks {
+    async fn acquire(&self, name: &str, token: &str, ttl: Duration) -> Result<bool> {
+        let now = Instant::now();
+        let mut held = self.held.lock().unwrap();
+        if matches!(held.get(name), Some((_, expires)) if *expires > now) {
+            return Ok(false);
+        }
+        held.insert(name.to_owned(), (token.to_owned(), now + ttl));
+        Ok(true)
+    }
+
+    async fn release(&self, name: &str, token: &str) -> Result<bool> {
+        let now = Instant::now();
+        let mut held = self.held.lock().unwrap();
+        match held.get(name) {
+            Some((holder, expires)) if holder == token => {
+                let was_held = *expires > now;
+                held.remove(name);
+                Ok(was_held)
+            }
+            _ => Ok(false),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn locks_have_one_holder() -> Result<()> {
+        let locks = MemoryLocks::new();
+        let ttl = Duration::from_secs(60);
+        assert!(locks.acquire("migrate", "a", ttl).await?);
+        assert!(!locks.acquire("migrate", "b", ttl).await?);
+        assert!(locks.acquire("other", "b", ttl).await?);
+
+        assert!(!locks.release("migrate", "b").await?);
+        assert!(locks.release("migrate", "a").await?);
+        assert!(!locks.release("migrate", "a").await?);
+        assert!(locks.acquire("migrate", "b", ttl).await?);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn expired_locks_can_be_acquired() -> Result<()> {
+        let locks = MemoryLocks::new();
+        assert!(locks.acquire("migrate", "a", Duration::ZERO).await?);
+        assert!(
+            locks
+                .acquire("migrate", "b", Duration::from_secs(60))
+                .await?
+        );
+        assert!(!locks.release("migrate", "a").await?);
+        Ok(())
+    }
+}
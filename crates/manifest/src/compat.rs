@@ -81,6 +81,15 @@ pub fn v1_to_v2_app(manifest: v1::AppManifestV1) -> Result<v2::AppManifest, Erro
                 key_value_stores,
                 sqlite_databases,
                 ai_models,
+                caches: Vec::new(),
+                crypto_keys: Vec::new(),
+                job_targets: Vec::new(),
+                capabilities: Vec::new(),
+                template_dirs: Vec::new(),
+                component_imports: Default::default(),
+                max_slice_ms: None,
+                wasi: None,
+                migrations: None,
                 build: component.build,
                 allowed_outbound_hosts,
                 allowed_http_hosts: Vec::new(),
@@ -5,6 +5,7 @@
 pub mod compat;
 pub mod error;
 pub mod normalize;
+pub mod overlay;
 pub mod schema;
 
 use std::path::Path;
@@ -19,6 +20,28 @@ pub fn manifest_from_file(path: impl AsRef<Path>) -> Result<AppManifest, Error>
     manifest_from_str(&manifest_str)
 }
 
+/// Parses a V1 or V2 app manifest file into a [`AppManifest`], after merging
+/// the given [overlay](overlay) files into it in order.
+pub fn manifest_from_file_with_overlays(
+    path: impl AsRef<Path>,
+    overlays: &[impl AsRef<Path>],
+) -> Result<AppManifest, Error> {
+    let manifest_str = std::fs::read_to_string(path)?;
+    if overlays.is_empty() {
+        return manifest_from_str(&manifest_str);
+    }
+    let mut merged: toml::Table = toml::from_str(&manifest_str)?;
+    for overlay in overlays {
+        let overlay_str = std::fs::read_to_string(overlay)?;
+        overlay::merge(&mut merged, toml::from_str(&overlay_str)?);
+    }
+    let merged = toml::Value::Table(merged);
+    match ManifestVersion::detect_value(&merged)? {
+        ManifestVersion::V1 => compat::v1_to_v2_app(merged.try_into()?),
+        ManifestVersion::V2 => Ok(merged.try_into()?),
+    }
+}
+
 /// Parses a V1 or V2 app manifest into a [`AppManifest`].
 pub fn manifest_from_str(v1_or_v2_toml: &str) -> Result<AppManifest, Error> {
     // TODO: would it be faster to parse into a toml::Table rather than parse twice?
@@ -43,9 +66,17 @@ pub enum ManifestVersion {
 impl ManifestVersion {
     /// Detects the Spin manifest schema version of the given TOML content.
     pub fn detect(s: &str) -> Result<Self, Error> {
+        Self::from_probe(toml::from_str(s)?)
+    }
+
+    fn detect_value(value: &toml::Value) -> Result<Self, Error> {
+        Self::from_probe(value.clone().try_into()?)
+    }
+
+    fn from_probe(probe: schema::VersionProbe) -> Result<Self, Error> {
         let schema::VersionProbe {
             spin_manifest_version,
-        } = toml::from_str(s)?;
+        } = probe;
         if spin_manifest_version.as_str() == Some("1") {
             Ok(Self::V1)
         } else if spin_manifest_version.as_integer() == Some(2) {
@@ -0,0 +1,77 @@
+//! Environment-specific manifest overlays.
+//!
+//! An overlay is a partial manifest, named after the environment it is for
+//! and kept beside the manifest it changes, e.g. `spin.production.toml`
+//! beside `spin.toml`. Tables in the overlay are merged into the manifest's
+//! key by key, so an overlay can set one variable's default or one
+//! component's `allowed_outbound_hosts` without repeating the rest. Any other
+//! value, including an array such as `files`, replaces the manifest's.
+
+use std::path::{Path, PathBuf};
+
+/// Returns the path of the overlay named `name` for the manifest at
+/// `manifest_path`, e.g. `spin.production.toml` for `spin.toml`.
+pub fn overlay_path(manifest_path: &Path, name: &str) -> PathBuf {
+    let stem = manifest_path
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "spin".into());
+    manifest_path.with_file_name(format!("{stem}.{name}.toml"))
+}
+
+/// Merges `overlay` into `base`.
+pub fn merge(base: &mut toml::Table, overlay: toml::Table) {
+    for (key, value) in overlay {
+        match (base.get_mut(&key), value) {
+            (Some(toml::Value::Table(base)), toml::Value::Table(overlay)) => merge(base, overlay),
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn overlays_are_named_after_the_manifest() {
+        assert_eq!(
+            Path::new("app/spin.production.toml"),
+            overlay_path(Path::new("app/spin.toml"), "production")
+        );
+    }
+
+    #[test]
+    fn tables_merge_and_other_values_replace() {
+        let mut base: toml::Table = toml::toml! {
+            [variables]
+            api_url = { default = "http://localhost" }
+            token = { required = true }
+
+            [component.api]
+            source = "api.wasm"
+            allowed_outbound_hosts = ["http://localhost:3000"]
+        };
+        let overlay: toml::Table = toml::toml! {
+            [variables]
+            api_url = { default = "https://api.example.com" }
+
+            [component.api]
+            allowed_outbound_hosts = ["https://api.example.com"]
+        };
+        merge(&mut base, overlay);
+
+        let expected: toml::Table = toml::toml! {
+            [variables]
+            api_url = { default = "https://api.example.com" }
+            token = { required = true }
+
+            [component.api]
+            source = "api.wasm"
+            allowed_outbound_hosts = ["https://api.example.com"]
+        };
+        assert_eq!(expected, base);
+    }
+}
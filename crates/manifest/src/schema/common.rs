@@ -68,6 +68,47 @@ pub struct ComponentBuildConfig {
     /// watch = ["src/**/*.rs"]
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub watch: Vec<String>,
+    /// `optimize = true`
+    #[serde(default, skip_serializing_if = "Optimize::is_off")]
+    pub optimize: Optimize,
+    /// `install = "npm"`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub install: Option<Install>,
+}
+
+/// Dependencies which `spin build` installs before running a component's
+/// build command
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Install {
+    /// `install = "npm"`, to run `npm install` if there is no `node_modules`
+    Npm,
+    /// `install = "python"`, to install `requirements.txt` into a virtual
+    /// environment which the build command runs in
+    Python,
+}
+
+/// Post-build optimization of a component's Wasm with `wasm-opt`
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Optimize {
+    /// `optimize = true`, to optimize with the default passes
+    Enabled(bool),
+    /// `optimize = ["-O3", "--strip-debug"]`, to optimize with the given `wasm-opt` arguments
+    Passes(Vec<String>),
+}
+
+impl Optimize {
+    /// Whether optimization is off.
+    pub fn is_off(&self) -> bool {
+        matches!(self, Self::Enabled(false))
+    }
+}
+
+impl Default for Optimize {
+    fn default() -> Self {
+        Self::Enabled(false)
+    }
 }
 
 fn is_false(v: &bool) -> bool {
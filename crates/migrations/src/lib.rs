@@ -0,0 +1,229 @@
+//! Database migrations declared by components.
+//!
+//! A component's migrations are SQL files named `<version>_<name>.sql`,
+//! which the loader embeds in the locked app. Each is applied once, in
+//! version order, and recorded in a `spin_migrations` table in the database,
+//! so that running the migrations again only applies new ones.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use spin_app::MetadataKey;
+use spin_core::async_trait;
+
+mod mysql;
+mod postgres;
+mod sqlite;
+
+pub use mysql::MysqlDatabase;
+pub use postgres::PostgresDatabase;
+pub use sqlite::SqliteDatabase;
+
+pub const MIGRATIONS_KEY: MetadataKey<ComponentMigrations> = MetadataKey::new("migrations");
+
+/// The migrations of a component, as recorded in the locked app.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ComponentMigrations {
+    pub database: MigrationDatabase,
+    pub files: Vec<MigrationFile>,
+}
+
+/// The database a component's migrations apply to.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MigrationDatabase {
+    /// A SQLite database label.
+    Sqlite(String),
+    /// The name of a Postgres database in the runtime config.
+    Postgres(String),
+    /// A MySQL connection URL.
+    Mysql(String),
+}
+
+/// A migration file, as recorded in the locked app.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct MigrationFile {
+    /// The file name, e.g. `0001_create_todos.sql`.
+    pub name: String,
+    pub sql: String,
+}
+
+/// A migration, ready to apply.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Migration {
+    pub version: u64,
+    /// The file name, without its version.
+    pub name: String,
+    pub sql: String,
+}
+
+impl ComponentMigrations {
+    /// Returns the migrations in the order they apply.
+    pub fn migrations(&self) -> Result<Vec<Migration>> {
+        let mut migrations = BTreeMap::new();
+        for file in &self.files {
+            let stem = file.name.strip_suffix(".sql").unwrap_or(&file.name);
+            let (version, name) = stem.split_once('_').unwrap_or((stem, ""));
+            let version: u64 = version.parse().with_context(|| {
+                format!(
+                    "Migration {:?} should be named `<version>_<name>.sql`",
+                    file.name
+                )
+            })?;
+            let migration = Migration {
+                version,
+                name: name.to_owned(),
+                sql: file.sql.clone(),
+            };
+            if migrations.insert(version, migration).is_some() {
+                bail!("More than one migration has version {version}");
+            }
+        }
+        Ok(migrations.into_values().collect())
+    }
+}
+
+/// A database which migrations are applied to.
+#[async_trait]
+pub trait Database: Send {
+    /// Returns the versions of the migrations applied for `component_id`,
+    /// creating the table which records them if it doesn't exist.
+    async fn applied(&mut self, component_id: &str) -> Result<BTreeSet<u64>>;
+    /// Applies `migration` for `component_id` and records it, in a single
+    /// transaction where the database supports one.
+    async fn apply(&mut self, component_id: &str, migration: &Migration) -> Result<()>;
+}
+
+/// Applies those of `migrations` which haven't been applied to `database`
+/// for `component_id`, returning those it applied.
+pub async fn run<'a>(
+    component_id: &str,
+    database: &mut dyn Database,
+    migrations: &'a [Migration],
+) -> Result<Vec<&'a Migration>> {
+    let applied = database.applied(component_id).await?;
+    let mut newly_applied = vec![];
+    for migration in migrations {
+        if applied.contains(&migration.version) {
+            continue;
+        }
+        database
+            .apply(component_id, migration)
+            .await
+            .with_context(|| {
+                format!(
+                    "Failed to apply migration {} ({}) for {component_id}",
+                    migration.version, migration.name
+                )
+            })?;
+        newly_applied.push(migration);
+    }
+    Ok(newly_applied)
+}
+
+// Versions are stored as signed 64-bit integers, which all the databases
+// support.
+fn stored_version(version: u64) -> Result<i64> {
+    version
+        .try_into()
+        .with_context(|| format!("Migration version {version} is too large"))
+}
+
+fn now_secs() -> i64 {
+    let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH);
+    now.unwrap_or_default()
+        .as_secs()
+        .try_into()
+        .unwrap_or(i64::MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(name: &str) -> MigrationFile {
+        MigrationFile {
+            name: name.into(),
+            sql: format!("-- {name}"),
+        }
+    }
+
+    #[derive(Default)]
+    struct FakeDatabase(BTreeMap<String, BTreeSet<u64>>);
+
+    #[async_trait]
+    impl Database for FakeDatabase {
+        async fn applied(&mut self, component_id: &str) -> Result<BTreeSet<u64>> {
+            Ok(self.0.get(component_id).cloned().unwrap_or_default())
+        }
+
+        async fn apply(&mut self, component_id: &str, migration: &Migration) -> Result<()> {
+            if migration.sql.contains("broken") {
+                bail!("syntax error");
+            }
+            self.0
+                .entry(component_id.into())
+                .or_default()
+                .insert(migration.version);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn migrations_are_ordered_by_version() -> Result<()> {
+        let migrations = ComponentMigrations {
+            database: MigrationDatabase::Sqlite("default".into()),
+            files: vec![file("10_add_index.sql"), file("0002_create_todos.sql")],
+        }
+        .migrations()?;
+        let versions: Vec<_> = migrations.iter().map(|m| (m.version, &*m.name)).collect();
+        assert_eq!(vec![(2, "create_todos"), (10, "add_index")], versions);
+
+        let duplicated = ComponentMigrations {
+            database: MigrationDatabase::Sqlite("default".into()),
+            files: vec![file("1_a.sql"), file("01_b.sql")],
+        };
+        assert!(duplicated.migrations().is_err());
+
+        let unversioned = ComponentMigrations {
+            database: MigrationDatabase::Sqlite("default".into()),
+            files: vec![file("create_todos.sql")],
+        };
+        assert!(unversioned.migrations().is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn only_new_migrations_are_applied() -> Result<()> {
+        let mut database = FakeDatabase::default();
+        let migrations = ComponentMigrations {
+            database: MigrationDatabase::Sqlite("default".into()),
+            files: vec![file("1_create.sql"), file("2_alter.sql")],
+        }
+        .migrations()?;
+
+        let applied = run("todo", &mut database, &migrations[..1]).await?;
+        assert_eq!(
+            vec![1],
+            applied.iter().map(|m| m.version).collect::<Vec<_>>()
+        );
+        let applied = run("todo", &mut database, &migrations).await?;
+        assert_eq!(
+            vec![2],
+            applied.iter().map(|m| m.version).collect::<Vec<_>>()
+        );
+        assert!(run("todo", &mut database, &migrations).await?.is_empty());
+
+        // Migrations are tracked per component.
+        assert_eq!(2, run("other", &mut database, &migrations).await?.len());
+
+        let broken = Migration {
+            version: 3,
+            name: "broken".into(),
+            sql: "broken".into(),
+        };
+        assert!(run("todo", &mut database, &[broken]).await.is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,68 @@
+use crate::{now_secs, stored_version, Database, Migration};
+use anyhow::Result;
+use mysql_async::{prelude::Queryable, Conn};
+use spin_core::async_trait;
+use std::collections::BTreeSet;
+
+/// A MySQL database.
+///
+/// MySQL commits schema changes as they are made, so a migration which fails
+/// part way may leave the changes before the failure in place.
+pub struct MysqlDatabase {
+    conn: Conn,
+}
+
+impl MysqlDatabase {
+    /// Connects to the database at the given `mysql://` URL.
+    pub async fn connect(address: &str) -> Result<Self> {
+        let conn = outbound_mysql::build_conn(address).await?;
+        Ok(Self { conn })
+    }
+}
+
+#[async_trait]
+impl Database for MysqlDatabase {
+    async fn applied(&mut self, component_id: &str) -> Result<BTreeSet<u64>> {
+        self.conn
+            .query_drop(
+                "CREATE TABLE IF NOT EXISTS spin_migrations (
+                   component  VARCHAR(255) NOT NULL,
+                   version    BIGINT NOT NULL,
+                   name       VARCHAR(255) NOT NULL,
+                   applied_at BIGINT NOT NULL,
+                   PRIMARY KEY (component, version)
+                 )",
+            )
+            .await?;
+        let versions: Vec<i64> = self
+            .conn
+            .exec(
+                "SELECT version FROM spin_migrations WHERE component = ?",
+                (component_id,),
+            )
+            .await?;
+        Ok(versions
+            .into_iter()
+            .filter_map(|version| u64::try_from(version).ok())
+            .collect())
+    }
+
+    async fn apply(&mut self, component_id: &str, migration: &Migration) -> Result<()> {
+        let mut transaction = self.conn.start_transaction(Default::default()).await?;
+        transaction.query_drop(&migration.sql).await?;
+        transaction
+            .exec_drop(
+                "INSERT INTO spin_migrations (component, version, name, applied_at)
+                 VALUES (?, ?, ?, ?)",
+                (
+                    component_id,
+                    stored_version(migration.version)?,
+                    &migration.name,
+                    now_secs(),
+                ),
+            )
+            .await?;
+        transaction.commit().await?;
+        Ok(())
+    }
+}
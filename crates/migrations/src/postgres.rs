@@ -0,0 +1,85 @@
+use crate::{now_secs, stored_version, Database, Migration};
+use anyhow::{Context, Result};
+use native_tls::TlsConnector;
+use postgres_native_tls::MakeTlsConnector;
+use spin_core::async_trait;
+use std::collections::BTreeSet;
+use tokio::task;
+use tokio_postgres::{config::SslMode, Client, NoTls};
+
+/// A Postgres database.
+pub struct PostgresDatabase {
+    client: Client,
+}
+
+impl PostgresDatabase {
+    /// Connects to the database with the given connection string.
+    pub async fn connect(connection_string: &str) -> Result<Self> {
+        let config: tokio_postgres::Config = connection_string
+            .parse()
+            .context("Invalid Postgres connection string")?;
+        let client = if config.get_ssl_mode() == SslMode::Disable {
+            let (client, connection) = config.connect(NoTls).await?;
+            task::spawn(connection);
+            client
+        } else {
+            let connector = MakeTlsConnector::new(TlsConnector::builder().build()?);
+            let (client, connection) = config.connect(connector).await?;
+            task::spawn(connection);
+            client
+        };
+        Ok(Self { client })
+    }
+
+    /// Runs `sql` outside of any migration, e.g. to seed the database.
+    pub async fn batch_execute(&self, sql: &str) -> Result<()> {
+        Ok(self.client.batch_execute(sql).await?)
+    }
+}
+
+#[async_trait]
+impl Database for PostgresDatabase {
+    async fn applied(&mut self, component_id: &str) -> Result<BTreeSet<u64>> {
+        self.client
+            .batch_execute(
+                "CREATE TABLE IF NOT EXISTS spin_migrations (
+                   component  TEXT NOT NULL,
+                   version    BIGINT NOT NULL,
+                   name       TEXT NOT NULL,
+                   applied_at BIGINT NOT NULL,
+                   PRIMARY KEY (component, version)
+                 )",
+            )
+            .await?;
+        let rows = self
+            .client
+            .query(
+                "SELECT version FROM spin_migrations WHERE component = $1",
+                &[&component_id],
+            )
+            .await?;
+        Ok(rows
+            .iter()
+            .filter_map(|row| u64::try_from(row.get::<_, i64>(0)).ok())
+            .collect())
+    }
+
+    async fn apply(&mut self, component_id: &str, migration: &Migration) -> Result<()> {
+        let transaction = self.client.transaction().await?;
+        transaction.batch_execute(&migration.sql).await?;
+        transaction
+            .execute(
+                "INSERT INTO spin_migrations (component, version, name, applied_at)
+                 VALUES ($1, $2, $3, $4)",
+                &[
+                    &component_id,
+                    &stored_version(migration.version)?,
+                    &migration.name,
+                    &now_secs(),
+                ],
+            )
+            .await?;
+        transaction.commit().await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,82 @@
+use crate::{now_secs, stored_version, Database, Migration};
+use anyhow::{anyhow, Result};
+use spin_core::async_trait;
+use spin_sqlite::Connection;
+use spin_world::v2::sqlite::Value;
+use std::{collections::BTreeSet, sync::Arc};
+
+/// A SQLite database, as configured for the application.
+pub struct SqliteDatabase {
+    connection: Arc<dyn Connection>,
+}
+
+impl SqliteDatabase {
+    pub fn new(connection: Arc<dyn Connection>) -> Self {
+        Self { connection }
+    }
+
+    async fn record(&self, component_id: &str, migration: &Migration) -> Result<()> {
+        self.connection
+            .query(
+                "INSERT INTO spin_migrations (component, version, name, applied_at)
+                 VALUES (?, ?, ?, ?)",
+                vec![
+                    Value::Text(component_id.to_owned()),
+                    Value::Integer(stored_version(migration.version)?),
+                    Value::Text(migration.name.clone()),
+                    Value::Integer(now_secs()),
+                ],
+            )
+            .await
+            .map_err(|e| anyhow!("{e:?}"))?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Database for SqliteDatabase {
+    async fn applied(&mut self, component_id: &str) -> Result<BTreeSet<u64>> {
+        self.connection
+            .execute_batch(
+                "CREATE TABLE IF NOT EXISTS spin_migrations (
+                   component  TEXT NOT NULL,
+                   version    INTEGER NOT NULL,
+                   name       TEXT NOT NULL,
+                   applied_at INTEGER NOT NULL,
+                   PRIMARY KEY (component, version)
+                 )",
+            )
+            .await?;
+        let result = self
+            .connection
+            .query(
+                "SELECT version FROM spin_migrations WHERE component = ?",
+                vec![Value::Text(component_id.to_owned())],
+            )
+            .await
+            .map_err(|e| anyhow!("{e:?}"))?;
+        Ok(result
+            .rows
+            .iter()
+            .filter_map(|row| match row.values.first() {
+                Some(Value::Integer(version)) => u64::try_from(*version).ok(),
+                _ => None,
+            })
+            .collect())
+    }
+
+    async fn apply(&mut self, component_id: &str, migration: &Migration) -> Result<()> {
+        self.connection.execute_batch("BEGIN").await?;
+        let applied = async {
+            self.connection.execute_batch(&migration.sql).await?;
+            self.record(component_id, migration).await
+        };
+        match applied.await {
+            Ok(()) => self.connection.execute_batch("COMMIT").await,
+            Err(e) => {
+                _ = self.connection.execute_batch("ROLLBACK").await;
+                Err(e)
+            }
+        }
+    }
+}
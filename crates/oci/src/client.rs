@@ -20,6 +20,7 @@ use tokio::fs;
 use walkdir::WalkDir;
 
 use crate::auth::AuthConfig;
+use crate::docker::{DockerImage, DockerImageOptions};
 
 // TODO: the media types for application, data and archive layer are not final
 /// Media type for a layer representing a locked Spin application configuration
@@ -103,6 +104,45 @@ impl Client {
         self.push_locked_core(locked, auth, reference).await
     }
 
+    /// Push a Spin application to an OCI registry as a container image which
+    /// runs the application with a Spin binary, and return the digest (or None
+    /// if the digest cannot be determined).
+    pub async fn push_docker(
+        &mut self,
+        manifest_path: &Path,
+        reference: impl AsRef<str>,
+        options: &DockerImageOptions,
+    ) -> Result<Option<String>> {
+        let reference: Reference = reference
+            .as_ref()
+            .parse()
+            .with_context(|| format!("cannot parse reference {}", reference.as_ref()))?;
+        let auth = Self::auth(&reference).await?;
+        let working_dir = tempfile::tempdir()?;
+
+        let locked = spin_loader::from_file(
+            manifest_path,
+            FilesMountStrategy::Copy(working_dir.path().into()),
+        )
+        .await?;
+        let image = DockerImage::build(locked, options).await?;
+
+        let oci_config =
+            oci_distribution::client::Config::oci_v1_from_config_file(image.config, None)?;
+        let manifest = OciImageManifest::build(&image.layers, &oci_config, None);
+
+        let response = self
+            .oci
+            .push(&reference, &image.layers, oci_config, &auth, Some(manifest))
+            .await
+            .map(|push_response| push_response.manifest_url)
+            .context("cannot push container image")?;
+
+        tracing::info!("Pushed {:?}", response);
+
+        Ok(digest_from_url(&response))
+    }
+
     /// Push a Spin application to an OCI registry and return the digest (or None
     /// if the digest cannot be determined).
     async fn push_locked_core(
@@ -344,6 +384,44 @@ impl Client {
         Ok(())
     }
 
+    /// Pull a single Wasm component from an OCI registry, returning its bytes.
+    ///
+    /// The artifact must contain exactly one Wasm layer. The component is also
+    /// written to the Wasm cache.
+    pub async fn pull_component(&mut self, reference: &str) -> Result<Vec<u8>> {
+        let reference: Reference = reference.parse().context("cannot parse reference")?;
+        let auth = Self::auth(&reference).await?;
+
+        let (manifest, _) = self.oci.pull_image_manifest(&reference, &auth).await?;
+        let mut wasm_layers = manifest
+            .layers
+            .iter()
+            .filter(|layer| is_wasm_media_type(&layer.media_type));
+        let layer = match (wasm_layers.next(), wasm_layers.next()) {
+            (Some(layer), None) => layer,
+            (None, _) => bail!("{reference} does not contain a Wasm layer"),
+            (Some(_), Some(_)) => bail!(
+                "{reference} contains more than one Wasm layer. Is it a Spin application rather than a component?"
+            ),
+        };
+
+        if let Ok(path) = self.cache.wasm_file(&layer.digest) {
+            tracing::debug!("Layer {} already exists in cache", &layer.digest);
+            return fs::read(&path)
+                .await
+                .with_context(|| format!("failed to read cached component {path:?}"));
+        }
+
+        let mut bytes = Vec::with_capacity(layer.size.try_into()?);
+        self.oci
+            .pull_blob(&reference, &layer.digest, &mut bytes)
+            .await?;
+        self.cache.write_wasm(&bytes, &layer.digest).await?;
+        tracing::info!("Pulled component {}@{}", reference, layer.digest);
+
+        Ok(bytes)
+    }
+
     /// Get the file path to an OCI manifest given a reference.
     /// If the directory for the manifest does not exist, this will create it.
     async fn manifest_path(&self, reference: impl AsRef<str>) -> Result<PathBuf> {
@@ -605,6 +683,11 @@ async fn layer_count(locked: LockedApp) -> Result<usize> {
     Ok(layer_count)
 }
 
+fn is_wasm_media_type(media_type: &str) -> bool {
+    // Components published by other tools commonly use `application/wasm`.
+    media_type == WASM_LAYER_MEDIA_TYPE || media_type == "application/wasm"
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -0,0 +1,284 @@
+//! Packaging Spin applications as container images.
+//!
+//! An image built here runs on platforms which only run container images,
+//! such as ECS or Cloud Run, without a Dockerfile. It has no base image: the
+//! first layer holds only a Spin binary and the second the application, with
+//! the trigger as the image's entrypoint. The runtime layer is identical for
+//! every app built with the same binary, so registries store it once.
+
+use std::path::PathBuf;
+
+use anyhow::{bail, Context, Result};
+use async_compression::tokio::write::GzipEncoder;
+use async_tar::{EntryType, Header};
+use oci_distribution::{
+    client::ImageLayer,
+    config::{Architecture, Config, ConfigFile, Os, Rootfs},
+    manifest::IMAGE_LAYER_GZIP_MEDIA_TYPE,
+};
+use spin_common::url::parse_file_url;
+use spin_locked_app::locked::{ContentRef, LockedApp};
+use tokio::io::AsyncWriteExt;
+use walkdir::WalkDir;
+
+/// Where the Spin binary is placed in the image.
+const RUNTIME_PATH: &str = "usr/local/bin/spin";
+/// Where the application is placed in the image.
+const APP_DIR: &str = "app";
+/// The triggers which the Spin binary runs itself, rather than via a plugin.
+const BUILT_IN_TRIGGERS: &[&str] = &["http", "redis"];
+
+/// How to build a container image for an application.
+#[derive(Clone, Debug)]
+pub struct DockerImageOptions {
+    /// The Spin binary to run the application with. There is no base image,
+    /// so this must run without a libc, e.g. a static musl build.
+    pub runtime: PathBuf,
+    /// The architecture of the runtime, as a Go architecture name, e.g. "amd64".
+    pub architecture: String,
+    /// The address an HTTP application listens on inside the container.
+    pub listen: String,
+}
+
+/// The layers and config of a container image.
+pub(crate) struct DockerImage {
+    pub layers: Vec<ImageLayer>,
+    pub config: ConfigFile,
+}
+
+impl DockerImage {
+    /// Builds an image which runs `locked`, whose component and file sources
+    /// must be local files.
+    pub(crate) async fn build(mut locked: LockedApp, options: &DockerImageOptions) -> Result<Self> {
+        let trigger_type = trigger_type(&locked)?.to_owned();
+        let architecture = parse_architecture(&options.architecture)?;
+
+        let runtime = tokio::fs::read(&options.runtime)
+            .await
+            .with_context(|| format!("cannot read Spin runtime {:?}", options.runtime))?;
+        let mut runtime_layer = LayerBuilder::new();
+        runtime_layer.file(RUNTIME_PATH, &runtime, 0o755).await?;
+
+        // Copy each component's sources into the image, and point the locked
+        // app at the copies.
+        let mut app_layer = LayerBuilder::new();
+        for c in &mut locked.components {
+            let component_dir = format!("{APP_DIR}/components/{}", c.id);
+            let wasm = local_source(&c.source.content)?;
+            let wasm_path = format!("{component_dir}/component.wasm");
+            app_layer
+                .file(&wasm_path, &tokio::fs::read(&wasm).await?, 0o644)
+                .await?;
+            c.source.content = file_content_ref(&wasm_path);
+
+            for (index, f) in c.files.iter_mut().enumerate() {
+                let source = local_source(&f.content)?;
+                let files_dir = format!("{component_dir}/files/{index}");
+                app_layer.dir(&files_dir).await?;
+                for entry in WalkDir::new(&source) {
+                    let entry = entry?;
+                    // Can unwrap because we got to 'entry' from walking 'source'
+                    let rel_path = entry.path().strip_prefix(&source).unwrap();
+                    if rel_path.as_os_str().is_empty() {
+                        continue;
+                    }
+                    let path = format!("{files_dir}/{}", rel_path.to_string_lossy());
+                    if entry.file_type().is_dir() {
+                        app_layer.dir(&path).await?;
+                    } else if entry.file_type().is_file() {
+                        app_layer
+                            .file(&path, &tokio::fs::read(entry.path()).await?, 0o644)
+                            .await?;
+                    }
+                }
+                f.content = file_content_ref(&files_dir);
+            }
+        }
+        locked.metadata.remove("origin");
+        let locked_path = format!("{APP_DIR}/spin.lock");
+        let locked_json = serde_json::to_vec(&locked).context("could not serialize locked app")?;
+        app_layer.file(&locked_path, &locked_json, 0o644).await?;
+        // The trigger's working directory; there is no /tmp without a base image.
+        let working_dir = format!("{APP_DIR}/work");
+        app_layer.dir(&working_dir).await?;
+
+        let (runtime_layer, runtime_diff_id) = runtime_layer.finish().await?;
+        let (app_layer, app_diff_id) = app_layer.finish().await?;
+
+        let mut entrypoint = vec![
+            format!("/{RUNTIME_PATH}"),
+            "trigger".to_owned(),
+            trigger_type.clone(),
+        ];
+        if trigger_type == "http" {
+            entrypoint.extend(["--listen".to_owned(), options.listen.clone()]);
+        }
+        let exposed_ports = (trigger_type == "http")
+            .then(|| exposed_port(&options.listen))
+            .flatten()
+            .map(|port| [format!("{port}/tcp")].into());
+
+        let config = ConfigFile {
+            architecture,
+            os: Os::Linux,
+            config: Some(Config {
+                entrypoint: Some(entrypoint),
+                env: Some(vec![
+                    format!("SPIN_LOCKED_URL=file:///{locked_path}"),
+                    format!("SPIN_WORKING_DIR=/{working_dir}"),
+                ]),
+                working_dir: Some(format!("/{APP_DIR}")),
+                exposed_ports,
+                ..Default::default()
+            }),
+            rootfs: Rootfs {
+                r#type: "layers".to_owned(),
+                diff_ids: vec![runtime_diff_id, app_diff_id],
+            },
+            ..Default::default()
+        };
+
+        Ok(Self {
+            layers: vec![runtime_layer, app_layer],
+            config,
+        })
+    }
+}
+
+/// Builds a gzipped tar layer.
+struct LayerBuilder {
+    tar: async_tar::Builder<Vec<u8>>,
+}
+
+impl LayerBuilder {
+    fn new() -> Self {
+        Self {
+            tar: async_tar::Builder::new(Vec::new()),
+        }
+    }
+
+    async fn file(&mut self, path: &str, data: &[u8], mode: u32) -> Result<()> {
+        let mut header = Header::new_gnu();
+        header.set_entry_type(EntryType::Regular);
+        header.set_size(data.len() as u64);
+        header.set_mode(mode);
+        header.set_mtime(0);
+        header.set_cksum();
+        self.tar
+            .append_data(&mut header, path, data)
+            .await
+            .with_context(|| format!("cannot add {path} to image layer"))
+    }
+
+    async fn dir(&mut self, path: &str) -> Result<()> {
+        let mut header = Header::new_gnu();
+        header.set_entry_type(EntryType::Directory);
+        header.set_size(0);
+        header.set_mode(0o755);
+        header.set_mtime(0);
+        header.set_cksum();
+        self.tar
+            .append_data(&mut header, format!("{path}/"), &[][..])
+            .await
+            .with_context(|| format!("cannot add {path} to image layer"))
+    }
+
+    /// Returns the compressed layer and the digest of its uncompressed
+    /// contents, which the image config lists as its diff ID.
+    async fn finish(self) -> Result<(ImageLayer, String)> {
+        let tar = self.tar.into_inner().await?;
+        let diff_id = format!(
+            "sha256:{}",
+            spin_common::sha256::hex_digest_from_bytes(&tar)
+        );
+        let mut encoder = GzipEncoder::new(Vec::new());
+        encoder.write_all(&tar).await?;
+        encoder.shutdown().await?;
+        let layer = ImageLayer::new(
+            encoder.into_inner(),
+            IMAGE_LAYER_GZIP_MEDIA_TYPE.to_string(),
+            None,
+        );
+        Ok((layer, diff_id))
+    }
+}
+
+fn trigger_type(locked: &LockedApp) -> Result<&str> {
+    let mut types = locked.triggers.iter().map(|t| t.trigger_type.as_str());
+    let Some(trigger_type) = types.next() else {
+        bail!("no triggers in app");
+    };
+    if types.any(|t| t != trigger_type) {
+        bail!("multiple trigger types not yet supported");
+    }
+    if !BUILT_IN_TRIGGERS.contains(&trigger_type) {
+        bail!("cannot build an image for the '{trigger_type}' trigger: only the built-in http and redis triggers can run without plugins");
+    }
+    Ok(trigger_type)
+}
+
+fn parse_architecture(architecture: &str) -> Result<Architecture> {
+    match architecture {
+        "amd64" => Ok(Architecture::Amd64),
+        "arm64" => Ok(Architecture::Arm64),
+        other => bail!("unsupported image architecture '{other}': expected amd64 or arm64"),
+    }
+}
+
+/// The Go architecture name of the machine, which is what the running Spin
+/// binary is built for.
+pub fn host_architecture() -> &'static str {
+    match std::env::consts::ARCH {
+        "x86_64" => "amd64",
+        "aarch64" => "arm64",
+        other => other,
+    }
+}
+
+fn local_source(content: &ContentRef) -> Result<PathBuf> {
+    let source = content
+        .source
+        .as_deref()
+        .context("content loaded from disk should contain a file source")?;
+    parse_file_url(source)
+}
+
+fn file_content_ref(image_path: &str) -> ContentRef {
+    ContentRef {
+        source: Some(format!("file:///{image_path}")),
+        ..Default::default()
+    }
+}
+
+fn exposed_port(listen: &str) -> Option<u16> {
+    listen.rsplit_once(':')?.1.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exposed_port_is_parsed_from_listen_address() {
+        assert_eq!(Some(80), exposed_port("0.0.0.0:80"));
+        assert_eq!(Some(8080), exposed_port("[::]:8080"));
+        assert_eq!(None, exposed_port("localhost"));
+    }
+
+    #[test]
+    fn only_docker_architectures_are_accepted() {
+        assert!(parse_architecture("amd64").is_ok());
+        assert!(parse_architecture("x86_64").is_err());
+    }
+
+    #[tokio::test]
+    async fn layers_record_uncompressed_digests() {
+        let mut layer = LayerBuilder::new();
+        layer.dir("app").await.unwrap();
+        layer.file("app/hello.txt", b"hello", 0o644).await.unwrap();
+        let (layer, diff_id) = layer.finish().await.unwrap();
+        assert_eq!(IMAGE_LAYER_GZIP_MEDIA_TYPE, layer.media_type);
+        assert!(diff_id.starts_with("sha256:"));
+        assert_ne!(diff_id, layer.sha256_digest());
+    }
+}
@@ -3,6 +3,7 @@
 
 mod auth;
 pub mod client;
+pub mod docker;
 mod loader;
 pub mod utils;
 
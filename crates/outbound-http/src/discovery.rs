@@ -0,0 +1,383 @@
+//! Service discovery for outbound HTTP.
+//!
+//! A component may name a service instead of a host, as
+//! `http://<service>.service/...`, and list it in `allowed_outbound_hosts` the
+//! same way. When service discovery is configured, the trigger resolves every
+//! service its components are allowed to reach, through DNS SRV records or a
+//! Consul catalog, and keeps the instances up to date in the background.
+//! Each request to a service is then sent to one of its instances in turn,
+//! in proportion to their SRV weights.
+//!
+//! The allowed hosts check is made against the service name, before the
+//! request is redirected to an instance.
+
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, OnceLock, RwLock,
+    },
+    time::Duration,
+};
+
+use anyhow::{Context, Result};
+use spin_outbound_networking::dns;
+
+/// The host suffix which marks a host as a service name.
+pub const SERVICE_HOST_SUFFIX: &str = ".service";
+
+const REFRESH_INTERVAL: Duration = Duration::from_secs(10);
+
+static RESOLVER: OnceLock<Arc<ServiceResolver>> = OnceLock::new();
+
+/// Where services are looked up.
+#[derive(Clone, Debug)]
+pub enum ServiceDiscovery {
+    /// DNS SRV records named `_<service>._tcp.<domain>`.
+    DnsSrv {
+        /// The DNS server to query.
+        nameserver: SocketAddr,
+        /// The domain under which services are registered.
+        domain: String,
+    },
+    /// The healthy instances in a Consul catalog.
+    Consul {
+        /// The Consul HTTP API, e.g. `http://127.0.0.1:8500`.
+        url: String,
+        /// The ACL token to send, if any.
+        token: Option<String>,
+        /// The datacenter to query, if not the agent's own.
+        datacenter: Option<String>,
+    },
+}
+
+/// An instance of a service.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Endpoint {
+    /// The instance's host name or address.
+    pub host: String,
+    /// The instance's port.
+    pub port: u16,
+    /// The instance's share of requests, relative to the others.
+    pub weight: u16,
+}
+
+/// Resolves the given services and keeps them up to date, so that requests
+/// to them are redirected to their instances. Must be called from a Tokio
+/// runtime, at most once per process.
+pub async fn start(
+    discovery: ServiceDiscovery,
+    services: impl IntoIterator<Item = String>,
+) -> Result<()> {
+    let resolver = Arc::new(ServiceResolver {
+        discovery,
+        services: services.into_iter().collect(),
+        endpoints: Default::default(),
+        next: AtomicUsize::new(0),
+    });
+    resolver.refresh().await;
+    RESOLVER
+        .set(resolver.clone())
+        .map_err(|_| anyhow::anyhow!("service discovery has already been started"))?;
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(REFRESH_INTERVAL).await;
+            resolver.refresh().await;
+        }
+    });
+    Ok(())
+}
+
+/// Returns the service named by a host, if it names one.
+pub fn service_name(host: &str) -> Option<&str> {
+    host.strip_suffix(SERVICE_HOST_SUFFIX)
+        .filter(|name| !name.is_empty())
+}
+
+/// If `url` names a service and service discovery is running, returns the
+/// URL of the instance the request should go to. It is an error if the
+/// service has no instances.
+pub fn resolve_url(url: &str) -> Result<Option<String>> {
+    let Some(resolver) = RESOLVER.get() else {
+        return Ok(None);
+    };
+    let Ok(mut parsed) = url::Url::parse(url) else {
+        return Ok(None);
+    };
+    let Some(service) = parsed.host_str().and_then(service_name) else {
+        return Ok(None);
+    };
+    let endpoint = resolver
+        .pick(service)
+        .with_context(|| format!("no instances of service '{service}' were found"))?;
+    parsed
+        .set_host(Some(&endpoint.host))
+        .with_context(|| format!("invalid instance host '{}'", endpoint.host))?;
+    parsed
+        .set_port(Some(endpoint.port))
+        .map_err(|_| anyhow::anyhow!("cannot set the port of {url}"))?;
+    Ok(Some(parsed.into()))
+}
+
+struct ServiceResolver {
+    discovery: ServiceDiscovery,
+    services: Vec<String>,
+    endpoints: RwLock<HashMap<String, Vec<Endpoint>>>,
+    next: AtomicUsize,
+}
+
+impl ServiceResolver {
+    async fn refresh(&self) {
+        for service in &self.services {
+            match self.discovery.lookup(service).await {
+                Ok(endpoints) => {
+                    if endpoints.is_empty() {
+                        tracing::warn!("Service discovery found no instances of '{service}'");
+                    }
+                    self.endpoints
+                        .write()
+                        .unwrap()
+                        .insert(service.clone(), endpoints);
+                }
+                // Keep the last known instances rather than failing requests.
+                Err(e) => tracing::warn!("Service discovery failed for '{service}': {e:#}"),
+            }
+        }
+    }
+
+    fn pick(&self, service: &str) -> Option<Endpoint> {
+        let endpoints = self.endpoints.read().unwrap();
+        pick_weighted(
+            endpoints.get(service)?,
+            self.next.fetch_add(1, Ordering::Relaxed),
+        )
+    }
+}
+
+/// Picks the endpoint for the `n`th request, so that successive requests go
+/// to each endpoint in proportion to its weight.
+fn pick_weighted(endpoints: &[Endpoint], n: usize) -> Option<Endpoint> {
+    let weight = |e: &Endpoint| usize::from(e.weight.max(1));
+    let total: usize = endpoints.iter().map(weight).sum();
+    if total == 0 {
+        return None;
+    }
+    let mut position = n % total;
+    for endpoint in endpoints {
+        if position < weight(endpoint) {
+            return Some(endpoint.clone());
+        }
+        position -= weight(endpoint);
+    }
+    None
+}
+
+impl ServiceDiscovery {
+    /// Returns the first nameserver in `/etc/resolv.conf`.
+    pub fn system_nameserver() -> Result<SocketAddr> {
+        let resolv_conf = std::fs::read_to_string("/etc/resolv.conf")
+            .context("cannot read /etc/resolv.conf; set a nameserver explicitly")?;
+        resolv_conf
+            .lines()
+            .find_map(|line| {
+                let address = line.trim().strip_prefix("nameserver")?.trim();
+                address.parse().ok().map(|ip| SocketAddr::new(ip, 53))
+            })
+            .context("no nameserver in /etc/resolv.conf; set a nameserver explicitly")
+    }
+
+    async fn lookup(&self, service: &str) -> Result<Vec<Endpoint>> {
+        match self {
+            Self::DnsSrv { nameserver, domain } => {
+                let name = format!("_{service}._tcp.{}", domain.trim_matches('.'));
+                lookup_srv(*nameserver, &name).await
+            }
+            Self::Consul {
+                url,
+                token,
+                datacenter,
+            } => lookup_consul(url, token.as_deref(), datacenter.as_deref(), service).await,
+        }
+    }
+}
+
+async fn lookup_consul(
+    url: &str,
+    token: Option<&str>,
+    datacenter: Option<&str>,
+    service: &str,
+) -> Result<Vec<Endpoint>> {
+    let mut request = reqwest::Client::new()
+        .get(format!(
+            "{}/v1/health/service/{service}",
+            url.trim_end_matches('/')
+        ))
+        .query(&[("passing", "true")]);
+    if let Some(datacenter) = datacenter {
+        request = request.query(&[("dc", datacenter)]);
+    }
+    if let Some(token) = token {
+        request = request.header("X-Consul-Token", token);
+    }
+    let entries: serde_json::Value = request.send().await?.error_for_status()?.json().await?;
+    parse_consul_entries(&entries)
+}
+
+fn parse_consul_entries(entries: &serde_json::Value) -> Result<Vec<Endpoint>> {
+    let entries = entries
+        .as_array()
+        .context("Consul returned an unexpected response")?;
+    entries
+        .iter()
+        .map(|entry| {
+            let service = &entry["Service"];
+            // Services registered without an address use their node's.
+            let host = service["Address"]
+                .as_str()
+                .filter(|a| !a.is_empty())
+                .or_else(|| entry["Node"]["Address"].as_str())
+                .context("Consul service entry has no address")?;
+            let port = service["Port"]
+                .as_u64()
+                .and_then(|p| u16::try_from(p).ok())
+                .context("Consul service entry has no port")?;
+            Ok(Endpoint {
+                host: host.to_owned(),
+                port,
+                weight: 1,
+            })
+        })
+        .collect()
+}
+
+async fn lookup_srv(nameserver: SocketAddr, name: &str) -> Result<Vec<Endpoint>> {
+    let id = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .subsec_nanos() as u16;
+    let response = dns::exchange_udp(nameserver, &srv_query(id, name)?).await?;
+    parse_srv_response(id, &response)
+}
+
+fn srv_query(id: u16, name: &str) -> Result<Vec<u8>> {
+    dns::encode_query(id, name, dns::DNS_TYPE_SRV)
+}
+
+/// Parses the SRV records in a DNS response, returning those with the best
+/// (lowest) priority.
+fn parse_srv_response(id: u16, response: &[u8]) -> Result<Vec<Endpoint>> {
+    let mut reader = dns::MessageReader::new(response);
+    // NXDOMAIN: the service is not registered.
+    let Some(answers) = reader.header(id)? else {
+        return Ok(vec![]);
+    };
+
+    let mut records = vec![];
+    for _ in 0..answers {
+        reader.name()?;
+        let record_type = reader.u16()?;
+        reader.skip(6); // Class and TTL.
+        let length = usize::from(reader.u16()?);
+        let end = reader.position() + length;
+        if record_type == dns::DNS_TYPE_SRV {
+            let priority = reader.u16()?;
+            let weight = reader.u16()?;
+            let port = reader.u16()?;
+            let target = reader.name()?;
+            // A target of "." means the service is decidedly not available.
+            if !target.is_empty() {
+                records.push((
+                    priority,
+                    Endpoint {
+                        host: target,
+                        port,
+                        weight,
+                    },
+                ));
+            }
+        }
+        reader.seek(end);
+    }
+
+    let Some(best) = records.iter().map(|(priority, _)| *priority).min() else {
+        return Ok(vec![]);
+    };
+    Ok(records
+        .into_iter()
+        .filter(|(priority, _)| *priority == best)
+        .map(|(_, endpoint)| endpoint)
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn endpoint(host: &str, weight: u16) -> Endpoint {
+        Endpoint {
+            host: host.into(),
+            port: 8080,
+            weight,
+        }
+    }
+
+    #[test]
+    fn service_names_are_recognised() {
+        assert_eq!(Some("orders"), service_name("orders.service"));
+        assert_eq!(None, service_name(".service"));
+        assert_eq!(None, service_name("example.com"));
+    }
+
+    #[test]
+    fn picks_follow_weights() {
+        let endpoints = [endpoint("a", 3), endpoint("b", 1)];
+        let picks = (0..8)
+            .map(|n| pick_weighted(&endpoints, n).unwrap().host)
+            .collect::<Vec<_>>();
+        assert_eq!(vec!["a", "a", "a", "b", "a", "a", "a", "b"], picks);
+        assert_eq!(None, pick_weighted(&[], 0));
+    }
+
+    #[test]
+    fn srv_responses_are_parsed() {
+        let query = srv_query(0x1234, "_orders._tcp.example.com").unwrap();
+        let mut response = query.clone();
+        // Response, recursion available, no error, two answers.
+        response[2..4].copy_from_slice(&[0x81, 0x80]);
+        response[6..8].copy_from_slice(&[0, 2]);
+        let answer = |priority: u8, port: u8, target: &[u8]| {
+            // Name compressed to point at the question.
+            let mut answer = vec![0xc0, 12, 0, 33, 0, 1, 0, 0, 0, 60];
+            answer.extend([0, (6 + target.len()) as u8]);
+            answer.extend([0, priority, 0, 5, 0x1f, port]);
+            answer.extend(target);
+            answer
+        };
+        response.extend(answer(10, 0x90, b"\x02a1\x07example\x03com\x00"));
+        response.extend(answer(20, 0x91, b"\x02b1\xc0\x19"));
+
+        let endpoints = parse_srv_response(0x1234, &response).unwrap();
+        assert_eq!(
+            vec![Endpoint {
+                host: "a1.example.com".into(),
+                port: 0x1f90,
+                weight: 5
+            }],
+            endpoints
+        );
+        parse_srv_response(0x4321, &response).unwrap_err();
+    }
+
+    #[test]
+    fn consul_entries_fall_back_to_node_addresses() {
+        let entries = serde_json::json!([
+            { "Node": { "Address": "10.0.0.1" }, "Service": { "Address": "", "Port": 8080 } },
+            { "Node": { "Address": "10.0.0.2" }, "Service": { "Address": "10.1.0.2", "Port": 8081 } },
+        ]);
+        let endpoints = parse_consul_entries(&entries).unwrap();
+        assert_eq!("10.0.0.1", endpoints[0].host);
+        assert_eq!("10.1.0.2", endpoints[1].host);
+        assert_eq!(8081, endpoints[1].port);
+    }
+}
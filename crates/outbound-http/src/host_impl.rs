@@ -1,7 +1,6 @@
 use anyhow::Result;
 use http::HeaderMap;
-use reqwest::Client;
-use spin_core::async_trait;
+use spin_core::{async_trait, faults::Fault, replay::RecordedExchange};
 use spin_outbound_networking::{AllowedHostsConfig, OutboundUrl};
 use spin_world::v1::{
     http as outbound_http,
@@ -16,7 +15,6 @@ pub struct OutboundHttp {
     /// During an incoming HTTP request, origin is set to the host of that incoming HTTP request.
     /// This is used to direct outbound requests to the same host when allowed.
     pub origin: String,
-    client: Option<Client>,
 }
 
 impl OutboundHttp {
@@ -62,6 +60,7 @@ impl outbound_http::Host for OutboundHttp {
             };
 
             let req_url = reqwest::Url::parse(&abs_url).map_err(|_| HttpError::InvalidUrl)?;
+            spin_core::audit::record_outbound("https", &abs_url);
 
             let headers = request_headers(req.headers).map_err(|_| HttpError::RuntimeError)?;
             let body = req.body.unwrap_or_default().to_vec();
@@ -70,19 +69,67 @@ impl outbound_http::Host for OutboundHttp {
                 tracing::log::warn!("HTTP params field is deprecated");
             }
 
-            // Allow reuse of Client's internal connection pool for multiple requests
-            // in a single component execution
-            let client = self.client.get_or_insert_with(Default::default);
-
-            let resp = client
-                .request(method, req_url)
-                .headers(headers)
-                .body(body)
-                .send()
-                .await
-                .map_err(log_reqwest_error)?;
+            let replayed = spin_core::replay::replayed_response(method.as_str(), &abs_url);
+            if let Some(replayed) = replayed {
+                return replayed.map(response_from_recording).map_err(|e| {
+                    tracing::warn!("Outbound HTTP replay error: {e}");
+                    HttpError::RuntimeError
+                });
+            }
+
+            match spin_core::faults::inject("https", &abs_url).await {
+                Some(Fault::Status(status)) => {
+                    return Ok(Response {
+                        status,
+                        headers: None,
+                        body: None,
+                    })
+                }
+                Some(fault) => {
+                    tracing::warn!("Outbound HTTP error: URL {abs_url}, {fault}");
+                    return Err(HttpError::RuntimeError);
+                }
+                None => {}
+            }
+
+            // Requests to a service go to one of its instances.
+            let req_url = match crate::discovery::resolve_url(&abs_url) {
+                Ok(Some(target_url)) => {
+                    reqwest::Url::parse(&target_url).map_err(|_| HttpError::InvalidUrl)?
+                }
+                Ok(None) => req_url,
+                Err(e) => {
+                    tracing::warn!("Outbound HTTP service discovery error: {e:#}");
+                    return Err(HttpError::RuntimeError);
+                }
+            };
+
+            // Connections are pooled across invocations, and a request
+            // holds its place under the per-host limit until its response
+            // body has been read.
+            let resp = spin_core::usage::outbound(async {
+                let _request = crate::pool::acquire(req_url.host_str().unwrap_or_default()).await;
+                let resp = crate::pool::client()
+                    .request(method.clone(), req_url)
+                    .headers(headers)
+                    .body(body)
+                    .send()
+                    .await
+                    .map_err(log_reqwest_error)?;
+                response_from_reqwest(resp).await
+            })
+            .await?;
             tracing::log::trace!("Returning response from outbound request to {}", req.uri);
-            response_from_reqwest(resp).await
+            if spin_core::replay::is_recording() {
+                spin_core::replay::record_response(RecordedExchange {
+                    method: method.to_string(),
+                    url: abs_url,
+                    status: resp.status,
+                    headers: resp.headers.clone().unwrap_or_default(),
+                    body: resp.body.clone().unwrap_or_default(),
+                });
+            }
+            Ok(resp)
         }
         .await)
     }
@@ -141,6 +188,14 @@ async fn response_from_reqwest(res: reqwest::Response) -> Result<Response, HttpE
     })
 }
 
+fn response_from_recording(exchange: RecordedExchange) -> Response {
+    Response {
+        status: exchange.status,
+        headers: Some(exchange.headers),
+        body: Some(exchange.body),
+    }
+}
+
 fn request_headers(h: Headers) -> anyhow::Result<HeaderMap> {
     let mut res = HeaderMap::new();
     for (k, v) in h {
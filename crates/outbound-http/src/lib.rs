@@ -1,7 +1,13 @@
 #[cfg(feature = "runtime")]
+pub mod discovery;
+#[cfg(feature = "runtime")]
 mod host_component;
 #[cfg(feature = "runtime")]
 mod host_impl;
+#[cfg(feature = "runtime")]
+pub mod pool;
+#[cfg(feature = "runtime")]
+pub mod proxy;
 
 #[cfg(feature = "runtime")]
 pub use host_component::OutboundHttpComponent;
@@ -0,0 +1,247 @@
+//! The connection pool shared by outbound HTTP requests.
+//!
+//! Every outbound request, whether made through the Spin HTTP interface or
+//! through `wasi:http/outgoing-handler`, goes through one client, so that connections are kept alive and reused across
+//! invocations rather than opened, and their TLS handshakes repeated, for
+//! every request. Where a server negotiates HTTP/2, concurrent requests to
+//! it are multiplexed over a single connection.
+//!
+//! The number of requests in flight to each host can be limited; requests
+//! over the limit wait for an earlier one to finish. Requests go through the
+//! proxies described in [`crate::proxy`].
+
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex, OnceLock},
+    time::Duration,
+};
+
+use anyhow::{bail, Result};
+use hyper::client::connect::dns::Name;
+use reqwest::{
+    dns::{Addrs, Resolve, Resolving},
+    Client,
+};
+use serde_json::{json, Value};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use crate::proxy::{Proxies, ProxyConfig};
+
+/// How long an idle connection is kept open by default.
+pub const DEFAULT_IDLE_TIMEOUT: Duration = Duration::from_secs(90);
+
+static CONFIG: OnceLock<PoolConfig> = OnceLock::new();
+static POOL: OnceLock<Pool> = OnceLock::new();
+
+/// The settings of the connection pool.
+#[derive(Clone, Debug)]
+pub struct PoolConfig {
+    /// The most requests which may be in flight to one host at a time, or
+    /// `None` for no limit.
+    pub max_connections_per_host: Option<usize>,
+    /// How long an idle connection is kept open.
+    pub idle_timeout: Duration,
+    /// The proxies requests are sent through.
+    pub proxy: ProxyConfig,
+}
+
+impl Default for PoolConfig {
+    fn default() -> Self {
+        Self {
+            max_connections_per_host: None,
+            idle_timeout: DEFAULT_IDLE_TIMEOUT,
+            proxy: Default::default(),
+        }
+    }
+}
+
+/// Configures the connection pool. This must be called before the first
+/// outbound request, at most once per process.
+pub fn configure(config: PoolConfig) -> Result<()> {
+    if let Some(max) = config.max_connections_per_host {
+        if max == 0 {
+            bail!("the maximum connections per host must be greater than zero");
+        }
+    }
+    Proxies::new(&config.proxy)?;
+    if POOL.get().is_some() || CONFIG.set(config).is_err() {
+        bail!("the outbound HTTP connection pool has already been configured");
+    }
+    Ok(())
+}
+
+/// Returns the connection pool's metrics, per host.
+pub fn metrics() -> Value {
+    let hosts = match POOL.get() {
+        Some(pool) => pool.hosts.lock().unwrap().clone(),
+        None => Default::default(),
+    };
+    let config = CONFIG.get().cloned().unwrap_or_default();
+    let hosts: BTreeMap<_, _> = hosts
+        .into_iter()
+        .map(|(host, stats)| {
+            let value = json!({
+                "requests": stats.requests,
+                "connections_opened": stats.connections_opened,
+                "in_flight": stats.in_flight,
+                "queued": stats.queued,
+            });
+            (host, value)
+        })
+        .collect();
+    json!({
+        "max_connections_per_host": config.max_connections_per_host,
+        "idle_timeout_secs": config.idle_timeout.as_secs(),
+        "hosts": hosts,
+    })
+}
+
+/// Returns the shared client.
+pub fn client() -> &'static Client {
+    &pool().client
+}
+
+/// Waits until a request may be sent to `host`, and counts it as in flight
+/// until the returned guard is dropped.
+pub async fn acquire(host: &str) -> RequestGuard {
+    let pool = pool();
+    let permits = {
+        let mut hosts = pool.hosts.lock().unwrap();
+        let stats = hosts.entry(host.to_owned()).or_default();
+        stats.requests += 1;
+        stats.queued += 1;
+        pool.permits(host)
+    };
+    let permit = match permits {
+        // The semaphore is never closed.
+        Some(permits) => permits.acquire_owned().await.ok(),
+        None => None,
+    };
+    let mut hosts = pool.hosts.lock().unwrap();
+    let stats = hosts.entry(host.to_owned()).or_default();
+    stats.queued -= 1;
+    stats.in_flight += 1;
+    RequestGuard {
+        host: host.to_owned(),
+        _permit: permit,
+    }
+}
+
+/// Keeps a request counted as in flight, and holds its place under the
+/// per-host limit.
+pub struct RequestGuard {
+    host: String,
+    _permit: Option<OwnedSemaphorePermit>,
+}
+
+impl Drop for RequestGuard {
+    fn drop(&mut self) {
+        if let Some(stats) = pool().hosts.lock().unwrap().get_mut(&self.host) {
+            stats.in_flight -= 1;
+        }
+    }
+}
+
+struct Pool {
+    client: Client,
+    max_connections_per_host: Option<usize>,
+    hosts: Mutex<BTreeMap<String, HostStats>>,
+    permits: Mutex<BTreeMap<String, Arc<Semaphore>>>,
+}
+
+#[derive(Clone, Debug, Default)]
+struct HostStats {
+    requests: u64,
+    connections_opened: u64,
+    in_flight: u64,
+    queued: u64,
+}
+
+impl Pool {
+    fn new(config: &PoolConfig) -> Self {
+        let mut builder = Client::builder()
+            .pool_idle_timeout(config.idle_timeout)
+            .tcp_keepalive(Duration::from_secs(60))
+            .dns_resolver(Arc::new(CountingResolver));
+        if let Some(max) = config.max_connections_per_host {
+            builder = builder.pool_max_idle_per_host(max);
+        }
+        // The environment is read by `Proxies`, not the client, so that
+        // configured proxies and `NO_PROXY` combine as documented.
+        builder = builder.no_proxy();
+        let proxies = Proxies::new(&config.proxy).unwrap_or_else(|err| {
+            tracing::warn!("Ignoring outbound HTTP proxy settings: {err:#}");
+            Proxies::default()
+        });
+        if !proxies.is_empty() {
+            builder = builder.proxy(reqwest::Proxy::custom(move |url| proxies.proxy_for(url)));
+        }
+        Self {
+            // Building only fails if the TLS backend can't be initialized,
+            // in which case no request could succeed anyway.
+            client: builder.build().unwrap_or_default(),
+            max_connections_per_host: config.max_connections_per_host,
+            hosts: Default::default(),
+            permits: Default::default(),
+        }
+    }
+
+    fn permits(&self, host: &str) -> Option<Arc<Semaphore>> {
+        let max = self.max_connections_per_host?;
+        let mut permits = self.permits.lock().unwrap();
+        let semaphore = permits
+            .entry(host.to_owned())
+            .or_insert_with(|| Arc::new(Semaphore::new(max)));
+        Some(semaphore.clone())
+    }
+}
+
+fn pool() -> &'static Pool {
+    POOL.get_or_init(|| Pool::new(CONFIG.get_or_init(Default::default)))
+}
+
+/// Resolves host names with [`spin_outbound_networking::dns`], counting
+/// each lookup as a new connection: the client only resolves a host when it
+/// has no idle connection to reuse. Connections to IP addresses aren't
+/// counted.
+struct CountingResolver;
+
+impl Resolve for CountingResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let host = name.as_str().to_owned();
+        if let Some(stats) = pool().hosts.lock().unwrap().get_mut(&host) {
+            stats.connections_opened += 1;
+        }
+        Box::pin(async move {
+            let addrs = spin_outbound_networking::dns::lookup_socket_addrs(&host, 0).await?;
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn requests_over_the_limit_wait() {
+        let pool = Pool::new(&PoolConfig {
+            max_connections_per_host: Some(1),
+            ..Default::default()
+        });
+        let permits = pool.permits("example.com").unwrap();
+        let first = permits.clone().try_acquire_owned().unwrap();
+        assert!(permits.clone().try_acquire_owned().is_err());
+        drop(first);
+        assert!(permits.try_acquire_owned().is_ok());
+
+        // Hosts are limited separately.
+        assert_eq!(1, pool.permits("example.org").unwrap().available_permits());
+    }
+
+    #[test]
+    fn unlimited_pools_have_no_permits() {
+        let pool = Pool::new(&PoolConfig::default());
+        assert!(pool.permits("example.com").is_none());
+    }
+}
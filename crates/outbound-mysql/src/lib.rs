@@ -19,6 +19,7 @@ pub struct OutboundMysql {
 
 impl OutboundMysql {
     async fn open_connection(&mut self, address: &str) -> Result<Resource<Connection>, v2::Error> {
+        spin_core::audit::record_outbound("mysql", address);
         self.connections
             .push(
                 build_conn(address)
@@ -94,6 +95,7 @@ impl v2::HostConnection for OutboundMysql {
         params: Vec<ParameterValue>,
     ) -> Result<Result<(), v2::Error>> {
         Ok(async {
+            spin_core::audit::record_query("mysql", &statement);
             let db_params = params.into_iter().map(to_sql_parameter).collect::<Vec<_>>();
             let parameters = mysql_async::Params::Positional(db_params);
 
@@ -115,6 +117,7 @@ impl v2::HostConnection for OutboundMysql {
         params: Vec<ParameterValue>,
     ) -> Result<Result<v2_types::RowSet, v2::Error>> {
         Ok(async {
+            spin_core::audit::record_query("mysql", &statement);
             let db_params = params.into_iter().map(to_sql_parameter).collect::<Vec<_>>();
             let parameters = mysql_async::Params::Positional(db_params);
 
@@ -332,16 +335,33 @@ fn convert_value(value: mysql_async::Value, column: &Column) -> Result<DbValue,
     }
 }
 
-async fn build_conn(address: &str) -> Result<mysql_async::Conn, mysql_async::Error> {
+/// Opens a connection to the MySQL database at `address`, a `mysql://` URL.
+pub async fn build_conn(address: &str) -> Result<mysql_async::Conn, mysql_async::Error> {
     tracing::log::debug!("Build new connection: {}", address);
 
-    let opts = build_opts(address)?;
+    let opts = resolve_host(build_opts(address)?).await?;
 
     let connection_pool = mysql_async::Pool::new(opts);
 
     connection_pool.get_conn().await
 }
 
+/// Points a plaintext connection at the address its host resolves to with
+/// [`spin_outbound_networking::dns`]. TLS connections are left to the
+/// driver to resolve, as it checks the server's certificate against the
+/// host name.
+async fn resolve_host(opts: Opts) -> Result<Opts, mysql_async::Error> {
+    if opts.ssl_opts().is_some() {
+        return Ok(opts);
+    }
+    let addrs = spin_outbound_networking::dns::lookup(opts.ip_or_hostname())
+        .await
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::NotFound, format!("{e:#}")))?;
+    Ok(OptsBuilder::from_opts(opts)
+        .ip_or_hostname(addrs[0].to_string())
+        .into())
+}
+
 fn is_ssl_param(s: &str) -> bool {
     ["ssl-mode", "sslmode"].contains(&s.to_lowercase().as_str())
 }
@@ -370,7 +390,16 @@ fn build_opts(address: &str) -> Result<Opts, mysql_async::Error> {
         .query_pairs_mut()
         .extend_pairs(query_without_ssl);
 
-    Ok(OptsBuilder::from_opts(cleaned_url.as_str())
+    let mut builder = OptsBuilder::from_opts(cleaned_url.as_str());
+    // The host is connected to without the brackets of an IPv6 address, and
+    // with an internationalized domain name in its ASCII form.
+    if let Some(host) = url.host_str() {
+        if let Ok(host) = spin_outbound_networking::connect_host(host) {
+            builder = builder.ip_or_hostname(host);
+        }
+    }
+
+    Ok(builder
         .ssl_opts(if use_ssl {
             Some(SslOpts::default())
         } else {
@@ -424,4 +453,17 @@ mod test {
             10
         )
     }
+
+    #[test]
+    fn test_mysql_address_with_ipv6_host() {
+        let opts = build_opts("mysql://myuser:password@[::1]:3306/db").unwrap();
+        assert_eq!("::1", opts.ip_or_hostname());
+        assert_eq!(3306, opts.tcp_port());
+    }
+
+    #[test]
+    fn test_mysql_address_with_internationalized_host() {
+        let opts = build_opts("mysql://myuser:password@bücher.example/db").unwrap();
+        assert_eq!("xn--bcher-kva.example", opts.ip_or_hostname());
+    }
 }
@@ -0,0 +1,567 @@
+//! Host name resolution for outbound connections.
+//!
+//! Outbound HTTP requests, and plaintext Postgres, MySQL and Redis
+//! connections, look hosts up here rather than leaving it to each client
+//! library, so that they share one cache and one configuration:
+//!
+//! - static host overrides, which are checked first, e.g. to point a
+//!   database's host name at a test container without editing `/etc/hosts`;
+//! - the resolver: the system's, or a nameserver over UDP, DNS over HTTPS
+//!   (RFC 8484) or DNS over TLS (RFC 7858).
+//!
+//! Answers are cached for their TTL. The system resolver doesn't report
+//! TTLs, so its answers are cached for a fixed time instead.
+//!
+//! TLS connections to databases are left to their drivers to resolve, since
+//! the drivers check the server's certificate against the name they connect
+//! to.
+
+use std::{
+    collections::HashMap,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex, OnceLock,
+    },
+    time::{Duration, Instant},
+};
+
+use anyhow::{bail, ensure, Context, Result};
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
+    net::{TcpStream, UdpSocket},
+};
+
+/// The DNS record type of IPv4 addresses.
+pub const DNS_TYPE_A: u16 = 1;
+/// The DNS record type of IPv6 addresses.
+pub const DNS_TYPE_AAAA: u16 = 28;
+/// The DNS record type of service locations.
+pub const DNS_TYPE_SRV: u16 = 33;
+const DNS_CLASS_IN: u16 = 1;
+const FLAG_TRUNCATED: u16 = 0x0200;
+
+/// How long the system resolver's answers are cached by default.
+pub const DEFAULT_SYSTEM_TTL: Duration = Duration::from_secs(30);
+/// The longest any answer is cached, whatever its TTL.
+const MAX_TTL: Duration = Duration::from_secs(3600);
+const DNS_TIMEOUT: Duration = Duration::from_secs(2);
+
+static CONFIG: OnceLock<DnsConfig> = OnceLock::new();
+static CACHE: OnceLock<Mutex<HashMap<String, CachedAnswer>>> = OnceLock::new();
+static CACHE_HITS: AtomicU64 = AtomicU64::new(0);
+static CACHE_MISSES: AtomicU64 = AtomicU64::new(0);
+
+/// Where host names are looked up.
+#[derive(Clone, Debug, Default)]
+pub enum Resolver {
+    /// The system resolver, as configured by e.g. `/etc/resolv.conf`.
+    #[default]
+    System,
+    /// A nameserver, queried over UDP (and TCP for long answers).
+    Udp {
+        /// The nameserver's address, e.g. `1.1.1.1:53`.
+        nameserver: SocketAddr,
+    },
+    /// A DNS over HTTPS endpoint.
+    Https {
+        /// The endpoint's URL, e.g. `https://cloudflare-dns.com/dns-query`.
+        url: String,
+    },
+    /// A DNS over TLS nameserver.
+    Tls {
+        /// The nameserver's address, e.g. `1.1.1.1:853`.
+        nameserver: SocketAddr,
+        /// The name the nameserver's certificate is checked against.
+        server_name: String,
+    },
+}
+
+/// How hosts are resolved.
+#[derive(Clone, Debug)]
+pub struct DnsConfig {
+    /// The resolver for hosts which aren't overridden.
+    pub resolver: Resolver,
+    /// Host name -> the addresses it resolves to, ahead of the resolver.
+    pub hosts: HashMap<String, Vec<IpAddr>>,
+    /// How long the system resolver's answers are cached.
+    pub system_ttl: Duration,
+}
+
+impl Default for DnsConfig {
+    fn default() -> Self {
+        Self {
+            resolver: Resolver::default(),
+            hosts: HashMap::new(),
+            system_ttl: DEFAULT_SYSTEM_TTL,
+        }
+    }
+}
+
+/// Configures host resolution. This must be called before the first
+/// outbound connection, at most once per process.
+pub fn configure(mut config: DnsConfig) -> Result<()> {
+    config.hosts = config
+        .hosts
+        .into_iter()
+        .map(|(host, addrs)| (cache_key(&host), addrs))
+        .collect();
+    CONFIG
+        .set(config)
+        .map_err(|_| anyhow::anyhow!("DNS resolution has already been configured"))
+}
+
+/// The DNS cache's counters.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    /// Lookups answered from the cache.
+    pub hits: u64,
+    /// Lookups which went to the resolver.
+    pub misses: u64,
+    /// Hosts currently cached.
+    pub entries: usize,
+}
+
+/// Returns the DNS cache's counters.
+pub fn cache_stats() -> CacheStats {
+    CacheStats {
+        hits: CACHE_HITS.load(Ordering::Relaxed),
+        misses: CACHE_MISSES.load(Ordering::Relaxed),
+        entries: cache().lock().unwrap().len(),
+    }
+}
+
+/// Returns the addresses of `host`, which may be an IP address.
+pub async fn lookup(host: &str) -> Result<Vec<IpAddr>> {
+    lookup_with(CONFIG.get_or_init(Default::default), host).await
+}
+
+/// Returns the socket addresses of `host` at `port`.
+pub async fn lookup_socket_addrs(host: &str, port: u16) -> Result<Vec<SocketAddr>> {
+    Ok(lookup(host)
+        .await?
+        .into_iter()
+        .map(|ip| SocketAddr::new(ip, port))
+        .collect())
+}
+
+async fn lookup_with(config: &DnsConfig, host: &str) -> Result<Vec<IpAddr>> {
+    let bare = host.trim_start_matches('[').trim_end_matches(']');
+    if let Ok(ip) = bare.parse::<IpAddr>() {
+        return Ok(vec![ip]);
+    }
+    let key = cache_key(host);
+    if let Some(addrs) = config.hosts.get(&key) {
+        return Ok(addrs.clone());
+    }
+    if let Some(addrs) = cached(&key) {
+        CACHE_HITS.fetch_add(1, Ordering::Relaxed);
+        return Ok(addrs);
+    }
+    CACHE_MISSES.fetch_add(1, Ordering::Relaxed);
+
+    let (addrs, ttl) = config
+        .resolver
+        .lookup(&key, config.system_ttl)
+        .await
+        .with_context(|| format!("cannot resolve host '{host}'"))?;
+    ensure!(!addrs.is_empty(), "host '{host}' has no addresses");
+    if !ttl.is_zero() {
+        cache().lock().unwrap().insert(
+            key,
+            CachedAnswer {
+                addrs: addrs.clone(),
+                expires: Instant::now() + ttl.min(MAX_TTL),
+            },
+        );
+    }
+    Ok(addrs)
+}
+
+struct CachedAnswer {
+    addrs: Vec<IpAddr>,
+    expires: Instant,
+}
+
+fn cache() -> &'static Mutex<HashMap<String, CachedAnswer>> {
+    CACHE.get_or_init(Default::default)
+}
+
+fn cached(key: &str) -> Option<Vec<IpAddr>> {
+    let mut cache = cache().lock().unwrap();
+    match cache.get(key) {
+        Some(answer) if answer.expires > Instant::now() => Some(answer.addrs.clone()),
+        Some(_) => {
+            cache.remove(key);
+            None
+        }
+        None => None,
+    }
+}
+
+fn cache_key(host: &str) -> String {
+    host.trim_end_matches('.').to_ascii_lowercase()
+}
+
+impl Resolver {
+    /// Returns the addresses of `host` and how long they may be cached.
+    async fn lookup(&self, host: &str, system_ttl: Duration) -> Result<(Vec<IpAddr>, Duration)> {
+        if let Self::System = self {
+            let addrs = tokio::net::lookup_host((host, 0)).await?;
+            return Ok((addrs.map(|addr| addr.ip()).collect(), system_ttl));
+        }
+        let (v4, v6) = tokio::try_join!(
+            self.query(host, DNS_TYPE_A),
+            self.query(host, DNS_TYPE_AAAA)
+        )?;
+        // An empty answer says nothing about how long the other is valid.
+        let ttl = [&v4, &v6]
+            .iter()
+            .filter(|answer| !answer.addrs.is_empty())
+            .map(|answer| answer.ttl)
+            .min()
+            .unwrap_or_default();
+        Ok(([v4.addrs, v6.addrs].concat(), ttl))
+    }
+
+    async fn query(&self, host: &str, record_type: u16) -> Result<Answer> {
+        let id = match self {
+            // RFC 8484 recommends an ID of 0, so that responses are cacheable.
+            Self::Https { .. } => 0,
+            _ => query_id(),
+        };
+        let query = encode_query(id, host, record_type)?;
+        let response = match self {
+            Self::System => bail!("the system resolver can't be queried directly"),
+            Self::Udp { nameserver } => exchange_udp(*nameserver, &query).await?,
+            Self::Https { url } => exchange_https(url, &query).await?,
+            Self::Tls {
+                nameserver,
+                server_name,
+            } => exchange_tls(*nameserver, server_name, &query).await?,
+        };
+        parse_address_response(id, &response)
+    }
+}
+
+/// The addresses in a DNS response.
+#[derive(Debug, Default, PartialEq, Eq)]
+struct Answer {
+    addrs: Vec<IpAddr>,
+    /// The lowest TTL of the address records.
+    ttl: Duration,
+}
+
+fn query_id() -> u16 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .subsec_nanos() as u16
+}
+
+/// Encodes a recursive query for the records of `record_type` named `name`.
+pub fn encode_query(id: u16, name: &str, record_type: u16) -> Result<Vec<u8>> {
+    let mut query = Vec::with_capacity(name.len() + 18);
+    query.extend(id.to_be_bytes());
+    // Recursion desired, one question.
+    query.extend([0x01, 0x00, 0, 1, 0, 0, 0, 0, 0, 0]);
+    for label in name.trim_end_matches('.').split('.') {
+        ensure!(
+            !label.is_empty() && label.len() < 64,
+            "invalid DNS name '{name}'"
+        );
+        query.push(label.len() as u8);
+        query.extend(label.as_bytes());
+    }
+    query.push(0);
+    query.extend(record_type.to_be_bytes());
+    query.extend(DNS_CLASS_IN.to_be_bytes());
+    Ok(query)
+}
+
+/// Sends `query` to `nameserver` over UDP, retrying over TCP if the
+/// response is truncated.
+pub async fn exchange_udp(nameserver: SocketAddr, query: &[u8]) -> Result<Vec<u8>> {
+    let bind_addr: SocketAddr = if nameserver.is_ipv4() {
+        ([0, 0, 0, 0], 0).into()
+    } else {
+        ([0u16; 8], 0).into()
+    };
+    let socket = UdpSocket::bind(bind_addr).await?;
+    socket.connect(nameserver).await?;
+    socket.send(query).await?;
+    let mut buf = vec![0; 4096];
+    let len = tokio::time::timeout(DNS_TIMEOUT, socket.recv(&mut buf))
+        .await
+        .with_context(|| format!("DNS server {nameserver} did not respond"))??;
+    buf.truncate(len);
+
+    let flags = buf.get(2..4).map(|f| u16::from_be_bytes([f[0], f[1]]));
+    if flags.is_some_and(|flags| flags & FLAG_TRUNCATED != 0) {
+        let stream = TcpStream::connect(nameserver).await?;
+        return exchange_stream(stream, query).await;
+    }
+    Ok(buf)
+}
+
+async fn exchange_tls(nameserver: SocketAddr, server_name: &str, query: &[u8]) -> Result<Vec<u8>> {
+    let connect = async {
+        let tcp = TcpStream::connect(nameserver).await?;
+        let connector = tokio_native_tls::TlsConnector::from(native_tls::TlsConnector::new()?);
+        Ok::<_, anyhow::Error>(connector.connect(server_name, tcp).await?)
+    };
+    let stream = tokio::time::timeout(DNS_TIMEOUT, connect)
+        .await
+        .with_context(|| format!("DNS server {nameserver} did not respond"))??;
+    exchange_stream(stream, query).await
+}
+
+/// Sends `query` over a stream, as TCP and TLS nameservers expect: each
+/// message is prefixed with its length.
+async fn exchange_stream(
+    mut stream: impl AsyncRead + AsyncWrite + Unpin,
+    query: &[u8],
+) -> Result<Vec<u8>> {
+    let exchange = async {
+        let mut message = (query.len() as u16).to_be_bytes().to_vec();
+        message.extend(query);
+        stream.write_all(&message).await?;
+        let len = stream.read_u16().await?;
+        let mut response = vec![0; usize::from(len)];
+        stream.read_exact(&mut response).await?;
+        Ok::<_, std::io::Error>(response)
+    };
+    Ok(tokio::time::timeout(DNS_TIMEOUT, exchange)
+        .await
+        .context("DNS server did not respond")??)
+}
+
+async fn exchange_https(url: &str, query: &[u8]) -> Result<Vec<u8>> {
+    static CLIENT: OnceLock<reqwest::Client> = OnceLock::new();
+    const DNS_MESSAGE: &str = "application/dns-message";
+    let client = CLIENT.get_or_init(|| {
+        reqwest::Client::builder()
+            .timeout(DNS_TIMEOUT)
+            .build()
+            .unwrap_or_default()
+    });
+    let response = client
+        .post(url)
+        .header(reqwest::header::CONTENT_TYPE, DNS_MESSAGE)
+        .header(reqwest::header::ACCEPT, DNS_MESSAGE)
+        .body(query.to_vec())
+        .send()
+        .await?
+        .error_for_status()?;
+    Ok(response.bytes().await?.to_vec())
+}
+
+/// Parses the A and AAAA records in a DNS response.
+fn parse_address_response(id: u16, response: &[u8]) -> Result<Answer> {
+    let mut reader = MessageReader::new(response);
+    let Some(answers) = reader.header(id)? else {
+        return Ok(Answer::default());
+    };
+
+    let mut answer = Answer::default();
+    let mut ttl = None;
+    for _ in 0..answers {
+        reader.name()?;
+        let record_type = reader.u16()?;
+        reader.skip(2); // Class.
+        let record_ttl = Duration::from_secs(reader.u32()?.into());
+        let length = usize::from(reader.u16()?);
+        let data = reader.bytes(length)?;
+        // Records of other types, such as CNAMEs, lead to the addresses.
+        let ip = match (record_type, data.len()) {
+            (DNS_TYPE_A, 4) => IpAddr::from(Ipv4Addr::new(data[0], data[1], data[2], data[3])),
+            (DNS_TYPE_AAAA, 16) => {
+                let octets: [u8; 16] = data.try_into().unwrap();
+                IpAddr::from(Ipv6Addr::from(octets))
+            }
+            _ => continue,
+        };
+        answer.addrs.push(ip);
+        ttl = Some(ttl.map_or(record_ttl, |ttl: Duration| ttl.min(record_ttl)));
+    }
+    answer.ttl = ttl.unwrap_or_default();
+    Ok(answer)
+}
+
+/// Reads the fields of a DNS message.
+pub struct MessageReader<'a> {
+    message: &'a [u8],
+    position: usize,
+}
+
+impl<'a> MessageReader<'a> {
+    /// Starts reading `message` from its beginning.
+    pub fn new(message: &'a [u8]) -> Self {
+        Self {
+            message,
+            position: 0,
+        }
+    }
+
+    /// Reads the header and questions of a response to the query `id`,
+    /// returning the number of answers, or `None` if the name doesn't exist.
+    pub fn header(&mut self, id: u16) -> Result<Option<u16>> {
+        ensure!(self.u16()? == id, "DNS response does not match the query");
+        let flags = self.u16()?;
+        ensure!(
+            flags & FLAG_TRUNCATED == 0,
+            "DNS response was truncated; too many records for UDP"
+        );
+        match flags & 0x000f {
+            0 => {}
+            // NXDOMAIN
+            3 => return Ok(None),
+            rcode => bail!("DNS server returned error code {rcode}"),
+        }
+        let questions = self.u16()?;
+        let answers = self.u16()?;
+        self.skip(4); // Authority and additional record counts.
+        for _ in 0..questions {
+            self.name()?;
+            self.skip(4);
+        }
+        Ok(Some(answers))
+    }
+
+    /// Reads a big-endian `u16`.
+    pub fn u16(&mut self) -> Result<u16> {
+        let bytes = self.bytes(2)?;
+        Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    /// Reads a big-endian `u32`.
+    pub fn u32(&mut self) -> Result<u32> {
+        let bytes = self.bytes(4)?;
+        Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    /// Reads `len` bytes.
+    pub fn bytes(&mut self, len: usize) -> Result<&'a [u8]> {
+        let bytes = self
+            .message
+            .get(self.position..self.position + len)
+            .context("DNS response is too short")?;
+        self.position += len;
+        Ok(bytes)
+    }
+
+    /// Skips `len` bytes.
+    pub fn skip(&mut self, len: usize) {
+        self.position += len;
+    }
+
+    /// Moves to `position`, e.g. the end of a record.
+    pub fn seek(&mut self, position: usize) {
+        self.position = position;
+    }
+
+    /// Returns the current position.
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    /// Reads a possibly compressed name, without its trailing dot.
+    pub fn name(&mut self) -> Result<String> {
+        let mut labels = vec![];
+        let mut position = self.position;
+        let mut resume = None;
+        // Bound the pointers followed, in case of a loop.
+        for _ in 0..128 {
+            let len = *self
+                .message
+                .get(position)
+                .context("DNS response is too short")?;
+            match len {
+                0 => {
+                    self.position = resume.unwrap_or(position + 1);
+                    return Ok(labels.join("."));
+                }
+                len if len & 0xc0 == 0xc0 => {
+                    let low = *self
+                        .message
+                        .get(position + 1)
+                        .context("DNS response is too short")?;
+                    resume.get_or_insert(position + 2);
+                    position = usize::from(u16::from_be_bytes([len & 0x3f, low]));
+                }
+                len => {
+                    let label = self
+                        .message
+                        .get(position + 1..position + 1 + usize::from(len))
+                        .context("DNS response is too short")?;
+                    labels.push(String::from_utf8_lossy(label).into_owned());
+                    position += 1 + usize::from(len);
+                }
+            }
+        }
+        bail!("DNS response contains a name compression loop")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn address_responses_are_parsed() {
+        let query = encode_query(0x1234, "www.example.com", DNS_TYPE_A).unwrap();
+        let mut response = query.clone();
+        // Response, recursion available, no error, two answers.
+        response[2..4].copy_from_slice(&[0x81, 0x80]);
+        response[6..8].copy_from_slice(&[0, 2]);
+        // A CNAME to example.com, pointing within the question...
+        response.extend([0xc0, 12, 0, 5, 0, 1, 0, 0, 0x0e, 0x10, 0, 2, 0xc0, 16]);
+        // ...whose address has the lower TTL.
+        response.extend([0xc0, 16, 0, 1, 0, 1, 0, 0, 0, 60, 0, 4, 93, 184, 216, 34]);
+
+        let answer = parse_address_response(0x1234, &response).unwrap();
+        assert_eq!(vec![IpAddr::from([93, 184, 216, 34])], answer.addrs);
+        assert_eq!(Duration::from_secs(60), answer.ttl);
+        parse_address_response(0x4321, &response).unwrap_err();
+
+        // NXDOMAIN
+        response[3] = 0x83;
+        assert_eq!(
+            Answer::default(),
+            parse_address_response(0x1234, &response).unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn overrides_and_addresses_need_no_resolver() {
+        let config = DnsConfig {
+            // Nothing listens here, so any query would fail.
+            resolver: Resolver::Udp {
+                nameserver: ([127, 0, 0, 1], 9).into(),
+            },
+            hosts: [("db.test".to_owned(), vec![IpAddr::from([10, 0, 0, 5])])].into(),
+            ..Default::default()
+        };
+        assert_eq!(
+            vec![IpAddr::from([10, 0, 0, 5])],
+            lookup_with(&config, "DB.test.").await.unwrap()
+        );
+        assert_eq!(
+            vec![IpAddr::from(Ipv6Addr::LOCALHOST)],
+            lookup_with(&config, "[::1]").await.unwrap()
+        );
+    }
+
+    #[test]
+    fn expired_answers_are_not_used() {
+        cache().lock().unwrap().insert(
+            "expired.test".to_owned(),
+            CachedAnswer {
+                addrs: vec![IpAddr::from([10, 0, 0, 1])],
+                expires: Instant::now(),
+            },
+        );
+        assert_eq!(None, cached("expired.test"));
+        assert!(!cache().lock().unwrap().contains_key("expired.test"));
+    }
+}
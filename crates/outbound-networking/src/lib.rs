@@ -1,3 +1,5 @@
+pub mod dns;
+
 use std::ops::Range;
 
 use anyhow::{bail, Context};
@@ -50,10 +52,7 @@ impl AllowedHostConfig {
         let (scheme, rest) = url.split_once("://").with_context(|| {
             format!("{url:?} does not contain a scheme (e.g., 'http://' or '*://')")
         })?;
-        let (host, rest) = rest
-            .split_once(':')
-            .or_else(|| rest.split_once('/'))
-            .unwrap_or((rest, ""));
+        let (host, rest) = split_host(rest);
         let port = match rest.split_once('/') {
             Some((port, path)) => {
                 if !path.is_empty() {
@@ -149,7 +148,8 @@ impl HostConfig {
             bail!("host lists are not yet supported")
         }
 
-        Ok(Self::List(vec![host.into()]))
+        let host = normalize_host(host)?;
+        Ok(Self::List(vec![host]))
     }
 
     fn allows(&self, host: &str) -> bool {
@@ -161,6 +161,65 @@ impl HostConfig {
     }
 }
 
+/// Splits the host from the rest of an allowed host, after the scheme. An IPv6
+/// address must be in brackets, as in a URL, since it contains colons.
+fn split_host(rest: &str) -> (&str, &str) {
+    if rest.starts_with('[') {
+        if let Some(end) = rest.find(']') {
+            let (host, rest) = rest.split_at(end + 1);
+            let rest = rest
+                .strip_prefix(':')
+                .or_else(|| rest.strip_prefix('/'))
+                .unwrap_or(rest);
+            return (host, rest);
+        }
+    }
+    rest.split_once(':')
+        .or_else(|| rest.split_once('/'))
+        .unwrap_or((rest, ""))
+}
+
+/// Parses a host, which may be percent-encoded, as it is in URLs whose scheme
+/// `url` doesn't know (such as `postgres://`), or an IPv6 address without
+/// brackets, as database drivers report them.
+fn parse_host(host: &str) -> anyhow::Result<url::Host> {
+    if let Ok(ip) = host.parse::<std::net::Ipv6Addr>() {
+        return Ok(url::Host::Ipv6(ip));
+    }
+    let decoded = percent_encoding::percent_decode_str(host)
+        .decode_utf8()
+        .with_context(|| format!("host {host:?} is not valid UTF-8"))?;
+    url::Host::parse(&decoded).with_context(|| format!("{host:?} is not a valid host"))
+}
+
+/// Returns the form of a host used to compare it against allowed hosts: domain
+/// names are lowercased and internationalized domain names converted to
+/// their ASCII (punycode) form, and IP addresses are written canonically, in
+/// brackets for IPv6.
+fn normalize_host(host: &str) -> anyhow::Result<String> {
+    Ok(parse_host(host)?.to_string())
+}
+
+/// Returns the form of a host used to connect to it: like [`normalize_host`]
+/// except that IPv6 addresses aren't bracketed, as socket address lookups
+/// expect.
+pub fn connect_host(host: &str) -> anyhow::Result<String> {
+    Ok(match parse_host(host)? {
+        url::Host::Ipv6(ip) => ip.to_string(),
+        host => host.to_string(),
+    })
+}
+
+/// Formats a host and port to check against allowed hosts, bracketing IPv6
+/// addresses (such as those of a Postgres key-value connection string).
+pub fn host_and_port(host: &str, port: u16) -> String {
+    if host.contains(':') && !host.starts_with('[') {
+        format!("[{host}]:{port}")
+    } else {
+        format!("{host}:{port}")
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, Clone)]
 enum PortConfig {
     Any,
@@ -236,7 +295,7 @@ fn well_known_port(scheme: &str) -> Option<u16> {
     match scheme {
         "postgres" => Some(5432),
         "mysql" => Some(3306),
-        "redis" => Some(6379),
+        "redis" | "rediss" => Some(6379),
         "http" => Some(80),
         "https" => Some(443),
         _ => None,
@@ -311,12 +370,17 @@ impl OutboundUrl {
             }
         }?;
 
+        let host = match parsed
+            .host()
+            .with_context(|| format!("{url:?} does not have a host component"))?
+        {
+            url::Host::Domain(domain) => normalize_host(domain)?,
+            ip => ip.to_string(),
+        };
+
         Ok(Self {
             scheme: parsed.scheme().to_owned(),
-            host: parsed
-                .host_str()
-                .with_context(|| format!("{url:?} does not have a host component"))?
-                .to_owned(),
+            host,
             port: parsed.port(),
             original: url,
         })
@@ -480,10 +544,35 @@ mod test {
             ),
             AllowedHostConfig::parse("http://192.168.1.1:3002").unwrap()
         );
-        // assert_eq!(
-        //     AllowedHostConfig::new(Some("http"), "[::1]", 8001),
-        //     AllowedHostConfig::parse("http://[::1]:8001").unwrap()
-        // );
+        assert_eq!(
+            AllowedHostConfig::new(
+                SchemeConfig::new("http"),
+                HostConfig::new("[::1]"),
+                PortConfig::new(8001)
+            ),
+            AllowedHostConfig::parse("http://[::1]:8001").unwrap()
+        );
+        assert_eq!(
+            AllowedHostConfig::new(
+                SchemeConfig::new("postgres"),
+                HostConfig::new("[2001:db8::1]"),
+                PortConfig::new(5432)
+            ),
+            AllowedHostConfig::parse("postgres://[2001:DB8:0::1]").unwrap()
+        );
+        assert!(AllowedHostConfig::parse("http://[::1]x").is_err());
+    }
+
+    #[test]
+    fn test_allowed_hosts_normalizes_domain_names() {
+        assert_eq!(
+            AllowedHostConfig::new(
+                SchemeConfig::new("https"),
+                HostConfig::new("xn--bcher-kva.example"),
+                PortConfig::new(443)
+            ),
+            AllowedHostConfig::parse("https://Bücher.example").unwrap()
+        );
     }
 
     #[test]
@@ -520,4 +609,45 @@ mod test {
         assert!(allowed.allows(&OutboundUrl::parse("spin.fermyon.dev:443", "https").unwrap()));
         assert!(allowed.allows(&OutboundUrl::parse("example.com:8383", "http").unwrap()));
     }
+
+    #[test]
+    fn test_allowed_hosts_match_ipv6_addresses() {
+        let allowed =
+            AllowedHostsConfig::parse(&["postgres://[::1]:5432", "redis://[::1]"]).unwrap();
+        assert!(allowed.allows(&OutboundUrl::parse("[::1]:5432", "postgres").unwrap()));
+        assert!(allowed.allows(
+            &OutboundUrl::parse("postgres://user:pass@[0:0::1]:5432/db", "postgres").unwrap()
+        ));
+        assert!(allowed.allows(&OutboundUrl::parse("redis://[::1]:6379", "redis").unwrap()));
+        assert!(!allowed.allows(&OutboundUrl::parse("[::2]:5432", "postgres").unwrap()));
+        assert!(
+            allowed.allows(&OutboundUrl::parse(host_and_port("::1", 5432), "postgres").unwrap())
+        );
+    }
+
+    #[test]
+    fn test_allowed_hosts_match_internationalized_domain_names() {
+        let allowed = AllowedHostsConfig::parse(&[
+            "https://bücher.example",
+            "mysql://xn--bcher-kva.example:3306",
+        ])
+        .unwrap();
+        assert!(
+            allowed.allows(&OutboundUrl::parse("https://xn--bcher-kva.example/", "https").unwrap())
+        );
+        assert!(allowed.allows(&OutboundUrl::parse("https://BÜCHER.example/", "https").unwrap()));
+        assert!(allowed
+            .allows(&OutboundUrl::parse("mysql://user@bücher.example:3306/db", "mysql").unwrap()));
+    }
+
+    #[test]
+    fn test_connect_host() {
+        assert_eq!("::1", connect_host("[::1]").unwrap());
+        assert_eq!("::1", connect_host("::1").unwrap());
+        assert_eq!(
+            "xn--bcher-kva.example",
+            connect_host("b%C3%BCcher.example").unwrap()
+        );
+        assert_eq!("127.0.0.1", connect_host("127.0.0.1").unwrap());
+    }
 }
@@ -0,0 +1,210 @@
+//! Support for Redis Cluster.
+//!
+//! Rather than tracking the cluster's slot map, a connection is opened to the
+//! node given in the address and cluster `MOVED` and `ASK` redirects are
+//! followed as they occur, connecting to the other nodes as they are
+//! discovered. Against a standalone server this behaves like a plain
+//! connection.
+//!
+//! A redirect is only followed to a node the component's
+//! `allowed_outbound_hosts` allow, so that a server can't send a
+//! component's commands to any address it likes.
+
+use std::collections::{hash_map::Entry, HashMap};
+
+use redis::{
+    aio::{Connection, ConnectionLike},
+    Cmd, ConnectionAddr, ConnectionInfo, ErrorKind, Pipeline, RedisError, RedisFuture, RedisResult,
+    Value,
+};
+use spin_outbound_networking::AllowedHostsConfig;
+
+/// The maximum number of redirects to follow for a single command.
+const MAX_REDIRECTS: usize = 5;
+
+/// A connection which follows Redis Cluster redirects.
+pub struct RedirectingConnection {
+    info: ConnectionInfo,
+    allowed_hosts: AllowedHostsConfig,
+    connection: Connection,
+    // (host, port) -> connection to another cluster node
+    nodes: HashMap<(String, u16), Connection>,
+}
+
+impl RedirectingConnection {
+    /// Opens a connection to the server or cluster node given by `info`,
+    /// following redirects only to nodes in `allowed_hosts`.
+    pub async fn open(
+        info: ConnectionInfo,
+        allowed_hosts: AllowedHostsConfig,
+    ) -> RedisResult<Self> {
+        let connection = redis::Client::open(resolve(info.clone()).await?)?
+            .get_async_connection()
+            .await?;
+        Ok(Self {
+            info,
+            allowed_hosts,
+            connection,
+            nodes: HashMap::new(),
+        })
+    }
+
+    /// The server's address, as a URL without credentials.
+    pub fn address(&self) -> String {
+        match &self.info.addr {
+            ConnectionAddr::Tcp(host, port) => format!("redis://{host}:{port}"),
+            ConnectionAddr::TcpTls { host, port, .. } => format!("rediss://{host}:{port}"),
+            ConnectionAddr::Unix(path) => format!("redis+unix://{}", path.display()),
+        }
+    }
+
+    async fn node(&mut self, host: String, port: u16) -> RedisResult<&mut Connection> {
+        match self.nodes.entry((host, port)) {
+            Entry::Occupied(entry) => Ok(entry.into_mut()),
+            Entry::Vacant(entry) => {
+                let (host, port) = entry.key().clone();
+                let scheme = match self.info.addr {
+                    ConnectionAddr::TcpTls { .. } => "rediss",
+                    _ => "redis",
+                };
+                let address = spin_outbound_networking::host_and_port(&host, port);
+                let url = format!("{scheme}://{address}");
+                if !spin_outbound_networking::check_url(&url, scheme, &self.allowed_hosts) {
+                    return Err(RedisError::from((
+                        ErrorKind::InvalidClientConfig,
+                        "redirected to a host which is not permitted",
+                        address,
+                    )));
+                }
+                tracing::debug!("Connecting to Redis Cluster node {host}:{port}");
+                // Other nodes use the same credentials and TLS settings.
+                let info = ConnectionInfo {
+                    addr: node_addr(&self.info.addr, host, port),
+                    redis: self.info.redis.clone(),
+                };
+                let connection = redis::Client::open(resolve(info).await?)?
+                    .get_async_connection()
+                    .await?;
+                Ok(entry.insert(connection))
+            }
+        }
+    }
+}
+
+impl ConnectionLike for RedirectingConnection {
+    fn req_packed_command<'a>(&'a mut self, cmd: &'a Cmd) -> RedisFuture<'a, Value> {
+        Box::pin(async move {
+            let mut result = self.connection.req_packed_command(cmd).await;
+            for _ in 0..MAX_REDIRECTS {
+                let (host, port, asking) = match &result {
+                    Err(e) if matches!(e.kind(), ErrorKind::Moved | ErrorKind::Ask) => {
+                        match redirect_target(e) {
+                            Some((host, port)) => (host, port, e.kind() == ErrorKind::Ask),
+                            None => break,
+                        }
+                    }
+                    _ => break,
+                };
+                let node = self.node(host, port).await?;
+                if asking {
+                    redis::cmd("ASKING").query_async::<_, ()>(node).await?;
+                }
+                result = node.req_packed_command(cmd).await;
+            }
+            result
+        })
+    }
+
+    fn req_packed_commands<'a>(
+        &'a mut self,
+        cmd: &'a Pipeline,
+        offset: usize,
+        count: usize,
+    ) -> RedisFuture<'a, Vec<Value>> {
+        // A pipeline may span several nodes, so it can't be redirected as a whole.
+        self.connection.req_packed_commands(cmd, offset, count)
+    }
+
+    fn get_db(&self) -> i64 {
+        self.connection.get_db()
+    }
+}
+
+/// Returns the node a `MOVED` or `ASK` error redirects to. The error detail
+/// has the form `<slot> <host>:<port>`.
+fn redirect_target(error: &RedisError) -> Option<(String, u16)> {
+    let (_slot, node) = error.detail()?.split_once(' ')?;
+    let (host, port) = node.rsplit_once(':')?;
+    Some((host.to_owned(), port.parse().ok()?))
+}
+
+/// Points a plaintext connection at the address its host resolves to with
+/// [`spin_outbound_networking::dns`]. TLS connections are left to the
+/// client to resolve, as it checks the server's certificate against the
+/// host name.
+async fn resolve(mut info: ConnectionInfo) -> RedisResult<ConnectionInfo> {
+    if let ConnectionAddr::Tcp(host, _) = &mut info.addr {
+        let addrs = spin_outbound_networking::dns::lookup(host)
+            .await
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::NotFound, format!("{e:#}")))?;
+        *host = addrs[0].to_string();
+    }
+    Ok(info)
+}
+
+fn node_addr(addr: &ConnectionAddr, host: String, port: u16) -> ConnectionAddr {
+    match addr {
+        ConnectionAddr::TcpTls { insecure, .. } => ConnectionAddr::TcpTls {
+            host,
+            port,
+            insecure: *insecure,
+        },
+        _ => ConnectionAddr::Tcp(host, port),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use redis::IntoConnectionInfo;
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
+
+    use super::*;
+
+    /// Serves a single connection, answering every command with `reply`.
+    async fn serve(reply: &'static str) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = format!("redis://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut buf = [0; 1024];
+            while let Ok(n) = stream.read(&mut buf).await {
+                if n == 0 || stream.write_all(reply.as_bytes()).await.is_err() {
+                    break;
+                }
+            }
+        });
+        address
+    }
+
+    #[tokio::test]
+    async fn redirects_to_hosts_which_are_not_allowed_are_refused() {
+        let address = serve("-MOVED 3999 10.0.0.1:6379\r\n").await;
+        let allowed_hosts = AllowedHostsConfig::parse(&[&address]).unwrap();
+        let info = address.as_str().into_connection_info().unwrap();
+        let mut connection = RedirectingConnection::open(info, allowed_hosts)
+            .await
+            .unwrap();
+
+        let err = redis::cmd("GET")
+            .arg("key")
+            .query_async::<_, Option<String>>(&mut connection)
+            .await
+            .unwrap_err();
+        assert_eq!(ErrorKind::InvalidClientConfig, err.kind());
+        assert!(err.to_string().contains("10.0.0.1:6379"), "{err}");
+        assert!(connection.nodes.is_empty());
+    }
+}
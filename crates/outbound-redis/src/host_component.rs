@@ -13,7 +13,8 @@ impl HostComponent for OutboundRedisComponent {
         get: impl Fn(&mut spin_core::Data<T>) -> &mut Self::Data + Send + Sync + Copy + 'static,
     ) -> anyhow::Result<()> {
         spin_world::v1::redis::add_to_linker(linker, get)?;
-        spin_world::v2::redis::add_to_linker(linker, get)
+        spin_world::v2::redis::add_to_linker(linker, get)?;
+        spin_world::v3::redis::add_to_linker(linker, get)
     }
 
     fn build_data(&self) -> Self::Data {
@@ -1,13 +1,20 @@
+mod cluster;
 mod host_component;
 
+use std::collections::HashMap;
+
 use anyhow::Result;
-use redis::{aio::Connection, AsyncCommands, FromRedisValue, Value};
+use redis::{
+    AsyncCommands, ConnectionAddr, ConnectionInfo, FromRedisValue, IntoConnectionInfo, Value,
+};
 use spin_core::{async_trait, wasmtime::component::Resource};
 use spin_world::v1::redis as v1;
-use spin_world::v2::redis::{
-    self as v2, Connection as RedisConnection, Error, RedisParameter, RedisResult,
+use spin_world::v2::redis as v2;
+use spin_world::v3::redis::{
+    self as v3, Connection as RedisConnection, Error, RedisParameter, RedisResult,
 };
 
+use crate::cluster::RedirectingConnection;
 pub use host_component::OutboundRedisComponent;
 
 struct RedisResults(Vec<RedisResult>);
@@ -32,7 +39,7 @@ impl FromRedisValue for RedisResults {
 
 pub struct OutboundRedis {
     allowed_hosts: spin_outbound_networking::AllowedHostsConfig,
-    connections: table::Table<Connection>,
+    connections: table::Table<RedirectingConnection>,
 }
 
 impl Default for OutboundRedis {
@@ -54,9 +61,12 @@ impl OutboundRedis {
         address: String,
     ) -> Result<Result<Resource<RedisConnection>, Error>> {
         Ok(async {
-            let conn = redis::Client::open(address.as_str())
-                .map_err(|_| Error::InvalidAddress)?
-                .get_async_connection()
+            let info = connection_info(&address).map_err(|_| Error::InvalidAddress)?;
+            spin_core::audit::record_outbound("redis", &address);
+            if let Some(fault) = spin_core::faults::inject("redis", &address).await {
+                return Err(other_error(fault));
+            }
+            let conn = RedirectingConnection::open(info, self.allowed_hosts.clone())
                 .await
                 .map_err(other_error)?;
             self.connections
@@ -68,10 +78,23 @@ impl OutboundRedis {
     }
 }
 
-impl v2::Host for OutboundRedis {}
+/// Parses an address into connection info whose host is in the form used to
+/// connect: without the brackets of an IPv6 address, which address lookups
+/// reject, and with internationalized domain names in their ASCII form.
+fn connection_info(address: &str) -> redis::RedisResult<ConnectionInfo> {
+    let mut info = address.into_connection_info()?;
+    if let ConnectionAddr::Tcp(host, _) | ConnectionAddr::TcpTls { host, .. } = &mut info.addr {
+        if let Ok(connect_host) = spin_outbound_networking::connect_host(host) {
+            *host = connect_host;
+        }
+    }
+    Ok(info)
+}
+
+impl v3::Host for OutboundRedis {}
 
 #[async_trait]
-impl v2::HostConnection for OutboundRedis {
+impl v3::HostConnection for OutboundRedis {
     async fn open(&mut self, address: String) -> Result<Result<Resource<RedisConnection>, Error>> {
         if !self.is_address_allowed(&address) {
             return Ok(Err(Error::InvalidAddress));
@@ -157,13 +180,7 @@ impl v2::HostConnection for OutboundRedis {
     ) -> Result<Result<u32, Error>> {
         Ok(async {
             let conn = self.get_conn(connection).await.map_err(other_error)?;
-            let value = conn.sadd(&key, &values).await.map_err(|e| {
-                if e.kind() == redis::ErrorKind::TypeError {
-                    Error::TypeError
-                } else {
-                    Error::Other(e.to_string())
-                }
-            })?;
+            let value = conn.sadd(&key, &values).await.map_err(type_error)?;
             Ok(value)
         }
         .await)
@@ -196,6 +213,163 @@ impl v2::HostConnection for OutboundRedis {
         .await)
     }
 
+    async fn exists(
+        &mut self,
+        connection: Resource<RedisConnection>,
+        keys: Vec<String>,
+    ) -> Result<Result<u32, Error>> {
+        Ok(async {
+            if keys.is_empty() {
+                return Ok(0);
+            }
+            let conn = self.get_conn(connection).await.map_err(other_error)?;
+            let value = conn.exists(&keys).await.map_err(other_error)?;
+            Ok(value)
+        }
+        .await)
+    }
+
+    async fn expire(
+        &mut self,
+        connection: Resource<RedisConnection>,
+        key: String,
+        seconds: u64,
+    ) -> Result<Result<bool, Error>> {
+        Ok(async {
+            let conn = self.get_conn(connection).await.map_err(other_error)?;
+            let value = redis::cmd("EXPIRE")
+                .arg(&key)
+                .arg(seconds)
+                .query_async(conn)
+                .await
+                .map_err(other_error)?;
+            Ok(value)
+        }
+        .await)
+    }
+
+    async fn ttl(
+        &mut self,
+        connection: Resource<RedisConnection>,
+        key: String,
+    ) -> Result<Result<i64, Error>> {
+        Ok(async {
+            let conn = self.get_conn(connection).await.map_err(other_error)?;
+            let value = conn.ttl(&key).await.map_err(other_error)?;
+            Ok(value)
+        }
+        .await)
+    }
+
+    async fn mget(
+        &mut self,
+        connection: Resource<RedisConnection>,
+        keys: Vec<String>,
+    ) -> Result<Result<Vec<Option<Vec<u8>>>, Error>> {
+        Ok(async {
+            if keys.is_empty() {
+                return Ok(vec![]);
+            }
+            let conn = self.get_conn(connection).await.map_err(other_error)?;
+            let value = redis::cmd("MGET")
+                .arg(&keys)
+                .query_async(conn)
+                .await
+                .map_err(other_error)?;
+            Ok(value)
+        }
+        .await)
+    }
+
+    async fn mset(
+        &mut self,
+        connection: Resource<RedisConnection>,
+        items: Vec<(String, Vec<u8>)>,
+    ) -> Result<Result<(), Error>> {
+        Ok(async {
+            if items.is_empty() {
+                return Ok(());
+            }
+            let conn = self.get_conn(connection).await.map_err(other_error)?;
+            conn.set_multiple(&items).await.map_err(other_error)?;
+            Ok(())
+        }
+        .await)
+    }
+
+    async fn lpush(
+        &mut self,
+        connection: Resource<RedisConnection>,
+        key: String,
+        values: Vec<Vec<u8>>,
+    ) -> Result<Result<u32, Error>> {
+        Ok(async {
+            let conn = self.get_conn(connection).await.map_err(other_error)?;
+            let value = conn.lpush(&key, &values).await.map_err(type_error)?;
+            Ok(value)
+        }
+        .await)
+    }
+
+    async fn rpop(
+        &mut self,
+        connection: Resource<RedisConnection>,
+        key: String,
+    ) -> Result<Result<Option<Vec<u8>>, Error>> {
+        Ok(async {
+            let conn = self.get_conn(connection).await.map_err(other_error)?;
+            let value = redis::cmd("RPOP")
+                .arg(&key)
+                .query_async(conn)
+                .await
+                .map_err(type_error)?;
+            Ok(value)
+        }
+        .await)
+    }
+
+    async fn hset(
+        &mut self,
+        connection: Resource<RedisConnection>,
+        key: String,
+        field: String,
+        value: Vec<u8>,
+    ) -> Result<Result<bool, Error>> {
+        Ok(async {
+            let conn = self.get_conn(connection).await.map_err(other_error)?;
+            let value = conn.hset(&key, &field, &value).await.map_err(type_error)?;
+            Ok(value)
+        }
+        .await)
+    }
+
+    async fn hget(
+        &mut self,
+        connection: Resource<RedisConnection>,
+        key: String,
+        field: String,
+    ) -> Result<Result<Option<Vec<u8>>, Error>> {
+        Ok(async {
+            let conn = self.get_conn(connection).await.map_err(other_error)?;
+            let value = conn.hget(&key, &field).await.map_err(type_error)?;
+            Ok(value)
+        }
+        .await)
+    }
+
+    async fn hgetall(
+        &mut self,
+        connection: Resource<RedisConnection>,
+        key: String,
+    ) -> Result<Result<Vec<(String, Vec<u8>)>, Error>> {
+        Ok(async {
+            let conn = self.get_conn(connection).await.map_err(other_error)?;
+            let value: HashMap<String, Vec<u8>> = conn.hgetall(&key).await.map_err(type_error)?;
+            Ok(value.into_iter().collect())
+        }
+        .await)
+    }
+
     async fn execute(
         &mut self,
         connection: Resource<RedisConnection>,
@@ -232,7 +406,164 @@ fn other_error(e: impl std::fmt::Display) -> Error {
     Error::Other(e.to_string())
 }
 
-/// Delegate a function call to the v2::HostConnection implementation
+/// Converts an error, distinguishing operations against a key holding the
+/// wrong kind of value.
+fn type_error(e: redis::RedisError) -> Error {
+    if e.kind() == redis::ErrorKind::TypeError {
+        Error::TypeError
+    } else {
+        Error::Other(e.to_string())
+    }
+}
+
+/// Converts a connection handle between interface versions. The handle
+/// refers to the same entry in the connection table whichever version of the
+/// interface created it.
+fn convert_connection<T: 'static, U: 'static>(connection: Resource<T>) -> Resource<U> {
+    Resource::new_own(connection.rep())
+}
+
+impl v2::Host for OutboundRedis {}
+
+/// The `fermyon:spin@2.0.0` interface, adapted onto the v3 implementation
+#[async_trait]
+impl v2::HostConnection for OutboundRedis {
+    async fn open(
+        &mut self,
+        address: String,
+    ) -> Result<Result<Resource<v2::Connection>, v2::Error>> {
+        Ok(<Self as v3::HostConnection>::open(self, address)
+            .await?
+            .map(convert_connection)
+            .map_err(Into::into))
+    }
+
+    async fn publish(
+        &mut self,
+        connection: Resource<v2::Connection>,
+        channel: String,
+        payload: Vec<u8>,
+    ) -> Result<Result<(), v2::Error>> {
+        Ok(<Self as v3::HostConnection>::publish(
+            self,
+            convert_connection(connection),
+            channel,
+            payload,
+        )
+        .await?
+        .map_err(Into::into))
+    }
+
+    async fn get(
+        &mut self,
+        connection: Resource<v2::Connection>,
+        key: String,
+    ) -> Result<Result<Option<Vec<u8>>, v2::Error>> {
+        Ok(
+            <Self as v3::HostConnection>::get(self, convert_connection(connection), key)
+                .await?
+                .map_err(Into::into),
+        )
+    }
+
+    async fn set(
+        &mut self,
+        connection: Resource<v2::Connection>,
+        key: String,
+        value: Vec<u8>,
+    ) -> Result<Result<(), v2::Error>> {
+        Ok(
+            <Self as v3::HostConnection>::set(self, convert_connection(connection), key, value)
+                .await?
+                .map_err(Into::into),
+        )
+    }
+
+    async fn incr(
+        &mut self,
+        connection: Resource<v2::Connection>,
+        key: String,
+    ) -> Result<Result<i64, v2::Error>> {
+        Ok(
+            <Self as v3::HostConnection>::incr(self, convert_connection(connection), key)
+                .await?
+                .map_err(Into::into),
+        )
+    }
+
+    async fn del(
+        &mut self,
+        connection: Resource<v2::Connection>,
+        keys: Vec<String>,
+    ) -> Result<Result<u32, v2::Error>> {
+        Ok(
+            <Self as v3::HostConnection>::del(self, convert_connection(connection), keys)
+                .await?
+                .map_err(Into::into),
+        )
+    }
+
+    async fn sadd(
+        &mut self,
+        connection: Resource<v2::Connection>,
+        key: String,
+        values: Vec<String>,
+    ) -> Result<Result<u32, v2::Error>> {
+        Ok(
+            <Self as v3::HostConnection>::sadd(self, convert_connection(connection), key, values)
+                .await?
+                .map_err(Into::into),
+        )
+    }
+
+    async fn smembers(
+        &mut self,
+        connection: Resource<v2::Connection>,
+        key: String,
+    ) -> Result<Result<Vec<String>, v2::Error>> {
+        Ok(
+            <Self as v3::HostConnection>::smembers(self, convert_connection(connection), key)
+                .await?
+                .map_err(Into::into),
+        )
+    }
+
+    async fn srem(
+        &mut self,
+        connection: Resource<v2::Connection>,
+        key: String,
+        values: Vec<String>,
+    ) -> Result<Result<u32, v2::Error>> {
+        Ok(
+            <Self as v3::HostConnection>::srem(self, convert_connection(connection), key, values)
+                .await?
+                .map_err(Into::into),
+        )
+    }
+
+    async fn execute(
+        &mut self,
+        connection: Resource<v2::Connection>,
+        command: String,
+        arguments: Vec<v2::RedisParameter>,
+    ) -> Result<Result<Vec<v2::RedisResult>, v2::Error>> {
+        Ok(<Self as v3::HostConnection>::execute(
+            self,
+            convert_connection(connection),
+            command,
+            arguments.into_iter().map(Into::into).collect(),
+        )
+        .await?
+        .map(|results| results.into_iter().map(Into::into).collect())
+        .map_err(Into::into))
+    }
+
+    fn drop(&mut self, connection: Resource<v2::Connection>) -> anyhow::Result<()> {
+        <Self as v3::HostConnection>::drop(self, convert_connection(connection))
+    }
+}
+
+/// Delegate a function call to the v3::HostConnection implementation
 macro_rules! delegate {
     ($self:ident.$name:ident($address:expr, $($arg:expr),*)) => {{
         if !$self.is_address_allowed(&$address) {
@@ -242,7 +573,7 @@ macro_rules! delegate {
             Ok(c) => c,
             Err(_) => return Ok(Err(v1::Error::Error)),
         };
-        Ok(<Self as v2::HostConnection>::$name($self, connection, $($arg),*)
+        Ok(<Self as v3::HostConnection>::$name($self, connection, $($arg),*)
             .await?
             .map_err(|_| v1::Error::Error))
     }};
@@ -325,11 +656,88 @@ impl OutboundRedis {
     async fn get_conn(
         &mut self,
         connection: Resource<RedisConnection>,
-    ) -> Result<&mut Connection, Error> {
-        self.connections
+    ) -> Result<&mut RedirectingConnection, Error> {
+        let conn = self
+            .connections
             .get_mut(connection.rep())
             .ok_or(Error::Other(
                 "could not find connection for resource".into(),
-            ))
+            ))?;
+        if let Some(fault) = spin_core::faults::inject("redis", &conn.address()).await {
+            return Err(other_error(fault));
+        }
+        Ok(conn)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn unknown_connection() -> Resource<RedisConnection> {
+        Resource::new_own(0)
+    }
+
+    #[tokio::test]
+    async fn empty_key_lists_need_no_connection() {
+        let mut redis = OutboundRedis::default();
+        let exists =
+            <OutboundRedis as v3::HostConnection>::exists(&mut redis, unknown_connection(), vec![])
+                .await
+                .unwrap();
+        assert!(matches!(exists, Ok(0)));
+
+        let mget =
+            <OutboundRedis as v3::HostConnection>::mget(&mut redis, unknown_connection(), vec![])
+                .await
+                .unwrap();
+        assert!(matches!(mget, Ok(values) if values.is_empty()));
+
+        let mset =
+            <OutboundRedis as v3::HostConnection>::mset(&mut redis, unknown_connection(), vec![])
+                .await
+                .unwrap();
+        assert!(matches!(mset, Ok(())));
+    }
+
+    #[tokio::test]
+    async fn unknown_connections_are_errors() {
+        let mut redis = OutboundRedis::default();
+        let exists = <OutboundRedis as v3::HostConnection>::exists(
+            &mut redis,
+            unknown_connection(),
+            vec!["key".to_owned()],
+        )
+        .await
+        .unwrap();
+        assert!(matches!(exists, Err(Error::Other(_))));
+
+        let hget = <OutboundRedis as v3::HostConnection>::hget(
+            &mut redis,
+            unknown_connection(),
+            "hash".to_owned(),
+            "field".to_owned(),
+        )
+        .await
+        .unwrap();
+        assert!(matches!(hget, Err(Error::Other(_))));
+    }
+
+    #[test]
+    fn execute_results_flatten_nested_replies() {
+        let value = Value::Bulk(vec![
+            Value::Int(1),
+            Value::Bulk(vec![Value::Data(b"a".to_vec()), Value::Nil]),
+            Value::Status("QUEUED".to_owned()),
+        ]);
+        let RedisResults(results) = RedisResults::from_redis_value(&value).unwrap();
+        assert!(matches!(
+            results.as_slice(),
+            [
+                RedisResult::Int64(1),
+                RedisResult::Binary(a),
+                RedisResult::Status(status),
+            ] if a == b"a" && status == "QUEUED"
+        ));
     }
 }
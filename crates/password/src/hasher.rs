@@ -0,0 +1,253 @@
+use anyhow::{anyhow, ensure, Result};
+use argon2::{
+    password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
+    Argon2,
+};
+use rand::{rngs::OsRng, RngCore};
+
+use crate::{Algorithm, Error};
+
+/// The most bytes of a password bcrypt uses.
+const BCRYPT_MAX_PASSWORD_LEN: usize = 72;
+/// The costs bcrypt accepts, which the `bcrypt` crate doesn't export.
+const BCRYPT_COSTS: std::ops::RangeInclusive<u32> = 4..=31;
+
+/// The highest cost of hashes verified, unless new hashes cost more.
+///
+/// A hash sets its own cost, so these keep a hash from, say, asking for
+/// gigabytes of memory per verification.
+const MAX_COST: Params = Params {
+    argon2_memory_kib: 256 * 1024,
+    argon2_iterations: 16,
+    argon2_parallelism: 16,
+    bcrypt_cost: 16,
+};
+
+/// The cost of new hashes.
+///
+/// The defaults are those OWASP recommends: Argon2id with 19 MiB of memory,
+/// 2 iterations and 1 lane, and bcrypt with a cost of 12.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Params {
+    pub argon2_memory_kib: u32,
+    pub argon2_iterations: u32,
+    pub argon2_parallelism: u32,
+    pub bcrypt_cost: u32,
+}
+
+impl Default for Params {
+    fn default() -> Self {
+        Self {
+            argon2_memory_kib: 19 * 1024,
+            argon2_iterations: 2,
+            argon2_parallelism: 1,
+            bcrypt_cost: 12,
+        }
+    }
+}
+
+pub(crate) struct Hasher {
+    params: Params,
+    argon2: Argon2<'static>,
+}
+
+impl Hasher {
+    pub fn new(params: Params) -> Result<Self> {
+        let argon2_params = argon2::Params::new(
+            params.argon2_memory_kib,
+            params.argon2_iterations,
+            params.argon2_parallelism,
+            None,
+        )
+        .map_err(|e| anyhow!("invalid Argon2 parameters: {e}"))?;
+        ensure!(
+            BCRYPT_COSTS.contains(&params.bcrypt_cost),
+            "bcrypt cost must be from {} to {}",
+            BCRYPT_COSTS.start(),
+            BCRYPT_COSTS.end()
+        );
+        let argon2 = Argon2::new(
+            argon2::Algorithm::Argon2id,
+            argon2::Version::V0x13,
+            argon2_params,
+        );
+        Ok(Self { params, argon2 })
+    }
+
+    pub fn hash(&self, password: &str, algorithm: Algorithm) -> Result<String, Error> {
+        match algorithm {
+            Algorithm::Argon2id => {
+                let mut salt = [0; 16];
+                OsRng.fill_bytes(&mut salt);
+                let salt = SaltString::encode_b64(&salt).map_err(other)?;
+                let hash = self
+                    .argon2
+                    .hash_password(password.as_bytes(), &salt)
+                    .map_err(other)?;
+                Ok(hash.to_string())
+            }
+            Algorithm::Bcrypt => {
+                if password.len() > BCRYPT_MAX_PASSWORD_LEN {
+                    return Err(Error::PasswordTooLong);
+                }
+                bcrypt::hash(password, self.params.bcrypt_cost).map_err(other)
+            }
+        }
+    }
+
+    pub fn verify(&self, password: &str, hash: &str) -> Result<bool, Error> {
+        let max = |param: fn(&Params) -> u32| param(&self.params).max(param(&MAX_COST));
+        match parse(hash)? {
+            Parsed::Argon2(hash) => {
+                let param = |name| hash.params.get_decimal(name).unwrap_or(0);
+                if param("m") > max(|p| p.argon2_memory_kib)
+                    || param("t") > max(|p| p.argon2_iterations)
+                    || param("p") > max(|p| p.argon2_parallelism)
+                {
+                    return Err(Error::CostTooHigh);
+                }
+                Ok(Argon2::default()
+                    .verify_password(password.as_bytes(), &hash)
+                    .is_ok())
+            }
+            Parsed::Bcrypt(cost) => {
+                if cost > max(|p| p.bcrypt_cost) {
+                    return Err(Error::CostTooHigh);
+                }
+                bcrypt::verify(password, hash).map_err(other)
+            }
+        }
+    }
+
+    pub fn needs_rehash(&self, hash: &str) -> Result<bool, Error> {
+        match parse(hash)? {
+            Parsed::Argon2(hash) => {
+                let param = |name| hash.params.get_decimal(name).unwrap_or(0);
+                Ok(hash.algorithm.as_str() != "argon2id"
+                    || param("m") < self.params.argon2_memory_kib
+                    || param("t") < self.params.argon2_iterations
+                    || param("p") < self.params.argon2_parallelism)
+            }
+            Parsed::Bcrypt(cost) => Ok(cost < self.params.bcrypt_cost),
+        }
+    }
+}
+
+#[allow(clippy::large_enum_variant)] // it's matched on straight away
+enum Parsed<'a> {
+    Argon2(PasswordHash<'a>),
+    /// A bcrypt hash, with its cost.
+    Bcrypt(u32),
+}
+
+fn parse(hash: &str) -> Result<Parsed<'_>, Error> {
+    if hash.starts_with("$argon2") {
+        let hash = PasswordHash::new(hash).map_err(|_| Error::InvalidHash)?;
+        return Ok(Parsed::Argon2(hash));
+    }
+    // A bcrypt hash is `$2b$<cost>$<salt and hash>`, or `$2a$` or `$2y$`.
+    match hash.split('$').collect::<Vec<_>>()[..] {
+        ["", "2a" | "2b" | "2y", cost, rest] if cost.len() == 2 && rest.len() == 53 => cost
+            .parse()
+            .map(Parsed::Bcrypt)
+            .map_err(|_| Error::InvalidHash),
+        _ => Err(Error::InvalidHash),
+    }
+}
+
+fn other(err: impl std::fmt::Display) -> Error {
+    Error::Other(err.to_string())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Parameters cheap enough for tests.
+    const PARAMS: Params = Params {
+        argon2_memory_kib: 64,
+        argon2_iterations: 1,
+        argon2_parallelism: 1,
+        bcrypt_cost: 4,
+    };
+
+    #[test]
+    fn passwords_are_hashed_and_verified() {
+        let hasher = Hasher::new(PARAMS).unwrap();
+        for algorithm in [Algorithm::Argon2id, Algorithm::Bcrypt] {
+            let hash = hasher.hash("hunter2", algorithm).unwrap();
+            assert_ne!(hash, hasher.hash("hunter2", algorithm).unwrap());
+            assert!(hasher.verify("hunter2", &hash).unwrap());
+            assert!(!hasher.verify("hunter3", &hash).unwrap());
+            assert!(!hasher.needs_rehash(&hash).unwrap());
+        }
+        assert!(hasher
+            .hash("x".repeat(73).as_str(), Algorithm::Bcrypt)
+            .is_err());
+        assert!(matches!(
+            hasher.verify("hunter2", "hunter2"),
+            Err(Error::InvalidHash)
+        ));
+    }
+
+    #[test]
+    fn weaker_hashes_need_rehashing() {
+        let weak = Hasher::new(PARAMS).unwrap();
+        let strong = Hasher::new(Params {
+            argon2_iterations: 2,
+            bcrypt_cost: 5,
+            ..PARAMS
+        })
+        .unwrap();
+        for algorithm in [Algorithm::Argon2id, Algorithm::Bcrypt] {
+            let hash = weak.hash("hunter2", algorithm).unwrap();
+            assert!(strong.needs_rehash(&hash).unwrap());
+            assert!(strong.verify("hunter2", &hash).unwrap());
+            assert!(!weak
+                .needs_rehash(&strong.hash("hunter2", algorithm).unwrap())
+                .unwrap());
+        }
+    }
+
+    #[test]
+    fn costly_hashes_are_not_verified() {
+        let hasher = Hasher::new(PARAMS).unwrap();
+        let hashes = [
+            // Argon2id with 4 GiB of memory.
+            "$argon2id$v=19$m=4194304,t=1,p=1$c29tZXNhbHQ$RdescudvJCsgt3ub+b+dWRWJTmaaJObG",
+            "$argon2id$v=19$m=64,t=1000,p=1$c29tZXNhbHQ$RdescudvJCsgt3ub+b+dWRWJTmaaJObG",
+            "$argon2id$v=19$m=64,t=1,p=255$c29tZXNhbHQ$RdescudvJCsgt3ub+b+dWRWJTmaaJObG",
+            "$2b$31$N9qo8uLOickgx2ZMRZoMyeIjZAgcfl7p92ldGxad68LJZdL17lhWy",
+        ];
+        for hash in hashes {
+            assert!(matches!(
+                hasher.verify("hunter2", hash),
+                Err(Error::CostTooHigh)
+            ));
+        }
+
+        // Configuring a higher cost raises the limit.
+        let hasher = Hasher::new(Params {
+            argon2_memory_kib: 8 * 17,
+            argon2_parallelism: 17,
+            ..PARAMS
+        })
+        .unwrap();
+        let hash = hasher.hash("hunter2", Algorithm::Argon2id).unwrap();
+        assert!(hasher.verify("hunter2", &hash).unwrap());
+    }
+
+    #[test]
+    fn invalid_params_are_rejected() {
+        let params = Params {
+            bcrypt_cost: 40,
+            ..PARAMS
+        };
+        assert!(Hasher::new(params).is_err());
+        let params = Params {
+            argon2_memory_kib: 1,
+            ..PARAMS
+        };
+        assert!(Hasher::new(params).is_err());
+    }
+}
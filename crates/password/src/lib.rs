@@ -0,0 +1,94 @@
+//! Password hashing on the host, which is many times faster than hashing
+//! inside a component, so that hashes can afford the recommended cost.
+//!
+//! The cost of new hashes is set by the runtime configuration rather than by
+//! components. Hashing runs on blocking threads, as many at once as there are
+//! CPUs, so that a burst of logins can't exhaust the host's memory.
+
+use std::sync::Arc;
+
+use anyhow::Result;
+use spin_core::{async_trait, HostComponent};
+use spin_world::v3::password;
+use tokio::sync::Semaphore;
+
+mod hasher;
+
+use hasher::Hasher;
+
+pub use hasher::Params;
+pub use password::{Algorithm, Error};
+
+pub struct PasswordComponent {
+    hasher: Arc<Hasher>,
+    permits: Arc<Semaphore>,
+}
+
+impl PasswordComponent {
+    /// A component hashing new passwords with `params`.
+    pub fn new(params: Params) -> Result<Self> {
+        let cpus = std::thread::available_parallelism().map_or(1, |n| n.get());
+        Ok(Self {
+            hasher: Arc::new(Hasher::new(params)?),
+            permits: Arc::new(Semaphore::new(cpus)),
+        })
+    }
+}
+
+impl HostComponent for PasswordComponent {
+    type Data = PasswordDispatch;
+
+    fn add_to_linker<T: Send>(
+        linker: &mut spin_core::Linker<T>,
+        get: impl Fn(&mut spin_core::Data<T>) -> &mut Self::Data + Send + Sync + Copy + 'static,
+    ) -> anyhow::Result<()> {
+        password::add_to_linker(linker, get)
+    }
+
+    fn build_data(&self) -> Self::Data {
+        PasswordDispatch {
+            hasher: self.hasher.clone(),
+            permits: self.permits.clone(),
+        }
+    }
+}
+
+pub struct PasswordDispatch {
+    hasher: Arc<Hasher>,
+    permits: Arc<Semaphore>,
+}
+
+impl PasswordDispatch {
+    /// Runs `f` with the hasher on a blocking thread, once one of the
+    /// hashing permits is free.
+    async fn run<T: Send + 'static>(
+        &self,
+        f: impl FnOnce(&Hasher) -> Result<T, Error> + Send + 'static,
+    ) -> Result<Result<T, Error>> {
+        let _permit = self.permits.acquire().await?;
+        let hasher = self.hasher.clone();
+        Ok(tokio::task::spawn_blocking(move || f(&hasher)).await?)
+    }
+}
+
+#[async_trait]
+impl password::Host for PasswordDispatch {
+    async fn hash(
+        &mut self,
+        password: String,
+        algorithm: Algorithm,
+    ) -> Result<Result<String, Error>> {
+        self.run(move |hasher| hasher.hash(&password, algorithm))
+            .await
+    }
+
+    async fn verify(&mut self, password: String, hash: String) -> Result<Result<bool, Error>> {
+        self.run(move |hasher| hasher.verify(&password, &hash))
+            .await
+    }
+
+    async fn needs_rehash(&mut self, hash: String) -> Result<Result<bool, Error>> {
+        // This only parses the hash, so needn't wait for a permit.
+        Ok(self.hasher.needs_rehash(&hash))
+    }
+}
@@ -0,0 +1,136 @@
+//! HTML-to-PDF rendering for components, by a renderer which the host
+//! provides: typically a headless browser, which can't run inside Wasm.
+//!
+//! Embedders plug a [`PdfRenderer`] in, or the runtime config names a
+//! renderer service (see [`RemoteHttpRenderer`]). The renderer is shared by
+//! every application on the host, and a document can make a browser fetch
+//! and lay out anything it links to, so a component must be granted the
+//! `pdf` capability in the manifest before it may hand documents to it.
+
+use std::sync::Arc;
+
+use anyhow::Result;
+use spin_app::{AppComponent, DynamicHostComponent, CAPABILITIES_KEY};
+use spin_core::{async_trait, HostComponent};
+use spin_world::v3::pdf;
+
+mod remote_http;
+
+pub use pdf::{Error, Options, PageSize};
+pub use remote_http::RemoteHttpRenderer;
+
+/// The capability components must be granted to render PDFs.
+pub const PDF_CAPABILITY: &str = "pdf";
+
+/// Renders HTML documents as PDFs.
+#[async_trait]
+pub trait PdfRenderer: Send + Sync {
+    async fn render(&self, html: String, options: Options) -> Result<Vec<u8>, Error>;
+}
+
+#[derive(Default)]
+pub struct PdfComponent {
+    renderer: Option<Arc<dyn PdfRenderer>>,
+}
+
+impl PdfComponent {
+    /// A component which renders with `renderer`, or which reports that
+    /// rendering is unavailable if there is none.
+    pub fn new(renderer: Option<Arc<dyn PdfRenderer>>) -> Self {
+        Self { renderer }
+    }
+}
+
+impl HostComponent for PdfComponent {
+    type Data = PdfDispatch;
+
+    fn add_to_linker<T: Send>(
+        linker: &mut spin_core::Linker<T>,
+        get: impl Fn(&mut spin_core::Data<T>) -> &mut Self::Data + Send + Sync + Copy + 'static,
+    ) -> anyhow::Result<()> {
+        pdf::add_to_linker(linker, get)
+    }
+
+    fn build_data(&self) -> Self::Data {
+        PdfDispatch {
+            allowed: false,
+            renderer: self.renderer.clone(),
+        }
+    }
+}
+
+impl DynamicHostComponent for PdfComponent {
+    fn update_data(&self, data: &mut Self::Data, component: &AppComponent) -> anyhow::Result<()> {
+        let capabilities = component
+            .get_metadata(CAPABILITIES_KEY)?
+            .unwrap_or_default();
+        data.allowed = capabilities.iter().any(|c| c == PDF_CAPABILITY);
+        Ok(())
+    }
+}
+
+pub struct PdfDispatch {
+    allowed: bool,
+    renderer: Option<Arc<dyn PdfRenderer>>,
+}
+
+#[async_trait]
+impl pdf::Host for PdfDispatch {
+    async fn render(&mut self, html: String, options: Options) -> Result<Result<Vec<u8>, Error>> {
+        if !self.allowed {
+            return Ok(Err(Error::AccessDenied));
+        }
+        let Some(renderer) = &self.renderer else {
+            return Ok(Err(Error::Unavailable));
+        };
+        Ok(renderer.render(html, options).await)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use pdf::Host;
+
+    use super::*;
+
+    struct FakeRenderer;
+
+    #[async_trait]
+    impl PdfRenderer for FakeRenderer {
+        async fn render(&self, html: String, _options: Options) -> Result<Vec<u8>, Error> {
+            Ok(format!("%PDF-{html}").into_bytes())
+        }
+    }
+
+    fn options() -> Options {
+        Options {
+            page_size: PageSize::A4,
+            landscape: false,
+            margin_mm: 10,
+            print_background: true,
+        }
+    }
+
+    #[tokio::test]
+    async fn rendering_needs_capability_and_renderer() -> Result<()> {
+        let renderer: Arc<dyn PdfRenderer> = Arc::new(FakeRenderer);
+        let mut data = PdfComponent::new(Some(renderer)).build_data();
+        assert!(matches!(
+            data.render("hi".into(), options()).await?,
+            Err(Error::AccessDenied)
+        ));
+        data.allowed = true;
+        assert_eq!(
+            b"%PDF-hi",
+            &data.render("hi".into(), options()).await?.unwrap()[..]
+        );
+
+        let mut data = PdfComponent::default().build_data();
+        data.allowed = true;
+        assert!(matches!(
+            data.render("hi".into(), options()).await?,
+            Err(Error::Unavailable)
+        ));
+        Ok(())
+    }
+}
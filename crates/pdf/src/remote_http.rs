@@ -0,0 +1,185 @@
+use std::time::Duration;
+
+use reqwest::{header::CONTENT_TYPE, Client, Url};
+use serde::Serialize;
+use spin_core::async_trait;
+
+use crate::{Error, Options, PageSize, PdfRenderer};
+
+/// How long a render may take, from connecting to the end of the PDF.
+const TIMEOUT: Duration = Duration::from_secs(60);
+/// The largest PDF read from the service.
+const MAX_PDF_SIZE: usize = 64 * 1024 * 1024;
+
+/// Renders by posting documents to a renderer service, such as one run by a
+/// Spin plugin.
+///
+/// The service is sent a JSON object with the `html` and its `pageSize`,
+/// `landscape`, `marginMm` and `printBackground` options, and responds with
+/// the PDF.
+pub struct RemoteHttpRenderer {
+    url: Url,
+    auth_token: Option<String>,
+    client: Client,
+    timeout: Duration,
+    max_size: usize,
+}
+
+impl RemoteHttpRenderer {
+    pub fn new(url: Url, auth_token: Option<String>) -> Self {
+        Self {
+            url,
+            auth_token,
+            client: Client::new(),
+            timeout: TIMEOUT,
+            max_size: MAX_PDF_SIZE,
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct RenderRequest {
+    html: String,
+    page_size: &'static str,
+    landscape: bool,
+    margin_mm: u32,
+    print_background: bool,
+}
+
+#[async_trait]
+impl PdfRenderer for RemoteHttpRenderer {
+    async fn render(&self, html: String, options: Options) -> Result<Vec<u8>, Error> {
+        let body = RenderRequest {
+            html,
+            page_size: page_size_name(options.page_size),
+            landscape: options.landscape,
+            margin_mm: options.margin_mm,
+            print_background: options.print_background,
+        };
+        let mut request = self
+            .client
+            .post(self.url.clone())
+            .timeout(self.timeout)
+            .json(&body);
+        if let Some(token) = &self.auth_token {
+            request = request.bearer_auth(token);
+        }
+        let mut response = request
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| Error::RenderFailed(e.to_string()))?;
+        let content_type = response.headers().get(CONTENT_TYPE).cloned();
+        let too_large = || {
+            Error::RenderFailed(format!(
+                "renderer responded with more than {} bytes",
+                self.max_size
+            ))
+        };
+        if response
+            .content_length()
+            .is_some_and(|len| len > self.max_size as u64)
+        {
+            return Err(too_large());
+        }
+        let mut pdf = Vec::new();
+        while let Some(chunk) = response
+            .chunk()
+            .await
+            .map_err(|e| Error::RenderFailed(e.to_string()))?
+        {
+            if pdf.len() + chunk.len() > self.max_size {
+                return Err(too_large());
+            }
+            pdf.extend_from_slice(&chunk);
+        }
+        if !pdf.starts_with(b"%PDF-") {
+            return Err(Error::RenderFailed(format!(
+                "renderer responded with {content_type:?} content rather than a PDF"
+            )));
+        }
+        Ok(pdf)
+    }
+}
+
+fn page_size_name(page_size: PageSize) -> &'static str {
+    match page_size {
+        PageSize::A3 => "A3",
+        PageSize::A4 => "A4",
+        PageSize::A5 => "A5",
+        PageSize::Letter => "Letter",
+        PageSize::Legal => "Legal",
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
+
+    use super::*;
+
+    fn options() -> Options {
+        Options {
+            page_size: PageSize::A4,
+            landscape: false,
+            margin_mm: 10,
+            print_background: true,
+        }
+    }
+
+    /// A renderer for a service which answers each request with `response`,
+    /// or never answers if there is none.
+    async fn renderer_for(response: Option<&'static [u8]>) -> RemoteHttpRenderer {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                tokio::spawn(async move {
+                    let mut request = [0; 4096];
+                    _ = stream.read(&mut request).await;
+                    match response {
+                        Some(response) => _ = stream.write_all(response).await,
+                        None => std::future::pending().await,
+                    }
+                });
+            }
+        });
+        let mut renderer = RemoteHttpRenderer::new(url.parse().unwrap(), None);
+        renderer.timeout = Duration::from_millis(100);
+        renderer.max_size = 16;
+        renderer
+    }
+
+    #[tokio::test]
+    async fn responses_are_limited() -> Result<(), Error> {
+        let renderer = renderer_for(Some(
+            b"HTTP/1.1 200 OK\r\nContent-Length: 9\r\nConnection: close\r\n\r\n%PDF-1.7\n",
+        ))
+        .await;
+        assert_eq!(
+            b"%PDF-1.7\n",
+            &renderer.render("hi".into(), options()).await?[..]
+        );
+
+        // Too large, whether or not the response says so up front.
+        let renderer = renderer_for(Some(
+            b"HTTP/1.1 200 OK\r\nContent-Length: 17\r\nConnection: close\r\n\r\n%PDF-1.7\n01234567",
+        ))
+        .await;
+        assert!(renderer.render("hi".into(), options()).await.is_err());
+        let renderer = renderer_for(Some(
+            b"HTTP/1.1 200 OK\r\nConnection: close\r\n\r\n%PDF-1.7\n01234567",
+        ))
+        .await;
+        assert!(renderer.render("hi".into(), options()).await.is_err());
+
+        // Too slow.
+        let renderer = renderer_for(None).await;
+        assert!(renderer.render("hi".into(), options()).await.is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,150 @@
+use std::{
+    fmt::Write,
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use rand::{rngs::OsRng, RngCore};
+
+const CROCKFORD: &[u8; 32] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+
+/// Makes identifiers which sort in the order they were made: a 48-bit
+/// millisecond timestamp followed by `BITS` random bits, which are one
+/// more than the last identifier's within a millisecond.
+pub(crate) struct Monotonic<const BITS: u32> {
+    last: Mutex<(u64, u128)>,
+}
+
+impl<const BITS: u32> Default for Monotonic<BITS> {
+    fn default() -> Self {
+        Self {
+            last: Mutex::new((0, 0)),
+        }
+    }
+}
+
+impl<const BITS: u32> Monotonic<BITS> {
+    /// Returns the timestamp and random bits of the next identifier.
+    pub fn next(&self) -> (u64, u128) {
+        self.next_at(now_millis())
+    }
+
+    fn next_at(&self, millis: u64) -> (u64, u128) {
+        let mask = (1u128 << BITS) - 1;
+        let mut last = self.last.lock().unwrap();
+        let (last_millis, last_random) = *last;
+        // If the clock has gone back, or the random bits could not be
+        // incremented, the last timestamp is kept or moved on, so that the
+        // order is still kept.
+        let next = if millis > last_millis {
+            (millis, random_u128() & mask)
+        } else if last_random < mask {
+            (last_millis, last_random + 1)
+        } else {
+            (last_millis + 1, random_u128() & mask)
+        };
+        *last = next;
+        next
+    }
+}
+
+/// Returns a random (version 4) UUID.
+pub(crate) fn uuid_v4() -> String {
+    uuid(random_u128() & !(0xf << 76) | (0x4 << 76))
+}
+
+/// Returns a time-ordered (version 7) UUID with the timestamp `millis` and
+/// 74 random bits.
+pub(crate) fn uuid_v7((millis, random): (u64, u128)) -> String {
+    let rand_a = random >> 62;
+    let rand_b = random & ((1 << 62) - 1);
+    let bits = (millis as u128) << 80 | 0x7 << 76 | rand_a << 64 | rand_b;
+    uuid(bits)
+}
+
+/// Formats `bits`, which hold the version, as a UUID with the RFC 9562
+/// variant.
+fn uuid(bits: u128) -> String {
+    let bits = bits & !(0b11 << 62) | (0b10 << 62);
+    let hex = format!("{bits:032x}");
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    )
+}
+
+/// Returns a ULID with the timestamp `millis` and 80 random bits.
+pub(crate) fn ulid((millis, random): (u64, u128)) -> String {
+    let bits = (millis as u128) << 80 | random;
+    let mut ulid = String::with_capacity(26);
+    for i in (0..26).rev() {
+        let digit = (bits >> (i * 5)) & 0x1f;
+        ulid.write_char(CROCKFORD[digit as usize] as char).unwrap();
+    }
+    ulid
+}
+
+fn random_u128() -> u128 {
+    let mut bytes = [0; 16];
+    OsRng.fill_bytes(&mut bytes);
+    u128::from_be_bytes(bytes)
+}
+
+fn now_millis() -> u64 {
+    let millis = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_millis() as u64);
+    // The timestamp has 48 bits.
+    millis & ((1 << 48) - 1)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn ulids_are_encoded() {
+        assert_eq!("00000000000000000000000000", ulid((0, 0)));
+        assert_eq!(
+            "7ZZZZZZZZZZZZZZZZZZZZZZZZZ",
+            ulid(((1 << 48) - 1, (1 << 80) - 1))
+        );
+        assert_eq!("01ARZ3NDEK", &ulid((1469918176385, 0))[..10]);
+    }
+
+    #[test]
+    fn ids_are_monotonic_within_a_millisecond() {
+        let ulids = Monotonic::<80>::default();
+        let first = ulids.next_at(1000);
+        let second = ulids.next_at(1000);
+        assert_eq!((1000, first.1 + 1), second);
+        assert!(ulid(first) < ulid(second));
+        // A clock going back keeps the order.
+        assert!(ulid(second) < ulid(ulids.next_at(999)));
+
+        let full = Monotonic::<2>::default();
+        *full.last.lock().unwrap() = (1000, 3);
+        assert_eq!(1001, full.next_at(1000).0);
+
+        let uuids = Monotonic::<74>::default();
+        let ids: Vec<_> = (0..100).map(|_| uuid_v7(uuids.next_at(1000))).collect();
+        let mut sorted = ids.clone();
+        sorted.sort();
+        assert_eq!(ids, sorted);
+    }
+
+    #[test]
+    fn uuids_have_their_version_and_variant() {
+        let v4 = uuid_v4();
+        assert_eq!(36, v4.len());
+        assert_eq!(Some('4'), v4.chars().nth(14));
+        assert!(matches!(v4.chars().nth(19), Some('8' | '9' | 'a' | 'b')));
+
+        let v7 = uuid_v7((0x0123_4567_89ab, 0));
+        assert_eq!("01234567-89ab-7000-8000-000000000000", v7);
+    }
+}
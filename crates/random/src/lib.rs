@@ -0,0 +1,76 @@
+//! Secure random bytes and identifiers made by the host, so that they don't
+//! depend on how a component's language seeds its generator, and so that
+//! time-ordered identifiers are ordered across the whole application.
+
+use std::sync::Arc;
+
+use anyhow::Result;
+use rand::{rngs::OsRng, RngCore};
+use spin_core::{async_trait, HostComponent};
+use spin_world::v3::random;
+
+mod id;
+
+use id::Monotonic;
+
+pub use random::Error;
+
+/// The most random bytes a component may request at once.
+const MAX_BYTES: u32 = 64 * 1024;
+
+#[derive(Default)]
+pub struct RandomComponent {
+    generators: Arc<Generators>,
+}
+
+/// The state of an application's time-ordered identifiers.
+#[derive(Default)]
+struct Generators {
+    uuid_v7: Monotonic<74>,
+    ulid: Monotonic<80>,
+}
+
+impl HostComponent for RandomComponent {
+    type Data = RandomDispatch;
+
+    fn add_to_linker<T: Send>(
+        linker: &mut spin_core::Linker<T>,
+        get: impl Fn(&mut spin_core::Data<T>) -> &mut Self::Data + Send + Sync + Copy + 'static,
+    ) -> anyhow::Result<()> {
+        random::add_to_linker(linker, get)
+    }
+
+    fn build_data(&self) -> Self::Data {
+        RandomDispatch {
+            generators: self.generators.clone(),
+        }
+    }
+}
+
+pub struct RandomDispatch {
+    generators: Arc<Generators>,
+}
+
+#[async_trait]
+impl random::Host for RandomDispatch {
+    async fn bytes(&mut self, len: u32) -> Result<Result<Vec<u8>, Error>> {
+        if len > MAX_BYTES {
+            return Ok(Err(Error::TooManyBytes));
+        }
+        let mut bytes = vec![0; len as usize];
+        OsRng.fill_bytes(&mut bytes);
+        Ok(Ok(bytes))
+    }
+
+    async fn uuid_v4(&mut self) -> Result<String> {
+        Ok(id::uuid_v4())
+    }
+
+    async fn uuid_v7(&mut self) -> Result<String> {
+        Ok(id::uuid_v7(self.generators.uuid_v7.next()))
+    }
+
+    async fn ulid(&mut self) -> Result<String> {
+        Ok(id::ulid(self.generators.ulid.next()))
+    }
+}
@@ -5,12 +5,15 @@ mod spin;
 use std::collections::HashMap;
 
 use anyhow::{anyhow, Context, Result};
-use futures::StreamExt;
-use redis::{Client, ConnectionLike};
+use futures::{
+    future::{self, Either},
+    StreamExt,
+};
+use redis::{Client, ConnectionInfo, ConnectionLike, IntoConnectionInfo};
 use serde::{de::IgnoredAny, Deserialize, Serialize};
 use spin_app::MetadataKey;
 use spin_core::async_trait;
-use spin_trigger::{cli::NoArgs, TriggerAppEngine, TriggerExecutor};
+use spin_trigger::{admin, cli::NoArgs, TriggerAppEngine, TriggerExecutor};
 
 use crate::spin::SpinRedisExecutor;
 
@@ -24,6 +27,9 @@ pub struct RedisTrigger {
     engine: TriggerAppEngine<Self>,
     // Redis address to connect to
     address: String,
+    // ACL username and password, overriding any in the address
+    username: Option<String>,
+    password: Option<String>,
     // Mapping of subscription channels to component IDs
     channel_components: HashMap<String, String>,
 }
@@ -45,7 +51,15 @@ pub struct RedisTriggerConfig {
 #[serde(deny_unknown_fields)]
 struct TriggerMetadata {
     r#type: String,
+    /// A `redis://` or `rediss://` (TLS) URL. Append `#insecure` to a
+    /// `rediss://` URL to skip certificate verification.
     address: String,
+    /// An ACL username, which may refer to application variables.
+    #[serde(default)]
+    username: Option<String>,
+    /// A password, which may refer to application variables.
+    #[serde(default)]
+    password: Option<String>,
 }
 
 #[async_trait]
@@ -56,7 +70,26 @@ impl TriggerExecutor for RedisTrigger {
     type RunConfig = NoArgs;
 
     async fn new(engine: TriggerAppEngine<Self>) -> Result<Self> {
-        let address = engine.app().require_metadata(TRIGGER_METADATA_KEY)?.address;
+        let metadata = engine.app().require_metadata(TRIGGER_METADATA_KEY)?;
+        let address = metadata.address;
+        let username = match metadata.username {
+            Some(username) => Some(
+                engine
+                    .resolve_template(&username)
+                    .await
+                    .context("Failed to resolve Redis trigger username")?,
+            ),
+            None => None,
+        };
+        let password = match metadata.password {
+            Some(password) => Some(
+                engine
+                    .resolve_template(&password)
+                    .await
+                    .context("Failed to resolve Redis trigger password")?,
+            ),
+            None => None,
+        };
 
         let channel_components = engine
             .trigger_configs()
@@ -66,20 +99,21 @@ impl TriggerExecutor for RedisTrigger {
         Ok(Self {
             engine,
             address,
+            username,
+            password,
             channel_components,
         })
     }
 
     /// Run the Redis trigger indefinitely.
     async fn run(self, _config: Self::RunConfig) -> Result<()> {
-        let address = &self.address;
-
-        tracing::info!("Connecting to Redis server at {}", address);
-        let mut client = Client::open(address.to_string())?;
+        let info = self.connection_info()?;
+        tracing::info!("Connecting to Redis server at {:?}", info.addr);
+        let mut client = Client::open(info.clone())?;
         let mut pubsub = client
             .get_async_connection()
             .await
-            .with_context(|| anyhow!("Redis trigger failed to connect to {}", address))?
+            .with_context(|| anyhow!("Redis trigger failed to connect to {:?}", info.addr))?
             .into_pubsub();
 
         // Subscribe to channels
@@ -88,23 +122,53 @@ impl TriggerExecutor for RedisTrigger {
             pubsub.subscribe(channel).await?;
         }
 
-        let mut stream = pubsub.on_message();
-        loop {
-            match stream.next().await {
-                Some(msg) => drop(self.handle(msg).await),
-                None => {
-                    tracing::trace!("Empty message");
-                    if !client.check_connection() {
-                        tracing::info!("No Redis connection available");
-                        break Ok(());
+        let messages = async {
+            let mut stream = pubsub.on_message();
+            loop {
+                match stream.next().await {
+                    Some(msg) => drop(self.handle(msg).await),
+                    None => {
+                        tracing::trace!("Empty message");
+                        if !client.check_connection() {
+                            tracing::info!("No Redis connection available");
+                            break Ok(());
+                        }
                     }
-                }
-            };
+                };
+            }
+        };
+        let jobs = async {
+            loop {
+                self.engine.dispatch_jobs().await;
+            }
+        };
+        futures::pin_mut!(messages, jobs);
+        match future::select(messages, jobs).await {
+            Either::Left((result, _)) => result,
+            Either::Right(((), _)) => unreachable!("jobs are dispatched until the trigger stops"),
         }
     }
 }
 
 impl RedisTrigger {
+    /// Returns the connection info for the trigger's Redis server. Messages
+    /// published anywhere in a Redis Cluster are delivered to subscribers on
+    /// every node, so any cluster node can be used as the address.
+    fn connection_info(&self) -> Result<ConnectionInfo> {
+        let mut info = self
+            .address
+            .as_str()
+            .into_connection_info()
+            .context("Invalid Redis trigger address")?;
+        if let Some(username) = &self.username {
+            info.redis.username = Some(username.clone());
+        }
+        if let Some(password) = &self.password {
+            info.redis.password = Some(password.clone());
+        }
+        Ok(info)
+    }
+
     // Handle the message.
     async fn handle(&self, msg: redis::Msg) -> Result<()> {
         let channel = msg.get_channel_name();
@@ -113,9 +177,10 @@ impl RedisTrigger {
         if let Some(component_id) = self.channel_components.get(channel) {
             tracing::trace!("Executing Redis component {component_id:?}");
             let executor = SpinRedisExecutor;
-            executor
-                .execute(&self.engine, component_id, channel, msg.get_payload_bytes())
-                .await?
+            let execution =
+                executor.execute(&self.engine, component_id, channel, msg.get_payload_bytes());
+            let execution = spin_core::audit::scope(component_id, execution);
+            admin::track_invocation(component_id, execution).await?
         } else {
             tracing::debug!("No subscription found for {:?}", channel);
         }
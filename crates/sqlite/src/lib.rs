@@ -104,6 +104,7 @@ impl sqlite::HostConnection for SqliteDispatch {
             Ok(c) => c,
             Err(err) => return Ok(Err(err)),
         };
+        spin_core::audit::record_query("sqlite", &query);
         Ok(conn.query(&query, parameters).await)
     }
 
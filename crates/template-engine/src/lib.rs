@@ -0,0 +1,130 @@
+//! Handlebars and Liquid templates rendered by the host, so that components
+//! producing HTML needn't embed a template engine.
+//!
+//! A component's templates are the `.hbs`, `.handlebars` and `.liquid` files
+//! under the directories listed as its `template_dirs`, which must be among
+//! its file mounts. They are compiled
+//! once, when the application is loaded, so that rendering doesn't parse them
+//! and so that invalid templates stop the application from starting.
+
+use std::{
+    collections::HashMap,
+    path::{Component, Path, PathBuf},
+    sync::{Arc, RwLock},
+};
+
+use anyhow::{ensure, Context, Result};
+use spin_app::{App, AppComponent, DynamicHostComponent, MetadataKey};
+use spin_common::url::parse_file_url;
+use spin_core::{async_trait, HostComponent};
+use spin_world::v3::template;
+
+mod templates;
+
+pub use template::Error;
+pub use templates::Templates;
+
+pub const TEMPLATE_DIRS_KEY: MetadataKey<Vec<String>> = MetadataKey::new("template_dirs");
+
+#[derive(Default)]
+pub struct TemplateComponent {
+    /// Each component's templates, by component ID.
+    compiled: RwLock<HashMap<String, Arc<Templates>>>,
+}
+
+impl HostComponent for TemplateComponent {
+    type Data = TemplateDispatch;
+
+    fn add_to_linker<T: Send>(
+        linker: &mut spin_core::Linker<T>,
+        get: impl Fn(&mut spin_core::Data<T>) -> &mut Self::Data + Send + Sync + Copy + 'static,
+    ) -> anyhow::Result<()> {
+        template::add_to_linker(linker, get)
+    }
+
+    fn build_data(&self) -> Self::Data {
+        TemplateDispatch { templates: None }
+    }
+}
+
+impl DynamicHostComponent for TemplateComponent {
+    fn update_data(&self, data: &mut Self::Data, component: &AppComponent) -> anyhow::Result<()> {
+        data.templates = self.compiled.read().unwrap().get(component.id()).cloned();
+        Ok(())
+    }
+
+    fn validate_app(&self, app: &App) -> anyhow::Result<()> {
+        let mut compiled = HashMap::new();
+        for component in app.components() {
+            let dirs = component
+                .get_metadata(TEMPLATE_DIRS_KEY)?
+                .unwrap_or_default();
+            if dirs.is_empty() {
+                continue;
+            }
+            let dirs = dirs
+                .iter()
+                .map(|dir| host_dir(&component, dir))
+                .collect::<Result<Vec<_>>>()?;
+            let templates = Templates::compile(&dirs).with_context(|| {
+                format!(
+                    "Failed to compile the templates of component {}",
+                    component.id()
+                )
+            })?;
+            compiled.insert(component.id().to_owned(), Arc::new(templates));
+        }
+        *self.compiled.write().unwrap() = compiled;
+        Ok(())
+    }
+}
+
+/// Returns the host directory of the component's files at the guest path
+/// `dir`.
+fn host_dir(component: &AppComponent, dir: &str) -> Result<PathBuf> {
+    let guest_dir = Path::new("/").join(dir);
+    let (mount, rest) = component
+        .files()
+        .filter_map(|mount| Some((mount, guest_dir.strip_prefix(&mount.path).ok()?)))
+        .max_by_key(|(mount, _)| mount.path.components().count())
+        .with_context(|| {
+            format!(
+                "Component {} has template directory {dir:?}, which is not among its files",
+                component.id()
+            )
+        })?;
+    // Otherwise the directory could be outside the mount on the host.
+    ensure!(
+        !rest.components().any(|c| c == Component::ParentDir),
+        "Component {} has template directory {dir:?}, which leaves its files mount",
+        component.id()
+    );
+    let source = mount
+        .content
+        .source
+        .as_deref()
+        .with_context(|| format!("Missing 'source' on files mount {mount:?}"))?;
+    Ok(parse_file_url(source)?.join(rest))
+}
+
+pub struct TemplateDispatch {
+    templates: Option<Arc<Templates>>,
+}
+
+#[async_trait]
+impl template::Host for TemplateDispatch {
+    async fn render(&mut self, name: String, data: String) -> Result<Result<String, Error>> {
+        Ok(match &self.templates {
+            Some(templates) => templates.render(&name, &data),
+            None => Err(Error::NoSuchTemplate),
+        })
+    }
+
+    async fn names(&mut self) -> Result<Vec<String>> {
+        Ok(self
+            .templates
+            .as_ref()
+            .map(|templates| templates.names())
+            .unwrap_or_default())
+    }
+}
@@ -0,0 +1,207 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::PathBuf,
+};
+
+use anyhow::{anyhow, Context, Result};
+use handlebars::Handlebars;
+use liquid::partials::{EagerCompiler, InMemorySource};
+use serde_json::Value;
+use walkdir::WalkDir;
+
+use crate::Error;
+
+/// A component's templates, compiled.
+pub struct Templates {
+    handlebars: Handlebars<'static>,
+    liquid: HashMap<String, liquid::Template>,
+}
+
+impl Templates {
+    /// Compiles the templates under `dirs`, named by their paths within
+    /// their directory. Files which aren't templates are skipped.
+    pub fn compile(dirs: &[PathBuf]) -> Result<Self> {
+        let mut sources = BTreeMap::new();
+        for dir in dirs {
+            for entry in WalkDir::new(dir).sort_by_file_name() {
+                let entry = entry?;
+                if !entry.file_type().is_file() {
+                    continue;
+                }
+                let name = entry
+                    .path()
+                    .strip_prefix(dir)?
+                    .components()
+                    .map(|c| c.as_os_str().to_string_lossy())
+                    .collect::<Vec<_>>()
+                    .join("/");
+                if !is_handlebars(&name) && !is_liquid(&name) {
+                    continue;
+                }
+                let source = std::fs::read_to_string(entry.path())
+                    .with_context(|| format!("Failed to read template {:?}", entry.path()))?;
+                sources.insert(name, source);
+            }
+        }
+
+        let mut handlebars = Handlebars::new();
+        let mut partials = InMemorySource::new();
+        for (name, source) in &sources {
+            if is_handlebars(name) {
+                handlebars
+                    .register_template_string(name, source)
+                    .with_context(|| format!("Invalid template {name:?}"))?;
+            } else {
+                partials.add(name, source);
+            }
+        }
+        let parser = liquid::ParserBuilder::with_stdlib()
+            .partials(EagerCompiler::new(partials))
+            .build()
+            .map_err(|e| anyhow!("Invalid template: {e}"))?;
+        let mut liquid = HashMap::new();
+        for (name, source) in &sources {
+            if !is_handlebars(name) {
+                let template = parser
+                    .parse(source)
+                    .map_err(|e| anyhow!("Invalid template {name:?}: {e}"))?;
+                liquid.insert(name.clone(), template);
+            }
+        }
+        Ok(Self { handlebars, liquid })
+    }
+
+    /// Renders the template `name` with `data`, a JSON object.
+    pub fn render(&self, name: &str, data: &str) -> Result<String, Error> {
+        let data: Value =
+            serde_json::from_str(data).map_err(|e| Error::InvalidData(e.to_string()))?;
+        if !data.is_object() {
+            return Err(Error::InvalidData("data is not a JSON object".into()));
+        }
+        if is_handlebars(name) {
+            if !self.handlebars.has_template(name) {
+                return Err(Error::NoSuchTemplate);
+            }
+            self.handlebars
+                .render(name, &data)
+                .map_err(|e| Error::RenderFailed(e.to_string()))
+        } else {
+            let template = self.liquid.get(name).ok_or(Error::NoSuchTemplate)?;
+            let globals =
+                liquid::model::to_object(&data).map_err(|e| Error::InvalidData(e.to_string()))?;
+            template
+                .render(&globals)
+                .map_err(|e| Error::RenderFailed(e.to_string()))
+        }
+    }
+
+    /// Returns the names of the templates.
+    pub fn names(&self) -> Vec<String> {
+        let mut names: Vec<_> = self
+            .handlebars
+            .get_templates()
+            .keys()
+            .chain(self.liquid.keys())
+            .cloned()
+            .collect();
+        names.sort();
+        names
+    }
+}
+
+fn is_handlebars(name: &str) -> bool {
+    name.ends_with(".hbs") || name.ends_with(".handlebars")
+}
+
+fn is_liquid(name: &str) -> bool {
+    name.ends_with(".liquid")
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+
+    use super::*;
+
+    fn compile(files: &[(&str, &str)]) -> Result<Templates> {
+        let dir = tempfile::tempdir()?;
+        for (name, source) in files {
+            let path = dir.path().join(name);
+            fs::create_dir_all(path.parent().unwrap())?;
+            fs::write(path, source)?;
+        }
+        Templates::compile(&[dir.path().to_owned()])
+    }
+
+    #[test]
+    fn templates_are_rendered() -> Result<()> {
+        let templates = compile(&[
+            ("header.liquid", "<h1>{{ title | escape }}</h1>"),
+            (
+                "pages/index.liquid",
+                r#"{% include "header.liquid" %}{% for item in items %}[{{ item }}]{% endfor %}"#,
+            ),
+            ("emails/footer.hbs", "Bye, {{name}}"),
+            (
+                "emails/welcome.hbs",
+                "Hi, {{name}}! {{> emails/footer.hbs}}",
+            ),
+        ])?;
+        assert_eq!(
+            vec![
+                "emails/footer.hbs",
+                "emails/welcome.hbs",
+                "header.liquid",
+                "pages/index.liquid"
+            ],
+            templates.names()
+        );
+        assert_eq!(
+            "<h1>Fish &amp; chips</h1>[1][2]",
+            templates
+                .render(
+                    "pages/index.liquid",
+                    r#"{"title": "Fish & chips", "items": [1, 2]}"#
+                )
+                .unwrap()
+        );
+        assert_eq!(
+            "Hi, &lt;b&gt;! Bye, &lt;b&gt;",
+            templates
+                .render("emails/welcome.hbs", r#"{"name": "<b>"}"#)
+                .unwrap()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn errors_are_reported() -> Result<()> {
+        let templates = compile(&[("page.liquid", "{{ title }}")])?;
+        assert!(matches!(
+            templates.render("missing.liquid", "{}"),
+            Err(Error::NoSuchTemplate)
+        ));
+        assert!(matches!(
+            templates.render("missing.hbs", "{}"),
+            Err(Error::NoSuchTemplate)
+        ));
+        assert!(matches!(
+            templates.render("page.liquid", "[]"),
+            Err(Error::InvalidData(_))
+        ));
+        assert!(compile(&[("broken.liquid", "{% if %}")]).is_err());
+        assert!(compile(&[("broken.hbs", "{{#if}}")]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn other_files_are_skipped() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        fs::write(dir.path().join("page.liquid"), "{{ title }}")?;
+        fs::write(dir.path().join("logo.png"), [0x89, b'P', b'N', b'G', 0xff])?;
+        fs::write(dir.path().join("README.md"), "{% if %}")?;
+        let templates = Templates::compile(&[dir.path().to_owned()])?;
+        assert_eq!(vec!["page.liquid"], templates.names());
+        Ok(())
+    }
+}
@@ -33,9 +33,12 @@ pub(crate) trait InteractionStrategy {
         run: &Run,
         parameter: &TemplateParameter,
     ) -> Cancellable<String, anyhow::Error>;
+    fn allow_post_generate_commands(&self, commands: &[String]) -> anyhow::Result<bool>;
 }
 
+#[derive(Clone, Copy)]
 pub(crate) struct Interactive;
+#[derive(Clone, Copy)]
 pub(crate) struct Silent;
 
 impl InteractionStrategy for Interactive {
@@ -71,6 +74,14 @@ impl InteractionStrategy for Interactive {
             },
         }
     }
+
+    fn allow_post_generate_commands(&self, commands: &[String]) -> anyhow::Result<bool> {
+        println!("The template runs these commands in the generated directory:");
+        for command in commands {
+            println!("    {command}");
+        }
+        Ok(crate::interaction::confirm("Run them?")?)
+    }
 }
 
 impl InteractionStrategy for Silent {
@@ -99,6 +110,14 @@ impl InteractionStrategy for Silent {
             },
         }
     }
+
+    fn allow_post_generate_commands(&self, commands: &[String]) -> anyhow::Result<bool> {
+        println!(
+            "Skipped the template's post-generation commands, which need confirmation: `{}`",
+            commands.join("`, `")
+        );
+        Ok(false)
+    }
 }
 
 pub(crate) fn confirm(text: &str) -> std::io::Result<bool> {
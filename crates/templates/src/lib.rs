@@ -21,7 +21,7 @@ mod writer;
 
 pub use manager::*;
 pub use run::{Run, RunOptions};
-pub use source::TemplateSource;
+pub use source::{SourceTemplates, TemplateSource};
 pub use template::{Template, TemplateVariantInfo};
 
 #[cfg(test)]
@@ -467,7 +467,7 @@ mod tests {
         PathBuf::from(crate_dir).join("tests")
     }
 
-    const TPLS_IN_THIS: usize = 12;
+    const TPLS_IN_THIS: usize = 15;
 
     #[tokio::test]
     async fn can_install_into_new_directory() {
@@ -1328,6 +1328,70 @@ mod tests {
         assert_contains(&err_str, "unknown filter 'lol_snort'");
     }
 
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn runs_post_generate_commands() {
+        let source = TemplateSource::File(test_data_root());
+        let mut source_templates = source.load_templates().await.unwrap();
+
+        let dest_temp_dir = tempdir().unwrap();
+        let options = |name: &str| RunOptions {
+            variant: crate::template::TemplateVariantInfo::NewApplication,
+            output_path: dest_temp_dir.path().join(name),
+            name: name.to_owned(),
+            values: HashMap::new(),
+            accept_defaults: false,
+        };
+
+        let template = source_templates.take("post-generate").unwrap();
+        template
+            .run(options("hooked"))
+            .allow_hooks()
+            .silent()
+            .await
+            .unwrap();
+        let hooked = dest_temp_dir.path().join("hooked");
+        assert!(hooked.join("README.md").exists());
+        let marker = tokio::fs::read_to_string(hooked.join("hooked.txt"))
+            .await
+            .unwrap();
+        assert_eq!("generated", marker.trim());
+
+        let mut source_templates = source.load_templates().await.unwrap();
+        let template = source_templates.take("post-generate").unwrap();
+        template
+            .run(options("unhooked"))
+            .without_hooks()
+            .silent()
+            .await
+            .unwrap();
+        let unhooked = dest_temp_dir.path().join("unhooked");
+        assert!(unhooked.join("README.md").exists());
+        assert!(!unhooked.join("hooked.txt").exists());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn silent_runs_skip_unconfirmed_post_generate_commands() {
+        let source = TemplateSource::File(test_data_root());
+        let mut source_templates = source.load_templates().await.unwrap();
+
+        let dest_temp_dir = tempdir().unwrap();
+        let output_path = dest_temp_dir.path().join("unconfirmed");
+        let options = RunOptions {
+            variant: crate::template::TemplateVariantInfo::NewApplication,
+            output_path: output_path.clone(),
+            name: "unconfirmed".to_owned(),
+            values: HashMap::new(),
+            accept_defaults: false,
+        };
+
+        let template = source_templates.take("post-generate").unwrap();
+        template.run(options).silent().await.unwrap();
+        assert!(output_path.join("README.md").exists());
+        assert!(!output_path.join("hooked.txt").exists());
+    }
+
     fn assert_contains(actual: &str, expected: &str) {
         assert!(
             actual.contains(expected),
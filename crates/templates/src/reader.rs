@@ -32,6 +32,7 @@ pub(crate) struct RawTemplateVariant {
     pub skip_files: Option<Vec<String>>,
     pub skip_parameters: Option<Vec<String>>,
     pub snippets: Option<HashMap<String, String>>,
+    pub post_generate: Option<Vec<String>>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -24,6 +24,19 @@ use crate::{
 pub struct Run {
     pub(crate) template: Template,
     pub(crate) options: RunOptions,
+    hooks: Hooks,
+}
+
+/// Whether to run a template's post-generation commands.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Hooks {
+    /// Run them if the user confirms. A silent run, which can't ask, skips
+    /// them.
+    Confirm,
+    /// Run them without asking.
+    Allow,
+    /// Don't run them.
+    Skip,
 }
 
 /// Options controlling the execution of a template.
@@ -42,7 +55,26 @@ pub struct RunOptions {
 
 impl Run {
     pub(crate) fn new(template: Template, options: RunOptions) -> Self {
-        Self { template, options }
+        Self {
+            template,
+            options,
+            hooks: Hooks::Confirm,
+        }
+    }
+
+    /// Skips the template's post-generation commands (such as `cargo fmt` or
+    /// `npm install`). The generated files are the same either way.
+    pub fn without_hooks(mut self) -> Self {
+        self.hooks = Hooks::Skip;
+        self
+    }
+
+    /// Runs the template's post-generation commands without asking the user
+    /// to confirm them first. By default they are run only once the user
+    /// has seen and confirmed them, so a silent run skips them.
+    pub fn allow_hooks(mut self) -> Self {
+        self.hooks = Hooks::Allow;
+        self
     }
 
     /// Runs the template interactively. The user will be prompted for any
@@ -60,15 +92,52 @@ impl Run {
         self.run(Silent).await
     }
 
-    async fn run(&self, interaction: impl InteractionStrategy) -> anyhow::Result<()> {
+    async fn run(&self, interaction: impl InteractionStrategy + Copy) -> anyhow::Result<()> {
         self.build_renderer(interaction)
             .await
             .and_then(|t| t.render())
             .and_then_async(|o| async move { o.write().await })
             .await
+            .and_then_async(|_| async move { self.run_post_generate_commands(interaction).await })
+            .await
             .err()
     }
 
+    async fn run_post_generate_commands(
+        &self,
+        interaction: impl InteractionStrategy,
+    ) -> anyhow::Result<()> {
+        let commands = self.template.post_generate_commands(&self.options.variant);
+        if commands.is_empty() {
+            return Ok(());
+        }
+        let allowed = match self.hooks {
+            Hooks::Confirm => interaction.allow_post_generate_commands(commands)?,
+            Hooks::Allow => true,
+            Hooks::Skip => false,
+        };
+        if !allowed {
+            return Ok(());
+        }
+
+        let dir = self.generation_target_dir();
+        for command in commands {
+            println!("Running `{command}`...");
+            let status = shell_command(command)
+                .current_dir(&dir)
+                .status()
+                .await
+                .with_context(|| format!("Failed to run post-generation command `{command}`"))?;
+            if !status.success() {
+                return Err(anyhow!(
+                    "Post-generation command `{command}` failed with {status}. The {} was generated, but may need further setup.",
+                    self.options.variant.prompt_noun()
+                ));
+            }
+        }
+        Ok(())
+    }
+
     async fn build_renderer(
         &self,
         interaction: impl InteractionStrategy,
@@ -345,3 +414,15 @@ impl Run {
             .expect("can't fail due to no partials support")
     }
 }
+
+fn shell_command(command: &str) -> tokio::process::Command {
+    if cfg!(windows) {
+        let mut cmd = tokio::process::Command::new("cmd");
+        cmd.arg("/C").arg(command);
+        cmd
+    } else {
+        let mut cmd = tokio::process::Command::new("sh");
+        cmd.arg("-c").arg(command);
+        cmd
+    }
+}
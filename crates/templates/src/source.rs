@@ -5,7 +5,9 @@ use tempfile::{tempdir, TempDir};
 use tokio::process::Command;
 use url::Url;
 
-use crate::{directory::subdirectories, git::UnderstandGitResult};
+use crate::{
+    directory::subdirectories, git::UnderstandGitResult, store::TemplateLayout, template::Template,
+};
 
 const TEMPLATE_SOURCE_DIR: &str = "templates";
 const TEMPLATE_VERSION_TAG_PREFIX: &str = "spin/templates/v";
@@ -86,6 +88,37 @@ impl TemplateSource {
     }
 }
 
+/// Templates read directly from a source, without installing them.
+///
+/// The templates refer to files in the source (which, for a Git source, is a
+/// temporary clone), so this must be kept alive while they are being run.
+pub struct SourceTemplates {
+    templates: Vec<Template>,
+    _source: LocalTemplateSource,
+}
+
+impl SourceTemplates {
+    /// The IDs of the templates in the source.
+    pub fn ids(&self) -> impl Iterator<Item = &str> {
+        self.templates.iter().map(|t| t.id())
+    }
+
+    /// Removes the template with the specified ID from the set, returning it
+    /// if the source contained it.
+    pub fn take(&mut self, id: impl AsRef<str>) -> Option<Template> {
+        let index = self.templates.iter().position(|t| t.id() == id.as_ref())?;
+        Some(self.templates.swap_remove(index))
+    }
+
+    /// If the source contains only one template, removes and returns it.
+    pub fn take_only(&mut self) -> Option<Template> {
+        match self.templates.len() {
+            1 => self.templates.pop(),
+            _ => None,
+        }
+    }
+}
+
 pub(crate) struct LocalTemplateSource {
     root: PathBuf,
     _temp_dir: Option<TempDir>,
@@ -99,6 +132,26 @@ impl TemplateSource {
         }
     }
 
+    /// Reads the templates in the source without installing them. Directories
+    /// which do not contain a valid template are ignored.
+    pub async fn load_templates(&self) -> anyhow::Result<SourceTemplates> {
+        let local_source = self
+            .get_local()
+            .await
+            .context("Failed to get template source")?;
+        let templates = local_source
+            .template_directories()
+            .await
+            .context("Could not find templates in source")?
+            .iter()
+            .filter_map(|dir| Template::load_from(&TemplateLayout::new(dir)).ok())
+            .collect();
+        Ok(SourceTemplates {
+            templates,
+            _source: local_source,
+        })
+    }
+
     pub(crate) fn requires_copy(&self) -> bool {
         match self {
             Self::Git { .. } => true,
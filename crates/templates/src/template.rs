@@ -92,6 +92,7 @@ pub(crate) struct TemplateVariant {
     skip_files: Vec<String>,
     skip_parameters: Vec<String>,
     snippets: HashMap<String, String>,
+    post_generate: Vec<String>,
 }
 
 #[derive(Clone, Debug, Eq, PartialEq, Hash)]
@@ -255,6 +256,11 @@ impl Template {
         &variant.snippets
     }
 
+    pub(crate) fn post_generate_commands(&self, variant_kind: &TemplateVariantInfo) -> &[String] {
+        let variant = self.variant(variant_kind).unwrap(); // TODO: for now
+        &variant.post_generate
+    }
+
     /// Creates a runner for the template, governed by the given options. Call
     /// the relevant associated function of the `Run` to execute the template
     /// as appropriate to your application (e.g. `interactive()` to prompt the user
@@ -327,6 +333,7 @@ impl Template {
             skip_files: raw.skip_files.unwrap_or_default(),
             skip_parameters: raw.skip_parameters.unwrap_or_default(),
             snippets: raw.snippets.unwrap_or_default(),
+            post_generate: raw.post_generate.unwrap_or_default(),
         }
     }
 
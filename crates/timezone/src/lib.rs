@@ -0,0 +1,147 @@
+//! Time zone conversions with the host's IANA time zone database, so that
+//! components can show local times without each embedding the database.
+
+use anyhow::Result;
+use chrono::{Datelike, LocalResult, NaiveDate, Offset, TimeZone, Timelike};
+use chrono_tz::{OffsetComponents, Tz, TZ_VARIANTS};
+use spin_core::{async_trait, HostComponent};
+use spin_world::v3::timezone;
+
+pub use timezone::{DateTime, Error, LocalTime};
+
+#[derive(Default)]
+pub struct TimezoneComponent;
+
+impl HostComponent for TimezoneComponent {
+    type Data = TimezoneDispatch;
+
+    fn add_to_linker<T: Send>(
+        linker: &mut spin_core::Linker<T>,
+        get: impl Fn(&mut spin_core::Data<T>) -> &mut Self::Data + Send + Sync + Copy + 'static,
+    ) -> anyhow::Result<()> {
+        timezone::add_to_linker(linker, get)
+    }
+
+    fn build_data(&self) -> Self::Data {
+        TimezoneDispatch
+    }
+}
+
+pub struct TimezoneDispatch;
+
+#[async_trait]
+impl timezone::Host for TimezoneDispatch {
+    async fn convert(&mut self, timestamp: i64, zone: String) -> Result<Result<LocalTime, Error>> {
+        Ok(convert(timestamp, &zone))
+    }
+
+    async fn to_timestamp(&mut self, local: DateTime, zone: String) -> Result<Result<i64, Error>> {
+        Ok(to_timestamp(&local, &zone))
+    }
+
+    async fn zones(&mut self) -> Result<Vec<String>> {
+        Ok(TZ_VARIANTS.iter().map(|tz| tz.name().to_owned()).collect())
+    }
+}
+
+/// Returns the local time in `zone` at `timestamp`, in seconds since the
+/// Unix epoch.
+pub fn convert(timestamp: i64, zone: &str) -> Result<LocalTime, Error> {
+    let local = parse_zone(zone)?
+        .timestamp_opt(timestamp, 0)
+        .single()
+        .ok_or(Error::InvalidTime)?;
+    let offset = local.offset();
+    Ok(LocalTime {
+        date_time: DateTime {
+            year: local.year(),
+            month: local.month() as u8,
+            day: local.day() as u8,
+            hour: local.hour() as u8,
+            minute: local.minute() as u8,
+            second: local.second() as u8,
+        },
+        weekday: local.weekday().number_from_monday() as u8,
+        offset: offset.fix().local_minus_utc(),
+        abbreviation: offset.to_string(),
+        dst: offset.dst_offset() != chrono::Duration::zero(),
+    })
+}
+
+/// Returns the timestamp, in seconds since the Unix epoch, of `local` in
+/// `zone`, taking the earlier of a repeated local time.
+pub fn to_timestamp(local: &DateTime, zone: &str) -> Result<i64, Error> {
+    let tz = parse_zone(zone)?;
+    let naive = NaiveDate::from_ymd_opt(local.year, local.month.into(), local.day.into())
+        .and_then(|date| {
+            date.and_hms_opt(local.hour.into(), local.minute.into(), local.second.into())
+        })
+        .ok_or(Error::InvalidTime)?;
+    match tz.from_local_datetime(&naive) {
+        LocalResult::Single(time) | LocalResult::Ambiguous(time, _) => Ok(time.timestamp()),
+        LocalResult::None => Err(Error::InvalidTime),
+    }
+}
+
+fn parse_zone(zone: &str) -> Result<Tz, Error> {
+    zone.parse().map_err(|_| Error::UnknownZone)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn date_time(year: i32, month: u8, day: u8, hour: u8, minute: u8) -> DateTime {
+        DateTime {
+            year,
+            month,
+            day,
+            hour,
+            minute,
+            second: 0,
+        }
+    }
+
+    #[test]
+    fn timestamps_are_converted_to_local_times() {
+        // 2023-01-15T12:00:00Z, a Sunday.
+        let winter = convert(1673784000, "America/New_York").unwrap();
+        assert_eq!(7, winter.date_time.hour);
+        assert_eq!(7, winter.weekday);
+        assert_eq!(-5 * 3600, winter.offset);
+        assert_eq!(("EST", false), (&*winter.abbreviation, winter.dst));
+
+        // 2023-07-04T16:00:00Z.
+        let summer = convert(1688486400, "America/New_York").unwrap();
+        assert_eq!(12, summer.date_time.hour);
+        assert_eq!(-4 * 3600, summer.offset);
+        assert_eq!(("EDT", true), (&*summer.abbreviation, summer.dst));
+
+        assert!(matches!(
+            convert(0, "Mars/Olympus_Mons"),
+            Err(Error::UnknownZone)
+        ));
+    }
+
+    #[test]
+    fn local_times_are_converted_to_timestamps() {
+        let noon = date_time(2023, 7, 4, 12, 0);
+        assert_eq!(1688486400, to_timestamp(&noon, "America/New_York").unwrap());
+        // 01:30 happens twice as the clocks go back; the first is in EDT.
+        let repeated = date_time(2023, 11, 5, 1, 30);
+        assert_eq!(
+            1699162200,
+            to_timestamp(&repeated, "America/New_York").unwrap()
+        );
+        // 02:30 is skipped as the clocks go forward.
+        let skipped = date_time(2023, 3, 12, 2, 30);
+        assert!(matches!(
+            to_timestamp(&skipped, "America/New_York"),
+            Err(Error::InvalidTime)
+        ));
+        assert!(matches!(
+            to_timestamp(&date_time(2023, 2, 30, 0, 0), "UTC"),
+            Err(Error::InvalidTime)
+        ));
+    }
+}
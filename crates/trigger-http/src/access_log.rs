@@ -0,0 +1,496 @@
+//! Access logging for the HTTP trigger.
+//!
+//! With `--access-log <PATH>`, a line is written for each request once its
+//! response body has been sent, so that the byte count and latency cover the
+//! whole response. Lines are in the Common or Combined Log Format, as JSON,
+//! or follow a template, and go to stdout or to a file which is optionally
+//! rotated by size.
+
+use std::{
+    fmt::Write as _,
+    fs::{File, OpenOptions},
+    io::Write,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    pin::Pin,
+    str::FromStr,
+    sync::{Arc, Mutex},
+    task::{Context as TaskContext, Poll},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{Context, Result};
+use http_body_util::BodyExt;
+use hyper::{
+    body::{Body as HttpBody, Frame, SizeHint},
+    Request, Response,
+};
+use wasmtime_wasi_http::body::HyperIncomingBody as Body;
+
+/// The fields which a template may use, e.g. `{method} {uri} {status}`.
+const TEMPLATE_FIELDS: &[&str] = &[
+    "remote_addr",
+    "time",
+    "method",
+    "uri",
+    "protocol",
+    "host",
+    "status",
+    "bytes",
+    "latency_ms",
+    "component",
+    "referer",
+    "user_agent",
+];
+
+/// How access log lines are formatted.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum AccessLogFormat {
+    /// The Common Log Format.
+    Common,
+    /// The Combined Log Format, which adds the referer and user agent.
+    Combined,
+    /// A JSON object per line, with every field.
+    Json,
+    /// A template in which fields such as `{status}` are replaced.
+    Template(String),
+}
+
+impl FromStr for AccessLogFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(match s {
+            "common" => Self::Common,
+            "combined" => Self::Combined,
+            "json" => Self::Json,
+            template => {
+                let mut rest = template;
+                while let Some(start) = rest.find('{') {
+                    let end = rest[start..]
+                        .find('}')
+                        .with_context(|| format!("unclosed '{{' in access log template {s:?}"))?;
+                    let field = &rest[start + 1..start + end];
+                    anyhow::ensure!(
+                        TEMPLATE_FIELDS.contains(&field),
+                        "unknown access log field {{{field}}}; expected one of {}",
+                        TEMPLATE_FIELDS.join(", ")
+                    );
+                    rest = &rest[start + end + 1..];
+                }
+                Self::Template(template.to_owned())
+            }
+        })
+    }
+}
+
+/// Where and how to write the access log.
+#[derive(Clone, Debug)]
+pub struct AccessLogConfig {
+    /// The file to write to, or "-" for stdout.
+    pub destination: PathBuf,
+    /// The line format.
+    pub format: AccessLogFormat,
+    /// Rotate the file once it would grow beyond this many bytes.
+    pub max_size: Option<u64>,
+    /// The number of rotated files to keep.
+    pub max_files: usize,
+}
+
+/// An open access log.
+pub(crate) struct AccessLog {
+    format: AccessLogFormat,
+    output: Mutex<Output>,
+}
+
+enum Output {
+    Stdout,
+    File {
+        path: PathBuf,
+        file: File,
+        size: u64,
+        max_size: Option<u64>,
+        max_files: usize,
+    },
+}
+
+/// What is known about a request when it arrives.
+pub(crate) struct RequestInfo {
+    remote_addr: SocketAddr,
+    time: SystemTime,
+    start: Instant,
+    method: String,
+    uri: String,
+    protocol: String,
+    host: String,
+    referer: String,
+    user_agent: String,
+    component: Option<String>,
+}
+
+impl RequestInfo {
+    pub(crate) fn new<B>(
+        req: &Request<B>,
+        remote_addr: SocketAddr,
+        component: Option<&str>,
+    ) -> Self {
+        let header = |name| {
+            req.headers()
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .unwrap_or("-")
+                .to_owned()
+        };
+        Self {
+            remote_addr,
+            time: SystemTime::now(),
+            start: Instant::now(),
+            method: req.method().to_string(),
+            uri: req
+                .uri()
+                .path_and_query()
+                .map(|p| p.to_string())
+                .unwrap_or_else(|| "/".into()),
+            protocol: format!("{:?}", req.version()),
+            host: header(http::header::HOST),
+            referer: header(http::header::REFERER),
+            user_agent: header(http::header::USER_AGENT),
+            component: component.map(str::to_owned),
+        }
+    }
+}
+
+impl AccessLog {
+    pub(crate) fn open(config: &AccessLogConfig) -> Result<Self> {
+        let output = if config.destination == Path::new("-") {
+            Output::Stdout
+        } else {
+            let path = config.destination.clone();
+            let file = open_append(&path)?;
+            let size = file.metadata().map(|m| m.len()).unwrap_or(0);
+            Output::File {
+                path,
+                file,
+                size,
+                max_size: config.max_size,
+                max_files: config.max_files,
+            }
+        };
+        Ok(Self {
+            format: config.format.clone(),
+            output: Mutex::new(output),
+        })
+    }
+
+    /// Logs the request once the response body has been sent.
+    pub(crate) fn wrap(self: &Arc<Self>, info: RequestInfo, res: Response<Body>) -> Response<Body> {
+        let status = res.status().as_u16();
+        let log = self.clone();
+        res.map(|body| {
+            LoggedBody {
+                inner: body,
+                bytes: 0,
+                pending: Some((log, info, status)),
+            }
+            .boxed()
+        })
+    }
+
+    fn write(&self, info: &RequestInfo, status: u16, bytes: u64, latency: Duration) {
+        let mut line = self.format_line(info, status, bytes, latency);
+        line.push('\n');
+        if let Err(e) = self.output.lock().unwrap().write_line(&line) {
+            tracing::warn!("Failed to write access log: {e:?}");
+        }
+    }
+
+    fn format_line(
+        &self,
+        info: &RequestInfo,
+        status: u16,
+        bytes: u64,
+        latency: Duration,
+    ) -> String {
+        let clf_bytes = if bytes == 0 {
+            "-".to_owned()
+        } else {
+            bytes.to_string()
+        };
+        let common = || {
+            format!(
+                "{} - - [{}] \"{} {} {}\" {status} {clf_bytes}",
+                info.remote_addr.ip(),
+                clf_time(info.time),
+                info.method,
+                info.uri,
+                info.protocol,
+            )
+        };
+        match &self.format {
+            AccessLogFormat::Common => common(),
+            AccessLogFormat::Combined => format!(
+                "{} \"{}\" \"{}\"",
+                common(),
+                escape_quotes(&info.referer),
+                escape_quotes(&info.user_agent)
+            ),
+            AccessLogFormat::Json => serde_json::json!({
+                "remote_addr": info.remote_addr.ip().to_string(),
+                "time": rfc3339_time(info.time),
+                "method": info.method,
+                "uri": info.uri,
+                "protocol": info.protocol,
+                "host": info.host,
+                "status": status,
+                "bytes": bytes,
+                "latency_ms": latency_ms(latency),
+                "component": info.component,
+                "referer": info.referer,
+                "user_agent": info.user_agent,
+            })
+            .to_string(),
+            AccessLogFormat::Template(template) => {
+                let mut line = String::with_capacity(template.len() * 2);
+                let mut rest = template.as_str();
+                // Fields were validated when the template was parsed.
+                while let Some(start) = rest.find('{') {
+                    let end = start + rest[start..].find('}').unwrap();
+                    line.push_str(&rest[..start]);
+                    match &rest[start + 1..end] {
+                        "remote_addr" => _ = write!(line, "{}", info.remote_addr.ip()),
+                        "time" => line.push_str(&rfc3339_time(info.time)),
+                        "method" => line.push_str(&info.method),
+                        "uri" => line.push_str(&info.uri),
+                        "protocol" => line.push_str(&info.protocol),
+                        "host" => line.push_str(&info.host),
+                        "status" => _ = write!(line, "{status}"),
+                        "bytes" => _ = write!(line, "{bytes}"),
+                        "latency_ms" => _ = write!(line, "{:.3}", latency_ms(latency)),
+                        "component" => line.push_str(info.component.as_deref().unwrap_or("-")),
+                        "referer" => line.push_str(&info.referer),
+                        "user_agent" => line.push_str(&info.user_agent),
+                        _ => {}
+                    }
+                    rest = &rest[end + 1..];
+                }
+                line.push_str(rest);
+                line
+            }
+        }
+    }
+}
+
+impl Output {
+    fn write_line(&mut self, line: &str) -> std::io::Result<()> {
+        match self {
+            Self::Stdout => std::io::stdout().lock().write_all(line.as_bytes()),
+            Self::File {
+                path,
+                file,
+                size,
+                max_size,
+                max_files,
+            } => {
+                let len = line.len() as u64;
+                if max_size.is_some_and(|max| *size > 0 && *size + len > max) {
+                    rotate(path, *max_files)?;
+                    *file = open_append(path)?;
+                    *size = 0;
+                }
+                file.write_all(line.as_bytes())?;
+                *size += len;
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Renames `path` to `path.1`, `path.1` to `path.2`, and so on, dropping the
+/// oldest once there are `max_files`.
+fn rotate(path: &Path, max_files: usize) -> std::io::Result<()> {
+    let rotated = |n: usize| {
+        let mut name = path.as_os_str().to_owned();
+        name.push(format!(".{n}"));
+        PathBuf::from(name)
+    };
+    if max_files == 0 {
+        return std::fs::remove_file(path);
+    }
+    for n in (1..max_files).rev() {
+        let from = rotated(n);
+        if from.exists() {
+            std::fs::rename(from, rotated(n + 1))?;
+        }
+    }
+    std::fs::rename(path, rotated(1))
+}
+
+fn open_append(path: &Path) -> std::io::Result<File> {
+    OpenOptions::new().create(true).append(true).open(path)
+}
+
+/// A response body which writes the access log line when it is finished or
+/// dropped, e.g. because the client went away.
+struct LoggedBody {
+    inner: Body,
+    bytes: u64,
+    pending: Option<(Arc<AccessLog>, RequestInfo, u16)>,
+}
+
+impl HttpBody for LoggedBody {
+    type Data = <Body as HttpBody>::Data;
+    type Error = <Body as HttpBody>::Error;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut TaskContext<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let frame = Pin::new(&mut self.inner).poll_frame(cx);
+        if let Poll::Ready(Some(Ok(frame))) = &frame {
+            if let Some(data) = frame.data_ref() {
+                self.bytes += data.len() as u64;
+            }
+        }
+        frame
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+impl Drop for LoggedBody {
+    fn drop(&mut self) {
+        if let Some((log, info, status)) = self.pending.take() {
+            log.write(&info, status, self.bytes, info.start.elapsed());
+        }
+    }
+}
+
+fn latency_ms(latency: Duration) -> f64 {
+    latency.as_secs_f64() * 1000.0
+}
+
+fn escape_quotes(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// Splits a time into UTC calendar fields: year, month, day, hour, minute,
+/// second.
+fn utc_fields(time: SystemTime) -> (i64, u32, u32, u64, u64, u64) {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let (days, rem) = ((secs / 86_400) as i64, secs % 86_400);
+    // Howard Hinnant's days-to-civil algorithm.
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day, rem / 3600, rem % 3600 / 60, rem % 60)
+}
+
+/// Formats a time as in the Common Log Format, e.g. `10/Oct/2000:13:55:36 +0000`.
+fn clf_time(time: SystemTime) -> String {
+    const MONTHS: [&str; 12] = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ];
+    let (year, month, day, hour, minute, second) = utc_fields(time);
+    format!(
+        "{day:02}/{}/{year}:{hour:02}:{minute:02}:{second:02} +0000",
+        MONTHS[month as usize - 1]
+    )
+}
+
+/// Formats a time as RFC 3339 in UTC, e.g. `2000-10-10T13:55:36Z`.
+fn rfc3339_time(time: SystemTime) -> String {
+    let (year, month, day, hour, minute, second) = utc_fields(time);
+    format!("{year}-{month:02}-{day:02}T{hour:02}:{minute:02}:{second:02}Z")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn info() -> RequestInfo {
+        let req = Request::get("http://localhost:3000/hello?x=1")
+            .header("host", "localhost:3000")
+            .header("user-agent", "curl/8.0")
+            .body(())
+            .unwrap();
+        RequestInfo {
+            // 10/Oct/2000:13:55:36 UTC
+            time: UNIX_EPOCH + Duration::from_secs(971_186_136),
+            ..RequestInfo::new(&req, "192.0.2.1:5000".parse().unwrap(), Some("hello"))
+        }
+    }
+
+    fn log(format: &str) -> AccessLog {
+        AccessLog {
+            format: format.parse().unwrap(),
+            output: Mutex::new(Output::Stdout),
+        }
+    }
+
+    #[test]
+    fn lines_use_the_combined_log_format() {
+        let line = log("combined").format_line(&info(), 200, 5, Duration::ZERO);
+        assert_eq!(
+            "192.0.2.1 - - [10/Oct/2000:13:55:36 +0000] \"GET /hello?x=1 HTTP/1.1\" 200 5 \"-\" \"curl/8.0\"",
+            line
+        );
+    }
+
+    #[test]
+    fn json_lines_include_the_component_and_latency() {
+        let line = log("json").format_line(&info(), 500, 0, Duration::from_millis(12));
+        let json: serde_json::Value = serde_json::from_str(&line).unwrap();
+        assert_eq!("hello", json["component"]);
+        assert_eq!(500, json["status"]);
+        assert_eq!(12.0, json["latency_ms"]);
+        assert_eq!("2000-10-10T13:55:36Z", json["time"]);
+    }
+
+    #[test]
+    fn templates_replace_fields() {
+        let line = log("{component} {status} {bytes}B {latency_ms}ms").format_line(
+            &info(),
+            404,
+            9,
+            Duration::from_micros(1500),
+        );
+        assert_eq!("hello 404 9B 1.500ms", line);
+        "{nope}".parse::<AccessLogFormat>().unwrap_err();
+        "{status".parse::<AccessLogFormat>().unwrap_err();
+    }
+
+    #[test]
+    fn files_are_rotated_by_size() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("access.log");
+        let log = AccessLog::open(&AccessLogConfig {
+            destination: path.clone(),
+            format: AccessLogFormat::Template("{status}".into()),
+            max_size: Some(8),
+            max_files: 2,
+        })
+        .unwrap();
+        for status in [200, 201, 202, 203] {
+            log.write(&info(), status, 0, Duration::ZERO);
+        }
+        let read = |name: &str| std::fs::read_to_string(dir.path().join(name)).unwrap();
+        assert_eq!("202\n203\n", read("access.log"));
+        assert_eq!("200\n201\n", read("access.log.1"));
+        assert!(!dir.path().join("access.log.2").exists());
+    }
+}
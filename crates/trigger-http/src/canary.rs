@@ -0,0 +1,90 @@
+//! Per-route canaries, which serve a share of a route's requests.
+
+use anyhow::{ensure, Result};
+use rand::Rng;
+use spin_http::config::CanaryConfig;
+
+/// Chooses which component serves each request for a route.
+pub(crate) struct Canaries {
+    // Component ID -> percentage of requests, which total at most 100
+    weights: Vec<(String, u32)>,
+}
+
+impl Canaries {
+    pub(crate) fn from_config(configs: &[CanaryConfig]) -> Result<Option<Self>> {
+        if configs.is_empty() {
+            return Ok(None);
+        }
+        let mut total = 0;
+        for config in configs {
+            ensure!(
+                config.weight > 0,
+                "canary {:?} must have a weight of at least 1",
+                config.component
+            );
+            total += config.weight;
+        }
+        ensure!(
+            total <= 100,
+            "canary weights must total at most 100, not {total}"
+        );
+        let weights = configs
+            .iter()
+            .map(|config| (config.component.clone(), config.weight))
+            .collect();
+        Ok(Some(Self { weights }))
+    }
+
+    /// The IDs of the canary components.
+    pub(crate) fn components(&self) -> impl Iterator<Item = &str> {
+        self.weights.iter().map(|(id, _)| id.as_str())
+    }
+
+    /// Returns the component which serves a request, either a canary or
+    /// `component`, the route's own.
+    pub(crate) fn choose<'a>(&'a self, component: &'a str) -> &'a str {
+        self.choose_with(rand::thread_rng().gen_range(0..100), component)
+    }
+
+    fn choose_with<'a>(&'a self, mut roll: u32, component: &'a str) -> &'a str {
+        for (id, weight) in &self.weights {
+            if roll < *weight {
+                return id;
+            }
+            roll -= weight;
+        }
+        component
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn canary(component: &str, weight: u32) -> CanaryConfig {
+        CanaryConfig {
+            component: component.into(),
+            weight,
+        }
+    }
+
+    #[test]
+    fn requests_are_shared_by_weight() {
+        let canaries = Canaries::from_config(&[canary("v2", 10), canary("v3", 5)])
+            .unwrap()
+            .unwrap();
+        let served: Vec<_> = (0..100)
+            .map(|roll| canaries.choose_with(roll, "v1"))
+            .collect();
+        assert_eq!(10, served.iter().filter(|id| **id == "v2").count());
+        assert_eq!(5, served.iter().filter(|id| **id == "v3").count());
+        assert_eq!(85, served.iter().filter(|id| **id == "v1").count());
+    }
+
+    #[test]
+    fn invalid_weights_are_rejected() {
+        assert!(Canaries::from_config(&[]).unwrap().is_none());
+        Canaries::from_config(&[canary("v2", 0)]).unwrap_err();
+        Canaries::from_config(&[canary("v2", 60), canary("v3", 50)]).unwrap_err();
+    }
+}
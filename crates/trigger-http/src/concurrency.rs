@@ -0,0 +1,100 @@
+//! Per-route limits on concurrent invocations.
+
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+
+use anyhow::{ensure, Result};
+use spin_http::config::ConcurrencyConfig;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+/// Admits up to `max_concurrent` invocations at once, queuing up to
+/// `max_queued` more and shedding the rest.
+pub(crate) struct ConcurrencyLimit {
+    permits: Arc<Semaphore>,
+    queued: AtomicUsize,
+    max_queued: usize,
+}
+
+impl ConcurrencyLimit {
+    pub(crate) fn from_config(config: &ConcurrencyConfig) -> Result<Self> {
+        ensure!(
+            config.max_concurrent > 0,
+            "max_concurrent must be at least 1"
+        );
+        Ok(Self {
+            permits: Arc::new(Semaphore::new(config.max_concurrent)),
+            queued: AtomicUsize::new(0),
+            max_queued: config.max_queued,
+        })
+    }
+
+    /// Waits for a turn to invoke the component, returning a permit to hold
+    /// while it runs, or `None` straight away if the queue is full.
+    pub(crate) async fn acquire(&self) -> Option<OwnedSemaphorePermit> {
+        if let Ok(permit) = self.permits.clone().try_acquire_owned() {
+            return Some(permit);
+        }
+        self.queued
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |queued| {
+                (queued < self.max_queued).then_some(queued + 1)
+            })
+            .ok()?;
+        let _queued = Dequeue(&self.queued);
+        // The semaphore is never closed.
+        self.permits.clone().acquire_owned().await.ok()
+    }
+}
+
+/// Leaves the queue when dropped, including if the request is abandoned
+/// while waiting.
+struct Dequeue<'a>(&'a AtomicUsize);
+
+impl Drop for Dequeue<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limit(max_concurrent: usize, max_queued: usize) -> Arc<ConcurrencyLimit> {
+        let config = ConcurrencyConfig {
+            max_concurrent,
+            max_queued,
+        };
+        Arc::new(ConcurrencyLimit::from_config(&config).unwrap())
+    }
+
+    #[tokio::test]
+    async fn requests_beyond_the_queue_are_shed() {
+        let limit = limit(1, 1);
+        let running = limit.acquire().await.unwrap();
+
+        let waiting = tokio::spawn({
+            let limit = limit.clone();
+            async move { limit.acquire().await.is_some() }
+        });
+        while limit.queued.load(Ordering::SeqCst) == 0 {
+            tokio::task::yield_now().await;
+        }
+        assert!(limit.acquire().await.is_none());
+
+        drop(running);
+        assert!(waiting.await.unwrap());
+        assert_eq!(0, limit.queued.load(Ordering::SeqCst));
+        assert!(limit.acquire().await.is_some());
+    }
+
+    #[test]
+    fn at_least_one_invocation_is_allowed() {
+        let config = ConcurrencyConfig {
+            max_concurrent: 0,
+            max_queued: 10,
+        };
+        assert!(ConcurrencyLimit::from_config(&config).is_err());
+    }
+}
@@ -0,0 +1,279 @@
+//! Trusted proxy handling for the HTTP trigger.
+//!
+//! With `--trusted-proxy <RANGE>`, the `X-Forwarded-For`, `X-Forwarded-Proto`
+//! and `X-Forwarded-Host` headers of requests from those addresses are
+//! honoured: the client address, scheme and host which components see (as
+//! `spin-client-addr` and in `spin-full-url`) are the ones the proxy reports.
+//! The headers are removed from requests from anywhere else, so that clients
+//! can't spoof them.
+
+use std::{
+    net::{IpAddr, SocketAddr},
+    str::FromStr,
+};
+
+use anyhow::{Context, Result};
+use http::{uri::Authority, uri::Scheme, HeaderValue, Request};
+
+const FORWARDED_FOR: &str = "x-forwarded-for";
+const FORWARDED_PROTO: &str = "x-forwarded-proto";
+const FORWARDED_HOST: &str = "x-forwarded-host";
+
+/// An IP address range in CIDR notation, e.g. `10.0.0.0/8`, or a single
+/// address.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IpRange {
+    network: IpAddr,
+    prefix_len: u8,
+}
+
+impl FromStr for IpRange {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (address, prefix_len) = match s.split_once('/') {
+            Some((address, prefix_len)) => (address, Some(prefix_len)),
+            None => (s, None),
+        };
+        let network: IpAddr = address
+            .parse()
+            .with_context(|| format!("invalid IP address in range {s:?}"))?;
+        let max_len = if network.is_ipv4() { 32 } else { 128 };
+        let prefix_len = match prefix_len {
+            Some(prefix_len) => prefix_len
+                .parse()
+                .ok()
+                .filter(|len| *len <= max_len)
+                .with_context(|| {
+                    format!("invalid prefix length in range {s:?}; expected 0 to {max_len}")
+                })?,
+            None => max_len,
+        };
+        Ok(Self {
+            network,
+            prefix_len,
+        })
+    }
+}
+
+impl IpRange {
+    fn contains(&self, ip: IpAddr) -> bool {
+        match (self.network, canonical(ip)) {
+            (IpAddr::V4(network), IpAddr::V4(ip)) => {
+                let mask = u32::MAX
+                    .checked_shl(32 - self.prefix_len as u32)
+                    .unwrap_or(0);
+                u32::from(network) & mask == u32::from(ip) & mask
+            }
+            (IpAddr::V6(network), IpAddr::V6(ip)) => {
+                let mask = u128::MAX
+                    .checked_shl(128 - self.prefix_len as u32)
+                    .unwrap_or(0);
+                u128::from(network) & mask == u128::from(ip) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Treats IPv4-mapped IPv6 addresses, as a dual-stack listener reports IPv4
+/// peers, as the IPv4 addresses they are.
+fn canonical(ip: IpAddr) -> IpAddr {
+    match ip {
+        IpAddr::V6(v6) => v6.to_ipv4_mapped().map(IpAddr::V4).unwrap_or(ip),
+        ip => ip,
+    }
+}
+
+/// The proxies whose forwarding headers are trusted.
+#[derive(Clone, Debug, Default)]
+pub struct TrustedProxies {
+    ranges: Vec<IpRange>,
+}
+
+impl TrustedProxies {
+    pub fn new(ranges: Vec<IpRange>) -> Self {
+        Self { ranges }
+    }
+
+    fn is_trusted(&self, ip: IpAddr) -> bool {
+        self.ranges.iter().any(|range| range.contains(ip))
+    }
+
+    /// Returns the scheme and client address of a request which `peer` sent
+    /// over `scheme`. If the peer is a trusted proxy, these are the ones it
+    /// forwarded, and the request's `Host` header is replaced by the
+    /// forwarded host; otherwise any forwarding headers are removed.
+    pub fn resolve<B>(
+        &self,
+        req: &mut Request<B>,
+        scheme: Scheme,
+        peer: SocketAddr,
+    ) -> (Scheme, SocketAddr) {
+        if !self.is_trusted(peer.ip()) {
+            let headers = req.headers_mut();
+            for name in [FORWARDED_FOR, FORWARDED_PROTO, FORWARDED_HOST] {
+                headers.remove(name);
+            }
+            return (scheme, peer);
+        }
+
+        let client = self.forwarded_client(req).unwrap_or(peer);
+        let scheme = match first_value(req, FORWARDED_PROTO) {
+            Some(proto) if proto.eq_ignore_ascii_case("https") => Scheme::HTTPS,
+            Some(proto) if proto.eq_ignore_ascii_case("http") => Scheme::HTTP,
+            _ => scheme,
+        };
+        let host = first_value(req, FORWARDED_HOST)
+            .filter(|host| host.parse::<Authority>().is_ok())
+            .and_then(|host| HeaderValue::from_str(host).ok());
+        if let Some(host) = host {
+            req.headers_mut().insert(http::header::HOST, host);
+        }
+        (scheme, client)
+    }
+
+    /// Returns the client address from `X-Forwarded-For`: the nearest address
+    /// which isn't a trusted proxy, since any before it may be spoofed.
+    fn forwarded_client<B>(&self, req: &Request<B>) -> Option<SocketAddr> {
+        let hops: Vec<&str> = req
+            .headers()
+            .get_all(FORWARDED_FOR)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .map(str::trim)
+            .collect();
+        let mut client = None;
+        for hop in hops.into_iter().rev() {
+            let Some(addr) = parse_hop(hop) else {
+                break;
+            };
+            client = Some(addr);
+            if !self.is_trusted(addr.ip()) {
+                break;
+            }
+        }
+        client
+    }
+}
+
+/// Parses an `X-Forwarded-For` entry, which some proxies give with a port.
+fn parse_hop(hop: &str) -> Option<SocketAddr> {
+    if let Ok(addr) = hop.parse::<SocketAddr>() {
+        return Some(addr);
+    }
+    let ip = hop.strip_prefix('[').and_then(|hop| hop.strip_suffix(']'));
+    let ip: IpAddr = ip.unwrap_or(hop).parse().ok()?;
+    Some(SocketAddr::new(ip, 0))
+}
+
+/// Returns the first of a header's comma-separated values, which for a chain
+/// of proxies is the one the outermost proxy set.
+fn first_value<'a, B>(req: &'a Request<B>, name: &str) -> Option<&'a str> {
+    let value = req.headers().get(name)?.to_str().ok()?;
+    value.split(',').next().map(str::trim)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn proxies(ranges: &[&str]) -> TrustedProxies {
+        TrustedProxies::new(ranges.iter().map(|r| r.parse().unwrap()).collect())
+    }
+
+    fn request(headers: &[(&str, &str)]) -> Request<()> {
+        let mut builder = Request::get("/").header("host", "internal:3000");
+        for (name, value) in headers {
+            builder = builder.header(*name, *value);
+        }
+        builder.body(()).unwrap()
+    }
+
+    #[test]
+    fn ranges_are_parsed() {
+        let range: IpRange = "10.0.0.0/8".parse().unwrap();
+        assert!(range.contains("10.1.2.3".parse().unwrap()));
+        assert!(!range.contains("11.0.0.1".parse().unwrap()));
+        assert!(range.contains("::ffff:10.0.0.1".parse().unwrap()));
+
+        let range: IpRange = "fd00::/8".parse().unwrap();
+        assert!(range.contains("fd12::1".parse().unwrap()));
+        assert!(!range.contains("fe80::1".parse().unwrap()));
+
+        let range: IpRange = "0.0.0.0/0".parse().unwrap();
+        assert!(range.contains("192.0.2.1".parse().unwrap()));
+
+        let range: IpRange = "127.0.0.1".parse().unwrap();
+        assert!(range.contains("127.0.0.1".parse().unwrap()));
+        assert!(!range.contains("127.0.0.2".parse().unwrap()));
+
+        assert!("10.0.0.0/33".parse::<IpRange>().is_err());
+        assert!("example.com/8".parse::<IpRange>().is_err());
+    }
+
+    #[test]
+    fn forwarding_headers_from_untrusted_peers_are_removed() {
+        let mut req = request(&[
+            ("x-forwarded-for", "203.0.113.7"),
+            ("x-forwarded-proto", "https"),
+            ("x-forwarded-host", "example.com"),
+        ]);
+        let peer = "192.0.2.1:5000".parse().unwrap();
+        let (scheme, client) = proxies(&["10.0.0.0/8"]).resolve(&mut req, Scheme::HTTP, peer);
+
+        assert_eq!(Scheme::HTTP, scheme);
+        assert_eq!(peer, client);
+        assert_eq!("internal:3000", req.headers()["host"]);
+        assert!(!req.headers().contains_key("x-forwarded-for"));
+        assert!(!req.headers().contains_key("x-forwarded-proto"));
+        assert!(!req.headers().contains_key("x-forwarded-host"));
+    }
+
+    #[test]
+    fn forwarding_headers_from_trusted_peers_are_honoured() {
+        let mut req = request(&[
+            ("x-forwarded-for", "198.51.100.1, 203.0.113.7, 10.0.0.2"),
+            ("x-forwarded-proto", "https"),
+            ("x-forwarded-host", "example.com"),
+        ]);
+        let peer = "10.0.0.1:5000".parse().unwrap();
+        let (scheme, client) = proxies(&["10.0.0.0/8"]).resolve(&mut req, Scheme::HTTP, peer);
+
+        assert_eq!(Scheme::HTTPS, scheme);
+        // The first address isn't trusted, as the untrusted client may have
+        // sent it.
+        assert_eq!("203.0.113.7:0".parse::<SocketAddr>().unwrap(), client);
+        assert_eq!("example.com", req.headers()["host"]);
+    }
+
+    #[test]
+    fn invalid_forwarding_headers_are_ignored() {
+        let mut req = request(&[
+            ("x-forwarded-for", "unknown"),
+            ("x-forwarded-proto", "gopher"),
+            ("x-forwarded-host", "not a host"),
+        ]);
+        let peer = "[::1]:5000".parse().unwrap();
+        let (scheme, client) = proxies(&["::1"]).resolve(&mut req, Scheme::HTTP, peer);
+
+        assert_eq!(Scheme::HTTP, scheme);
+        assert_eq!(peer, client);
+        assert_eq!("internal:3000", req.headers()["host"]);
+    }
+
+    #[test]
+    fn forwarded_addresses_may_have_ports() {
+        assert_eq!(
+            Some("[2001:db8::1]:8080".parse().unwrap()),
+            parse_hop("[2001:db8::1]:8080")
+        );
+        assert_eq!(
+            Some("[2001:db8::1]:0".parse().unwrap()),
+            parse_hop("[2001:db8::1]")
+        );
+        assert_eq!(Some("192.0.2.1:0".parse().unwrap()), parse_hop("192.0.2.1"));
+        assert_eq!(None, parse_hop("unknown"));
+    }
+}
@@ -0,0 +1,65 @@
+//! Client metadata enrichment for the HTTP trigger.
+//!
+//! With a `[geoip]` runtime config section, requests are given headers
+//! describing the client address (after trusted proxy handling), as looked up
+//! in MaxMind DB files on the host: `spin-client-country` (an ISO 3166-1
+//! alpha-2 code), `spin-client-asn`, and `spin-client-as-org`. Headers of
+//! these names are always removed from incoming requests, so that clients
+//! can't spoof them.
+
+use std::net::IpAddr;
+
+use http::{HeaderValue, Request};
+use spin_geoip::GeoIp;
+
+const CLIENT_COUNTRY: &str = "spin-client-country";
+const CLIENT_ASN: &str = "spin-client-asn";
+const CLIENT_AS_ORG: &str = "spin-client-as-org";
+
+/// Replaces any client metadata headers of `req` with what `geoip` knows
+/// about `client`.
+pub(crate) fn enrich<B>(req: &mut Request<B>, geoip: Option<&GeoIp>, client: IpAddr) {
+    let headers = req.headers_mut();
+    for name in [CLIENT_COUNTRY, CLIENT_ASN, CLIENT_AS_ORG] {
+        headers.remove(name);
+    }
+    let Some(geoip) = geoip else {
+        return;
+    };
+    let metadata = match geoip.lookup(client) {
+        Ok(metadata) => metadata,
+        Err(e) => {
+            tracing::warn!("Failed to look up client address {client}: {e:?}");
+            return;
+        }
+    };
+    let values = [
+        (CLIENT_COUNTRY, metadata.country),
+        (CLIENT_ASN, metadata.asn.map(|asn| asn.to_string())),
+        (CLIENT_AS_ORG, metadata.as_org),
+    ];
+    for (name, value) in values {
+        if let Some(value) = value.and_then(|v| HeaderValue::from_str(&v).ok()) {
+            headers.insert(name, value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn client_metadata_headers_are_not_spoofable() {
+        let mut req = Request::get("/")
+            .header("spin-client-country", "NZ")
+            .header("spin-client-asn", "64496")
+            .body(())
+            .unwrap();
+        let geoip = GeoIp::new(None, None);
+        enrich(&mut req, Some(&geoip), "192.0.2.1".parse().unwrap());
+
+        assert!(!req.headers().contains_key("spin-client-country"));
+        assert!(!req.headers().contains_key("spin-client-asn"));
+    }
+}
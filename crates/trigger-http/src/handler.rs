@@ -1,23 +1,42 @@
-use std::{net::SocketAddr, str, str::FromStr};
+use std::{
+    net::SocketAddr,
+    pin::Pin,
+    str,
+    str::FromStr,
+    task::{Context as TaskContext, Poll},
+    time::{Duration, Instant, SystemTime},
+};
 
 use crate::{Body, HttpExecutor, HttpTrigger, Store};
 use anyhow::bail;
 use anyhow::{anyhow, Context, Result};
 use http::{HeaderName, HeaderValue};
 use http_body_util::BodyExt;
-use hyper::{Request, Response};
+use hyper::{
+    body::{Body as HttpBody, Frame, SizeHint},
+    Request, Response,
+};
 use outbound_http::OutboundHttpComponent;
 use spin_core::async_trait;
 use spin_core::Instance;
 use spin_http::body;
-use spin_trigger::{EitherInstance, TriggerAppEngine};
+use spin_trigger::{
+    context::{RequestContextComponent, TlsInfo},
+    EitherInstance, TriggerAppEngine,
+};
 use spin_world::v1::http_types;
 use std::sync::Arc;
 use tokio::{sync::oneshot, task};
 use wasmtime_wasi_http::{proxy::Proxy, WasiHttpView};
 
 #[derive(Clone)]
-pub struct HttpHandlerExecutor;
+pub struct HttpHandlerExecutor {
+    /// How long a `wasi-http` component may keep running once its response
+    /// body has been sent.
+    pub after_response_timeout: Duration,
+    /// How long the component may take to handle the request.
+    pub timeout: Option<Duration>,
+}
 
 #[async_trait]
 impl HttpExecutor for HttpHandlerExecutor {
@@ -39,11 +58,15 @@ impl HttpExecutor for HttpHandlerExecutor {
         let EitherInstance::Component(instance) = instance else {
             unreachable!()
         };
+        // If the component is being profiled, this gives each route a
+        // profile of its own.
+        store.set_sample_label(raw_route);
 
         set_http_origin_from_request(&mut store, engine, &req);
+        set_request_context(&mut store, engine, &req, client_addr, self.timeout);
 
         let resp = match HandlerType::from_exports(instance.exports(&mut store)) {
-            Some(HandlerType::Wasi) => Self::execute_wasi(store, instance, base, raw_route, req, client_addr).await?,
+            Some(HandlerType::Wasi) => self.execute_wasi(store, instance, base, raw_route, req, client_addr).await?,
             Some(HandlerType::Spin) => {
                 Self::execute_spin(store, instance, base, raw_route, req, client_addr)
                     .await
@@ -142,6 +165,7 @@ impl HttpHandlerExecutor {
     }
 
     async fn execute_wasi(
+        &self,
         mut store: Store,
         instance: Instance,
         base: &str,
@@ -171,7 +195,7 @@ impl HttpHandlerExecutor {
 
         let proxy = Proxy::new(&mut store, &instance)?;
 
-        let handle = task::spawn(async move {
+        let mut handle = task::spawn(async move {
             let result = proxy
                 .wasi_http_incoming_handler()
                 .call_handle(&mut store, request, response)
@@ -186,7 +210,30 @@ impl HttpHandlerExecutor {
         });
 
         match response_rx.await {
-            Ok(response) => Ok(response.context("guest failed to produce a response")?),
+            Ok(response) => {
+                let response = response.context("guest failed to produce a response")?;
+
+                // The guest may carry on, e.g. with work deferred until after
+                // its response, but only for so long once the body is sent.
+                let (sent_tx, sent_rx) = oneshot::channel();
+                let timeout = self.after_response_timeout;
+                task::spawn(async move {
+                    _ = sent_rx.await;
+                    if tokio::time::timeout(timeout, &mut handle).await.is_err() {
+                        tracing::warn!(
+                            "Stopping guest still running {timeout:?} after its response was sent"
+                        );
+                        handle.abort();
+                    }
+                });
+                Ok(response.map(|body| {
+                    SentBody {
+                        inner: body,
+                        _sent: sent_tx,
+                    }
+                    .boxed()
+                }))
+            }
 
             Err(_) => {
                 handle
@@ -275,6 +322,33 @@ impl HandlerType {
     }
 }
 
+/// A response body which, when dropped once it has been sent or the client
+/// has gone away, starts the guest's after-response timeout.
+struct SentBody {
+    inner: Body,
+    _sent: oneshot::Sender<()>,
+}
+
+impl HttpBody for SentBody {
+    type Data = <Body as HttpBody>::Data;
+    type Error = <Body as HttpBody>::Error;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut TaskContext<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        Pin::new(&mut self.inner).poll_frame(cx)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
+
 fn set_http_origin_from_request(
     store: &mut Store,
     engine: &TriggerAppEngine<HttpTrigger>,
@@ -300,6 +374,27 @@ fn set_http_origin_from_request(
     }
 }
 
+fn set_request_context(
+    store: &mut Store,
+    engine: &TriggerAppEngine<HttpTrigger>,
+    req: &Request<Body>,
+    client_addr: SocketAddr,
+    timeout: Option<Duration>,
+) {
+    if let Some(timeout) = timeout {
+        store.set_deadline(Instant::now() + timeout);
+    }
+    if let Some(handle) = engine
+        .engine
+        .find_host_component_handle::<Arc<RequestContextComponent>>()
+    {
+        let context = store.host_components_data().get_or_insert(handle);
+        context.deadline = timeout.map(|timeout| SystemTime::now() + timeout);
+        context.remote_address = Some(client_addr.to_string());
+        context.tls = req.extensions().get::<TlsInfo>().cloned();
+    }
+}
+
 fn contextualise_err(e: anyhow::Error) -> anyhow::Error {
     if e.to_string()
         .contains("failed to find function export `canonical_abi_free`")
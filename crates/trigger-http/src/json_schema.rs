@@ -0,0 +1,534 @@
+//! Validation of JSON request bodies against a route's `request_schema`.
+//!
+//! The schema is given inline in the route's trigger config, as a JSON
+//! Schema. The validator supports the keywords most used to describe request
+//! bodies - `type`, `enum`, `const`, the object, array, string and number
+//! constraints, and the `allOf`/`anyOf`/`oneOf`/`not` combinators - and
+//! rejects any other assertion keyword, such as `$ref`, rather than silently
+//! ignoring it. Annotations such as `title` and `format` are ignored.
+
+use anyhow::{bail, Context, Result};
+use http::{Method, StatusCode};
+use http_body_util::BodyExt;
+use hyper::{Request, Response};
+use regex::Regex;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use spin_http::body;
+use wasmtime_wasi_http::body::HyperIncomingBody as Body;
+
+/// Keywords which carry no constraint.
+const ANNOTATIONS: &[&str] = &[
+    "$schema",
+    "$id",
+    "$comment",
+    "title",
+    "description",
+    "default",
+    "examples",
+    "format",
+    "deprecated",
+    "readOnly",
+    "writeOnly",
+];
+
+/// A compiled JSON Schema.
+#[derive(Debug, Default)]
+pub(crate) struct JsonSchema {
+    never: bool,
+    types: Option<Vec<String>>,
+    enum_values: Option<Vec<Value>>,
+    const_value: Option<Value>,
+    properties: Vec<(String, JsonSchema)>,
+    required: Vec<String>,
+    additional_properties: Option<Box<JsonSchema>>,
+    min_properties: Option<u64>,
+    max_properties: Option<u64>,
+    items: Option<Box<JsonSchema>>,
+    min_items: Option<u64>,
+    max_items: Option<u64>,
+    unique_items: bool,
+    min_length: Option<u64>,
+    max_length: Option<u64>,
+    pattern: Option<Regex>,
+    minimum: Option<f64>,
+    maximum: Option<f64>,
+    exclusive_minimum: Option<f64>,
+    exclusive_maximum: Option<f64>,
+    multiple_of: Option<f64>,
+    all_of: Vec<JsonSchema>,
+    any_of: Vec<JsonSchema>,
+    one_of: Vec<JsonSchema>,
+    not: Option<Box<JsonSchema>>,
+}
+
+/// Where and why a value does not match a schema.
+#[derive(Debug, PartialEq, Serialize)]
+pub(crate) struct ValidationError {
+    /// A JSON pointer to the value, e.g. `/items/0/name`.
+    pub path: String,
+    pub message: String,
+}
+
+impl JsonSchema {
+    pub(crate) fn compile(schema: &Value) -> Result<Self> {
+        Self::compile_at(schema, "")
+    }
+
+    fn compile_at(schema: &Value, at: &str) -> Result<Self> {
+        let object = match schema {
+            Value::Bool(true) => return Ok(Self::default()),
+            Value::Bool(false) => {
+                return Ok(Self {
+                    never: true,
+                    ..Self::default()
+                })
+            }
+            Value::Object(object) => object,
+            _ => bail!("schema at {at:?} must be an object or a boolean"),
+        };
+        let mut compiled = Self::default();
+        for (keyword, value) in object {
+            let here = format!("{at}/{keyword}");
+            let count = || {
+                value
+                    .as_u64()
+                    .with_context(|| format!("{here:?} must be a non-negative integer"))
+            };
+            let number = || {
+                value
+                    .as_f64()
+                    .with_context(|| format!("{here:?} must be a number"))
+            };
+            let list = || -> Result<Vec<Self>> {
+                value
+                    .as_array()
+                    .with_context(|| format!("{here:?} must be an array"))?
+                    .iter()
+                    .enumerate()
+                    .map(|(i, schema)| Self::compile_at(schema, &format!("{here}/{i}")))
+                    .collect()
+            };
+            match keyword.as_str() {
+                "type" => {
+                    compiled.types = Some(match value {
+                        Value::String(t) => vec![t.clone()],
+                        Value::Array(ts) => ts
+                            .iter()
+                            .map(|t| t.as_str().map(str::to_owned))
+                            .collect::<Option<_>>()
+                            .with_context(|| format!("{here:?} must contain strings"))?,
+                        _ => bail!("{here:?} must be a string or an array"),
+                    })
+                }
+                "enum" => {
+                    compiled.enum_values = Some(
+                        value
+                            .as_array()
+                            .with_context(|| format!("{here:?} must be an array"))?
+                            .clone(),
+                    )
+                }
+                "const" => compiled.const_value = Some(value.clone()),
+                "properties" => {
+                    compiled.properties = value
+                        .as_object()
+                        .with_context(|| format!("{here:?} must be an object"))?
+                        .iter()
+                        .map(|(name, schema)| {
+                            Ok((
+                                name.clone(),
+                                Self::compile_at(schema, &format!("{here}/{name}"))?,
+                            ))
+                        })
+                        .collect::<Result<_>>()?
+                }
+                "required" => {
+                    compiled.required = value
+                        .as_array()
+                        .and_then(|names| {
+                            names
+                                .iter()
+                                .map(|n| n.as_str().map(str::to_owned))
+                                .collect()
+                        })
+                        .with_context(|| format!("{here:?} must be an array of strings"))?
+                }
+                "additionalProperties" => {
+                    compiled.additional_properties = Some(Box::new(Self::compile_at(value, &here)?))
+                }
+                "minProperties" => compiled.min_properties = Some(count()?),
+                "maxProperties" => compiled.max_properties = Some(count()?),
+                "items" => compiled.items = Some(Box::new(Self::compile_at(value, &here)?)),
+                "minItems" => compiled.min_items = Some(count()?),
+                "maxItems" => compiled.max_items = Some(count()?),
+                "uniqueItems" => {
+                    compiled.unique_items = value
+                        .as_bool()
+                        .with_context(|| format!("{here:?} must be a boolean"))?
+                }
+                "minLength" => compiled.min_length = Some(count()?),
+                "maxLength" => compiled.max_length = Some(count()?),
+                "pattern" => {
+                    let pattern = value
+                        .as_str()
+                        .with_context(|| format!("{here:?} must be a string"))?;
+                    compiled.pattern = Some(
+                        Regex::new(pattern)
+                            .with_context(|| format!("{here:?} is not a valid pattern"))?,
+                    )
+                }
+                "minimum" => compiled.minimum = Some(number()?),
+                "maximum" => compiled.maximum = Some(number()?),
+                "exclusiveMinimum" => compiled.exclusive_minimum = Some(number()?),
+                "exclusiveMaximum" => compiled.exclusive_maximum = Some(number()?),
+                "multipleOf" => compiled.multiple_of = Some(number()?),
+                "allOf" => compiled.all_of = list()?,
+                "anyOf" => compiled.any_of = list()?,
+                "oneOf" => compiled.one_of = list()?,
+                "not" => compiled.not = Some(Box::new(Self::compile_at(value, &here)?)),
+                annotation if ANNOTATIONS.contains(&annotation) => {}
+                other => bail!("unsupported JSON Schema keyword {other:?} at {at:?}"),
+            }
+        }
+        Ok(compiled)
+    }
+
+    /// Returns every way in which `value` does not match the schema.
+    pub(crate) fn validate(&self, value: &Value) -> Vec<ValidationError> {
+        let mut errors = vec![];
+        self.validate_at(value, "", &mut errors);
+        errors
+    }
+
+    fn is_valid(&self, value: &Value) -> bool {
+        let mut errors = vec![];
+        self.validate_at(value, "", &mut errors);
+        errors.is_empty()
+    }
+
+    fn validate_at(&self, value: &Value, path: &str, errors: &mut Vec<ValidationError>) {
+        let mut fail = |message: String| {
+            errors.push(ValidationError {
+                path: path.to_owned(),
+                message,
+            })
+        };
+        if self.never {
+            return fail("no value is allowed here".into());
+        }
+        if let Some(types) = &self.types {
+            if !types.iter().any(|t| has_type(value, t)) {
+                return fail(format!(
+                    "expected {}, found {}",
+                    types.join(" or "),
+                    type_name(value)
+                ));
+            }
+        }
+        if let Some(values) = &self.enum_values {
+            if !values.contains(value) {
+                fail(format!("must be one of {}", Value::Array(values.clone())));
+            }
+        }
+        if let Some(expected) = &self.const_value {
+            if expected != value {
+                fail(format!("must be {expected}"));
+            }
+        }
+
+        match value {
+            Value::Object(object) => self.validate_object(object, path, errors),
+            Value::Array(array) => {
+                if let Some(min) = self.min_items {
+                    if (array.len() as u64) < min {
+                        fail(format!("must have at least {min} items"));
+                    }
+                }
+                if let Some(max) = self.max_items {
+                    if array.len() as u64 > max {
+                        fail(format!("must have at most {max} items"));
+                    }
+                }
+                if self.unique_items
+                    && array
+                        .iter()
+                        .enumerate()
+                        .any(|(i, item)| array[..i].contains(item))
+                {
+                    fail("items must be unique".into());
+                }
+                if let Some(items) = &self.items {
+                    for (i, item) in array.iter().enumerate() {
+                        items.validate_at(item, &format!("{path}/{i}"), errors);
+                    }
+                }
+            }
+            Value::String(s) => {
+                let len = s.chars().count() as u64;
+                if let Some(min) = self.min_length {
+                    if len < min {
+                        fail(format!("must be at least {min} characters long"));
+                    }
+                }
+                if let Some(max) = self.max_length {
+                    if len > max {
+                        fail(format!("must be at most {max} characters long"));
+                    }
+                }
+                if let Some(pattern) = &self.pattern {
+                    if !pattern.is_match(s) {
+                        fail(format!("must match the pattern {:?}", pattern.as_str()));
+                    }
+                }
+            }
+            Value::Number(n) => {
+                let n = n.as_f64().unwrap_or(f64::NAN);
+                if let Some(min) = self.minimum {
+                    if n < min {
+                        fail(format!("must be at least {min}"));
+                    }
+                }
+                if let Some(max) = self.maximum {
+                    if n > max {
+                        fail(format!("must be at most {max}"));
+                    }
+                }
+                if let Some(min) = self.exclusive_minimum {
+                    if n <= min {
+                        fail(format!("must be greater than {min}"));
+                    }
+                }
+                if let Some(max) = self.exclusive_maximum {
+                    if n >= max {
+                        fail(format!("must be less than {max}"));
+                    }
+                }
+                if let Some(divisor) = self.multiple_of {
+                    let quotient = n / divisor;
+                    if (quotient - quotient.round()).abs() > f64::EPSILON * quotient.abs().max(1.0)
+                    {
+                        fail(format!("must be a multiple of {divisor}"));
+                    }
+                }
+            }
+            _ => {}
+        }
+
+        for schema in &self.all_of {
+            schema.validate_at(value, path, errors);
+        }
+        let mut fail = |message: &str| {
+            errors.push(ValidationError {
+                path: path.to_owned(),
+                message: message.to_owned(),
+            })
+        };
+        if !self.any_of.is_empty() && !self.any_of.iter().any(|s| s.is_valid(value)) {
+            fail("must match at least one of the allowed schemas");
+        }
+        if !self.one_of.is_empty() && self.one_of.iter().filter(|s| s.is_valid(value)).count() != 1
+        {
+            fail("must match exactly one of the allowed schemas");
+        }
+        if let Some(not) = &self.not {
+            if not.is_valid(value) {
+                fail("must not match the disallowed schema");
+            }
+        }
+    }
+
+    fn validate_object(
+        &self,
+        object: &Map<String, Value>,
+        path: &str,
+        errors: &mut Vec<ValidationError>,
+    ) {
+        for name in &self.required {
+            if !object.contains_key(name) {
+                errors.push(ValidationError {
+                    path: path.to_owned(),
+                    message: format!("missing required property {name:?}"),
+                });
+            }
+        }
+        if let Some(min) = self.min_properties {
+            if (object.len() as u64) < min {
+                errors.push(ValidationError {
+                    path: path.to_owned(),
+                    message: format!("must have at least {min} properties"),
+                });
+            }
+        }
+        if let Some(max) = self.max_properties {
+            if object.len() as u64 > max {
+                errors.push(ValidationError {
+                    path: path.to_owned(),
+                    message: format!("must have at most {max} properties"),
+                });
+            }
+        }
+        for (name, value) in object {
+            let here = format!("{path}/{}", name.replace('~', "~0").replace('/', "~1"));
+            match self.properties.iter().find(|(n, _)| n == name) {
+                Some((_, schema)) => schema.validate_at(value, &here, errors),
+                None => match &self.additional_properties {
+                    Some(schema) if schema.never => errors.push(ValidationError {
+                        path: here,
+                        message: "unexpected property".into(),
+                    }),
+                    Some(schema) => schema.validate_at(value, &here, errors),
+                    None => {}
+                },
+            }
+        }
+    }
+
+    /// Reads and validates the body of `req`, returning either the request,
+    /// ready to pass on, or a 400 response describing what is wrong with it.
+    ///
+    /// Requests whose methods have no body, such as GET, are passed on as
+    /// they are.
+    pub(crate) async fn check_request(
+        &self,
+        req: Request<Body>,
+    ) -> Result<std::result::Result<Request<Body>, Response<Body>>> {
+        if matches!(
+            *req.method(),
+            Method::GET | Method::HEAD | Method::OPTIONS | Method::DELETE
+        ) {
+            return Ok(Ok(req));
+        }
+        let (parts, body) = req.into_parts();
+        let bytes = body.collect().await?.to_bytes();
+        let errors = match serde_json::from_slice::<Value>(&bytes) {
+            Ok(value) => self.validate(&value),
+            Err(e) => vec![ValidationError {
+                path: String::new(),
+                message: format!("request body is not valid JSON: {e}"),
+            }],
+        };
+        if errors.is_empty() {
+            return Ok(Ok(Request::from_parts(parts, body::full(bytes))));
+        }
+        let body = serde_json::json!({
+            "error": "request body does not match the schema",
+            "errors": errors,
+        });
+        Ok(Err(Response::builder()
+            .status(StatusCode::BAD_REQUEST)
+            .header(http::header::CONTENT_TYPE, "application/json")
+            .body(body::full(serde_json::to_vec(&body)?.into()))?))
+    }
+}
+
+fn has_type(value: &Value, t: &str) -> bool {
+    match t {
+        "integer" => value.as_f64().is_some_and(|n| n.fract() == 0.0),
+        "number" => value.is_number(),
+        other => type_name(value) == other,
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn order_schema() -> JsonSchema {
+        JsonSchema::compile(&json!({
+            "type": "object",
+            "required": ["sku", "quantity"],
+            "additionalProperties": false,
+            "properties": {
+                "sku": { "type": "string", "pattern": "^[A-Z]{3}-[0-9]+$" },
+                "quantity": { "type": "integer", "minimum": 1, "maximum": 100 },
+                "tags": { "type": "array", "items": { "type": "string" }, "uniqueItems": true },
+                "gift": { "type": "boolean" },
+            }
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn valid_values_have_no_errors() {
+        let errors = order_schema().validate(&json!({
+            "sku": "ABC-123",
+            "quantity": 2,
+            "tags": ["red", "large"],
+        }));
+        assert_eq!(Vec::<ValidationError>::new(), errors);
+    }
+
+    #[test]
+    fn errors_point_at_the_invalid_values() {
+        let errors = order_schema().validate(&json!({
+            "sku": "abc",
+            "quantity": 1.5,
+            "tags": ["red", 7],
+            "note": "leave at door",
+        }));
+        let mut paths = errors.iter().map(|e| e.path.as_str()).collect::<Vec<_>>();
+        paths.sort();
+        assert_eq!(vec!["/note", "/quantity", "/sku", "/tags/1"], paths);
+        let quantity = errors.iter().find(|e| e.path == "/quantity").unwrap();
+        assert_eq!("expected integer, found number", quantity.message);
+
+        let errors = order_schema().validate(&json!({ "sku": "ABC-1" }));
+        assert_eq!("missing required property \"quantity\"", errors[0].message);
+        assert_eq!("", errors[0].path);
+    }
+
+    #[test]
+    fn combinators_are_supported() {
+        let schema = JsonSchema::compile(&json!({
+            "oneOf": [{ "type": "string" }, { "type": "integer", "multipleOf": 5 }]
+        }))
+        .unwrap();
+        assert!(schema.is_valid(&json!("x")));
+        assert!(schema.is_valid(&json!(15)));
+        assert!(!schema.is_valid(&json!(7)));
+        assert!(!schema.is_valid(&json!(null)));
+    }
+
+    #[test]
+    fn unsupported_keywords_are_rejected() {
+        JsonSchema::compile(&json!({ "$ref": "#/definitions/order" })).unwrap_err();
+        JsonSchema::compile(&json!({ "type": "string", "format": "email" })).unwrap();
+    }
+
+    #[tokio::test]
+    async fn invalid_requests_are_answered_with_bad_request() {
+        let req = Request::post("/orders")
+            .body(body::full(r#"{"sku": "ABC-1"}"#.into()))
+            .unwrap();
+        let res = order_schema()
+            .check_request(req)
+            .await
+            .unwrap()
+            .unwrap_err();
+        assert_eq!(StatusCode::BAD_REQUEST, res.status());
+        let body = res.into_body().collect().await.unwrap().to_bytes();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!("", body["errors"][0]["path"]);
+
+        let req = Request::post("/orders")
+            .body(body::full(r#"{"sku": "ABC-1", "quantity": 3}"#.into()))
+            .unwrap();
+        let req = order_schema().check_request(req).await.unwrap().unwrap();
+        let body = req.into_body().collect().await.unwrap().to_bytes();
+        assert_eq!(br#"{"sku": "ABC-1", "quantity": 3}"#, &body[..]);
+    }
+}
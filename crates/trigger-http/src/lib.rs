@@ -1,17 +1,29 @@
 //! Implementation for the Spin HTTP engine.
 
+mod access_log;
+mod canary;
+mod concurrency;
+mod forwarded;
+mod geoip;
 mod handler;
+mod json_schema;
+mod outbound;
+mod redeploy;
+mod replay;
+mod response_cache;
+mod response_headers;
 mod tls;
 mod wagi;
 
 use std::{
     collections::HashMap,
     net::{Ipv4Addr, SocketAddr, ToSocketAddrs},
-    path::PathBuf,
-    sync::Arc,
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock},
+    time::Duration,
 };
 
-use anyhow::{anyhow, Context, Result};
+use anyhow::{anyhow, bail, ensure, Context, Result};
 use async_trait::async_trait;
 use clap::Args;
 use http::{uri::Scheme, StatusCode, Uri};
@@ -31,7 +43,12 @@ use spin_http::{
     routes::{RoutePattern, Router},
 };
 use spin_outbound_networking::{AllowedHostsConfig, OutboundUrl};
-use spin_trigger::{EitherInstancePre, TriggerAppEngine, TriggerExecutor};
+use spin_trigger::{
+    admin,
+    context::TlsInfo,
+    upgrade::{Upgrade, UpgradeAction, Upgrades},
+    EitherInstancePre, TriggerAppEngine, TriggerExecutor,
+};
 use tokio::{
     io::{AsyncRead, AsyncWrite},
     net::TcpListener,
@@ -40,21 +57,81 @@ use tokio::{
 use tracing::log;
 use wasmtime_wasi_http::body::HyperIncomingBody as Body;
 
-use crate::{handler::HttpHandlerExecutor, wagi::WagiHttpExecutor};
+use crate::{
+    access_log::{AccessLog, AccessLogConfig, AccessLogFormat, RequestInfo},
+    canary::Canaries,
+    concurrency::ConcurrencyLimit,
+    forwarded::{IpRange, TrustedProxies},
+    handler::HttpHandlerExecutor,
+    json_schema::JsonSchema,
+    redeploy::{RedeployHook, REDEPLOY_PATH},
+    replay::{RecordedRequest, ReplayBundle},
+    response_cache::{CacheableRequest, Directive, ResponseCache},
+    response_headers::ResponseHeaders,
+    wagi::WagiHttpExecutor,
+};
 
 pub use tls::TlsConfig;
 
 pub(crate) type RuntimeData = HttpRuntimeData;
 pub(crate) type Store = spin_core::Store<RuntimeData>;
 
+/// The trigger currently serving requests, which an upgrade may replace.
+type CurrentTrigger = Arc<RwLock<Arc<HttpTrigger>>>;
+
+const DEFAULT_AFTER_RESPONSE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A request with this header gets a `Server-Timing` response header giving
+/// where the time of its invocation went, as used by `spin bench`.
+pub const SERVER_TIMING_REQUEST_HEADER: &str = "spin-server-timing";
+const SERVER_TIMING: &str = "server-timing";
+
+/// Per-server settings shared by every connection.
+#[derive(Clone)]
+struct ServeOptions {
+    /// Where to record failed requests as replay bundles.
+    record_failures: Option<Arc<Path>>,
+    /// Whether replay bundles include secret variables and credentials.
+    record_secrets: bool,
+    access_log: Option<Arc<AccessLog>>,
+    trusted_proxies: Arc<TrustedProxies>,
+}
+
+/// How new versions of the application are brought into service.
+struct UpgradeOptions {
+    /// A path which must respond successfully before a version goes live.
+    health_check: Option<String>,
+    base_url: String,
+    /// Where staged versions are served.
+    staging_address: Option<SocketAddr>,
+    serve: ServeOptions,
+}
+
 /// The Spin HTTP trigger.
 pub struct HttpTrigger {
     engine: TriggerAppEngine<Self>,
     router: Router,
     // Base path for component routes.
     base: String,
+    // Hosts whose requests are routed by their first label
+    hosts: Vec<String>,
     // Component ID -> component trigger config
     component_trigger_configs: HashMap<String, HttpTriggerConfig>,
+    // Component ID -> headers to set on its responses
+    component_response_headers: HashMap<String, ResponseHeaders>,
+    // Component ID -> schema its request bodies must match
+    component_request_schemas: HashMap<String, JsonSchema>,
+    // Component ID -> how its route's responses are cached
+    component_cache_directives: HashMap<String, Directive>,
+    response_cache: ResponseCache,
+    // Component ID -> limit on its concurrent invocations
+    component_concurrency_limits: HashMap<String, ConcurrencyLimit>,
+    // Component ID -> canaries serving a share of its route's requests
+    component_canaries: HashMap<String, Canaries>,
+    // How long components may keep running once their response is sent
+    after_response_timeout: Duration,
+    // Where requests to redeploy the application are passed on to
+    redeploy: Option<Arc<RedeployHook>>,
 }
 
 #[derive(Args)]
@@ -70,9 +147,102 @@ pub struct CliArgs {
     /// The path to the certificate key to use for https, if this is not set, normal http will be used. The key should be in PKCS#8 format
     #[clap(long, env = "SPIN_TLS_KEY", requires = "tls-cert")]
     pub tls_key: Option<PathBuf>,
+
+    /// When upgrading via `--upgrade-socket`, request this path from the new
+    /// version before routing to it. The upgrade is rolled back unless the
+    /// request succeeds.
+    #[clap(long = "upgrade-health-check")]
+    pub upgrade_health_check: Option<String>,
+
+    /// Serve versions staged with `spin ctl stage` on this address, e.g. for
+    /// smoke tests before they are promoted. Staged versions are served
+    /// over plain HTTP.
+    #[clap(long = "staging-listen", value_parser = parse_listen_addr)]
+    pub staging_address: Option<SocketAddr>,
+
+    /// Write each request that fails with a server error to the given
+    /// directory as a replay bundle, for use with `spin replay`. Bundles
+    /// contain request bodies and variable values, which may be sensitive;
+    /// secret variables and credential headers are left out unless
+    /// `--record-secrets` is given.
+    #[clap(long = "record-failures")]
+    pub record_failures: Option<PathBuf>,
+
+    /// Include secret variable values and credential headers, such as
+    /// `authorization` and `cookie`, in replay bundles.
+    #[clap(long = "record-secrets", requires = "record-failures")]
+    pub record_secrets: bool,
+
+    /// Instead of serving, send the request from the given replay bundle to
+    /// the application, answering its variable lookups and outbound HTTP
+    /// requests from the bundle, and print the response.
+    #[clap(long = "replay", conflicts_with = "record-failures")]
+    pub replay: Option<PathBuf>,
+
+    /// Write a line for each request to the given access log file, or to
+    /// stdout if "-".
+    #[clap(long = "access-log")]
+    pub access_log: Option<PathBuf>,
+
+    /// The access log format: "common", "combined", "json", or a template
+    /// such as "{method} {uri} {status} {component} {latency_ms}". Templates
+    /// may use remote_addr, time, method, uri, protocol, host, status, bytes,
+    /// latency_ms, component, referer and user_agent.
+    #[clap(long = "access-log-format", default_value = "combined")]
+    pub access_log_format: AccessLogFormat,
+
+    /// Rotate the access log file when it reaches this many megabytes.
+    #[clap(long = "access-log-max-size")]
+    pub access_log_max_size: Option<u64>,
+
+    /// The number of rotated access log files to keep.
+    #[clap(long = "access-log-max-files", default_value = "5")]
+    pub access_log_max_files: usize,
+
+    /// Honour the X-Forwarded-For, X-Forwarded-Proto and X-Forwarded-Host
+    /// headers of requests from this address or CIDR range, e.g. a load
+    /// balancer's. The headers are removed from requests from other
+    /// addresses. May be given more than once.
+    #[clap(long = "trusted-proxy", multiple_occurrences = true)]
+    pub trusted_proxies: Vec<IpRange>,
+
+    /// How many seconds a component may keep running once its response has
+    /// been sent, e.g. for work deferred with `spawn_after_response`, before
+    /// it is stopped.
+    #[clap(long = "after-response-timeout", default_value = "5")]
+    pub after_response_timeout: u64,
+
+    /// Print the routing table, in order of precedence, and exit without
+    /// serving. Exact routes take precedence over wildcard routes, and
+    /// longer wildcard prefixes over shorter ones.
+    #[clap(long = "print-routes")]
+    pub print_routes: bool,
+
+    /// Pass requests to redeploy the application, made to /__spin/redeploy,
+    /// to this socket. Set by `spin up --from-git`.
+    #[clap(long = "redeploy-socket", hide = true, requires = "redeploy-secret")]
+    pub redeploy_socket: Option<PathBuf>,
+
+    /// The secret redeploy requests must be signed with.
+    #[clap(
+        long = "redeploy-secret",
+        env = "SPIN_REDEPLOY_SECRET",
+        hide = true,
+        hide_env_values = true
+    )]
+    pub redeploy_secret: Option<String>,
 }
 
 impl CliArgs {
+    fn access_log_config(&self) -> Option<AccessLogConfig> {
+        Some(AccessLogConfig {
+            destination: self.access_log.clone()?,
+            format: self.access_log_format.clone(),
+            max_size: self.access_log_max_size.map(|mb| mb * 1024 * 1024),
+            max_files: self.access_log_max_files,
+        })
+    }
+
     fn into_tls_config(self) -> Option<TlsConfig> {
         match (self.tls_cert, self.tls_key) {
             (Some(cert_path), Some(key_path)) => Some(TlsConfig {
@@ -93,10 +263,10 @@ impl TriggerExecutor for HttpTrigger {
     type RunConfig = CliArgs;
 
     async fn new(engine: TriggerAppEngine<Self>) -> Result<Self> {
-        let mut base = engine
+        let metadata = engine
             .app()
-            .require_metadata(spin_http::trigger::METADATA_KEY)?
-            .base;
+            .require_metadata(spin_http::trigger::METADATA_KEY)?;
+        let mut base = metadata.base;
         if !base.starts_with('/') {
             base = format!("/{base}");
         }
@@ -108,15 +278,21 @@ impl TriggerExecutor for HttpTrigger {
         let (router, duplicate_routes) = Router::build(&base, component_routes)?;
 
         if !duplicate_routes.is_empty() {
-            log::error!("The following component routes are duplicates and will never be used:");
-            for dup in &duplicate_routes {
-                log::error!(
-                    "  {}: {} (duplicate of {})",
-                    dup.replaced_id,
-                    dup.route.full_pattern_non_empty(),
-                    dup.effective_id,
-                );
-            }
+            let duplicates = duplicate_routes
+                .iter()
+                .map(|dup| {
+                    format!(
+                        "  {}: {} (duplicate of {})",
+                        dup.replaced_id,
+                        dup.route.full_pattern_non_empty(),
+                        dup.effective_id,
+                    )
+                })
+                .collect::<Vec<_>>();
+            bail!(
+                "The following component routes are duplicates, so only one of each could be used:\n{}",
+                duplicates.join("\n")
+            );
         }
 
         log::trace!(
@@ -125,21 +301,144 @@ impl TriggerExecutor for HttpTrigger {
             router.routes().collect::<Vec<_>>()
         );
 
-        let component_trigger_configs = engine
+        let component_trigger_configs: HashMap<String, HttpTriggerConfig> = engine
             .trigger_configs()
             .map(|(_, config)| (config.component.clone(), config.clone()))
             .collect();
 
+        let mut component_response_headers = HashMap::new();
+        let mut component_request_schemas = HashMap::new();
+        let mut component_cache_directives = HashMap::new();
+        let mut component_concurrency_limits = HashMap::new();
+        let mut component_canaries = HashMap::new();
+        for (component_id, config) in &component_trigger_configs {
+            if let Some(headers) = ResponseHeaders::from_config(config)
+                .with_context(|| format!("Invalid response headers for route {:?}", config.route))?
+            {
+                component_response_headers.insert(component_id.clone(), headers);
+            }
+            if let Some(schema) = &config.request_schema {
+                let schema = JsonSchema::compile(schema).with_context(|| {
+                    format!("Invalid request schema for route {:?}", config.route)
+                })?;
+                component_request_schemas.insert(component_id.clone(), schema);
+            }
+            if let Some(cache) = &config.response_cache {
+                let directive = Directive::from_config(cache).with_context(|| {
+                    format!("Invalid response cache for route {:?}", config.route)
+                })?;
+                component_cache_directives.insert(component_id.clone(), directive);
+            }
+            if let Some(concurrency) = &config.concurrency {
+                let limit = ConcurrencyLimit::from_config(concurrency)
+                    .with_context(|| format!("Invalid concurrency for route {:?}", config.route))?;
+                component_concurrency_limits.insert(component_id.clone(), limit);
+            }
+            if let Some(canaries) = Canaries::from_config(&config.canary)
+                .with_context(|| format!("Invalid canary for route {:?}", config.route))?
+            {
+                ensure!(
+                    !matches!(config.executor, Some(HttpExecutorType::Wagi(_))),
+                    "Route {:?} has a canary, which the Wagi executor doesn't support",
+                    config.route
+                );
+                for canary in canaries.components() {
+                    ensure!(
+                        engine.app().get_component(canary).is_some(),
+                        "Canary {canary:?} for route {:?} is not a component of the application",
+                        config.route
+                    );
+                }
+                component_canaries.insert(component_id.clone(), canaries);
+            }
+        }
+
         Ok(Self {
             engine,
             router,
             base,
+            hosts: metadata.hosts,
             component_trigger_configs,
+            component_response_headers,
+            component_request_schemas,
+            component_cache_directives,
+            response_cache: Default::default(),
+            component_concurrency_limits,
+            component_canaries,
+            after_response_timeout: DEFAULT_AFTER_RESPONSE_TIMEOUT,
+            redeploy: None,
         })
     }
 
     async fn run(self, config: Self::RunConfig) -> Result<()> {
+        self.run_inner(config, None).await
+    }
+
+    async fn run_with_upgrades(
+        self,
+        config: Self::RunConfig,
+        upgrades: Upgrades<Self>,
+    ) -> Result<()> {
+        self.run_inner(config, Some(upgrades)).await
+    }
+
+    async fn instantiate_pre(
+        engine: &Engine<Self::RuntimeData>,
+        component: &AppComponent,
+        config: &Self::TriggerConfig,
+    ) -> Result<EitherInstancePre<Self::RuntimeData>> {
+        if let Some(HttpExecutorType::Wagi(_)) = &config.executor {
+            // A Wagi program is either a core module, run with WASI Preview 1,
+            // or a WASI CLI command component, run with WASI Preview 2.
+            match component.load_module(engine).await {
+                Ok(module) => Ok(EitherInstancePre::Module(
+                    engine.module_instantiate_pre(&module)?,
+                )),
+                Err(module_err) => {
+                    let comp = component
+                        .load_component(engine)
+                        .await
+                        .map_err(|_| module_err)?;
+                    Ok(EitherInstancePre::Component(engine.instantiate_pre(&comp)?))
+                }
+            }
+        } else {
+            let comp = component.load_component(engine).await?;
+            Ok(EitherInstancePre::Component(engine.instantiate_pre(&comp)?))
+        }
+    }
+}
+
+impl HttpTrigger {
+    async fn run_inner(mut self, config: CliArgs, upgrades: Option<Upgrades<Self>>) -> Result<()> {
+        self.after_response_timeout = Duration::from_secs(config.after_response_timeout);
+        if let (Some(socket), Some(secret)) = (&config.redeploy_socket, &config.redeploy_secret) {
+            self.redeploy = Some(Arc::new(RedeployHook::new(
+                secret.as_bytes(),
+                socket.clone(),
+            )));
+        }
+        if let Some(bundle) = &config.replay {
+            return self.replay(bundle).await;
+        }
+        if config.print_routes {
+            self.print_routing_table();
+            return Ok(());
+        }
+
         let listen_addr = config.address;
+        let health_check = config.upgrade_health_check.clone();
+        let options = ServeOptions {
+            record_failures: config.record_failures.clone().map(Into::into),
+            record_secrets: config.record_secrets,
+            access_log: match config.access_log_config() {
+                Some(access_log) => Some(Arc::new(AccessLog::open(&access_log).with_context(
+                    || format!("Unable to open access log {:?}", access_log.destination),
+                )?)),
+                None => None,
+            },
+            trusted_proxies: Arc::new(TrustedProxies::new(config.trusted_proxies.clone())),
+        };
         let tls = config.into_tls_config();
 
         // Print startup messages
@@ -148,6 +447,101 @@ impl TriggerExecutor for HttpTrigger {
         terminal::step!("\nServing", "{}", base_url);
         log::info!("Serving {}", base_url);
 
+        self.print_routes(&base_url)?;
+
+        let pooled = self.engine.has_instance_pools();
+        let has_jobs = self.engine.has_jobs();
+        let current = Arc::new(RwLock::new(Arc::new(self)));
+
+        if pooled {
+            task::spawn(Self::maintain_instance_pools(current.clone()));
+        }
+        if has_jobs {
+            task::spawn(Self::dispatch_jobs(current.clone()));
+        }
+
+        if let Some(upgrades) = upgrades {
+            task::spawn(Self::apply_upgrades(
+                current.clone(),
+                upgrades,
+                UpgradeOptions {
+                    health_check,
+                    base_url,
+                    staging_address: config.staging_address,
+                    serve: options.clone(),
+                },
+            ));
+        }
+
+        if let Some(tls) = tls {
+            Self::serve_tls(current, listen_addr, tls, options).await?
+        } else {
+            Self::serve(current, listen_addr, options).await?
+        };
+        Ok(())
+    }
+
+    /// Sends the request from a replay bundle, printing the response.
+    async fn replay(&self, bundle: &Path) -> Result<()> {
+        let bundle = ReplayBundle::load(bundle)?;
+        let req = bundle.request.to_request()?;
+        terminal::step!(
+            "Replaying",
+            "{} {}",
+            bundle.request.method,
+            bundle.request.uri
+        );
+        let res = spin_core::replay::replay(
+            bundle.recording,
+            self.handle(req, Scheme::HTTP, (Ipv4Addr::LOCALHOST, 0).into()),
+        )
+        .await?;
+
+        println!("{:?} {}", res.version(), res.status());
+        for (name, value) in res.headers() {
+            println!("{name}: {}", String::from_utf8_lossy(value.as_bytes()));
+        }
+        let body = res.into_body().collect().await?.to_bytes();
+        println!();
+        println!("{}", String::from_utf8_lossy(&body));
+        Ok(())
+    }
+
+    /// Handles the request, writing it to a replay bundle in `record_dir` if
+    /// it fails with a server error.
+    async fn handle_recording_failures(
+        &self,
+        req: Request<Body>,
+        scheme: Scheme,
+        addr: SocketAddr,
+        record_dir: &Path,
+        record_secrets: bool,
+    ) -> Result<Response<Body>> {
+        let (recorded, req) = RecordedRequest::read(req, record_secrets).await?;
+        let Some(recorded) = recorded else {
+            log::debug!("Not recording request with a body too large for a replay bundle");
+            return self.handle(req, scheme, addr).await;
+        };
+        let (res, recording) =
+            spin_core::replay::record(record_secrets, self.handle(req, scheme, addr)).await;
+        let res = res?;
+        if res.status().is_server_error() {
+            let path = recorded.uri.split('?').next().unwrap_or_default();
+            let bundle = ReplayBundle {
+                component: self.router.route(path).ok().map(str::to_owned),
+                request: recorded,
+                status: res.status().as_u16(),
+                recording,
+            };
+            match bundle.write(record_dir) {
+                Ok(path) => log::info!("Recorded failed request to {path:?}"),
+                Err(e) => log::warn!("Failed to record failed request: {e:?}"),
+            }
+        }
+        Ok(res)
+    }
+
+    fn print_routes(&self, base_url: &str) -> Result<()> {
         println!("Available Routes:");
         for (route, component_id) in self.router.routes() {
             println!("  {}: {}{}", component_id, base_url, route);
@@ -157,33 +551,185 @@ impl TriggerExecutor for HttpTrigger {
                 }
             }
         }
+        Ok(())
+    }
 
-        if let Some(tls) = tls {
-            self.serve_tls(listen_addr, tls).await?
-        } else {
-            self.serve(listen_addr).await?
+    /// Prints each route in order of precedence with the component it runs
+    /// and any canaries, noting the routes which take precedence over it for
+    /// some paths.
+    fn print_routing_table(&self) {
+        let routes = self.router.routes_by_precedence();
+        let width = routes
+            .iter()
+            .map(|(route, _)| route.full_pattern_non_empty().len())
+            .max()
+            .unwrap_or_default();
+        println!("Routes, in order of precedence:");
+        for (route, component_id) in routes {
+            let pattern = route.full_pattern_non_empty();
+            let mut line = format!("  {pattern:width$}  {component_id}");
+            if let Some(config) = self.component_trigger_configs.get(component_id) {
+                for canary in &config.canary {
+                    line += &format!(", {} ({}%)", canary.component, canary.weight);
+                }
+            }
+            let overriding = self.router.overriding(route);
+            if !overriding.is_empty() {
+                let overriding = overriding
+                    .iter()
+                    .map(|(route, _)| route.full_pattern_non_empty())
+                    .collect::<Vec<_>>();
+                line += &format!(" (except {})", overriding.join(", "));
+            }
+            println!("{line}");
+        }
+    }
+
+    /// Switches the current trigger to each new version received from
+    /// `upgrades`, provided it passes the health check, or stages it to be
+    /// promoted later. Requests already in progress complete on the version
+    /// that accepted them.
+    async fn apply_upgrades(
+        current: CurrentTrigger,
+        mut upgrades: Upgrades<Self>,
+        options: UpgradeOptions,
+    ) {
+        let mut staged: Option<(String, Arc<HttpTrigger>)> = None;
+        // Serves the staged version, once one has been staged.
+        let mut staging: Option<CurrentTrigger> = None;
+
+        while let Some(Upgrade { action, result }) = upgrades.recv().await {
+            let outcome = match action {
+                UpgradeAction::Replace(executor) => {
+                    let next = Self::prepare_upgrade(&current, executor);
+                    let outcome = match &options.health_check {
+                        Some(path) => next.health_check(path).await,
+                        None => Ok(()),
+                    };
+                    if outcome.is_ok() {
+                        Self::switch_to(&current, next, &options.base_url);
+                    }
+                    outcome
+                }
+                UpgradeAction::Stage { executor, label } => {
+                    let next = Self::prepare_upgrade(&current, executor);
+                    Self::serve_staged(&mut staging, &next, &options)
+                        .await
+                        .map(|()| {
+                            log::info!("Staged application {} as {label:?}", next.engine.app_name);
+                            staged = Some((label, next));
+                        })
+                }
+                UpgradeAction::Promote { label, checks } => match staged.take() {
+                    Some((staged_label, next)) if staged_label == label => {
+                        let mut outcome = Ok(());
+                        for path in options.health_check.iter().chain(&checks) {
+                            outcome = next.health_check(path).await;
+                            if outcome.is_err() {
+                                break;
+                            }
+                        }
+                        if outcome.is_ok() {
+                            Self::switch_to(&current, next, &options.base_url);
+                        } else {
+                            staged = Some((staged_label, next));
+                        }
+                        outcome
+                    }
+                    other => {
+                        staged = other;
+                        Err(anyhow!("no version is staged as {label:?}"))
+                    }
+                },
+            };
+            _ = result.send(outcome);
+        }
+    }
+
+    fn prepare_upgrade(current: &CurrentTrigger, mut executor: Self) -> Arc<Self> {
+        let current = current.read().unwrap();
+        executor.after_response_timeout = current.after_response_timeout;
+        executor.redeploy = current.redeploy.clone();
+        Arc::new(executor)
+    }
+
+    /// Switches live traffic to `next`.
+    fn switch_to(current: &CurrentTrigger, next: Arc<Self>, base_url: &str) {
+        let previous = std::mem::replace(&mut *current.write().unwrap(), next.clone());
+        log::info!(
+            "Upgraded application {} to {}",
+            previous.engine.app_name,
+            next.engine.app_name
+        );
+        if let Err(e) = next.print_routes(base_url) {
+            log::warn!("Failed to print routes: {e:?}");
+        }
+    }
+
+    /// Serves `next` on the staging address, if there is one, starting the
+    /// staging listener the first time a version is staged.
+    async fn serve_staged(
+        staging: &mut Option<CurrentTrigger>,
+        next: &Arc<Self>,
+        options: &UpgradeOptions,
+    ) -> Result<()> {
+        let Some(staging_address) = options.staging_address else {
+            return Ok(());
         };
+        match staging {
+            Some(staging) => *staging.write().unwrap() = next.clone(),
+            None => {
+                let listener = TcpListener::bind(staging_address)
+                    .await
+                    .with_context(|| format!("Unable to listen on {staging_address}"))?;
+                terminal::step!("Staging", "on http://{staging_address}");
+                let current = Arc::new(RwLock::new(next.clone()));
+                let serve = Self::serve_listener(current.clone(), listener, options.serve.clone());
+                task::spawn(async move {
+                    if let Err(e) = serve.await {
+                        log::error!("Staging listener failed: {e:?}");
+                    }
+                });
+                *staging = Some(current);
+            }
+        }
         Ok(())
     }
 
-    async fn instantiate_pre(
-        engine: &Engine<Self::RuntimeData>,
-        component: &AppComponent,
-        config: &Self::TriggerConfig,
-    ) -> Result<EitherInstancePre<Self::RuntimeData>> {
-        if let Some(HttpExecutorType::Wagi(_)) = &config.executor {
-            let module = component.load_module(engine).await?;
-            Ok(EitherInstancePre::Module(
-                engine.module_instantiate_pre(&module)?,
-            ))
-        } else {
-            let comp = component.load_component(engine).await?;
-            Ok(EitherInstancePre::Component(engine.instantiate_pre(&comp)?))
+    /// Keeps the current version's instance pools filled. Each round of
+    /// maintenance is short, so that the pools of a version which has been
+    /// upgraded are dropped along with it.
+    async fn maintain_instance_pools(current: CurrentTrigger) {
+        loop {
+            let trigger = current.read().unwrap().clone();
+            trigger.engine.maintain_instance_pools().await;
         }
     }
-}
 
-impl HttpTrigger {
+    /// Runs the current version's jobs as they fall due.
+    async fn dispatch_jobs(current: CurrentTrigger) {
+        loop {
+            let trigger = current.read().unwrap().clone();
+            trigger.engine.dispatch_jobs().await;
+        }
+    }
+
+    /// Sends a request for `path` to this trigger, failing unless it returns
+    /// a success status.
+    async fn health_check(&self, path: &str) -> Result<()> {
+        let req = Request::get(path).body(body::empty())?;
+        let res = self
+            .handle(req, Scheme::HTTP, (Ipv4Addr::LOCALHOST, 0).into())
+            .await
+            .with_context(|| format!("Health check request for {path} failed"))?;
+        anyhow::ensure!(
+            res.status().is_success(),
+            "Health check request for {path} returned status {}",
+            res.status()
+        );
+        Ok(())
+    }
+
     /// Handles incoming requests using an HTTP executor.
     pub async fn handle(
         &self,
@@ -192,6 +738,7 @@ impl HttpTrigger {
         addr: SocketAddr,
     ) -> Result<Response<Body>> {
         set_req_uri(&mut req, scheme)?;
+        geoip::enrich(&mut req, self.engine.geoip(), addr.ip());
 
         log::info!(
             "Processing request for application {} on URI {}",
@@ -201,6 +748,12 @@ impl HttpTrigger {
 
         let path = req.uri().path();
 
+        if path == REDEPLOY_PATH {
+            if let Some(redeploy) = &self.redeploy {
+                return redeploy.handle(req).await;
+            }
+        }
+
         // Handle well-known spin paths
         if let Some(well_known) = path.strip_prefix(spin_http::WELL_KNOWN_PREFIX) {
             return match well_known {
@@ -211,30 +764,62 @@ impl HttpTrigger {
         }
 
         // Route to app component
+        route_by_host(&mut req, &self.hosts)?;
+        let path = req.uri().path();
         match self.router.route(path) {
-            Ok(component_id) => {
-                let trigger = self.component_trigger_configs.get(component_id).unwrap();
+            Ok(route_component_id) => {
+                let trigger = self
+                    .component_trigger_configs
+                    .get(route_component_id)
+                    .unwrap();
+                // The route's settings apply whichever component serves it.
+                let component_id = match self.component_canaries.get(route_component_id) {
+                    Some(canaries) => canaries.choose(route_component_id),
+                    None => route_component_id,
+                };
 
                 let executor = trigger.executor.as_ref().unwrap_or(&HttpExecutorType::Http);
 
-                let res = match executor {
-                    HttpExecutorType::Http => {
-                        HttpHandlerExecutor
-                            .execute(
-                                &self.engine,
-                                component_id,
-                                &self.base,
-                                &trigger.route,
-                                req,
-                                addr,
-                            )
-                            .await
+                let server_timing = req.headers().contains_key(SERVER_TIMING_REQUEST_HEADER);
+                let cacheable = CacheableRequest::new(&req);
+                if let Some(cacheable) = &cacheable {
+                    if let Some(res) = self.response_cache.lookup(component_id, cacheable)? {
+                        return Ok(res);
                     }
-                    HttpExecutorType::Wagi(wagi_config) => {
-                        let executor = WagiHttpExecutor {
-                            wagi_config: wagi_config.clone(),
-                        };
-                        executor
+                }
+
+                let req = match self.component_request_schemas.get(route_component_id) {
+                    Some(schema) => match schema.check_request(req).await? {
+                        Ok(req) => req,
+                        Err(mut rejection) => {
+                            self.apply_response_headers(route_component_id, &mut rejection);
+                            return Ok(rejection);
+                        }
+                    },
+                    None => req,
+                };
+
+                // Held until the component has responded.
+                let _permit = match self.component_concurrency_limits.get(route_component_id) {
+                    Some(limit) => match limit.acquire().await {
+                        Some(permit) => Some(permit),
+                        None => {
+                            log::debug!("Shedding request for {component_id}: queue is full");
+                            let mut res = Self::overloaded()?;
+                            self.apply_response_headers(route_component_id, &mut res);
+                            return Ok(res);
+                        }
+                    },
+                    None => None,
+                };
+
+                let execution = async {
+                    match executor {
+                        HttpExecutorType::Http => {
+                            HttpHandlerExecutor {
+                                after_response_timeout: self.after_response_timeout,
+                                timeout: trigger.timeout.map(Duration::from_secs),
+                            }
                             .execute(
                                 &self.engine,
                                 component_id,
@@ -244,20 +829,62 @@ impl HttpTrigger {
                                 addr,
                             )
                             .await
+                        }
+                        HttpExecutorType::Wagi(wagi_config) => {
+                            let executor = WagiHttpExecutor {
+                                wagi_config: wagi_config.clone(),
+                            };
+                            executor
+                                .execute(
+                                    &self.engine,
+                                    component_id,
+                                    &self.base,
+                                    &trigger.route,
+                                    req,
+                                    addr,
+                                )
+                                .await
+                        }
                     }
                 };
-                match res {
-                    Ok(res) => Ok(res),
+                let execution = spin_core::audit::scope(component_id, execution);
+                let (res, timings) = admin::time_invocation(component_id, execution).await;
+                let mut res = match res {
+                    Ok(res) => res,
                     Err(e) => {
                         log::error!("Error processing request: {:?}", e);
-                        Self::internal_error(None)
+                        Self::internal_error(None)?
+                    }
+                };
+                self.apply_response_headers(route_component_id, &mut res);
+                let mut res = self
+                    .response_cache
+                    .store(
+                        component_id,
+                        cacheable,
+                        self.component_cache_directives.get(route_component_id),
+                        res,
+                    )
+                    .await?;
+                // Added after caching, as the timings are this request's alone.
+                if server_timing {
+                    if let Ok(value) = http::HeaderValue::from_str(&timings.server_timing()) {
+                        res.headers_mut().insert(SERVER_TIMING, value);
                     }
                 }
+                Ok(res)
             }
             Err(_) => Self::not_found(),
         }
     }
 
+    /// Sets the headers configured for the component's route on a response.
+    fn apply_response_headers(&self, component_id: &str, res: &mut Response<Body>) {
+        if let Some(headers) = self.component_response_headers.get(component_id) {
+            headers.apply(res.headers_mut());
+        }
+    }
+
     /// Returns spin status information.
     fn app_info(&self) -> Result<Response<Body>> {
         let info = AppInfo::new(self.engine.app());
@@ -279,6 +906,24 @@ impl HttpTrigger {
             .body(body)?)
     }
 
+    /// Creates an HTTP 503 response, asking the client not to reuse the
+    /// connection.
+    fn service_unavailable() -> Result<Response<Body>> {
+        Ok(Response::builder()
+            .status(StatusCode::SERVICE_UNAVAILABLE)
+            .header(http::header::CONNECTION, "close")
+            .body(body::empty())?)
+    }
+
+    /// Creates an HTTP 503 response for a request shed because its component
+    /// is handling as many requests as it may, and has as many waiting.
+    fn overloaded() -> Result<Response<Body>> {
+        Ok(Response::builder()
+            .status(StatusCode::SERVICE_UNAVAILABLE)
+            .header(http::header::RETRY_AFTER, "1")
+            .body(body::empty())?)
+    }
+
     /// Creates an HTTP 404 response.
     fn not_found() -> Result<Response<Body>> {
         Ok(Response::builder()
@@ -287,26 +932,60 @@ impl HttpTrigger {
     }
 
     fn serve_connection<S: AsyncRead + AsyncWrite + Unpin + Send + 'static>(
-        self_: Arc<Self>,
+        current: CurrentTrigger,
         stream: S,
-        addr: SocketAddr,
+        scheme: Scheme,
+        peer: SocketAddr,
+        tls_info: Option<TlsInfo>,
+        options: ServeOptions,
     ) {
         task::spawn(async move {
             if let Err(e) = http1::Builder::new()
                 .keep_alive(true)
                 .serve_connection(
                     stream,
-                    service_fn(move |request| {
-                        let self_ = self_.clone();
+                    service_fn(move |mut request| {
+                        // Resolve the current version per request, so that
+                        // kept-alive connections pick up upgrades.
+                        let self_ = current.read().unwrap().clone();
+                        let options = options.clone();
+                        if let Some(tls_info) = &tls_info {
+                            request.extensions_mut().insert(tls_info.clone());
+                        }
+                        let (scheme, addr) =
+                            options
+                                .trusted_proxies
+                                .resolve(&mut request, scheme.clone(), peer);
                         async move {
-                            self_
-                                .handle(
-                                    request
-                                        .map(|body: Incoming| body.map_err(|e| anyhow!(e)).boxed()),
-                                    Scheme::HTTP,
-                                    addr,
-                                )
-                                .await
+                            let log_info = options.access_log.as_ref().map(|_| {
+                                let component = self_.router.route(request.uri().path()).ok();
+                                RequestInfo::new(&request, addr, component)
+                            });
+                            let res = if admin::is_draining() {
+                                Self::service_unavailable()
+                            } else {
+                                let _in_flight = admin::track_request();
+                                let request = request
+                                    .map(|body: Incoming| body.map_err(|e| anyhow!(e)).boxed());
+                                match &options.record_failures {
+                                    Some(dir) => {
+                                        self_
+                                            .handle_recording_failures(
+                                                request,
+                                                scheme,
+                                                addr,
+                                                dir,
+                                                options.record_secrets,
+                                            )
+                                            .await
+                                    }
+                                    None => self_.handle(request, scheme, addr).await,
+                                }
+                            }?;
+                            Ok::<_, anyhow::Error>(match (&options.access_log, log_info) {
+                                (Some(log), Some(info)) => log.wrap(info, res),
+                                _ => res,
+                            })
                         }
                     }),
                 )
@@ -317,22 +996,41 @@ impl HttpTrigger {
         });
     }
 
-    async fn serve(self, listen_addr: SocketAddr) -> Result<()> {
-        let self_ = Arc::new(self);
-
+    async fn serve(
+        current: CurrentTrigger,
+        listen_addr: SocketAddr,
+        options: ServeOptions,
+    ) -> Result<()> {
         let listener = TcpListener::bind(listen_addr)
             .await
             .with_context(|| format!("Unable to listen on {}", listen_addr))?;
+        Self::serve_listener(current, listener, options).await
+    }
 
+    async fn serve_listener(
+        current: CurrentTrigger,
+        listener: TcpListener,
+        options: ServeOptions,
+    ) -> Result<()> {
         loop {
             let (stream, addr) = listener.accept().await?;
-            Self::serve_connection(self_.clone(), stream, addr);
+            Self::serve_connection(
+                current.clone(),
+                stream,
+                Scheme::HTTP,
+                addr,
+                None,
+                options.clone(),
+            );
         }
     }
 
-    async fn serve_tls(self, listen_addr: SocketAddr, tls: TlsConfig) -> Result<()> {
-        let self_ = Arc::new(self);
-
+    async fn serve_tls(
+        current: CurrentTrigger,
+        listen_addr: SocketAddr,
+        tls: TlsConfig,
+        options: ServeOptions,
+    ) -> Result<()> {
         let listener = TcpListener::bind(listen_addr)
             .await
             .with_context(|| format!("Unable to listen on {}", listen_addr))?;
@@ -342,7 +1040,15 @@ impl HttpTrigger {
         loop {
             let (stream, addr) = listener.accept().await?;
             let stream = acceptor.accept(stream).await?;
-            Self::serve_connection(self_.clone(), stream, addr);
+            let tls_info = tls::connection_info(stream.get_ref().1);
+            Self::serve_connection(
+                current.clone(),
+                stream,
+                Scheme::HTTPS,
+                addr,
+                Some(tls_info),
+                options.clone(),
+            );
         }
     }
 }
@@ -379,6 +1085,25 @@ fn set_req_uri(req: &mut Request<Body>, scheme: Scheme) -> Result<()> {
     Ok(())
 }
 
+/// Routes a request for one of the given hosts as though its path began with
+/// the first label of the host, e.g. `hello.example.com/x` as `/hello/x`.
+fn route_by_host(req: &mut Request<Body>, hosts: &[String]) -> Result<()> {
+    let Some(label) = req.uri().host().and_then(|host| host.split('.').next()) else {
+        return Ok(());
+    };
+    let Some(host) = hosts.iter().find(|h| h.eq_ignore_ascii_case(label)) else {
+        return Ok(());
+    };
+    let path_and_query = match req.uri().path_and_query() {
+        Some(path_and_query) => format!("/{host}{path_and_query}"),
+        None => format!("/{host}"),
+    };
+    let mut parts = req.uri().clone().into_parts();
+    parts.path_and_query = Some(path_and_query.parse()?);
+    *req.uri_mut() = Uri::from_parts(parts)?;
+    Ok(())
+}
+
 // We need to make the following pieces of information available to both executors.
 // While the values we set are identical, the way they are passed to the
 // modules is going to be different, so each executor must must use the info
@@ -529,7 +1254,18 @@ impl OutboundWasiHttpHandler for HttpRuntimeData {
             anyhow::bail!("destination-not-allowed (error 1)")
         }
 
-        wasmtime_wasi_http::types::default_send_request(data, request)
+        // Requests to a service go to one of its instances.
+        if let Some(target) = outbound_http::discovery::resolve_url(&uri_string)? {
+            let uri: Uri = target.parse()?;
+            // We know that `uri` has an authority because it is an absolute URL
+            request.authority = uri.authority().unwrap().as_str().to_owned();
+            *request.request.uri_mut() = uri;
+        }
+
+        spin_core::audit::record_outbound("https", &uri_string);
+        // As with Spin HTTP requests, connections come from the shared pool
+        // and go through the configured proxies.
+        outbound::send_request(data, request)
     }
 }
 
@@ -540,6 +1276,7 @@ mod tests {
     use anyhow::Result;
     use serde::Deserialize;
     use spin_testing::test_socket_addr;
+    use spin_trigger::upgrade::Upgrader;
 
     use super::*;
 
@@ -698,6 +1435,36 @@ mod tests {
             .build_trigger()
             .await;
 
+        assert_wagi_test_response(&trigger).await
+    }
+
+    #[tokio::test]
+    async fn test_wagi_http_command_component() -> Result<()> {
+        // The same program as in `test_wagi_http`, as a WASI CLI command
+        // component rather than a core module.
+        let module = std::fs::read(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/../../target/test-programs/wagi-test.wasm"
+        ))?;
+        let dir = tempfile::tempdir()?;
+        let component_path = dir.path().join("wagi-test-component.wasm");
+        std::fs::write(
+            &component_path,
+            spin_componentize::componentize_command(&module)?,
+        )?;
+
+        let trigger: HttpTrigger = spin_testing::HttpTestConfig::default()
+            .module_path(&component_path)
+            .http_wagi_trigger("/test", Default::default())
+            .build_trigger()
+            .await;
+
+        assert_wagi_test_response(&trigger).await
+    }
+
+    /// Sends a request to the `wagi-test` program, checking that it saw the
+    /// request's path, query and headers.
+    async fn assert_wagi_test_response(trigger: &HttpTrigger) -> Result<()> {
         let body = body::full(Bytes::from_static("Fermyon".as_bytes()));
         let req = http::Request::builder()
             .method("POST")
@@ -727,10 +1494,156 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn guests_can_read_the_request_context() -> Result<()> {
+        let trigger: HttpTrigger = spin_testing::HttpTestConfig::default()
+            .test_program("context-test.wasm")
+            .http_spin_trigger("/context")
+            .build_trigger()
+            .await;
+
+        let req = http::Request::get("http://localhost/context").body(body::empty())?;
+        let res = trigger
+            .handle(req, Scheme::HTTP, test_socket_addr())
+            .await?;
+        assert_eq!(res.status(), StatusCode::OK);
+        let body_bytes = res.into_body().collect().await?.to_bytes();
+        assert_eq!(
+            format!(
+                "trigger=http app=test-app component=test-component remote={} tls=false",
+                test_socket_addr()
+            ),
+            std::str::from_utf8(&body_bytes)?
+        );
+        Ok(())
+    }
+
+    async fn test_trigger(route: &str) -> HttpTrigger {
+        spin_testing::HttpTestConfig::default()
+            .test_program("rust-http-test.wasm")
+            .http_spin_trigger(route)
+            .build_trigger()
+            .await
+    }
+
+    /// Starts applying upgrades to a trigger serving `/current`.
+    async fn upgradable_trigger() -> (CurrentTrigger, Upgrader<HttpTrigger>) {
+        let current = Arc::new(RwLock::new(Arc::new(test_trigger("/current").await)));
+        let (upgrader, upgrades) = Upgrader::new();
+        let options = UpgradeOptions {
+            health_check: None,
+            base_url: "http://127.0.0.1:3000".into(),
+            staging_address: None,
+            serve: ServeOptions {
+                record_failures: None,
+                record_secrets: false,
+                access_log: None,
+                trusted_proxies: Default::default(),
+            },
+        };
+        task::spawn(HttpTrigger::apply_upgrades(
+            current.clone(),
+            upgrades,
+            options,
+        ));
+        (current, upgrader)
+    }
+
+    /// Whether the current trigger serves `route`.
+    async fn serves(current: &CurrentTrigger, route: &str) -> Result<bool> {
+        let trigger = current.read().unwrap().clone();
+        // The test program checks for these headers and query string.
+        let req = http::Request::post(format!("http://localhost{route}?abc=def"))
+            .header("x-custom-foo", "bar")
+            .header("x-custom-foo2", "bar2")
+            .body(body::full(Bytes::from_static(b"Fermyon")))?;
+        let res = trigger
+            .handle(req, Scheme::HTTP, test_socket_addr())
+            .await?;
+        Ok(res.status() == StatusCode::OK)
+    }
+
+    #[tokio::test]
+    async fn staged_versions_are_promoted_by_label() -> Result<()> {
+        let (current, upgrader) = upgradable_trigger().await;
+
+        upgrader
+            .stage(test_trigger("/next").await, "canary".into())
+            .await?;
+        // Staging leaves the current version serving.
+        assert!(serves(&current, "/current").await?);
+        assert!(!serves(&current, "/next").await?);
+
+        let err = upgrader.promote("other".into(), vec![]).await.unwrap_err();
+        assert_eq!("no version is staged as \"other\"", err.to_string());
+        assert!(serves(&current, "/current").await?);
+
+        upgrader.promote("canary".into(), vec![]).await?;
+        assert!(serves(&current, "/next").await?);
+        assert!(!serves(&current, "/current").await?);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn failed_checks_keep_the_version_staged() -> Result<()> {
+        let (current, upgrader) = upgradable_trigger().await;
+        upgrader
+            .stage(test_trigger("/next").await, "canary".into())
+            .await?;
+
+        assert!(upgrader
+            .promote("canary".into(), vec!["/missing".into()])
+            .await
+            .is_err());
+        assert!(serves(&current, "/current").await?);
+
+        upgrader.promote("canary".into(), vec![]).await?;
+        assert!(serves(&current, "/next").await?);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn promoting_with_nothing_staged_fails() -> Result<()> {
+        let (current, upgrader) = upgradable_trigger().await;
+
+        let err = upgrader.promote("canary".into(), vec![]).await.unwrap_err();
+        assert_eq!("no version is staged as \"canary\"", err.to_string());
+        assert!(serves(&current, "/current").await?);
+        Ok(())
+    }
+
     #[test]
     fn parse_listen_addr_prefers_ipv4() {
         let addr = parse_listen_addr("localhost:12345").unwrap();
         assert_eq!(addr.ip(), Ipv4Addr::LOCALHOST);
         assert_eq!(addr.port(), 12345);
     }
+
+    #[test]
+    fn routes_requests_by_host_label() -> Result<()> {
+        let hosts = vec!["hello".to_owned()];
+        let route = |uri: &str| -> Result<String> {
+            let mut req = http::Request::builder().uri(uri).body(body::empty())?;
+            route_by_host(&mut req, &hosts)?;
+            Ok(req.uri().to_string())
+        };
+
+        assert_eq!(
+            "http://hello.example.com/hello/x?y=z",
+            route("http://hello.example.com/x?y=z")?
+        );
+        assert_eq!(
+            "http://Hello.localhost:3000/hello/",
+            route("http://Hello.localhost:3000/")?
+        );
+        assert_eq!(
+            "http://other.example.com/x",
+            route("http://other.example.com/x")?
+        );
+        assert_eq!(
+            "http://localhost/hello/x",
+            route("http://localhost/hello/x")?
+        );
+        Ok(())
+    }
 }
@@ -0,0 +1,148 @@
+//! Sending `wasi:http/outgoing-handler` requests through the shared
+//! outbound HTTP connection pool.
+//!
+//! Rather than opening a connection for each request, as wasmtime's
+//! default handler does, requests are sent with the client from
+//! [`outbound_http::pool`], so that connections are reused across
+//! invocations, requests are subject to the pool's per-host limit, and they
+//! go through the proxies described in [`outbound_http::proxy`].
+
+use std::{
+    pin::Pin,
+    sync::Mutex,
+    task::{Context, Poll},
+};
+
+use anyhow::anyhow;
+use futures::{stream::BoxStream, StreamExt};
+use http_body_util::BodyExt;
+use hyper::body::{Bytes, Frame};
+use outbound_http::pool::RequestGuard;
+use wasmtime::component::Resource;
+use wasmtime_wasi::preview2;
+use wasmtime_wasi_http::{
+    bindings::http::types::Error,
+    types::{HostFutureIncomingResponse, IncomingResponseInternal, OutgoingRequest},
+    WasiHttpView,
+};
+
+/// Sends `request` through the pooled client, returning the future of its
+/// response.
+pub(crate) fn send_request(
+    view: &mut impl WasiHttpView,
+    OutgoingRequest {
+        use_tls,
+        authority,
+        request,
+        connect_timeout,
+        first_byte_timeout,
+        between_bytes_timeout,
+    }: OutgoingRequest,
+) -> wasmtime::Result<Resource<HostFutureIncomingResponse>> {
+    let handle = preview2::spawn(async move {
+        let (parts, body) = request.into_parts();
+        let url = match parts.uri.scheme() {
+            Some(_) => parts.uri.to_string(),
+            None => {
+                let scheme = if use_tls { "https" } else { "http" };
+                let path_and_query = parts.uri.path_and_query().map_or("/", |p| p.as_str());
+                format!("{scheme}://{authority}{path_and_query}")
+            }
+        };
+        let url =
+            reqwest::Url::parse(&url).map_err(|e| anyhow!(Error::InvalidUrl(e.to_string())))?;
+
+        let request = outbound_http::pool::client()
+            .request(parts.method, url.clone())
+            .headers(parts.headers)
+            .body(reqwest::Body::wrap_stream(data_stream(body)));
+        // The client connects as part of sending, so the connect timeout
+        // is counted towards the time to the first byte.
+        let guard = outbound_http::pool::acquire(url.host_str().unwrap_or_default()).await;
+        let timeout = connect_timeout.saturating_add(first_byte_timeout);
+        let resp = tokio::time::timeout(timeout, request.send())
+            .await
+            .map_err(|_| anyhow!(Error::TimeoutError("first byte timed out".to_owned())))?
+            .map_err(send_error)?;
+
+        let mut builder = hyper::Response::builder()
+            .status(resp.status())
+            .version(resp.version());
+        if let Some(headers) = builder.headers_mut() {
+            *headers = resp.headers().clone();
+        }
+        let body = PooledBody {
+            stream: Mutex::new(resp.bytes_stream().boxed()),
+            _guard: guard,
+        };
+        let resp = builder
+            .body(body.boxed())
+            .map_err(|e| anyhow!(Error::UnexpectedError(e.to_string())))?;
+        Ok::<_, anyhow::Error>(IncomingResponseInternal {
+            resp,
+            // The pool drives the connection, so there is no worker.
+            worker: preview2::spawn(async { Ok(()) }),
+            between_bytes_timeout,
+        })
+    });
+    Ok(view
+        .table()
+        .push_resource(HostFutureIncomingResponse::new(handle))?)
+}
+
+fn send_error(err: reqwest::Error) -> anyhow::Error {
+    let err = if err.is_timeout() {
+        Error::TimeoutError(err.to_string())
+    } else if err.is_connect() {
+        // As for wasmtime's default handler, a host which can't be
+        // connected to is an invalid URL.
+        Error::InvalidUrl(err.to_string())
+    } else {
+        Error::ProtocolError(err.to_string())
+    };
+    anyhow!(err)
+}
+
+/// The data frames of a request body, as the client sends them.
+fn data_stream<B>(body: B) -> impl futures::Stream<Item = anyhow::Result<Bytes>> + Send + Sync
+where
+    B: hyper::body::Body<Data = Bytes> + Send + Sync + Unpin + 'static,
+    B::Error: Into<anyhow::Error>,
+{
+    futures::stream::unfold(body, |mut body| async move {
+        loop {
+            match body.frame().await? {
+                Ok(frame) => match frame.into_data() {
+                    Ok(data) => return Some((Ok(data), body)),
+                    // Trailers can't be sent through the client.
+                    Err(_) => continue,
+                },
+                Err(e) => return Some((Err(e.into()), body)),
+            }
+        }
+    })
+}
+
+/// A response body read from the pool, which holds its request's place
+/// under the per-host limit until it is dropped.
+struct PooledBody {
+    // The stream is only polled through `&mut self`, so the mutex is never
+    // contended; it makes the body `Sync`, as a boxed body must be.
+    stream: Mutex<BoxStream<'static, reqwest::Result<Bytes>>>,
+    _guard: RequestGuard,
+}
+
+impl hyper::body::Body for PooledBody {
+    type Data = Bytes;
+    type Error = anyhow::Error;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Bytes>, anyhow::Error>>> {
+        let stream = self.get_mut().stream.get_mut().unwrap();
+        stream
+            .poll_next_unpin(cx)
+            .map(|chunk| chunk.map(|chunk| chunk.map(Frame::data).map_err(|e| anyhow!(e))))
+    }
+}
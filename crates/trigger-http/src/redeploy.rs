@@ -0,0 +1,178 @@
+//! The `/__spin/redeploy` endpoint, which lets a webhook redeploy an
+//! application run with `spin up --from-git`.
+//!
+//! Requests must be signed the way GitHub signs webhook deliveries: the
+//! `X-Hub-Signature-256` header carries `sha256=` and the hex HMAC-SHA256 of
+//! the body, keyed with the webhook secret. A signed request is passed on to
+//! `spin up` over its redeploy socket, which pulls the branch and upgrades
+//! the application if it has changed.
+
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use hmac::{Hmac, Mac};
+use http::{header::CONTENT_LENGTH, Method, StatusCode};
+use http_body_util::{BodyExt, LengthLimitError, Limited};
+use hyper::{Request, Response};
+use sha2::Sha256;
+use spin_http::body;
+use tokio::{io::AsyncWriteExt, net::UnixStream};
+use tracing::log;
+use wasmtime_wasi_http::body::HyperIncomingBody as Body;
+
+/// The path the endpoint is served on.
+pub(crate) const REDEPLOY_PATH: &str = "/__spin/redeploy";
+
+const SIGNATURE_HEADER: &str = "x-hub-signature-256";
+
+/// The largest body read, which is the most GitHub sends in a delivery.
+const MAX_BODY_SIZE: usize = 25 * 1024 * 1024;
+
+/// Where signed redeploy requests are passed on to.
+pub(crate) struct RedeployHook {
+    secret: Vec<u8>,
+    socket: PathBuf,
+}
+
+impl RedeployHook {
+    pub(crate) fn new(secret: impl Into<Vec<u8>>, socket: PathBuf) -> Self {
+        Self {
+            secret: secret.into(),
+            socket,
+        }
+    }
+
+    /// Handles a request for the redeploy endpoint.
+    pub(crate) async fn handle(&self, req: Request<Body>) -> Result<Response<Body>> {
+        if req.method() != Method::POST {
+            return respond(StatusCode::METHOD_NOT_ALLOWED, "");
+        }
+        let too_large = req
+            .headers()
+            .get(CONTENT_LENGTH)
+            .and_then(|value| value.to_str().ok()?.parse::<u64>().ok())
+            .is_some_and(|len| len > MAX_BODY_SIZE as u64);
+        if too_large {
+            return respond(StatusCode::PAYLOAD_TOO_LARGE, "Request body too large\n");
+        }
+        let (parts, body) = req.into_parts();
+        let body = match Limited::new(body, MAX_BODY_SIZE).collect().await {
+            Ok(body) => body.to_bytes(),
+            Err(e) if e.is::<LengthLimitError>() => {
+                return respond(StatusCode::PAYLOAD_TOO_LARGE, "Request body too large\n");
+            }
+            Err(e) => return Err(anyhow::anyhow!(e)),
+        };
+        let signature = parts
+            .headers
+            .get(SIGNATURE_HEADER)
+            .and_then(|value| value.to_str().ok());
+        if !signature.is_some_and(|signature| self.verify(&body, signature)) {
+            log::warn!("Rejected redeploy request with a missing or invalid signature");
+            return respond(StatusCode::UNAUTHORIZED, "Invalid signature\n");
+        }
+
+        if let Err(e) = self.notify().await {
+            log::error!("Unable to request a redeploy: {e:?}");
+            return respond(StatusCode::SERVICE_UNAVAILABLE, "Unable to redeploy\n");
+        }
+        respond(StatusCode::ACCEPTED, "Redeploying\n")
+    }
+
+    /// Returns whether `signature` is the signature of `body`.
+    fn verify(&self, body: &[u8], signature: &str) -> bool {
+        let Some(Ok(signature)) = signature.strip_prefix("sha256=").map(hex::decode) else {
+            return false;
+        };
+        let Ok(mut mac) = Hmac::<Sha256>::new_from_slice(&self.secret) else {
+            return false;
+        };
+        mac.update(body);
+        // Compares in constant time.
+        mac.verify_slice(&signature).is_ok()
+    }
+
+    async fn notify(&self) -> Result<()> {
+        let mut stream = UnixStream::connect(&self.socket)
+            .await
+            .with_context(|| format!("Unable to connect to {:?}", self.socket))?;
+        stream.write_all(b"redeploy\n").await?;
+        Ok(())
+    }
+}
+
+fn respond(status: StatusCode, message: &'static str) -> Result<Response<Body>> {
+    Ok(Response::builder()
+        .status(status)
+        .body(body::full(message.into()))?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sign(secret: &[u8], body: &[u8]) -> String {
+        let mut mac = Hmac::<Sha256>::new_from_slice(secret).unwrap();
+        mac.update(body);
+        format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+    }
+
+    #[test]
+    fn signatures_are_verified() {
+        let hook = RedeployHook::new("It's a Secret to Everybody", "unused".into());
+        let body = b"Hello, World!";
+
+        // The example from GitHub's webhook documentation.
+        let signature = "sha256=757107ea0eb2509fc211221cce984b8a37570b6d7586c22c46f4379c8b043e17";
+        assert_eq!(signature, sign(b"It's a Secret to Everybody", body));
+        assert!(hook.verify(body, signature));
+
+        assert!(!hook.verify(b"Hello, World?", signature));
+        assert!(!hook.verify(body, &sign(b"another secret", body)));
+        assert!(!hook.verify(body, signature.trim_start_matches("sha256=")));
+        assert!(!hook.verify(body, "sha256=not hex"));
+    }
+
+    #[tokio::test]
+    async fn only_signed_posts_are_accepted() -> Result<()> {
+        let hook = RedeployHook::new("secret", "/nonexistent/redeploy.sock".into());
+
+        let req = Request::get(REDEPLOY_PATH).body(body::empty())?;
+        let res = hook.handle(req).await?;
+        assert_eq!(StatusCode::METHOD_NOT_ALLOWED, res.status());
+
+        let req = Request::post(REDEPLOY_PATH)
+            .header(SIGNATURE_HEADER, sign(b"wrong", b"{}"))
+            .body(body::full("{}".into()))?;
+        let res = hook.handle(req).await?;
+        assert_eq!(StatusCode::UNAUTHORIZED, res.status());
+
+        // Signed, but there is nothing listening to pass it on to.
+        let req = Request::post(REDEPLOY_PATH)
+            .header(SIGNATURE_HEADER, sign(b"secret", b"{}"))
+            .body(body::full("{}".into()))?;
+        let res = hook.handle(req).await?;
+        assert_eq!(StatusCode::SERVICE_UNAVAILABLE, res.status());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn large_bodies_are_rejected() -> Result<()> {
+        let hook = RedeployHook::new("secret", "/nonexistent/redeploy.sock".into());
+
+        let req = Request::post(REDEPLOY_PATH)
+            .header(CONTENT_LENGTH, MAX_BODY_SIZE + 1)
+            .body(body::full("{}".into()))?;
+        let res = hook.handle(req).await?;
+        assert_eq!(StatusCode::PAYLOAD_TOO_LARGE, res.status());
+
+        // A body without a length is read only up to the limit.
+        let large = vec![b' '; MAX_BODY_SIZE + 1];
+        let req = Request::post(REDEPLOY_PATH)
+            .header(SIGNATURE_HEADER, sign(b"secret", &large))
+            .body(body::full(large.into()))?;
+        let res = hook.handle(req).await?;
+        assert_eq!(StatusCode::PAYLOAD_TOO_LARGE, res.status());
+        Ok(())
+    }
+}
@@ -0,0 +1,243 @@
+//! Recording failed requests into replay bundles, and replaying them.
+//!
+//! With `--record-failures <DIR>`, each request is recorded while it runs,
+//! and if the response is a server error the request is written to `DIR` as
+//! a replay bundle, along with the variable values and outbound HTTP
+//! responses the component saw. `--replay <BUNDLE>` sends the bundled
+//! request to the application once, answering the component's variable
+//! lookups and outbound requests from the bundle, and prints the response.
+//!
+//! Unless `--record-secrets` is given, bundles leave out the values of
+//! secret variables and of the request's credential headers, such as
+//! `authorization` and `cookie`. Requests with bodies larger than
+//! [`MAX_RECORDED_BODY_SIZE`] are handled as usual but not recorded.
+
+use std::{
+    path::{Path, PathBuf},
+    pin::Pin,
+    sync::atomic::{AtomicU64, Ordering},
+    task::{Context as TaskContext, Poll},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{Context, Result};
+use http::{HeaderName, HeaderValue, Request};
+use http_body_util::{combinators::BoxBody, BodyExt};
+use hyper::body::{Bytes, Frame};
+use serde::{Deserialize, Serialize};
+use spin_core::replay::Recording;
+use spin_http::body;
+use wasmtime_wasi_http::body::HyperIncomingBody as Body;
+
+static NEXT_BUNDLE: AtomicU64 = AtomicU64::new(0);
+
+/// The largest request body that is recorded.
+pub const MAX_RECORDED_BODY_SIZE: usize = 1024 * 1024;
+
+/// The request headers whose values are left out of bundles unless secrets
+/// are recorded.
+const REDACTED_HEADERS: [&str; 3] = ["authorization", "cookie", "proxy-authorization"];
+
+/// Recorded in place of a value which was left out of a bundle.
+const REDACTED: &str = "<redacted>";
+
+/// A failed request, with what the component learned while handling it.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ReplayBundle {
+    /// The component the request was routed to, if any.
+    pub component: Option<String>,
+    /// The request.
+    pub request: RecordedRequest,
+    /// The status of the failed response.
+    pub status: u16,
+    /// The variable values and outbound responses seen by the component.
+    #[serde(flatten)]
+    pub recording: Recording,
+}
+
+/// An incoming request, with its body.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RecordedRequest {
+    /// The request method, e.g. "POST".
+    pub method: String,
+    /// The request path and query.
+    pub uri: String,
+    /// The request headers.
+    pub headers: Vec<(String, String)>,
+    /// The request body.
+    pub body: Vec<u8>,
+}
+
+impl RecordedRequest {
+    /// Reads the whole of `req`, returning the recording and a request with
+    /// the same contents to be handled in its place. The values of credential
+    /// headers are left out unless `include_secrets` is set. If the body is
+    /// larger than [`MAX_RECORDED_BODY_SIZE`], the request is not recorded,
+    /// and only as much of it is read as is needed to find that out.
+    pub async fn read(
+        req: Request<Body>,
+        include_secrets: bool,
+    ) -> Result<(Option<Self>, Request<Body>)> {
+        let (parts, mut incoming) = req.into_parts();
+        let mut bytes = Vec::new();
+        while let Some(frame) = incoming.frame().await {
+            let Ok(data) = frame?.into_data() else {
+                continue;
+            };
+            bytes.extend_from_slice(&data);
+            if bytes.len() > MAX_RECORDED_BODY_SIZE {
+                let body = PrefixedBody {
+                    prefix: Some(bytes.into()),
+                    rest: incoming,
+                };
+                return Ok((None, Request::from_parts(parts, BoxBody::new(body))));
+            }
+        }
+        let recorded = Self {
+            method: parts.method.to_string(),
+            uri: parts.uri.to_string(),
+            headers: parts
+                .headers
+                .iter()
+                .filter_map(|(k, v)| {
+                    let value = if include_secrets || !REDACTED_HEADERS.contains(&k.as_str()) {
+                        v.to_str().ok()?
+                    } else {
+                        REDACTED
+                    };
+                    Some((k.to_string(), value.to_owned()))
+                })
+                .collect(),
+            body: bytes.clone(),
+        };
+        Ok((
+            Some(recorded),
+            Request::from_parts(parts, body::full(bytes.into())),
+        ))
+    }
+
+    /// Builds a request with the recorded contents.
+    pub fn to_request(&self) -> Result<Request<Body>> {
+        let mut builder = Request::builder()
+            .method(self.method.as_str())
+            .uri(self.uri.as_str());
+        for (name, value) in &self.headers {
+            builder = builder.header(
+                HeaderName::try_from(name.as_str())?,
+                HeaderValue::try_from(value.as_str())?,
+            );
+        }
+        Ok(builder.body(body::full(self.body.clone().into()))?)
+    }
+}
+
+/// A body which has had its first bytes read already.
+struct PrefixedBody {
+    prefix: Option<Bytes>,
+    rest: Body,
+}
+
+impl hyper::body::Body for PrefixedBody {
+    type Data = Bytes;
+    type Error = <Body as hyper::body::Body>::Error;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut TaskContext<'_>,
+    ) -> Poll<Option<Result<Frame<Bytes>, Self::Error>>> {
+        match self.prefix.take() {
+            Some(prefix) => Poll::Ready(Some(Ok(Frame::data(prefix)))),
+            None => Pin::new(&mut self.rest).poll_frame(cx),
+        }
+    }
+}
+
+impl ReplayBundle {
+    /// Reads a bundle written by [`ReplayBundle::write`].
+    pub fn load(path: &Path) -> Result<Self> {
+        let json = std::fs::read(path).with_context(|| format!("Failed to read {path:?}"))?;
+        serde_json::from_slice(&json).with_context(|| format!("Invalid replay bundle {path:?}"))
+    }
+
+    /// Writes the bundle to a new file in `dir`, returning its path.
+    pub fn write(&self, dir: &Path) -> Result<PathBuf> {
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("Failed to create replay directory {dir:?}"))?;
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        let n = NEXT_BUNDLE.fetch_add(1, Ordering::Relaxed);
+        let path = dir.join(format!("replay-{timestamp}-{n}.json"));
+        std::fs::write(&path, serde_json::to_vec_pretty(self)?)
+            .with_context(|| format!("Failed to write replay bundle {path:?}"))?;
+        Ok(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn recorded_requests_round_trip() {
+        let req = Request::post("/orders?id=3")
+            .header("content-type", "application/json")
+            .body(body::full("{}".into()))
+            .unwrap();
+        let (recorded, req) = RecordedRequest::read(req, false).await.unwrap();
+        let recorded = recorded.unwrap();
+        assert_eq!("POST", recorded.method);
+        assert_eq!("/orders?id=3", recorded.uri);
+        assert_eq!(b"{}", recorded.body.as_slice());
+
+        let rebuilt = recorded.to_request().unwrap();
+        assert_eq!(req.uri(), rebuilt.uri());
+        assert_eq!(req.headers(), rebuilt.headers());
+        let body = rebuilt.into_body().collect().await.unwrap().to_bytes();
+        assert_eq!("{}", body);
+    }
+
+    #[tokio::test]
+    async fn credentials_are_redacted_unless_secrets_are_recorded() {
+        let request = || {
+            Request::get("/")
+                .header("authorization", "Bearer token")
+                .header("cookie", "session=abc")
+                .header("accept", "text/html")
+                .body(body::empty())
+                .unwrap()
+        };
+        let header = |recorded: &RecordedRequest, name: &str| {
+            recorded
+                .headers
+                .iter()
+                .find(|(n, _)| n == name)
+                .map(|(_, v)| v.clone())
+                .unwrap()
+        };
+
+        let (recorded, req) = RecordedRequest::read(request(), false).await.unwrap();
+        let recorded = recorded.unwrap();
+        assert_eq!(REDACTED, header(&recorded, "authorization"));
+        assert_eq!(REDACTED, header(&recorded, "cookie"));
+        assert_eq!("text/html", header(&recorded, "accept"));
+        // The request that is handled keeps its credentials.
+        assert_eq!("Bearer token", req.headers()["authorization"]);
+
+        let (recorded, _) = RecordedRequest::read(request(), true).await.unwrap();
+        assert_eq!("Bearer token", header(&recorded.unwrap(), "authorization"));
+    }
+
+    #[tokio::test]
+    async fn large_bodies_are_not_recorded() {
+        let content = vec![b'x'; MAX_RECORDED_BODY_SIZE + 1];
+        let req = Request::post("/upload")
+            .body(body::full(content.clone().into()))
+            .unwrap();
+        let (recorded, req) = RecordedRequest::read(req, false).await.unwrap();
+        assert!(recorded.is_none());
+        let body = req.into_body().collect().await.unwrap().to_bytes();
+        assert_eq!(content, body);
+    }
+}
@@ -0,0 +1,543 @@
+//! An in-host cache of responses, so that repeated requests to a route are
+//! served without instantiating its component.
+//!
+//! A response is cached if its route has a `response_cache` in the manifest,
+//! or if the component sets the `x-spin-cache` header, e.g.
+//! `x-spin-cache: max-age=60, key=path, vary="accept-language"`. The header
+//! overrides the manifest for that response, and `x-spin-cache: no-store`
+//! prevents it being cached. The header is removed before the response is
+//! sent.
+//!
+//! Only responses to `GET` and `HEAD` requests with a heuristically
+//! cacheable status and no `set-cookie` header are cached. Requests with
+//! credentials (`authorization` or `cookie` headers) bypass the cache unless
+//! those headers are among the `vary` headers, so that one user's response is
+//! never served to another.
+
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use anyhow::{bail, Context, Result};
+use http::{
+    header::{HeaderName, AGE, AUTHORIZATION, COOKIE, SET_COOKIE},
+    HeaderMap, HeaderValue, Method, StatusCode,
+};
+use http_body_util::BodyExt;
+use hyper::{
+    body::{Body as _, Bytes},
+    Request, Response,
+};
+use lru::LruCache;
+use spin_http::{
+    body,
+    config::{ResponseCacheConfig, ResponseCacheKey},
+};
+use wasmtime_wasi_http::body::HyperIncomingBody as Body;
+
+/// The response header with which a component controls caching.
+pub(crate) const DIRECTIVE_HEADER: HeaderName = HeaderName::from_static("x-spin-cache");
+
+/// The most bytes of response bodies and headers held in the cache.
+const MAX_CACHE_SIZE: usize = 64 * 1024 * 1024;
+
+/// The largest response body which is cached.
+const MAX_BODY_SIZE: usize = 1024 * 1024;
+
+/// Statuses which may be cached without explicit freshness information, per
+/// RFC 9110 section 15.1, less those Spin components rarely return.
+const CACHEABLE_STATUSES: &[StatusCode] = &[
+    StatusCode::OK,
+    StatusCode::NON_AUTHORITATIVE_INFORMATION,
+    StatusCode::NO_CONTENT,
+    StatusCode::MOVED_PERMANENTLY,
+    StatusCode::NOT_FOUND,
+    StatusCode::GONE,
+];
+
+/// Request headers which carry credentials.
+const CREDENTIAL_HEADERS: &[HeaderName] = &[AUTHORIZATION, COOKIE];
+
+/// How a response is cached.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Directive {
+    max_age: Duration,
+    key: ResponseCacheKey,
+    vary: Vec<HeaderName>,
+}
+
+impl Directive {
+    /// Returns the directive configured for a route.
+    pub(crate) fn from_config(config: &ResponseCacheConfig) -> Result<Self> {
+        Ok(Self {
+            max_age: Duration::from_secs(config.max_age),
+            key: config.key,
+            vary: parse_header_names(config.vary.iter().map(String::as_str))?,
+        })
+    }
+
+    /// Parses an `x-spin-cache` header, with any directive configured for the
+    /// route supplying the attributes it omits. Returns `None` if the
+    /// response must not be cached.
+    fn parse(header: &str, route: Option<&Directive>) -> Result<Option<Self>> {
+        let mut max_age = route.map(|d| d.max_age);
+        let mut key = route.map_or(ResponseCacheKey::Uri, |d| d.key);
+        let mut vary = route.map(|d| d.vary.clone()).unwrap_or_default();
+        for attribute in split_attributes(header) {
+            let (name, value) = match attribute.split_once('=') {
+                Some((name, value)) => (name.trim(), unquote(value.trim())),
+                None => (attribute, ""),
+            };
+            match name.to_ascii_lowercase().as_str() {
+                "no-store" => return Ok(None),
+                "max-age" => {
+                    let seconds = value
+                        .parse()
+                        .with_context(|| format!("invalid max-age {value:?}"))?;
+                    max_age = Some(Duration::from_secs(seconds));
+                }
+                "key" => {
+                    key = match value {
+                        "uri" => ResponseCacheKey::Uri,
+                        "path" => ResponseCacheKey::Path,
+                        other => bail!("invalid key {other:?}: expected 'uri' or 'path'"),
+                    }
+                }
+                "vary" => vary = parse_header_names(value.split_whitespace())?,
+                other => bail!("unknown attribute {other:?}"),
+            }
+        }
+        let Some(max_age) = max_age else {
+            bail!("no max-age given");
+        };
+        if max_age.is_zero() {
+            return Ok(None);
+        }
+        Ok(Some(Self { max_age, key, vary }))
+    }
+}
+
+/// Whether the credentials a request has, if any, are among the `vary`
+/// headers.
+fn varies_on_credentials(vary: &[HeaderName], headers: &HeaderMap) -> bool {
+    CREDENTIAL_HEADERS
+        .iter()
+        .all(|name| !headers.contains_key(name) || vary.contains(name))
+}
+
+fn parse_header_names<'a>(names: impl Iterator<Item = &'a str>) -> Result<Vec<HeaderName>> {
+    names
+        .map(|name| {
+            HeaderName::try_from(name).with_context(|| format!("invalid vary header {name:?}"))
+        })
+        .collect()
+}
+
+/// Splits a header value at commas which are not within quotes.
+fn split_attributes(header: &str) -> impl Iterator<Item = &str> {
+    let mut in_quotes = false;
+    header
+        .split(move |c| {
+            if c == '"' {
+                in_quotes = !in_quotes;
+            }
+            c == ',' && !in_quotes
+        })
+        .map(str::trim)
+        .filter(|attribute| !attribute.is_empty())
+}
+
+fn unquote(value: &str) -> &str {
+    value
+        .strip_prefix('"')
+        .and_then(|v| v.strip_suffix('"'))
+        .unwrap_or(value)
+}
+
+/// The parts of a request needed to cache its response, taken before the
+/// request is passed to the component.
+pub(crate) struct CacheableRequest {
+    method: Method,
+    authority: String,
+    path: String,
+    query: Option<String>,
+    headers: HeaderMap,
+}
+
+impl CacheableRequest {
+    /// Returns the cacheable parts of the request, if its method is cacheable.
+    pub(crate) fn new<B>(req: &Request<B>) -> Option<Self> {
+        if req.method() != Method::GET && req.method() != Method::HEAD {
+            return None;
+        }
+        let uri = req.uri();
+        Some(Self {
+            method: req.method().clone(),
+            authority: uri.authority().map(ToString::to_string).unwrap_or_default(),
+            path: uri.path().to_owned(),
+            query: uri.query().map(str::to_owned),
+            headers: req.headers().clone(),
+        })
+    }
+
+    fn key(&self, component_id: &str, key: ResponseCacheKey) -> String {
+        // The kind of key is included so that a response cached by path
+        // alone isn't taken for one to a request without a query string.
+        let mut cache_key = format!(
+            "{component_id} {} {key:?} {}{}",
+            self.method, self.authority, self.path
+        );
+        if let (ResponseCacheKey::Uri, Some(query)) = (key, &self.query) {
+            cache_key.push('?');
+            cache_key.push_str(query);
+        }
+        cache_key
+    }
+
+    fn vary_values(&self, vary: &[HeaderName]) -> Vec<Option<HeaderValue>> {
+        vary.iter()
+            .map(|name| self.headers.get(name).cloned())
+            .collect()
+    }
+}
+
+struct Entry {
+    vary: Vec<HeaderName>,
+    vary_values: Vec<Option<HeaderValue>>,
+    status: StatusCode,
+    headers: HeaderMap,
+    body: Bytes,
+    stored: Instant,
+    expires: Instant,
+}
+
+impl Entry {
+    fn size(&self) -> usize {
+        let headers: usize = self
+            .headers
+            .iter()
+            .map(|(name, value)| name.as_str().len() + value.len())
+            .sum();
+        self.body.len() + headers
+    }
+
+    fn response(&self, now: Instant) -> Result<Response<Body>> {
+        let mut res = Response::builder()
+            .status(self.status)
+            .body(body::full(self.body.clone()))?;
+        *res.headers_mut() = self.headers.clone();
+        res.headers_mut()
+            .insert(AGE, (now - self.stored).as_secs().into());
+        Ok(res)
+    }
+}
+
+struct State {
+    /// Cache key -> the responses stored under it, one per set of values of
+    /// their `vary` headers
+    entries: LruCache<String, Vec<Entry>>,
+    size: usize,
+}
+
+/// The response cache of an HTTP trigger.
+pub(crate) struct ResponseCache {
+    state: Mutex<State>,
+}
+
+impl Default for ResponseCache {
+    fn default() -> Self {
+        Self {
+            state: Mutex::new(State {
+                entries: LruCache::unbounded(),
+                size: 0,
+            }),
+        }
+    }
+}
+
+impl ResponseCache {
+    /// Returns a cached response to the request, if there is one.
+    pub(crate) fn lookup(
+        &self,
+        component_id: &str,
+        req: &CacheableRequest,
+    ) -> Result<Option<Response<Body>>> {
+        let now = Instant::now();
+        let mut state = self.state.lock().unwrap();
+        for key in [ResponseCacheKey::Uri, ResponseCacheKey::Path] {
+            let Some(entries) = state.entries.get(&req.key(component_id, key)) else {
+                continue;
+            };
+            let found = entries.iter().find(|entry| {
+                entry.expires > now
+                    && varies_on_credentials(&entry.vary, &req.headers)
+                    && req.vary_values(&entry.vary) == entry.vary_values
+            });
+            if let Some(entry) = found {
+                return entry.response(now).map(Some);
+            }
+        }
+        Ok(None)
+    }
+
+    /// Caches the response if it should be, removing any `x-spin-cache`
+    /// header from it. `route` is the directive configured for the route.
+    pub(crate) async fn store(
+        &self,
+        component_id: &str,
+        req: Option<CacheableRequest>,
+        route: Option<&Directive>,
+        mut res: Response<Body>,
+    ) -> Result<Response<Body>> {
+        let header = res.headers_mut().remove(DIRECTIVE_HEADER);
+        let Some(req) = req else {
+            return Ok(res);
+        };
+        let directive = match &header {
+            Some(header) => {
+                let parsed = header
+                    .to_str()
+                    .context("not text")
+                    .and_then(|header| Directive::parse(header, route));
+                match parsed {
+                    Ok(directive) => directive,
+                    Err(e) => {
+                        tracing::warn!(
+                            "Ignoring invalid {DIRECTIVE_HEADER} header from component {component_id}: {e:#}"
+                        );
+                        return Ok(res);
+                    }
+                }
+            }
+            None => route.cloned(),
+        };
+        let Some(directive) = directive else {
+            return Ok(res);
+        };
+        if !CACHEABLE_STATUSES.contains(&res.status())
+            || res.headers().contains_key(SET_COOKIE)
+            || !varies_on_credentials(&directive.vary, &req.headers)
+        {
+            return Ok(res);
+        }
+        if res
+            .body()
+            .size_hint()
+            .upper()
+            .map_or(false, |size| size > MAX_BODY_SIZE as u64)
+        {
+            return Ok(res);
+        }
+
+        let (parts, body) = res.into_parts();
+        let body = body.collect().await?.to_bytes();
+        if body.len() <= MAX_BODY_SIZE {
+            let now = Instant::now();
+            let entry = Entry {
+                vary_values: req.vary_values(&directive.vary),
+                vary: directive.vary,
+                status: parts.status,
+                headers: parts.headers.clone(),
+                body: body.clone(),
+                stored: now,
+                expires: now + directive.max_age,
+            };
+            self.insert(req.key(component_id, directive.key), entry, now);
+        }
+        Ok(Response::from_parts(parts, body::full(body)))
+    }
+
+    fn insert(&self, key: String, entry: Entry, now: Instant) {
+        let mut state = self.state.lock().unwrap();
+        let mut entries = state.entries.pop(&key).unwrap_or_default();
+        state.size -= entries.iter().map(Entry::size).sum::<usize>();
+        entries.retain(|e| {
+            e.expires > now && (e.vary != entry.vary || e.vary_values != entry.vary_values)
+        });
+        entries.push(entry);
+        let size = entries.iter().map(Entry::size).sum::<usize>();
+        while state.size + size > MAX_CACHE_SIZE {
+            match state.entries.pop_lru() {
+                Some((_, evicted)) => state.size -= evicted.iter().map(Entry::size).sum::<usize>(),
+                None => break,
+            }
+        }
+        state.entries.put(key, entries);
+        state.size += size;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(uri: &str, headers: &[(&str, &str)]) -> CacheableRequest {
+        let mut builder = Request::get(uri);
+        for (name, value) in headers {
+            builder = builder.header(*name, *value);
+        }
+        CacheableRequest::new(&builder.body(()).unwrap()).unwrap()
+    }
+
+    fn response(status: StatusCode, directive: Option<&str>) -> Response<Body> {
+        let mut builder = Response::builder().status(status);
+        if let Some(directive) = directive {
+            builder = builder.header(DIRECTIVE_HEADER, directive);
+        }
+        builder
+            .body(body::full(Bytes::from_static(b"hello")))
+            .unwrap()
+    }
+
+    async fn text(res: Response<Body>) -> String {
+        let body = res.into_body().collect().await.unwrap().to_bytes();
+        String::from_utf8(body.to_vec()).unwrap()
+    }
+
+    #[test]
+    fn directives_are_parsed() {
+        let route = Directive::from_config(&ResponseCacheConfig {
+            max_age: 60,
+            key: ResponseCacheKey::Uri,
+            vary: vec!["accept".to_owned()],
+        })
+        .unwrap();
+
+        assert_eq!(
+            Some(Directive {
+                max_age: Duration::from_secs(10),
+                key: ResponseCacheKey::Path,
+                vary: vec![HeaderName::from_static("accept-language"), COOKIE],
+            }),
+            Directive::parse(
+                r#"max-age=10, key=path, vary="accept-language cookie""#,
+                None
+            )
+            .unwrap()
+        );
+        assert_eq!(
+            Some(Directive {
+                max_age: Duration::from_secs(60),
+                key: ResponseCacheKey::Path,
+                vary: vec![HeaderName::from_static("accept")],
+            }),
+            Directive::parse("key=path", Some(&route)).unwrap()
+        );
+        assert_eq!(None, Directive::parse("no-store", Some(&route)).unwrap());
+        assert_eq!(None, Directive::parse("max-age=0", None).unwrap());
+        Directive::parse("key=path", None).unwrap_err();
+        Directive::parse("max-age=10, stale=1", None).unwrap_err();
+    }
+
+    #[tokio::test]
+    async fn cached_responses_are_served() -> Result<()> {
+        let cache = ResponseCache::default();
+        let req = request("/a?x=1", &[]);
+        assert!(cache.lookup("c", &req)?.is_none());
+
+        let res = cache
+            .store(
+                "c",
+                Some(req),
+                None,
+                response(StatusCode::OK, Some("max-age=60")),
+            )
+            .await?;
+        assert!(!res.headers().contains_key(DIRECTIVE_HEADER));
+        assert_eq!("hello", text(res).await);
+
+        let hit = cache.lookup("c", &request("/a?x=1", &[]))?.unwrap();
+        assert_eq!(StatusCode::OK, hit.status());
+        assert_eq!("0", hit.headers()[AGE]);
+        assert_eq!("hello", text(hit).await);
+
+        assert!(cache.lookup("c", &request("/a?x=2", &[]))?.is_none());
+        assert!(cache.lookup("other", &request("/a?x=1", &[]))?.is_none());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn path_keys_ignore_the_query() -> Result<()> {
+        let cache = ResponseCache::default();
+        cache
+            .store(
+                "c",
+                Some(request("/a?x=1", &[])),
+                None,
+                response(StatusCode::OK, Some("max-age=60, key=path")),
+            )
+            .await?;
+        assert!(cache.lookup("c", &request("/a?x=2", &[]))?.is_some());
+
+        cache
+            .store(
+                "c",
+                Some(request("/b", &[])),
+                None,
+                response(StatusCode::OK, Some("max-age=60")),
+            )
+            .await?;
+        assert!(cache.lookup("c", &request("/b?x=1", &[]))?.is_none());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn responses_vary_on_request_headers() -> Result<()> {
+        let cache = ResponseCache::default();
+        let route = Directive::parse("max-age=60, vary=accept-language", None)?;
+        cache
+            .store(
+                "c",
+                Some(request("/", &[("accept-language", "en")])),
+                route.as_ref(),
+                response(StatusCode::OK, None),
+            )
+            .await?;
+        assert!(cache
+            .lookup("c", &request("/", &[("accept-language", "en")]))?
+            .is_some());
+        assert!(cache
+            .lookup("c", &request("/", &[("accept-language", "de")]))?
+            .is_none());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn private_and_uncacheable_responses_are_not_stored() -> Result<()> {
+        let cache = ResponseCache::default();
+        let with_cookie = || request("/", &[("cookie", "session=1")]);
+        cache
+            .store(
+                "c",
+                Some(with_cookie()),
+                None,
+                response(StatusCode::OK, Some("max-age=60")),
+            )
+            .await?;
+        cache
+            .store(
+                "c",
+                Some(request("/", &[])),
+                None,
+                response(StatusCode::INTERNAL_SERVER_ERROR, Some("max-age=60")),
+            )
+            .await?;
+        assert!(cache.lookup("c", &request("/", &[]))?.is_none());
+
+        cache
+            .store(
+                "c",
+                Some(request("/", &[])),
+                None,
+                response(StatusCode::OK, Some("max-age=60")),
+            )
+            .await?;
+        assert!(cache.lookup("c", &request("/", &[]))?.is_some());
+        assert!(cache.lookup("c", &with_cookie())?.is_none());
+
+        let post = Request::post("/").body(()).unwrap();
+        assert!(CacheableRequest::new(&post).is_none());
+        Ok(())
+    }
+}
@@ -0,0 +1,97 @@
+//! Static response headers declared for a route in the manifest.
+
+use anyhow::{Context, Result};
+use http::{header::HeaderName, HeaderMap, HeaderValue};
+use spin_http::config::HttpTriggerConfig;
+
+/// The headers to apply to a route's responses after its component responds.
+#[derive(Debug, Default)]
+pub(crate) struct ResponseHeaders {
+    /// Set only if the component did not set them, e.g. from a preset.
+    defaults: HeaderMap,
+    /// Set in place of any the component set.
+    overrides: HeaderMap,
+}
+
+impl ResponseHeaders {
+    /// Returns the headers configured for a route, if any.
+    pub(crate) fn from_config(config: &HttpTriggerConfig) -> Result<Option<Self>> {
+        if config.response_headers.is_empty() && config.security_headers.is_none() {
+            return Ok(None);
+        }
+        let mut headers = Self::default();
+        if let Some(preset) = config.security_headers {
+            for (name, value) in preset.headers() {
+                headers.defaults.insert(
+                    HeaderName::from_static(name),
+                    HeaderValue::from_static(value),
+                );
+            }
+        }
+        for (name, value) in &config.response_headers {
+            let name = HeaderName::try_from(name)
+                .with_context(|| format!("invalid response header name {name:?}"))?;
+            let value = HeaderValue::try_from(value)
+                .with_context(|| format!("invalid value for response header {name}"))?;
+            headers.overrides.insert(name, value);
+        }
+        Ok(Some(headers))
+    }
+
+    pub(crate) fn apply(&self, headers: &mut HeaderMap) {
+        for (name, value) in &self.defaults {
+            if !headers.contains_key(name) {
+                headers.insert(name, value.clone());
+            }
+        }
+        for (name, value) in &self.overrides {
+            headers.insert(name, value.clone());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use spin_http::config::SecurityHeaders;
+
+    use super::*;
+
+    #[test]
+    fn configured_headers_replace_and_presets_fill_in() {
+        let config = HttpTriggerConfig {
+            response_headers: [
+                ("cache-control".to_owned(), "no-store".to_owned()),
+                ("x-frame-options".to_owned(), "SAMEORIGIN".to_owned()),
+            ]
+            .into_iter()
+            .collect(),
+            security_headers: Some(SecurityHeaders::Strict),
+            ..Default::default()
+        };
+        let headers = ResponseHeaders::from_config(&config).unwrap().unwrap();
+
+        let mut response = HeaderMap::new();
+        response.insert("cache-control", "max-age=60".parse().unwrap());
+        response.insert("content-security-policy", "default-src *".parse().unwrap());
+        headers.apply(&mut response);
+
+        assert_eq!("no-store", response["cache-control"]);
+        assert_eq!("SAMEORIGIN", response["x-frame-options"]);
+        assert_eq!("default-src *", response["content-security-policy"]);
+        assert_eq!("nosniff", response["x-content-type-options"]);
+    }
+
+    #[test]
+    fn invalid_headers_are_rejected() {
+        let config = HttpTriggerConfig {
+            response_headers: [("bad header".to_owned(), "x".to_owned())]
+                .into_iter()
+                .collect(),
+            ..Default::default()
+        };
+        ResponseHeaders::from_config(&config).unwrap_err();
+        assert!(ResponseHeaders::from_config(&HttpTriggerConfig::default())
+            .unwrap()
+            .is_none());
+    }
+}
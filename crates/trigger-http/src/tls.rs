@@ -1,4 +1,5 @@
 use rustls_pemfile::{certs, pkcs8_private_keys};
+use spin_trigger::context::TlsInfo;
 use std::{
     fs, io,
     path::{Path, PathBuf},
@@ -31,6 +32,21 @@ impl TlsConfig {
     }
 }
 
+/// Describes an established TLS connection, for the `context` interface.
+pub(super) fn connection_info(connection: &rustls::ServerConnection) -> TlsInfo {
+    TlsInfo {
+        protocol: connection
+            .protocol_version()
+            .map(|version| format!("{version:?}"))
+            .unwrap_or_default(),
+        cipher_suite: connection
+            .negotiated_cipher_suite()
+            .map(|suite| format!("{:?}", suite.suite()))
+            .unwrap_or_default(),
+        server_name: connection.sni_hostname().map(ToOwned::to_owned),
+    }
+}
+
 // Loads public certificate from file.
 fn load_certs(path: impl AsRef<Path>) -> io::Result<Vec<rustls::Certificate>> {
     certs(&mut io::BufReader::new(fs::File::open(path)?))
@@ -1,12 +1,12 @@
 use std::{io::Cursor, net::SocketAddr};
 
-use anyhow::{anyhow, ensure, Context, Result};
+use anyhow::{anyhow, bail, ensure, Context, Result};
 use async_trait::async_trait;
 use http_body_util::BodyExt;
 use hyper::{Request, Response};
-use spin_core::WasiVersion;
+use spin_core::{OutputBuffer, WasiVersion};
 use spin_http::{config::WagiTriggerConfig, routes::RoutePattern, wagi};
-use spin_trigger::{EitherInstance, TriggerAppEngine};
+use spin_trigger::{compat::WASI_VERSION, EitherInstance, TriggerAppEngine};
 use wasi_common_preview1::{pipe::WritePipe, I32Exit};
 
 use crate::{Body, HttpExecutor, HttpTrigger};
@@ -80,49 +80,85 @@ impl HttpExecutor for WagiHttpExecutor {
             headers.insert(keys[1].to_string(), val);
         }
 
-        let stdout = WritePipe::new_in_memory();
+        // A core module is run with WASI Preview 1 through its entrypoint;
+        // a component is run with WASI Preview 2 as a WASI CLI command.
+        let is_module = engine.is_module(component);
+        let wasi_version = if is_module {
+            WasiVersion::Preview1
+        } else {
+            WasiVersion::Preview2
+        };
 
-        let mut store_builder = engine.store_builder(component, WasiVersion::Preview1)?;
+        let mut store_builder = engine.store_builder(component, wasi_version)?;
         // Set up Wagi environment
         store_builder.args(argv.split(' '))?;
         store_builder.env(headers)?;
         store_builder.stdin_pipe(Cursor::new(body));
-        store_builder.stdout(Box::new(stdout.clone()))?;
+        let stdout = if is_module {
+            let pipe = WritePipe::new_in_memory();
+            store_builder.stdout(Box::new(pipe.clone()))?;
+            Stdout::Module(pipe)
+        } else {
+            Stdout::Component(store_builder.stdout_buffered()?)
+        };
 
         let (instance, mut store) = engine
             .prepare_instance_with_store(component, store_builder)
             .await?;
-
-        let EitherInstance::Module(instance) = instance else {
-            unreachable!()
-        };
-
-        let start = instance
-            .get_func(&mut store, &self.wagi_config.entrypoint)
-            .ok_or_else(|| {
-                anyhow::anyhow!(
-                    "No such function '{}' in {}",
-                    self.wagi_config.entrypoint,
-                    component
-                )
-            })?;
-        tracing::trace!("Calling Wasm entry point");
-        start
-            .call_async(&mut store, &[], &mut [])
-            .await
-            .or_else(ignore_successful_proc_exit_trap)
-            .with_context(|| {
-                anyhow!(
-                    "invoking {} for component {component}",
-                    self.wagi_config.entrypoint
-                )
-            })?;
+        store.set_sample_label(raw_route);
+
+        match instance {
+            EitherInstance::Module(instance) => {
+                let start = instance
+                    .get_func(&mut store, &self.wagi_config.entrypoint)
+                    .ok_or_else(|| {
+                        anyhow::anyhow!(
+                            "No such function '{}' in {}",
+                            self.wagi_config.entrypoint,
+                            component
+                        )
+                    })?;
+                tracing::trace!("Calling Wasm entry point");
+                start
+                    .call_async(&mut store, &[], &mut [])
+                    .await
+                    .or_else(ignore_successful_proc_exit_trap)
+                    .with_context(|| {
+                        anyhow!(
+                            "invoking {} for component {component}",
+                            self.wagi_config.entrypoint
+                        )
+                    })?;
+            }
+            EitherInstance::Component(instance) => {
+                let run = instance
+                    .exports(&mut store)
+                    .instance(&format!("wasi:cli/run@{WASI_VERSION}"))
+                    .with_context(|| {
+                        format!(
+                            "The {component:?} component is configured to use the WAGI executor \
+                             but is not a WASI CLI command: it does not export `wasi:cli/run`"
+                        )
+                    })?
+                    .typed_func::<(), (Result<(), ()>,)>("run")?;
+                tracing::trace!("Calling wasi:cli/run");
+                match run.call_async(&mut store, ()).await {
+                    Ok((Ok(()),)) => (),
+                    Ok((Err(()),)) => bail!("component {component} exited with an error"),
+                    Err(e) => ignore_successful_proc_exit_trap(e)
+                        .with_context(|| format!("running component {component}"))?,
+                }
+            }
+        }
         tracing::info!("Module execution complete");
 
         // Drop the store so we're left with a unique reference to `stdout`:
         drop(store);
 
-        let stdout = stdout.try_into_inner().unwrap().into_inner();
+        let stdout = match stdout {
+            Stdout::Module(pipe) => pipe.try_into_inner().unwrap().into_inner(),
+            Stdout::Component(buffer) => buffer.contents().to_vec(),
+        };
         ensure!(
             !stdout.is_empty(),
             "The {component:?} component is configured to use the WAGI executor \
@@ -133,12 +169,22 @@ impl HttpExecutor for WagiHttpExecutor {
     }
 }
 
+/// Where a Wagi program's stdout, and so its response, is collected.
+enum Stdout {
+    Module(WritePipe<Cursor<Vec<u8>>>),
+    Component(OutputBuffer),
+}
+
 fn ignore_successful_proc_exit_trap(guest_err: anyhow::Error) -> Result<()> {
-    match guest_err.root_cause().downcast_ref::<I32Exit>() {
-        Some(trap) => match trap.0 {
-            0 => Ok(()),
-            _ => Err(guest_err),
-        },
-        None => Err(guest_err),
+    let root_cause = guest_err.root_cause();
+    let code = match root_cause.downcast_ref::<I32Exit>() {
+        Some(trap) => Some(trap.0),
+        None => root_cause
+            .downcast_ref::<spin_core::I32Exit>()
+            .map(|trap| trap.0),
+    };
+    match code {
+        Some(0) => Ok(()),
+        _ => Err(guest_err),
     }
 }
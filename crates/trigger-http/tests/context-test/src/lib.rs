@@ -0,0 +1,15 @@
+use spin_sdk::{context, http_component};
+
+/// Responds with what the host tells the component about the request.
+#[http_component]
+fn handle_context(_req: http::Request<()>) -> anyhow::Result<http::Response<String>> {
+    let body = format!(
+        "trigger={} app={} component={} remote={} tls={}",
+        context::trigger_type(),
+        context::app_name(),
+        context::component_id(),
+        context::remote_address().unwrap_or_default(),
+        context::tls().is_some(),
+    );
+    Ok(http::Response::builder().status(200).body(body)?)
+}
@@ -0,0 +1,596 @@
+//! Admin API for a running trigger.
+//!
+//! When a trigger is started with `--admin-socket <PATH>`, it listens on a
+//! Unix socket for admin requests. Each connection carries one request and
+//! one response, each a single line of JSON. `spin ctl` is the client for
+//! this API.
+
+use std::{
+    collections::BTreeMap,
+    future::Future,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Mutex, OnceLock,
+    },
+    time::{Duration, Instant},
+};
+
+use anyhow::{ensure, Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use spin_app::AppComponent;
+use spin_core::StoreBuilder;
+use tokio::sync::{Semaphore, SemaphorePermit};
+
+use crate::TriggerHooks;
+
+/// A request to the admin API.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case", tag = "op")]
+pub enum AdminRequest {
+    /// List the running application and its components.
+    Components,
+    /// Show the current runtime configuration.
+    Config,
+    /// Take a snapshot of the trigger's metrics.
+    Metrics,
+    /// Replace the log filter, e.g. `debug` or `spin_trigger=trace`.
+    SetLogLevel { filter: String },
+    /// Stop accepting new work, wait for in-flight requests, then exit.
+    Drain {
+        #[serde(default = "default_drain_timeout_secs")]
+        timeout_secs: u64,
+    },
+    /// Reload the runtime configuration, rebuilding the running application.
+    Reload,
+    /// Upgrade the running application to the one at the given manifest path.
+    Upgrade { manifest: PathBuf },
+    /// Load the application at the given manifest path alongside the running
+    /// one under `label`, without sending it live traffic.
+    Stage { manifest: PathBuf, label: String },
+    /// Switch live traffic to the application staged under `label`, provided
+    /// a request for each of the `checks` paths succeeds.
+    Promote {
+        label: String,
+        #[serde(default)]
+        checks: Vec<String>,
+    },
+}
+
+fn default_drain_timeout_secs() -> u64 {
+    30
+}
+
+/// A response from the admin API.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AdminResponse {
+    /// Whether the request succeeded.
+    pub ok: bool,
+    /// The result of a successful request.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub result: Option<Value>,
+    /// The error message for a failed request.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl From<Result<Value>> for AdminResponse {
+    fn from(result: Result<Value>) -> Self {
+        match result {
+            Ok(value) => Self {
+                ok: true,
+                result: Some(value),
+                error: None,
+            },
+            Err(e) => Self {
+                ok: false,
+                result: None,
+                error: Some(format!("{e:#}")),
+            },
+        }
+    }
+}
+
+type LogLevelHandler = Box<dyn Fn(&str) -> Result<()> + Send + Sync>;
+
+static LOG_LEVEL_HANDLER: OnceLock<LogLevelHandler> = OnceLock::new();
+
+/// Registers the function used to apply `set-log-level` requests. This is
+/// provided by the binary that installs the tracing subscriber.
+pub fn set_log_level_handler(handler: impl Fn(&str) -> Result<()> + Send + Sync + 'static) {
+    if LOG_LEVEL_HANDLER.set(Box::new(handler)).is_err() {
+        tracing::warn!("Log level handler was already set");
+    }
+}
+
+fn set_log_level(filter: &str) -> Result<()> {
+    let handler = LOG_LEVEL_HANDLER
+        .get()
+        .context("changing the log level is not supported by this host")?;
+    handler(filter)
+}
+
+static STARTED: OnceLock<Instant> = OnceLock::new();
+static DRAINING: AtomicBool = AtomicBool::new(false);
+static REQUESTS_TOTAL: AtomicU64 = AtomicU64::new(0);
+static REQUESTS_IN_FLIGHT: AtomicU64 = AtomicU64::new(0);
+static COMPONENT_INSTANCES: Mutex<BTreeMap<String, u64>> = Mutex::new(BTreeMap::new());
+static COMPONENT_USAGE: Mutex<BTreeMap<String, ComponentUsage>> = Mutex::new(BTreeMap::new());
+static INVOCATION_LIMIT: OnceLock<InvocationLimit> = OnceLock::new();
+static INVOCATIONS_RUNNING: AtomicU64 = AtomicU64::new(0);
+static INVOCATIONS_QUEUED: AtomicU64 = AtomicU64::new(0);
+static QUEUE_WAIT: Mutex<Latency> = Mutex::new(Latency::new());
+static INSTANTIATION_LATENCY: Mutex<BTreeMap<String, Latency>> = Mutex::new(BTreeMap::new());
+
+/// The resources used by all invocations of a component, as reported in the
+/// `component_usage` metric.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct ComponentUsage {
+    /// The number of invocations measured.
+    pub invocations: u64,
+    /// The total CPU time of those invocations, in microseconds.
+    pub cpu_time_us: u64,
+    /// The largest CPU time of any one invocation, in microseconds.
+    pub max_cpu_time_us: u64,
+    /// The largest linear memory of any one invocation, in bytes.
+    pub peak_memory_bytes: u64,
+    /// The number of times instances were made to yield at the end of their
+    /// `max_slice_ms` time slice.
+    #[serde(default)]
+    pub forced_yields: u64,
+}
+
+/// How long something took, over every time it was measured, as reported
+/// in the `queue_wait` and `instantiation_latency` metrics.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Latency {
+    /// The number of times measured.
+    pub count: u64,
+    /// The total time, in microseconds.
+    pub total_us: u64,
+    /// The longest time, in microseconds.
+    pub max_us: u64,
+}
+
+impl Latency {
+    const fn new() -> Self {
+        Self {
+            count: 0,
+            total_us: 0,
+            max_us: 0,
+        }
+    }
+
+    fn record(&mut self, elapsed: Duration) {
+        let us = elapsed.as_micros() as u64;
+        self.count += 1;
+        self.total_us += us;
+        self.max_us = self.max_us.max(us);
+    }
+
+    /// The mean time, in microseconds.
+    pub fn mean_us(&self) -> u64 {
+        self.total_us / self.count.max(1)
+    }
+}
+
+/// A limit on the number of invocations running at once, across all of a
+/// trigger's components.
+struct InvocationLimit {
+    permits: Semaphore,
+    max: usize,
+}
+
+impl InvocationLimit {
+    /// Waits for a turn to run an invocation, returning a permit to hold
+    /// while it runs.
+    async fn acquire(&self) -> SemaphorePermit<'_> {
+        if let Ok(permit) = self.permits.try_acquire() {
+            return permit;
+        }
+        let _queued = Gauge::increment(&INVOCATIONS_QUEUED);
+        let started = Instant::now();
+        // The semaphore is never closed.
+        let permit = self.permits.acquire().await.unwrap();
+        QUEUE_WAIT.lock().unwrap().record(started.elapsed());
+        permit
+    }
+}
+
+/// Limits the number of component invocations which run at once to `max`;
+/// invocations beyond this wait for a turn. This applies to every
+/// subsequent [`track_invocation`], and may only be set once.
+pub fn limit_concurrent_invocations(max: usize) -> Result<()> {
+    ensure!(
+        max > 0,
+        "the concurrent invocation limit must be at least 1"
+    );
+    let limit = InvocationLimit {
+        permits: Semaphore::new(max),
+        max,
+    };
+    if INVOCATION_LIMIT.set(limit).is_err() {
+        tracing::warn!("The concurrent invocation limit was already set");
+    }
+    Ok(())
+}
+
+/// Records how long it took to instantiate the given component, for the
+/// `instantiation_latency` metric and the current invocation's timings.
+pub fn record_instantiation(component_id: &str, elapsed: Duration) {
+    spin_core::usage::record_instantiation(elapsed);
+    INSTANTIATION_LATENCY
+        .lock()
+        .unwrap()
+        .entry(component_id.to_owned())
+        .or_default()
+        .record(elapsed);
+}
+
+/// Counts something in a gauge metric until dropped.
+struct Gauge(&'static AtomicU64);
+
+impl Gauge {
+    fn increment(gauge: &'static AtomicU64) -> Self {
+        gauge.fetch_add(1, Ordering::Relaxed);
+        Self(gauge)
+    }
+}
+
+impl Drop for Gauge {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Returns true once a drain has been requested. Triggers should refuse new
+/// work while draining.
+pub fn is_draining() -> bool {
+    DRAINING.load(Ordering::Relaxed)
+}
+
+/// Records the start of a request; the request is counted as in flight until
+/// the returned guard is dropped.
+pub fn track_request() -> RequestGuard {
+    REQUESTS_TOTAL.fetch_add(1, Ordering::Relaxed);
+    REQUESTS_IN_FLIGHT.fetch_add(1, Ordering::Relaxed);
+    RequestGuard(())
+}
+
+/// Marks a request as in flight until dropped. See [`track_request`].
+pub struct RequestGuard(());
+
+impl Drop for RequestGuard {
+    fn drop(&mut self) {
+        REQUESTS_IN_FLIGHT.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Runs `f` as an invocation of the given component, logging the CPU time
+/// and memory it used and adding them to the component's usage metrics.
+/// If the number of concurrent invocations is limited, this first waits for
+/// a turn.
+///
+/// CPU time is the time spent polling `f`, on the host as well as in the
+/// guest; work done in tasks spawned by `f`, such as streaming a response
+/// body, is not included.
+pub async fn track_invocation<F: Future>(component_id: &str, f: F) -> F::Output {
+    time_invocation(component_id, f).await.0
+}
+
+/// Where the time of one invocation went, as reported in the
+/// `Server-Timing` header of HTTP responses.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct InvocationTimings {
+    /// Waiting for a turn under `--max-concurrent-invocations`.
+    pub queue: Duration,
+    /// Instantiating components.
+    pub instantiate: Duration,
+    /// Running the component, apart from its outbound calls.
+    pub execute: Duration,
+    /// Waiting on outbound calls.
+    pub outbound: Duration,
+}
+
+impl InvocationTimings {
+    /// Formats the timings as a `Server-Timing` header value, in
+    /// milliseconds.
+    pub fn server_timing(&self) -> String {
+        [
+            ("queue", self.queue),
+            ("instantiate", self.instantiate),
+            ("execute", self.execute),
+            ("outbound", self.outbound),
+        ]
+        .iter()
+        .map(|(name, d)| format!("{name};dur={:.3}", d.as_secs_f64() * 1000.0))
+        .collect::<Vec<_>>()
+        .join(", ")
+    }
+}
+
+/// As [`track_invocation`], also returning where the invocation's time went.
+pub async fn time_invocation<F: Future>(
+    component_id: &str,
+    f: F,
+) -> (F::Output, InvocationTimings) {
+    let queued = Instant::now();
+    let _permit = match INVOCATION_LIMIT.get() {
+        Some(limit) => Some(limit.acquire().await),
+        None => None,
+    };
+    let _running = Gauge::increment(&INVOCATIONS_RUNNING);
+    let started = Instant::now();
+    let (output, usage) = spin_core::usage::measure(f).await;
+    let timings = InvocationTimings {
+        queue: started - queued,
+        instantiate: usage.instantiation_time,
+        execute: started
+            .elapsed()
+            .saturating_sub(usage.instantiation_time)
+            .saturating_sub(usage.outbound_time),
+        outbound: usage.outbound_time,
+    };
+    let cpu_time_us = usage.cpu_time.as_micros() as u64;
+    tracing::info!(
+        component = component_id,
+        cpu_time_us,
+        peak_memory_bytes = usage.peak_memory_bytes,
+        forced_yields = usage.forced_yields,
+        "Invocation complete"
+    );
+    let mut components = COMPONENT_USAGE.lock().unwrap();
+    let totals = components.entry(component_id.to_owned()).or_default();
+    totals.invocations += 1;
+    totals.cpu_time_us += cpu_time_us;
+    totals.max_cpu_time_us = totals.max_cpu_time_us.max(cpu_time_us);
+    totals.peak_memory_bytes = totals.peak_memory_bytes.max(usage.peak_memory_bytes);
+    totals.forced_yields += usage.forced_yields;
+    (output, timings)
+}
+
+/// Returns a snapshot of the trigger's metrics.
+pub fn metrics() -> Value {
+    let uptime = STARTED.get_or_init(Instant::now).elapsed();
+    let instances = COMPONENT_INSTANCES.lock().unwrap().clone();
+    let usage = COMPONENT_USAGE.lock().unwrap().clone();
+    let cpu_time_us: u64 = usage.values().map(|u| u.cpu_time_us).sum();
+    let worker_threads = worker_threads();
+    let dns_cache = spin_outbound_networking::dns::cache_stats();
+    serde_json::json!({
+        "uptime_secs": uptime.as_secs(),
+        "requests_total": REQUESTS_TOTAL.load(Ordering::Relaxed),
+        "requests_in_flight": REQUESTS_IN_FLIGHT.load(Ordering::Relaxed),
+        "component_instances": instances,
+        "component_usage": usage,
+        "invocations_running": INVOCATIONS_RUNNING.load(Ordering::Relaxed),
+        "invocations_queued": INVOCATIONS_QUEUED.load(Ordering::Relaxed),
+        "max_concurrent_invocations": INVOCATION_LIMIT.get().map(|limit| limit.max),
+        "queue_wait": QUEUE_WAIT.lock().unwrap().clone(),
+        "instantiation_latency": INSTANTIATION_LATENCY.lock().unwrap().clone(),
+        "executor": {
+            "worker_threads": worker_threads,
+            "utilization": utilization(cpu_time_us, uptime, worker_threads),
+        },
+        "outbound_http": outbound_http::pool::metrics(),
+        "dns_cache": {
+            "hits": dns_cache.hits,
+            "misses": dns_cache.misses,
+            "entries": dns_cache.entries,
+        },
+    })
+}
+
+/// The number of threads the runtime runs tasks on: the number of CPUs,
+/// unless the `TOKIO_WORKER_THREADS` environment variable sets it.
+fn worker_threads() -> usize {
+    std::env::var("TOKIO_WORKER_THREADS")
+        .ok()
+        .and_then(|threads| threads.parse().ok())
+        .or_else(|| std::thread::available_parallelism().ok().map(Into::into))
+        .unwrap_or(1)
+}
+
+/// The share of the worker threads' time spent running invocations since
+/// the trigger started, between 0 and 1.
+fn utilization(cpu_time_us: u64, uptime: Duration, worker_threads: usize) -> f64 {
+    let capacity_us = uptime.as_micros() as f64 * worker_threads as f64;
+    if capacity_us == 0.0 {
+        return 0.0;
+    }
+    (cpu_time_us as f64 / capacity_us).min(1.0)
+}
+
+/// Implements TriggerHooks, counting component instantiations for the
+/// admin metrics.
+pub(crate) struct MetricsTriggerHooks;
+
+impl TriggerHooks for MetricsTriggerHooks {
+    fn app_loaded(
+        &mut self,
+        _app: &spin_app::App,
+        _runtime_config: &crate::RuntimeConfig,
+    ) -> Result<()> {
+        STARTED.get_or_init(Instant::now);
+        Ok(())
+    }
+
+    fn component_store_builder(
+        &self,
+        component: &AppComponent,
+        _store_builder: &mut StoreBuilder,
+    ) -> Result<()> {
+        *COMPONENT_INSTANCES
+            .lock()
+            .unwrap()
+            .entry(component.id().to_owned())
+            .or_default() += 1;
+        Ok(())
+    }
+}
+
+/// The outcome of handling an admin request.
+pub(crate) enum Handled {
+    /// Keep serving admin requests.
+    Continue(Result<Value>),
+    /// Respond, then stop the trigger.
+    Shutdown(Result<Value>),
+}
+
+/// Handles the requests which don't depend on the running application,
+/// passing the rest to `handle`.
+pub(crate) async fn dispatch<F, Fut>(request: AdminRequest, handle: F) -> Handled
+where
+    F: FnOnce(AdminRequest) -> Fut,
+    Fut: Future<Output = Result<Value>>,
+{
+    match request {
+        AdminRequest::Metrics => Handled::Continue(Ok(metrics())),
+        AdminRequest::SetLogLevel { filter } => {
+            Handled::Continue(set_log_level(&filter).map(|()| Value::Null))
+        }
+        AdminRequest::Drain { timeout_secs } => {
+            Handled::Shutdown(drain(Duration::from_secs(timeout_secs)).await)
+        }
+        request => Handled::Continue(handle(request).await),
+    }
+}
+
+async fn drain(timeout: Duration) -> Result<Value> {
+    DRAINING.store(true, Ordering::Relaxed);
+    tracing::info!("Draining: waiting for in-flight requests to complete");
+    let deadline = Instant::now() + timeout;
+    loop {
+        let in_flight = REQUESTS_IN_FLIGHT.load(Ordering::Relaxed);
+        if in_flight == 0 {
+            return Ok(serde_json::json!({ "abandoned_requests": 0 }));
+        }
+        if Instant::now() >= deadline {
+            tracing::warn!("Drain timed out with {in_flight} request(s) still in flight");
+            return Ok(serde_json::json!({ "abandoned_requests": in_flight }));
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+}
+
+/// Serves admin requests from `socket_path` until a drain completes.
+#[cfg(unix)]
+pub(crate) async fn serve_admin_socket<F, Fut>(socket_path: PathBuf, handle: F) -> Result<()>
+where
+    F: FnMut(AdminRequest) -> Fut,
+    Fut: Future<Output = Result<Value>>,
+{
+    // Shared with each response, which can't borrow from the socket loop.
+    let handle = std::sync::Arc::new(tokio::sync::Mutex::new(handle));
+    crate::socket::serve_line_socket("admin", socket_path, |line| {
+        let handle = handle.clone();
+        async move {
+            let handled = match serde_json::from_str::<AdminRequest>(&line) {
+                Ok(request) => {
+                    tracing::info!("Admin request: {request:?}");
+                    dispatch(request, &mut *handle.lock().await).await
+                }
+                Err(e) => Handled::Continue(Err(anyhow::Error::from(e).context("invalid request"))),
+            };
+            let (result, shutdown) = match handled {
+                Handled::Continue(result) => (result, false),
+                Handled::Shutdown(result) => (result, true),
+            };
+            let response =
+                serde_json::to_string(&AdminResponse::from(result)).unwrap_or_else(|e| {
+                    serde_json::json!({ "ok": false, "error": e.to_string() }).to_string()
+                });
+            if shutdown {
+                std::ops::ControlFlow::Break(response)
+            } else {
+                std::ops::ControlFlow::Continue(response)
+            }
+        }
+    })
+    .await
+}
+
+#[cfg(not(unix))]
+pub(crate) async fn serve_admin_socket<F>(_socket_path: PathBuf, _handle: F) -> Result<()> {
+    anyhow::bail!("--admin-socket is only supported on Unix platforms")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn latency_tracks_mean_and_max() {
+        let mut latency = Latency::new();
+        assert_eq!(0, latency.mean_us());
+        latency.record(Duration::from_micros(100));
+        latency.record(Duration::from_micros(300));
+        assert_eq!(2, latency.count);
+        assert_eq!(200, latency.mean_us());
+        assert_eq!(300, latency.max_us);
+    }
+
+    #[test]
+    fn utilization_is_a_share_of_worker_time() {
+        assert_eq!(0.0, utilization(1_000, Duration::ZERO, 4));
+        assert_eq!(0.25, utilization(1_000_000, Duration::from_secs(1), 4));
+        assert_eq!(1.0, utilization(8_000_000, Duration::from_secs(1), 4));
+    }
+
+    #[test]
+    fn timings_are_formatted_for_server_timing() {
+        let timings = InvocationTimings {
+            queue: Duration::ZERO,
+            instantiate: Duration::from_micros(1_500),
+            execute: Duration::from_millis(12),
+            outbound: Duration::from_micros(250),
+        };
+        assert_eq!(
+            "queue;dur=0.000, instantiate;dur=1.500, execute;dur=12.000, outbound;dur=0.250",
+            timings.server_timing()
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn admin_socket_keeps_serving_after_invalid_requests() {
+        use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+        async fn request(socket_path: &std::path::Path, request: &str) -> AdminResponse {
+            let mut stream = tokio::net::UnixStream::connect(socket_path).await.unwrap();
+            stream.write_all(request.as_bytes()).await.unwrap();
+            let mut response = String::new();
+            BufReader::new(stream)
+                .read_line(&mut response)
+                .await
+                .unwrap();
+            serde_json::from_str(&response).unwrap()
+        }
+
+        let dir = tempfile::tempdir().unwrap();
+        let socket_path = dir.path().join("admin.sock");
+        let server = tokio::spawn(serve_admin_socket(
+            socket_path.clone(),
+            |request| async move {
+                match request {
+                    AdminRequest::Components => Ok(serde_json::json!(["hello"])),
+                    request => anyhow::bail!("unexpected request {request:?}"),
+                }
+            },
+        ));
+        while !socket_path.exists() {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        let response = request(&socket_path, "not json\n").await;
+        assert!(!response.ok);
+        assert!(response.error.unwrap().contains("invalid request"));
+
+        let response = request(&socket_path, "{\"op\":\"components\"}\n").await;
+        assert!(response.ok);
+        assert_eq!(Some(serde_json::json!(["hello"])), response.result);
+
+        server.abort();
+    }
+}
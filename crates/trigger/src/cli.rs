@@ -1,20 +1,31 @@
-use std::path::PathBuf;
+use std::{
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Duration,
+};
 
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 use clap::{Args, IntoApp, Parser};
+use futures::{future::Either, FutureExt};
 use serde::de::DeserializeOwned;
 use spin_app::Loader;
 use spin_common::{arg_parser::parse_kv, sloth};
+use tokio::sync::Mutex;
 
+use crate::deterministic::DeterministicTriggerHooks;
+use crate::profiling::ProfilingTriggerHooks;
 use crate::runtime_config::llm::LLmOptions;
 use crate::runtime_config::sqlite::SqlitePersistenceMessageHook;
+use crate::sandbox::{SandboxProfile, SandboxTriggerHooks};
+use crate::seed::Seed;
 use crate::stdio::StdioLoggingTriggerHooks;
+use crate::{admin::AdminRequest, upgrade::Upgrader};
 use crate::{
     loader::TriggerLoader,
     runtime_config::{key_value::KeyValuePersistenceMessageHook, RuntimeConfig},
     stdio::FollowComponents,
 };
-use crate::{TriggerExecutor, TriggerExecutorBuilder};
+use crate::{InstancePoolConfig, TriggerExecutor, TriggerExecutorBuilder};
 
 pub const APP_LOG_DIR: &str = "APP_LOG_DIR";
 pub const DISABLE_WASMTIME_CACHE: &str = "DISABLE_WASMTIME_CACHE";
@@ -68,6 +79,31 @@ where
     #[clap(long = "disable-pooling")]
     pub disable_pooling: bool,
 
+    /// Keep up to this many instances of each component prepared ahead of
+    /// requests, growing towards it as requests have to wait for instances.
+    /// Zero, the default, prepares instances only as requests arrive.
+    #[clap(long = "max-ready-instances", default_value = "0")]
+    pub max_ready_instances: usize,
+
+    /// Keep at least this many instances of each component prepared, however
+    /// idle it is.
+    #[clap(long = "min-ready-instances", default_value = "0")]
+    pub min_ready_instances: usize,
+
+    /// Tear down prepared instances which go unused for this many seconds,
+    /// down to --min-ready-instances.
+    #[clap(long = "ready-instance-idle-timeout", default_value = "60")]
+    pub ready_instance_idle_timeout_secs: u64,
+
+    /// Run at most this many component invocations at once, across all of
+    /// the trigger's components; further invocations wait for a turn. The
+    /// number waiting is reported in the `invocations_queued` metric. By
+    /// default there is no limit. The number of threads invocations run on
+    /// is set by the TOKIO_WORKER_THREADS environment variable, which
+    /// defaults to the number of CPUs.
+    #[clap(long = "max-concurrent-invocations", conflicts_with = "deterministic")]
+    pub max_concurrent_invocations: Option<usize>,
+
     /// Print output to stdout/stderr only for given component(s)
     #[clap(
         name = FOLLOW_LOG_OPT,
@@ -89,6 +125,35 @@ where
     #[clap(long = "allow-transient-write")]
     pub allow_transient_write: bool,
 
+    /// The sandbox profile. In the `strict` profile, components can use only
+    /// the capabilities explicitly granted in the manifest: wildcard
+    /// outbound hosts are refused, variables are not read from the host
+    /// environment, and files cannot be made writable.
+    #[clap(
+        value_enum,
+        long = "sandbox",
+        default_value = "default",
+        conflicts_with = "allow-transient-write"
+    )]
+    pub sandbox: SandboxProfile,
+
+    /// Make the WASI clocks coarse: they advance only in steps of the given
+    /// number of milliseconds, with a random offset of up to one step.
+    #[clap(long = "clock-resolution-ms", conflicts_with = "deterministic")]
+    pub clock_resolution_ms: Option<u64>,
+
+    /// Run deterministically, so that test runs are reproducible: the WASI
+    /// random sources are seeded, the WASI clocks are frozen at
+    /// 2000-01-01T00:00:00Z, and invocations run one at a time in the order
+    /// they arrive. A component's own `wasi` random seed and frozen time are
+    /// kept.
+    #[clap(long = "deterministic")]
+    pub deterministic: bool,
+
+    /// The seed for the random sources in deterministic mode. Defaults to 0.
+    #[clap(long = "deterministic-seed", requires = "deterministic")]
+    pub deterministic_seed: Option<u64>,
+
     /// Configuration file for config providers and wasmtime config.
     #[clap(
         name = RUNTIME_CONFIG_FILE,
@@ -106,6 +171,62 @@ where
     #[clap(long)]
     pub state_dir: Option<String>,
 
+    /// Listen on the given Unix socket for requests to upgrade the running
+    /// application to a new version. Each request is a line containing the
+    /// path to the new version's manifest; the response is `OK` or an error.
+    #[clap(long = "upgrade-socket")]
+    pub upgrade_socket: Option<PathBuf>,
+
+    /// Listen on the given Unix socket for admin requests, such as those
+    /// sent by `spin ctl`.
+    #[clap(long = "admin-socket")]
+    pub admin_socket: Option<PathBuf>,
+
+    /// Append an audit log of the host capabilities used by components to
+    /// the given file, as JSON lines: outbound hosts contacted, database
+    /// statements (with literals removed), key-value keys, and the
+    /// directories mounted into each instance.
+    #[clap(long = "audit-log")]
+    pub audit_log: Option<PathBuf>,
+
+    /// Inject latency, errors or dropped connections into outbound HTTP,
+    /// PostgreSQL and Redis calls according to the `[[rule]]`s in the given
+    /// TOML file, to test how components behave when their dependencies
+    /// fail.
+    #[clap(long = "fault-inject")]
+    pub fault_inject: Option<PathBuf>,
+
+    /// Profile the given component, writing its sampled guest stacks to
+    /// `<COMPONENT>/<ROUTE>.folded` in the profile directory for each HTTP
+    /// route it serves, and to `<COMPONENT>.folded` for other triggers, for
+    /// use with flamegraph tools. Can be used multiple times.
+    #[clap(long = "profile", multiple_occurrences = true)]
+    pub profile: Vec<String>,
+
+    /// The directory to write profiles to. Defaults to `profiles/` in the
+    /// application state directory.
+    #[clap(long = "profile-dir", requires = "profile")]
+    pub profile_dir: Option<PathBuf>,
+
+    /// Prepare components for debugging: traps print backtraces with source
+    /// locations, and debuggers with Wasmtime JIT support, such as LLDB
+    /// (`lldb -- spin up --debug`), can set breakpoints in component code.
+    /// Spin doesn't serve the debug adapter protocol itself; editors debug
+    /// through their LLDB or GDB integration.
+    #[clap(long = "debug")]
+    pub debug: bool,
+
+    /// Print the fully resolved application configuration and exit without
+    /// running it: each trigger and the component it runs, and each
+    /// component's variables, environment, files, allowed hosts and stores,
+    /// together with the runtime config. Secrets are masked.
+    #[clap(long = "print-config")]
+    pub print_config: bool,
+
+    /// Apply pending database migrations before starting.
+    #[clap(long = "migrate")]
+    pub migrate: bool,
+
     #[clap(flatten)]
     pub run_config: Executor::RunConfig,
 
@@ -120,6 +241,15 @@ where
     #[clap(long = "sqlite")]
     sqlite_statements: Vec<String>,
 
+    /// Seed a database or key-value store for local development, once any
+    /// migrations have been applied. The value is `[NAME=]FILE`: a .sql file
+    /// is run against the database NAME (Postgres if the runtime config
+    /// names one, otherwise SQLite), and a .json object's entries are set
+    /// in the key-value store NAME. NAME defaults to `default`.
+    /// Can be used multiple times.
+    #[clap(long = "seed")]
+    seeds: Vec<Seed>,
+
     #[clap(long = "help-args-only", hide = true)]
     pub help_args_only: bool,
 }
@@ -131,7 +261,7 @@ pub struct NoArgs;
 
 impl<Executor: TriggerExecutor> TriggerExecutorCommand<Executor>
 where
-    Executor::RunConfig: Args,
+    Executor::RunConfig: Args + Send,
     Executor::TriggerConfig: DeserializeOwned,
 {
     /// Create a new TriggerExecutorBuilder from this TriggerExecutorCommand.
@@ -148,16 +278,74 @@ where
         let working_dir = std::env::var(SPIN_WORKING_DIR).context(SPIN_WORKING_DIR)?;
         let locked_url = std::env::var(SPIN_LOCKED_URL).context(SPIN_LOCKED_URL)?;
 
+        if let Some(audit_log) = &self.audit_log {
+            spin_core::audit::open_audit_log(audit_log)?;
+        }
+
+        if let Some(fault_inject) = &self.fault_inject {
+            let contents = std::fs::read_to_string(fault_inject)
+                .with_context(|| format!("Failed to read fault injection file {fault_inject:?}"))?;
+            let config = toml::from_str(&contents).with_context(|| {
+                format!("Failed to parse fault injection file {fault_inject:?}")
+            })?;
+            spin_core::faults::configure(config)?;
+            terminal::warn!("Injecting faults into outbound calls, as set in {fault_inject:?}.");
+        }
+
+        // Seeds are for developing an application, not for running one
+        // from a registry.
+        if !self.seeds.is_empty() && std::env::var_os(SPIN_LOCAL_APP_DIR).is_none() {
+            bail!("--seed can only be used with a local application");
+        }
+
         let init_data = crate::HostComponentInitData::new(
             &*self.key_values,
             &*self.sqlite_statements,
             LLmOptions { use_gpu: true },
-        );
+        )
+        .with_seeds(self.seeds.clone());
+
+        if let Some(max) = self.max_concurrent_invocations {
+            crate::admin::limit_concurrent_invocations(max)?;
+        }
+        if self.deterministic {
+            crate::admin::limit_concurrent_invocations(1)?;
+        }
 
-        let loader = TriggerLoader::new(working_dir, self.allow_transient_write);
-        let executor = self.build_executor(loader, locked_url, init_data).await?;
+        let options = self.build_options()?;
+        let loader = TriggerLoader::new(&working_dir, self.allow_transient_write);
+        let executor = options
+            .build_executor(loader, locked_url.clone(), init_data)
+            .await?;
+        if self.print_config {
+            return Ok(());
+        }
 
-        let run_fut = executor.run(self.run_config);
+        let run_fut = if self.upgrade_socket.is_none() && self.admin_socket.is_none() {
+            executor.run(self.run_config).boxed_local()
+        } else {
+            let (upgrader, upgrades) = Upgrader::new();
+            let reloader = AppReloader {
+                options,
+                working_dir: working_dir.clone().into(),
+                upgrader,
+                generations: AtomicUsize::new(0),
+                current: Mutex::new(CurrentApp {
+                    app_dir: working_dir.into(),
+                    locked_url,
+                }),
+                staged: Mutex::new(None),
+            };
+            let control_fut = reloader.serve(self.upgrade_socket, self.admin_socket);
+            let run_fut = executor.run_with_upgrades(self.run_config, upgrades);
+            async move {
+                futures::pin_mut!(control_fut, run_fut);
+                match futures::future::select(run_fut, control_fut).await {
+                    Either::Left((res, _)) | Either::Right((res, _)) => res,
+                }
+            }
+            .boxed_local()
+        };
 
         let (abortable, abort_handle) = futures::future::abortable(run_fut);
         ctrlc::set_handler(move || abort_handle.abort())?;
@@ -177,22 +365,122 @@ where
         }
     }
 
-    async fn build_executor(
+    fn build_options(&self) -> Result<BuildOptions> {
+        Ok(BuildOptions {
+            log: self.log.clone(),
+            disable_cache: self.disable_cache,
+            cache: self.cache.clone(),
+            disable_pooling: self.disable_pooling,
+            instance_pool: self.instance_pool_config()?,
+            follow_components: self.follow_components(),
+            runtime_config_file: self.runtime_config_file.clone(),
+            state_dir: self.state_dir.clone(),
+            sandbox: self.sandbox,
+            clock_resolution: self.clock_resolution_ms.map(Duration::from_millis),
+            deterministic_seed: self
+                .deterministic
+                .then(|| self.deterministic_seed.unwrap_or_default()),
+            profile: self.profile.clone(),
+            profile_dir: self.profile_dir.clone(),
+            debug: self.debug,
+            print_config: self.print_config,
+            migrate: self.migrate,
+        })
+    }
+
+    fn instance_pool_config(&self) -> Result<Option<InstancePoolConfig>> {
+        if self.max_ready_instances == 0 && self.min_ready_instances == 0 {
+            return Ok(None);
+        }
+        InstancePoolConfig::new(
+            self.min_ready_instances,
+            self.max_ready_instances,
+            Duration::from_secs(self.ready_instance_idle_timeout_secs),
+        )
+        .map(Some)
+    }
+
+    fn follow_components(&self) -> FollowComponents {
+        if self.silence_component_logs {
+            FollowComponents::None
+        } else if self.follow_components.is_empty() {
+            FollowComponents::All
+        } else {
+            let followed = self.follow_components.clone().into_iter().collect();
+            FollowComponents::Named(followed)
+        }
+    }
+}
+
+/// The options needed to build an executor. These are kept apart from the
+/// command's run config so that executors for upgraded versions of the
+/// application can be built while the original one is running.
+struct BuildOptions {
+    log: Option<PathBuf>,
+    disable_cache: bool,
+    cache: Option<PathBuf>,
+    disable_pooling: bool,
+    instance_pool: Option<InstancePoolConfig>,
+    follow_components: FollowComponents,
+    runtime_config_file: Option<PathBuf>,
+    state_dir: Option<String>,
+    sandbox: SandboxProfile,
+    clock_resolution: Option<Duration>,
+    /// The random seed, if running deterministically.
+    deterministic_seed: Option<u64>,
+    profile: Vec<String>,
+    profile_dir: Option<PathBuf>,
+    debug: bool,
+    print_config: bool,
+    migrate: bool,
+}
+
+impl BuildOptions {
+    async fn build_executor<Executor: TriggerExecutor>(
         &self,
         loader: impl Loader + Send + Sync + 'static,
         locked_url: String,
         init_data: crate::HostComponentInitData,
-    ) -> Result<Executor> {
+    ) -> Result<Executor>
+    where
+        Executor::TriggerConfig: DeserializeOwned,
+    {
         let runtime_config = self.build_runtime_config()?;
 
         let _sloth_guard = warn_if_wasm_build_slothful();
 
         let mut builder = TriggerExecutorBuilder::new(loader);
         self.update_config(builder.config_mut())?;
+        if let Some(wasmtime) = runtime_config.wasmtime() {
+            wasmtime.apply(builder.config_mut());
+        }
+        if let Some(config) = self.instance_pool {
+            builder.instance_pool(config);
+        }
+        if self.print_config {
+            builder.print_config();
+        }
+        if self.migrate {
+            builder.migrate();
+        }
 
-        builder.hooks(StdioLoggingTriggerHooks::new(self.follow_components()));
+        builder.hooks(StdioLoggingTriggerHooks::new(
+            self.follow_components.clone(),
+        ));
         builder.hooks(KeyValuePersistenceMessageHook);
         builder.hooks(SqlitePersistenceMessageHook);
+        builder.hooks(crate::admin::MetricsTriggerHooks);
+        builder.hooks(SandboxTriggerHooks::new(
+            self.sandbox,
+            self.clock_resolution,
+        ));
+        if let Some(seed) = self.deterministic_seed {
+            builder.hooks(DeterministicTriggerHooks::new(seed));
+        }
+        builder.hooks(ProfilingTriggerHooks::new(
+            self.profile.clone(),
+            self.profile_dir.clone(),
+        ));
 
         builder.build(locked_url, runtime_config, init_data).await
     }
@@ -209,18 +497,10 @@ where
         if let Some(config_file) = &self.runtime_config_file {
             config.merge_config_file(config_file)?;
         }
-        Ok(config)
-    }
-
-    fn follow_components(&self) -> FollowComponents {
-        if self.silence_component_logs {
-            FollowComponents::None
-        } else if self.follow_components.is_empty() {
-            FollowComponents::All
-        } else {
-            let followed = self.follow_components.clone().into_iter().collect();
-            FollowComponents::Named(followed)
+        if self.sandbox == SandboxProfile::Strict {
+            config.disable_default_variables_provider();
         }
+        Ok(config)
     }
 
     fn update_config(&self, config: &mut spin_core::Config) -> Result<()> {
@@ -233,10 +513,169 @@ where
             config.disable_pooling();
         }
 
+        if self.debug {
+            config.enable_guest_debugging();
+        }
+
         Ok(())
     }
 }
 
+/// Builds new versions of the running application and hands them to the
+/// executor, on behalf of the upgrade and admin sockets.
+struct AppReloader<Executor> {
+    options: BuildOptions,
+    working_dir: PathBuf,
+    upgrader: Upgrader<Executor>,
+    // The number of versions loaded since startup, which names their directories.
+    generations: AtomicUsize,
+    current: Mutex<CurrentApp>,
+    // The version staged under a label, if any.
+    staged: Mutex<Option<(String, CurrentApp)>>,
+}
+
+/// The version of the application which is currently running.
+struct CurrentApp {
+    app_dir: PathBuf,
+    locked_url: String,
+}
+
+impl<Executor: TriggerExecutor> AppReloader<Executor>
+where
+    Executor::TriggerConfig: DeserializeOwned,
+{
+    async fn serve(
+        self,
+        upgrade_socket: Option<PathBuf>,
+        admin_socket: Option<PathBuf>,
+    ) -> Result<()> {
+        let this = &self;
+        let upgrades = async {
+            match upgrade_socket {
+                Some(path) => crate::upgrade::serve_upgrade_socket(path, |manifest| async move {
+                    this.upgrade(&manifest).await
+                })
+                .await
+                .context("upgrade socket failed"),
+                None => std::future::pending().await,
+            }
+        };
+        let admin = async {
+            match admin_socket {
+                Some(path) => {
+                    crate::admin::serve_admin_socket(path, |request| this.handle_admin(request))
+                        .await
+                        .context("admin socket failed")
+                }
+                None => std::future::pending().await,
+            }
+        };
+        tokio::select! {
+            res = upgrades => res,
+            res = admin => res,
+        }
+    }
+
+    async fn upgrade(&self, manifest_path: &Path) -> Result<()> {
+        let mut current = self.current.lock().await;
+        let (app_dir, locked_url) = self.prepare_upgrade(manifest_path).await?;
+        self.replace(&app_dir, &locked_url).await?;
+        *current = CurrentApp {
+            app_dir,
+            locked_url,
+        };
+        Ok(())
+    }
+
+    async fn stage(&self, manifest_path: &Path, label: String) -> Result<()> {
+        let mut staged = self.staged.lock().await;
+        let (app_dir, locked_url) = self.prepare_upgrade(manifest_path).await?;
+        let executor = self.build_executor(&app_dir, &locked_url).await?;
+        self.upgrader.stage(executor, label.clone()).await?;
+        *staged = Some((
+            label,
+            CurrentApp {
+                app_dir,
+                locked_url,
+            },
+        ));
+        Ok(())
+    }
+
+    async fn promote(&self, label: String, checks: Vec<String>) -> Result<()> {
+        let mut current = self.current.lock().await;
+        let mut staged = self.staged.lock().await;
+        match staged.as_ref() {
+            Some((staged_label, _)) if *staged_label == label => (),
+            Some((staged_label, _)) => {
+                anyhow::bail!("the staged version is labelled {staged_label:?}, not {label:?}")
+            }
+            None => anyhow::bail!("no version is staged"),
+        }
+        self.upgrader.promote(label, checks).await?;
+        let (_, promoted) = staged.take().expect("staged version was checked above");
+        *current = promoted;
+        Ok(())
+    }
+
+    async fn prepare_upgrade(&self, manifest_path: &Path) -> Result<(PathBuf, String)> {
+        let generation = self.generations.fetch_add(1, Ordering::Relaxed) + 1;
+        crate::upgrade::prepare_upgrade(manifest_path, &self.working_dir, generation).await
+    }
+
+    async fn reload(&self) -> Result<()> {
+        let current = self.current.lock().await;
+        self.replace(&current.app_dir, &current.locked_url).await
+    }
+
+    async fn replace(&self, app_dir: &Path, locked_url: &str) -> Result<()> {
+        let executor = self.build_executor(app_dir, locked_url).await?;
+        self.upgrader.upgrade(executor).await
+    }
+
+    async fn build_executor(&self, app_dir: &Path, locked_url: &str) -> Result<Executor> {
+        // Seed data (--key-value, --sqlite) only applies at startup.
+        let init_data =
+            crate::HostComponentInitData::new(vec![], vec![], LLmOptions { use_gpu: true });
+        let loader = TriggerLoader::new(app_dir, false);
+        self.options
+            .build_executor(loader, locked_url.to_owned(), init_data)
+            .await
+    }
+
+    async fn handle_admin(&self, request: AdminRequest) -> Result<serde_json::Value> {
+        match request {
+            AdminRequest::Components => {
+                let locked_url = self.current.lock().await.locked_url.clone();
+                let app = TriggerLoader::new(&self.working_dir, false)
+                    .load_app(&locked_url)
+                    .await?;
+                Ok(serde_json::json!({
+                    "name": app.metadata.get("name"),
+                    "version": app.metadata.get("version"),
+                    "components": app.components.iter().map(|c| &c.id).collect::<Vec<_>>(),
+                    "triggers": app.triggers,
+                }))
+            }
+            AdminRequest::Config => Ok(self.options.build_runtime_config()?.summary()),
+            AdminRequest::Reload => self.reload().await.map(|()| serde_json::Value::Null),
+            AdminRequest::Upgrade { manifest } => self
+                .upgrade(&manifest)
+                .await
+                .map(|()| serde_json::Value::Null),
+            AdminRequest::Stage { manifest, label } => self
+                .stage(&manifest, label)
+                .await
+                .map(|()| serde_json::Value::Null),
+            AdminRequest::Promote { label, checks } => self
+                .promote(label, checks)
+                .await
+                .map(|()| serde_json::Value::Null),
+            request => anyhow::bail!("unexpected admin request {request:?}"),
+        }
+    }
+}
+
 const SLOTH_WARNING_DELAY_MILLIS: u64 = 1250;
 
 fn warn_if_wasm_build_slothful() -> sloth::SlothGuard {
@@ -272,3 +711,102 @@ pub mod help {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{help::HelpArgsOnlyTrigger, *};
+    use crate::upgrade::{Upgrade, UpgradeAction, Upgrades};
+
+    fn reloader() -> (
+        AppReloader<HelpArgsOnlyTrigger>,
+        Upgrades<HelpArgsOnlyTrigger>,
+    ) {
+        let command = TriggerExecutorCommand::<HelpArgsOnlyTrigger>::try_parse_from(["spin"])
+            .expect("no options are required");
+        let (upgrader, upgrades) = Upgrader::new();
+        let reloader = AppReloader {
+            options: command.build_options().unwrap(),
+            working_dir: std::env::temp_dir(),
+            upgrader,
+            generations: AtomicUsize::new(0),
+            current: Mutex::new(app("current")),
+            staged: Mutex::new(None),
+        };
+        (reloader, upgrades)
+    }
+
+    fn app(name: &str) -> CurrentApp {
+        CurrentApp {
+            app_dir: name.into(),
+            locked_url: format!("file:///{name}/spin.lock"),
+        }
+    }
+
+    /// Answers the next upgrade, which must be a promotion of `expected`,
+    /// with `outcome`.
+    async fn answer_promotion(
+        upgrades: &mut Upgrades<HelpArgsOnlyTrigger>,
+        expected: &str,
+        outcome: Result<()>,
+    ) {
+        let Upgrade { action, result } = upgrades.recv().await.unwrap();
+        match action {
+            UpgradeAction::Promote { label, .. } => assert_eq!(expected, label),
+            _ => panic!("expected a promotion"),
+        }
+        result.send(outcome).unwrap();
+    }
+
+    #[tokio::test]
+    async fn promoting_makes_the_staged_version_current() {
+        let (reloader, mut upgrades) = reloader();
+        *reloader.staged.lock().await = Some(("canary".into(), app("next")));
+
+        let (promoted, ()) = tokio::join!(
+            reloader.promote("canary".into(), vec!["/health".into()]),
+            answer_promotion(&mut upgrades, "canary", Ok(())),
+        );
+        promoted.unwrap();
+        assert_eq!(
+            "file:///next/spin.lock",
+            reloader.current.lock().await.locked_url
+        );
+        assert!(reloader.staged.lock().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn a_rejected_promotion_keeps_the_staged_version() {
+        let (reloader, mut upgrades) = reloader();
+        *reloader.staged.lock().await = Some(("canary".into(), app("next")));
+
+        let (promoted, ()) = tokio::join!(
+            reloader.promote("canary".into(), vec!["/health".into()]),
+            answer_promotion(&mut upgrades, "canary", Err(anyhow::anyhow!("unhealthy"))),
+        );
+        assert_eq!("unhealthy", promoted.unwrap_err().to_string());
+        assert_eq!(
+            "file:///current/spin.lock",
+            reloader.current.lock().await.locked_url
+        );
+        assert!(reloader.staged.lock().await.is_some());
+    }
+
+    #[tokio::test]
+    async fn promoting_needs_a_version_staged_under_the_label() {
+        let (reloader, mut upgrades) = reloader();
+
+        let err = reloader.promote("canary".into(), vec![]).await.unwrap_err();
+        assert_eq!("no version is staged", err.to_string());
+
+        *reloader.staged.lock().await = Some(("canary".into(), app("next")));
+        let err = reloader.promote("other".into(), vec![]).await.unwrap_err();
+        assert_eq!(
+            "the staged version is labelled \"canary\", not \"other\"",
+            err.to_string()
+        );
+
+        // Neither was passed on to the executor.
+        assert!(upgrades.try_recv().is_err());
+        assert!(reloader.staged.lock().await.is_some());
+    }
+}
@@ -0,0 +1,174 @@
+//! Checks that a component's imports are interfaces this host provides.
+//!
+//! The linker matches imports by exact name, including the package version,
+//! so a component built against a newer (or much older) SDK fails with a
+//! bare "unknown import" error. Checking the imports up front lets us say
+//! which interface version was wanted, which versions this runtime has, and
+//! which SDK the component was built with.
+//!
+//! Components built against older, unversioned Spin interfaces are still
+//! supported: the host provides both, converting between them where needed
+//! (see the `v1` bindings in `spin-world`).
+
+use anyhow::{Context, Result};
+use wasmparser::{Parser, Payload};
+
+/// The versions of the `fermyon:spin` package provided to components.
+/// The empty string stands for the unversioned (Spin 1.x) interfaces.
+pub const SPIN_PACKAGE_VERSIONS: &[&str] = &["2.0.0", ""];
+
+/// Interfaces which are also provided at a newer version than the rest of
+/// the `fermyon:spin` package, as (interface, version).
+pub const SPIN_INTERFACE_VERSIONS: &[(&str, &str)] = &[
+    ("postgres", "3.0.0"),
+    ("redis", "3.0.0"),
+    ("key-value", "3.0.0"),
+    ("cache", "3.0.0"),
+    ("jobs", "3.0.0"),
+    ("workflows", "3.0.0"),
+    ("context", "3.0.0"),
+    ("lock", "3.0.0"),
+    ("app-state", "3.0.0"),
+    ("flags", "3.0.0"),
+    ("image", "3.0.0"),
+    ("crypto", "3.0.0"),
+    ("jwt", "3.0.0"),
+    ("password", "3.0.0"),
+    ("random", "3.0.0"),
+    ("timezone", "3.0.0"),
+    ("template", "3.0.0"),
+    ("html", "3.0.0"),
+    ("pdf", "3.0.0"),
+];
+
+/// The WASI version provided to components.
+pub const WASI_VERSION: &str = "0.2.0-rc-2023-10-18";
+
+/// The WASI packages provided to components.
+pub const WASI_PACKAGES: &[&str] = &[
+    "wasi:cli",
+    "wasi:clocks",
+    "wasi:filesystem",
+    "wasi:http",
+    "wasi:io",
+    "wasi:random",
+    "wasi:sockets",
+];
+
+const SDK_VERSION_EXPORT_PREFIX: &str = "spin-sdk-version-";
+
+/// Returns the version of a component import this host provides, if it does
+/// not provide the version that was asked for.
+///
+/// Imports from packages this host knows nothing about are not checked here;
+/// the linker reports those.
+pub fn unsupported_version(import: &str) -> Option<String> {
+    let (interface, version) = import.split_once('@').unwrap_or((import, ""));
+    let package = interface.split('/').next().unwrap_or(interface);
+    if package == "fermyon:spin" {
+        let name = interface.split('/').nth(1).unwrap_or_default();
+        let newer = SPIN_INTERFACE_VERSIONS
+            .iter()
+            .find(|(interface, _)| *interface == name)
+            .map(|(_, version)| *version);
+        if SPIN_PACKAGE_VERSIONS.contains(&version) || newer == Some(version) {
+            None
+        } else {
+            Some(newer.unwrap_or(SPIN_PACKAGE_VERSIONS[0]).to_owned())
+        }
+    } else if WASI_PACKAGES.contains(&package) && version != WASI_VERSION {
+        Some(WASI_VERSION.to_owned())
+    } else {
+        None
+    }
+}
+
+/// Returns the Spin SDK version a component was built with, if the SDK
+/// recorded it.
+pub fn sdk_version(wasm: &[u8]) -> Option<String> {
+    for payload in Parser::new(0).parse_all(wasm) {
+        let Ok(Payload::ExportSection(reader)) = payload else {
+            continue;
+        };
+        for export in reader.into_iter().flatten() {
+            if let Some(version) = export.name.strip_prefix(SDK_VERSION_EXPORT_PREFIX) {
+                // e.g. `2-0-pre0` => `2.0-pre0`
+                return Some(version.replacen('-', ".", 1));
+            }
+        }
+    }
+    None
+}
+
+/// Fails if the component imports a version of a Spin or WASI interface
+/// that this host does not provide.
+pub(crate) fn check_component_imports(wasm: &[u8]) -> Result<()> {
+    let mut depth = 0;
+    let mut mismatches = vec![];
+    for payload in Parser::new(0).parse_all(wasm) {
+        match payload.context("invalid component")? {
+            Payload::Version { .. } => depth += 1,
+            Payload::End(_) => depth -= 1,
+            Payload::ComponentImportSection(reader) if depth == 1 => {
+                for import in reader {
+                    let name = import?.name.0;
+                    if let Some(supported) = unsupported_version(name) {
+                        mismatches.push((name.to_owned(), supported));
+                    }
+                }
+            }
+            _ => (),
+        }
+    }
+
+    let Some((import, supported)) = mismatches.first() else {
+        return Ok(());
+    };
+    let built_with = match sdk_version(wasm) {
+        Some(version) => format!(" built with spin-sdk {version}"),
+        None => String::new(),
+    };
+    let interface = import.split('@').next().unwrap_or(import);
+    let others = match mismatches.len() {
+        1 => String::new(),
+        n => format!(" (and {} other import(s))", n - 1),
+    };
+    anyhow::bail!(
+        "The component{built_with} imports {import}{others}; this runtime supports {interface}@{supported}. Upgrade Spin, or rebuild the component with an SDK version that matches this runtime."
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn supported_imports_pass() {
+        assert_eq!(None, unsupported_version("fermyon:spin/postgres@3.0.0"));
+        assert_eq!(None, unsupported_version("fermyon:spin/redis@3.0.0"));
+        assert_eq!(None, unsupported_version("fermyon:spin/key-value@3.0.0"));
+        assert_eq!(None, unsupported_version("fermyon:spin/postgres@2.0.0"));
+        assert_eq!(None, unsupported_version("fermyon:spin/postgres"));
+        assert_eq!(
+            None,
+            unsupported_version("wasi:io/streams@0.2.0-rc-2023-10-18")
+        );
+        assert_eq!(None, unsupported_version("acme:widgets/frobnicate@9.0.0"));
+    }
+
+    #[test]
+    fn unsupported_versions_report_the_supported_one() {
+        assert_eq!(
+            Some("3.0.0".to_owned()),
+            unsupported_version("fermyon:spin/postgres@4.0.0")
+        );
+        assert_eq!(
+            Some("2.0.0".to_owned()),
+            unsupported_version("fermyon:spin/sqlite@3.0.0")
+        );
+        assert_eq!(
+            Some(WASI_VERSION.to_owned()),
+            unsupported_version("wasi:http/types@0.2.0")
+        );
+    }
+}
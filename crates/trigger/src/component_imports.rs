@@ -0,0 +1,446 @@
+//! Calls from one component of an app to another.
+//!
+//! A component may import interfaces which other components of the app
+//! export, listing them in its manifest as, e.g.,
+//! `component_imports = { "acme:orders/api" = "orders" }`, named as the
+//! component imports them, including any version. The components aren't
+//! composed: each keeps its own store, with its own files, variables and
+//! allowed hosts. When an instance of the importing component is prepared,
+//! an instance of each component it imports from is prepared alongside it,
+//! and the host passes calls to the imported functions on to that instance,
+//! in the same process.
+//!
+//! Host functions aren't told the types of their results, so imported
+//! functions may only return values which have no types of their own:
+//! numbers, chars, bools and strings. Their arguments may be of any type
+//! except resources.
+
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    marker::PhantomData,
+};
+
+use anyhow::{bail, ensure, Context, Result};
+use futures::{future::BoxFuture, FutureExt};
+use spin_app::{App, MetadataKey};
+use spin_core::{
+    wasmtime::{
+        component::{Enum, Flags, List, OptionVal, Record, ResultVal, Tuple, Type, Val, Variant},
+        StoreContextMut,
+    },
+    Data, Engine, HostComponent, HostComponentDataHandle, Instance, Linker,
+    OutboundWasiHttpHandler, Store,
+};
+use wit_parser::{Function, FunctionKind, Resolve, TypeDefKind, WorldId, WorldItem};
+
+use crate::{EitherInstance, TriggerAppEngine, TriggerExecutor};
+
+/// Interface name -> the ID of the component it is imported from.
+pub(crate) const COMPONENT_IMPORTS_KEY: MetadataKey<BTreeMap<String, String>> =
+    MetadataKey::new("component_imports");
+
+/// Holds the instances an importing component calls into, in its store.
+pub(crate) struct ComponentImports<T>(PhantomData<fn() -> T>);
+
+impl<T> Default for ComponentImports<T> {
+    fn default() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<T: Send + 'static> HostComponent for ComponentImports<T> {
+    type Data = Imported<T>;
+
+    fn add_to_linker<U: Send>(
+        _linker: &mut Linker<U>,
+        _get: impl Fn(&mut Data<U>) -> &mut Self::Data + Send + Sync + Copy + 'static,
+    ) -> Result<()> {
+        // The imported functions are defined by `link`, once the components
+        // which import them have been loaded.
+        Ok(())
+    }
+
+    fn build_data(&self) -> Self::Data {
+        Imported::default()
+    }
+}
+
+pub(crate) struct Imported<T> {
+    // Interface name -> component ID.
+    targets: HashMap<String, String>,
+    // Component ID -> its instance, unless it is handling a call.
+    instances: HashMap<String, Option<(Instance, Store<T>)>>,
+}
+
+impl<T> Default for Imported<T> {
+    fn default() -> Self {
+        Self {
+            targets: HashMap::new(),
+            instances: HashMap::new(),
+        }
+    }
+}
+
+/// Defines the functions the app's components import from each other. Each
+/// interface is defined once, with the types the first component to import it
+/// expects; components which import it later must expect the same types.
+pub(crate) async fn link<T: OutboundWasiHttpHandler + Send + Sync + 'static>(
+    engine: &mut Engine<T>,
+    app: &App,
+) -> Result<()> {
+    let handle = engine.find_host_component_handle::<ComponentImports<T>>();
+    let mut defined = HashSet::new();
+    for component in app.components() {
+        let imports = component
+            .get_metadata(COMPONENT_IMPORTS_KEY)?
+            .unwrap_or_default();
+        if imports.is_empty() {
+            continue;
+        }
+        let context = || {
+            format!(
+                "Failed to link the imports of component '{}'",
+                component.id()
+            )
+        };
+        let handle = handle
+            .context("this trigger does not support `component_imports`")
+            .with_context(context)?;
+
+        let (_, wasm) = crate::loader::read_component(component.source())
+            .await
+            .with_context(context)?;
+        let decoded = wit_component::decode(&wasm).with_context(context)?;
+        let wit_component::DecodedWasm::Component(resolve, world) = decoded else {
+            bail!("{}: it is not a component", context());
+        };
+        // Function types are looked up in the compiled component itself.
+        let compiled = component.load_component(engine).await?;
+
+        engine
+            .link_import(|linker, _| {
+                for interface in imports.keys() {
+                    if !defined.insert(interface.clone()) {
+                        continue;
+                    }
+                    let functions = imported_functions(&resolve, world, interface)?;
+                    let mut instance = linker.instance(interface)?;
+                    for name in functions {
+                        let call = call_import(handle, interface.clone(), name.clone());
+                        instance.func_new(&compiled, &name, call)?;
+                    }
+                }
+                Ok(())
+            })
+            .with_context(context)?;
+    }
+    Ok(())
+}
+
+impl<Executor: TriggerExecutor> TriggerAppEngine<Executor> {
+    /// Prepares an instance of each component `component_id` imports from,
+    /// for the calls it makes from `store`.
+    pub(crate) fn prepare_imports<'a>(
+        &'a self,
+        component_id: &'a str,
+        store: &'a mut Store<Executor::RuntimeData>,
+    ) -> BoxFuture<'a, Result<()>> {
+        // Boxed, as the imported components' own imports are prepared in turn.
+        async move {
+            let imports = self
+                .get_component(component_id)?
+                .get_metadata(COMPONENT_IMPORTS_KEY)?
+                .unwrap_or_default();
+            if imports.is_empty() {
+                return Ok(());
+            }
+            let handle = self
+                .engine
+                .find_host_component_handle::<ComponentImports<Executor::RuntimeData>>()
+                .context("this trigger does not support `component_imports`")?;
+
+            let mut imported = Imported::default();
+            for (interface, target) in imports {
+                if !imported.instances.contains_key(&target) {
+                    let (instance, target_store) = self.prepare_instance(&target).await?;
+                    let EitherInstance::Component(instance) = instance else {
+                        bail!("component '{target}' is a module, so can't export {interface}");
+                    };
+                    imported
+                        .instances
+                        .insert(target.clone(), Some((instance, target_store)));
+                }
+                imported.targets.insert(interface, target);
+            }
+            store.host_components_data().set(handle, imported);
+            Ok(())
+        }
+        .boxed()
+    }
+}
+
+/// Returns the names of the functions of `interface`, as the component
+/// imports it, checking that they can be called across components.
+fn imported_functions(resolve: &Resolve, world: WorldId, interface: &str) -> Result<Vec<String>> {
+    let id = resolve.worlds[world]
+        .imports
+        .iter()
+        .find_map(|(key, item)| match item {
+            WorldItem::Interface(id) if resolve.name_world_key(key) == interface => Some(*id),
+            _ => None,
+        })
+        .with_context(|| format!("the component does not import {interface}"))?;
+    resolve.interfaces[id]
+        .functions
+        .values()
+        .map(|func| {
+            check_function(resolve, func).with_context(|| {
+                format!(
+                    "{interface}#{} can't be called across components",
+                    func.name
+                )
+            })?;
+            Ok(func.name.clone())
+        })
+        .collect()
+}
+
+fn check_function(resolve: &Resolve, func: &Function) -> Result<()> {
+    ensure!(
+        matches!(func.kind, FunctionKind::Freestanding)
+            && !func.params.iter().any(|(_, ty)| has_resources(resolve, ty)),
+        "resources can't be passed between components"
+    );
+    ensure!(
+        func.results.iter_types().all(|ty| is_plain(resolve, ty)),
+        "it may only return numbers, chars, bools and strings"
+    );
+    Ok(())
+}
+
+/// Whether a type has no type definition of its own, once aliases are
+/// followed.
+fn is_plain(resolve: &Resolve, ty: &wit_parser::Type) -> bool {
+    let wit_parser::Type::Id(id) = ty else {
+        return true;
+    };
+    match &resolve.types[*id].kind {
+        TypeDefKind::Type(ty) => is_plain(resolve, ty),
+        _ => false,
+    }
+}
+
+fn has_resources(resolve: &Resolve, ty: &wit_parser::Type) -> bool {
+    let wit_parser::Type::Id(id) = ty else {
+        return false;
+    };
+    match &resolve.types[*id].kind {
+        TypeDefKind::Resource | TypeDefKind::Handle(_) => true,
+        TypeDefKind::Type(ty) | TypeDefKind::List(ty) | TypeDefKind::Option(ty) => {
+            has_resources(resolve, ty)
+        }
+        TypeDefKind::Record(record) => record.fields.iter().any(|f| has_resources(resolve, &f.ty)),
+        TypeDefKind::Tuple(tuple) => tuple.types.iter().any(|ty| has_resources(resolve, ty)),
+        TypeDefKind::Variant(variant) => variant
+            .cases
+            .iter()
+            .filter_map(|case| case.ty.as_ref())
+            .any(|ty| has_resources(resolve, ty)),
+        TypeDefKind::Result(result) => result
+            .ok
+            .iter()
+            .chain(&result.err)
+            .any(|ty| has_resources(resolve, ty)),
+        _ => false,
+    }
+}
+
+/// Returns the host function for `interface#name`, which calls the same
+/// function of the instance the calling store imports `interface` from.
+fn call_import<T: Send + 'static>(
+    handle: HostComponentDataHandle<ComponentImports<T>>,
+    interface: String,
+    name: String,
+) -> impl Fn(StoreContextMut<'_, Data<T>>, &[Val], &mut [Val]) -> Result<()> + Send + Sync + 'static
+{
+    move |mut store, params, results| {
+        let imported = store
+            .data_mut()
+            .host_components_data()
+            .get_or_insert(handle);
+        let target = imported
+            .targets
+            .get(&interface)
+            .with_context(|| format!("no component was prepared to export {interface}"))?
+            .clone();
+        let (instance, mut target_store) = imported
+            .instances
+            .get_mut(&target)
+            .and_then(Option::take)
+            .with_context(|| format!("component '{target}' is already handling a call"))?;
+
+        // Host functions are synchronous, so the call blocks this thread,
+        // letting the runtime move other tasks off it meanwhile.
+        let values = tokio::task::block_in_place(|| {
+            tokio::runtime::Handle::current().block_on(call_export(
+                &instance,
+                &mut target_store,
+                &interface,
+                &name,
+                params,
+            ))
+        });
+
+        store
+            .data_mut()
+            .host_components_data()
+            .get_or_insert(handle)
+            .instances
+            .insert(target.clone(), Some((instance, target_store)));
+        let values = values.with_context(|| format!("component '{target}' failed"))?;
+        ensure!(
+            values.len() == results.len(),
+            "{interface}#{name} returned {} values, where {} were expected",
+            values.len(),
+            results.len()
+        );
+        results.clone_from_slice(&values);
+        Ok(())
+    }
+}
+
+async fn call_export<T: Send>(
+    instance: &Instance,
+    store: &mut Store<T>,
+    interface: &str,
+    name: &str,
+    params: &[Val],
+) -> Result<Vec<Val>> {
+    let func = {
+        let mut exports = instance.exports(&mut *store);
+        exports
+            .instance(interface)
+            .and_then(|mut instance| instance.func(name))
+    }
+    .with_context(|| format!("it does not export {interface}#{name}"))?;
+
+    let types = func.params(&*store);
+    ensure!(
+        types.len() == params.len(),
+        "its {interface}#{name} takes {} arguments, where {} were given",
+        types.len(),
+        params.len()
+    );
+    let params = params
+        .iter()
+        .zip(types.iter())
+        .map(|(val, ty)| convert(val, ty))
+        .collect::<Result<Vec<_>>>()?;
+    let mut results = vec![Val::Bool(false); func.results(&*store).len()];
+    func.call_async(&mut *store, &params, &mut results).await?;
+    func.post_return_async(&mut *store).await?;
+    Ok(results)
+}
+
+/// Rebuilds `val`, whose type is the calling component's, with the
+/// equivalent type `ty` of the called component.
+fn convert(val: &Val, ty: &Type) -> Result<Val> {
+    Ok(match (val, ty) {
+        (Val::List(list), Type::List(ty)) => {
+            let values = list
+                .iter()
+                .map(|val| convert(val, &ty.ty()))
+                .collect::<Result<_>>()?;
+            Val::List(List::new(ty, values)?)
+        }
+        (Val::Record(record), Type::Record(ty)) => {
+            let fields = record
+                .fields()
+                .zip(ty.fields())
+                .map(|((name, val), field)| Ok((name, convert(val, &field.ty)?)))
+                .collect::<Result<Vec<_>>>()?;
+            Val::Record(Record::new(ty, fields)?)
+        }
+        (Val::Tuple(tuple), Type::Tuple(ty)) => {
+            let values = tuple
+                .values()
+                .iter()
+                .zip(ty.types())
+                .map(|(val, ty)| convert(val, &ty))
+                .collect::<Result<_>>()?;
+            Val::Tuple(Tuple::new(ty, values)?)
+        }
+        (Val::Variant(variant), Type::Variant(ty)) => {
+            let case = ty
+                .cases()
+                .find(|case| case.name == variant.discriminant())
+                .with_context(|| format!("unknown case {:?}", variant.discriminant()))?;
+            let payload = match (variant.payload(), case.ty) {
+                (Some(val), Some(ty)) => Some(convert(val, &ty)?),
+                _ => None,
+            };
+            Val::Variant(Variant::new(ty, variant.discriminant(), payload)?)
+        }
+        (Val::Enum(value), Type::Enum(ty)) => Val::Enum(Enum::new(ty, value.discriminant())?),
+        (Val::Option(option), Type::Option(ty)) => {
+            let value = option
+                .value()
+                .map(|val| convert(val, &ty.ty()))
+                .transpose()?;
+            Val::Option(OptionVal::new(ty, value)?)
+        }
+        (Val::Result(result), Type::Result(ty)) => {
+            let payload = |val: Option<&Val>, ty: Option<Type>| match (val, ty) {
+                (Some(val), Some(ty)) => convert(val, &ty).map(Some),
+                _ => Ok(None),
+            };
+            let value = match result.value() {
+                Ok(val) => Ok(payload(val, ty.ok())?),
+                Err(val) => Err(payload(val, ty.err())?),
+            };
+            Val::Result(ResultVal::new(ty, value)?)
+        }
+        (Val::Flags(flags), Type::Flags(ty)) => {
+            Val::Flags(Flags::new(ty, &flags.flags().collect::<Vec<_>>())?)
+        }
+        (Val::Resource(_), _) => bail!("resources can't be passed between components"),
+        // Numbers, chars, bools and strings have no types of their own.
+        (val, _) => val.clone(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use wit_parser::UnresolvedPackage;
+
+    use super::*;
+
+    #[test]
+    fn only_plain_results_are_supported() {
+        let wit = r#"
+            package acme:orders;
+
+            interface api {
+                resource order {}
+                record item { sku: string, count: u32 }
+
+                place: func(items: list<item>) -> string;
+                total: func(items: list<item>) -> u64;
+                lookup: func(id: string) -> option<item>;
+                open: func(o: borrow<order>) -> bool;
+            }
+        "#;
+        let mut resolve = Resolve::default();
+        let package = UnresolvedPackage::parse(Path::new("orders.wit"), wit).unwrap();
+        let package = resolve.push(package).unwrap();
+        let api = resolve.packages[package].interfaces["api"];
+
+        let check = |name: &str| check_function(&resolve, &resolve.interfaces[api].functions[name]);
+        check("place").unwrap();
+        check("total").unwrap();
+        check("lookup").unwrap_err();
+        check("open").unwrap_err();
+    }
+}
@@ -0,0 +1,108 @@
+//! The `context` interface, which tells a component about the request it is
+//! handling and where it is running.
+//!
+//! The application and component are filled in when each instance is
+//! prepared; triggers fill in what they know about the request, e.g.
+//!
+//! ```ignore
+//! if let Some(handle) = engine.engine.find_host_component_handle::<Arc<RequestContextComponent>>() {
+//!     store.host_components_data().get_or_insert(handle).remote_address = Some(addr.to_string());
+//! }
+//! ```
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::Result;
+use spin_app::{AppComponent, DynamicHostComponent, APP_NAME_KEY, APP_VERSION_KEY};
+use spin_core::{async_trait, HostComponent};
+use spin_world::v3::context;
+
+pub use context::TlsInfo;
+
+pub struct RequestContextComponent {
+    trigger_type: &'static str,
+}
+
+impl RequestContextComponent {
+    /// A component which reports requests as coming from the given type of
+    /// trigger.
+    pub fn new(trigger_type: &'static str) -> Self {
+        Self { trigger_type }
+    }
+}
+
+impl HostComponent for RequestContextComponent {
+    type Data = RequestContext;
+
+    fn add_to_linker<T: Send>(
+        linker: &mut spin_core::Linker<T>,
+        get: impl Fn(&mut spin_core::Data<T>) -> &mut Self::Data + Send + Sync + Copy + 'static,
+    ) -> anyhow::Result<()> {
+        context::add_to_linker(linker, get)
+    }
+
+    fn build_data(&self) -> Self::Data {
+        RequestContext {
+            trigger_type: self.trigger_type.to_owned(),
+            ..Default::default()
+        }
+    }
+}
+
+impl DynamicHostComponent for RequestContextComponent {
+    fn update_data(&self, data: &mut Self::Data, component: &AppComponent) -> anyhow::Result<()> {
+        data.app_name = component.app.require_metadata(APP_NAME_KEY)?;
+        data.app_version = component.app.get_metadata(APP_VERSION_KEY)?;
+        data.component_id = component.id().to_owned();
+        Ok(())
+    }
+}
+
+/// What a component is told about the request it is handling.
+#[derive(Default)]
+pub struct RequestContext {
+    trigger_type: String,
+    app_name: String,
+    app_version: Option<String>,
+    component_id: String,
+    /// When the request must be handled by.
+    pub deadline: Option<SystemTime>,
+    /// The address of the client which made the request.
+    pub remote_address: Option<String>,
+    /// The TLS connection the request arrived on.
+    pub tls: Option<TlsInfo>,
+}
+
+#[async_trait]
+impl context::Host for RequestContext {
+    async fn deadline(&mut self) -> Result<Option<u64>> {
+        Ok(self.deadline.map(|deadline| {
+            let millis = deadline.duration_since(UNIX_EPOCH).unwrap_or_default();
+            millis.as_millis().try_into().unwrap_or(u64::MAX)
+        }))
+    }
+
+    async fn remote_address(&mut self) -> Result<Option<String>> {
+        Ok(self.remote_address.clone())
+    }
+
+    async fn tls(&mut self) -> Result<Option<TlsInfo>> {
+        Ok(self.tls.clone())
+    }
+
+    async fn trigger_type(&mut self) -> Result<String> {
+        Ok(self.trigger_type.clone())
+    }
+
+    async fn app_name(&mut self) -> Result<String> {
+        Ok(self.app_name.clone())
+    }
+
+    async fn app_version(&mut self) -> Result<Option<String>> {
+        Ok(self.app_version.clone())
+    }
+
+    async fn component_id(&mut self) -> Result<String> {
+        Ok(self.component_id.clone())
+    }
+}
@@ -0,0 +1,180 @@
+//! Deterministic mode, for reproducible test runs.
+//!
+//! With `--deterministic`, every component sees WASI random sources seeded
+//! from a fixed seed and clocks frozen at a fixed time, and invocations run
+//! one at a time in the order they arrive. A component's own `wasi`
+//! `random_seed` and `frozen_time` settings are kept, as they are already
+//! deterministic.
+//!
+//! Like the manifest's `wasi` settings, this is only supported for
+//! components which use WASI Preview 2.
+
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use spin_app::AppComponent;
+use spin_core::StoreBuilder;
+
+use crate::{wasi_config::WASI_KEY, TriggerHooks};
+
+/// The time the clocks are frozen at: 2000-01-01T00:00:00Z.
+const DETERMINISTIC_TIME: Duration = Duration::from_secs(946_684_800);
+
+/// Implements TriggerHooks, seeding the random sources and freezing the
+/// clocks of every component. Running invocations one at a time is up to the
+/// caller, with [`crate::admin::limit_concurrent_invocations`].
+pub(crate) struct DeterministicTriggerHooks {
+    seed: u64,
+}
+
+impl DeterministicTriggerHooks {
+    pub(crate) fn new(seed: u64) -> Self {
+        Self { seed }
+    }
+}
+
+impl TriggerHooks for DeterministicTriggerHooks {
+    fn component_store_builder(
+        &self,
+        component: &AppComponent,
+        store_builder: &mut StoreBuilder,
+    ) -> Result<()> {
+        let wasi = component.get_metadata(WASI_KEY)?.unwrap_or_default();
+        let context = || {
+            format!(
+                "Component '{}' can't be run in deterministic mode",
+                component.id()
+            )
+        };
+        if wasi.random_seed.is_none() {
+            store_builder.random_seed(self.seed).with_context(context)?;
+        }
+        if wasi.frozen_time.is_none() {
+            store_builder
+                .frozen_clocks(DETERMINISTIC_TIME)
+                .with_context(context)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use spin_app::AppLoader;
+    use spin_core::{Config, Engine, Store, WasiVersion};
+    use wasmtime_wasi::preview2::bindings::{
+        clocks::{monotonic_clock, wall_clock},
+        random::random,
+    };
+
+    use super::*;
+    use crate::loader::TriggerLoader;
+
+    /// What a component sees of its random sources and clocks.
+    #[derive(Debug, PartialEq)]
+    struct Observed {
+        random: Vec<u64>,
+        wall_clock: (u64, u32),
+        monotonic_clock: u64,
+    }
+
+    /// Writes an app with one component, `test`, with the given `wasi`
+    /// settings, returning a loader for it.
+    fn app_loader(dir: &std::path::Path, wasi: serde_json::Value) -> AppLoader {
+        let locked = serde_json::json!({
+            "spin_lock_version": 0,
+            "triggers": [],
+            "components": [{
+                "id": "test",
+                "metadata": { "wasi": wasi },
+                "source": {
+                    "content_type": "application/wasm",
+                    "source": "file:///test.wasm",
+                },
+            }],
+        });
+        std::fs::write(dir.join("spin.lock"), locked.to_string()).unwrap();
+        AppLoader::new(TriggerLoader::new(dir, false))
+    }
+
+    /// Sets up a store for `test` as the trigger does in deterministic mode,
+    /// and reads its random sources and clocks twice over.
+    async fn observe(seed: u64, wasi: serde_json::Value) -> Vec<Observed> {
+        let dir = tempfile::tempdir().unwrap();
+        let loader = app_loader(dir.path(), wasi);
+        let url = format!("file://{}", dir.path().join("spin.lock").display());
+        let app = loader.load_app(url).await.unwrap();
+        let component = app.get_component("test").unwrap();
+
+        let engine: Engine<()> = Engine::builder(&Config::default()).unwrap().build();
+        let mut store_builder = engine.store_builder(WasiVersion::Preview2);
+        crate::wasi_config::apply(&component, &mut store_builder).unwrap();
+        DeterministicTriggerHooks::new(seed)
+            .component_store_builder(&component, &mut store_builder)
+            .unwrap();
+        let mut store: Store<()> = store_builder.build().unwrap();
+        let data = store.as_mut().data_mut();
+        (0..2)
+            .map(|_| {
+                let now = wall_clock::Host::now(data).unwrap();
+                Observed {
+                    random: (0..4)
+                        .map(|_| random::Host::get_random_u64(data).unwrap())
+                        .collect(),
+                    wall_clock: (now.seconds, now.nanoseconds),
+                    monotonic_clock: monotonic_clock::Host::now(data).unwrap(),
+                }
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn runs_with_the_same_seed_see_the_same_values() {
+        let first = observe(7, serde_json::json!({})).await;
+        assert_eq!(first, observe(7, serde_json::json!({})).await);
+        assert_ne!(first, observe(8, serde_json::json!({})).await);
+
+        // Within a run, the random numbers vary but the clocks don't move.
+        assert_ne!(first[0].random, first[1].random);
+        assert_eq!((DETERMINISTIC_TIME.as_secs(), 0), first[0].wall_clock);
+        assert_eq!(first[0].wall_clock, first[1].wall_clock);
+        assert_eq!(first[0].monotonic_clock, first[1].monotonic_clock);
+    }
+
+    #[tokio::test]
+    async fn component_settings_are_kept() {
+        let wasi = serde_json::json!({ "random_seed": 42, "frozen_time": 1_700_000_000 });
+        let observed = observe(7, wasi.clone()).await;
+        assert_eq!(observed, observe(8, wasi).await);
+        assert_eq!((1_700_000_000, 0), observed[0].wall_clock);
+    }
+
+    #[tokio::test]
+    async fn invocations_run_one_at_a_time_in_order() {
+        // As the trigger command does in deterministic mode.
+        crate::admin::limit_concurrent_invocations(1).unwrap();
+
+        async fn run() -> Vec<String> {
+            let log = Arc::new(Mutex::new(vec![]));
+            let invocations = (0..3).map(|i| {
+                let log = log.clone();
+                crate::admin::track_invocation("test", async move {
+                    log.lock().unwrap().push(format!("start {i}"));
+                    tokio::time::sleep(Duration::from_millis(10)).await;
+                    log.lock().unwrap().push(format!("end {i}"));
+                })
+            });
+            futures::future::join_all(invocations).await;
+            Arc::try_unwrap(log).unwrap().into_inner().unwrap()
+        }
+
+        let first = run().await;
+        assert_eq!(
+            vec!["start 0", "end 0", "start 1", "end 1", "start 2", "end 2"],
+            first
+        );
+        assert_eq!(first, run().await);
+    }
+}
@@ -0,0 +1,155 @@
+//! Pools of component instances prepared ahead of the requests which use
+//! them.
+//!
+//! Each instance still serves a single request. A pool keeps between
+//! `min_ready` and `max_ready` instances ready: it grows when requests find
+//! it empty and have to wait for an instance to be prepared, and instances
+//! which go unused for `idle_timeout` are torn down, so that memory use
+//! follows the load rather than its peak.
+
+use std::{
+    collections::VecDeque,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use anyhow::{ensure, Result};
+use spin_core::Store;
+
+use crate::EitherInstance;
+
+/// How many instances of each component to keep ready.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InstancePoolConfig {
+    /// Instances kept ready however idle the component.
+    pub min_ready: usize,
+    /// The most instances kept ready, however busy the component.
+    pub max_ready: usize,
+    /// How long an instance may stay unused before it is torn down, unless
+    /// it is one of the `min_ready`.
+    pub idle_timeout: Duration,
+}
+
+impl InstancePoolConfig {
+    pub fn new(min_ready: usize, max_ready: usize, idle_timeout: Duration) -> Result<Self> {
+        ensure!(
+            min_ready <= max_ready,
+            "the minimum number of ready instances ({min_ready}) is more than the maximum ({max_ready})"
+        );
+        Ok(Self {
+            min_ready,
+            max_ready,
+            idle_timeout,
+        })
+    }
+}
+
+pub(crate) struct InstancePool<T> {
+    config: InstancePoolConfig,
+    state: Mutex<PoolState<T>>,
+}
+
+struct PoolState<T> {
+    // Oldest first. Instances are taken from the back, so that those at the
+    // front are the ones which go idle.
+    ready: VecDeque<ReadyInstance<T>>,
+    // How many instances the pool is trying to keep ready.
+    target: usize,
+    // Requests which found the pool empty since it was last maintained.
+    misses: usize,
+}
+
+struct ReadyInstance<T> {
+    instance: EitherInstance,
+    store: Store<T>,
+    since: Instant,
+}
+
+impl<T> InstancePool<T> {
+    pub(crate) fn new(config: InstancePoolConfig) -> Self {
+        Self {
+            config,
+            state: Mutex::new(PoolState {
+                ready: VecDeque::new(),
+                target: config.min_ready,
+                misses: 0,
+            }),
+        }
+    }
+
+    /// Takes a ready instance. If there is none, the caller must prepare
+    /// one itself, and the pool will grow.
+    pub(crate) fn take(&self) -> Option<(EitherInstance, Store<T>)> {
+        let mut state = self.state.lock().unwrap();
+        match state.ready.pop_back() {
+            Some(ready) => Some((ready.instance, ready.store)),
+            None => {
+                state.misses += 1;
+                None
+            }
+        }
+    }
+
+    /// Adds a newly prepared instance.
+    pub(crate) fn put(&self, instance: EitherInstance, store: Store<T>) {
+        self.state.lock().unwrap().ready.push_back(ReadyInstance {
+            instance,
+            store,
+            since: Instant::now(),
+        });
+    }
+
+    /// Resizes the pool for the demand since it was last maintained, tearing
+    /// down idle instances, and returns how many instances to prepare.
+    pub(crate) fn rescale(&self, now: Instant) -> usize {
+        let mut state = self.state.lock().unwrap();
+        let misses = std::mem::take(&mut state.misses);
+        state.target = (state.target + misses).min(self.config.max_ready);
+
+        while state.ready.len() > self.config.min_ready {
+            match state.ready.front() {
+                Some(oldest) if now - oldest.since >= self.config.idle_timeout => {
+                    state.ready.pop_front();
+                    state.target = state.target.saturating_sub(1);
+                }
+                _ => break,
+            }
+        }
+        state.target = state.target.max(self.config.min_ready);
+
+        state.target.saturating_sub(state.ready.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pool(min_ready: usize, max_ready: usize) -> InstancePool<()> {
+        let config = InstancePoolConfig::new(min_ready, max_ready, Duration::from_secs(60));
+        InstancePool::new(config.unwrap())
+    }
+
+    #[test]
+    fn pools_grow_with_misses_up_to_the_maximum() {
+        let pool = pool(1, 3);
+        let now = Instant::now();
+        assert_eq!(1, pool.rescale(now));
+
+        assert!(pool.take().is_none());
+        assert!(pool.take().is_none());
+        assert_eq!(3, pool.rescale(now));
+
+        for _ in 0..5 {
+            assert!(pool.take().is_none());
+        }
+        assert_eq!(3, pool.rescale(now));
+        assert_eq!(3, pool.rescale(now));
+    }
+
+    #[test]
+    fn minimum_must_not_exceed_maximum() {
+        assert!(InstancePoolConfig::new(2, 1, Duration::ZERO).is_err());
+        assert!(InstancePoolConfig::new(0, 0, Duration::ZERO).is_ok());
+    }
+}
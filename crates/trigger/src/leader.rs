@@ -0,0 +1,51 @@
+//! Leader election for scheduled invocations.
+//!
+//! When a trigger which fires on a schedule runs on several replicas of an
+//! application, every replica sees each tick of each schedule. Ticks are
+//! numbered from the Unix epoch, so that replicas agree which tick is which,
+//! and a replica claims a tick with a lock from the runtime config's
+//! `[locks]` backend before firing it, so that each tick fires on only one
+//! replica. See [`crate::TriggerAppEngine::claim_tick`].
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Returns the number of the next tick of a schedule firing every
+/// `interval`, and how long there is until it.
+pub fn next_tick(interval: Duration, now: SystemTime) -> (u64, Duration) {
+    let interval = interval.as_millis().max(1);
+    let now = now
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis();
+    let tick = now / interval + 1;
+    let until = Duration::from_millis((tick * interval - now) as u64);
+    (tick as u64, until)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ticks_are_aligned_to_the_epoch() {
+        let interval = Duration::from_secs(10);
+        let at = |millis| UNIX_EPOCH + Duration::from_millis(millis);
+
+        assert_eq!(
+            (1, Duration::from_secs(10)),
+            next_tick(interval, UNIX_EPOCH)
+        );
+        assert_eq!(
+            (101, Duration::from_millis(7_500)),
+            next_tick(interval, at(1_002_500))
+        );
+        assert_eq!(
+            (101, Duration::from_millis(1)),
+            next_tick(interval, at(1_009_999))
+        );
+        assert_eq!(
+            (102, Duration::from_secs(10)),
+            next_tick(interval, at(1_010_000))
+        );
+    }
+}
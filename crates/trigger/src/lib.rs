@@ -1,24 +1,55 @@
+pub mod admin;
 pub mod cli;
+pub mod compat;
+mod component_imports;
+pub mod context;
+mod describe;
+mod deterministic;
+mod instance_pool;
+pub mod leader;
 pub mod loader;
+pub mod migrate;
+mod profiling;
 mod runtime_config;
+pub mod sandbox;
+pub mod seed;
+#[cfg(unix)]
+mod socket;
 mod stdio;
+pub mod upgrade;
+mod wasi_config;
+
+use std::{
+    collections::HashMap,
+    marker::PhantomData,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
-use std::{collections::HashMap, marker::PhantomData};
-
-use anyhow::{Context, Result};
+use anyhow::{anyhow, bail, Context, Result};
 pub use async_trait::async_trait;
 use indexmap::IndexMap;
 use runtime_config::llm::LLmOptions;
 use serde::de::DeserializeOwned;
 
-use spin_app::{App, AppComponent, AppLoader, AppTrigger, Loader, OwnedApp, APP_NAME_KEY};
+use spin_app::{
+    App, AppComponent, AppLoader, AppTrigger, Loader, MetadataKey, OwnedApp, APP_NAME_KEY,
+};
 use spin_core::{
     Config, Engine, EngineBuilder, Instance, InstancePre, ModuleInstance, ModuleInstancePre,
     OutboundWasiHttpHandler, Store, StoreBuilder, WasiVersion,
 };
+use spin_geoip::GeoIp;
+use spin_jobs::{Job, JobStore, INBOUND_JOB_EXPORT, JOB_TARGETS_KEY, MAX_ATTEMPTS};
+use spin_locks::{LockManager, MemoryLocks};
 
+pub use crate::instance_pool::InstancePoolConfig;
 pub use crate::runtime_config::RuntimeConfig;
 
+use crate::instance_pool::InstancePool;
+
+const MAX_SLICE_KEY: MetadataKey<u64> = MetadataKey::new("max_slice_ms");
+
 pub enum EitherInstancePre<T> {
     Component(InstancePre<T>),
     Module(ModuleInstancePre<T>),
@@ -42,6 +73,33 @@ pub trait TriggerExecutor: Sized + Send + Sync {
     /// Run the trigger executor.
     async fn run(self, config: Self::RunConfig) -> Result<()>;
 
+    /// Run the trigger executor, replacing the running application with
+    /// each new version received from `upgrades`.
+    ///
+    /// The default implementation rejects all upgrades.
+    async fn run_with_upgrades(
+        self,
+        config: Self::RunConfig,
+        mut upgrades: upgrade::Upgrades<Self>,
+    ) -> Result<()>
+    where
+        Self::RunConfig: Send,
+    {
+        let reject_upgrades = async move {
+            while let Some(upgrade) = upgrades.recv().await {
+                _ = upgrade.result.send(Err(anyhow::anyhow!(
+                    "the {} trigger does not support upgrades",
+                    Self::TRIGGER_TYPE
+                )));
+            }
+            std::future::pending::<()>().await
+        };
+        tokio::select! {
+            res = self.run(config) => res,
+            _ = reject_upgrades => unreachable!(),
+        }
+    }
+
     /// Make changes to the ExecutionContext using the given Builder.
     fn configure_engine(_builder: &mut EngineBuilder<Self::RuntimeData>) -> Result<()> {
         Ok(())
@@ -66,6 +124,10 @@ pub struct TriggerExecutorBuilder<Executor: TriggerExecutor> {
     config: Config,
     hooks: Vec<Box<dyn TriggerHooks>>,
     disable_default_host_components: bool,
+    instance_pool: Option<InstancePoolConfig>,
+    print_config: bool,
+    migrate: bool,
+    pdf_renderer: Option<Arc<dyn spin_pdf::PdfRenderer>>,
     _phantom: PhantomData<Executor>,
 }
 
@@ -77,6 +139,10 @@ impl<Executor: TriggerExecutor> TriggerExecutorBuilder<Executor> {
             config: Default::default(),
             hooks: Default::default(),
             disable_default_host_components: false,
+            instance_pool: None,
+            print_config: false,
+            migrate: false,
+            pdf_renderer: None,
             _phantom: PhantomData,
         }
     }
@@ -98,6 +164,34 @@ impl<Executor: TriggerExecutor> TriggerExecutorBuilder<Executor> {
         self
     }
 
+    /// Keep instances of each component ready ahead of requests. See
+    /// [`TriggerAppEngine::maintain_instance_pools`].
+    pub fn instance_pool(&mut self, config: InstancePoolConfig) -> &mut Self {
+        self.instance_pool = Some(config);
+        self
+    }
+
+    /// Print the fully resolved application configuration to stdout once
+    /// the application is loaded.
+    pub fn print_config(&mut self) -> &mut Self {
+        self.print_config = true;
+        self
+    }
+
+    /// Apply the components' pending database migrations once the
+    /// application is loaded. See [`migrate::migrate`].
+    pub fn migrate(&mut self) -> &mut Self {
+        self.migrate = true;
+        self
+    }
+
+    /// Render PDFs for components with `renderer`, rather than with the
+    /// renderer in the runtime config.
+    pub fn pdf_renderer(&mut self, renderer: Arc<dyn spin_pdf::PdfRenderer>) -> &mut Self {
+        self.pdf_renderer = Some(renderer);
+        self
+    }
+
     pub async fn build(
         mut self,
         app_uri: String,
@@ -107,11 +201,66 @@ impl<Executor: TriggerExecutor> TriggerExecutorBuilder<Executor> {
     where
         Executor::TriggerConfig: DeserializeOwned,
     {
+        let lock_manager = runtime_config::locks::build_manager(&runtime_config)?;
+        let job_store = if self.disable_default_host_components {
+            None
+        } else {
+            Some(runtime_config::jobs::build_store(&runtime_config)?)
+        };
+
         let engine = {
             let mut builder = Engine::builder(&self.config)?;
+            builder.add_host_component(component_imports::ComponentImports::default())?;
+
+            if let Some(job_store) = &job_store {
+                self.loader.add_dynamic_host_component(
+                    &mut builder,
+                    spin_jobs::JobsComponent::new(job_store.clone()),
+                )?;
+            }
 
             if !self.disable_default_host_components {
                 builder.link_import(|l, _| wasmtime_wasi_http::proxy::add_to_linker(l))?;
+                self.loader.add_dynamic_host_component(
+                    &mut builder,
+                    context::RequestContextComponent::new(Executor::TRIGGER_TYPE),
+                )?;
+                self.loader.add_dynamic_host_component(
+                    &mut builder,
+                    spin_jobs::WorkflowsComponent::new(runtime_config::jobs::build_step_store(
+                        &runtime_config,
+                    )?),
+                )?;
+                self.loader.add_dynamic_host_component(
+                    &mut builder,
+                    spin_locks::LocksComponent::new(lock_manager.clone()),
+                )?;
+                builder.add_host_component(runtime_config::app_state::build_component(
+                    &runtime_config,
+                ))?;
+                builder
+                    .add_host_component(runtime_config::flags::build_component(&runtime_config)?)?;
+                self.loader
+                    .add_dynamic_host_component(&mut builder, spin_image::ImageComponent::new())?;
+                let crypto = runtime_config::crypto::build_component(&runtime_config)?;
+                let jwt = spin_jwt::JwtComponent::new(crypto.keys());
+                self.loader
+                    .add_dynamic_host_component(&mut builder, crypto)?;
+                self.loader.add_dynamic_host_component(&mut builder, jwt)?;
+                builder.add_host_component(runtime_config::password::build_component(
+                    &runtime_config,
+                )?)?;
+                builder.add_host_component(spin_random::RandomComponent::default())?;
+                builder.add_host_component(spin_timezone::TimezoneComponent)?;
+                self.loader.add_dynamic_host_component(
+                    &mut builder,
+                    spin_template_engine::TemplateComponent::default(),
+                )?;
+                builder.add_host_component(spin_html::HtmlComponent)?;
+                self.loader.add_dynamic_host_component(
+                    &mut builder,
+                    runtime_config::pdf::build_component(&runtime_config, self.pdf_renderer.take()),
+                )?;
                 self.loader.add_dynamic_host_component(
                     &mut builder,
                     outbound_redis::OutboundRedisComponent,
@@ -120,8 +269,10 @@ impl<Executor: TriggerExecutor> TriggerExecutorBuilder<Executor> {
                     &mut builder,
                     outbound_mysql::OutboundMysql::default(),
                 )?;
-                self.loader
-                    .add_dynamic_host_component(&mut builder, outbound_pg::OutboundPg::default())?;
+                self.loader.add_dynamic_host_component(
+                    &mut builder,
+                    runtime_config::postgres::build_component(&runtime_config),
+                )?;
                 self.loader.add_dynamic_host_component(
                     &mut builder,
                     runtime_config::llm::build_component(&runtime_config, init_data.llm.use_gpu)
@@ -140,6 +291,10 @@ impl<Executor: TriggerExecutor> TriggerExecutorBuilder<Executor> {
                     runtime_config::sqlite::build_component(&runtime_config, &init_data.sqlite)
                         .await?,
                 )?;
+                self.loader.add_dynamic_host_component(
+                    &mut builder,
+                    runtime_config::cache::build_component(&runtime_config)?,
+                )?;
                 self.loader.add_dynamic_host_component(
                     &mut builder,
                     outbound_http::OutboundHttpComponent,
@@ -164,17 +319,89 @@ impl<Executor: TriggerExecutor> TriggerExecutorBuilder<Executor> {
             .iter_mut()
             .try_for_each(|h| h.app_loaded(app.borrowed(), &runtime_config))?;
 
+        if let Some(opts) = runtime_config.dns() {
+            opts.configure()?;
+        }
+        runtime_config::service_discovery::start(&runtime_config, app.borrowed()).await?;
+        if let Some(opts) = runtime_config.outbound_http() {
+            opts.configure_pool()?;
+        }
+
+        let mut variables = spin_variables::Resolver::new(
+            app.borrowed()
+                .variables()
+                .map(|(key, var)| (key.clone(), var.clone())),
+        )?;
+        for provider in runtime_config.variables_providers() {
+            variables.add_provider(provider);
+        }
+
+        if self.print_config {
+            let config = describe::describe(app.borrowed(), &variables, &runtime_config).await?;
+            println!("{}", serde_json::to_string_pretty(&config)?);
+        }
+
+        if self.migrate {
+            // Replicas starting together take turns, so that each migration
+            // is applied once.
+            let lock = format!("{app_name}/migrations");
+            let token = spin_locks::new_token();
+            while !lock_manager
+                .acquire(&lock, &token, MIGRATION_LOCK_TTL)
+                .await?
+            {
+                tokio::time::sleep(Duration::from_secs(1)).await;
+            }
+            let migrated = migrate::migrate(app.borrowed(), &runtime_config).await;
+            lock_manager.release(&lock, &token).await?;
+            migrated?;
+        }
+        seed::apply(&init_data.seeds, &runtime_config).await?;
+
         // Run trigger executor
-        Executor::new(TriggerAppEngine::new(engine, app_name, app, self.hooks).await?).await
+        let mut engine =
+            TriggerAppEngine::new(engine, app_name, app, self.hooks, variables).await?;
+        if let Some(config) = self.instance_pool {
+            engine.enable_instance_pools(config);
+        }
+        if let Some(job_store) = job_store {
+            engine.enable_jobs(job_store)?;
+        }
+        engine.lock_manager = lock_manager;
+        engine.geoip = runtime_config::geoip::build(&runtime_config)?;
+        Executor::new(engine).await
     }
 }
 
+/// How long a replica may hold the migration lock before another may take it,
+/// as if the first had gone away.
+const MIGRATION_LOCK_TTL: Duration = Duration::from_secs(600);
+
+/// How often instance pools are checked for idle instances.
+const INSTANCE_POOL_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How often the job store is checked for due jobs.
+const JOB_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How long a claimed job may run before it is claimed again, as if its
+/// dispatcher had gone away.
+const JOB_LEASE: Duration = Duration::from_secs(300);
+
+/// How long a job may run before it is interrupted and retried. This is well
+/// within [`JOB_LEASE`], so that a job is never claimed again, and run
+/// twice, while it is still running.
+const JOB_TIMEOUT: Duration = Duration::from_secs(240);
+
+/// The most jobs claimed at once.
+const JOB_BATCH_SIZE: usize = 16;
+
 /// Initialization data for host components.
 #[derive(Default)] // TODO: the implementation of Default is only for tests - would like to get rid of
 pub struct HostComponentInitData {
     kv: Vec<(String, String)>,
     sqlite: Vec<String>,
     llm: LLmOptions,
+    seeds: Vec<seed::Seed>,
 }
 
 impl HostComponentInitData {
@@ -190,8 +417,16 @@ impl HostComponentInitData {
             kv: key_value_init_values.into(),
             sqlite: sqlite_init_statements.into(),
             llm,
+            seeds: vec![],
         }
     }
+
+    /// Apply `seeds` once the application is loaded and any migrations are
+    /// applied. See [`seed`].
+    pub fn with_seeds(mut self, seeds: impl Into<Vec<seed::Seed>>) -> Self {
+        self.seeds = seeds.into();
+        self
+    }
 }
 
 /// Execution context for a TriggerExecutor executing a particular App.
@@ -204,20 +439,33 @@ pub struct TriggerAppEngine<Executor: TriggerExecutor> {
     app: OwnedApp,
     // Trigger hooks
     hooks: Vec<Box<dyn TriggerHooks>>,
+    // Resolver for variables used in trigger configuration
+    variables: spin_variables::Resolver,
     // Trigger configs for this trigger type, with order matching `app.triggers_with_type(Executor::TRIGGER_TYPE)`
     trigger_configs: Vec<Executor::TriggerConfig>,
     // Map of {Component ID -> InstancePre} for each component.
     component_instance_pres: HashMap<String, EitherInstancePre<Executor::RuntimeData>>,
+    // Map of {Component ID -> ready instances}, if instances are pooled.
+    instance_pools: HashMap<String, InstancePool<Executor::RuntimeData>>,
+    // Notified when a pool runs out of ready instances.
+    instance_pools_drained: tokio::sync::Notify,
+    // Where jobs are scheduled, if any component schedules them.
+    job_store: Option<Arc<dyn JobStore>>,
+    // Holds claims on scheduled ticks.
+    lock_manager: Arc<dyn LockManager>,
+    // Where client metadata is looked up, if anywhere.
+    geoip: Option<Arc<GeoIp>>,
 }
 
 impl<Executor: TriggerExecutor> TriggerAppEngine<Executor> {
     /// Returns a new TriggerAppEngine. May return an error if trigger config validation or
     /// component pre-instantiation fails.
     pub async fn new(
-        engine: Engine<Executor::RuntimeData>,
+        mut engine: Engine<Executor::RuntimeData>,
         app_name: String,
         app: OwnedApp,
         hooks: Vec<Box<dyn TriggerHooks>>,
+        variables: spin_variables::Resolver,
     ) -> Result<Self>
     where
         <Executor as TriggerExecutor>::TriggerConfig: DeserializeOwned,
@@ -235,14 +483,19 @@ impl<Executor: TriggerExecutor> TriggerAppEngine<Executor> {
             })
             .collect::<Result<IndexMap<_, _>>>()?;
 
+        component_imports::link(&mut engine, app.borrowed()).await?;
+
         let mut component_instance_pres = HashMap::default();
         for component in app.borrowed().components() {
             let id = component.id();
+            let pre = match trigger_configs.get(id) {
+                Some(config) => Executor::instantiate_pre(&engine, &component, config).await,
+                // A component without a trigger, e.g. one which only handles jobs.
+                None => Self::untriggered_instantiate_pre(&engine, &component).await,
+            };
             component_instance_pres.insert(
                 id.to_owned(),
-                Executor::instantiate_pre(&engine, &component, trigger_configs.get(id).unwrap())
-                    .await
-                    .with_context(|| format!("Failed to instantiate component '{id}'"))?,
+                pre.with_context(|| format!("Failed to instantiate component '{id}'"))?,
             );
         }
 
@@ -251,16 +504,198 @@ impl<Executor: TriggerExecutor> TriggerAppEngine<Executor> {
             app_name,
             app,
             hooks,
+            variables,
             trigger_configs: trigger_configs.into_values().collect(),
             component_instance_pres,
+            instance_pools: HashMap::new(),
+            instance_pools_drained: tokio::sync::Notify::new(),
+            job_store: None,
+            lock_manager: Arc::new(MemoryLocks::new()),
+            geoip: None,
         })
     }
 
+    async fn untriggered_instantiate_pre(
+        engine: &Engine<Executor::RuntimeData>,
+        component: &AppComponent<'_>,
+    ) -> Result<EitherInstancePre<Executor::RuntimeData>> {
+        let comp = component.load_component(engine).await?;
+        Ok(EitherInstancePre::Component(engine.instantiate_pre(&comp)?))
+    }
+
+    /// Keeps instances of each component ready ahead of requests. Modules,
+    /// such as Wagi components, which are instantiated with a store built
+    /// for each request, aren't pooled.
+    pub fn enable_instance_pools(&mut self, config: InstancePoolConfig) {
+        self.instance_pools = self
+            .component_instance_pres
+            .iter()
+            .filter(|(_, pre)| matches!(pre, EitherInstancePre::Component(_)))
+            .map(|(id, _)| (id.clone(), InstancePool::new(config)))
+            .collect();
+    }
+
+    /// Returns whether instances are pooled, in which case the executor must
+    /// call [`Self::maintain_instance_pools`] in a loop.
+    pub fn has_instance_pools(&self) -> bool {
+        !self.instance_pools.is_empty()
+    }
+
+    /// Prepares instances for each component's pool and tears down idle
+    /// ones, then waits until a pool runs out of instances or some may have
+    /// gone idle. Instances are only taken from pools by
+    /// [`Self::prepare_instance`].
+    pub async fn maintain_instance_pools(&self) {
+        if self.instance_pools.is_empty() {
+            return std::future::pending().await;
+        }
+
+        let now = Instant::now();
+        for (component_id, pool) in &self.instance_pools {
+            for _ in 0..pool.rescale(now) {
+                match self.instantiate(component_id).await {
+                    Ok((instance, store)) => pool.put(instance, store),
+                    Err(e) => {
+                        tracing::warn!("Failed to prepare a ready instance: {e:?}");
+                        break;
+                    }
+                }
+            }
+        }
+
+        let drained = self.instance_pools_drained.notified();
+        _ = tokio::time::timeout(INSTANCE_POOL_CHECK_INTERVAL, drained).await;
+    }
+
+    /// Delivers jobs scheduled in `store` to their components, if any
+    /// component schedules jobs.
+    pub fn enable_jobs(&mut self, store: Arc<dyn JobStore>) -> Result<()> {
+        let mut scheduled = false;
+        for component in self.app().components() {
+            let targets = component.get_metadata(JOB_TARGETS_KEY)?;
+            scheduled |= targets.map_or(false, |targets| !targets.is_empty());
+        }
+        if scheduled {
+            self.job_store = Some(store);
+        }
+        Ok(())
+    }
+
+    /// Claims the `tick`th tick of the schedule `schedule` for this replica
+    /// of the application, returning whether it should fire it here. Ticks
+    /// are numbered as by [`leader::next_tick`].
+    ///
+    /// The claim is held for `ttl`, which should be longer than the
+    /// replicas' clocks and wakeups may differ by, but shorter than the
+    /// schedule's interval.
+    pub async fn claim_tick(&self, schedule: &str, tick: u64, ttl: Duration) -> Result<bool> {
+        // Claims are never released, so that a replica which wakes late
+        // doesn't fire the tick again; they lapse after `ttl`.
+        let name = format!("{}/schedule/{schedule}/{tick}", self.app_name);
+        self.lock_manager
+            .acquire(&name, &spin_locks::new_token(), ttl)
+            .await
+    }
+
+    /// Returns where client metadata is looked up, if the runtime config has
+    /// a `[geoip]` section.
+    pub fn geoip(&self) -> Option<&GeoIp> {
+        self.geoip.as_deref()
+    }
+
+    /// Returns whether jobs are scheduled, in which case the executor must
+    /// call [`Self::dispatch_jobs`] in a loop.
+    pub fn has_jobs(&self) -> bool {
+        self.job_store.is_some()
+    }
+
+    /// Runs the jobs which are due, retrying those which fail later, then
+    /// waits until more may be due.
+    pub async fn dispatch_jobs(&self) {
+        let Some(job_store) = &self.job_store else {
+            return std::future::pending().await;
+        };
+
+        let now = spin_jobs::now();
+        match job_store
+            .claim_due(now, JOB_LEASE.as_secs(), JOB_BATCH_SIZE)
+            .await
+        {
+            Ok(jobs) => {
+                let claimed = jobs.len();
+                futures::future::join_all(jobs.into_iter().map(|job| self.run_job(job_store, job)))
+                    .await;
+                // More jobs may be due already.
+                if claimed == JOB_BATCH_SIZE {
+                    return;
+                }
+            }
+            Err(e) => tracing::warn!("Failed to claim due jobs: {e:?}"),
+        }
+        tokio::time::sleep(JOB_POLL_INTERVAL).await;
+    }
+
+    async fn run_job(&self, job_store: &Arc<dyn JobStore>, mut job: Job) {
+        let updated = match self.execute_job(&job).await {
+            Ok(()) => job_store.complete(&job.id).await,
+            Err(e) if job.retry(spin_jobs::now()) => {
+                tracing::warn!(
+                    "Job {} for component {:?} failed, retrying: {e:?}",
+                    job.id,
+                    job.component_id
+                );
+                job_store.put(&job).await
+            }
+            Err(e) => {
+                tracing::error!(
+                    "Job {} for component {:?} failed {MAX_ATTEMPTS} times, abandoning it: {e:?}",
+                    job.id,
+                    job.component_id
+                );
+                job_store.complete(&job.id).await
+            }
+        };
+        if let Err(e) = updated {
+            tracing::warn!("Failed to update job {}: {e:?}", job.id);
+        }
+    }
+
+    async fn execute_job(&self, job: &Job) -> Result<()> {
+        let (instance, mut store) = self.prepare_instance(&job.component_id).await?;
+        store.set_deadline(Instant::now() + JOB_TIMEOUT);
+        let EitherInstance::Component(instance) = instance else {
+            bail!(
+                "component {:?} is a module, so can't handle jobs",
+                job.component_id
+            );
+        };
+        let func = instance
+            .exports(&mut store)
+            .instance(INBOUND_JOB_EXPORT)
+            .with_context(|| {
+                format!(
+                    "component {:?} does not export {INBOUND_JOB_EXPORT}",
+                    job.component_id
+                )
+            })?
+            .typed_func::<(String, Vec<u8>), (Result<(), String>,)>("handle-job")?;
+        let (result,) = func
+            .call_async(&mut store, (job.id.clone(), job.payload.clone()))
+            .await?;
+        result.map_err(|e| anyhow!("component returned an error: {e}"))
+    }
+
     /// Returns a reference to the App.
     pub fn app(&self) -> &App {
         self.app.borrowed()
     }
 
+    /// Resolves any `{{ variable }}` expressions in a trigger setting, such as
+    /// a Redis password.
+    pub async fn resolve_template(&self, template: &str) -> Result<String> {
+        Ok(self.variables.resolve_app_template(template).await?)
+    }
+
     /// Returns AppTriggers and typed TriggerConfigs for this executor type.
     pub fn trigger_configs(&self) -> impl Iterator<Item = (AppTrigger, &Executor::TriggerConfig)> {
         self.app()
@@ -276,16 +711,36 @@ impl<Executor: TriggerExecutor> TriggerAppEngine<Executor> {
     ) -> Result<StoreBuilder> {
         let mut builder = self.engine.store_builder(wasi_version);
         let component = self.get_component(component_id)?;
+        // Hooks run afterwards, so that e.g. the sandbox's coarse clocks take
+        // precedence over the component's own clock settings.
+        wasi_config::apply(&component, &mut builder)?;
+        if let Some(ms) = component.get_metadata(MAX_SLICE_KEY)? {
+            builder.max_slice(Duration::from_millis(ms));
+        }
         self.hooks
             .iter()
             .try_for_each(|h| h.component_store_builder(&component, &mut builder))?;
         Ok(builder)
     }
 
-    /// Returns a new Store and Instance for the given component ID.
+    /// Returns a new Store and Instance for the given component ID, from the
+    /// component's pool if instances are pooled.
     pub async fn prepare_instance(
         &self,
         component_id: &str,
+    ) -> Result<(EitherInstance, Store<Executor::RuntimeData>)> {
+        if let Some(pool) = self.instance_pools.get(component_id) {
+            if let Some(ready) = pool.take() {
+                return Ok(ready);
+            }
+            self.instance_pools_drained.notify_one();
+        }
+        self.instantiate(component_id).await
+    }
+
+    async fn instantiate(
+        &self,
+        component_id: &str,
     ) -> Result<(EitherInstance, Store<Executor::RuntimeData>)> {
         let store_builder = self.store_builder(component_id, WasiVersion::Preview2)?;
         self.prepare_instance_with_store(component_id, store_builder)
@@ -299,10 +754,12 @@ impl<Executor: TriggerExecutor> TriggerAppEngine<Executor> {
         mut store_builder: StoreBuilder,
     ) -> Result<(EitherInstance, Store<Executor::RuntimeData>)> {
         let component = self.get_component(component_id)?;
+        let started = Instant::now();
 
         // Build Store
         component.apply_store_config(&mut store_builder).await?;
         let mut store = store_builder.build()?;
+        self.prepare_imports(component_id, &mut store).await?;
 
         // Instantiate
         let pre = self
@@ -327,10 +784,20 @@ impl<Executor: TriggerExecutor> TriggerAppEngine<Executor> {
                 self.app_name, component_id
             )
         })?;
+        admin::record_instantiation(component_id, started.elapsed());
 
         Ok((instance, store))
     }
 
+    /// Whether the given component was loaded as a core module, which must be
+    /// run with WASI Preview 1, rather than as a component.
+    pub fn is_module(&self, component_id: &str) -> bool {
+        matches!(
+            self.component_instance_pres.get(component_id),
+            Some(EitherInstancePre::Module(_))
+        )
+    }
+
     pub fn get_component(&self, component_id: &str) -> Result<AppComponent> {
         self.app().get_component(component_id).with_context(|| {
             format!(
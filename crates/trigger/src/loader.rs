@@ -8,7 +8,7 @@ use spin_app::{
     locked::{LockedApp, LockedComponentSource},
     AppComponent, Loader,
 };
-use spin_core::StoreBuilder;
+use spin_core::{audit::AuditEvent, StoreBuilder};
 use tokio::fs;
 
 use spin_common::url::parse_file_url;
@@ -43,20 +43,9 @@ impl Loader for TriggerLoader {
         engine: &spin_core::wasmtime::Engine,
         source: &LockedComponentSource,
     ) -> Result<spin_core::Component> {
-        let source = source
-            .content
-            .source
-            .as_ref()
-            .context("LockedComponentSource missing source field")?;
-        let path = parse_file_url(source)?;
-        let bytes = fs::read(&path).await.with_context(|| {
-            format!(
-                "failed to read component source from disk at path '{}'",
-                path.display()
-            )
-        })?;
-        let component = spin_componentize::componentize_if_necessary(&bytes)?;
-        spin_core::Component::new(engine, component.as_ref())
+        let (path, component) = read_component(source).await?;
+        crate::compat::check_component_imports(&component)?;
+        spin_core::Component::new(engine, &component)
             .with_context(|| format!("loading module {path:?}"))
     }
 
@@ -92,6 +81,10 @@ impl Loader for TriggerLoader {
                 "TriggerLoader only supports directory mounts; {source_path:?} is not a directory"
             );
             let guest_path = content_dir.path.clone();
+            spin_core::audit::record(AuditEvent::Files {
+                path: &guest_path.to_string_lossy(),
+                writable: self.allow_transient_write,
+            });
             if self.allow_transient_write {
                 store_builder.read_write_preopened_dir(source_path, guest_path)?;
             } else {
@@ -101,3 +94,22 @@ impl Loader for TriggerLoader {
         Ok(())
     }
 }
+
+/// Reads the Wasm component at `source`, converting it from a module if
+/// necessary. Returns the path it was read from, too.
+pub(crate) async fn read_component(source: &LockedComponentSource) -> Result<(PathBuf, Vec<u8>)> {
+    let source = source
+        .content
+        .source
+        .as_ref()
+        .context("LockedComponentSource missing source field")?;
+    let path = parse_file_url(source)?;
+    let bytes = fs::read(&path).await.with_context(|| {
+        format!(
+            "failed to read component source from disk at path '{}'",
+            path.display()
+        )
+    })?;
+    let component = spin_componentize::componentize_if_necessary(&bytes)?.into_owned();
+    Ok((path, component))
+}
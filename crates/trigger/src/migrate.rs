@@ -0,0 +1,65 @@
+//! Applying the database migrations declared by an application's components,
+//! as by `spin migrate` or `spin up --migrate`.
+
+use std::{collections::HashMap, sync::Arc};
+
+use anyhow::{anyhow, Context, Result};
+use spin_app::App;
+use spin_migrations::{
+    Database, MigrationDatabase, MysqlDatabase, PostgresDatabase, SqliteDatabase, MIGRATIONS_KEY,
+};
+use spin_sqlite::Connection;
+
+use crate::runtime_config::RuntimeConfig;
+
+/// Applies each component's pending migrations to the database it names,
+/// as configured by `runtime_config`.
+pub async fn migrate(app: &App<'_>, runtime_config: &RuntimeConfig) -> Result<()> {
+    // Built on first use, as building them opens each database.
+    let mut sqlite_databases: Option<HashMap<String, Arc<dyn Connection>>> = None;
+    for component in app.components() {
+        let Some(migrations) = component.get_metadata(MIGRATIONS_KEY)? else {
+            continue;
+        };
+        let id = component.id();
+        let pending = migrations.migrations()?;
+
+        let mut database: Box<dyn Database> = match &migrations.database {
+            MigrationDatabase::Sqlite(label) => {
+                if sqlite_databases.is_none() {
+                    sqlite_databases =
+                        Some(runtime_config.sqlite_databases()?.into_iter().collect());
+                }
+                let connection = sqlite_databases
+                    .as_ref()
+                    .and_then(|databases| databases.get(label))
+                    .ok_or_else(|| anyhow!("No SQLite database {label:?} is configured"))?;
+                Box::new(SqliteDatabase::new(connection.clone()))
+            }
+            MigrationDatabase::Postgres(name) => {
+                let opts = runtime_config
+                    .postgres_databases()
+                    .remove(name)
+                    .ok_or_else(|| anyhow!("No Postgres database {name:?} is configured"))?;
+                Box::new(PostgresDatabase::connect(&opts.primary).await?)
+            }
+            MigrationDatabase::Mysql(address) => Box::new(MysqlDatabase::connect(address).await?),
+        };
+
+        let applied = spin_migrations::run(id, database.as_mut(), &pending)
+            .await
+            .with_context(|| format!("Failed to migrate the database of component {id:?}"))?;
+        if applied.is_empty() {
+            terminal::step!("Up to date", "{id}");
+        }
+        for migration in applied {
+            terminal::step!(
+                "Migrated",
+                "{id} to version {} ({})",
+                migration.version,
+                migration.name
+            );
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,203 @@
+//! Profiling components with `--profile`.
+//!
+//! Each profiled component's guest stack is sampled at every epoch tick.
+//! Samples from the invocations for an HTTP route are written to
+//! `<COMPONENT>/<ROUTE>.folded` in the profile directory, with the route's
+//! slashes replaced by underscores, and samples from other triggers'
+//! invocations to `<COMPONENT>.folded`. The files are in the folded format
+//! read by flamegraph tools, e.g.:
+//!
+//! ```text
+//! inferno-flamegraph .spin/profiles/hello/api_users.folded > users.svg
+//! ```
+//!
+//! The files are rewritten every second while the application runs, so they
+//! are complete shortly after the workload being profiled finishes.
+
+use std::{
+    collections::HashMap,
+    io::Write,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+use anyhow::{bail, Context, Result};
+use spin_app::AppComponent;
+use spin_core::{profiling::StackSamples, StoreBuilder};
+
+use crate::{RuntimeConfig, TriggerHooks};
+
+const PROFILES_DIR: &str = "profiles";
+const WRITE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Implements TriggerHooks, sampling the stacks of the profiled components.
+pub(crate) struct ProfilingTriggerHooks {
+    components: Vec<String>,
+    profile_dir: Option<PathBuf>,
+    samples: HashMap<String, Arc<StackSamples>>,
+}
+
+impl ProfilingTriggerHooks {
+    pub(crate) fn new(components: Vec<String>, profile_dir: Option<PathBuf>) -> Self {
+        Self {
+            components,
+            profile_dir,
+            samples: HashMap::new(),
+        }
+    }
+}
+
+impl TriggerHooks for ProfilingTriggerHooks {
+    fn app_loaded(&mut self, app: &spin_app::App, runtime_config: &RuntimeConfig) -> Result<()> {
+        if self.components.is_empty() {
+            return Ok(());
+        }
+        for id in &self.components {
+            if app.get_component(id).is_none() {
+                bail!("Can't profile component '{id}': the application has no such component");
+            }
+        }
+
+        let dir = match (&self.profile_dir, runtime_config.state_dir()) {
+            (Some(dir), _) => dir.clone(),
+            (None, Some(state_dir)) => state_dir.join(PROFILES_DIR),
+            (None, None) => bail!("This application has no state directory, so --profile-dir must be given to say where profiles should be written"),
+        };
+        std::fs::create_dir_all(&dir)
+            .with_context(|| format!("Failed to create profile directory {dir:?}"))?;
+
+        let mut profiles = vec![];
+        for id in &self.components {
+            let samples = self.samples.entry(id.clone()).or_default().clone();
+            println!(
+                "Profiling component '{id}': samples will be written to {:?}, and to {:?} for each HTTP route",
+                dir.join(format!("{id}.folded")),
+                dir.join(id),
+            );
+            profiles.push(Profile {
+                samples,
+                dir: dir.clone(),
+                component_id: id.clone(),
+            });
+        }
+        std::thread::spawn(move || write_profiles(profiles));
+        Ok(())
+    }
+
+    fn component_store_builder(
+        &self,
+        component: &AppComponent,
+        store_builder: &mut StoreBuilder,
+    ) -> Result<()> {
+        if let Some(samples) = self.samples.get(component.id()) {
+            store_builder.sample_stacks(samples.clone());
+        }
+        Ok(())
+    }
+}
+
+/// The samples of one profiled component.
+struct Profile {
+    samples: Arc<StackSamples>,
+    dir: PathBuf,
+    component_id: String,
+}
+
+impl Profile {
+    /// The file for the samples taken under `label`, which is the route for
+    /// invocations from the HTTP trigger.
+    fn path(&self, label: Option<&str>) -> PathBuf {
+        match label {
+            None => self.dir.join(format!("{}.folded", self.component_id)),
+            Some(route) => self
+                .dir
+                .join(&self.component_id)
+                .join(format!("{}.folded", route_file_stem(route))),
+        }
+    }
+}
+
+/// A file name for a route's profile, e.g. `api_users` for `/api/users`
+/// and `_` for `/`.
+fn route_file_stem(route: &str) -> String {
+    let stem = route
+        .trim_start_matches('/')
+        .chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '.' => c,
+            _ => '_',
+        })
+        .collect::<String>();
+    if stem.is_empty() {
+        "_".to_owned()
+    } else {
+        stem
+    }
+}
+
+/// Rewrites each component's profiles whenever it has new samples.
+fn write_profiles(profiles: Vec<Profile>) {
+    let mut written = vec![0; profiles.len()];
+    loop {
+        std::thread::sleep(WRITE_INTERVAL);
+        for (profile, written) in profiles.iter().zip(&mut written) {
+            let total = profile.samples.total();
+            if total == *written {
+                continue;
+            }
+            let mut all_written = true;
+            for label in profile.samples.labels() {
+                let path = profile.path(label.as_deref());
+                if let Err(e) = write_profile(&profile.samples, label.as_deref(), &path) {
+                    tracing::warn!("Failed to write profile {path:?}: {e:#}");
+                    all_written = false;
+                }
+            }
+            if all_written {
+                *written = total;
+            }
+        }
+    }
+}
+
+fn write_profile(samples: &StackSamples, label: Option<&str>, path: &Path) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    // Write to a temporary file first, so that a tool reading the profile
+    // never sees it half-written.
+    let partial = path.with_extension("folded.partial");
+    let mut out = std::io::BufWriter::new(std::fs::File::create(&partial)?);
+    samples.write_folded(label, &mut out)?;
+    out.flush()?;
+    std::fs::rename(&partial, path)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn routes_have_a_profile_each() {
+        let profile = Profile {
+            samples: Default::default(),
+            dir: PathBuf::from("profiles"),
+            component_id: "hello".to_owned(),
+        };
+        assert_eq!(PathBuf::from("profiles/hello.folded"), profile.path(None));
+        assert_eq!(
+            PathBuf::from("profiles/hello/api_users.folded"),
+            profile.path(Some("/api/users"))
+        );
+        assert_eq!(
+            PathBuf::from("profiles/hello/_.folded"),
+            profile.path(Some("/"))
+        );
+        assert_eq!(
+            PathBuf::from("profiles/hello/static_....folded"),
+            profile.path(Some("/static/..."))
+        );
+    }
+}
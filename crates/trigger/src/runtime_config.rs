@@ -1,10 +1,24 @@
+pub mod app_state;
+pub mod cache;
+pub mod crypto;
+pub mod dns;
+pub mod flags;
+pub mod geoip;
+pub mod jobs;
 pub mod key_value;
 pub mod llm;
+pub mod locks;
+pub mod outbound_http;
+pub mod password;
+pub mod pdf;
+pub mod postgres;
+pub mod service_discovery;
 pub mod sqlite;
 pub mod variables_provider;
+pub mod wasmtime;
 
 use std::{
-    collections::HashMap,
+    collections::{BTreeSet, HashMap},
     fs,
     path::{Path, PathBuf},
     sync::Arc,
@@ -12,13 +26,28 @@ use std::{
 
 use anyhow::{Context, Result};
 use serde::Deserialize;
+use spin_cache::Cache;
 use spin_sqlite::Connection;
 
 use self::{
-    key_value::{KeyValueStore, KeyValueStoreOpts},
+    app_state::AppStateOpts,
+    cache::CacheOpts,
+    crypto::CryptoKeyOpts,
+    dns::DnsOpts,
+    flags::FlagsOpts,
+    geoip::GeoIpOpts,
+    jobs::JobStoreOpts,
+    key_value::{KeyValueStore, KeyValueStoreConfig, KeyValueStoreOpts, StoreScope},
     llm::LlmComputeOpts,
+    locks::LockManagerOpts,
+    outbound_http::OutboundHttpOpts,
+    password::PasswordOpts,
+    pdf::PdfOpts,
+    postgres::PostgresDatabaseOpts,
+    service_discovery::ServiceDiscoveryOpts,
     sqlite::SqliteDatabaseOpts,
     variables_provider::{VariablesProvider, VariablesProviderOpts},
+    wasmtime::WasmtimeOpts,
 };
 
 pub const DEFAULT_STATE_DIR: &str = ".spin";
@@ -31,6 +60,7 @@ pub struct RuntimeConfig {
     local_app_dir: Option<PathBuf>,
     files: Vec<RuntimeConfigOpts>,
     overrides: RuntimeConfigOpts,
+    no_default_variables_provider: bool,
 }
 
 impl RuntimeConfig {
@@ -57,10 +87,18 @@ impl RuntimeConfig {
         Ok(())
     }
 
+    /// Stop variables being read from the host environment (and `.env`
+    /// file) unless a provider for them is configured explicitly.
+    pub fn disable_default_variables_provider(&mut self) {
+        self.no_default_variables_provider = true;
+    }
+
     /// Return a Vec of configured [`VariablesProvider`]s.
     pub fn variables_providers(&self) -> Vec<VariablesProvider> {
-        let default_provider = VariablesProviderOpts::default_provider_opts(self).build_provider();
-        let mut providers: Vec<VariablesProvider> = vec![default_provider];
+        let mut providers: Vec<VariablesProvider> = vec![];
+        if !self.no_default_variables_provider {
+            providers.push(VariablesProviderOpts::default_provider_opts(self).build_provider());
+        }
         providers.extend(self.opts_layers().flat_map(|opts| {
             opts.variables_providers
                 .iter()
@@ -69,15 +107,20 @@ impl RuntimeConfig {
         providers
     }
 
-    /// Return an iterator of named configured [`KeyValueStore`]s.
-    pub fn key_value_stores(&self) -> Result<impl IntoIterator<Item = (String, KeyValueStore)>> {
+    /// Return an iterator of named configured [`KeyValueStore`]s, with
+    /// their scopes.
+    pub fn key_value_stores(
+        &self,
+    ) -> Result<impl IntoIterator<Item = (String, (KeyValueStore, StoreScope))>> {
         let mut stores = HashMap::new();
         // Insert explicitly-configured stores
         for opts in self.opts_layers() {
             for (name, store) in &opts.key_value_stores {
                 if !stores.contains_key(name) {
-                    let store = store.build_store(opts)?;
-                    stores.insert(name.to_owned(), store);
+                    let built = store
+                        .build_store(opts)
+                        .with_context(|| format!("Failed to build key-value store {name:?}"))?;
+                    stores.insert(name.to_owned(), (built, store.scope));
                 }
             }
         }
@@ -85,7 +128,7 @@ impl RuntimeConfig {
         if !stores.contains_key("default") {
             let store = KeyValueStoreOpts::default_store_opts(self)
                 .build_store(&RuntimeConfigOpts::default())?;
-            stores.insert("default".into(), store);
+            stores.insert("default".into(), (store, StoreScope::Local));
         }
         Ok(stores.into_iter())
     }
@@ -94,7 +137,7 @@ impl RuntimeConfig {
     fn default_key_value_opts(&self) -> KeyValueStoreOpts {
         self.opts_layers()
             .find_map(|opts| opts.key_value_stores.get("default"))
-            .cloned()
+            .map(|config| config.opts.clone())
             .unwrap_or_else(|| KeyValueStoreOpts::default_store_opts(self))
     }
 
@@ -128,6 +171,155 @@ impl RuntimeConfig {
         Ok(databases.into_iter())
     }
 
+    /// Return the named configured caches, including the `default` cache.
+    pub fn caches(&self) -> Result<HashMap<String, Arc<dyn Cache>>> {
+        let mut caches = HashMap::new();
+        for opts in self.opts_layers() {
+            for (name, cache) in &opts.caches {
+                if !caches.contains_key(name) {
+                    let built = cache
+                        .build_cache(name)
+                        .with_context(|| format!("Failed to build cache {name:?}"))?;
+                    caches.insert(name.to_owned(), built);
+                }
+            }
+        }
+        if !caches.contains_key("default") {
+            let cache = CacheOpts::default_cache_opts().build_cache("default")?;
+            caches.insert("default".into(), cache);
+        }
+        Ok(caches)
+    }
+
+    /// Return the named configured Postgres databases.
+    pub fn postgres_databases(&self) -> HashMap<String, PostgresDatabaseOpts> {
+        let mut databases = HashMap::new();
+        for opts in self.opts_layers() {
+            for (name, database) in &opts.postgres_databases {
+                if !databases.contains_key(name) {
+                    databases.insert(name.to_owned(), database.clone());
+                }
+            }
+        }
+        databases
+    }
+
+    /// Return the paths of the files in which Spin keeps the data of the
+    /// SQLite databases and key-value stores, including the defaults. Stores
+    /// which are held in memory or by another service are not included.
+    pub fn local_data_files(&self) -> Result<Vec<PathBuf>> {
+        let mut databases = HashMap::new();
+        let mut stores = HashMap::new();
+        for opts in self.opts_layers() {
+            for (name, database) in &opts.sqlite_databases {
+                if !databases.contains_key(name.as_str()) {
+                    let path = match database {
+                        SqliteDatabaseOpts::Spin(spin) => spin.path.as_deref(),
+                        SqliteDatabaseOpts::Libsql(_) => None,
+                    };
+                    let path = path.map(|p| resolve_config_path(p, opts)).transpose()?;
+                    databases.insert(name.as_str(), path);
+                }
+            }
+            for (name, store) in &opts.key_value_stores {
+                if !stores.contains_key(name.as_str()) {
+                    let path = match &store.opts {
+                        KeyValueStoreOpts::Spin(spin) => spin.path.as_deref(),
+                        _ => None,
+                    };
+                    let path = path.map(|p| resolve_config_path(p, opts)).transpose()?;
+                    stores.insert(name.as_str(), path);
+                }
+            }
+        }
+        if !databases.contains_key("default") {
+            if let SqliteDatabaseOpts::Spin(spin) = SqliteDatabaseOpts::default(self) {
+                databases.insert("default", spin.path);
+            }
+        }
+        if !stores.contains_key("default") {
+            if let KeyValueStoreOpts::Spin(spin) = KeyValueStoreOpts::default_store_opts(self) {
+                stores.insert("default", spin.path);
+            }
+        }
+        Ok(databases
+            .into_values()
+            .chain(stores.into_values())
+            .flatten()
+            .collect())
+    }
+
+    /// Return the service discovery config, if any.
+    pub fn service_discovery(&self) -> Option<&ServiceDiscoveryOpts> {
+        self.find_opt(|opts| &opts.service_discovery)
+    }
+
+    /// Return the DNS resolution config, if any.
+    pub fn dns(&self) -> Option<&DnsOpts> {
+        self.find_opt(|opts| &opts.dns)
+    }
+
+    /// Return the outbound HTTP connection pool config, if any.
+    pub fn outbound_http(&self) -> Option<&OutboundHttpOpts> {
+        self.find_opt(|opts| &opts.outbound_http)
+    }
+
+    /// Return the job store config, if any.
+    pub fn jobs(&self) -> Option<&JobStoreOpts> {
+        self.find_opt(|opts| &opts.jobs)
+    }
+
+    /// Return the app state config, if any.
+    pub fn app_state(&self) -> Option<&AppStateOpts> {
+        self.find_opt(|opts| &opts.app_state)
+    }
+
+    /// Return the flags config, if any.
+    pub fn flags(&self) -> Option<&FlagsOpts> {
+        self.find_opt(|opts| &opts.flags)
+    }
+
+    /// Return the labelled crypto keys.
+    pub fn crypto_keys(&self) -> Result<HashMap<String, Arc<spin_crypto::Key>>> {
+        let mut keys = HashMap::new();
+        for opts in self.opts_layers() {
+            for (label, key) in &opts.crypto_keys {
+                if !keys.contains_key(label) {
+                    let built = key
+                        .build_key(opts)
+                        .with_context(|| format!("Failed to build crypto key {label:?}"))?;
+                    keys.insert(label.to_owned(), built);
+                }
+            }
+        }
+        Ok(keys)
+    }
+
+    /// Return the GeoIP config, if any.
+    pub fn geoip(&self) -> Option<&GeoIpOpts> {
+        self.find_opt(|opts| &opts.geoip)
+    }
+
+    /// Return the password hashing config, if any.
+    pub fn password(&self) -> Option<&PasswordOpts> {
+        self.find_opt(|opts| &opts.password)
+    }
+
+    /// Return the PDF renderer config, if any.
+    pub fn pdf(&self) -> Option<&PdfOpts> {
+        self.find_opt(|opts| &opts.pdf)
+    }
+
+    /// Return the lock manager config, if any.
+    pub fn locks(&self) -> Option<&LockManagerOpts> {
+        self.find_opt(|opts| &opts.locks)
+    }
+
+    /// Return the Wasmtime engine options, if any.
+    pub fn wasmtime(&self) -> Option<&WasmtimeOpts> {
+        self.find_opt(|opts| &opts.wasmtime)
+    }
+
     /// Set the state dir, overriding any other runtime config source.
     pub fn set_state_dir(&mut self, state_dir: impl Into<String>) {
         self.overrides.state_dir = Some(state_dir.into());
@@ -175,6 +367,35 @@ impl RuntimeConfig {
         }
     }
 
+    /// Returns a description of the effective runtime config for display.
+    /// Store and database options are omitted as they may contain secrets.
+    pub fn summary(&self) -> serde_json::Value {
+        let files = self
+            .files
+            .iter()
+            .filter_map(|opts| opts.file_path.as_ref())
+            .collect::<Vec<_>>();
+        let mut key_value_stores = BTreeSet::from(["default"]);
+        let mut sqlite_databases = BTreeSet::from(["default"]);
+        let mut postgres_databases = BTreeSet::new();
+        let mut caches = BTreeSet::from(["default"]);
+        for opts in self.opts_layers() {
+            caches.extend(opts.caches.keys().map(String::as_str));
+            key_value_stores.extend(opts.key_value_stores.keys().map(String::as_str));
+            sqlite_databases.extend(opts.sqlite_databases.keys().map(String::as_str));
+            postgres_databases.extend(opts.postgres_databases.keys().map(String::as_str));
+        }
+        serde_json::json!({
+            "files": files,
+            "state_dir": self.state_dir(),
+            "log_dir": self.log_dir(),
+            "key_value_stores": key_value_stores,
+            "sqlite_databases": sqlite_databases,
+            "postgres_databases": postgres_databases,
+            "caches": caches,
+        })
+    }
+
     /// Returns an iterator of RuntimeConfigOpts in order of decreasing precedence
     fn opts_layers(&self) -> impl Iterator<Item = &RuntimeConfigOpts> {
         std::iter::once(&self.overrides).chain(self.files.iter().rev())
@@ -202,11 +423,53 @@ pub struct RuntimeConfigOpts {
     pub variables_providers: Vec<VariablesProviderOpts>,
 
     #[serde(rename = "key_value_store", default)]
-    pub key_value_stores: HashMap<String, KeyValueStoreOpts>,
+    pub key_value_stores: HashMap<String, KeyValueStoreConfig>,
 
     #[serde(rename = "sqlite_database", default)]
     pub sqlite_databases: HashMap<String, SqliteDatabaseOpts>,
 
+    #[serde(rename = "postgres_database", default)]
+    pub postgres_databases: HashMap<String, PostgresDatabaseOpts>,
+
+    #[serde(rename = "cache", default)]
+    pub caches: HashMap<String, CacheOpts>,
+
+    #[serde(rename = "crypto_key", default)]
+    pub crypto_keys: HashMap<String, CryptoKeyOpts>,
+
+    #[serde(default)]
+    pub service_discovery: Option<ServiceDiscoveryOpts>,
+
+    #[serde(default)]
+    pub outbound_http: Option<OutboundHttpOpts>,
+
+    #[serde(default)]
+    pub dns: Option<DnsOpts>,
+
+    #[serde(default)]
+    pub jobs: Option<JobStoreOpts>,
+
+    #[serde(default)]
+    pub locks: Option<LockManagerOpts>,
+
+    #[serde(default)]
+    pub app_state: Option<AppStateOpts>,
+
+    #[serde(default)]
+    pub flags: Option<FlagsOpts>,
+
+    #[serde(default)]
+    pub geoip: Option<GeoIpOpts>,
+
+    #[serde(default)]
+    pub password: Option<PasswordOpts>,
+
+    #[serde(default)]
+    pub pdf: Option<PdfOpts>,
+
+    #[serde(default)]
+    pub wasmtime: Option<WasmtimeOpts>,
+
     #[serde(skip)]
     pub file_path: Option<PathBuf>,
 }
@@ -350,6 +613,24 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn default_variables_provider_can_be_disabled() -> Result<()> {
+        let mut config = RuntimeConfig::new(None);
+        config.disable_default_variables_provider();
+        assert_eq!(config.variables_providers().len(), 0);
+
+        merge_config_toml(
+            &mut config,
+            toml! {
+                [[variables_provider]]
+                type = "env"
+            },
+        );
+        assert_eq!(config.variables_providers().len(), 1);
+
+        Ok(())
+    }
+
     #[test]
     fn key_value_stores_from_file() -> Result<()> {
         let mut config = RuntimeConfig::new(None);
@@ -396,6 +677,452 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn default_aws_dynamo_key_value_store_from_file() -> Result<()> {
+        let mut config = RuntimeConfig::new(None);
+
+        merge_config_toml(
+            &mut config,
+            toml! {
+                [key_value_store.default]
+                type = "aws_dynamo"
+                table = "spin-kv"
+                region = "us-west-2"
+                consistent_read = true
+                scope = "shared"
+            },
+        );
+        assert_eq!(config.key_value_stores().unwrap().into_iter().count(), 1);
+
+        assert!(
+            matches!(
+                config.default_key_value_opts(),
+                KeyValueStoreOpts::AwsDynamo(_)
+            ),
+            "expected default DynamoDB store",
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn postgres_key_value_store_from_file() -> Result<()> {
+        let mut config = RuntimeConfig::new(None);
+
+        merge_config_toml(
+            &mut config,
+            toml! {
+                [key_value_store.state]
+                type = "postgres"
+                connection_string = "host=localhost user=spin dbname=spin"
+                scope = "shared"
+            },
+        );
+        let scopes = config
+            .key_value_stores()?
+            .into_iter()
+            .map(|(name, (_, scope))| (name, scope))
+            .collect::<HashMap<_, _>>();
+        assert_eq!(scopes["state"], StoreScope::Shared);
+
+        Ok(())
+    }
+
+    #[test]
+    fn caches_from_file() -> Result<()> {
+        let mut config = RuntimeConfig::new(None);
+        assert_eq!(config.caches()?.len(), 1);
+
+        merge_config_toml(
+            &mut config,
+            toml! {
+                [cache.pages]
+                type = "memory"
+                max_size = 1048576
+
+                [cache.shared]
+                type = "redis"
+                url = "redis://127.0.0.1/"
+            },
+        );
+        let caches = config.caches()?;
+        assert!(caches.contains_key("default"));
+        assert!(caches.contains_key("pages"));
+        assert!(caches.contains_key("shared"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn wasmtime_options_from_file() -> Result<()> {
+        let mut config = RuntimeConfig::new(None);
+        assert!(config.wasmtime().is_none());
+
+        merge_config_toml(
+            &mut config,
+            toml! {
+                [wasmtime]
+                memory_init_cow = false
+            },
+        );
+        let opts = config.wasmtime().unwrap();
+        assert_eq!(Some(false), opts.memory_init_cow);
+        assert_eq!(None, opts.memory_guaranteed_dense_image_size);
+
+        Ok(())
+    }
+
+    #[test]
+    fn outbound_http_options_from_file() -> Result<()> {
+        let mut config = RuntimeConfig::new(None);
+        assert!(config.outbound_http().is_none());
+
+        merge_config_toml(
+            &mut config,
+            toml! {
+                [outbound_http]
+                max_connections_per_host = 8
+            },
+        );
+        let opts = config.outbound_http().unwrap();
+        assert_eq!(Some(8), opts.max_connections_per_host);
+        assert_eq!(None, opts.idle_timeout_secs);
+        assert_eq!(None, opts.proxy);
+
+        let mut config = RuntimeConfig::new(None);
+        merge_config_toml(
+            &mut config,
+            toml! {
+                [outbound_http]
+                proxy = "http://proxy.corp:3128"
+                no_proxy = ["localhost", ".internal"]
+                proxy_hosts = { "*.partner.com" = "socks5h://partner-proxy:1080" }
+            },
+        );
+        let opts = config.outbound_http().unwrap();
+        assert_eq!(Some("http://proxy.corp:3128"), opts.proxy.as_deref());
+        assert_eq!(vec!["localhost", ".internal"], opts.no_proxy);
+        assert_eq!(
+            Some("socks5h://partner-proxy:1080"),
+            opts.proxy_hosts.get("*.partner.com").map(String::as_str)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn dns_options_from_file() -> Result<()> {
+        let mut config = RuntimeConfig::new(None);
+        assert!(config.dns().is_none());
+
+        merge_config_toml(
+            &mut config,
+            toml! {
+                [dns]
+                resolver = { type = "udp", nameserver = "1.1.1.1:53" }
+                hosts = { "db.internal" = "127.0.0.1", "cache.internal" = ["10.0.0.1", "::1"] }
+            },
+        );
+        let opts = config.dns().unwrap();
+        assert!(matches!(opts.resolver, dns::ResolverOpts::Udp { .. }));
+        assert!(matches!(opts.hosts["db.internal"], dns::HostAddrs::One(_)));
+        assert!(
+            matches!(&opts.hosts["cache.internal"], dns::HostAddrs::Many(addrs) if addrs.len() == 2)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn job_store_from_file() -> Result<()> {
+        let mut config = RuntimeConfig::new(None);
+        assert!(config.jobs().is_none());
+
+        merge_config_toml(
+            &mut config,
+            toml! {
+                [jobs]
+                type = "key_value"
+            },
+        );
+        assert!(
+            matches!(config.jobs(), Some(JobStoreOpts::KeyValue(opts)) if opts.store == "default")
+        );
+
+        let mut config = RuntimeConfig::new(None);
+        merge_config_toml(
+            &mut config,
+            toml! {
+                [jobs]
+                type = "postgres"
+                connection = "host=localhost user=app"
+            },
+        );
+        assert!(matches!(config.jobs(), Some(JobStoreOpts::Postgres(_))));
+        jobs::build_store(&config)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn app_state_from_file() {
+        let mut config = RuntimeConfig::new(None);
+        assert!(config.app_state().is_none());
+
+        merge_config_toml(
+            &mut config,
+            toml! {
+                [app_state]
+                max_size = 1048576
+            },
+        );
+        assert_eq!(1048576, config.app_state().unwrap().max_size);
+    }
+
+    #[test]
+    fn flags_from_file() {
+        let mut config = RuntimeConfig::new(None);
+        assert!(config.flags().is_none());
+
+        merge_config_toml(
+            &mut config,
+            toml! {
+                [flags]
+                type = "key_value"
+                key_prefix = "features/"
+            },
+        );
+        let Some(FlagsOpts::KeyValue(opts)) = config.flags() else {
+            panic!("expected key-value flags");
+        };
+        assert_eq!("default", opts.store);
+        assert_eq!("features/", opts.key_prefix);
+        assert_eq!(10, opts.cache_ttl_secs);
+    }
+
+    #[test]
+    fn crypto_keys_from_file() -> Result<()> {
+        let mut config = RuntimeConfig::new(None);
+        assert!(config.crypto_keys()?.is_empty());
+
+        merge_config_toml(
+            &mut config,
+            toml! {
+                [crypto_key.webhook]
+                type = "hmac_sha256"
+                secret = "whsec_test"
+
+                [crypto_key.sessions]
+                type = "aes_256_gcm"
+                base64 = "AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA="
+            },
+        );
+        assert_eq!(2, config.crypto_keys()?.len());
+
+        merge_config_toml(
+            &mut config,
+            toml! {
+                [crypto_key.invalid]
+                type = "hmac_sha256"
+                secret = "whsec_test"
+                base64 = "AAAA"
+            },
+        );
+        assert!(config.crypto_keys().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn password_from_file() {
+        let mut config = RuntimeConfig::new(None);
+        assert!(config.password().is_none());
+
+        merge_config_toml(
+            &mut config,
+            toml! {
+                [password]
+                bcrypt_cost = 13
+            },
+        );
+        let params = config.password().unwrap().params();
+        assert_eq!(13, params.bcrypt_cost);
+        assert_eq!(
+            spin_password::Params::default().argon2_memory_kib,
+            params.argon2_memory_kib
+        );
+    }
+
+    #[test]
+    fn pdf_from_file() {
+        let mut config = RuntimeConfig::new(None);
+        assert!(config.pdf().is_none());
+
+        merge_config_toml(
+            &mut config,
+            toml! {
+                [pdf]
+                type = "remote_http"
+                url = "http://localhost:3070/render"
+            },
+        );
+        let PdfOpts::RemoteHttp(opts) = config.pdf().unwrap();
+        assert_eq!("http://localhost:3070/render", opts.url.as_str());
+        assert!(opts.auth_token.is_none());
+    }
+
+    #[test]
+    fn geoip_from_file() -> Result<()> {
+        let mut config = RuntimeConfig::new(None);
+        assert!(geoip::build(&config)?.is_none());
+
+        merge_config_toml(
+            &mut config,
+            toml! {
+                [geoip]
+                asn_database = "GeoLite2-ASN.mmdb"
+            },
+        );
+        let opts = config.geoip().unwrap();
+        assert_eq!(None, opts.country_database);
+        assert_eq!(Some(PathBuf::from("GeoLite2-ASN.mmdb")), opts.asn_database);
+        Ok(())
+    }
+
+    #[test]
+    fn lock_manager_from_file() -> Result<()> {
+        let mut config = RuntimeConfig::new(None);
+        assert!(config.locks().is_none());
+        locks::build_manager(&config)?;
+
+        merge_config_toml(
+            &mut config,
+            toml! {
+                [locks]
+                type = "redis"
+                url = "redis://localhost:6379"
+            },
+        );
+        assert!(
+            matches!(config.locks(), Some(LockManagerOpts::Redis(opts)) if opts.key_prefix.is_none())
+        );
+        locks::build_manager(&config)?;
+
+        let mut config = RuntimeConfig::new(None);
+        merge_config_toml(
+            &mut config,
+            toml! {
+                [locks]
+                type = "postgres"
+                connection = "host=localhost user=app"
+            },
+        );
+        assert!(matches!(config.locks(), Some(LockManagerOpts::Postgres(_))));
+        locks::build_manager(&config)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn postgres_databases_from_file() -> Result<()> {
+        let mut config = RuntimeConfig::new(None);
+        assert!(config.postgres_databases().is_empty());
+
+        merge_config_toml(
+            &mut config,
+            toml! {
+                [postgres_database.orders]
+                primary = "host=primary user=app"
+                replicas = ["host=replica1 user=app", "host=replica2 user=app"]
+
+                [postgres_database.audit]
+                primary = "host=audit user=app"
+            },
+        );
+        let databases = config.postgres_databases();
+        assert_eq!(databases["orders"].replicas.len(), 2);
+        assert!(databases["audit"].replicas.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn local_data_files_from_file() -> Result<()> {
+        let app_dir = tempfile::tempdir()?;
+        let mut config = RuntimeConfig::new(Some(app_dir.path().into()));
+        assert_eq!(config.local_data_files()?.len(), 2);
+
+        merge_config_toml(
+            &mut config,
+            toml! {
+                [sqlite_database.default]
+                type = "libsql"
+                url = "https://example.com"
+                token = "secret"
+
+                [sqlite_database.analytics]
+                type = "spin"
+                path = "/data/analytics.db"
+
+                [key_value_store.cache]
+                type = "spin"
+
+                [key_value_store.shared]
+                type = "redis"
+                url = "redis://localhost"
+            },
+        );
+        let mut files = config.local_data_files()?;
+        files.sort();
+        assert_eq!(files[0], PathBuf::from("/data/analytics.db"));
+        assert!(files[1].starts_with(app_dir.path()));
+        assert_eq!(files.len(), 2);
+
+        Ok(())
+    }
+
+    #[test]
+    fn key_value_store_scopes_from_file() -> Result<()> {
+        let mut config = RuntimeConfig::new(None);
+
+        merge_config_toml(
+            &mut config,
+            toml! {
+                [key_value_store.shared]
+                type = "redis"
+                url = "redis://127.0.0.1/"
+                scope = "shared"
+
+                [key_value_store.cache]
+                type = "spin"
+            },
+        );
+        let scopes = config
+            .key_value_stores()?
+            .into_iter()
+            .map(|(name, (_, scope))| (name, scope))
+            .collect::<HashMap<_, _>>();
+        assert_eq!(scopes["shared"], StoreScope::Shared);
+        assert_eq!(scopes["cache"], StoreScope::Local);
+        assert_eq!(scopes["default"], StoreScope::Local);
+
+        Ok(())
+    }
+
+    #[test]
+    fn in_memory_key_value_store_cannot_be_shared() {
+        let mut config = RuntimeConfig::new(None);
+
+        merge_config_toml(
+            &mut config,
+            toml! {
+                [key_value_store.default]
+                type = "spin"
+                scope = "shared"
+            },
+        );
+        assert!(config.key_value_stores().is_err());
+    }
+
     fn merge_config_toml(config: &mut RuntimeConfig, value: toml::Value) {
         let data = toml::to_vec(&value).expect("encode toml");
         let mut file = NamedTempFile::new().expect("temp file");
@@ -0,0 +1,29 @@
+use serde::Deserialize;
+use spin_app_state::AppStateComponent;
+
+use super::RuntimeConfig;
+
+/// The most bytes of keys and values an application's state holds by
+/// default.
+const DEFAULT_MAX_SIZE: usize = 16 * 1024 * 1024;
+
+/// Builds an [`AppStateComponent`] from the given [`RuntimeConfig`].
+pub fn build_component(runtime_config: &RuntimeConfig) -> AppStateComponent {
+    let max_size = runtime_config
+        .app_state()
+        .map_or(DEFAULT_MAX_SIZE, |opts| opts.max_size);
+    AppStateComponent::new(max_size)
+}
+
+// Holds deserialized options from an `[app_state]` runtime config section.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AppStateOpts {
+    /// The most bytes of keys and values held before writes fail.
+    #[serde(default = "default_max_size")]
+    pub max_size: usize,
+}
+
+fn default_max_size() -> usize {
+    DEFAULT_MAX_SIZE
+}
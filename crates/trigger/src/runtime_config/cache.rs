@@ -0,0 +1,75 @@
+use std::{collections::HashMap, sync::Arc};
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use spin_cache::{Cache, CacheComponent, MemoryCache};
+use spin_cache_redis::RedisCache;
+
+use super::RuntimeConfig;
+
+/// The size of the in-memory default cache.
+const DEFAULT_MEMORY_CACHE_SIZE: usize = 64 * 1024 * 1024;
+
+/// Builds a [`CacheComponent`] from the given [`RuntimeConfig`].
+pub fn build_component(runtime_config: &RuntimeConfig) -> Result<CacheComponent> {
+    let caches = runtime_config
+        .caches()
+        .context("Failed to build cache component")?;
+    Ok(CacheComponent::new(caches.into_iter().collect()))
+}
+
+// Holds deserialized options from a `[cache.<name>]` runtime config section.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "snake_case", tag = "type")]
+pub enum CacheOpts {
+    Memory(MemoryCacheOpts),
+    Redis(RedisCacheOpts),
+}
+
+impl CacheOpts {
+    pub fn default_cache_opts() -> Self {
+        Self::Memory(MemoryCacheOpts {
+            max_size: DEFAULT_MEMORY_CACHE_SIZE,
+        })
+    }
+
+    pub fn build_cache(&self, name: &str) -> Result<Arc<dyn Cache>> {
+        match self {
+            Self::Memory(opts) => Ok(Arc::new(MemoryCache::new(opts.max_size))),
+            Self::Redis(opts) => opts.build_cache(name),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MemoryCacheOpts {
+    /// The most bytes of keys and values held before the least recently used
+    /// entries are evicted.
+    #[serde(default = "default_memory_cache_size")]
+    pub max_size: usize,
+}
+
+fn default_memory_cache_size() -> usize {
+    DEFAULT_MEMORY_CACHE_SIZE
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RedisCacheOpts {
+    pub url: String,
+    /// Prepended to every key. Defaults to `spin-cache:<name>:`, so that
+    /// caches can share a Redis database.
+    pub key_prefix: Option<String>,
+}
+
+impl RedisCacheOpts {
+    fn build_cache(&self, name: &str) -> Result<Arc<dyn Cache>> {
+        let key_prefix = self
+            .key_prefix
+            .clone()
+            .unwrap_or_else(|| format!("spin-cache:{name}:"));
+        let cache = RedisCache::new(self.url.clone(), key_prefix)?;
+        Ok(Arc::new(cache))
+    }
+}
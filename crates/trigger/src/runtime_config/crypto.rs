@@ -0,0 +1,95 @@
+use std::{path::PathBuf, sync::Arc};
+
+use anyhow::{bail, ensure, Context, Result};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use serde::Deserialize;
+use spin_crypto::{CryptoComponent, Key};
+
+use super::{resolve_config_path, RuntimeConfig, RuntimeConfigOpts};
+
+/// Builds a [`CryptoComponent`] from the given [`RuntimeConfig`].
+pub fn build_component(runtime_config: &RuntimeConfig) -> Result<CryptoComponent> {
+    let keys = runtime_config
+        .crypto_keys()
+        .context("Failed to build crypto component")?;
+    Ok(CryptoComponent::new(keys))
+}
+
+// Holds deserialized options from a `[crypto_key.<label>]` runtime config
+// section. The key material is given by exactly one of `secret`, `base64`,
+// `file` or, to only verify signatures, `public_key`.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CryptoKeyOpts {
+    #[serde(rename = "type")]
+    pub key_type: CryptoKeyType,
+    /// The key material as text, such as a webhook secret.
+    #[serde(default)]
+    pub secret: Option<String>,
+    /// The key material, base64-encoded.
+    #[serde(default)]
+    pub base64: Option<String>,
+    /// A file holding the key material, relative to the runtime config file:
+    /// a PKCS#8 document, as DER or PEM, for signing keys, or the raw key.
+    #[serde(default)]
+    pub file: Option<PathBuf>,
+    /// The public key of a signing key, base64-encoded: 32 bytes for Ed25519,
+    /// or an uncompressed point for ECDSA.
+    #[serde(default)]
+    pub public_key: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CryptoKeyType {
+    HmacSha256,
+    HmacSha384,
+    HmacSha512,
+    Ed25519,
+    EcdsaP256,
+    #[serde(rename = "aes_256_gcm")]
+    Aes256Gcm,
+    Chacha20Poly1305,
+}
+
+impl CryptoKeyOpts {
+    pub fn build_key(&self, config_opts: &RuntimeConfigOpts) -> Result<Arc<Key>> {
+        if let Some(public_key) = &self.public_key {
+            ensure!(
+                self.secret.is_none() && self.base64.is_none() && self.file.is_none(),
+                "`public_key` cannot be combined with `secret`, `base64` or `file`"
+            );
+            let public_key = BASE64
+                .decode(public_key)
+                .context("`public_key` is not valid base64")?;
+            let key = match self.key_type {
+                CryptoKeyType::Ed25519 => Key::ed25519_public(&public_key)?,
+                CryptoKeyType::EcdsaP256 => Key::ecdsa_p256_public(&public_key)?,
+                _ => bail!("only signing keys can be given as a `public_key`"),
+            };
+            return Ok(Arc::new(key));
+        }
+
+        let material = match (&self.secret, &self.base64, &self.file) {
+            (Some(secret), None, None) => secret.as_bytes().to_vec(),
+            (None, Some(base64), None) => BASE64
+                .decode(base64)
+                .context("`base64` is not valid base64")?,
+            (None, None, Some(path)) => {
+                let path = resolve_config_path(path, config_opts)?;
+                std::fs::read(&path).with_context(|| format!("Failed to read key file {path:?}"))?
+            }
+            _ => bail!("exactly one of `secret`, `base64`, `file` or `public_key` must be set"),
+        };
+        let key = match self.key_type {
+            CryptoKeyType::HmacSha256 => Key::hmac_sha256(&material),
+            CryptoKeyType::HmacSha384 => Key::hmac_sha384(&material),
+            CryptoKeyType::HmacSha512 => Key::hmac_sha512(&material),
+            CryptoKeyType::Ed25519 => Key::ed25519(&material)?,
+            CryptoKeyType::EcdsaP256 => Key::ecdsa_p256(&material)?,
+            CryptoKeyType::Aes256Gcm => Key::aes_256_gcm(&material)?,
+            CryptoKeyType::Chacha20Poly1305 => Key::chacha20_poly1305(&material)?,
+        };
+        Ok(Arc::new(key))
+    }
+}
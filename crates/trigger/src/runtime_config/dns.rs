@@ -0,0 +1,90 @@
+use std::{
+    collections::HashMap,
+    net::{IpAddr, SocketAddr},
+    time::Duration,
+};
+
+use anyhow::Result;
+use serde::Deserialize;
+use spin_outbound_networking::dns::{self, DnsConfig, Resolver, DEFAULT_SYSTEM_TTL};
+
+// Holds deserialized options from a `[dns]` runtime config section.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DnsOpts {
+    /// Where hosts are looked up. Defaults to the system resolver.
+    #[serde(default)]
+    pub resolver: ResolverOpts,
+    /// Host names which resolve to the given addresses, e.g.
+    /// `hosts = { "db.internal" = "127.0.0.1" }`.
+    #[serde(default)]
+    pub hosts: HashMap<String, HostAddrs>,
+    /// How long the system resolver's answers are cached, in seconds. Other
+    /// resolvers' answers are cached for their TTL.
+    #[serde(default)]
+    pub system_ttl_secs: Option<u64>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "snake_case", tag = "type", deny_unknown_fields)]
+pub enum ResolverOpts {
+    #[default]
+    System,
+    Udp {
+        nameserver: SocketAddr,
+    },
+    Https {
+        url: String,
+    },
+    Tls {
+        nameserver: SocketAddr,
+        server_name: String,
+    },
+}
+
+/// One address or several.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(untagged)]
+pub enum HostAddrs {
+    One(IpAddr),
+    Many(Vec<IpAddr>),
+}
+
+impl DnsOpts {
+    /// Configures how outbound connections resolve hosts.
+    pub fn configure(&self) -> Result<()> {
+        let resolver = match &self.resolver {
+            ResolverOpts::System => Resolver::System,
+            ResolverOpts::Udp { nameserver } => Resolver::Udp {
+                nameserver: *nameserver,
+            },
+            ResolverOpts::Https { url } => Resolver::Https { url: url.clone() },
+            ResolverOpts::Tls {
+                nameserver,
+                server_name,
+            } => Resolver::Tls {
+                nameserver: *nameserver,
+                server_name: server_name.clone(),
+            },
+        };
+        let hosts = self
+            .hosts
+            .iter()
+            .map(|(host, addrs)| {
+                let addrs = match addrs {
+                    HostAddrs::One(addr) => vec![*addr],
+                    HostAddrs::Many(addrs) => addrs.clone(),
+                };
+                (host.clone(), addrs)
+            })
+            .collect();
+        dns::configure(DnsConfig {
+            resolver,
+            hosts,
+            system_ttl: self
+                .system_ttl_secs
+                .map(Duration::from_secs)
+                .unwrap_or(DEFAULT_SYSTEM_TTL),
+        })
+    }
+}
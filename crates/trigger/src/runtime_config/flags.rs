@@ -0,0 +1,114 @@
+use std::{path::PathBuf, sync::Arc, time::Duration};
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use spin_flags::{
+    FileFlags, FlagProvider, FlagsComponent, KeyValueFlags, LaunchDarklyFlags, NoFlags,
+};
+
+use super::{resolve_config_path, RuntimeConfig, RuntimeConfigOpts};
+
+/// Builds a [`FlagsComponent`] from the given [`RuntimeConfig`]. Without a
+/// `[flags]` section, every flag is disabled.
+pub fn build_component(runtime_config: &RuntimeConfig) -> Result<FlagsComponent> {
+    let provider: Arc<dyn FlagProvider> = match runtime_config
+        .opts_layers()
+        .find_map(|layer| Some((layer.flags.as_ref()?, layer)))
+    {
+        Some((opts, config_opts)) => opts
+            .build_provider(runtime_config, config_opts)
+            .context("Failed to build flag provider")?,
+        None => Arc::new(NoFlags),
+    };
+    Ok(FlagsComponent::new(provider))
+}
+
+// Holds deserialized options from a `[flags]` runtime config section.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "snake_case", tag = "type")]
+pub enum FlagsOpts {
+    File(FileFlagsOpts),
+    Launchdarkly(LaunchDarklyFlagsOpts),
+    KeyValue(KeyValueFlagsOpts),
+}
+
+impl FlagsOpts {
+    fn build_provider(
+        &self,
+        runtime_config: &RuntimeConfig,
+        config_opts: &RuntimeConfigOpts,
+    ) -> Result<Arc<dyn FlagProvider>> {
+        match self {
+            Self::File(opts) => {
+                let path = resolve_config_path(&opts.path, config_opts)?;
+                Ok(Arc::new(FileFlags::new(path)?))
+            }
+            Self::Launchdarkly(opts) => Ok(Arc::new(LaunchDarklyFlags::new(
+                &opts.stream_url,
+                opts.sdk_key.clone(),
+            )?)),
+            Self::KeyValue(opts) => {
+                let (manager, _) = runtime_config
+                    .key_value_stores()?
+                    .into_iter()
+                    .find_map(|(name, store)| (name == opts.store).then_some(store))
+                    .with_context(|| format!("No key-value store named {:?}", opts.store))?;
+                Ok(Arc::new(KeyValueFlags::new(
+                    manager,
+                    opts.store.clone(),
+                    opts.key_prefix.clone(),
+                    Duration::from_secs(opts.cache_ttl_secs),
+                )))
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FileFlagsOpts {
+    /// The flags file, relative to the runtime config file. It is reloaded
+    /// when it changes.
+    pub path: PathBuf,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LaunchDarklyFlagsOpts {
+    /// The server-side SDK key of the environment.
+    pub sdk_key: String,
+    /// The streaming service, such as a relay proxy.
+    #[serde(default = "default_stream_url")]
+    pub stream_url: String,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct KeyValueFlagsOpts {
+    /// The key-value store, from those in the runtime config, in which flags
+    /// are stored.
+    #[serde(default = "default_store")]
+    pub store: String,
+    /// The prefix of the key of each flag.
+    #[serde(default = "default_key_prefix")]
+    pub key_prefix: String,
+    /// How long a flag is kept before it is read again.
+    #[serde(default = "default_cache_ttl_secs")]
+    pub cache_ttl_secs: u64,
+}
+
+fn default_stream_url() -> String {
+    "https://stream.launchdarkly.com".into()
+}
+
+fn default_store() -> String {
+    "default".into()
+}
+
+fn default_key_prefix() -> String {
+    "flags/".into()
+}
+
+fn default_cache_ttl_secs() -> u64 {
+    10
+}
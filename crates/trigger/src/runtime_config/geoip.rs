@@ -0,0 +1,41 @@
+use std::{path::PathBuf, sync::Arc};
+
+use anyhow::Result;
+use serde::Deserialize;
+use spin_geoip::{Database, GeoIp};
+
+use super::{resolve_config_path, RuntimeConfig};
+
+/// Opens the GeoIP databases named in the given [`RuntimeConfig`], if it has
+/// a `[geoip]` section.
+pub fn build(runtime_config: &RuntimeConfig) -> Result<Option<Arc<GeoIp>>> {
+    let Some((opts, config_opts)) = runtime_config
+        .opts_layers()
+        .find_map(|layer| Some((layer.geoip.as_ref()?, layer)))
+    else {
+        return Ok(None);
+    };
+    let open = |path: &Option<PathBuf>| {
+        path.as_deref()
+            .map(|path| Database::open(&resolve_config_path(path, config_opts)?))
+            .transpose()
+    };
+    Ok(Some(Arc::new(GeoIp::new(
+        open(&opts.country_database)?,
+        open(&opts.asn_database)?,
+    ))))
+}
+
+// Holds deserialized options from a `[geoip]` runtime config section.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct GeoIpOpts {
+    /// A MaxMind DB file with countries, such as GeoLite2-Country or
+    /// GeoLite2-City, relative to the runtime config file.
+    #[serde(default)]
+    pub country_database: Option<PathBuf>,
+    /// A MaxMind DB file with autonomous systems, such as GeoLite2-ASN,
+    /// relative to the runtime config file.
+    #[serde(default)]
+    pub asn_database: Option<PathBuf>,
+}
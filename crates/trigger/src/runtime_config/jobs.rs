@@ -0,0 +1,96 @@
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use spin_jobs::{JobStore, KeyValueJobStore, KeyValueStepStore, StepStore};
+use spin_jobs_postgres::{PostgresJobStore, PostgresStepStore};
+use spin_key_value::StoreManager;
+
+use super::RuntimeConfig;
+
+/// Builds the store in which jobs are scheduled from the given
+/// [`RuntimeConfig`].
+pub fn build_store(runtime_config: &RuntimeConfig) -> Result<Arc<dyn JobStore>> {
+    let default_opts = JobStoreOpts::default();
+    let opts = runtime_config.jobs().unwrap_or(&default_opts);
+    opts.build_store(runtime_config)
+        .context("Failed to build job store")
+}
+
+/// Builds the store in which workflow progress is recorded from the given
+/// [`RuntimeConfig`]. Workflows share the `[jobs]` section, as runs are
+/// usually driven by jobs.
+pub fn build_step_store(runtime_config: &RuntimeConfig) -> Result<Arc<dyn StepStore>> {
+    let default_opts = JobStoreOpts::default();
+    let opts = runtime_config.jobs().unwrap_or(&default_opts);
+    opts.build_step_store(runtime_config)
+        .context("Failed to build workflow store")
+}
+
+// Holds deserialized options from a `[jobs]` runtime config section.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "snake_case", tag = "type")]
+pub enum JobStoreOpts {
+    KeyValue(KeyValueJobStoreOpts),
+    Postgres(PostgresJobStoreOpts),
+}
+
+impl Default for JobStoreOpts {
+    fn default() -> Self {
+        Self::KeyValue(KeyValueJobStoreOpts {
+            store: default_store(),
+        })
+    }
+}
+
+impl JobStoreOpts {
+    fn build_store(&self, runtime_config: &RuntimeConfig) -> Result<Arc<dyn JobStore>> {
+        match self {
+            Self::KeyValue(opts) => Ok(Arc::new(KeyValueJobStore::new(
+                opts.manager(runtime_config)?,
+                opts.store.clone(),
+            ))),
+            Self::Postgres(opts) => Ok(Arc::new(PostgresJobStore::new(&opts.connection)?)),
+        }
+    }
+
+    fn build_step_store(&self, runtime_config: &RuntimeConfig) -> Result<Arc<dyn StepStore>> {
+        match self {
+            Self::KeyValue(opts) => Ok(Arc::new(KeyValueStepStore::new(
+                opts.manager(runtime_config)?,
+                opts.store.clone(),
+            ))),
+            Self::Postgres(opts) => Ok(Arc::new(PostgresStepStore::new(&opts.connection)?)),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct KeyValueJobStoreOpts {
+    /// The key-value store, from those in the runtime config, in which jobs
+    /// are stored.
+    #[serde(default = "default_store")]
+    pub store: String,
+}
+
+impl KeyValueJobStoreOpts {
+    fn manager(&self, runtime_config: &RuntimeConfig) -> Result<Arc<dyn StoreManager>> {
+        let (manager, _) = runtime_config
+            .key_value_stores()?
+            .into_iter()
+            .find_map(|(name, store)| (name == self.store).then_some(store))
+            .with_context(|| format!("No key-value store named {:?}", self.store))?;
+        Ok(manager)
+    }
+}
+
+fn default_store() -> String {
+    "default".into()
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PostgresJobStoreOpts {
+    pub connection: String,
+}
@@ -7,7 +7,9 @@ use spin_key_value::{
     CachingStoreManager, DelegatingStoreManager, KeyValueComponent, StoreManager,
     KEY_VALUE_STORES_KEY,
 };
+use spin_key_value_aws_dynamo::KeyValueAwsDynamo;
 use spin_key_value_azure::KeyValueAzureCosmos;
+use spin_key_value_postgres::KeyValuePostgres;
 use spin_key_value_sqlite::{DatabaseLocation, KeyValueSqlite};
 
 use super::{resolve_config_path, RuntimeConfigOpts};
@@ -29,7 +31,7 @@ pub async fn build_key_value_component(
 
     // Avoid creating a database as a side-effect if one is not needed.
     if !init_data.is_empty() {
-        if let Some(manager) = stores.get("default") {
+        if let Some((manager, _)) = stores.get("default") {
             let default_store = manager
                 .get("default")
                 .await
@@ -47,20 +49,64 @@ pub async fn build_key_value_component(
         }
     }
 
-    let delegating_manager = DelegatingStoreManager::new(stores);
-    let caching_manager = Arc::new(CachingStoreManager::new(delegating_manager));
+    // Local stores are cached in process; shared stores may be written by
+    // other replicas, so every operation goes straight to the backend.
+    let delegates = stores.into_iter().map(|(name, (store, scope))| {
+        let store: KeyValueStore = match scope {
+            StoreScope::Local => {
+                let local = DelegatingStoreManager::new([(name.clone(), store)]);
+                Arc::new(CachingStoreManager::new(local))
+            }
+            StoreScope::Shared => store,
+        };
+        (name, store)
+    });
+    let manager: KeyValueStore = Arc::new(DelegatingStoreManager::new(delegates));
     Ok(KeyValueComponent::new(spin_key_value::manager(move |_| {
-        caching_manager.clone()
+        manager.clone()
     })))
 }
 
+/// Whether a store's data is shared with other replicas of the application.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum StoreScope {
+    /// The data is only used by this Spin instance, and may be cached in
+    /// process.
+    #[default]
+    Local,
+    /// The data is shared with other replicas, and must not be cached.
+    Shared,
+}
+
 // Holds deserialized options from a `[key_value_store.<name>]` runtime config section.
+#[derive(Clone, Debug, Deserialize)]
+pub struct KeyValueStoreConfig {
+    #[serde(default)]
+    pub scope: StoreScope,
+    #[serde(flatten)]
+    pub opts: KeyValueStoreOpts,
+}
+
+impl KeyValueStoreConfig {
+    pub fn build_store(&self, config_opts: &RuntimeConfigOpts) -> Result<KeyValueStore> {
+        if self.scope == StoreScope::Shared {
+            if let KeyValueStoreOpts::Spin(SpinKeyValueStoreOpts { path: None }) = &self.opts {
+                bail!("An in-memory store cannot be shared; set a path or use a networked store type such as 'redis'");
+            }
+        }
+        self.opts.build_store(config_opts)
+    }
+}
+
 #[derive(Clone, Debug, Deserialize)]
 #[serde(rename_all = "snake_case", tag = "type")]
 pub enum KeyValueStoreOpts {
     Spin(SpinKeyValueStoreOpts),
     Redis(RedisKeyValueStoreOpts),
     AzureCosmos(AzureCosmosConfig),
+    AwsDynamo(AwsDynamoConfig),
+    Postgres(PostgresKeyValueStoreOpts),
 }
 
 impl KeyValueStoreOpts {
@@ -73,6 +119,8 @@ impl KeyValueStoreOpts {
             Self::Spin(opts) => opts.build_store(config_opts),
             Self::Redis(opts) => opts.build_store(),
             Self::AzureCosmos(opts) => opts.build_store(),
+            Self::AwsDynamo(opts) => opts.build_store(),
+            Self::Postgres(opts) => opts.build_store(),
         }
     }
 }
@@ -119,6 +167,18 @@ impl RedisKeyValueStoreOpts {
     }
 }
 
+#[derive(Clone, Debug, Deserialize)]
+pub struct PostgresKeyValueStoreOpts {
+    pub connection_string: String,
+}
+
+impl PostgresKeyValueStoreOpts {
+    fn build_store(&self) -> Result<KeyValueStore> {
+        let kv_postgres = KeyValuePostgres::new(&self.connection_string)?;
+        Ok(Arc::new(kv_postgres))
+    }
+}
+
 #[derive(Clone, Debug, Deserialize)]
 pub struct AzureCosmosConfig {
     key: String,
@@ -139,6 +199,30 @@ impl AzureCosmosConfig {
     }
 }
 
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AwsDynamoConfig {
+    table: String,
+    /// Defaults to the region of the AWS SDK's standard config chain.
+    region: Option<String>,
+    /// Overrides the DynamoDB endpoint, e.g. to use DynamoDB Local.
+    endpoint: Option<String>,
+    #[serde(default)]
+    consistent_read: bool,
+}
+
+impl AwsDynamoConfig {
+    pub fn build_store(&self) -> Result<Arc<dyn StoreManager>> {
+        let kv_aws_dynamo = KeyValueAwsDynamo::new(
+            self.table.clone(),
+            self.region.clone(),
+            self.endpoint.clone(),
+            self.consistent_read,
+        );
+        Ok(Arc::new(kv_aws_dynamo))
+    }
+}
+
 // Prints startup messages about the default key value store config.
 pub struct KeyValuePersistenceMessageHook;
 
@@ -167,6 +251,15 @@ impl TriggerHooks for KeyValuePersistenceMessageHook {
             KeyValueStoreOpts::AzureCosmos(store_opts) => {
                 println!("Storing default key-value data to Azure CosmosDB: account: {}, database: {}, container: {}", store_opts.account, store_opts.database, store_opts.container);
             }
+            KeyValueStoreOpts::Postgres(_store_opts) => {
+                println!("Storing default key-value data to Postgres");
+            }
+            KeyValueStoreOpts::AwsDynamo(store_opts) => {
+                println!(
+                    "Storing default key-value data to AWS DynamoDB table {}",
+                    store_opts.table
+                );
+            }
         }
         Ok(())
     }
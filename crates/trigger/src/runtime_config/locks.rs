@@ -0,0 +1,56 @@
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use spin_locks::{LockManager, MemoryLocks};
+use spin_locks_postgres::PostgresLocks;
+use spin_locks_redis::RedisLocks;
+
+use super::RuntimeConfig;
+
+/// Builds the manager which holds the application's locks from the given
+/// [`RuntimeConfig`].
+pub fn build_manager(runtime_config: &RuntimeConfig) -> Result<Arc<dyn LockManager>> {
+    let opts = runtime_config.locks().unwrap_or(&LockManagerOpts::Memory);
+    opts.build_manager().context("Failed to build lock manager")
+}
+
+// Holds deserialized options from a `[locks]` runtime config section.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "snake_case", tag = "type")]
+pub enum LockManagerOpts {
+    /// Locks only shared within this process, for a single instance.
+    Memory,
+    Redis(RedisLockManagerOpts),
+    Postgres(PostgresLockManagerOpts),
+}
+
+impl LockManagerOpts {
+    fn build_manager(&self) -> Result<Arc<dyn LockManager>> {
+        match self {
+            Self::Memory => Ok(Arc::new(MemoryLocks::new())),
+            Self::Redis(opts) => {
+                let key_prefix = opts
+                    .key_prefix
+                    .clone()
+                    .unwrap_or_else(|| "spin-locks:".into());
+                Ok(Arc::new(RedisLocks::new(opts.url.clone(), key_prefix)?))
+            }
+            Self::Postgres(opts) => Ok(Arc::new(PostgresLocks::new(&opts.connection)?)),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RedisLockManagerOpts {
+    pub url: String,
+    /// Prepended to every lock's key. Defaults to `spin-locks:`.
+    pub key_prefix: Option<String>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PostgresLockManagerOpts {
+    pub connection: String,
+}
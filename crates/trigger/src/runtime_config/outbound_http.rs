@@ -0,0 +1,55 @@
+use std::{collections::BTreeMap, time::Duration};
+
+use anyhow::Result;
+use outbound_http::{
+    pool::{self, PoolConfig, DEFAULT_IDLE_TIMEOUT},
+    proxy::ProxyConfig,
+};
+use serde::Deserialize;
+
+// Holds deserialized options from an `[outbound_http]` runtime config section.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct OutboundHttpOpts {
+    /// The most requests which may be in flight to one host at a time.
+    /// Unlimited if unset.
+    #[serde(default)]
+    pub max_connections_per_host: Option<usize>,
+    /// How long an idle connection is kept open for reuse, in seconds.
+    #[serde(default)]
+    pub idle_timeout_secs: Option<u64>,
+    /// The proxy requests are sent through, e.g. `http://proxy:3128` or
+    /// `socks5://proxy:1080`. If unset, `HTTPS_PROXY`, `HTTP_PROXY`,
+    /// `ALL_PROXY` and `NO_PROXY` are honored.
+    #[serde(default)]
+    pub proxy: Option<String>,
+    /// Hosts which are connected to directly rather than through `proxy`.
+    #[serde(default)]
+    pub no_proxy: Vec<String>,
+    /// Host pattern -> the proxy for matching hosts, overriding `proxy` and
+    /// `no_proxy`.
+    #[serde(default)]
+    pub proxy_hosts: BTreeMap<String, String>,
+}
+
+impl OutboundHttpOpts {
+    /// Configures the outbound HTTP connection pool.
+    pub fn configure_pool(&self) -> Result<()> {
+        pool::configure(PoolConfig {
+            max_connections_per_host: self.max_connections_per_host,
+            idle_timeout: self
+                .idle_timeout_secs
+                .map(Duration::from_secs)
+                .unwrap_or(DEFAULT_IDLE_TIMEOUT),
+            proxy: ProxyConfig {
+                default: self.proxy.clone(),
+                hosts: self
+                    .proxy_hosts
+                    .iter()
+                    .map(|(host, proxy)| (host.clone(), proxy.clone()))
+                    .collect(),
+                no_proxy: self.no_proxy.clone(),
+            },
+        })
+    }
+}
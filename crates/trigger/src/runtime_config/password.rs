@@ -0,0 +1,46 @@
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use spin_password::{Params, PasswordComponent};
+
+use super::RuntimeConfig;
+
+/// Builds a [`PasswordComponent`] from the given [`RuntimeConfig`].
+pub fn build_component(runtime_config: &RuntimeConfig) -> Result<PasswordComponent> {
+    let params = runtime_config
+        .password()
+        .map_or_else(Params::default, PasswordOpts::params);
+    PasswordComponent::new(params).context("Invalid `[password]` runtime config")
+}
+
+// Holds deserialized options from a `[password]` runtime config section.
+// Omitted options keep the default cost.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PasswordOpts {
+    /// The KiB of memory each Argon2id hash uses.
+    #[serde(default)]
+    pub argon2_memory_kib: Option<u32>,
+    /// The passes each Argon2id hash makes over its memory.
+    #[serde(default)]
+    pub argon2_iterations: Option<u32>,
+    /// The lanes of each Argon2id hash.
+    #[serde(default)]
+    pub argon2_parallelism: Option<u32>,
+    /// The log2 of the rounds of each bcrypt hash.
+    #[serde(default)]
+    pub bcrypt_cost: Option<u32>,
+}
+
+impl PasswordOpts {
+    pub fn params(&self) -> Params {
+        let default = Params::default();
+        Params {
+            argon2_memory_kib: self.argon2_memory_kib.unwrap_or(default.argon2_memory_kib),
+            argon2_iterations: self.argon2_iterations.unwrap_or(default.argon2_iterations),
+            argon2_parallelism: self
+                .argon2_parallelism
+                .unwrap_or(default.argon2_parallelism),
+            bcrypt_cost: self.bcrypt_cost.unwrap_or(default.bcrypt_cost),
+        }
+    }
+}
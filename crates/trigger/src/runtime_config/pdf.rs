@@ -0,0 +1,38 @@
+use std::sync::Arc;
+
+use serde::Deserialize;
+use spin_pdf::{PdfComponent, PdfRenderer, RemoteHttpRenderer};
+use url::Url;
+
+use super::RuntimeConfig;
+
+/// Builds a [`PdfComponent`] which renders with `renderer` if given, or else
+/// with the renderer in the given [`RuntimeConfig`].
+pub fn build_component(
+    runtime_config: &RuntimeConfig,
+    renderer: Option<Arc<dyn PdfRenderer>>,
+) -> PdfComponent {
+    let renderer = renderer.or_else(|| match runtime_config.pdf()? {
+        PdfOpts::RemoteHttp(opts) => Some(Arc::new(RemoteHttpRenderer::new(
+            opts.url.clone(),
+            opts.auth_token.clone(),
+        )) as Arc<dyn PdfRenderer>),
+    });
+    PdfComponent::new(renderer)
+}
+
+// Holds deserialized options from a `[pdf]` runtime config section.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "snake_case", tag = "type")]
+pub enum PdfOpts {
+    RemoteHttp(RemoteHttpPdfOpts),
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct RemoteHttpPdfOpts {
+    /// The URL documents are posted to.
+    pub url: Url,
+    /// The bearer token the renderer service requires, if any.
+    #[serde(default)]
+    pub auth_token: Option<String>,
+}
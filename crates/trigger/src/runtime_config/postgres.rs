@@ -0,0 +1,34 @@
+use std::collections::HashMap;
+
+use outbound_pg::{OutboundPg, PgDatabaseConfig};
+
+use crate::runtime_config::RuntimeConfig;
+
+pub(crate) fn build_component(runtime_config: &RuntimeConfig) -> OutboundPg {
+    let databases = runtime_config
+        .postgres_databases()
+        .into_iter()
+        .map(|(name, opts)| (name, opts.into()))
+        .collect::<HashMap<_, _>>();
+    OutboundPg::new(databases)
+}
+
+// Holds deserialized options from a `[postgres_database.<name>]` runtime config section.
+#[derive(Clone, Debug, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PostgresDatabaseOpts {
+    /// The connection string of the primary database.
+    pub primary: String,
+    /// The connection strings of any read replicas.
+    #[serde(default)]
+    pub replicas: Vec<String>,
+}
+
+impl From<PostgresDatabaseOpts> for PgDatabaseConfig {
+    fn from(opts: PostgresDatabaseOpts) -> Self {
+        Self {
+            primary: opts.primary,
+            replicas: opts.replicas,
+        }
+    }
+}
@@ -0,0 +1,84 @@
+use std::{collections::BTreeSet, net::SocketAddr};
+
+use anyhow::Result;
+use outbound_http::discovery::{self, ServiceDiscovery};
+use spin_outbound_networking::ALLOWED_HOSTS_KEY;
+
+use crate::runtime_config::RuntimeConfig;
+
+const DEFAULT_CONSUL_URL: &str = "http://127.0.0.1:8500";
+
+/// Starts resolving the services which the app's components are allowed to
+/// reach, if service discovery is configured.
+pub(crate) async fn start(runtime_config: &RuntimeConfig, app: &spin_app::App) -> Result<()> {
+    let Some(opts) = runtime_config.service_discovery() else {
+        return Ok(());
+    };
+    let mut services = BTreeSet::new();
+    for component in app.components() {
+        for host in component
+            .get_metadata(ALLOWED_HOSTS_KEY)?
+            .unwrap_or_default()
+        {
+            let Ok(url) = url::Url::parse(&host) else {
+                continue;
+            };
+            if let Some(service) = url.host_str().and_then(discovery::service_name) {
+                services.insert(service.to_owned());
+            }
+        }
+    }
+    if services.is_empty() {
+        return Ok(());
+    }
+    discovery::start(opts.build()?, services).await
+}
+
+// Holds deserialized options from a `[service_discovery]` runtime config section.
+#[derive(Clone, Debug, serde::Deserialize)]
+#[serde(rename_all = "snake_case", tag = "type", deny_unknown_fields)]
+pub enum ServiceDiscoveryOpts {
+    /// Look services up as `_<service>._tcp.<domain>` SRV records.
+    DnsSrv {
+        domain: String,
+        /// Defaults to the system's first nameserver.
+        #[serde(default)]
+        nameserver: Option<SocketAddr>,
+    },
+    /// Look up the healthy instances of services in a Consul catalog.
+    Consul {
+        #[serde(default = "default_consul_url")]
+        url: String,
+        #[serde(default)]
+        token: Option<String>,
+        #[serde(default)]
+        datacenter: Option<String>,
+    },
+}
+
+impl ServiceDiscoveryOpts {
+    fn build(&self) -> Result<ServiceDiscovery> {
+        Ok(match self {
+            Self::DnsSrv { domain, nameserver } => ServiceDiscovery::DnsSrv {
+                nameserver: match nameserver {
+                    Some(nameserver) => *nameserver,
+                    None => ServiceDiscovery::system_nameserver()?,
+                },
+                domain: domain.clone(),
+            },
+            Self::Consul {
+                url,
+                token,
+                datacenter,
+            } => ServiceDiscovery::Consul {
+                url: url.clone(),
+                token: token.clone(),
+                datacenter: datacenter.clone(),
+            },
+        })
+    }
+}
+
+fn default_consul_url() -> String {
+    DEFAULT_CONSUL_URL.to_owned()
+}
@@ -0,0 +1,28 @@
+use serde::Deserialize;
+
+// Holds deserialized options from a `[wasmtime]` runtime config section.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WasmtimeOpts {
+    /// Initialize linear memories by mapping a copy-on-write image of their
+    /// initial contents, so that instances of a component share the pages
+    /// they don't modify. Enabled unless set to false.
+    #[serde(default)]
+    pub memory_init_cow: Option<bool>,
+    /// The size in bytes up to which a sparsely initialized memory is given
+    /// a dense image, so that it can be mapped copy-on-write.
+    #[serde(default)]
+    pub memory_guaranteed_dense_image_size: Option<u64>,
+}
+
+impl WasmtimeOpts {
+    /// Applies the options to the engine configuration.
+    pub fn apply(&self, config: &mut spin_core::Config) {
+        if let Some(enable) = self.memory_init_cow {
+            config.memory_init_cow(enable);
+        }
+        if let Some(size) = self.memory_guaranteed_dense_image_size {
+            config.memory_guaranteed_dense_image_size(size);
+        }
+    }
+}
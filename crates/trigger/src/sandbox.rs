@@ -0,0 +1,104 @@
+//! Sandbox profiles, for operators running components they don't trust.
+//!
+//! In the `strict` profile a component can use only what its manifest grants
+//! it explicitly:
+//!
+//! * wildcard `allowed_outbound_hosts` (such as `*://*:*`, or the deprecated
+//!   `insecure:allow-all`) are refused rather than honoured;
+//! * application variables are not read from the host's environment or from
+//!   a `.env` file, only from explicitly configured providers and defaults;
+//! * mounted files cannot be made writable.
+//!
+//! Independently of the profile, the WASI clocks can be made coarse, so that
+//! a component cannot use them for precise timing.
+
+use std::time::Duration;
+
+use anyhow::{bail, Result};
+use clap::ValueEnum;
+use spin_app::AppComponent;
+use spin_core::StoreBuilder;
+use spin_outbound_networking::ALLOWED_HOSTS_KEY;
+
+use crate::{RuntimeConfig, TriggerHooks};
+
+/// How much a component may do beyond what its manifest grants.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum SandboxProfile {
+    /// The usual Spin behaviour.
+    #[default]
+    Default,
+    /// Only capabilities explicitly granted in the manifest are available.
+    Strict,
+}
+
+/// Implements TriggerHooks, enforcing the sandbox profile and applying the
+/// clock options.
+pub(crate) struct SandboxTriggerHooks {
+    profile: SandboxProfile,
+    clock_resolution: Option<Duration>,
+}
+
+impl SandboxTriggerHooks {
+    pub(crate) fn new(profile: SandboxProfile, clock_resolution: Option<Duration>) -> Self {
+        Self {
+            profile,
+            clock_resolution,
+        }
+    }
+}
+
+impl TriggerHooks for SandboxTriggerHooks {
+    fn app_loaded(&mut self, app: &spin_app::App, _runtime_config: &RuntimeConfig) -> Result<()> {
+        if self.profile == SandboxProfile::Strict {
+            for component in app.components() {
+                check_strict(&component)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn component_store_builder(
+        &self,
+        _component: &AppComponent,
+        store_builder: &mut StoreBuilder,
+    ) -> Result<()> {
+        if let Some(resolution) = self.clock_resolution {
+            store_builder.coarse_clocks(resolution)?;
+        }
+        Ok(())
+    }
+}
+
+fn check_strict(component: &AppComponent) -> Result<()> {
+    let hosts = component
+        .get_metadata(ALLOWED_HOSTS_KEY)?
+        .unwrap_or_default();
+    if let Some(host) = hosts.iter().find(|host| is_wildcard(host)) {
+        bail!(
+            "Component '{}' allows outbound connections to '{host}'. The strict sandbox requires each allowed host to be listed explicitly.",
+            component.id()
+        );
+    }
+    Ok(())
+}
+
+/// Whether an `allowed_outbound_hosts` entry allows any host or any port,
+/// e.g. `*://*:*` or `https://*.example.com`.
+fn is_wildcard(host: &str) -> bool {
+    host.contains('*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wildcard_hosts_are_recognised() {
+        assert!(is_wildcard("*://*:*"));
+        assert!(is_wildcard("https://*.example.com"));
+        assert!(is_wildcard("redis://cache:*"));
+        assert!(!is_wildcard("https://example.com"));
+        assert!(!is_wildcard("postgres://db:5432"));
+    }
+}
@@ -0,0 +1,136 @@
+//! Seed data for local development, as given to `spin up --seed`.
+//!
+//! A seed is applied each time the application starts, once any pending
+//! migrations have been applied, so seed files should be written to apply
+//! cleanly again, e.g. with `INSERT OR REPLACE`. `spin data reset` clears
+//! local data so that the seeds are applied to empty stores.
+
+use std::{collections::HashMap, path::PathBuf, str::FromStr, sync::Arc};
+
+use anyhow::{anyhow, bail, Context, Result};
+use serde_json::Value;
+use spin_sqlite::Connection;
+
+use crate::runtime_config::{key_value::KeyValueStore, RuntimeConfig};
+
+/// A seed file and the database or key-value store it applies to.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Seed {
+    /// The name of the database or store, `default` if not given.
+    pub store: String,
+    pub path: PathBuf,
+    pub kind: SeedKind,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SeedKind {
+    /// A `.sql` file, run against the Postgres database of the given name if
+    /// the runtime config has one, and the SQLite database otherwise.
+    Sql,
+    /// A `.json` file holding an object, whose entries are set in the
+    /// key-value store. String values are stored as they are, and other
+    /// values as JSON.
+    KeyValue,
+}
+
+impl FromStr for Seed {
+    type Err = anyhow::Error;
+
+    /// Parses `[NAME=]FILE`.
+    fn from_str(s: &str) -> Result<Self> {
+        let (store, path) = match s.split_once('=') {
+            Some((store, path)) => (store, path),
+            None => ("default", s),
+        };
+        let path = PathBuf::from(path);
+        let kind = match path.extension().and_then(|ext| ext.to_str()) {
+            Some("sql") => SeedKind::Sql,
+            Some("json") => SeedKind::KeyValue,
+            _ => bail!("Seed file {path:?} should be a .sql or .json file"),
+        };
+        Ok(Self {
+            store: store.to_owned(),
+            path,
+            kind,
+        })
+    }
+}
+
+/// Applies `seeds` to the databases and stores configured by
+/// `runtime_config`, in order.
+pub(crate) async fn apply(seeds: &[Seed], runtime_config: &RuntimeConfig) -> Result<()> {
+    if seeds.is_empty() {
+        return Ok(());
+    }
+    let sqlite_databases: HashMap<_, _> = runtime_config.sqlite_databases()?.into_iter().collect();
+    let key_value_stores: HashMap<_, _> = runtime_config.key_value_stores()?.into_iter().collect();
+
+    for Seed { store, path, kind } in seeds {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read seed file {path:?}"))?;
+        let seeded = match kind {
+            SeedKind::Sql => {
+                seed_database(store, &contents, runtime_config, &sqlite_databases).await
+            }
+            SeedKind::KeyValue => {
+                let Some((manager, _)) = key_value_stores.get(store) else {
+                    bail!("No key-value store named {store:?} is configured");
+                };
+                seed_key_value_store(store, &contents, manager).await
+            }
+        };
+        seeded.with_context(|| format!("Failed to seed {store:?} from {path:?}"))?;
+        terminal::step!("Seeded", "{store} from {}", path.display());
+    }
+    Ok(())
+}
+
+async fn seed_database(
+    name: &str,
+    sql: &str,
+    runtime_config: &RuntimeConfig,
+    sqlite_databases: &HashMap<String, Arc<dyn Connection>>,
+) -> Result<()> {
+    if let Some(opts) = runtime_config.postgres_databases().get(name) {
+        let database = spin_migrations::PostgresDatabase::connect(&opts.primary).await?;
+        database.batch_execute(sql).await
+    } else if let Some(database) = sqlite_databases.get(name) {
+        database.execute_batch(sql).await
+    } else {
+        Err(anyhow!("No database named {name:?} is configured"))
+    }
+}
+
+async fn seed_key_value_store(name: &str, json: &str, manager: &KeyValueStore) -> Result<()> {
+    let entries: serde_json::Map<String, Value> =
+        serde_json::from_str(json).context("The seed file should hold a JSON object")?;
+    let store = manager.get(name).await?;
+    for (key, value) in entries {
+        let value = match value {
+            Value::String(value) => value,
+            value => value.to_string(),
+        };
+        store.set(&key, value.as_bytes()).await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn seeds_name_their_store() -> Result<()> {
+        let seed: Seed = "seed.sql".parse()?;
+        assert_eq!("default", seed.store);
+        assert_eq!(SeedKind::Sql, seed.kind);
+
+        let seed: Seed = "cache=fixtures/cache.json".parse()?;
+        assert_eq!("cache", seed.store);
+        assert_eq!(PathBuf::from("fixtures/cache.json"), seed.path);
+        assert_eq!(SeedKind::KeyValue, seed.kind);
+
+        assert!("seed.csv".parse::<Seed>().is_err());
+        Ok(())
+    }
+}
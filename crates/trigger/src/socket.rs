@@ -0,0 +1,181 @@
+//! The Unix sockets through which a running trigger is controlled.
+//!
+//! Each connection carries a single request line and receives a single
+//! response line. A connection which fails, whether it can't be accepted,
+//! doesn't send a whole line in time or goes away before it is answered, is
+//! logged and the socket goes on serving the next one.
+
+use std::{future::Future, ops::ControlFlow, path::PathBuf, time::Duration};
+
+use anyhow::{Context, Result};
+
+/// How long a client may take to send its request line.
+const READ_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long to wait before accepting again after a failure, so that a
+/// persistent failure such as running out of file descriptors doesn't spin.
+const ACCEPT_RETRY_DELAY: Duration = Duration::from_millis(100);
+
+/// Serves the `name` socket at `socket_path`, passing each request line to
+/// `handle`. The line `handle` returns is written back as the response; if
+/// it is a [`ControlFlow::Break`], the socket stops serving once it has
+/// been written.
+pub(crate) async fn serve_line_socket<F, Fut>(
+    name: &str,
+    socket_path: PathBuf,
+    mut handle: F,
+) -> Result<()>
+where
+    F: FnMut(String) -> Fut,
+    Fut: Future<Output = ControlFlow<String, String>>,
+{
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+    // A stale socket from a previous run would otherwise prevent binding.
+    if socket_path.exists() {
+        std::fs::remove_file(&socket_path)
+            .with_context(|| format!("failed to remove stale {name} socket {socket_path:?}"))?;
+    }
+    let listener = tokio::net::UnixListener::bind(&socket_path)
+        .with_context(|| format!("unable to listen on {name} socket {socket_path:?}"))?;
+    tracing::info!("Listening for {name} requests on {socket_path:?}");
+
+    loop {
+        let stream = match listener.accept().await {
+            Ok((stream, _)) => stream,
+            Err(e) => {
+                tracing::warn!("Failed to accept {name} connection: {e}");
+                tokio::time::sleep(ACCEPT_RETRY_DELAY).await;
+                continue;
+            }
+        };
+        let (reader, mut writer) = stream.into_split();
+        let mut line = String::new();
+        let read = BufReader::new(reader).read_line(&mut line);
+        match tokio::time::timeout(READ_TIMEOUT, read).await {
+            Ok(Ok(_)) => (),
+            Ok(Err(e)) => {
+                tracing::warn!("Failed to read {name} request: {e}");
+                continue;
+            }
+            Err(_) => {
+                tracing::warn!("Timed out reading {name} request");
+                continue;
+            }
+        }
+        if line.is_empty() {
+            tracing::debug!("{name} connection closed without a request");
+            continue;
+        }
+
+        let (mut response, stop) = match handle(line).await {
+            ControlFlow::Continue(response) => (response, false),
+            ControlFlow::Break(response) => (response, true),
+        };
+        if !response.ends_with('\n') {
+            response.push('\n');
+        }
+        if let Err(e) = writer.write_all(response.as_bytes()).await {
+            tracing::warn!("Failed to respond to {name} request: {e}");
+        }
+        if stop {
+            return Ok(());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::{
+        io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+        net::UnixStream,
+    };
+
+    use super::*;
+
+    async fn request(socket_path: &std::path::Path, request: &[u8]) -> String {
+        let mut stream = UnixStream::connect(socket_path).await.unwrap();
+        stream.write_all(request).await.unwrap();
+        let mut response = String::new();
+        BufReader::new(stream)
+            .read_line(&mut response)
+            .await
+            .unwrap();
+        response
+    }
+
+    async fn serve(socket_path: PathBuf) -> tokio::task::JoinHandle<Result<()>> {
+        let server = tokio::spawn(serve_line_socket(
+            "test",
+            socket_path.clone(),
+            |line| async move {
+                match line.trim() {
+                    "stop" => ControlFlow::Break("stopping".to_owned()),
+                    other => ControlFlow::Continue(other.to_uppercase()),
+                }
+            },
+        ));
+        while !socket_path.exists() {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        server
+    }
+
+    #[tokio::test]
+    async fn answers_each_request_until_stopped() {
+        let dir = tempfile::tempdir().unwrap();
+        let socket_path = dir.path().join("test.sock");
+        let server = serve(socket_path.clone()).await;
+
+        assert_eq!("HELLO\n", request(&socket_path, b"hello\n").await);
+        assert_eq!("AGAIN\n", request(&socket_path, b"again\n").await);
+        assert_eq!("stopping\n", request(&socket_path, b"stop\n").await);
+        server.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn keeps_serving_after_bad_connections() {
+        let dir = tempfile::tempdir().unwrap();
+        let socket_path = dir.path().join("test.sock");
+        let server = serve(socket_path.clone()).await;
+
+        // Closed without sending anything.
+        drop(UnixStream::connect(&socket_path).await.unwrap());
+        // Not valid UTF-8, which fails the read.
+        let mut stream = UnixStream::connect(&socket_path).await.unwrap();
+        stream.write_all(b"\xff\xfe\n").await.unwrap();
+        drop(stream);
+        // Closed before the response is written.
+        let mut stream = UnixStream::connect(&socket_path).await.unwrap();
+        stream.write_all(b"gone\n").await.unwrap();
+        drop(stream);
+
+        assert_eq!("STILL HERE\n", request(&socket_path, b"still here\n").await);
+        assert_eq!("stopping\n", request(&socket_path, b"stop\n").await);
+        server.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn replaces_a_stale_socket() {
+        let dir = tempfile::tempdir().unwrap();
+        let socket_path = dir.path().join("test.sock");
+        std::fs::write(&socket_path, "stale").unwrap();
+        let server = tokio::spawn(serve_line_socket("test", socket_path.clone(), |_| async {
+            ControlFlow::Break("stopping".to_owned())
+        }));
+
+        let mut response = None;
+        for _ in 0..100 {
+            if let Ok(mut stream) = UnixStream::connect(&socket_path).await {
+                stream.write_all(b"stop\n").await.unwrap();
+                let mut line = String::new();
+                BufReader::new(stream).read_line(&mut line).await.unwrap();
+                response = Some(line);
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(Some("stopping\n".to_owned()), response);
+        server.await.unwrap().unwrap();
+    }
+}
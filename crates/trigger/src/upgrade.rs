@@ -0,0 +1,159 @@
+//! In-place upgrades of a running application.
+//!
+//! When a trigger is started with `--upgrade-socket <PATH>`, it listens on a
+//! Unix socket for upgrade requests. Each request is a single line containing
+//! the path to a new version of the application (a `spin.toml` file or a
+//! directory containing one). The new version is loaded and prepared
+//! alongside the running one and handed to the executor, which decides when
+//! (and whether) to switch over. The response is a single line: `OK`, or
+//! `ERROR: <message>` if the upgrade was rejected, in which case the
+//! previous version keeps running.
+//!
+//! Through the admin socket, a new version can instead be staged under a
+//! label alongside the running one, then promoted to take over once it
+//! passes smoke checks.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use tokio::sync::{mpsc, oneshot};
+
+/// A change to the version of the application which is running.
+pub struct Upgrade<Executor> {
+    /// What to change.
+    pub action: UpgradeAction<Executor>,
+    /// Receives the outcome of the upgrade.
+    pub result: oneshot::Sender<Result<()>>,
+}
+
+/// See [`Upgrade`].
+pub enum UpgradeAction<Executor> {
+    /// Replace the running version with the given executor's.
+    Replace(Executor),
+    /// Hold the given executor's version alongside the running one under
+    /// `label`, replacing any version already staged, until it is promoted.
+    Stage { executor: Executor, label: String },
+    /// Replace the running version with the one staged under `label`,
+    /// provided a request for each of the `checks` paths succeeds.
+    Promote { label: String, checks: Vec<String> },
+}
+
+/// A stream of upgrades for a running executor.
+pub type Upgrades<Executor> = mpsc::Receiver<Upgrade<Executor>>;
+
+/// Sends upgrades to a running executor.
+pub struct Upgrader<Executor> {
+    sender: mpsc::Sender<Upgrade<Executor>>,
+}
+
+impl<Executor> Clone for Upgrader<Executor> {
+    fn clone(&self) -> Self {
+        Self {
+            sender: self.sender.clone(),
+        }
+    }
+}
+
+impl<Executor> Upgrader<Executor> {
+    /// Creates a connected upgrader and upgrade stream.
+    pub fn new() -> (Self, Upgrades<Executor>) {
+        let (sender, receiver) = mpsc::channel(1);
+        (Self { sender }, receiver)
+    }
+
+    /// Hands the given executor to the running executor and waits for the
+    /// outcome of the upgrade.
+    pub async fn upgrade(&self, executor: Executor) -> Result<()> {
+        self.send(UpgradeAction::Replace(executor)).await
+    }
+
+    /// Hands the given executor to the running executor to be staged under
+    /// `label`.
+    pub async fn stage(&self, executor: Executor, label: String) -> Result<()> {
+        self.send(UpgradeAction::Stage { executor, label }).await
+    }
+
+    /// Asks the running executor to promote the version staged under
+    /// `label`, once it passes the `checks`.
+    pub async fn promote(&self, label: String, checks: Vec<String>) -> Result<()> {
+        self.send(UpgradeAction::Promote { label, checks }).await
+    }
+
+    async fn send(&self, action: UpgradeAction<Executor>) -> Result<()> {
+        let (result, outcome) = oneshot::channel();
+        self.sender
+            .send(Upgrade { action, result })
+            .await
+            .map_err(|_| anyhow::anyhow!("trigger executor is no longer running"))?;
+        outcome
+            .await
+            .context("trigger executor did not report the outcome of the upgrade")?
+    }
+}
+
+/// Loads the application at `manifest_path` into a fresh directory under
+/// `working_dir`, returning the new directory and the URL of its lock file.
+pub(crate) async fn prepare_upgrade(
+    manifest_path: &Path,
+    working_dir: &Path,
+    generation: usize,
+) -> Result<(PathBuf, String)> {
+    let manifest_path = spin_common::paths::resolve_manifest_file_path(manifest_path)?;
+    let upgrade_dir = working_dir.join(format!("upgrade-{generation}"));
+    tokio::fs::create_dir_all(&upgrade_dir)
+        .await
+        .with_context(|| format!("failed to create {upgrade_dir:?}"))?;
+
+    let locked_app = spin_loader::from_file(
+        &manifest_path,
+        spin_loader::FilesMountStrategy::Copy(upgrade_dir.join("assets")),
+    )
+    .await
+    .with_context(|| format!("failed to load manifest from {manifest_path:?}"))?;
+
+    let locked_path = upgrade_dir.join("spin.lock");
+    let contents = locked_app
+        .to_json()
+        .context("failed to serialize locked app")?;
+    tokio::fs::write(&locked_path, contents)
+        .await
+        .with_context(|| format!("failed to write {locked_path:?}"))?;
+    let locked_url = url::Url::from_file_path(&locked_path)
+        .map_err(|_| anyhow::anyhow!("cannot convert to file URL: {locked_path:?}"))?
+        .to_string();
+
+    Ok((upgrade_dir, locked_url))
+}
+
+/// Serves upgrade requests from `socket_path`, passing the manifest path of
+/// each new version to `upgrade`.
+#[cfg(unix)]
+pub(crate) async fn serve_upgrade_socket<F, Fut>(socket_path: PathBuf, mut upgrade: F) -> Result<()>
+where
+    F: FnMut(PathBuf) -> Fut,
+    Fut: std::future::Future<Output = Result<()>>,
+{
+    crate::socket::serve_line_socket("upgrade", socket_path, |line| {
+        let manifest_path = PathBuf::from(line.trim());
+        tracing::info!("Upgrading application from {manifest_path:?}");
+        let upgraded = upgrade(manifest_path);
+        async move {
+            std::ops::ControlFlow::Continue(match upgraded.await {
+                Ok(()) => {
+                    terminal::step!("Upgraded", "application");
+                    "OK".to_owned()
+                }
+                Err(e) => {
+                    tracing::error!("Upgrade failed: {e:?}");
+                    format!("ERROR: {e:#}")
+                }
+            })
+        }
+    })
+    .await
+}
+
+#[cfg(not(unix))]
+pub(crate) async fn serve_upgrade_socket<F>(_socket_path: PathBuf, _upgrade: F) -> Result<()> {
+    anyhow::bail!("--upgrade-socket is only supported on Unix platforms")
+}
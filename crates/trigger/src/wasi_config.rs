@@ -0,0 +1,37 @@
+//! The `wasi` settings of a component in the manifest, which control what
+//! the component sees of the host's clocks, randomness and files.
+
+use std::time::Duration;
+
+use anyhow::Result;
+use spin_app::{AppComponent, MetadataKey};
+use spin_core::{FilesystemAccess, StoreBuilder};
+use spin_manifest::schema::v2::{ComponentWasi, FilesystemVisibility};
+
+pub(crate) const WASI_KEY: MetadataKey<ComponentWasi> = MetadataKey::new("wasi");
+
+/// Applies the component's `wasi` settings, if any, to `store_builder`.
+pub(crate) fn apply(component: &AppComponent, store_builder: &mut StoreBuilder) -> Result<()> {
+    let Some(wasi) = component.get_metadata(WASI_KEY)? else {
+        return Ok(());
+    };
+    if let Some(seed) = wasi.random_seed {
+        store_builder.random_seed(seed)?;
+    }
+    if let Some(secs) = wasi.frozen_time {
+        store_builder.frozen_clocks(Duration::from_secs(secs))?;
+    }
+    if let Some(offset) = wasi.clock_offset {
+        store_builder.offset_wall_clock(offset)?;
+    }
+    store_builder.restrict_filesystem(filesystem_access(wasi.filesystem));
+    Ok(())
+}
+
+fn filesystem_access(visibility: FilesystemVisibility) -> FilesystemAccess {
+    match visibility {
+        FilesystemVisibility::Files => FilesystemAccess::ReadWrite,
+        FilesystemVisibility::ReadOnly => FilesystemAccess::ReadOnly,
+        FilesystemVisibility::None => FilesystemAccess::None,
+    }
+}
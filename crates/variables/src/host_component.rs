@@ -74,17 +74,19 @@ pub struct ComponentVariables {
 
 #[async_trait]
 impl variables::Host for ComponentVariables {
-    async fn get(&mut self, key: String) -> Result<Result<String, variables::Error>> {
+    async fn get(&mut self, name: String) -> Result<Result<String, variables::Error>> {
         Ok(async {
             // Set by DynamicHostComponent::update_data
             let component_id = self.component_id.as_deref().unwrap();
-            let key = Key::new(&key)?;
-            Ok(self
-                .resolver
-                .get()
-                .unwrap()
-                .resolve(component_id, key)
-                .await?)
+            let key = Key::new(&name)?;
+            if let Some(value) = spin_core::replay::replayed_variable(&name) {
+                return value.map_err(|e| variables::Error::Other(e.to_string()));
+            }
+            let resolver = self.resolver.get().unwrap();
+            let secret = resolver.is_secret(component_id, &key);
+            let value = resolver.resolve(component_id, key).await?;
+            spin_core::replay::record_variable(&name, &value, secret);
+            Ok(value)
         }
         .await)
     }
@@ -73,6 +73,36 @@ impl Resolver {
         self.resolve_template(template).await
     }
 
+    /// Resolves a template which isn't tied to a component, such as one in
+    /// trigger configuration. It may refer to any application variable.
+    pub async fn resolve_app_template(&self, template: impl Into<String>) -> Result<String> {
+        let template = self.validate_template(template.into())?;
+        self.resolve_template(&template).await
+    }
+
+    /// Returns whether a template refers to any secret variable, in which
+    /// case its resolved value shouldn't be displayed.
+    pub fn refers_to_secret(&self, template: impl Into<String>) -> Result<bool> {
+        let template = self.validate_template(template.into())?;
+        Ok(self.template_refers_to_secret(&template))
+    }
+
+    /// Returns whether a component variable's value comes from any secret
+    /// variable.
+    pub fn is_secret(&self, component_id: &str, key: &Key<'_>) -> bool {
+        self.component_configs
+            .get(component_id)
+            .and_then(|configs| configs.get(key.as_ref()))
+            .map_or(false, |template| self.template_refers_to_secret(template))
+    }
+
+    fn template_refers_to_secret(&self, template: &Template) -> bool {
+        template.parts().any(|part| match part {
+            Part::Expr(var) => self.variables.get(var.as_ref()).map_or(false, |v| v.secret),
+            Part::Lit(_) => false,
+        })
+    }
+
     async fn resolve_template(&self, template: &Template) -> Result<String> {
         let mut resolved_parts: Vec<Cow<str>> = Vec::with_capacity(template.parts().len());
         for part in template.parts() {
@@ -249,6 +279,44 @@ mod tests {
         );
     }
 
+    #[test]
+    fn templates_referring_to_secrets_are_recognised() {
+        let mut resolver = Resolver::new([
+            (
+                "password".into(),
+                Variable {
+                    default: None,
+                    secret: true,
+                },
+            ),
+            (
+                "host".into(),
+                Variable {
+                    default: None,
+                    secret: false,
+                },
+            ),
+        ])
+        .unwrap();
+        resolver
+            .add_component_variables(
+                "db",
+                [
+                    ("url".into(), "postgres://admin:{{ password }}@db".into()),
+                    ("host".into(), "{{ host }}".into()),
+                ],
+            )
+            .unwrap();
+        assert!(resolver.is_secret("db", &Key::new("url").unwrap()));
+        assert!(!resolver.is_secret("db", &Key::new("host").unwrap()));
+        assert!(!resolver.is_secret("other", &Key::new("url").unwrap()));
+        assert!(resolver
+            .refers_to_secret("postgres://admin:{{ password }}@{{ host }}")
+            .unwrap());
+        assert!(!resolver.refers_to_secret("postgres://{{ host }}").unwrap());
+        assert!(!resolver.refers_to_secret("static").unwrap());
+    }
+
     #[test]
     fn keys_good() {
         for key in ["a", "abc", "a1b2c3", "a_1", "a_1_b_3"] {
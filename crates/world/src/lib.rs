@@ -8,5 +8,6 @@ wasmtime::component::bindgen!({
 
 pub use fermyon::spin as v1;
 pub use fermyon::spin2_0_0 as v2;
+pub use fermyon::spin3_0_0 as v3;
 
 mod conversions;
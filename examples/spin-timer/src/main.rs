@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, time::SystemTime};
 
 use anyhow::Error;
 use clap::{Args, Parser};
@@ -6,7 +6,7 @@ use serde::{Deserialize, Serialize};
 use spin_app::MetadataKey;
 use spin_core::async_trait;
 use spin_trigger::{
-    cli::TriggerExecutorCommand, EitherInstance, TriggerAppEngine, TriggerExecutor,
+    cli::TriggerExecutorCommand, leader, EitherInstance, TriggerAppEngine, TriggerExecutor,
 };
 
 wasmtime::component::bindgen!({
@@ -37,7 +37,12 @@ pub struct CliArgs {
 struct TimerTrigger {
     engine: TriggerAppEngine<Self>,
     speedup: u64,
-    component_timings: HashMap<String, u64>,
+    component_timings: HashMap<String, Timing>,
+}
+
+struct Timing {
+    interval_secs: u64,
+    leader_election: bool,
 }
 
 // Application settings (raw serialization format)
@@ -54,6 +59,11 @@ struct TriggerMetadata {
 pub struct TimerTriggerConfig {
     component: String,
     interval_secs: u64,
+    /// When the application runs on several replicas, fire each tick on only
+    /// one of them. Ticks are then aligned to the clock rather than to when
+    /// the trigger started.
+    #[serde(default)]
+    leader_election: bool,
 }
 
 const TRIGGER_METADATA_KEY: MetadataKey<TriggerMetadata> = MetadataKey::new("trigger");
@@ -77,7 +87,13 @@ impl TriggerExecutor for TimerTrigger {
 
         let component_timings = engine
             .trigger_configs()
-            .map(|(_, config)| (config.component.clone(), config.interval_secs))
+            .map(|(_, config)| {
+                let timing = Timing {
+                    interval_secs: config.interval_secs,
+                    leader_election: config.leader_election,
+                };
+                (config.component.clone(), timing)
+            })
             .collect();
 
         Ok(Self {
@@ -104,11 +120,21 @@ impl TriggerExecutor for TimerTrigger {
             let speedup = self.speedup;
             tokio_scoped::scope(|scope| {
                 // For each component, run its own timer loop
-                for (c, d) in &self.component_timings {
+                for (c, timing) in &self.component_timings {
                     scope.spawn(async {
-                        let duration = tokio::time::Duration::from_millis(*d * 1000 / speedup);
+                        let duration = tokio::time::Duration::from_millis(
+                            timing.interval_secs * 1000 / speedup,
+                        );
                         loop {
-                            tokio::time::sleep(duration).await;
+                            if timing.leader_election {
+                                let (tick, until) = leader::next_tick(duration, SystemTime::now());
+                                tokio::time::sleep(until).await;
+                                if !self.engine.claim_tick(c, tick, duration / 2).await.unwrap() {
+                                    continue;
+                                }
+                            } else {
+                                tokio::time::sleep(duration).await;
+                            }
                             self.handle_timer_event(c).await.unwrap();
                         }
                     });
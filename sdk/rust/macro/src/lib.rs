@@ -4,11 +4,20 @@ use quote::quote;
 const WIT_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/wit");
 
 /// Generates the entrypoint to a Spin Redis component written in Rust.
+///
+/// The function may be `async`, in which case it can await outbound HTTP
+/// requests and the deferred variants of other host calls.
 #[proc_macro_attribute]
 pub fn redis_component(_attr: TokenStream, item: TokenStream) -> TokenStream {
     let func = syn::parse_macro_input!(item as syn::ItemFn);
     let func_name = &func.sig.ident;
     let preamble = preamble(Export::Redis);
+    let call =
+        quote!(super::#func_name(msg.try_into().expect("cannot convert from Spin Redis payload")));
+    let call = match func.sig.asyncness {
+        Some(_) => quote!(::spin_sdk::http::run(#call)),
+        None => call,
+    };
 
     quote!(
         #func
@@ -18,7 +27,7 @@ pub fn redis_component(_attr: TokenStream, item: TokenStream) -> TokenStream {
             }
             impl self::preamble::exports::fermyon::spin::inbound_redis::Guest for preamble::Spin {
                 fn handle_message(msg: self::preamble::exports::fermyon::spin::inbound_redis::Payload) -> Result<(), self::preamble::fermyon::spin::redis_types::Error> {
-                    match super::#func_name(msg.try_into().expect("cannot convert from Spin Redis payload")) {
+                    match #call {
                         Ok(()) => Ok(()),
                         Err(e) => {
                             eprintln!("{}", e);
@@ -62,6 +71,21 @@ pub fn redis_component(_attr: TokenStream, item: TokenStream) -> TokenStream {
 /// }
 /// ```
 ///
+/// An `async` handler can await outbound HTTP requests made with
+/// `spin_sdk::http::send`, and the `_async` variants of Postgres and Redis
+/// calls, and can join them to run them concurrently:
+///
+/// ```ignore
+/// #[http_component]
+/// async fn my_handler(_req: Request) -> anyhow::Result<impl IntoResponse> {
+///   let (a, b): (Response, Response) = futures::try_join!(
+///     spin_sdk::http::send(Request::get("https://a.example.com")),
+///     spin_sdk::http::send(Request::get("https://b.example.com")),
+///   )?;
+///   // ...
+/// }
+/// ```
+///
 /// ### Input/Output Params
 ///
 /// Input/Output functions allow for streaming HTTP bodies. This form is by its very nature harder to use than
@@ -110,6 +134,7 @@ pub fn http_component(_attr: TokenStream, item: TokenStream) -> TokenStream {
                             ::std::result::Result::Ok(req) => #handler,
                             ::std::result::Result::Err(e) => handle_response(response_out, e).await,
                         }
+                        ::spin_sdk::task::run_after_response().await;
                     });
                 }
             }
@@ -146,20 +171,55 @@ pub fn http_component(_attr: TokenStream, item: TokenStream) -> TokenStream {
     .into()
 }
 
+/// Generates the entrypoint to a component written in Rust which handles
+/// jobs scheduled with `spin_sdk::jobs`.
+///
+/// The function takes the job's ID and payload, and returns a `Result`;
+/// returning an error makes the host retry the job later. It may be `async`.
+#[proc_macro_attribute]
+pub fn job_component(_attr: TokenStream, item: TokenStream) -> TokenStream {
+    let func = syn::parse_macro_input!(item as syn::ItemFn);
+    let func_name = &func.sig.ident;
+    let preamble = preamble(Export::Job);
+    let call = quote!(super::#func_name(id, payload));
+    let call = match func.sig.asyncness {
+        Some(_) => quote!(::spin_sdk::http::run(#call)),
+        None => call,
+    };
+
+    quote!(
+        #func
+        mod __spin_job {
+            mod preamble {
+                #preamble
+            }
+            impl self::preamble::exports::fermyon::spin3_0_0::inbound_job::Guest for preamble::Spin {
+                fn handle_job(id: String, payload: Vec<u8>) -> Result<(), String> {
+                    #call.map_err(|e| e.to_string())
+                }
+            }
+        }
+    )
+    .into()
+}
+
 #[derive(Copy, Clone)]
 enum Export {
     WasiHttp,
     Redis,
+    Job,
 }
 
 fn preamble(export: Export) -> proc_macro2::TokenStream {
     let export_decl = match export {
         Export::WasiHttp => quote!("wasi:http/incoming-handler": Spin),
         Export::Redis => quote!("fermyon:spin/inbound-redis": Spin),
+        Export::Job => quote!("fermyon:spin/inbound-job@3.0.0": Spin),
     };
     let world = match export {
         Export::WasiHttp => quote!("wasi-http-trigger"),
         Export::Redis => quote!("redis-trigger"),
+        Export::Job => quote!("job-trigger"),
     };
     quote! {
         #![allow(missing_docs)]
@@ -0,0 +1,52 @@
+//! State shared in memory by an application
+//!
+//! The state is a small map of keys to values, shared by all the components and instances of the running
+//! application, for data such as feature flags and hot counters which is read and written too often for a
+//! key-value store round trip. It is not persisted: it starts out empty and is lost when the application stops,
+//! and it is not shared between replicas.
+//!
+//! ```ignore
+//! let hits = spin_sdk::app_state::increment("hits", 1)?;
+//! if spin_sdk::app_state::get("beta")?.as_deref() == Some(b"on") {
+//!     // ...
+//! }
+//! ```
+//!
+//! The state holds up to 16 MiB of keys and values by default; the limit is set in the runtime configuration,
+//! e.g. `[app_state] max_size = 1048576`.
+
+use super::wit::v3::app_state;
+
+#[doc(inline)]
+pub use app_state::Error;
+
+/// Get the value of `key`, or `None` if it is not set.
+pub fn get(key: &str) -> Result<Option<Vec<u8>>, Error> {
+    app_state::get(key)
+}
+
+/// Set the value of `key`, replacing any existing value.
+pub fn set(key: &str, value: &[u8]) -> Result<(), Error> {
+    app_state::set(key, value)
+}
+
+/// Remove `key`, returning whether it was set.
+pub fn delete(key: &str) -> Result<bool, Error> {
+    app_state::delete(key)
+}
+
+/// Atomically add `delta` to the counter at `key`, which starts at zero, returning its new value.
+pub fn increment(key: &str, delta: i64) -> Result<i64, Error> {
+    app_state::increment(key, delta)
+}
+
+/// Atomically set `key` to `new` if its value is `expected`, where `None` means it is not set, returning
+/// whether it was set.
+pub fn compare_and_swap(key: &str, expected: Option<&[u8]>, new: &[u8]) -> Result<bool, Error> {
+    app_state::compare_and_swap(key, expected, new)
+}
+
+/// Return the keys which are set, in no particular order.
+pub fn keys() -> Result<Vec<String>, Error> {
+    app_state::get_keys()
+}
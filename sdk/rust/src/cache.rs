@@ -0,0 +1,63 @@
+//! Spin caches
+//!
+//! A cache holds data which is expensive to compute or fetch, such as rendered pages or responses from slow
+//! services. Unlike a key-value store, a cache may drop entries at any time, for example to stay within its
+//! size limit, so a miss must always be handled by recomputing the value.
+
+use std::time::Duration;
+
+use super::wit::v3::cache;
+
+#[cfg(feature = "json")]
+use serde::{de::DeserializeOwned, Serialize};
+
+#[doc(inline)]
+pub use cache::{Cache, Error};
+
+impl Cache {
+    /// Open the default cache.
+    ///
+    /// This is equivalent to `Cache::open("default")`.
+    pub fn open_default() -> Result<Self, Error> {
+        Self::open("default")
+    }
+
+    /// Return the cached value for `key`, or compute it with `f` and cache it for `ttl`.
+    pub fn get_or_set_with<E: From<Error>>(
+        &self,
+        key: &str,
+        ttl: Option<Duration>,
+        f: impl FnOnce() -> Result<Vec<u8>, E>,
+    ) -> Result<Vec<u8>, E> {
+        if let Some(value) = self.get(key)? {
+            return Ok(value);
+        }
+        let value = f()?;
+        self.set(key, &value, ttl.map(|ttl| ttl.as_secs()))?;
+        Ok(value)
+    }
+
+    #[cfg(feature = "json")]
+    /// Serialize the given data to JSON, then cache it for `key`, expiring after `ttl` if given.
+    pub fn set_json<T: Serialize>(
+        &self,
+        key: impl AsRef<str>,
+        value: &T,
+        ttl: Option<Duration>,
+    ) -> Result<(), anyhow::Error> {
+        let value = serde_json::to_vec(value)?;
+        Ok(self.set(key.as_ref(), &value, ttl.map(|ttl| ttl.as_secs()))?)
+    }
+
+    #[cfg(feature = "json")]
+    /// Deserialize an instance of type `T` from the cached value for `key`.
+    pub fn get_json<T: DeserializeOwned>(
+        &self,
+        key: impl AsRef<str>,
+    ) -> Result<Option<T>, anyhow::Error> {
+        let Some(value) = self.get(key.as_ref())? else {
+            return Ok(None);
+        };
+        Ok(serde_json::from_slice(&value)?)
+    }
+}
@@ -0,0 +1,39 @@
+//! Spin request context
+//!
+//! Information about the request a component is handling and where it is running, e.g. to give up on work
+//! which can't finish before the request's deadline, or to tag log lines:
+//!
+//! ```ignore
+//! use spin_sdk::context;
+//!
+//! if let Some(left) = context::time_remaining() {
+//!     if left < std::time::Duration::from_secs(1) {
+//!         return Ok(cached_response());
+//!     }
+//! }
+//! println!("[{}] request from {:?}", context::component_id(), context::remote_address());
+//! ```
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use super::wit::v3::context;
+
+#[doc(inline)]
+pub use context::{
+    app_name, app_version, component_id, remote_address, tls, trigger_type, TlsInfo,
+};
+
+/// The time by which the current request must be handled, if it has a deadline. The host stops the
+/// component once the deadline has passed.
+pub fn deadline() -> Option<SystemTime> {
+    context::deadline().map(|millis| UNIX_EPOCH + Duration::from_millis(millis))
+}
+
+/// How long is left until the current request's deadline, if it has one.
+pub fn time_remaining() -> Option<Duration> {
+    deadline().map(|deadline| {
+        deadline
+            .duration_since(SystemTime::now())
+            .unwrap_or_default()
+    })
+}
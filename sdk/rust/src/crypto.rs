@@ -0,0 +1,58 @@
+//! Cryptography with keys held by the host
+//!
+//! Keys are defined in the runtime config and referred to by label, so key material never enters the
+//! component. The component must be granted each key in the manifest:
+//!
+//! ```toml
+//! [component.webhooks]
+//! crypto_keys = ["webhook"]
+//! ```
+//!
+//! ```ignore
+//! use spin_sdk::crypto;
+//!
+//! if !crypto::verify_hmac("webhook", &body, &signature)? {
+//!     return Ok(Response::new(401, ()));
+//! }
+//! ```
+
+use super::wit::v3::crypto;
+
+#[doc(inline)]
+pub use crypto::Error;
+
+/// Compute the HMAC of `data` with the `key` HMAC key.
+pub fn hmac(key: &str, data: &[u8]) -> Result<Vec<u8>, Error> {
+    crypto::hmac(key, data)
+}
+
+/// Check, in constant time, that `tag` is the HMAC of `data` with the `key` HMAC key.
+pub fn verify_hmac(key: &str, data: &[u8], tag: &[u8]) -> Result<bool, Error> {
+    crypto::verify_hmac(key, data, tag)
+}
+
+/// Sign `message` with the `key` signing key.
+pub fn sign(key: &str, message: &[u8]) -> Result<Vec<u8>, Error> {
+    crypto::sign(key, message)
+}
+
+/// Check that `signature` is a signature of `message` by the `key` signing or public key.
+pub fn verify(key: &str, message: &[u8], signature: &[u8]) -> Result<bool, Error> {
+    crypto::verify(key, message, signature)
+}
+
+/// Return the public key of the `key` signing key, for others to verify its signatures.
+pub fn public_key(key: &str) -> Result<Vec<u8>, Error> {
+    crypto::public_key(key)
+}
+
+/// Encrypt `plaintext` with the `key` encryption key, binding it to `associated_data`. The result holds the
+/// random nonce, so may be passed as is to [`decrypt`].
+pub fn encrypt(key: &str, plaintext: &[u8], associated_data: &[u8]) -> Result<Vec<u8>, Error> {
+    crypto::encrypt(key, plaintext, associated_data)
+}
+
+/// Decrypt `ciphertext` from [`encrypt`] with the `key` encryption key and the same `associated_data`.
+pub fn decrypt(key: &str, ciphertext: &[u8], associated_data: &[u8]) -> Result<Vec<u8>, Error> {
+    crypto::decrypt(key, ciphertext, associated_data)
+}
@@ -0,0 +1,42 @@
+//! Feature flags
+//!
+//! Flags let features be rolled out and toggled without redeploying the application. They are evaluated by the
+//! host for a context, such as the current user, from a provider set in the runtime configuration: a flags file,
+//! a key-value store, or a LaunchDarkly-compatible streaming service.
+//!
+//! ```ignore
+//! use spin_sdk::flags::{self, Context};
+//!
+//! let context = Context::new("user-123").with("country", "NZ");
+//! if flags::is_enabled("new-checkout", &context)? {
+//!     // ...
+//! }
+//! ```
+//!
+//! Without a provider, every flag is disabled.
+
+use super::wit::v3::feature_flags as flags;
+
+#[doc(inline)]
+pub use flags::{Context, Error};
+
+impl Context {
+    /// A context identified by `key`, such as a user ID, with no attributes.
+    pub fn new(key: impl Into<String>) -> Self {
+        Self {
+            key: key.into(),
+            attributes: vec![],
+        }
+    }
+
+    /// Add the attribute `name`, such as a country or plan, with `value`.
+    pub fn with(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.attributes.push((name.into(), value.into()));
+        self
+    }
+}
+
+/// Return whether the flag `name` is enabled for `context`. Flags the provider doesn't know are disabled.
+pub fn is_enabled(name: &str, context: &Context) -> Result<bool, Error> {
+    flags::is_enabled(name, context)
+}
@@ -0,0 +1,117 @@
+//! Running host calls concurrently.
+//!
+//! Outbound HTTP requests made with [`http::send`](crate::http::send), and
+//! the `_deferred` variants of Postgres and Redis calls such as
+//! [`pg::Connection::query_deferred`](crate::pg::Connection::query_deferred),
+//! are futures which can be joined with the macros and functions re-exported
+//! here, e.g. to query a database while an HTTP API is called:
+//!
+//! ```ignore
+//! use spin_sdk::futures::try_join;
+//! use spin_sdk::http::{send, Request, Response};
+//! use spin_sdk::pg::Connection;
+//!
+//! #[http_component]
+//! async fn handle(_req: Request) -> anyhow::Result<Response> {
+//!     let conn = Connection::open("postgres://...")?;
+//!     let (prices, rows): (Response, _) = try_join!(
+//!         async { Ok(send(Request::get("https://prices.example.com")).await?) },
+//!         async { Ok(conn.query_deferred("SELECT * FROM orders", &[]).await?) },
+//!     )?;
+//!     // ...
+//! }
+//! ```
+//!
+//! A handler which is not `async` can run joined futures with [`block_on`].
+//!
+//! # Concurrency limits
+//!
+//! An instance runs on a single thread, so joined futures take turns rather
+//! than running in parallel, and only one of them is ever running guest code:
+//!
+//! * Outbound HTTP requests run on the host. Any number may be in flight at
+//!   once; each is started when its future is first polled, and continues
+//!   while the instance waits on or runs other futures.
+//! * Postgres and Redis calls are made one at a time. Each blocks the
+//!   instance until it completes, though HTTP requests already in flight
+//!   continue meanwhile. A call's future first yields so that the futures
+//!   joined with it start before it blocks, so start HTTP requests in the
+//!   same join as database calls, not after them.
+//! * Joined futures share the instance's memory and execution time limits,
+//!   and everything must finish before the component's response is sent,
+//!   other than work deferred with
+//!   [`task::spawn_after_response`](crate::task::spawn_after_response).
+//!
+//! So joining an HTTP request with a database query takes about as long as
+//! the slower of the two, while joining two database queries takes as long
+//! as both.
+
+#[doc(inline)]
+pub use futures::{
+    future::{join_all, select_all, try_join_all, Either},
+    join, select, select_biased, try_join,
+};
+
+/// Runs a future to completion, blocking until it yields a result.
+pub fn block_on<T>(future: impl std::future::Future<Output = T>) -> T {
+    crate::http::run(future)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, future::Future, pin::Pin};
+
+    use super::*;
+
+    struct Recorder(RefCell<Vec<&'static str>>);
+
+    impl Recorder {
+        fn get(&self, key: &'static str) -> Result<&'static str, String> {
+            self.0.borrow_mut().push(key);
+            Ok(key)
+        }
+
+        deferred_methods! {
+            fn get_deferred => get(key: &'static str) -> Result<&'static str, String>;
+        }
+    }
+
+    #[test]
+    fn deferred_calls_wait_for_joined_futures_to_start() {
+        let recorder = Recorder(RefCell::new(vec![]));
+        let (value, ()) = block_on(async {
+            join!(recorder.get_deferred("query"), async {
+                recorder.0.borrow_mut().push("request");
+            })
+        });
+        assert_eq!(Ok("query"), value);
+        assert_eq!(vec!["request", "query"], recorder.0.into_inner());
+    }
+
+    #[test]
+    fn try_join_fails_with_the_first_error() {
+        let recorder = Recorder(RefCell::new(vec![]));
+        let result = block_on(async {
+            try_join!(recorder.get_deferred("query"), async {
+                Err::<(), _>("failed".to_owned())
+            })
+        });
+        assert_eq!(Err("failed".to_owned()), result);
+        // The deferred call was never made.
+        assert!(recorder.0.into_inner().is_empty());
+    }
+
+    #[test]
+    fn select_all_takes_the_first_to_finish() {
+        let slow = async {
+            crate::http::yield_now().await;
+            crate::http::yield_now().await;
+            "slow"
+        };
+        let fast = async { "fast" };
+        let futures: Vec<Pin<Box<dyn Future<Output = &str>>>> =
+            vec![Box::pin(slow), Box::pin(fast)];
+        let (value, index, _) = block_on(select_all(futures));
+        assert_eq!(("fast", 1), (value, index));
+    }
+}
@@ -0,0 +1,33 @@
+//! Markdown rendering and HTML sanitization
+//!
+//! Both are done by the host, so the component needn't embed a Markdown parser or an HTML5 parser. HTML in a
+//! Markdown document is passed through, so documents from untrusted sources should be sanitized once rendered:
+//!
+//! ```ignore
+//! use spin_sdk::html::{self, MarkdownOptions};
+//!
+//! let options = MarkdownOptions {
+//!     tables: true,
+//!     strikethrough: true,
+//!     task_lists: false,
+//!     footnotes: false,
+//!     smart_punctuation: true,
+//! };
+//! let body = html::sanitize(&html::render_markdown(&comment, options), None)?;
+//! ```
+
+use super::wit::v3::html;
+
+#[doc(inline)]
+pub use html::{Error, MarkdownOptions, Policy};
+
+/// Render the CommonMark document `markdown` as HTML, with the extensions enabled in `options`.
+pub fn render_markdown(markdown: &str, options: MarkdownOptions) -> String {
+    html::render_markdown(markdown, options)
+}
+
+/// Remove what `policy` doesn't allow from `html`. Without a policy, formatting, links and images are allowed,
+/// but scripts, styles, event handlers and `javascript:` URLs are not.
+pub fn sanitize(html: &str, policy: Option<&Policy>) -> Result<String, Error> {
+    html::sanitize(html, policy)
+}
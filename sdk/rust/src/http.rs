@@ -3,12 +3,16 @@ pub mod conversions;
 
 use std::collections::HashMap;
 
+mod error;
+
 #[doc(inline)]
 pub use conversions::IntoResponse;
 #[doc(inline)]
+pub use error::{Error, ResultExt};
+#[doc(inline)]
 pub use types::{
-    Error, Fields, Headers, IncomingRequest, IncomingResponse, Method, OutgoingBody,
-    OutgoingRequest, OutgoingResponse, Scheme, StatusCode, Trailers,
+    Error as WasiHttpError, Fields, Headers, IncomingRequest, IncomingResponse, Method,
+    OutgoingBody, OutgoingRequest, OutgoingResponse, Scheme, StatusCode, Trailers,
 };
 
 use self::conversions::{TryFromIncomingResponse, TryIntoOutgoingRequest};
@@ -522,7 +526,7 @@ impl OutgoingResponse {
     /// # Panics
     ///
     /// Panics if the body was already taken.
-    pub fn take_body(&self) -> impl futures::Sink<Vec<u8>, Error = Error> {
+    pub fn take_body(&self) -> impl futures::Sink<Vec<u8>, Error = WasiHttpError> {
         executor::outgoing_body(self.write().expect("response body was already taken"))
     }
 }
@@ -533,7 +537,7 @@ impl OutgoingRequest {
     /// # Panics
     ///
     /// Panics if the body was already taken.
-    pub fn take_body(&self) -> impl futures::Sink<Vec<u8>, Error = Error> {
+    pub fn take_body(&self) -> impl futures::Sink<Vec<u8>, Error = WasiHttpError> {
         executor::outgoing_body(self.write().expect("request body was already taken"))
     }
 }
@@ -561,7 +565,7 @@ impl ResponseOutparam {
         self,
         response: OutgoingResponse,
         buffer: Vec<u8>,
-    ) -> Result<(), Error> {
+    ) -> Result<(), WasiHttpError> {
         let mut body = response.take_body();
         self.set(response);
         body.send(buffer).await
@@ -593,7 +597,7 @@ where
         body_sink
             .send(body_buffer)
             .await
-            .map_err(|e| SendError::Http(Error::UnexpectedError(e.to_string())))?;
+            .map_err(|e| SendError::Http(WasiHttpError::UnexpectedError(e.to_string())))?;
         response
     } else {
         executor::outgoing_request_send(request)
@@ -617,7 +621,7 @@ pub enum SendError {
     ResponseConversion(Box<dyn std::error::Error + Send + Sync>),
     /// An HTTP error
     #[error(transparent)]
-    Http(Error),
+    Http(WasiHttpError),
 }
 
 #[doc(hidden)]
@@ -625,6 +629,7 @@ pub enum SendError {
 mod executor;
 #[doc(hidden)]
 pub use executor::run;
+pub(crate) use executor::yield_now;
 
 /// An error parsing a JSON body
 #[cfg(feature = "json")]
@@ -657,6 +662,17 @@ mod router;
 /// Exports HTTP Router items.
 pub use router::*;
 
+mod extract;
+/// Exports extractors for Router handlers.
+pub use extract::*;
+
+pub mod cookie;
+
+pub mod multipart;
+
+#[cfg(feature = "json")]
+pub mod oidc;
+
 /// A Body extractor
 #[derive(Debug)]
 pub struct Body<T>(pub T);
@@ -230,6 +230,56 @@ where
     }
 }
 
+impl IntoResponse for String {
+    fn into_response(self) -> Response {
+        Response::builder()
+            .header("content-type", "text/plain; charset=utf-8")
+            .body(self)
+            .build()
+    }
+}
+
+impl IntoResponse for &'static str {
+    fn into_response(self) -> Response {
+        self.to_owned().into_response()
+    }
+}
+
+impl IntoResponse for Vec<u8> {
+    fn into_response(self) -> Response {
+        Response::new(200, self)
+    }
+}
+
+impl IntoResponse for () {
+    fn into_response(self) -> Response {
+        Response::new(200, ())
+    }
+}
+
+/// A response with the given status, e.g. `(201, "created")` or
+/// `(404, Json(error))`.
+impl<S: IntoStatusCode, R: IntoResponse> IntoResponse for (S, R) {
+    fn into_response(self) -> Response {
+        let mut response = self.1.into_response();
+        response.status = self.0.into_status_code();
+        response
+    }
+}
+
+#[cfg(feature = "json")]
+impl<T: serde::Serialize> IntoResponse for Json<T> {
+    fn into_response(self) -> Response {
+        match serde_json::to_vec(&self.0) {
+            Ok(body) => Response::builder()
+                .header("content-type", "application/json")
+                .body(body)
+                .build(),
+            Err(e) => anyhow::Error::from(e).into_response(),
+        }
+    }
+}
+
 impl<R: IntoResponse, E: IntoResponse> IntoResponse for std::result::Result<R, E> {
     fn into_response(self) -> Response {
         match self {
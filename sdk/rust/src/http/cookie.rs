@@ -0,0 +1,259 @@
+//! Parsing, building and signing of HTTP cookies.
+//!
+//! ```ignore
+//! let theme = cookie::get(&req, "theme").unwrap_or("light");
+//!
+//! let cookie = Cookie::new("theme", "dark").max_age(Duration::from_secs(86400));
+//! Response::builder()
+//!     .status(200)
+//!     .header("set-cookie", cookie.to_string())
+//!     .build()
+//! ```
+
+use std::time::Duration;
+
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+use super::Request;
+
+/// Returns the name/value pairs of a `Cookie` request header.
+pub fn parse(header: &str) -> impl Iterator<Item = (&str, &str)> {
+    header.split(';').filter_map(|pair| {
+        let (name, value) = pair.split_once('=')?;
+        let value = value.trim();
+        let value = value
+            .strip_prefix('"')
+            .and_then(|v| v.strip_suffix('"'))
+            .unwrap_or(value);
+        Some((name.trim(), value))
+    })
+}
+
+/// Returns the value of the named cookie sent with a request.
+pub fn get<'a>(req: &'a Request, name: &str) -> Option<&'a str> {
+    let header = req.header("cookie")?.as_str()?;
+    parse(header).find(|(n, _)| *n == name).map(|(_, v)| v)
+}
+
+/// The `SameSite` attribute of a cookie.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SameSite {
+    /// Sent only with requests from the cookie's own site
+    Strict,
+    /// Also sent when navigating to the cookie's site from another one
+    Lax,
+    /// Sent with all requests; requires the cookie to be `Secure`
+    None,
+}
+
+/// A cookie to be sent in a `Set-Cookie` response header.
+///
+/// Its `Display` implementation formats the header's value.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Cookie {
+    name: String,
+    value: String,
+    path: Option<String>,
+    domain: Option<String>,
+    max_age: Option<Duration>,
+    secure: bool,
+    http_only: bool,
+    same_site: Option<SameSite>,
+}
+
+impl Cookie {
+    /// A cookie with the given name and value, and no attributes.
+    pub fn new(name: impl Into<String>, value: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            value: value.into(),
+            path: None,
+            domain: None,
+            max_age: None,
+            secure: false,
+            http_only: false,
+            same_site: None,
+        }
+    }
+
+    /// A cookie which removes the named cookie from the client.
+    pub fn removal(name: impl Into<String>) -> Self {
+        Self::new(name, "").max_age(Duration::ZERO)
+    }
+
+    /// The name of the cookie.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The value of the cookie.
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+
+    /// Sets the `Path` attribute.
+    pub fn path(mut self, path: impl Into<String>) -> Self {
+        self.path = Some(path.into());
+        self
+    }
+
+    /// Sets the `Domain` attribute.
+    pub fn domain(mut self, domain: impl Into<String>) -> Self {
+        self.domain = Some(domain.into());
+        self
+    }
+
+    /// Sets the `Max-Age` attribute. Without it, the cookie lasts until the
+    /// client's session ends.
+    pub fn max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = Some(max_age);
+        self
+    }
+
+    /// Sets whether the cookie is only sent over HTTPS.
+    pub fn secure(mut self, secure: bool) -> Self {
+        self.secure = secure;
+        self
+    }
+
+    /// Sets whether the cookie is hidden from scripts.
+    pub fn http_only(mut self, http_only: bool) -> Self {
+        self.http_only = http_only;
+        self
+    }
+
+    /// Sets the `SameSite` attribute.
+    pub fn same_site(mut self, same_site: SameSite) -> Self {
+        self.same_site = Some(same_site);
+        self
+    }
+}
+
+impl std::fmt::Display for Cookie {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}={}", self.name, self.value)?;
+        if let Some(path) = &self.path {
+            write!(f, "; Path={path}")?;
+        }
+        if let Some(domain) = &self.domain {
+            write!(f, "; Domain={domain}")?;
+        }
+        if let Some(max_age) = self.max_age {
+            write!(f, "; Max-Age={}", max_age.as_secs())?;
+        }
+        if self.secure {
+            f.write_str("; Secure")?;
+        }
+        if self.http_only {
+            f.write_str("; HttpOnly")?;
+        }
+        match self.same_site {
+            Some(SameSite::Strict) => f.write_str("; SameSite=Strict"),
+            Some(SameSite::Lax) => f.write_str("; SameSite=Lax"),
+            Some(SameSite::None) => f.write_str("; SameSite=None"),
+            None => Ok(()),
+        }
+    }
+}
+
+/// A secret key with which cookie values are signed, so that clients can't
+/// change them.
+///
+/// A signed value is the value followed by a `.` and an HMAC-SHA256 of the
+/// cookie's name and value. The key should be at least 32 random bytes, and
+/// is typically read from a secret variable.
+#[derive(Clone)]
+pub struct Key(Vec<u8>);
+
+impl Key {
+    /// A key with the given secret.
+    pub fn new(secret: impl AsRef<[u8]>) -> Self {
+        Self(secret.as_ref().to_vec())
+    }
+
+    /// Signs the value of the named cookie.
+    pub fn sign(&self, name: &str, value: &str) -> String {
+        let tag = self.mac(name, value).finalize().into_bytes();
+        format!("{value}.{}", hex(&tag))
+    }
+
+    /// Returns the value of the named cookie if its signature is valid.
+    pub fn verify<'a>(&self, name: &str, signed: &'a str) -> Option<&'a str> {
+        let (value, tag) = signed.rsplit_once('.')?;
+        let tag = unhex(tag)?;
+        self.mac(name, value).verify_slice(&tag).ok()?;
+        Some(value)
+    }
+
+    fn mac(&self, name: &str, value: &str) -> Hmac<Sha256> {
+        let mut mac =
+            Hmac::<Sha256>::new_from_slice(&self.0).expect("HMAC accepts keys of any length");
+        mac.update(name.as_bytes());
+        mac.update(b"=");
+        mac.update(value.as_bytes());
+        mac
+    }
+}
+
+impl std::fmt::Debug for Key {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Key(..)")
+    }
+}
+
+pub(crate) fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+fn unhex(s: &str) -> Option<Vec<u8>> {
+    if s.len() % 2 != 0 {
+        return None;
+    }
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(s.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn request_cookies_are_parsed() {
+        let req = Request::get("/")
+            .header("cookie", "theme=dark; session=\"abc\"; flag")
+            .build();
+        assert_eq!(Some("dark"), get(&req, "theme"));
+        assert_eq!(Some("abc"), get(&req, "session"));
+        assert_eq!(None, get(&req, "flag"));
+    }
+
+    #[test]
+    fn cookies_are_formatted() {
+        let cookie = Cookie::new("id", "42")
+            .path("/")
+            .max_age(Duration::from_secs(60))
+            .secure(true)
+            .http_only(true)
+            .same_site(SameSite::Lax);
+        assert_eq!(
+            "id=42; Path=/; Max-Age=60; Secure; HttpOnly; SameSite=Lax",
+            cookie.to_string()
+        );
+        assert_eq!("id=; Max-Age=0", Cookie::removal("id").to_string());
+    }
+
+    #[test]
+    fn signed_values_are_verified() {
+        let key = Key::new("0123456789abcdef0123456789abcdef");
+        let signed = key.sign("id", "42");
+        assert_eq!(Some("42"), key.verify("id", &signed));
+
+        assert_eq!(None, key.verify("other", &signed));
+        assert_eq!(None, key.verify("id", &signed.replacen("42", "43", 1)));
+        assert_eq!(None, Key::new("another key").verify("id", &signed));
+        assert_eq!(None, key.verify("id", "42"));
+    }
+}
@@ -0,0 +1,237 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use super::conversions::IntoResponse;
+use super::Response;
+
+static EXPOSE_INTERNAL_ERRORS: AtomicBool = AtomicBool::new(false);
+
+/// An error returned by a handler, sent as a response with a status code.
+///
+/// Any error can be converted into an `Error` with `?`, so a handler can
+/// return `Result<impl IntoResponse, Error>`. Errors with a client error
+/// status (4xx) send their message as the response body. Other errors are
+/// written to stderr, and respond with a generic message, so that details
+/// such as connection strings or SQL don't leak to clients; see
+/// [`Error::expose_internal_errors`].
+///
+/// Errors from the SDK's own extractors and body conversions keep their
+/// status, e.g. a JSON body which cannot be parsed is a 400. Any other error
+/// is a 500 unless given a status with [`Error::with_status`] or
+/// [`ResultExt::status`].
+pub struct Error {
+    status: u16,
+    message: Option<String>,
+    source: Option<anyhow::Error>,
+}
+
+impl Error {
+    /// An error with the given status and message.
+    pub fn new(status: u16, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            message: Some(message.into()),
+            source: None,
+        }
+    }
+
+    /// A 400 Bad Request error.
+    pub fn bad_request(message: impl Into<String>) -> Self {
+        Self::new(400, message)
+    }
+
+    /// A 401 Unauthorized error.
+    pub fn unauthorized(message: impl Into<String>) -> Self {
+        Self::new(401, message)
+    }
+
+    /// A 403 Forbidden error.
+    pub fn forbidden(message: impl Into<String>) -> Self {
+        Self::new(403, message)
+    }
+
+    /// A 404 Not Found error.
+    pub fn not_found(message: impl Into<String>) -> Self {
+        Self::new(404, message)
+    }
+
+    /// A 409 Conflict error.
+    pub fn conflict(message: impl Into<String>) -> Self {
+        Self::new(409, message)
+    }
+
+    /// A 422 Unprocessable Content error.
+    pub fn unprocessable(message: impl Into<String>) -> Self {
+        Self::new(422, message)
+    }
+
+    /// A 500 Internal Server Error caused by `source`.
+    pub fn internal(source: impl Into<anyhow::Error>) -> Self {
+        Self {
+            status: 500,
+            message: None,
+            source: Some(source.into()),
+        }
+    }
+
+    /// Sets the status sent for this error.
+    pub fn with_status(mut self, status: u16) -> Self {
+        self.status = status;
+        self
+    }
+
+    /// The status sent for this error.
+    pub fn status(&self) -> u16 {
+        self.status
+    }
+
+    /// The underlying error, if any.
+    pub fn source(&self) -> Option<&anyhow::Error> {
+        self.source.as_ref()
+    }
+
+    /// Sets whether the messages of server errors (5xx) are sent to clients.
+    ///
+    /// They are hidden by default. Exposing them can help during development,
+    /// but may leak details of the application's internals.
+    pub fn expose_internal_errors(expose: bool) {
+        EXPOSE_INTERNAL_ERRORS.store(expose, Ordering::Relaxed);
+    }
+
+    fn message(&self) -> String {
+        match (&self.message, &self.source) {
+            (Some(message), _) => message.clone(),
+            (None, Some(source)) => format!("{source:#}"),
+            (None, None) => String::new(),
+        }
+    }
+}
+
+impl<E: Into<anyhow::Error>> From<E> for Error {
+    fn from(error: E) -> Self {
+        let error = error.into();
+        let status = sdk_error_status(&error).unwrap_or(500);
+        let message = (status < 500).then(|| error.to_string());
+        Self {
+            status,
+            message,
+            source: Some(error),
+        }
+    }
+}
+
+/// The status of an error from the SDK's extractors or body conversions.
+fn sdk_error_status(error: &anyhow::Error) -> Option<u16> {
+    #[cfg(feature = "json")]
+    if let Some(e) = error.downcast_ref::<super::ExtractError>() {
+        return Some(e.status());
+    }
+    #[cfg(feature = "json")]
+    if error.is::<super::JsonBodyError>() {
+        return Some(400);
+    }
+    if let Some(e) = error.downcast_ref::<super::multipart::MultipartError>() {
+        return Some(e.status());
+    }
+    error.is::<super::NonUtf8BodyError>().then_some(400)
+}
+
+impl std::fmt::Debug for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Error")
+            .field("status", &self.status)
+            .field("message", &self.message)
+            .field("source", &self.source)
+            .finish()
+    }
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.status, self.message())
+    }
+}
+
+impl IntoResponse for Error {
+    fn into_response(self) -> Response {
+        if self.status < 500 {
+            return Response::builder()
+                .status(self.status)
+                .header("content-type", "text/plain; charset=utf-8")
+                .body(self.message())
+                .build();
+        }
+        eprintln!("Handler returned an error: {}", self.message());
+        if let Some(source) = &self.source {
+            for cause in source.chain().skip(1) {
+                eprintln!("  caused by: {cause}");
+            }
+        }
+        let body = if EXPOSE_INTERNAL_ERRORS.load(Ordering::Relaxed) {
+            self.message()
+        } else {
+            "Internal Server Error".to_owned()
+        };
+        Response::builder()
+            .status(self.status)
+            .header("content-type", "text/plain; charset=utf-8")
+            .body(body)
+            .build()
+    }
+}
+
+/// Gives the errors of a `Result` a status, e.g.
+/// `orders.get(id).status(404)?`.
+pub trait ResultExt<T> {
+    /// Converts the error into an [`Error`] with the given status, whose
+    /// message is the error's.
+    fn status(self, status: u16) -> Result<T, Error>;
+}
+
+impl<T, E: Into<anyhow::Error>> ResultExt<T> for Result<T, E> {
+    fn status(self, status: u16) -> Result<T, Error> {
+        self.map_err(|e| {
+            let source = e.into();
+            Error {
+                status,
+                message: (status < 500).then(|| source.to_string()),
+                source: Some(source),
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn body(response: &Response) -> &str {
+        std::str::from_utf8(response.body()).unwrap()
+    }
+
+    #[test]
+    fn client_errors_send_their_message() {
+        let response = Error::not_found("no such order").into_response();
+        assert_eq!(404, *response.status());
+        assert_eq!("no such order", body(&response));
+
+        let error = Err::<(), _>(std::fmt::Error).status(409).unwrap_err();
+        assert_eq!(409, error.status());
+    }
+
+    #[test]
+    fn server_errors_are_hidden() {
+        let error: Error = anyhow::anyhow!("password authentication failed for user app").into();
+        assert_eq!(500, error.status());
+        let response = error.into_response();
+        assert_eq!(500, *response.status());
+        assert_eq!("Internal Server Error", body(&response));
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn sdk_errors_keep_their_status() {
+        let json_error = serde_json::from_str::<u32>("x").unwrap_err();
+        let error: Error = super::super::JsonBodyError(json_error).into();
+        assert_eq!(400, error.status());
+    }
+}
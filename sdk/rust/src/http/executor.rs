@@ -11,6 +11,7 @@ use std::cell::RefCell;
 use std::future::Future;
 use std::mem;
 use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
 use std::task::{Context, Poll, Wake, Waker};
 
@@ -23,16 +24,27 @@ static WAKERS: Mutex<Vec<(io::poll::Pollable, Waker)>> = Mutex::new(Vec::new());
 /// Based on an executor using `wasi::io/poll/poll-list`,
 pub fn run<T>(future: impl Future<Output = T>) -> T {
     futures::pin_mut!(future);
-    struct DummyWaker;
+    /// Records wakes which happen without a pollable, e.g. from [`yield_now`].
+    #[derive(Default)]
+    struct FlagWaker(AtomicBool);
 
-    impl Wake for DummyWaker {
-        fn wake(self: Arc<Self>) {}
+    impl Wake for FlagWaker {
+        fn wake(self: Arc<Self>) {
+            self.wake_by_ref()
+        }
+
+        fn wake_by_ref(self: &Arc<Self>) {
+            self.0.store(true, Ordering::SeqCst);
+        }
     }
 
-    let waker = Arc::new(DummyWaker).into();
+    let flag = Arc::new(FlagWaker::default());
+    let waker = flag.clone().into();
 
     loop {
+        flag.0.store(false, Ordering::SeqCst);
         match future.as_mut().poll(&mut Context::from_waker(&waker)) {
+            Poll::Pending if flag.0.load(Ordering::SeqCst) => {}
             Poll::Pending => {
                 let mut new_wakers = Vec::new();
 
@@ -66,6 +78,22 @@ pub fn run<T>(future: impl Future<Output = T>) -> T {
     }
 }
 
+/// Returns `Pending` once, so that the other futures being polled alongside
+/// the caller, e.g. by `join!`, are polled before the caller continues.
+pub(crate) async fn yield_now() {
+    let mut yielded = false;
+    future::poll_fn(|context| {
+        if yielded {
+            Poll::Ready(())
+        } else {
+            yielded = true;
+            context.waker().wake_by_ref();
+            Poll::Pending
+        }
+    })
+    .await
+}
+
 pub(crate) fn outgoing_body(body: OutgoingBody) -> impl Sink<Vec<u8>, Error = types::Error> {
     struct Outgoing(Option<(OutputStream, OutgoingBody)>);
 
@@ -204,3 +232,23 @@ pub fn incoming_body(
         }
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn yielding_futures_run_to_completion() {
+        let order = RefCell::new(vec![]);
+        let task = |n| {
+            let order = &order;
+            async move {
+                order.borrow_mut().push(n);
+                yield_now().await;
+                order.borrow_mut().push(n * 10);
+            }
+        };
+        run(async { futures::join!(task(1), task(2)) });
+        assert_eq!(vec![1, 2, 10, 20], order.into_inner());
+    }
+}
@@ -0,0 +1,562 @@
+use super::conversions::IntoResponse;
+use super::{HeaderValue, Method, Params, Request, Response};
+use routefinder::Capture;
+
+/// A type which can be extracted from a request routed by a [`Router`](super::Router).
+///
+/// Extractors are used as the arguments of handlers wrapped with [`extract`].
+/// An extractor which consumes the request body, such as [`Json`](super::Json),
+/// must be the last argument.
+pub trait FromRequest: Sized {
+    /// The response sent if extraction fails
+    type Rejection: IntoResponse;
+
+    /// Extract `Self` from the request and the route parameters
+    fn from_request(req: &mut Request, params: &Params) -> Result<Self, Self::Rejection>;
+}
+
+/// A function whose arguments are all extractors.
+///
+/// This is implemented for functions of up to eight arguments, and should not
+/// need to be implemented by hand.
+pub trait Handler<Args> {
+    /// Extract the arguments from the request and call the function
+    fn call(&self, req: Request, params: Params) -> Response;
+}
+
+/// Wraps a handler whose arguments are extractors so that it can be registered
+/// with a [`Router`](super::Router).
+///
+/// ```ignore
+/// fn get_order(Path(id): Path<u32>, Query(page): Query<Page>) -> Json<Order> { ... }
+///
+/// router.get("/orders/:id", extract(get_order));
+/// ```
+pub fn extract<H, Args>(handler: H) -> impl Fn(Request, Params) -> Response
+where
+    H: Handler<Args>,
+{
+    move |req, params| handler.call(req, params)
+}
+
+macro_rules! impl_handler {
+    ($($arg:ident),*) => {
+        impl<F, O, $($arg),*> Handler<($($arg,)*)> for F
+        where
+            F: Fn($($arg),*) -> O,
+            O: IntoResponse,
+            $($arg: FromRequest,)*
+        {
+            #[allow(non_snake_case, unused_mut, unused_variables)]
+            fn call(&self, mut req: Request, params: Params) -> Response {
+                $(
+                    let $arg = match $arg::from_request(&mut req, &params) {
+                        Ok(value) => value,
+                        Err(rejection) => return rejection.into_response(),
+                    };
+                )*
+                (self)($($arg),*).into_response()
+            }
+        }
+    };
+}
+
+impl_handler!();
+impl_handler!(A1);
+impl_handler!(A1, A2);
+impl_handler!(A1, A2, A3);
+impl_handler!(A1, A2, A3, A4);
+impl_handler!(A1, A2, A3, A4, A5);
+impl_handler!(A1, A2, A3, A4, A5, A6);
+impl_handler!(A1, A2, A3, A4, A5, A6, A7);
+impl_handler!(A1, A2, A3, A4, A5, A6, A7, A8);
+
+impl FromRequest for Request {
+    type Rejection = std::convert::Infallible;
+
+    fn from_request(req: &mut Request, _params: &Params) -> Result<Self, Self::Rejection> {
+        Ok(std::mem::replace(req, Request::new(Method::Get, "/")))
+    }
+}
+
+impl FromRequest for Method {
+    type Rejection = std::convert::Infallible;
+
+    fn from_request(req: &mut Request, _params: &Params) -> Result<Self, Self::Rejection> {
+        Ok(req.method().clone())
+    }
+}
+
+impl FromRequest for Params {
+    type Rejection = std::convert::Infallible;
+
+    fn from_request(_req: &mut Request, params: &Params) -> Result<Self, Self::Rejection> {
+        // `Params` isn't `Clone`, so is copied capture by capture.
+        let mut copy: Params = params
+            .iter()
+            .map(|(name, value)| Capture::new(name.to_owned(), value.to_owned()))
+            .collect();
+        if let Some(wildcard) = params.wildcard() {
+            copy.set_wildcard(wildcard.to_owned());
+        }
+        Ok(copy)
+    }
+}
+
+/// Extracts the request headers, with lowercase names.
+impl FromRequest for Vec<(String, HeaderValue)> {
+    type Rejection = std::convert::Infallible;
+
+    fn from_request(req: &mut Request, _params: &Params) -> Result<Self, Self::Rejection> {
+        Ok(req
+            .headers()
+            .map(|(name, value)| (name.to_owned(), value.clone()))
+            .collect())
+    }
+}
+
+/// Extracts the request body as a UTF-8 string.
+impl FromRequest for String {
+    type Rejection = super::NonUtf8BodyError;
+
+    fn from_request(req: &mut Request, _params: &Params) -> Result<Self, Self::Rejection> {
+        String::from_utf8(std::mem::take(req.body_mut())).map_err(|_| super::NonUtf8BodyError)
+    }
+}
+
+/// Extracts the request body as bytes.
+impl FromRequest for Vec<u8> {
+    type Rejection = std::convert::Infallible;
+
+    fn from_request(req: &mut Request, _params: &Params) -> Result<Self, Self::Rejection> {
+        Ok(std::mem::take(req.body_mut()))
+    }
+}
+
+/// Extracts the request body as JSON, rejecting the request with a 400 if it
+/// cannot be deserialized.
+#[cfg(feature = "json")]
+impl<T: serde::de::DeserializeOwned> FromRequest for super::Json<T> {
+    type Rejection = super::JsonBodyError;
+
+    fn from_request(req: &mut Request, _params: &Params) -> Result<Self, Self::Rejection> {
+        serde_json::from_slice(req.body())
+            .map(super::Json)
+            .map_err(super::JsonBodyError)
+    }
+}
+
+/// Deserializes the route parameters, e.g. `:id` in `/orders/:id`.
+///
+/// The parameters can be deserialized into a struct with a field per
+/// parameter, a tuple with an element per parameter in order, or, for
+/// routes with a single parameter, a string or number.
+#[cfg(feature = "json")]
+#[derive(Debug)]
+pub struct Path<T>(pub T);
+
+#[cfg(feature = "json")]
+impl<T: serde::de::DeserializeOwned> FromRequest for Path<T> {
+    type Rejection = ExtractError;
+
+    fn from_request(_req: &mut Request, params: &Params) -> Result<Self, Self::Rejection> {
+        let pairs = params
+            .iter()
+            .map(|(name, value)| (name.to_owned(), value.to_owned()))
+            .collect::<Vec<_>>();
+        T::deserialize(de::Pairs(&pairs))
+            .map(Path)
+            .map_err(|e| ExtractError::new(404, format!("invalid path parameters: {e}")))
+    }
+}
+
+/// Deserializes the request's query string, e.g. `?page=2&tag=new`.
+#[cfg(feature = "json")]
+#[derive(Debug)]
+pub struct Query<T>(pub T);
+
+#[cfg(feature = "json")]
+impl<T: serde::de::DeserializeOwned> FromRequest for Query<T> {
+    type Rejection = ExtractError;
+
+    fn from_request(req: &mut Request, _params: &Params) -> Result<Self, Self::Rejection> {
+        let pairs = form_urlencoded::parse(req.query().as_bytes())
+            .into_owned()
+            .collect::<Vec<_>>();
+        T::deserialize(de::Pairs(&pairs))
+            .map(Query)
+            .map_err(|e| ExtractError::new(400, format!("invalid query string: {e}")))
+    }
+}
+
+/// Deserializes an `application/x-www-form-urlencoded` request body.
+///
+/// This consumes the request body, so must be the last argument.
+#[cfg(feature = "json")]
+#[derive(Debug)]
+pub struct Form<T>(pub T);
+
+#[cfg(feature = "json")]
+impl<T: serde::de::DeserializeOwned> FromRequest for Form<T> {
+    type Rejection = ExtractError;
+
+    fn from_request(req: &mut Request, _params: &Params) -> Result<Self, Self::Rejection> {
+        let is_form = req
+            .header("content-type")
+            .and_then(|v| v.as_str())
+            .and_then(|v| v.split(';').next())
+            .map_or(false, |v| {
+                v.trim()
+                    .eq_ignore_ascii_case("application/x-www-form-urlencoded")
+            });
+        if !is_form {
+            return Err(ExtractError::new(
+                415,
+                "expected an application/x-www-form-urlencoded body".to_owned(),
+            ));
+        }
+        let pairs = form_urlencoded::parse(&std::mem::take(req.body_mut()))
+            .into_owned()
+            .collect::<Vec<_>>();
+        T::deserialize(de::Pairs(&pairs))
+            .map(Form)
+            .map_err(|e| ExtractError::new(400, format!("invalid form body: {e}")))
+    }
+}
+
+#[cfg(feature = "json")]
+impl<T> std::ops::Deref for Form<T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+#[cfg(feature = "json")]
+impl<T> std::ops::Deref for Path<T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+#[cfg(feature = "json")]
+impl<T> std::ops::Deref for Query<T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+/// An error extracting a value from a request, sent as its response.
+#[cfg(feature = "json")]
+#[derive(Debug)]
+pub struct ExtractError {
+    status: u16,
+    message: String,
+}
+
+#[cfg(feature = "json")]
+impl ExtractError {
+    fn new(status: u16, message: String) -> Self {
+        Self { status, message }
+    }
+
+    /// The status of the response sent for this error.
+    pub fn status(&self) -> u16 {
+        self.status
+    }
+}
+
+#[cfg(feature = "json")]
+impl std::error::Error for ExtractError {}
+
+#[cfg(feature = "json")]
+impl std::fmt::Display for ExtractError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+#[cfg(feature = "json")]
+impl IntoResponse for ExtractError {
+    fn into_response(self) -> Response {
+        match self.status {
+            404 => super::responses::not_found(),
+            status => Response::new(status, self.message),
+        }
+    }
+}
+
+/// A deserializer for string name/value pairs, in which values are parsed
+/// as whatever type is asked for.
+#[cfg(feature = "json")]
+mod de {
+    use serde::de::{
+        value::{Error, MapDeserializer, SeqDeserializer},
+        Deserializer, Error as _, IntoDeserializer, Visitor,
+    };
+
+    pub(super) struct Pairs<'a>(pub &'a [(String, String)]);
+
+    struct Value<'a>(&'a str);
+
+    impl<'a> Pairs<'a> {
+        fn single(&self) -> Result<Value<'a>, Error> {
+            match self.0 {
+                [(_, value)] => Ok(Value(value)),
+                pairs => Err(Error::custom(format!(
+                    "expected one value, found {}",
+                    pairs.len()
+                ))),
+            }
+        }
+    }
+
+    macro_rules! forward_to_single {
+        ($($method:ident)*) => {
+            $(
+                fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+                    self.single()?.$method(visitor)
+                }
+            )*
+        };
+    }
+
+    impl<'de, 'a> Deserializer<'de> for Pairs<'a> {
+        type Error = Error;
+
+        fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+            self.deserialize_map(visitor)
+        }
+
+        fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+            let mut map = MapDeserializer::new(
+                self.0
+                    .iter()
+                    .map(|(name, value)| (name.as_str(), Value(value))),
+            );
+            let value = visitor.visit_map(&mut map)?;
+            map.end()?;
+            Ok(value)
+        }
+
+        fn deserialize_struct<V: Visitor<'de>>(
+            self,
+            _name: &'static str,
+            _fields: &'static [&'static str],
+            visitor: V,
+        ) -> Result<V::Value, Error> {
+            self.deserialize_map(visitor)
+        }
+
+        fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+            let mut seq = SeqDeserializer::new(self.0.iter().map(|(_, value)| Value(value)));
+            let value = visitor.visit_seq(&mut seq)?;
+            seq.end()?;
+            Ok(value)
+        }
+
+        fn deserialize_tuple<V: Visitor<'de>>(
+            self,
+            _len: usize,
+            visitor: V,
+        ) -> Result<V::Value, Error> {
+            self.deserialize_seq(visitor)
+        }
+
+        fn deserialize_tuple_struct<V: Visitor<'de>>(
+            self,
+            _name: &'static str,
+            _len: usize,
+            visitor: V,
+        ) -> Result<V::Value, Error> {
+            self.deserialize_seq(visitor)
+        }
+
+        fn deserialize_newtype_struct<V: Visitor<'de>>(
+            self,
+            _name: &'static str,
+            visitor: V,
+        ) -> Result<V::Value, Error> {
+            visitor.visit_newtype_struct(self)
+        }
+
+        fn deserialize_enum<V: Visitor<'de>>(
+            self,
+            name: &'static str,
+            variants: &'static [&'static str],
+            visitor: V,
+        ) -> Result<V::Value, Error> {
+            self.single()?.deserialize_enum(name, variants, visitor)
+        }
+
+        fn deserialize_unit_struct<V: Visitor<'de>>(
+            self,
+            _name: &'static str,
+            visitor: V,
+        ) -> Result<V::Value, Error> {
+            visitor.visit_unit()
+        }
+
+        forward_to_single! {
+            deserialize_bool deserialize_i8 deserialize_i16 deserialize_i32 deserialize_i64
+            deserialize_u8 deserialize_u16 deserialize_u32 deserialize_u64 deserialize_f32
+            deserialize_f64 deserialize_char deserialize_str deserialize_string
+            deserialize_bytes deserialize_byte_buf deserialize_option deserialize_unit
+            deserialize_identifier deserialize_ignored_any
+        }
+    }
+
+    macro_rules! parse_value {
+        ($($method:ident => $visit:ident),*) => {
+            $(
+                fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+                    let value = self.0.parse().map_err(|e| {
+                        Error::custom(format!("invalid value {:?}: {e}", self.0))
+                    })?;
+                    visitor.$visit(value)
+                }
+            )*
+        };
+    }
+
+    impl<'de, 'a> Deserializer<'de> for Value<'a> {
+        type Error = Error;
+
+        fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+            visitor.visit_str(self.0)
+        }
+
+        fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+            visitor.visit_some(self)
+        }
+
+        fn deserialize_newtype_struct<V: Visitor<'de>>(
+            self,
+            _name: &'static str,
+            visitor: V,
+        ) -> Result<V::Value, Error> {
+            visitor.visit_newtype_struct(self)
+        }
+
+        fn deserialize_enum<V: Visitor<'de>>(
+            self,
+            _name: &'static str,
+            _variants: &'static [&'static str],
+            visitor: V,
+        ) -> Result<V::Value, Error> {
+            visitor.visit_enum(self.0.into_deserializer())
+        }
+
+        parse_value! {
+            deserialize_bool => visit_bool,
+            deserialize_i8 => visit_i8,
+            deserialize_i16 => visit_i16,
+            deserialize_i32 => visit_i32,
+            deserialize_i64 => visit_i64,
+            deserialize_u8 => visit_u8,
+            deserialize_u16 => visit_u16,
+            deserialize_u32 => visit_u32,
+            deserialize_u64 => visit_u64,
+            deserialize_f32 => visit_f32,
+            deserialize_f64 => visit_f64,
+            deserialize_char => visit_char
+        }
+
+        serde::forward_to_deserialize_any! {
+            str string bytes byte_buf unit unit_struct seq tuple tuple_struct map struct
+            identifier ignored_any
+        }
+    }
+
+    impl<'de, 'a> IntoDeserializer<'de, Error> for Value<'a> {
+        type Deserializer = Self;
+
+        fn into_deserializer(self) -> Self {
+            self
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{Json, Router};
+    use super::*;
+
+    #[derive(serde::Deserialize)]
+    struct Page {
+        page: u32,
+        tag: Option<String>,
+    }
+
+    fn get_order(Path(id): Path<u32>, Query(page): Query<Page>) -> String {
+        format!("{id} {} {}", page.page, page.tag.unwrap_or_default())
+    }
+
+    fn create_order(method: Method, Json(order): Json<Vec<String>>) -> (u16, String) {
+        (201, format!("{method} {}", order.join(",")))
+    }
+
+    fn router() -> Router {
+        let mut router = Router::new();
+        router.get("/orders/:id", extract(get_order));
+        router.post("/orders", extract(create_order));
+        router.get(
+            "/pairs/:a/:b",
+            extract(|Path((a, b)): Path<(String, i64)>| format!("{a}{b}")),
+        );
+        router
+    }
+
+    #[test]
+    fn path_and_query_are_extracted() {
+        let res = router().handle(Request::new(Method::Get, "/orders/7?page=2&tag=new"));
+        assert_eq!(200, *res.status());
+        assert_eq!(b"7 2 new", res.body());
+
+        let res = router().handle(Request::new(Method::Get, "/pairs/x/3"));
+        assert_eq!(b"x3", res.body());
+    }
+
+    #[test]
+    fn invalid_values_are_rejected() {
+        let res = router().handle(Request::new(Method::Get, "/orders/seven?page=2"));
+        assert_eq!(404, *res.status());
+
+        let res = router().handle(Request::new(Method::Get, "/orders/7?page=two"));
+        assert_eq!(400, *res.status());
+
+        let res = router().handle(Request::post("/orders", "not json").build());
+        assert_eq!(400, *res.status());
+    }
+
+    #[test]
+    fn bodies_are_extracted() {
+        let res = router().handle(Request::post("/orders", r#"["a","b"]"#).build());
+        assert_eq!(201, *res.status());
+        assert_eq!(b"POST a,b", res.body());
+    }
+
+    #[test]
+    fn forms_are_extracted() {
+        let mut router = Router::new();
+        router.post(
+            "/pages",
+            extract(|Form(page): Form<Page>| page.page.to_string()),
+        );
+
+        let res = router.handle(
+            Request::post("/pages", "page=3&tag=a+b")
+                .header("content-type", "application/x-www-form-urlencoded")
+                .build(),
+        );
+        assert_eq!(b"3", res.body());
+
+        let res = router.handle(Request::post("/pages", "page=3").build());
+        assert_eq!(415, *res.status());
+    }
+}
@@ -0,0 +1,636 @@
+//! Parsing of `multipart/form-data` request bodies.
+//!
+//! [`Multipart`] reads the fields of a body as it is streamed from the host,
+//! so that large files can be processed without holding them in memory. For
+//! handlers registered with a [`Router`](super::Router), the [`Parts`]
+//! extractor parses a buffered body into its fields.
+//!
+//! ```ignore
+//! let mut multipart = Multipart::from_incoming_request(req)?;
+//! while let Some(mut field) = multipart.next_field().await? {
+//!     while let Some(chunk) = field.chunk().await? {
+//!         // write the chunk somewhere...
+//!     }
+//! }
+//! ```
+
+use std::collections::VecDeque;
+use std::pin::Pin;
+
+use futures::{Stream, StreamExt};
+
+use super::conversions::IntoResponse;
+use super::extract::FromRequest;
+use super::{IncomingRequest, Params, Request, Response};
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+type BodyStream = Pin<Box<dyn Stream<Item = Result<Vec<u8>, BoxError>>>>;
+
+/// The largest size of the headers of a single field.
+const MAX_HEADERS_SIZE: usize = 8 * 1024;
+
+/// Limits on the size of a multipart body.
+///
+/// Exceeding a limit stops parsing with an error, which is sent as a 413
+/// Payload Too Large response.
+#[derive(Clone, Copy, Debug)]
+pub struct Limits {
+    /// The largest size of a single field's data. Defaults to 10 MiB.
+    pub max_field_size: usize,
+    /// The largest size of the whole body. Defaults to 50 MiB.
+    pub max_body_size: usize,
+    /// The largest number of fields. Defaults to 100.
+    pub max_fields: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            max_field_size: 10 * 1024 * 1024,
+            max_body_size: 50 * 1024 * 1024,
+            max_fields: 100,
+        }
+    }
+}
+
+/// An error parsing a multipart body.
+#[derive(thiserror::Error, Debug)]
+pub enum MultipartError {
+    /// The request is not `multipart/form-data`, or has no boundary
+    #[error("expected a multipart/form-data body with a boundary")]
+    NotMultipart,
+    /// The body is not valid multipart
+    #[error("malformed multipart body: {0}")]
+    Malformed(&'static str),
+    /// A field's data is larger than the limit
+    #[error("field {name:?} is larger than {limit} bytes")]
+    FieldTooLarge {
+        /// The name of the field
+        name: String,
+        /// The limit which was exceeded
+        limit: usize,
+    },
+    /// The body is larger than the limit
+    #[error("body is larger than {0} bytes")]
+    BodyTooLarge(usize),
+    /// The body has more fields than the limit
+    #[error("body has more than {0} fields")]
+    TooManyFields(usize),
+    /// Reading the body failed
+    #[error(transparent)]
+    Body(BoxError),
+}
+
+impl MultipartError {
+    /// The status of the response sent for this error.
+    pub fn status(&self) -> u16 {
+        match self {
+            Self::NotMultipart => 415,
+            Self::FieldTooLarge { .. } | Self::BodyTooLarge(_) | Self::TooManyFields(_) => 413,
+            Self::Malformed(_) | Self::Body(_) => 400,
+        }
+    }
+}
+
+impl IntoResponse for MultipartError {
+    fn into_response(self) -> Response {
+        Response::new(self.status(), self.to_string())
+    }
+}
+
+/// Returns the boundary of a `multipart/form-data` content type.
+pub fn boundary(content_type: &str) -> Option<String> {
+    let mut params = content_type.split(';');
+    let mime = params.next()?.trim();
+    if !mime.eq_ignore_ascii_case("multipart/form-data") {
+        return None;
+    }
+    params.find_map(|param| {
+        let (name, value) = param.split_once('=')?;
+        if !name.trim().eq_ignore_ascii_case("boundary") {
+            return None;
+        }
+        let value = value.trim();
+        let value = value
+            .strip_prefix('"')
+            .and_then(|v| v.strip_suffix('"'))
+            .unwrap_or(value);
+        (!value.is_empty()).then(|| value.to_owned())
+    })
+}
+
+/// A streaming `multipart/form-data` parser.
+pub struct Multipart {
+    stream: BodyStream,
+    parser: Parser,
+    eof: bool,
+}
+
+impl Multipart {
+    /// Parses the chunks of `stream` as a multipart body with the given boundary.
+    pub fn new<S, E>(boundary: impl Into<String>, stream: S) -> Self
+    where
+        S: Stream<Item = Result<Vec<u8>, E>> + 'static,
+        E: Into<BoxError>,
+    {
+        Self {
+            stream: Box::pin(stream.map(|chunk| chunk.map_err(Into::into))),
+            parser: Parser::new(&boundary.into(), Limits::default()),
+            eof: false,
+        }
+    }
+
+    /// Parses the body of an incoming request as it is streamed.
+    pub fn from_incoming_request(req: IncomingRequest) -> Result<Self, MultipartError> {
+        let boundary = req
+            .headers()
+            .entries()
+            .into_iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case("content-type"))
+            .and_then(|(_, value)| boundary(&String::from_utf8_lossy(&value)))
+            .ok_or(MultipartError::NotMultipart)?;
+        Ok(Self::new(boundary, req.into_body_stream()))
+    }
+
+    /// Sets the limits on the size of the body.
+    pub fn with_limits(mut self, limits: Limits) -> Self {
+        self.parser.limits = limits;
+        self
+    }
+
+    /// Returns the next field, or `None` after the last one.
+    ///
+    /// Any data of the previous field which was not read is skipped.
+    pub async fn next_field(&mut self) -> Result<Option<Field<'_>>, MultipartError> {
+        loop {
+            match self.next_event().await? {
+                Event::Field(headers) => {
+                    return Ok(Some(Field {
+                        headers,
+                        multipart: self,
+                        done: false,
+                    }))
+                }
+                Event::Data(_) | Event::FieldEnd => continue,
+                Event::End => return Ok(None),
+            }
+        }
+    }
+
+    async fn next_event(&mut self) -> Result<Event, MultipartError> {
+        loop {
+            if let Some(event) = self.parser.next_event(self.eof)? {
+                return Ok(event);
+            }
+            match self.stream.next().await {
+                Some(chunk) => self.parser.feed(&chunk.map_err(MultipartError::Body)?)?,
+                None => self.eof = true,
+            }
+        }
+    }
+}
+
+/// A field of a multipart body, whose data is read in chunks.
+pub struct Field<'a> {
+    headers: FieldHeaders,
+    multipart: &'a mut Multipart,
+    done: bool,
+}
+
+impl Field<'_> {
+    /// The name of the field.
+    pub fn name(&self) -> &str {
+        &self.headers.name
+    }
+
+    /// The file name of the field, if it is a file.
+    pub fn file_name(&self) -> Option<&str> {
+        self.headers.file_name.as_deref()
+    }
+
+    /// The content type of the field, if given.
+    pub fn content_type(&self) -> Option<&str> {
+        self.headers.content_type.as_deref()
+    }
+
+    /// Reads the next chunk of the field's data, or `None` after the last one.
+    pub async fn chunk(&mut self) -> Result<Option<Vec<u8>>, MultipartError> {
+        if self.done {
+            return Ok(None);
+        }
+        match self.multipart.next_event().await? {
+            Event::Data(data) => Ok(Some(data)),
+            Event::FieldEnd => {
+                self.done = true;
+                Ok(None)
+            }
+            Event::Field(_) | Event::End => Err(MultipartError::Malformed("field has no end")),
+        }
+    }
+
+    /// Reads all of the field's data.
+    pub async fn bytes(mut self) -> Result<Vec<u8>, MultipartError> {
+        let mut data = Vec::new();
+        while let Some(chunk) = self.chunk().await? {
+            data.extend(chunk);
+        }
+        Ok(data)
+    }
+
+    /// Reads all of the field's data as a string.
+    pub async fn text(self) -> Result<String, MultipartError> {
+        String::from_utf8(self.bytes().await?)
+            .map_err(|_| MultipartError::Malformed("field is not UTF-8"))
+    }
+}
+
+/// A field of a buffered multipart body.
+#[derive(Clone, Debug)]
+pub struct Part {
+    /// The name of the field.
+    pub name: String,
+    /// The file name of the field, if it is a file.
+    pub file_name: Option<String>,
+    /// The content type of the field, if given.
+    pub content_type: Option<String>,
+    /// The field's data.
+    pub data: Vec<u8>,
+}
+
+impl Part {
+    /// The field's data as a string, if it is UTF-8.
+    pub fn text(&self) -> Option<&str> {
+        std::str::from_utf8(&self.data).ok()
+    }
+}
+
+/// The fields of a buffered `multipart/form-data` body, parsed with the
+/// default [`Limits`].
+#[derive(Clone, Debug, Default)]
+pub struct Parts(pub Vec<Part>);
+
+impl Parts {
+    /// Parses a multipart body with the given boundary.
+    pub fn parse(boundary: &str, body: &[u8], limits: Limits) -> Result<Self, MultipartError> {
+        let mut parser = Parser::new(boundary, limits);
+        parser.feed(body)?;
+        let mut parts = Vec::new();
+        loop {
+            match parser.next_event(true)? {
+                Some(Event::Field(headers)) => parts.push(Part {
+                    name: headers.name,
+                    file_name: headers.file_name,
+                    content_type: headers.content_type,
+                    data: Vec::new(),
+                }),
+                Some(Event::Data(data)) => match parts.last_mut() {
+                    Some(part) => part.data.extend(data),
+                    None => return Err(MultipartError::Malformed("data outside of a field")),
+                },
+                Some(Event::FieldEnd) => {}
+                Some(Event::End) => return Ok(Self(parts)),
+                None => return Err(MultipartError::Malformed("unexpected end of body")),
+            }
+        }
+    }
+
+    /// The first field with the given name.
+    pub fn get(&self, name: &str) -> Option<&Part> {
+        self.0.iter().find(|p| p.name == name)
+    }
+}
+
+impl std::ops::Deref for Parts {
+    type Target = [Part];
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl FromRequest for Parts {
+    type Rejection = MultipartError;
+
+    fn from_request(req: &mut Request, _params: &Params) -> Result<Self, Self::Rejection> {
+        let boundary = req
+            .header("content-type")
+            .and_then(|v| v.as_str())
+            .and_then(boundary)
+            .ok_or(MultipartError::NotMultipart)?;
+        Self::parse(&boundary, req.body(), Limits::default())
+    }
+}
+
+#[derive(Debug)]
+struct FieldHeaders {
+    name: String,
+    file_name: Option<String>,
+    content_type: Option<String>,
+}
+
+#[derive(Debug)]
+enum Event {
+    Field(FieldHeaders),
+    Data(Vec<u8>),
+    FieldEnd,
+    End,
+}
+
+#[derive(Debug, PartialEq)]
+enum State {
+    Preamble,
+    Delimiter,
+    Headers,
+    Data,
+    End,
+}
+
+/// A push parser, to which the body is fed in chunks and from which the
+/// fields are read as events.
+struct Parser {
+    /// `\r\n--<boundary>`; the body is given a leading CRLF so that the
+    /// first boundary is found like the others.
+    delimiter: Vec<u8>,
+    buf: Vec<u8>,
+    state: State,
+    pending: VecDeque<Event>,
+    limits: Limits,
+    body_size: usize,
+    fields: usize,
+    field_size: usize,
+    field_name: String,
+}
+
+impl Parser {
+    fn new(boundary: &str, limits: Limits) -> Self {
+        Self {
+            delimiter: format!("\r\n--{boundary}").into_bytes(),
+            buf: b"\r\n".to_vec(),
+            state: State::Preamble,
+            pending: VecDeque::new(),
+            limits,
+            body_size: 0,
+            fields: 0,
+            field_size: 0,
+            field_name: String::new(),
+        }
+    }
+
+    fn feed(&mut self, chunk: &[u8]) -> Result<(), MultipartError> {
+        self.body_size += chunk.len();
+        if self.body_size > self.limits.max_body_size {
+            return Err(MultipartError::BodyTooLarge(self.limits.max_body_size));
+        }
+        if self.state != State::End {
+            self.buf.extend_from_slice(chunk);
+        }
+        Ok(())
+    }
+
+    /// Returns the next event, or `None` if more of the body is needed.
+    fn next_event(&mut self, eof: bool) -> Result<Option<Event>, MultipartError> {
+        loop {
+            if let Some(event) = self.pending.pop_front() {
+                return Ok(Some(event));
+            }
+            if !self.step()? {
+                if eof && self.state != State::End {
+                    return Err(MultipartError::Malformed("unexpected end of body"));
+                }
+                return Ok(None);
+            }
+        }
+    }
+
+    /// Advances through the buffered body, returning whether any progress
+    /// was made.
+    fn step(&mut self) -> Result<bool, MultipartError> {
+        match self.state {
+            State::Preamble => match find(&self.buf, &self.delimiter) {
+                Some(i) => {
+                    self.buf.drain(..i + self.delimiter.len());
+                    self.state = State::Delimiter;
+                    Ok(true)
+                }
+                None => {
+                    let keep = self.delimiter.len() - 1;
+                    if self.buf.len() > keep {
+                        self.buf.drain(..self.buf.len() - keep);
+                    }
+                    Ok(false)
+                }
+            },
+            State::Delimiter => {
+                if self.buf.len() < 2 {
+                    return Ok(false);
+                }
+                match &self.buf[..2] {
+                    b"--" => {
+                        self.buf.clear();
+                        self.state = State::End;
+                        self.pending.push_back(Event::End);
+                    }
+                    b"\r\n" => {
+                        self.buf.drain(..2);
+                        self.state = State::Headers;
+                    }
+                    _ => return Err(MultipartError::Malformed("invalid boundary")),
+                }
+                Ok(true)
+            }
+            State::Headers => {
+                let Some(i) = find(&self.buf, b"\r\n\r\n") else {
+                    if self.buf.len() > MAX_HEADERS_SIZE {
+                        return Err(MultipartError::Malformed("field headers are too large"));
+                    }
+                    return Ok(false);
+                };
+                let headers = parse_headers(&self.buf[..i])?;
+                self.buf.drain(..i + 4);
+                self.fields += 1;
+                if self.fields > self.limits.max_fields {
+                    return Err(MultipartError::TooManyFields(self.limits.max_fields));
+                }
+                self.field_size = 0;
+                self.field_name = headers.name.clone();
+                self.state = State::Data;
+                self.pending.push_back(Event::Field(headers));
+                Ok(true)
+            }
+            State::Data => {
+                let (len, end) = match find(&self.buf, &self.delimiter) {
+                    Some(i) => (i, true),
+                    // A delimiter may start in the last bytes, so keep them.
+                    None => (
+                        self.buf.len().saturating_sub(self.delimiter.len() - 1),
+                        false,
+                    ),
+                };
+                if len == 0 && !end {
+                    return Ok(false);
+                }
+                self.field_size += len;
+                if self.field_size > self.limits.max_field_size {
+                    return Err(MultipartError::FieldTooLarge {
+                        name: std::mem::take(&mut self.field_name),
+                        limit: self.limits.max_field_size,
+                    });
+                }
+                let data = self.buf.drain(..len).collect::<Vec<_>>();
+                if !data.is_empty() {
+                    self.pending.push_back(Event::Data(data));
+                }
+                if end {
+                    self.buf.drain(..self.delimiter.len());
+                    self.state = State::Delimiter;
+                    self.pending.push_back(Event::FieldEnd);
+                }
+                Ok(true)
+            }
+            State::End => Ok(false),
+        }
+    }
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+fn parse_headers(raw: &[u8]) -> Result<FieldHeaders, MultipartError> {
+    let raw = std::str::from_utf8(raw)
+        .map_err(|_| MultipartError::Malformed("field headers are not UTF-8"))?;
+    let mut name = None;
+    let mut file_name = None;
+    let mut content_type = None;
+    for line in raw.split("\r\n") {
+        let Some((header, value)) = line.split_once(':') else {
+            return Err(MultipartError::Malformed("invalid field header"));
+        };
+        let value = value.trim();
+        if header.eq_ignore_ascii_case("content-disposition") {
+            let mut params = value.split(';');
+            if !params
+                .next()
+                .map_or(false, |d| d.trim().eq_ignore_ascii_case("form-data"))
+            {
+                return Err(MultipartError::Malformed("field is not form-data"));
+            }
+            for param in params {
+                let Some((key, value)) = param.split_once('=') else {
+                    continue;
+                };
+                let value = unquote(value.trim());
+                match key.trim().to_ascii_lowercase().as_str() {
+                    "name" => name = Some(value),
+                    "filename" => file_name = Some(value),
+                    _ => {}
+                }
+            }
+        } else if header.eq_ignore_ascii_case("content-type") {
+            content_type = Some(value.to_owned());
+        }
+    }
+    Ok(FieldHeaders {
+        name: name.ok_or(MultipartError::Malformed("field has no name"))?,
+        file_name,
+        content_type,
+    })
+}
+
+fn unquote(value: &str) -> String {
+    match value.strip_prefix('"').and_then(|v| v.strip_suffix('"')) {
+        Some(quoted) => quoted.replace("\\\"", "\"").replace("\\\\", "\\"),
+        None => value.to_owned(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BODY: &[u8] = b"preamble\r\n\
+        --XyZ\r\n\
+        Content-Disposition: form-data; name=\"title\"\r\n\
+        \r\n\
+        Holiday\r\n\
+        --XyZ\r\n\
+        Content-Disposition: form-data; name=\"photo\"; filename=\"beach.jpg\"\r\n\
+        Content-Type: image/jpeg\r\n\
+        \r\n\
+        \xff\xd8 not quite --XyZ\r\n\
+        --XyZ--\r\n";
+
+    #[test]
+    fn parses_boundaries() {
+        assert_eq!(
+            Some("XyZ".to_owned()),
+            boundary("multipart/form-data; boundary=XyZ")
+        );
+        assert_eq!(
+            Some("a b".to_owned()),
+            boundary("Multipart/Form-Data; charset=utf-8; boundary=\"a b\"")
+        );
+        assert_eq!(None, boundary("application/json; boundary=XyZ"));
+        assert_eq!(None, boundary("multipart/form-data"));
+    }
+
+    #[test]
+    fn parses_buffered_body() {
+        let parts = Parts::parse("XyZ", BODY, Limits::default()).unwrap();
+        assert_eq!(2, parts.len());
+        assert_eq!(Some("Holiday"), parts.get("title").unwrap().text());
+        let photo = parts.get("photo").unwrap();
+        assert_eq!(Some("beach.jpg"), photo.file_name.as_deref());
+        assert_eq!(Some("image/jpeg"), photo.content_type.as_deref());
+        assert_eq!(b"\xff\xd8 not quite --XyZ", photo.data.as_slice());
+    }
+
+    #[test]
+    fn parses_body_in_small_chunks() {
+        let chunks = BODY
+            .chunks(3)
+            .map(|c| Ok::<_, std::io::Error>(c.to_vec()))
+            .collect::<Vec<_>>();
+        let mut multipart = Multipart::new("XyZ", futures::stream::iter(chunks));
+        let fields = crate::http::run(async move {
+            let mut fields = Vec::new();
+            while let Some(field) = multipart.next_field().await? {
+                let name = field.name().to_owned();
+                fields.push((name, field.bytes().await?));
+            }
+            Ok::<_, MultipartError>(fields)
+        })
+        .unwrap();
+        assert_eq!(
+            vec![
+                ("title".to_owned(), b"Holiday".to_vec()),
+                ("photo".to_owned(), b"\xff\xd8 not quite --XyZ".to_vec()),
+            ],
+            fields
+        );
+    }
+
+    #[test]
+    fn enforces_limits() {
+        let limits = Limits {
+            max_field_size: 8,
+            ..Default::default()
+        };
+        let error = Parts::parse("XyZ", BODY, limits).unwrap_err();
+        assert!(matches!(error, MultipartError::FieldTooLarge { ref name, .. } if name == "photo"));
+        assert_eq!(413, error.status());
+
+        let limits = Limits {
+            max_fields: 1,
+            ..Default::default()
+        };
+        let error = Parts::parse("XyZ", BODY, limits).unwrap_err();
+        assert!(matches!(error, MultipartError::TooManyFields(1)));
+    }
+
+    #[test]
+    fn rejects_truncated_body() {
+        let error = Parts::parse("XyZ", &BODY[..60], Limits::default()).unwrap_err();
+        assert!(matches!(error, MultipartError::Malformed(_)));
+    }
+}
@@ -0,0 +1,587 @@
+//! Sign-in with an OpenID Connect provider.
+//!
+//! [`Oidc`] implements the authorization code flow with PKCE in front of a
+//! handler. Requests without a signed-in user are redirected to the provider,
+//! and the identity returned by the provider is kept in a [session](crate::session).
+//! The handler is given the user's [`Identity`], which is also set in
+//! `spin-auth-*` request headers so that it can be forwarded to other
+//! components. Headers of that name sent by clients are always removed.
+//!
+//! The component must be allowed to reach the provider in
+//! `allowed_outbound_hosts`, and to use the session's key-value store. The
+//! provider's issuer and endpoints must use HTTPS. Its discovered
+//! configuration is kept in the session's store for an hour, so that it
+//! isn't fetched for every sign in.
+//!
+//! ```ignore
+//! #[http_component]
+//! async fn handle(req: Request) -> Response {
+//!     let config = OidcConfig::new(
+//!         "https://accounts.example.com",
+//!         variables::get("client_id").unwrap(),
+//!         "https://app.example.com/auth/callback",
+//!     );
+//!     Oidc::new(config)
+//!         .protect(req, |_req, identity| async move {
+//!             format!("hello, {}", identity.email.unwrap_or(identity.subject))
+//!         })
+//!         .await
+//! }
+//! ```
+
+use std::future::Future;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use base64::engine::{
+    general_purpose::{GeneralPurpose, GeneralPurposeConfig},
+    DecodePaddingMode,
+};
+use base64::{alphabet, Engine};
+use once_cell::sync::OnceCell;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use sha2::{Digest, Sha256};
+
+use super::conversions::IntoResponse;
+use super::{Error, HeaderValue, Method, Request, Response};
+use crate::key_value::Store;
+use crate::session::{Session, SessionConfig};
+
+/// The header set to the user's subject identifier.
+pub const SUBJECT_HEADER: &str = "spin-auth-subject";
+/// The header set to the user's email address, if known.
+pub const EMAIL_HEADER: &str = "spin-auth-email";
+/// The header set to the user's name, if known.
+pub const NAME_HEADER: &str = "spin-auth-name";
+/// The header set to all of the ID token's claims, as JSON.
+pub const CLAIMS_HEADER: &str = "spin-auth-claims";
+
+const IDENTITY_KEY: &str = "oidc.identity";
+const PENDING_KEY: &str = "oidc.pending";
+const DISCOVERY_KEY_PREFIX: &str = "oidc.discovery:";
+/// How long a discovered provider configuration is reused, in seconds.
+const DISCOVERY_TTL: u64 = 60 * 60;
+
+/// Unpadded base64url, as used in JWTs and PKCE, accepting padding when
+/// decoding.
+const BASE64_URL: GeneralPurpose = GeneralPurpose::new(
+    &alphabet::URL_SAFE,
+    GeneralPurposeConfig::new()
+        .with_encode_padding(false)
+        .with_decode_padding_mode(DecodePaddingMode::Indifferent),
+);
+
+/// The provider and client with which users sign in.
+#[derive(Clone, Debug)]
+pub struct OidcConfig {
+    /// The provider's issuer URL.
+    pub issuer: String,
+    /// The application's client ID.
+    pub client_id: String,
+    /// The application's client secret, for providers which require one.
+    pub client_secret: Option<String>,
+    /// The absolute URL to which the provider redirects after sign in. Its
+    /// path is handled by [`Oidc`], and is not passed to the handler.
+    pub redirect_url: String,
+    /// The scopes requested. Defaults to `openid`, `email` and `profile`.
+    pub scopes: Vec<String>,
+    /// The path which signs the user out. Defaults to `/auth/logout`.
+    pub logout_path: String,
+    /// The provider's authorization endpoint. If not set, it is discovered
+    /// from the issuer's `/.well-known/openid-configuration`.
+    pub authorization_endpoint: Option<String>,
+    /// The provider's token endpoint. If not set, it is discovered like the
+    /// authorization endpoint.
+    pub token_endpoint: Option<String>,
+    /// How sessions are stored.
+    pub session: SessionConfig,
+}
+
+impl OidcConfig {
+    /// A configuration with the default scopes and paths.
+    pub fn new(
+        issuer: impl Into<String>,
+        client_id: impl Into<String>,
+        redirect_url: impl Into<String>,
+    ) -> Self {
+        Self {
+            issuer: issuer.into(),
+            client_id: client_id.into(),
+            client_secret: None,
+            redirect_url: redirect_url.into(),
+            scopes: ["openid", "email", "profile"].map(String::from).into(),
+            logout_path: "/auth/logout".to_owned(),
+            authorization_endpoint: None,
+            token_endpoint: None,
+            session: SessionConfig::default(),
+        }
+    }
+}
+
+/// A signed-in user.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Identity {
+    /// The provider's identifier for the user (the `sub` claim).
+    pub subject: String,
+    /// The user's email address (the `email` claim).
+    pub email: Option<String>,
+    /// The user's name (the `name` claim).
+    pub name: Option<String>,
+    /// All of the ID token's claims.
+    pub claims: Map<String, Value>,
+}
+
+impl Identity {
+    fn from_claims(claims: Map<String, Value>) -> Result<Self, Error> {
+        let claim = |name: &str| claims.get(name).and_then(Value::as_str).map(String::from);
+        Ok(Self {
+            subject: claim("sub").ok_or_else(|| Error::new(502, "ID token has no subject"))?,
+            email: claim("email"),
+            name: claim("name"),
+            claims,
+        })
+    }
+
+    fn apply(&self, req: &mut Request) {
+        let mut set = |name: &str, value: String| {
+            req.headers
+                .insert(name.to_owned(), HeaderValue::string(value));
+        };
+        set(SUBJECT_HEADER, self.subject.clone());
+        if let Some(email) = &self.email {
+            set(EMAIL_HEADER, email.clone());
+        }
+        if let Some(name) = &self.name {
+            set(NAME_HEADER, name.clone());
+        }
+        set(
+            CLAIMS_HEADER,
+            Value::Object(self.claims.clone()).to_string(),
+        );
+    }
+}
+
+/// The outcome of [`Oidc::authenticate`].
+#[allow(clippy::large_enum_variant)] // it's matched on straight away
+pub enum Authentication {
+    /// The user is signed in. The request has had the identity headers set.
+    Authenticated(Request, Identity),
+    /// The response should be sent as it is, e.g. a redirect to the provider.
+    Respond(Response),
+}
+
+/// A sign in which was redirected to the provider.
+#[derive(Serialize, Deserialize)]
+struct Pending {
+    state: String,
+    nonce: String,
+    verifier: String,
+    return_to: String,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct Discovery {
+    authorization_endpoint: String,
+    token_endpoint: String,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    id_token: String,
+}
+
+/// Signs users in with an OpenID Connect provider.
+pub struct Oidc {
+    config: OidcConfig,
+    discovery: OnceCell<Discovery>,
+}
+
+impl Oidc {
+    /// Signs users in with the configured provider.
+    pub fn new(config: OidcConfig) -> Self {
+        Self {
+            config,
+            discovery: OnceCell::new(),
+        }
+    }
+
+    /// Calls `handler` if the user is signed in, and otherwise responds so as
+    /// to sign them in.
+    pub async fn protect<F, Fut, R>(&self, req: Request, handler: F) -> Response
+    where
+        F: FnOnce(Request, Identity) -> Fut,
+        Fut: Future<Output = R>,
+        R: IntoResponse,
+    {
+        match self.authenticate(req).await {
+            Ok(Authentication::Authenticated(req, identity)) => {
+                handler(req, identity).await.into_response()
+            }
+            Ok(Authentication::Respond(response)) => response,
+            Err(e) => e.into_response(),
+        }
+    }
+
+    /// Finds the user of a request, or the response which signs them in or
+    /// out.
+    pub async fn authenticate(&self, mut req: Request) -> Result<Authentication, Error> {
+        req.headers
+            .retain(|name, _| !name.starts_with("spin-auth-"));
+        let mut session = Session::load(&req, &self.config.session)?;
+
+        if req.path() == path_of(&self.config.redirect_url) {
+            return Ok(Authentication::Respond(self.callback(&req, session).await?));
+        }
+        if req.path() == self.config.logout_path {
+            session.destroy();
+            return Ok(Authentication::Respond(session.save(redirect("/"))?));
+        }
+        if let Some(identity) = session.get::<Identity>(IDENTITY_KEY)? {
+            identity.apply(&mut req);
+            return Ok(Authentication::Authenticated(req, identity));
+        }
+        if !matches!(req.method(), Method::Get | Method::Head) {
+            return Err(Error::unauthorized("sign in required"));
+        }
+
+        let pending = Pending {
+            state: random_token()?,
+            nonce: random_token()?,
+            verifier: random_token()?,
+            return_to: local_path(req.path_and_query().unwrap_or("/")).to_owned(),
+        };
+        let (authorization_endpoint, _) = self.endpoints().await?;
+        let url = self.authorization_url(&authorization_endpoint, &pending);
+        session.set(PENDING_KEY, &pending)?;
+        Ok(Authentication::Respond(session.save(redirect(&url))?))
+    }
+
+    async fn callback(&self, req: &Request, mut session: Session) -> Result<Response, Error> {
+        let params = form_urlencoded::parse(req.query().as_bytes())
+            .into_owned()
+            .collect::<std::collections::HashMap<_, _>>();
+        if let Some(error) = params.get("error") {
+            return Err(Error::unauthorized(format!("sign in failed: {error}")));
+        }
+        let pending = session
+            .get::<Pending>(PENDING_KEY)?
+            .ok_or_else(|| Error::bad_request("no sign in is in progress"))?;
+        if params.get("state") != Some(&pending.state) {
+            return Err(Error::bad_request("sign in state does not match"));
+        }
+        let code = params
+            .get("code")
+            .ok_or_else(|| Error::bad_request("no authorization code"))?;
+
+        let (_, token_endpoint) = self.endpoints().await?;
+        let mut form = form_urlencoded::Serializer::new(String::new());
+        form.append_pair("grant_type", "authorization_code")
+            .append_pair("code", code)
+            .append_pair("redirect_uri", &self.config.redirect_url)
+            .append_pair("client_id", &self.config.client_id)
+            .append_pair("code_verifier", &pending.verifier);
+        if let Some(secret) = &self.config.client_secret {
+            form.append_pair("client_secret", secret);
+        }
+        let request = Request::builder()
+            .method(Method::Post)
+            .uri(token_endpoint)
+            .header("content-type", "application/x-www-form-urlencoded")
+            .header("accept", "application/json")
+            .body(form.finish())
+            .build();
+        let tokens: TokenResponse = fetch_json(request).await?;
+
+        // The token came straight from the provider over TLS, so its signature
+        // need not be checked (OpenID Connect Core 1.0, section 3.1.3.7).
+        let claims = decode_claims(&tokens.id_token)?;
+        self.validate_claims(&claims, &pending.nonce, now())?;
+        let identity = Identity::from_claims(claims)?;
+
+        session.remove(PENDING_KEY);
+        session.regenerate();
+        session.set(IDENTITY_KEY, &identity)?;
+        Ok(session.save(redirect(local_path(&pending.return_to)))?)
+    }
+
+    async fn endpoints(&self) -> Result<(String, String), Error> {
+        require_https("issuer", &self.config.issuer)?;
+        let (authorization, token) = match (
+            &self.config.authorization_endpoint,
+            &self.config.token_endpoint,
+        ) {
+            (Some(authorization), Some(token)) => (authorization.clone(), token.clone()),
+            (authorization, token) => {
+                let discovery = self.discover().await?;
+                (
+                    authorization
+                        .clone()
+                        .unwrap_or(discovery.authorization_endpoint),
+                    token.clone().unwrap_or(discovery.token_endpoint),
+                )
+            }
+        };
+        require_https("authorization endpoint", &authorization)?;
+        require_https("token endpoint", &token)?;
+        Ok((authorization, token))
+    }
+
+    /// Fetches the provider's configuration, or reuses the copy kept in the
+    /// session store.
+    async fn discover(&self) -> Result<Discovery, Error> {
+        if let Some(discovery) = self.discovery.get() {
+            return Ok(discovery.clone());
+        }
+        let store = Store::open(&self.config.session.store).map_err(Error::internal)?;
+        let key = format!("{DISCOVERY_KEY_PREFIX}{}", self.config.issuer);
+        let discovery = match store.get_json::<Discovery>(&key).ok().flatten() {
+            Some(discovery) => discovery,
+            None => {
+                let url = format!(
+                    "{}/.well-known/openid-configuration",
+                    self.config.issuer.trim_end_matches('/')
+                );
+                let discovery: Discovery = fetch_json(Request::new(Method::Get, url)).await?;
+                // Failing to keep a copy only means fetching it again next time.
+                _ = store.set_json_with_ttl(&key, &discovery, DISCOVERY_TTL);
+                discovery
+            }
+        };
+        Ok(self.discovery.get_or_init(|| discovery).clone())
+    }
+
+    fn authorization_url(&self, endpoint: &str, pending: &Pending) -> String {
+        let query = form_urlencoded::Serializer::new(String::new())
+            .append_pair("response_type", "code")
+            .append_pair("client_id", &self.config.client_id)
+            .append_pair("redirect_uri", &self.config.redirect_url)
+            .append_pair("scope", &self.config.scopes.join(" "))
+            .append_pair("state", &pending.state)
+            .append_pair("nonce", &pending.nonce)
+            .append_pair("code_challenge", &pkce_challenge(&pending.verifier))
+            .append_pair("code_challenge_method", "S256")
+            .finish();
+        let separator = if endpoint.contains('?') { '&' } else { '?' };
+        format!("{endpoint}{separator}{query}")
+    }
+
+    fn validate_claims(
+        &self,
+        claims: &Map<String, Value>,
+        nonce: &str,
+        now: u64,
+    ) -> Result<(), Error> {
+        let invalid = |message: &str| Err(Error::new(502, format!("invalid ID token: {message}")));
+        let issuer = claims
+            .get("iss")
+            .and_then(Value::as_str)
+            .unwrap_or_default();
+        if issuer.trim_end_matches('/') != self.config.issuer.trim_end_matches('/') {
+            return invalid("unexpected issuer");
+        }
+        let audience_ok = match claims.get("aud") {
+            Some(Value::String(aud)) => *aud == self.config.client_id,
+            Some(Value::Array(auds)) => auds.iter().any(|a| *a == *self.config.client_id),
+            _ => false,
+        };
+        if !audience_ok {
+            return invalid("unexpected audience");
+        }
+        if claims.get("exp").and_then(Value::as_u64).unwrap_or(0) <= now {
+            return invalid("expired");
+        }
+        if claims.get("nonce").and_then(Value::as_str) != Some(nonce) {
+            return invalid("nonce does not match");
+        }
+        Ok(())
+    }
+}
+
+async fn fetch_json<T: serde::de::DeserializeOwned>(request: Request) -> Result<T, Error> {
+    let uri = request.uri().to_owned();
+    let response: Response = super::send(request)
+        .await
+        .map_err(|e| Error::internal(e).with_status(502))?;
+    if !(200..300).contains(response.status()) {
+        return Err(Error::new(
+            502,
+            format!("{uri} responded with status {}", response.status()),
+        ));
+    }
+    serde_json::from_slice(response.body()).map_err(|e| Error::internal(e).with_status(502))
+}
+
+fn redirect(location: &str) -> Response {
+    Response::builder()
+        .status(302)
+        .header("location", location)
+        .build()
+}
+
+/// A path to redirect to after signing in. Anything other than a path on
+/// this site, such as `//evil.example` or `https://evil.example`, is
+/// replaced by `/`.
+fn local_path(path: &str) -> &str {
+    match path.strip_prefix('/') {
+        Some(rest) if !rest.starts_with(['/', '\\']) => path,
+        _ => "/",
+    }
+}
+
+/// Fails unless `url` uses HTTPS.
+fn require_https(what: &str, url: &str) -> Result<(), Error> {
+    let is_https = url
+        .as_bytes()
+        .get(..8)
+        .map_or(false, |scheme| scheme.eq_ignore_ascii_case(b"https://"));
+    if !is_https {
+        return Err(Error::new(
+            500,
+            format!("the OpenID Connect {what} must use https: {url}"),
+        ));
+    }
+    Ok(())
+}
+
+/// The path of an absolute URL.
+fn path_of(url: &str) -> &str {
+    let rest = url.split_once("://").map_or(url, |(_, rest)| rest);
+    let path = rest.find('/').map_or("/", |i| &rest[i..]);
+    path.split(['?', '#']).next().unwrap_or(path)
+}
+
+fn decode_claims(id_token: &str) -> Result<Map<String, Value>, Error> {
+    let payload = id_token
+        .split('.')
+        .nth(1)
+        .and_then(|payload| BASE64_URL.decode(payload).ok())
+        .ok_or_else(|| Error::new(502, "malformed ID token"))?;
+    serde_json::from_slice(&payload).map_err(|e| Error::internal(e).with_status(502))
+}
+
+fn pkce_challenge(verifier: &str) -> String {
+    BASE64_URL.encode(Sha256::digest(verifier.as_bytes()))
+}
+
+fn random_token() -> Result<String, Error> {
+    let mut bytes = [0; 32];
+    getrandom::getrandom(&mut bytes).map_err(Error::internal)?;
+    Ok(BASE64_URL.encode(bytes))
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn oidc() -> Oidc {
+        Oidc::new(OidcConfig::new(
+            "https://accounts.example.com/",
+            "app",
+            "https://app.example.com/auth/callback?x=1",
+        ))
+    }
+
+    #[test]
+    fn base64_url_is_unpadded() {
+        assert_eq!("-__-", BASE64_URL.encode(b"\xfb\xff\xfe"));
+        assert_eq!("Zm8", BASE64_URL.encode(b"fo"));
+        assert_eq!(b"fo".to_vec(), BASE64_URL.decode("Zm8=").unwrap());
+        assert_eq!(b"fo".to_vec(), BASE64_URL.decode("Zm8").unwrap());
+    }
+
+    #[test]
+    fn pkce_challenge_matches_rfc_7636() {
+        assert_eq!(
+            "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM",
+            pkce_challenge("dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk")
+        );
+    }
+
+    #[test]
+    fn only_local_paths_are_returned_to() {
+        assert_eq!("/orders?id=3", local_path("/orders?id=3"));
+        assert_eq!("/", local_path("/"));
+        assert_eq!("/", local_path("//evil.example/path"));
+        assert_eq!("/", local_path("/\\evil.example"));
+        assert_eq!("/", local_path("https://evil.example"));
+        assert_eq!("/", local_path(""));
+    }
+
+    #[test]
+    fn provider_urls_must_use_https() {
+        let endpoints = |issuer: &str, token: &str| {
+            let mut config = OidcConfig::new(issuer, "app", "https://app.example.com/cb");
+            config.authorization_endpoint = Some("https://accounts.example.com/auth".into());
+            config.token_endpoint = Some(token.into());
+            crate::http::run(Oidc::new(config).endpoints())
+        };
+
+        let (authorization, token) = endpoints(
+            "https://accounts.example.com",
+            "HTTPS://accounts.example.com/token",
+        )
+        .unwrap();
+        assert_eq!("https://accounts.example.com/auth", authorization);
+        assert_eq!("HTTPS://accounts.example.com/token", token);
+        assert!(endpoints(
+            "http://accounts.example.com",
+            "https://accounts.example.com/token"
+        )
+        .is_err());
+        assert!(endpoints(
+            "https://accounts.example.com",
+            "http://accounts.example.com/token"
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn paths_of_urls() {
+        assert_eq!("/auth/callback", path_of(&oidc().config.redirect_url));
+        assert_eq!("/", path_of("https://app.example.com"));
+    }
+
+    #[test]
+    fn id_token_claims_are_validated() {
+        let payload = serde_json::json!({
+            "iss": "https://accounts.example.com",
+            "aud": ["app"],
+            "exp": 2000,
+            "nonce": "n",
+            "sub": "u1",
+            "email": "u1@example.com",
+        });
+        let token = format!("e30.{}.sig", BASE64_URL.encode(payload.to_string()));
+        let claims = decode_claims(&token).unwrap();
+
+        let oidc = oidc();
+        oidc.validate_claims(&claims, "n", 1000).unwrap();
+        assert!(oidc.validate_claims(&claims, "other", 1000).is_err());
+        assert!(oidc.validate_claims(&claims, "n", 3000).is_err());
+
+        let identity = Identity::from_claims(claims).unwrap();
+        assert_eq!("u1", identity.subject);
+        assert_eq!(Some("u1@example.com"), identity.email.as_deref());
+    }
+
+    #[test]
+    fn identity_is_set_in_headers() {
+        let identity = Identity {
+            subject: "u1".to_owned(),
+            email: None,
+            name: Some("Ada".to_owned()),
+            claims: Map::new(),
+        };
+        let mut req = Request::get("/").build();
+        identity.apply(&mut req);
+        assert_eq!(Some("u1"), req.header(SUBJECT_HEADER).unwrap().as_str());
+        assert_eq!(Some("Ada"), req.header(NAME_HEADER).unwrap().as_str());
+        assert!(req.header(EMAIL_HEADER).is_none());
+    }
+}
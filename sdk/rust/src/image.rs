@@ -0,0 +1,47 @@
+//! Image processing
+//!
+//! Images are decoded, resized and re-encoded by the host, which is much faster than doing so in the component
+//! and keeps image codecs out of its binary. The component must be granted the `image` capability in the
+//! manifest:
+//!
+//! ```toml
+//! [component.thumbnails]
+//! capabilities = ["image"]
+//! ```
+//!
+//! ```ignore
+//! use spin_sdk::image::{self, Fit, Format};
+//!
+//! let thumbnail = image::resize(&upload, 200, 200, Fit::Cover)?;
+//! let jpeg = image::convert(&thumbnail, Format::Jpeg, Some(85))?;
+//! ```
+
+use super::wit::v3::image;
+
+#[doc(inline)]
+pub use image::{Error, Fit, Format, ImageInfo};
+
+/// Return the dimensions and format of `image`.
+pub fn info(image: &[u8]) -> Result<ImageInfo, Error> {
+    image::info(image)
+}
+
+/// Resize `image` to `width` by `height` pixels, as `fit` says, in the same format.
+pub fn resize(image: &[u8], width: u32, height: u32, fit: Fit) -> Result<Vec<u8>, Error> {
+    image::resize(image, width, height, fit)
+}
+
+/// Crop `image` to the `width` by `height` pixels whose top left corner is at (`x`, `y`), in the same format.
+pub fn crop(image: &[u8], x: u32, y: u32, width: u32, height: u32) -> Result<Vec<u8>, Error> {
+    image::crop(image, x, y, width, height)
+}
+
+/// Re-encode `image` in `format`, with `quality` from 1 to 100 for JPEG.
+pub fn convert(image: &[u8], format: Format, quality: Option<u8>) -> Result<Vec<u8>, Error> {
+    image::convert(image, format, quality)
+}
+
+/// Re-encode `image` without its metadata, such as EXIF location and camera details.
+pub fn strip_metadata(image: &[u8]) -> Result<Vec<u8>, Error> {
+    image::strip_metadata(image)
+}
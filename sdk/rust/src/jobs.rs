@@ -0,0 +1,56 @@
+//! Spin jobs
+//!
+//! A job delivers a payload to a component of the same application after a delay, e.g. to send a reminder a
+//! day after a user signs up, without an external scheduler. Jobs are stored durably by the host, so they
+//! still run if the application restarts in the meantime, and a job whose handler fails is retried later, so
+//! handlers should cope with a job being delivered more than once.
+//!
+//! The scheduling component must list the target in its manifest, with `job_targets = ["send-reminder"]`,
+//! and the target handles jobs with a function annotated with [`job_component`](crate::job_component):
+//!
+//! ```ignore
+//! use spin_sdk::job_component;
+//!
+//! #[job_component]
+//! fn handle_job(id: String, payload: Vec<u8>) -> anyhow::Result<()> {
+//!     println!("Running job {id}");
+//!     Ok(())
+//! }
+//! ```
+
+use std::time::Duration;
+
+use super::wit::v3::jobs;
+
+#[cfg(feature = "json")]
+use serde::Serialize;
+
+#[doc(inline)]
+pub use jobs::Error;
+
+/// Schedule a job which delivers `payload` to the component `component_id` after `delay`, returning the ID
+/// of the job.
+pub fn enqueue(
+    component_id: &str,
+    payload: impl AsRef<[u8]>,
+    delay: Duration,
+) -> Result<String, Error> {
+    jobs::enqueue(component_id, payload.as_ref(), delay.as_secs())
+}
+
+/// Serialize `payload` to JSON and schedule a job which delivers it to the component `component_id` after
+/// `delay`, returning the ID of the job.
+#[cfg(feature = "json")]
+pub fn enqueue_json<T: Serialize>(
+    component_id: &str,
+    payload: &T,
+    delay: Duration,
+) -> Result<String, anyhow::Error> {
+    let payload = serde_json::to_vec(payload)?;
+    Ok(enqueue(component_id, payload, delay)?)
+}
+
+/// Cancel the job with the given ID, returning whether it had yet to run.
+pub fn cancel(id: &str) -> Result<bool, Error> {
+    jobs::cancel(id)
+}
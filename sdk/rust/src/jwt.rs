@@ -0,0 +1,40 @@
+//! JSON Web Tokens
+//!
+//! Tokens are signed and verified by the host, with keys of the [`crypto`](crate::crypto) interface or with
+//! JSON Web Key Sets which the host fetches and caches, so the component needs no RSA or elliptic curve code.
+//! Key sets may only be fetched from the component's allowed outbound hosts:
+//!
+//! ```toml
+//! [component.api]
+//! allowed_outbound_hosts = ["https://auth.example.com"]
+//! ```
+//!
+//! ```ignore
+//! use spin_sdk::jwt::{self, Verifier, VerifyOptions};
+//!
+//! let claims = jwt::verify(
+//!     token,
+//!     &Verifier::Jwks("https://auth.example.com/.well-known/jwks.json".into()),
+//!     &VerifyOptions {
+//!         issuer: Some("https://auth.example.com/".into()),
+//!         audience: Some("api".into()),
+//!         leeway: 30,
+//!     },
+//! )?;
+//! ```
+
+use super::wit::v3::jwt;
+
+#[doc(inline)]
+pub use jwt::{Error, Verifier, VerifyOptions};
+
+/// Sign a token whose claims are `claims`, a JSON object, with the `key` crypto key, naming `key_id` in its
+/// header if given.
+pub fn sign(key: &str, claims: &str, key_id: Option<&str>) -> Result<String, Error> {
+    jwt::sign(key, claims, key_id)
+}
+
+/// Verify the signature and claims of `token` with `verifier`, returning its claims as a JSON object.
+pub fn verify(token: &str, verifier: &Verifier, options: &VerifyOptions) -> Result<String, Error> {
+    jwt::verify(token, verifier, options)
+}
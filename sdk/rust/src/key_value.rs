@@ -3,14 +3,20 @@
 //! This module provides a generic interface for key-value storage, which may be implemented by the host various
 //! ways (e.g. via an in-memory table, a local file, or a remote database). Details such as consistency model and
 //! durability will depend on the implementation and may vary from one to store to the next.
+//!
+//! Tuples may be given an expiry with `Store::set_with_ttl` or `Store::expire`, after which they behave as if
+//! they had been deleted. This is useful for caches, which would otherwise grow forever.
 
-use super::wit::v2::key_value;
+use super::wit::v3::key_value;
 
 #[cfg(feature = "json")]
 use serde::{de::DeserializeOwned, Serialize};
 
 #[doc(inline)]
-pub use key_value::{Error, Store};
+pub use key_value::{Error, ScanResult, Store};
+
+/// The number of keys fetched per `scan` by [`Store::keys_with_prefix`].
+const SCAN_PAGE_SIZE: u32 = 100;
 
 impl Store {
     /// Open the default store.
@@ -19,6 +25,55 @@ impl Store {
     pub fn open_default() -> Result<Self, Error> {
         Self::open("default")
     }
+
+    /// Iterate over the keys beginning with `prefix`, fetching them a page at a time with `scan`.
+    pub fn keys_with_prefix<'a>(&'a self, prefix: &'a str) -> Keys<'a> {
+        Keys {
+            store: self,
+            prefix,
+            page: Vec::new().into_iter(),
+            cursor: None,
+            done: false,
+        }
+    }
+}
+
+/// An iterator over keys, returned by [`Store::keys_with_prefix`].
+pub struct Keys<'a> {
+    store: &'a Store,
+    prefix: &'a str,
+    page: std::vec::IntoIter<String>,
+    cursor: Option<String>,
+    done: bool,
+}
+
+impl Iterator for Keys<'_> {
+    type Item = Result<String, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(key) = self.page.next() {
+                return Some(Ok(key));
+            }
+            if self.done {
+                return None;
+            }
+            match self
+                .store
+                .scan(self.prefix, self.cursor.as_deref(), SCAN_PAGE_SIZE)
+            {
+                Ok(result) => {
+                    self.page = result.keys.into_iter();
+                    self.done = result.cursor.is_none();
+                    self.cursor = result.cursor;
+                }
+                Err(e) => {
+                    self.done = true;
+                    return Some(Err(e));
+                }
+            }
+        }
+    }
 }
 
 impl Store {
@@ -43,4 +98,41 @@ impl Store {
         };
         Ok(serde_json::from_slice(&value)?)
     }
+
+    #[cfg(feature = "json")]
+    /// Serialize the given data to JSON, then set it as the value for the specified `key`, expiring after
+    /// `seconds`.
+    pub fn set_json_with_ttl<T: Serialize>(
+        &self,
+        key: impl AsRef<str>,
+        value: &T,
+        seconds: u64,
+    ) -> Result<(), anyhow::Error> {
+        Ok(self.set_with_ttl(key.as_ref(), &serde_json::to_vec(value)?, seconds)?)
+    }
+
+    #[cfg(feature = "json")]
+    /// Serialize `patch` to JSON, then apply it as a JSON merge patch (RFC 7396) to the value of `key`.
+    ///
+    /// Fields of `patch` which are `null` are removed from the value, and others are set in it, recursively.
+    /// Stores which support it apply the patch on the host, atomically.
+    pub fn patch_json<T: Serialize>(
+        &self,
+        key: impl AsRef<str>,
+        patch: &T,
+    ) -> Result<(), anyhow::Error> {
+        Ok(self.merge_patch(key.as_ref(), &serde_json::to_vec(patch)?)?)
+    }
+}
+
+#[cfg(feature = "json")]
+/// Deserialize an instance of type `T` from the value of `key` in the default store.
+pub fn get_json<T: DeserializeOwned>(key: impl AsRef<str>) -> Result<Option<T>, anyhow::Error> {
+    Store::open_default()?.get_json(key)
+}
+
+#[cfg(feature = "json")]
+/// Serialize the given data to JSON, then set it as the value for `key` in the default store.
+pub fn set_json<T: Serialize>(key: impl AsRef<str>, value: &T) -> Result<(), anyhow::Error> {
+    Store::open_default()?.set_json(key, value)
 }
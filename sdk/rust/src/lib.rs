@@ -2,12 +2,69 @@
 
 #![deny(missing_docs)]
 
+/// Defines deferred variants of blocking methods of a host resource.
+///
+/// A deferred variant is a future which makes the host call when it is
+/// first resumed after yielding once, so that all the futures joined with
+/// it, such as outbound HTTP requests, have been started by then. The host
+/// call itself is not asynchronous: it blocks the instance until it
+/// completes, though requests already in flight continue on the host in the
+/// meantime.
+macro_rules! deferred_methods {
+    ($($(#[$attr:meta])* fn $name:ident => $sync:ident($($arg:ident: $ty:ty),*) -> $ret:ty;)*) => {
+        $(
+            $(#[$attr])*
+            pub async fn $name(&self, $($arg: $ty),*) -> $ret {
+                $crate::http::yield_now().await;
+                self.$sync($($arg),*)
+            }
+        )*
+    };
+}
+
 /// Key/Value storage.
 pub mod key_value;
 
 /// SQLite storage.
 pub mod sqlite;
 
+/// Caches which may evict entries.
+pub mod cache;
+
+pub mod jobs;
+
+pub mod workflows;
+
+pub mod context;
+
+pub mod lock;
+
+pub mod app_state;
+
+pub mod flags;
+
+pub mod image;
+
+pub mod crypto;
+
+pub mod jwt;
+
+pub mod password;
+
+pub mod random;
+
+pub mod timezone;
+
+pub mod template;
+
+pub mod html;
+
+pub mod pdf;
+
+/// Cookie-based sessions.
+#[cfg(feature = "json")]
+pub mod session;
+
 /// Large Language Model APIs
 pub mod llm;
 
@@ -26,6 +83,7 @@ pub mod wit {
         path: "../../wit/preview2",
     });
     pub use fermyon::spin2_0_0 as v2;
+    pub use fermyon::spin3_0_0 as v3;
 }
 
 /// Needed by the export macro
@@ -48,12 +106,50 @@ extern "C" fn __spin_sdk_hash() {}
 /// Helpers for building Spin `wasi-http` components.
 pub mod http;
 
+pub mod futures;
+
+pub mod task;
+
 /// Implementation of the spin redis interface.
 #[allow(missing_docs)]
 pub mod redis {
     use std::hash::{Hash, Hasher};
 
-    pub use super::wit::v2::redis::{Connection, Error, Payload, RedisParameter, RedisResult};
+    pub use super::wit::v3::redis::{Connection, Error, Payload, RedisParameter, RedisResult};
+
+    /// Deferred variants of the connection's methods, for use with `join!`
+    /// alongside other futures such as outbound HTTP requests. Each call is
+    /// made once the futures joined with it have started, and blocks the
+    /// instance until it completes.
+    impl Connection {
+        /// Open a connection to the Redis instance at `address`, once joined
+        /// futures have started.
+        pub async fn open_deferred(address: &str) -> Result<Self, Error> {
+            super::http::yield_now().await;
+            Self::open(address)
+        }
+
+        deferred_methods! {
+            /// Publish a Redis message to the specified channel, once joined futures have started.
+            fn publish_deferred => publish(channel: &str, payload: &Payload) -> Result<(), Error>;
+            /// Get the value of a key, once joined futures have started.
+            fn get_deferred => get(key: &str) -> Result<Option<Payload>, Error>;
+            /// Set key to value, once joined futures have started.
+            fn set_deferred => set(key: &str, value: &Payload) -> Result<(), Error>;
+            /// Increment the number stored at key by one, once joined futures have started.
+            fn incr_deferred => incr(key: &str) -> Result<i64, Error>;
+            /// Remove the specified keys, once joined futures have started.
+            fn del_deferred => del(keys: &[String]) -> Result<u32, Error>;
+            /// Add values to the set named `key`, once joined futures have started.
+            fn sadd_deferred => sadd(key: &str, values: &[String]) -> Result<u32, Error>;
+            /// Retrieve the contents of the set named `key`, once joined futures have started.
+            fn smembers_deferred => smembers(key: &str) -> Result<Vec<String>, Error>;
+            /// Remove values from the set named `key`, once joined futures have started.
+            fn srem_deferred => srem(key: &str, values: &[String]) -> Result<u32, Error>;
+            /// Execute an arbitrary Redis command, once joined futures have started.
+            fn execute_deferred => execute(command: &str, arguments: &[RedisParameter]) -> Result<Vec<RedisResult>, Error>;
+        }
+    }
 
     impl PartialEq for RedisResult {
         fn eq(&self, other: &Self) -> bool {
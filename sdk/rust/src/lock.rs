@@ -0,0 +1,64 @@
+//! Spin locks
+//!
+//! A lock is shared by all instances of an application, so that only one replica performs a task such as a
+//! database migration at a time. Each lock is held for at most its time to live, so that a holder which
+//! crashes doesn't hold it forever; the task should finish well within that time.
+//!
+//! ```ignore
+//! use std::time::Duration;
+//!
+//! if let Some(lock) = spin_sdk::lock::acquire("migrate", Duration::from_secs(60))? {
+//!     run_migrations()?;
+//!     lock.release()?;
+//! }
+//! ```
+//!
+//! The backend is chosen in the runtime configuration, e.g. `[locks] type = "redis"`; by default, locks are
+//! only shared within one Spin process.
+
+use std::time::Duration;
+
+use super::wit::v3::lock;
+
+#[doc(inline)]
+pub use lock::Error;
+
+/// A held lock, which is released when dropped.
+#[derive(Debug)]
+pub struct Lock {
+    name: String,
+    token: Option<String>,
+}
+
+/// Try to acquire the lock called `name`, holding it for at most `ttl`, returning `None` if it is already
+/// held.
+pub fn acquire(name: &str, ttl: Duration) -> Result<Option<Lock>, Error> {
+    let ttl = ttl.as_millis().try_into().unwrap_or(u64::MAX);
+    Ok(lock::acquire(name, ttl)?.map(|token| Lock {
+        name: name.to_owned(),
+        token: Some(token),
+    }))
+}
+
+impl Lock {
+    /// The name of the lock.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Release the lock, returning whether it was still held, i.e. its time to live hadn't run out.
+    pub fn release(mut self) -> Result<bool, Error> {
+        match self.token.take() {
+            Some(token) => lock::release(&self.name, &token),
+            None => Ok(false),
+        }
+    }
+}
+
+impl Drop for Lock {
+    fn drop(&mut self) {
+        if let Some(token) = self.token.take() {
+            _ = lock::release(&self.name, &token);
+        }
+    }
+}
@@ -0,0 +1,35 @@
+//! Password hashing
+//!
+//! Passwords are hashed by the host, many times faster than inside a component, at the cost set in the
+//! runtime configuration. Hashes hold their algorithm, cost and salt, so may be stored and verified as is:
+//!
+//! ```ignore
+//! use spin_sdk::password::{self, Algorithm};
+//!
+//! let hash = password::hash(&new_password, Algorithm::Argon2id)?;
+//!
+//! if password::verify(&attempt, &stored_hash)? && password::needs_rehash(&stored_hash)? {
+//!     store(password::hash(&attempt, Algorithm::Argon2id)?);
+//! }
+//! ```
+
+use super::wit::v3::password;
+
+#[doc(inline)]
+pub use password::{Algorithm, Error};
+
+/// Hash `password` with `algorithm` and a random salt.
+pub fn hash(password: &str, algorithm: Algorithm) -> Result<String, Error> {
+    password::hash(password, algorithm)
+}
+
+/// Return whether `password` is the one `hash` was made from.
+pub fn verify(password: &str, hash: &str) -> Result<bool, Error> {
+    password::verify(password, hash)
+}
+
+/// Return whether `hash` was made at a lower cost than is now configured, and so should be replaced by a new
+/// hash once its password is next verified.
+pub fn needs_rehash(hash: &str) -> Result<bool, Error> {
+    password::needs_rehash(hash)
+}
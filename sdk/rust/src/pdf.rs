@@ -0,0 +1,26 @@
+//! PDF rendering
+//!
+//! HTML documents are rendered as PDFs by the host, with a renderer it is configured with. The component must be
+//! granted the `pdf` capability in the manifest:
+//!
+//! ```ignore
+//! use spin_sdk::pdf::{self, Options, PageSize};
+//!
+//! let options = Options {
+//!     page_size: PageSize::A4,
+//!     landscape: false,
+//!     margin_mm: 15,
+//!     print_background: true,
+//! };
+//! let invoice = pdf::render(&html, options)?;
+//! ```
+
+use super::wit::v3::pdf;
+
+#[doc(inline)]
+pub use pdf::{Error, Options, PageSize};
+
+/// Render the HTML document `html` as a PDF. Fails with [`Error::Unavailable`] if the host has no PDF renderer.
+pub fn render(html: &str, options: Options) -> Result<Vec<u8>, Error> {
+    pdf::render(html, options)
+}
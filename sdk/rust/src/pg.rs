@@ -12,11 +12,17 @@
 //! | `f64`      | floating64(float64) | DOUBLE PRECISION, FLOAT8     |
 //! | `String`   | str(string)         | VARCHAR, CHAR(N), TEXT       |
 //! | `Vec<u8>`  | binary(list\<u8\>)  | BYTEA                        |
+//!
+//! # NULL parameters
+//!
+//! Postgres can't always infer the type of a bare `ParameterValue::DbNull`
+//! (e.g. in `SELECT $1 IS NULL`). Use a typed NULL such as
+//! [`ParameterValue::null_int64`] to tell it which type is intended.
 
 #[doc(inline)]
-pub use super::wit::v2::postgres::{Connection, Error as PgError};
+pub use super::wit::v3::postgres::{Connection, Error as PgError, ExecuteResult};
 #[doc(inline)]
-pub use super::wit::v2::rdbms_types::*;
+pub use super::wit::v3::rdbms_types::*;
 
 /// A pg error
 #[derive(Debug, thiserror::Error)]
@@ -29,6 +35,85 @@ pub enum Error {
     PgError(#[from] PgError),
 }
 
+/// Deferred variants of the connection's methods, for use with `join!`
+/// alongside other futures such as outbound HTTP requests.
+///
+/// Postgres host calls are not asynchronous. Each deferred query runs when
+/// its future is first resumed, after all the futures joined with it have
+/// started, and blocks the instance until it completes. Outbound HTTP
+/// requests already in flight continue meanwhile, so a query joined with an
+/// HTTP request takes about as long as the slower of the two.
+impl Connection {
+    /// Open a connection to the Postgres instance at `address`, once joined
+    /// futures have started.
+    pub async fn open_deferred(address: &str) -> Result<Self, PgError> {
+        crate::http::yield_now().await;
+        Self::open(address)
+    }
+
+    /// Open a connection to a database configured by name, once joined
+    /// futures have started.
+    pub async fn open_database_deferred(name: &str) -> Result<Self, PgError> {
+        crate::http::yield_now().await;
+        Self::open_database(name)
+    }
+
+    deferred_methods! {
+        /// Query the database, once joined futures have started.
+        fn query_deferred => query(statement: &str, params: &[ParameterValue]) -> Result<RowSet, PgError>;
+        /// Query the database, preferring a read replica, once joined futures have started.
+        fn query_read_deferred => query_read(statement: &str, params: &[ParameterValue]) -> Result<RowSet, PgError>;
+        /// Execute a command, once joined futures have started.
+        fn execute_deferred => execute(statement: &str, params: &[ParameterValue]) -> Result<u64, PgError>;
+        /// Execute a command and return any rows it produces, once joined futures have started.
+        fn execute_returning_deferred => execute_returning(statement: &str, params: &[ParameterValue]) -> Result<ExecuteResult, PgError>;
+        /// Execute a script of statements, once joined futures have started.
+        fn batch_execute_deferred => batch_execute(statements: &str) -> Result<(), PgError>;
+    }
+}
+
+impl ParameterValue {
+    /// A NULL `BOOL` parameter.
+    pub fn null_boolean() -> Self {
+        Self::TypedNull(DbDataType::Boolean)
+    }
+
+    /// A NULL `SMALLINT` parameter.
+    pub fn null_int16() -> Self {
+        Self::TypedNull(DbDataType::Int16)
+    }
+
+    /// A NULL `INT` parameter.
+    pub fn null_int32() -> Self {
+        Self::TypedNull(DbDataType::Int32)
+    }
+
+    /// A NULL `BIGINT` parameter.
+    pub fn null_int64() -> Self {
+        Self::TypedNull(DbDataType::Int64)
+    }
+
+    /// A NULL `REAL` parameter.
+    pub fn null_floating32() -> Self {
+        Self::TypedNull(DbDataType::Floating32)
+    }
+
+    /// A NULL `DOUBLE PRECISION` parameter.
+    pub fn null_floating64() -> Self {
+        Self::TypedNull(DbDataType::Floating64)
+    }
+
+    /// A NULL `TEXT` parameter.
+    pub fn null_str() -> Self {
+        Self::TypedNull(DbDataType::Str)
+    }
+
+    /// A NULL `BYTEA` parameter.
+    pub fn null_binary() -> Self {
+        Self::TypedNull(DbDataType::Binary)
+    }
+}
+
 /// A type that can be decoded from the database.
 pub trait Decode: Sized {
     /// Decode a new value of this type using a [`DbValue`].
@@ -0,0 +1,36 @@
+//! Secure random bytes and identifiers
+//!
+//! These come from the host, so don't depend on how the component's language seeds its generator. UUIDv7s and
+//! ULIDs sort in the order they were made across the whole application, even within a millisecond.
+//!
+//! ```ignore
+//! use spin_sdk::random;
+//!
+//! let order_id = random::ulid();
+//! let session_token = random::bytes(32)?;
+//! ```
+
+use super::wit::v3::random;
+
+#[doc(inline)]
+pub use random::Error;
+
+/// Return `len` cryptographically secure random bytes, up to 65536.
+pub fn bytes(len: u32) -> Result<Vec<u8>, Error> {
+    random::bytes(len)
+}
+
+/// Return a random (version 4) UUID, such as `0b7a9f4e-52d1-4c3a-9d5e-2f6b8c1a7e30`.
+pub fn uuid_v4() -> String {
+    random::uuid_v4()
+}
+
+/// Return a time-ordered (version 7) UUID.
+pub fn uuid_v7() -> String {
+    random::uuid_v7()
+}
+
+/// Return a ULID, such as `01ARZ3NDEKTSV4RRFFQ69G5FAV`.
+pub fn ulid() -> String {
+    random::ulid()
+}
@@ -0,0 +1,240 @@
+//! Sessions stored in a key-value store
+//!
+//! A session is identified by a random ID sent to the client in a cookie, and
+//! its data is stored as JSON under `session:<id>` in a key-value store which
+//! the component is allowed to use. Sessions expire after
+//! [`SessionConfig::ttl`] without changes; expired sessions are removed from
+//! the store when they are next loaded.
+//!
+//! ```ignore
+//! let config = SessionConfig::default();
+//! let mut session = Session::load(&req, &config)?;
+//! let visits: u32 = session.get("visits")?.unwrap_or(0);
+//! session.set("visits", &(visits + 1))?;
+//! session.save(Response::new(200, format!("visit {visits}")))
+//! ```
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::Context;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::http::cookie::{self, Cookie, Key, SameSite};
+use crate::http::{IntoResponse, Request, Response};
+use crate::key_value::Store;
+
+/// How sessions are stored, and the attributes of their cookie.
+#[derive(Clone, Debug)]
+pub struct SessionConfig {
+    /// The key-value store in which sessions are stored. Defaults to `default`.
+    pub store: String,
+    /// The name of the session cookie. Defaults to `spin-session`.
+    pub cookie_name: String,
+    /// How long a session lasts without changes. Defaults to one day.
+    pub ttl: Duration,
+    /// If set, the session ID is signed with this key, so that IDs which
+    /// weren't issued by the application are rejected without a store lookup.
+    pub key: Option<Key>,
+    /// The `Path` attribute of the cookie. Defaults to `/`.
+    pub path: String,
+    /// The `Domain` attribute of the cookie.
+    pub domain: Option<String>,
+    /// Whether the cookie is only sent over HTTPS. Defaults to `true`.
+    pub secure: bool,
+    /// Whether the cookie is hidden from scripts. Defaults to `true`.
+    pub http_only: bool,
+    /// The `SameSite` attribute of the cookie. Defaults to `Lax`.
+    pub same_site: SameSite,
+}
+
+impl Default for SessionConfig {
+    fn default() -> Self {
+        Self {
+            store: "default".to_owned(),
+            cookie_name: "spin-session".to_owned(),
+            ttl: Duration::from_secs(24 * 60 * 60),
+            key: None,
+            path: "/".to_owned(),
+            domain: None,
+            secure: true,
+            http_only: true,
+            same_site: SameSite::Lax,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct Stored {
+    /// Seconds since the Unix epoch
+    expires: u64,
+    data: Map<String, Value>,
+}
+
+/// The session of a request.
+pub struct Session {
+    config: SessionConfig,
+    store: Store,
+    id: Option<String>,
+    /// The ID under which the session was loaded, if it has since changed.
+    previous_id: Option<String>,
+    data: Map<String, Value>,
+    changed: bool,
+    destroyed: bool,
+}
+
+impl Session {
+    /// Loads the session of a request, or starts a new, empty one if the
+    /// request has no valid session cookie.
+    pub fn load(req: &Request, config: &SessionConfig) -> anyhow::Result<Self> {
+        let store = Store::open(&config.store)
+            .with_context(|| format!("failed to open session store {:?}", config.store))?;
+        let mut session = Self {
+            config: config.clone(),
+            store,
+            id: None,
+            previous_id: None,
+            data: Map::new(),
+            changed: false,
+            destroyed: false,
+        };
+        let Some(id) = session.cookie_id(req) else {
+            return Ok(session);
+        };
+        let Some(stored) = session.store.get(&storage_key(&id))? else {
+            return Ok(session);
+        };
+        let stored: Stored = serde_json::from_slice(&stored)?;
+        if stored.expires <= now() {
+            session.store.delete(&storage_key(&id))?;
+            return Ok(session);
+        }
+        session.id = Some(id);
+        session.data = stored.data;
+        Ok(session)
+    }
+
+    fn cookie_id(&self, req: &Request) -> Option<String> {
+        let value = cookie::get(req, &self.config.cookie_name)?;
+        let id = match &self.config.key {
+            Some(key) => key.verify(&self.config.cookie_name, value)?,
+            None => value,
+        };
+        let valid = id.len() == 64 && id.bytes().all(|b| b.is_ascii_hexdigit());
+        valid.then(|| id.to_owned())
+    }
+
+    /// The session's ID, if it has been stored.
+    pub fn id(&self) -> Option<&str> {
+        self.id.as_deref()
+    }
+
+    /// Deserializes the value of `key`.
+    pub fn get<T: DeserializeOwned>(&self, key: &str) -> anyhow::Result<Option<T>> {
+        self.data
+            .get(key)
+            .map(|value| T::deserialize(value).map_err(Into::into))
+            .transpose()
+    }
+
+    /// Sets the value of `key`.
+    pub fn set<T: Serialize>(&mut self, key: &str, value: &T) -> anyhow::Result<()> {
+        self.data
+            .insert(key.to_owned(), serde_json::to_value(value)?);
+        self.changed = true;
+        Ok(())
+    }
+
+    /// Removes `key`, returning whether it was set.
+    pub fn remove(&mut self, key: &str) -> bool {
+        let removed = self.data.remove(key).is_some();
+        self.changed |= removed;
+        removed
+    }
+
+    /// Gives the session a new ID, keeping its data.
+    ///
+    /// This should be done when a user signs in, so that an ID set by an
+    /// attacker before then can't be used to act as the user.
+    pub fn regenerate(&mut self) {
+        if let Some(id) = self.id.take() {
+            self.previous_id.get_or_insert(id);
+        }
+        self.changed = true;
+    }
+
+    /// Removes the session from the store and the client when saved.
+    pub fn destroy(&mut self) {
+        self.data.clear();
+        self.destroyed = true;
+    }
+
+    /// Stores the session if it changed, and adds its cookie to `response`.
+    pub fn save(mut self, response: impl IntoResponse) -> anyhow::Result<Response> {
+        let response = response.into_response();
+        if let Some(id) = self.previous_id.take() {
+            self.store.delete(&storage_key(&id))?;
+        }
+        if self.destroyed {
+            if let Some(id) = &self.id {
+                self.store.delete(&storage_key(id))?;
+            }
+            let cookie = self.cookie(Cookie::removal(&self.config.cookie_name));
+            return Ok(with_cookie(response, cookie));
+        }
+        if !self.changed {
+            return Ok(response);
+        }
+        let id = match &self.id {
+            Some(id) => id.clone(),
+            None => new_id()?,
+        };
+        let stored = Stored {
+            expires: now() + self.config.ttl.as_secs(),
+            data: std::mem::take(&mut self.data),
+        };
+        self.store
+            .set(&storage_key(&id), &serde_json::to_vec(&stored)?)?;
+        let value = match &self.config.key {
+            Some(key) => key.sign(&self.config.cookie_name, &id),
+            None => id,
+        };
+        let cookie = Cookie::new(&self.config.cookie_name, value).max_age(self.config.ttl);
+        Ok(with_cookie(response, self.cookie(cookie)))
+    }
+
+    fn cookie(&self, cookie: Cookie) -> Cookie {
+        let cookie = cookie
+            .path(&self.config.path)
+            .secure(self.config.secure)
+            .http_only(self.config.http_only)
+            .same_site(self.config.same_site);
+        match &self.config.domain {
+            Some(domain) => cookie.domain(domain),
+            None => cookie,
+        }
+    }
+}
+
+fn with_cookie(response: Response, cookie: Cookie) -> Response {
+    response
+        .into_builder()
+        .header("set-cookie", cookie.to_string())
+        .build()
+}
+
+fn storage_key(id: &str) -> String {
+    format!("session:{id}")
+}
+
+fn new_id() -> anyhow::Result<String> {
+    let mut bytes = [0; 32];
+    getrandom::getrandom(&mut bytes).context("failed to generate a session ID")?;
+    Ok(crate::http::cookie::hex(&bytes))
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
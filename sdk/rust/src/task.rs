@@ -0,0 +1,84 @@
+//! Work deferred until after a component's response is sent.
+//!
+//! Sending analytics events or webhooks needn't delay the response to the
+//! client. A future passed to [`spawn_after_response`] is run once the
+//! HTTP handler has returned and its response has been sent:
+//!
+//! ```ignore
+//! use spin_sdk::http::{send, Request, Response};
+//! use spin_sdk::task::spawn_after_response;
+//!
+//! #[http_component]
+//! fn handle(req: Request) -> Response {
+//!     let event = Request::post("https://analytics.example.com/events", req.path().to_owned());
+//!     spawn_after_response(async move {
+//!         if let Err(e) = send::<_, Response>(event).await {
+//!             eprintln!("Could not send analytics event: {e}");
+//!         }
+//!     });
+//!     Response::new(200, "hello")
+//! }
+//! ```
+//!
+//! The host keeps the instance running only for a short time after the
+//! response body is complete (`--after-response-timeout` on `spin up`, five
+//! seconds by default), after which any deferred work which hasn't finished
+//! is abandoned, so it is for best-effort work rather than anything which
+//! must happen.
+
+use std::cell::RefCell;
+use std::future::Future;
+use std::pin::Pin;
+
+type Task = Pin<Box<dyn Future<Output = ()>>>;
+
+thread_local! {
+    static AFTER_RESPONSE: RefCell<Vec<Task>> = RefCell::new(Vec::new());
+}
+
+/// Runs `future` after the handler's response has been sent.
+///
+/// Deferred futures run concurrently with each other, and may themselves
+/// defer more work.
+pub fn spawn_after_response(future: impl Future<Output = ()> + 'static) {
+    AFTER_RESPONSE.with(|tasks| tasks.borrow_mut().push(Box::pin(future)));
+}
+
+/// Runs the work deferred with [`spawn_after_response`] to completion.
+///
+/// This is called by the `http_component` macro once the handler returns.
+#[doc(hidden)]
+pub async fn run_after_response() {
+    loop {
+        let tasks = AFTER_RESPONSE.with(|tasks| std::mem::take(&mut *tasks.borrow_mut()));
+        if tasks.is_empty() {
+            break;
+        }
+        futures::future::join_all(tasks).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::rc::Rc;
+
+    #[test]
+    fn deferred_work_runs_including_work_it_defers() {
+        let order = Rc::new(RefCell::new(vec![]));
+        let push = |n| {
+            let order = order.clone();
+            async move { order.borrow_mut().push(n) }
+        };
+        let nested = push(3);
+        spawn_after_response(push(1));
+        spawn_after_response(async move {
+            spawn_after_response(nested);
+        });
+        spawn_after_response(push(2));
+        assert!(order.borrow().is_empty());
+
+        crate::http::run(run_after_response());
+        assert_eq!(vec![1, 2, 3], *order.borrow());
+    }
+}
@@ -0,0 +1,41 @@
+//! Template rendering
+//!
+//! Templates are rendered by the host, which compiles them when the application starts, so the component
+//! needn't embed a template engine. They are the files under the component's `template_dirs`, which must be
+//! among its `files`:
+//!
+//! ```toml
+//! [component.site]
+//! files = [{ source = "templates", destination = "/templates" }]
+//! template_dirs = ["/templates"]
+//! ```
+//!
+//! Files ending in `.hbs` or `.handlebars` are Handlebars templates; any others are Liquid templates.
+//!
+//! ```ignore
+//! use spin_sdk::template;
+//!
+//! let html = template::render("pages/index.liquid", r#"{"title": "Home"}"#)?;
+//! ```
+
+use super::wit::v3::template;
+
+#[doc(inline)]
+pub use template::Error;
+
+/// Render the template `name`, such as "emails/welcome.hbs", with `data`, a JSON object.
+pub fn render(name: &str, data: &str) -> Result<String, Error> {
+    template::render(name, data)
+}
+
+/// Render the template `name` with `data`, which must serialize to a JSON object.
+#[cfg(feature = "json")]
+pub fn render_with<T: serde::Serialize>(name: &str, data: &T) -> Result<String, Error> {
+    let data = serde_json::to_string(data).map_err(|e| Error::InvalidData(e.to_string()))?;
+    template::render(name, &data)
+}
+
+/// Return the names of the component's templates.
+pub fn names() -> Vec<String> {
+    template::names()
+}
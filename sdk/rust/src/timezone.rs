@@ -0,0 +1,32 @@
+//! Time zone conversions
+//!
+//! Conversions use the host's IANA time zone database, so the component needn't embed one:
+//!
+//! ```ignore
+//! use spin_sdk::timezone;
+//!
+//! let local = timezone::convert(created_at, "America/New_York")?;
+//! let time = format!("{:02}:{:02} {}", local.date_time.hour, local.date_time.minute, local.abbreviation);
+//! ```
+
+use super::wit::v3::timezone;
+
+#[doc(inline)]
+pub use timezone::{DateTime, Error, LocalTime};
+
+/// Return the local time in `zone`, such as "America/New_York", at `timestamp`, in seconds since the Unix
+/// epoch.
+pub fn convert(timestamp: i64, zone: &str) -> Result<LocalTime, Error> {
+    timezone::convert(timestamp, zone)
+}
+
+/// Return the timestamp, in seconds since the Unix epoch, of `local` in `zone`. A local time which occurs twice
+/// is taken to be the earlier one.
+pub fn to_timestamp(local: DateTime, zone: &str) -> Result<i64, Error> {
+    timezone::to_timestamp(local, zone)
+}
+
+/// Return the names of the time zones the host knows.
+pub fn zones() -> Vec<String> {
+    timezone::zones()
+}
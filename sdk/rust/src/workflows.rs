@@ -0,0 +1,69 @@
+//! Spin workflows
+//!
+//! A workflow run records the output of each step of a multi-step process, so that a process interrupted
+//! part way, for example by a crash, can be retried without repeating the steps which already completed.
+//! Runs are usually driven by [jobs](crate::jobs), which are retried until their handler succeeds:
+//!
+//! ```ignore
+//! use spin_sdk::{job_component, workflows::Run};
+//!
+//! #[job_component]
+//! fn handle_job(_id: String, order_id: Vec<u8>) -> anyhow::Result<()> {
+//!     let run = Run::open(std::str::from_utf8(&order_id)?)?;
+//!     let charge = run.step("charge-card", || charge_card(&order_id))?;
+//!     run.step("reserve-stock", || reserve_stock(&order_id))?;
+//!     run.step("send-email", || send_receipt(&order_id, &charge))?;
+//!     run.finish()?;
+//!     Ok(())
+//! }
+//! ```
+//!
+//! A step which fails part way, or whose output isn't recorded before a crash, runs again, so steps should
+//! still be idempotent where possible, for example by passing the run's key to payment providers.
+
+use super::wit::v3::workflows;
+
+#[cfg(feature = "json")]
+use serde::{de::DeserializeOwned, Serialize};
+
+#[doc(inline)]
+pub use workflows::{Error, Run};
+
+impl Run {
+    /// Return the recorded output of the step named `name`, or run the step with `f` and record its output.
+    ///
+    /// If another instance records the step first, its output is returned instead.
+    pub fn step<E: From<Error>>(
+        &self,
+        name: &str,
+        f: impl FnOnce() -> Result<Vec<u8>, E>,
+    ) -> Result<Vec<u8>, E> {
+        if let Some(output) = self.get_step(name)? {
+            return Ok(output);
+        }
+        let output = f()?;
+        self.record_step(name, &output)?;
+        // Another instance may have recorded the step first.
+        Ok(self.get_step(name)?.unwrap_or(output))
+    }
+
+    #[cfg(feature = "json")]
+    /// Return the recorded output of the step named `name`, or run the step with `f` and record its output
+    /// serialized to JSON.
+    pub fn step_json<T: Serialize + DeserializeOwned>(
+        &self,
+        name: &str,
+        f: impl FnOnce() -> Result<T, anyhow::Error>,
+    ) -> Result<T, anyhow::Error> {
+        if let Some(output) = self.get_step(name)? {
+            return Ok(serde_json::from_slice(&output)?);
+        }
+        let output = f()?;
+        self.record_step(name, &serde_json::to_vec(&output)?)?;
+        // Another instance may have recorded the step first.
+        match self.get_step(name)? {
+            Some(recorded) => Ok(serde_json::from_slice(&recorded)?),
+            None => Ok(output),
+        }
+    }
+}
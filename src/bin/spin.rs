@@ -4,14 +4,27 @@ use is_terminal::IsTerminal;
 use lazy_static::lazy_static;
 use spin_cli::commands::external::predefined_externals;
 use spin_cli::commands::{
+    archive::ArchiveCommand,
+    bench::BenchCommand,
     build::BuildCommand,
     cloud::{DeployCommand, LoginCommand},
+    compose::ComposeCommands,
+    conformance::ConformanceCommands,
+    ctl::CtlCommand,
+    data::DataCommands,
     doctor::DoctorCommand,
     external::execute_external_subcommand,
+    inspect::InspectCommand,
+    migrate::MigrateCommand,
     new::{AddCommand, NewCommand},
     plugins::PluginCommands,
     registry::RegistryCommands,
+    replay::ReplayCommand,
+    sdk::SdkCommands,
+    service::ServiceCommands,
+    stats::StatsCommand,
     templates::TemplateCommands,
+    test::TestCommands,
     up::UpCommand,
     watch::WatchCommand,
 };
@@ -42,14 +55,22 @@ async fn main() {
 }
 
 async fn _main() -> anyhow::Result<()> {
-    tracing_subscriber::fmt()
+    let subscriber = tracing_subscriber::fmt()
         .with_writer(std::io::stderr)
         .with_env_filter(
             tracing_subscriber::EnvFilter::from_default_env()
                 .add_directive("watchexec=off".parse()?),
         )
         .with_ansi(std::io::stderr().is_terminal())
-        .init();
+        .with_filter_reloading();
+    let filter_handle = subscriber.reload_handle();
+    subscriber.init();
+    spin_trigger::admin::set_log_level_handler(move |filter| {
+        let filter =
+            tracing_subscriber::EnvFilter::try_new(filter)?.add_directive("watchexec=off".parse()?);
+        filter_handle.reload(filter)?;
+        Ok(())
+    });
 
     let plugin_help_entries = plugin_help_entries();
 
@@ -125,6 +146,7 @@ enum SpinApp {
     #[clap(subcommand, alias = "oci")]
     Registry(RegistryCommands),
     Build(BuildCommand),
+    Archive(ArchiveCommand),
     #[clap(subcommand, alias = "plugin")]
     Plugins(PluginCommands),
     #[clap(subcommand, hide = true)]
@@ -133,6 +155,24 @@ enum SpinApp {
     External(Vec<String>),
     Watch(WatchCommand),
     Doctor(DoctorCommand),
+    Ctl(CtlCommand),
+    #[clap(subcommand)]
+    Data(DataCommands),
+    Stats(StatsCommand),
+    Bench(BenchCommand),
+    Replay(ReplayCommand),
+    Inspect(InspectCommand),
+    Migrate(MigrateCommand),
+    #[clap(subcommand)]
+    Sdk(SdkCommands),
+    #[clap(subcommand)]
+    Compose(ComposeCommands),
+    #[clap(subcommand)]
+    Conformance(ConformanceCommands),
+    #[clap(subcommand)]
+    Test(TestCommands),
+    #[clap(subcommand)]
+    Service(ServiceCommands),
 }
 
 #[derive(Subcommand)]
@@ -155,6 +195,7 @@ impl SpinApp {
             Self::Login(cmd) => cmd.run(SpinApp::command()).await,
             Self::Registry(cmd) => cmd.run().await,
             Self::Build(cmd) => cmd.run().await,
+            Self::Archive(cmd) => cmd.run().await,
             Self::Trigger(TriggerCommands::Http(cmd)) => cmd.run().await,
             Self::Trigger(TriggerCommands::Redis(cmd)) => cmd.run().await,
             Self::Trigger(TriggerCommands::HelpArgsOnly(cmd)) => cmd.run().await,
@@ -162,6 +203,18 @@ impl SpinApp {
             Self::External(cmd) => execute_external_subcommand(cmd, app).await,
             Self::Watch(cmd) => cmd.run().await,
             Self::Doctor(cmd) => cmd.run().await,
+            Self::Ctl(cmd) => cmd.run().await,
+            Self::Data(cmd) => cmd.run().await,
+            Self::Stats(cmd) => cmd.run().await,
+            Self::Bench(cmd) => cmd.run().await,
+            Self::Replay(cmd) => cmd.run().await,
+            Self::Inspect(cmd) => cmd.run().await,
+            Self::Migrate(cmd) => cmd.run().await,
+            Self::Sdk(cmd) => cmd.run().await,
+            Self::Compose(cmd) => cmd.run().await,
+            Self::Conformance(cmd) => cmd.run().await,
+            Self::Test(cmd) => cmd.run().await,
+            Self::Service(cmd) => cmd.run().await,
         }
     }
 }
@@ -1,21 +1,47 @@
 //! Commands for the Spin CLI.
 
+/// Command for packaging an application as a single file.
+pub mod archive;
+/// Command for driving load against a running application.
+pub mod bench;
 /// Commands for building Spin applications.
 pub mod build;
 /// Commands for publishing applications to the Fermyon Platform.
 pub mod cloud;
+/// Commands for running several applications together.
+pub mod compose;
+/// Commands for checking that a host implements the Spin interfaces.
+pub mod conformance;
+/// Commands for controlling a running application.
+pub mod ctl;
+/// Commands for working with an application's local data.
+pub mod data;
 /// Command for running the Spin Doctor.
 pub mod doctor;
 /// Commands for external subcommands (i.e. plugins)
 pub mod external;
+/// Command for inspecting Wasm components.
+pub mod inspect;
+/// Command for applying database migrations.
+pub mod migrate;
 /// Command for creating a new application.
 pub mod new;
 /// Command for adding a plugin to Spin
 pub mod plugins;
 /// Commands for working with OCI registries.
 pub mod registry;
+/// Command for re-running a failed request from a replay bundle.
+pub mod replay;
+/// Commands for generating guest SDKs for other languages.
+pub mod sdk;
+/// Commands for running applications as managed services.
+pub mod service;
+/// Command for showing the resources used by a running application.
+pub mod stats;
 /// Commands for working with templates.
 pub mod templates;
+/// Commands for testing applications.
+pub mod test;
 /// Commands for starting the runtime.
 pub mod up;
 /// Command for rebuilding and restarting a Spin app when files change.
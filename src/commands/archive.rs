@@ -0,0 +1,55 @@
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use clap::Parser;
+use spin_loader::FilesMountStrategy;
+
+use crate::opts::{ALWAYS_BUILD_ENV, APP_MANIFEST_FILE_OPT, DEFAULT_MANIFEST_FILE};
+
+/// Package an application as a single file.
+#[derive(Parser, Debug)]
+#[clap(
+    about = "Package the application as a single file which `spin up --from` can run without network access"
+)]
+pub struct ArchiveCommand {
+    /// The application to package. This may be a manifest (spin.toml) file,
+    /// or a directory containing a spin.toml file.
+    /// If omitted, it defaults to "spin.toml".
+    #[clap(
+        name = APP_MANIFEST_FILE_OPT,
+        short = 'f',
+        long = "from",
+        alias = "file",
+        default_value = DEFAULT_MANIFEST_FILE
+    )]
+    pub app_source: PathBuf,
+
+    /// The file to write the archive to, e.g. `app.spin`.
+    #[clap(short = 'o', long = "output")]
+    pub output: PathBuf,
+
+    /// Specifies to perform `spin build` before packaging the application.
+    #[clap(long, takes_value = false, env = ALWAYS_BUILD_ENV)]
+    pub build: bool,
+}
+
+impl ArchiveCommand {
+    pub async fn run(self) -> Result<()> {
+        let manifest_file = spin_common::paths::resolve_manifest_file_path(&self.app_source)?;
+        if self.build {
+            spin_build::build(&manifest_file, &[]).await?;
+        }
+
+        let working_dir = tempfile::tempdir()?;
+        let locked_app = spin_loader::from_file(
+            &manifest_file,
+            FilesMountStrategy::Copy(working_dir.path().into()),
+        )
+        .await?;
+        spin_archive::write(locked_app, &manifest_file, &self.output)
+            .with_context(|| format!("Failed to write archive {:?}", self.output))?;
+
+        terminal::step!("Archived", "{:?}", self.output);
+        Ok(())
+    }
+}
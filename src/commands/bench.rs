@@ -0,0 +1,369 @@
+use std::{collections::BTreeMap, path::PathBuf, time::Duration};
+
+use anyhow::{bail, Context, Result};
+use clap::Parser;
+use serde::Serialize;
+use spin_trigger_http::SERVER_TIMING_REQUEST_HEADER;
+use tokio::time::{Instant, MissedTickBehavior};
+
+/// The phases of an invocation reported by the trigger in `Server-Timing`.
+const PHASES: &[&str] = &["queue", "instantiate", "execute", "outbound"];
+
+/// Drive load against a locally running application, and report how long
+/// requests took and where that time went.
+#[derive(Parser, Debug)]
+#[clap(about = "Drive load against a running application and report its latency")]
+pub struct BenchCommand {
+    /// The route to request, e.g. `/api`.
+    #[clap(long = "route", default_value = "/")]
+    pub route: String,
+
+    /// The address the application is listening on.
+    #[clap(long = "address", default_value = "http://127.0.0.1:3000")]
+    pub address: String,
+
+    /// The request method.
+    #[clap(short = 'X', long = "method", default_value = "GET")]
+    pub method: String,
+
+    /// A header to send with each request, as `NAME: VALUE`. Can be used
+    /// multiple times.
+    #[clap(short = 'H', long = "header", multiple_occurrences = true)]
+    pub headers: Vec<String>,
+
+    /// A file whose contents are sent as each request's body.
+    #[clap(long = "body")]
+    pub body: Option<PathBuf>,
+
+    /// The number of requests to send per second. Requests are sent at this
+    /// rate whether or not earlier ones have finished.
+    #[clap(long = "rps", default_value = "100")]
+    pub rps: u32,
+
+    /// How long to send requests for, e.g. `30s`, `500ms` or `2m`.
+    #[clap(long = "duration", default_value = "10s", parse(try_from_str = parse_duration))]
+    pub duration: Duration,
+
+    /// Write the results as JSON to the given file, or to stdout if `-`, for
+    /// comparison against earlier runs.
+    #[clap(long = "json")]
+    pub json: Option<PathBuf>,
+}
+
+impl BenchCommand {
+    pub async fn run(self) -> Result<()> {
+        if self.rps == 0 {
+            bail!("--rps must be at least 1");
+        }
+        let url = format!(
+            "{}/{}",
+            self.address.trim_end_matches('/'),
+            self.route.trim_start_matches('/')
+        );
+        let url = reqwest::Url::parse(&url).with_context(|| format!("Invalid URL {url}"))?;
+        let method = reqwest::Method::from_bytes(self.method.to_ascii_uppercase().as_bytes())
+            .with_context(|| format!("Invalid method {}", self.method))?;
+        let mut headers = reqwest::header::HeaderMap::new();
+        for header in &self.headers {
+            let (name, value) = header
+                .split_once(':')
+                .with_context(|| format!("Header {header:?} is not `NAME: VALUE`"))?;
+            headers.insert(
+                reqwest::header::HeaderName::from_bytes(name.trim().as_bytes())?,
+                value.trim().parse()?,
+            );
+        }
+        headers.insert(SERVER_TIMING_REQUEST_HEADER, "1".parse()?);
+        let body = match &self.body {
+            Some(path) => std::fs::read(path)
+                .with_context(|| format!("Failed to read request body {path:?}"))?,
+            None => vec![],
+        };
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(30))
+            .build()?;
+
+        let total = (self.duration.as_secs_f64() * self.rps as f64).ceil() as u64;
+        let progress = indicatif::ProgressBar::new(total);
+        progress.set_style(
+            indicatif::ProgressStyle::with_template("{spinner} Sent {pos}/{len} requests to {msg}")
+                .unwrap(),
+        );
+        progress.set_message(url.to_string());
+
+        let mut interval = tokio::time::interval(Duration::from_secs_f64(1.0 / self.rps as f64));
+        // Ticks missed because the timer is coarser than the period fire at
+        // once, so that the target rate is kept on average.
+        interval.set_missed_tick_behavior(MissedTickBehavior::Burst);
+        let started = Instant::now();
+        let mut requests = tokio::task::JoinSet::new();
+        for _ in 0..total {
+            interval.tick().await;
+            let request = client
+                .request(method.clone(), url.clone())
+                .headers(headers.clone())
+                .body(body.clone());
+            requests.spawn(send(request));
+            progress.inc(1);
+        }
+        progress.finish_and_clear();
+        let sending_time = started.elapsed();
+
+        let mut samples = Vec::with_capacity(total as usize);
+        while let Some(sample) = requests.join_next().await {
+            samples.push(sample?);
+        }
+        let results = BenchResults::new(&self, url.as_str(), sending_time, &samples);
+
+        match self.json.as_deref() {
+            Some(path) if path.as_os_str() == "-" => {
+                println!("{}", serde_json::to_string_pretty(&results)?)
+            }
+            Some(path) => {
+                std::fs::write(path, serde_json::to_vec_pretty(&results)?)
+                    .with_context(|| format!("Failed to write results to {path:?}"))?;
+                results.print();
+            }
+            None => results.print(),
+        }
+        Ok(())
+    }
+}
+
+/// The outcome of one request.
+#[derive(Debug, Default)]
+struct Sample {
+    latency: Duration,
+    /// `None` if the request failed without a response.
+    status: Option<u16>,
+    /// Phase name -> duration, as reported by the trigger.
+    phases: BTreeMap<String, Duration>,
+}
+
+async fn send(request: reqwest::RequestBuilder) -> Sample {
+    let started = Instant::now();
+    let response = request.send().await;
+    let mut sample = Sample::default();
+    if let Ok(response) = response {
+        sample.status = Some(response.status().as_u16());
+        if let Some(timing) = response.headers().get("server-timing") {
+            sample.phases = parse_server_timing(timing.to_str().unwrap_or_default());
+        }
+        // The request isn't done until its body has arrived.
+        if response.bytes().await.is_err() {
+            sample.status = None;
+        }
+    }
+    sample.latency = started.elapsed();
+    sample
+}
+
+/// Parses a `Server-Timing` header value such as
+/// `queue;dur=0.1, execute;dur=12.5`.
+fn parse_server_timing(value: &str) -> BTreeMap<String, Duration> {
+    value
+        .split(',')
+        .filter_map(|metric| {
+            let mut params = metric.split(';').map(str::trim);
+            let name = params.next()?;
+            let ms: f64 = params.find_map(|p| p.strip_prefix("dur="))?.parse().ok()?;
+            (ms >= 0.0).then(|| {
+                (
+                    name.to_owned(),
+                    Duration::from_nanos((ms * 1e6).round() as u64),
+                )
+            })
+        })
+        .collect()
+}
+
+#[derive(Debug, Serialize)]
+struct BenchResults {
+    url: String,
+    method: String,
+    target_rps: u32,
+    achieved_rps: f64,
+    duration_secs: f64,
+    requests: u64,
+    /// Requests which failed without a response, or with a 5xx status.
+    errors: u64,
+    status_codes: BTreeMap<String, u64>,
+    /// Phase name, or "total" -> latency percentiles.
+    latency_ms: BTreeMap<String, Percentiles>,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+struct Percentiles {
+    mean: f64,
+    p50: f64,
+    p90: f64,
+    p99: f64,
+    max: f64,
+}
+
+impl Percentiles {
+    fn of(mut durations: Vec<Duration>) -> Option<Self> {
+        if durations.is_empty() {
+            return None;
+        }
+        durations.sort();
+        let ms = |d: Duration| d.as_micros() as f64 / 1000.0;
+        let percentile = |p: f64| {
+            // Nearest rank.
+            let rank = (p / 100.0 * durations.len() as f64).ceil() as usize;
+            ms(durations[rank.clamp(1, durations.len()) - 1])
+        };
+        let total: Duration = durations.iter().sum();
+        Some(Self {
+            mean: ms(total) / durations.len() as f64,
+            p50: percentile(50.0),
+            p90: percentile(90.0),
+            p99: percentile(99.0),
+            max: ms(*durations.last().unwrap()),
+        })
+    }
+}
+
+impl BenchResults {
+    fn new(cmd: &BenchCommand, url: &str, sending_time: Duration, samples: &[Sample]) -> Self {
+        let mut status_codes = BTreeMap::new();
+        let mut errors = 0;
+        for sample in samples {
+            let status = match sample.status {
+                Some(status) => status.to_string(),
+                None => "error".to_owned(),
+            };
+            *status_codes.entry(status).or_default() += 1;
+            if sample.status.map_or(true, |status| status >= 500) {
+                errors += 1;
+            }
+        }
+
+        let mut latency_ms = BTreeMap::new();
+        if let Some(total) = Percentiles::of(samples.iter().map(|s| s.latency).collect()) {
+            latency_ms.insert("total".to_owned(), total);
+        }
+        for phase in PHASES {
+            let durations = samples.iter().filter_map(|s| s.phases.get(*phase).copied());
+            if let Some(percentiles) = Percentiles::of(durations.collect()) {
+                latency_ms.insert(phase.to_string(), percentiles);
+            }
+        }
+
+        Self {
+            url: url.to_owned(),
+            method: cmd.method.to_ascii_uppercase(),
+            target_rps: cmd.rps,
+            achieved_rps: samples.len() as f64 / sending_time.as_secs_f64().max(f64::EPSILON),
+            duration_secs: sending_time.as_secs_f64(),
+            requests: samples.len() as u64,
+            errors,
+            status_codes,
+            latency_ms,
+        }
+    }
+
+    fn print(&self) {
+        println!(
+            "Sent {} requests in {:.1}s ({:.1}/s, target {}/s); {} errors",
+            self.requests, self.duration_secs, self.achieved_rps, self.target_rps, self.errors
+        );
+        let statuses: Vec<_> = self
+            .status_codes
+            .iter()
+            .map(|(status, count)| format!("{status}: {count}"))
+            .collect();
+        println!("Responses: {}", statuses.join(", "));
+        println!();
+        println!(
+            "{:12}  {:>10}  {:>10}  {:>10}  {:>10}  {:>10}",
+            "PHASE", "MEAN", "P50", "P90", "P99", "MAX"
+        );
+        let rows = std::iter::once("total").chain(PHASES.iter().copied());
+        for phase in rows {
+            let Some(p) = self.latency_ms.get(phase) else {
+                continue;
+            };
+            println!(
+                "{phase:12}  {:>10}  {:>10}  {:>10}  {:>10}  {:>10}",
+                format_ms(p.mean),
+                format_ms(p.p50),
+                format_ms(p.p90),
+                format_ms(p.p99),
+                format_ms(p.max)
+            );
+        }
+        if self.requests > 0 && self.latency_ms.len() == 1 {
+            println!();
+            println!("The application did not report where the time of its requests went; this needs an HTTP app run by this version of Spin.");
+        }
+    }
+}
+
+fn format_ms(ms: f64) -> String {
+    if ms >= 1000.0 {
+        format!("{:.2}s", ms / 1000.0)
+    } else {
+        format!("{ms:.2}ms")
+    }
+}
+
+/// Parses a duration such as `30s`, `500ms` or `2m`; a bare number is in
+/// seconds.
+fn parse_duration(value: &str) -> Result<Duration> {
+    let value = value.trim();
+    let split = value
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let number: f64 = number
+        .parse()
+        .with_context(|| format!("Invalid duration {value:?}"))?;
+    let secs = match unit {
+        "ms" => number / 1000.0,
+        "" | "s" => number,
+        "m" => number * 60.0,
+        "h" => number * 3600.0,
+        _ => bail!("Invalid duration {value:?}: the unit must be ms, s, m or h"),
+    };
+    if secs <= 0.0 {
+        bail!("The duration must be greater than zero");
+    }
+    Ok(Duration::from_secs_f64(secs))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn durations_are_parsed() {
+        assert_eq!(Duration::from_secs(30), parse_duration("30s").unwrap());
+        assert_eq!(Duration::from_millis(500), parse_duration("500ms").unwrap());
+        assert_eq!(Duration::from_secs(120), parse_duration("2m").unwrap());
+        assert_eq!(Duration::from_secs(5), parse_duration("5").unwrap());
+        parse_duration("5 days").unwrap_err();
+        parse_duration("0s").unwrap_err();
+    }
+
+    #[test]
+    fn server_timing_is_parsed() {
+        let phases = parse_server_timing("queue;dur=0.500, execute;dur=12, cache;desc=\"hit\"");
+        assert_eq!(Some(&Duration::from_micros(500)), phases.get("queue"));
+        assert_eq!(Some(&Duration::from_millis(12)), phases.get("execute"));
+        assert!(!phases.contains_key("cache"));
+    }
+
+    #[test]
+    fn percentiles_use_the_nearest_rank() {
+        let durations = (1..=100).map(Duration::from_millis).collect();
+        let p = Percentiles::of(durations).unwrap();
+        assert_eq!(50.0, p.p50);
+        assert_eq!(90.0, p.p90);
+        assert_eq!(99.0, p.p99);
+        assert_eq!(100.0, p.max);
+        assert_eq!(50.5, p.mean);
+
+        assert!(Percentiles::of(vec![]).is_none());
+    }
+}
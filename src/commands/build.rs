@@ -27,6 +27,19 @@ pub struct BuildCommand {
     #[clap(short = 'c', long, multiple = true)]
     pub component_id: Vec<String>,
 
+    /// After building, pre-initialize each component which exports a
+    /// `wizer.initialize` function: run it, and snapshot the component's
+    /// memory into its Wasm file, so that instances start already
+    /// initialized. Requires `wizer` (`cargo install wizer --all-features`).
+    #[clap(long = "pre-init")]
+    pub pre_init: bool,
+
+    /// After building, report the size of each component, broken down by
+    /// section and by the crate its code comes from, with suggestions for
+    /// making it smaller.
+    #[clap(long = "size-report")]
+    pub size_report: bool,
+
     /// Run the application after building.
     #[clap(name = BUILD_UP_OPT, short = 'u', long = "up")]
     pub up: bool,
@@ -39,6 +52,12 @@ impl BuildCommand {
     pub async fn run(self) -> Result<()> {
         let manifest_file = spin_common::paths::resolve_manifest_file_path(&self.app_source)?;
         spin_build::build(&manifest_file, &self.component_id).await?;
+        if self.pre_init {
+            spin_build::pre_initialize(&manifest_file, &self.component_id).await?;
+        }
+        if self.size_report {
+            spin_build::size_report(&manifest_file, &self.component_id).await?;
+        }
 
         if self.up {
             let mut cmd = UpCommand::parse_from(
@@ -0,0 +1,273 @@
+use std::{
+    collections::BTreeMap,
+    ffi::OsString,
+    path::{Path, PathBuf},
+    process::{Child, Command},
+    time::Duration,
+};
+
+use anyhow::{bail, Context, Result};
+use clap::{Parser, Subcommand};
+use serde::Deserialize;
+
+use super::up::dev_services::{DevService, DevServices};
+
+/// The compose file used if none is given.
+const DEFAULT_COMPOSE_FILE: &str = "spin-compose.toml";
+
+/// Commands for running several applications together.
+#[derive(Subcommand, Debug)]
+pub enum ComposeCommands {
+    /// Run each application in a compose file, together with the services
+    /// they depend on, until one exits or Spin is interrupted.
+    Up(ComposeUpCommand),
+}
+
+impl ComposeCommands {
+    pub async fn run(self) -> Result<()> {
+        match self {
+            Self::Up(cmd) => cmd.run().await,
+        }
+    }
+}
+
+#[derive(Parser, Debug)]
+pub struct ComposeUpCommand {
+    /// The compose file describing the applications.
+    #[clap(short = 'f', long = "file", default_value = DEFAULT_COMPOSE_FILE)]
+    pub file: PathBuf,
+}
+
+/// A `spin-compose.toml` file.
+///
+/// ```toml
+/// services = ["postgres"]
+///
+/// [variables]
+/// log_level = "debug"
+///
+/// [apps.frontend]
+/// source = "frontend"
+/// listen = "127.0.0.1:3000"
+/// variables = { api_url = "http://127.0.0.1:3001" }
+///
+/// [apps.api]
+/// source = "api/spin.toml"
+/// listen = "127.0.0.1:3001"
+/// ```
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ComposeFile {
+    /// Services started for the applications, as for `spin up --with`.
+    #[serde(default)]
+    services: Vec<DevService>,
+    /// Variable values given to every application.
+    #[serde(default)]
+    variables: BTreeMap<String, String>,
+    apps: BTreeMap<String, ComposeApp>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ComposeApp {
+    /// The application, as for `spin up --from`. Paths are relative to the
+    /// compose file.
+    source: String,
+    /// The address an HTTP application listens on.
+    #[serde(default)]
+    listen: Option<String>,
+    /// Variable values given to this application, overriding the shared
+    /// ones.
+    #[serde(default)]
+    variables: BTreeMap<String, String>,
+    /// Any other arguments to pass to `spin up`.
+    #[serde(default)]
+    args: Vec<String>,
+}
+
+impl ComposeUpCommand {
+    pub async fn run(self) -> Result<()> {
+        let contents = std::fs::read_to_string(&self.file)
+            .with_context(|| format!("Couldn't read compose file {:?}", self.file))?;
+        let compose: ComposeFile = toml::from_str(&contents)
+            .with_context(|| format!("Invalid compose file {:?}", self.file))?;
+        if compose.apps.is_empty() {
+            bail!("Compose file {:?} doesn't list any apps", self.file);
+        }
+        let base_dir = self.file.parent().unwrap_or(Path::new("."));
+
+        // Held until the applications exit, when the services are removed.
+        let services = DevServices::start(&compose.services)?;
+
+        let spin = std::env::current_exe()?;
+        let mut children = vec![];
+        for (name, app) in &compose.apps {
+            let (args, env) = up_command(base_dir, app, &compose.variables, &services);
+            let child = Command::new(&spin)
+                .args(args)
+                .envs(env)
+                .spawn()
+                .with_context(|| format!("Failed to start app {name:?}"))?;
+            terminal::step!("Started", "app {name} (pid {})", child.id());
+            children.push((name.as_str(), child));
+        }
+
+        // Stop every application if `spin compose` itself is interrupted.
+        #[cfg(not(windows))]
+        {
+            let pids: Vec<_> = children.iter().map(|(_, child)| child.id()).collect();
+            ctrlc::set_handler(move || {
+                for pid in &pids {
+                    let pid = nix::unistd::Pid::from_raw(*pid as i32);
+                    _ = nix::sys::signal::kill(pid, nix::sys::signal::SIGTERM);
+                }
+            })?;
+        }
+
+        // When one application exits, stop the others.
+        let (name, status) = loop {
+            if let Some(exited) = first_exited(&mut children)? {
+                break exited;
+            }
+            std::thread::sleep(Duration::from_millis(200));
+        };
+        for (_, child) in &mut children {
+            stop(child);
+        }
+        if status.success() {
+            Ok(())
+        } else {
+            terminal::error!("App {name} exited with {status}");
+            Err(crate::subprocess::ExitStatusError::new(status).into())
+        }
+    }
+}
+
+/// Returns the arguments and environment of the `spin up` command which runs
+/// `app`.
+fn up_command(
+    base_dir: &Path,
+    app: &ComposeApp,
+    shared_variables: &BTreeMap<String, String>,
+    services: &DevServices,
+) -> (Vec<OsString>, Vec<(String, String)>) {
+    let local_source = base_dir.join(&app.source);
+    let source = if local_source.exists() {
+        local_source.into_os_string()
+    } else {
+        app.source.clone().into()
+    };
+
+    let mut args: Vec<OsString> = vec!["up".into(), "--from".into(), source];
+    args.extend(services.up_args().into_iter().map(Into::into));
+    args.extend(app.args.iter().map(Into::into));
+    if let Some(listen) = &app.listen {
+        args.extend(["--listen".into(), listen.into()]);
+    }
+
+    // Later values take precedence.
+    let variables: BTreeMap<_, _> = services
+        .variables()
+        .chain(shared_variables.clone())
+        .chain(app.variables.clone())
+        .collect();
+    let env = variables
+        .into_iter()
+        .map(|(name, value)| (format!("SPIN_VARIABLE_{}", name.to_uppercase()), value))
+        .collect();
+    (args, env)
+}
+
+/// Returns the first of `children` to have exited, if any has.
+fn first_exited<'a>(
+    children: &mut [(&'a str, Child)],
+) -> Result<Option<(&'a str, std::process::ExitStatus)>> {
+    for (name, child) in children {
+        if let Some(status) = child.try_wait()? {
+            return Ok(Some((name, status)));
+        }
+    }
+    Ok(None)
+}
+
+fn stop(child: &mut Child) {
+    #[cfg(not(windows))]
+    {
+        let pid = nix::unistd::Pid::from_raw(child.id() as i32);
+        if nix::sys::signal::kill(pid, nix::sys::signal::SIGTERM).is_ok() {
+            _ = child.wait();
+            return;
+        }
+    }
+    _ = child.kill();
+    _ = child.wait();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COMPOSE_FILE: &str = r#"
+        [variables]
+        log_level = "debug"
+        api_url = "http://127.0.0.1:3001"
+
+        [apps.frontend]
+        source = "frontend"
+        listen = "127.0.0.1:3000"
+        variables = { log_level = "trace" }
+
+        [apps.api]
+        source = "ghcr.io/example/api:v1"
+        args = ["--build"]
+    "#;
+
+    #[test]
+    fn apps_are_run_with_their_variables() {
+        let compose: ComposeFile = toml::from_str(COMPOSE_FILE).unwrap();
+        let services = DevServices::default();
+        let base_dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(base_dir.path().join("frontend")).unwrap();
+
+        let (args, env) = up_command(
+            base_dir.path(),
+            &compose.apps["frontend"],
+            &compose.variables,
+            &services,
+        );
+        let frontend = base_dir.path().join("frontend").into_os_string();
+        assert_eq!(
+            vec![
+                "up".into(),
+                "--from".into(),
+                frontend,
+                "--listen".into(),
+                "127.0.0.1:3000".into()
+            ],
+            args
+        );
+        assert!(env.contains(&("SPIN_VARIABLE_LOG_LEVEL".into(), "trace".into())));
+        assert!(env.contains(&(
+            "SPIN_VARIABLE_API_URL".into(),
+            "http://127.0.0.1:3001".into()
+        )));
+
+        let (args, env) = up_command(
+            base_dir.path(),
+            &compose.apps["api"],
+            &compose.variables,
+            &services,
+        );
+        assert_eq!(
+            vec!["up", "--from", "ghcr.io/example/api:v1", "--build"],
+            args
+        );
+        assert!(env.contains(&("SPIN_VARIABLE_LOG_LEVEL".into(), "debug".into())));
+    }
+
+    #[test]
+    fn unknown_fields_are_rejected() {
+        let file = "[apps.api]\nsource = \"api\"\nport = 3000\n";
+        assert!(toml::from_str::<ComposeFile>(file).is_err());
+    }
+}
@@ -0,0 +1,372 @@
+use std::{
+    net::{Ipv4Addr, SocketAddr, TcpListener},
+    path::PathBuf,
+    process::{Child, Command},
+    time::{Duration, Instant},
+};
+
+use anyhow::{bail, Context, Result};
+use clap::{Parser, Subcommand, ValueEnum};
+use reqwest::StatusCode;
+
+/// The header giving a suite's component the database to test against.
+const DB_URL_HEADER: &str = "x-conformance-db-url";
+/// The header distinguishing the requests of a concurrency case.
+const REQUEST_ID_HEADER: &str = "x-conformance-request-id";
+/// The case which the runner sends many requests to at once.
+const CONCURRENCY_CASE: &str = "/concurrency";
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Commands for checking that a host implements the Spin interfaces.
+#[derive(Subcommand, Debug)]
+pub enum ConformanceCommands {
+    /// Run a conformance suite against a host.
+    Run(RunCommand),
+}
+
+impl ConformanceCommands {
+    pub async fn run(self) -> Result<()> {
+        match self {
+            Self::Run(cmd) => cmd.run().await,
+        }
+    }
+}
+
+/// The interfaces which have conformance suites.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Interface {
+    /// `fermyon:spin/postgres`
+    Pg,
+}
+
+impl Interface {
+    fn suite_name(&self) -> &'static str {
+        match self {
+            Self::Pg => "pg",
+        }
+    }
+}
+
+#[derive(Parser, Debug)]
+pub struct RunCommand {
+    /// The interface to check.
+    #[clap(value_enum, long = "interface")]
+    pub interface: Interface,
+
+    /// The database the suite should use. Only temporary tables are created.
+    #[clap(long = "db-url")]
+    pub db_url: String,
+
+    /// The directory containing the suites. Each suite is a Spin application
+    /// in a subdirectory named after its interface.
+    #[clap(long = "suite-dir", default_value = "conformance")]
+    pub suite_dir: PathBuf,
+
+    /// Run the suite against a host which is already serving the suite's
+    /// application at this URL, instead of starting one.
+    #[clap(long = "url", conflicts_with = "host-command")]
+    pub url: Option<String>,
+
+    /// The command which starts the host being checked. `{manifest}` is
+    /// replaced by the suite's manifest path and `{listen}` by the address
+    /// to serve on. Defaults to running this Spin.
+    #[clap(long = "host-command")]
+    pub host_command: Option<String>,
+
+    /// The number of requests to send at once in the concurrency case.
+    #[clap(long = "concurrency", default_value = "16")]
+    pub concurrency: usize,
+}
+
+impl RunCommand {
+    pub async fn run(self) -> Result<()> {
+        let manifest = self
+            .suite_dir
+            .join(self.interface.suite_name())
+            .join("spin.toml");
+        if !manifest.exists() {
+            bail!("No conformance suite found at {manifest:?}. Use --suite-dir to give the directory containing the suites.");
+        }
+
+        let mut host = None;
+        let base_url = match &self.url {
+            Some(url) => url.trim_end_matches('/').to_owned(),
+            None => {
+                build_suite(&manifest)?;
+                let listen = free_local_address()?;
+                host = Some(HostProcess::start(
+                    self.host_command.as_deref(),
+                    &manifest,
+                    listen,
+                )?);
+                format!("http://{listen}")
+            }
+        };
+
+        let client = reqwest::Client::new();
+        let cases = wait_for_cases(&client, &base_url, host.as_mut()).await?;
+        let mut failures = 0;
+        for case in &cases {
+            let count = if case == CONCURRENCY_CASE {
+                self.concurrency
+            } else {
+                1
+            };
+            match self.run_case(&client, &base_url, case, count).await {
+                Ok(()) => println!("PASS {case}"),
+                Err(e) => {
+                    failures += 1;
+                    println!("FAIL {case}: {e:#}");
+                }
+            }
+        }
+
+        println!(
+            "\n{} of {} cases passed",
+            cases.len() - failures,
+            cases.len()
+        );
+        if failures > 0 {
+            bail!("{failures} conformance case(s) failed");
+        }
+        Ok(())
+    }
+
+    /// Sends `count` requests to the case at once, failing if any does.
+    async fn run_case(
+        &self,
+        client: &reqwest::Client,
+        base_url: &str,
+        case: &str,
+        count: usize,
+    ) -> Result<()> {
+        let requests = (0..count).map(|id| {
+            client
+                .get(format!("{base_url}{case}"))
+                .header(DB_URL_HEADER, &self.db_url)
+                .header(REQUEST_ID_HEADER, id.to_string())
+                .send()
+        });
+        for response in futures::future::join_all(requests).await {
+            let response = response.context("request failed")?;
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            if status != StatusCode::OK {
+                bail!("{status}: {body}");
+            }
+        }
+        Ok(())
+    }
+}
+
+fn build_suite(manifest: &std::path::Path) -> Result<()> {
+    let status = Command::new(std::env::current_exe()?)
+        .arg("build")
+        .arg("-f")
+        .arg(manifest)
+        .status()
+        .context("Failed to run `spin build`")?;
+    if !status.success() {
+        bail!("Failed to build the conformance suite {manifest:?}");
+    }
+    Ok(())
+}
+
+pub(super) fn free_local_address() -> Result<SocketAddr> {
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0))?;
+    Ok(listener.local_addr()?)
+}
+
+/// Polls the suite's list of cases until the host is serving it.
+async fn wait_for_cases(
+    client: &reqwest::Client,
+    base_url: &str,
+    mut host: Option<&mut HostProcess>,
+) -> Result<Vec<String>> {
+    let deadline = Instant::now() + STARTUP_TIMEOUT;
+    loop {
+        if let Some(host) = host.as_deref_mut() {
+            if let Some(status) = host.0.try_wait()? {
+                bail!("The host exited before serving the suite: {status}");
+            }
+        }
+        match client.get(format!("{base_url}/cases")).send().await {
+            Ok(response) if response.status().is_success() => {
+                let cases = response.text().await?;
+                return Ok(cases.lines().map(str::to_owned).collect());
+            }
+            Ok(response) => bail!(
+                "{base_url} is not serving a conformance suite: /cases returned {}",
+                response.status()
+            ),
+            Err(_) if Instant::now() < deadline => {
+                tokio::time::sleep(Duration::from_millis(250)).await;
+            }
+            Err(e) => return Err(e).context(format!("{base_url} did not start serving")),
+        }
+    }
+}
+
+/// The host being checked, which is stopped when dropped.
+pub(super) struct HostProcess(pub(super) Child);
+
+impl HostProcess {
+    pub(super) fn start(
+        host_command: Option<&str>,
+        manifest: &std::path::Path,
+        listen: SocketAddr,
+    ) -> Result<Self> {
+        let mut command = host_command_line(host_command, manifest, listen)?;
+        let child = command.spawn().context("Failed to start the host")?;
+        Ok(Self(child))
+    }
+}
+
+/// The command which starts the host, from the `--host-command` template or
+/// else running this Spin.
+fn host_command_line(
+    host_command: Option<&str>,
+    manifest: &std::path::Path,
+    listen: SocketAddr,
+) -> Result<Command> {
+    let manifest = manifest.display().to_string();
+    let listen = listen.to_string();
+    let command = match host_command {
+        Some(template) => {
+            let mut args = template.split_whitespace().map(|arg| {
+                arg.replace("{manifest}", &manifest)
+                    .replace("{listen}", &listen)
+            });
+            let program = args.next().context("--host-command is empty")?;
+            let mut command = Command::new(program);
+            command.args(args);
+            command
+        }
+        None => {
+            let mut command = Command::new(std::env::current_exe()?);
+            command.args(["up", "-f", &manifest, "--listen", &listen]);
+            command
+        }
+    };
+    Ok(command)
+}
+
+impl Drop for HostProcess {
+    fn drop(&mut self) {
+        // `spin up` stops its trigger process on SIGTERM, but not on SIGKILL.
+        #[cfg(not(windows))]
+        {
+            let pid = nix::unistd::Pid::from_raw(self.0.id() as i32);
+            _ = nix::sys::signal::kill(pid, nix::sys::signal::SIGTERM);
+        }
+        #[cfg(windows)]
+        {
+            _ = self.0.kill();
+        }
+        _ = self.0.wait();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    use super::*;
+
+    /// Serves a canned suite: `/cases` lists a passing and a failing case.
+    async fn stub_suite() -> String {
+        let listener = tokio::net::TcpListener::bind((Ipv4Addr::LOCALHOST, 0))
+            .await
+            .unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            loop {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                tokio::spawn(async move {
+                    let mut request = vec![0; 4096];
+                    let n = stream.read(&mut request).await.unwrap();
+                    let request = String::from_utf8_lossy(&request[..n]).into_owned();
+                    let path = request.split_whitespace().nth(1).unwrap_or_default();
+                    let (status, body) = match path {
+                        "/cases" => ("200 OK", "/select\n/fail\n".to_owned()),
+                        "/select" if request.contains(DB_URL_HEADER) => ("200 OK", String::new()),
+                        _ => ("500 Internal Server Error", format!("boom at {path}")),
+                    };
+                    let response = format!(
+                        "HTTP/1.1 {status}\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{body}",
+                        body.len()
+                    );
+                    stream.write_all(response.as_bytes()).await.unwrap();
+                });
+            }
+        });
+        format!("http://{addr}")
+    }
+
+    fn run_command(url: &str) -> RunCommand {
+        RunCommand {
+            interface: Interface::Pg,
+            db_url: "postgres://localhost/test".to_owned(),
+            suite_dir: "conformance".into(),
+            url: Some(url.to_owned()),
+            host_command: None,
+            concurrency: 4,
+        }
+    }
+
+    #[tokio::test]
+    async fn cases_are_listed_by_the_suite() {
+        let url = stub_suite().await;
+        let client = reqwest::Client::new();
+        let cases = wait_for_cases(&client, &url, None).await.unwrap();
+        assert_eq!(vec!["/select", "/fail"], cases);
+    }
+
+    #[tokio::test]
+    async fn cases_pass_only_with_an_ok_response() {
+        let url = stub_suite().await;
+        let client = reqwest::Client::new();
+        let cmd = run_command(&url);
+
+        cmd.run_case(&client, &url, "/select", 4).await.unwrap();
+        let err = cmd
+            .run_case(&client, &url, "/fail", 1)
+            .await
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("500"), "{err}");
+        assert!(err.contains("boom at /fail"), "{err}");
+    }
+
+    #[test]
+    fn host_commands_are_templated() {
+        let listen: SocketAddr = "127.0.0.1:3000".parse().unwrap();
+        let manifest = std::path::Path::new("conformance/pg/spin.toml");
+
+        let command = host_command_line(
+            Some("my-host serve {manifest} --addr={listen}"),
+            manifest,
+            listen,
+        )
+        .unwrap();
+        assert_eq!("my-host", command.get_program());
+        assert_eq!(
+            vec!["serve", "conformance/pg/spin.toml", "--addr=127.0.0.1:3000"],
+            command.get_args().collect::<Vec<_>>()
+        );
+
+        let command = host_command_line(None, manifest, listen).unwrap();
+        assert_eq!(
+            vec![
+                "up",
+                "-f",
+                "conformance/pg/spin.toml",
+                "--listen",
+                "127.0.0.1:3000"
+            ],
+            command.get_args().collect::<Vec<_>>()
+        );
+
+        host_command_line(Some("  "), manifest, listen).unwrap_err();
+    }
+}
@@ -0,0 +1,162 @@
+use std::path::PathBuf;
+
+use anyhow::{bail, Context, Result};
+use clap::{Parser, Subcommand};
+use spin_trigger::admin::{AdminRequest, AdminResponse};
+
+pub(crate) const ADMIN_SOCKET_ENV: &str = "SPIN_ADMIN_SOCKET";
+
+/// Control a running Spin application.
+#[derive(Parser, Debug)]
+#[clap(about = "Control a running Spin application")]
+pub struct CtlCommand {
+    /// The admin socket of the running application, as passed to
+    /// `spin up --admin-socket`.
+    #[clap(short = 's', long = "socket", env = ADMIN_SOCKET_ENV)]
+    pub socket: PathBuf,
+
+    #[clap(subcommand)]
+    pub command: CtlCommands,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum CtlCommands {
+    /// List the running application and its components.
+    Components,
+    /// Show the current runtime configuration.
+    Config,
+    /// Show a snapshot of the application's metrics.
+    Metrics,
+    /// Change the log filter, e.g. `debug` or `spin_trigger=trace`.
+    LogLevel {
+        /// The new log filter.
+        filter: String,
+    },
+    /// Stop accepting requests, wait for in-flight requests, then shut down.
+    Drain {
+        /// Maximum time to wait for in-flight requests, in seconds.
+        #[clap(long = "timeout", default_value = "30")]
+        timeout_secs: u64,
+    },
+    /// Reload the runtime configuration file(s).
+    Reload,
+    /// Upgrade the running application to a new version.
+    Upgrade {
+        /// The new version of the application. This may be a manifest
+        /// (spin.toml) file, or a directory containing a spin.toml file.
+        #[clap(short = 'f', long = "from")]
+        app_source: PathBuf,
+    },
+    /// Load a new version of the application alongside the running one,
+    /// without sending it live traffic. If the application was started with
+    /// `--staging-listen`, the staged version is served on that address.
+    Stage {
+        /// The new version of the application. This may be a manifest
+        /// (spin.toml) file, or a directory containing a spin.toml file.
+        #[clap(short = 'f', long = "from")]
+        app_source: PathBuf,
+        /// A label identifying the staged version.
+        #[clap(long = "label", default_value = "staging")]
+        label: String,
+    },
+    /// Run smoke checks against a staged version and, if they pass, switch
+    /// live traffic to it.
+    Promote {
+        /// Stage this version first. This may be a manifest (spin.toml)
+        /// file, or a directory containing a spin.toml file.
+        #[clap(short = 'f', long = "from")]
+        app_source: Option<PathBuf>,
+        /// The label of the staged version.
+        #[clap(long = "label", default_value = "staging")]
+        label: String,
+        /// A path, e.g. `/health`, which must respond successfully on the
+        /// staged version. May be repeated.
+        #[clap(long = "check", multiple_occurrences = true)]
+        checks: Vec<String>,
+    },
+}
+
+impl CtlCommand {
+    pub async fn run(self) -> Result<()> {
+        let request = match self.command {
+            CtlCommands::Components => AdminRequest::Components,
+            CtlCommands::Config => AdminRequest::Config,
+            CtlCommands::Metrics => AdminRequest::Metrics,
+            CtlCommands::LogLevel { filter } => AdminRequest::SetLogLevel { filter },
+            CtlCommands::Drain { timeout_secs } => AdminRequest::Drain { timeout_secs },
+            CtlCommands::Reload => AdminRequest::Reload,
+            CtlCommands::Upgrade { app_source } => AdminRequest::Upgrade {
+                manifest: manifest_path(&app_source)?,
+            },
+            CtlCommands::Stage { app_source, label } => AdminRequest::Stage {
+                manifest: manifest_path(&app_source)?,
+                label,
+            },
+            CtlCommands::Promote {
+                app_source,
+                label,
+                checks,
+            } => {
+                if let Some(app_source) = app_source {
+                    let stage = AdminRequest::Stage {
+                        manifest: manifest_path(&app_source)?,
+                        label: label.clone(),
+                    };
+                    call(&self.socket, &stage).await?;
+                }
+                AdminRequest::Promote { label, checks }
+            }
+        };
+
+        match call(&self.socket, &request).await? {
+            None | Some(serde_json::Value::Null) => println!("OK"),
+            Some(result) => println!("{}", serde_json::to_string_pretty(&result)?),
+        }
+        Ok(())
+    }
+}
+
+// The trigger may not share our working directory.
+fn manifest_path(app_source: &std::path::Path) -> Result<PathBuf> {
+    dunce::canonicalize(app_source).with_context(|| format!("Couldn't find {app_source:?}"))
+}
+
+/// Sends a request to the admin socket, returning the result if it succeeded.
+pub(crate) async fn call(
+    socket: &std::path::Path,
+    request: &AdminRequest,
+) -> Result<Option<serde_json::Value>> {
+    let response = send(socket, request).await?;
+    if !response.ok {
+        bail!(
+            "{}",
+            response
+                .error
+                .unwrap_or_else(|| "request failed".to_owned())
+        );
+    }
+    Ok(response.result)
+}
+
+#[cfg(unix)]
+async fn send(socket: &std::path::Path, request: &AdminRequest) -> Result<AdminResponse> {
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+    let stream = tokio::net::UnixStream::connect(socket)
+        .await
+        .with_context(|| format!("Couldn't connect to admin socket {socket:?}. Is the application running with `--admin-socket`?"))?;
+    let (reader, mut writer) = stream.into_split();
+
+    let mut line = serde_json::to_vec(request)?;
+    line.push(b'\n');
+    writer.write_all(&line).await?;
+
+    let mut response = String::new();
+    BufReader::new(reader).read_line(&mut response).await?;
+    serde_json::from_str(&response).context("Invalid response from admin socket")
+}
+
+#[cfg(not(unix))]
+async fn send(_socket: &std::path::Path, _request: &AdminRequest) -> Result<AdminResponse> {
+    bail!("`spin ctl` is only supported on Unix platforms")
+}
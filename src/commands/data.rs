@@ -0,0 +1,107 @@
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use clap::{Parser, Subcommand};
+use spin_trigger::RuntimeConfig;
+
+use crate::opts::{APP_MANIFEST_FILE_OPT, DEFAULT_MANIFEST_FILE};
+
+/// Commands for working with an application's local data.
+#[derive(Subcommand, Debug)]
+pub enum DataCommands {
+    /// Delete the data which Spin keeps locally for the application's SQLite
+    /// databases and key-value stores, so that the next `spin up --seed`
+    /// starts from empty stores.
+    Reset(ResetCommand),
+}
+
+impl DataCommands {
+    pub async fn run(self) -> Result<()> {
+        match self {
+            Self::Reset(cmd) => cmd.run().await,
+        }
+    }
+}
+
+#[derive(Parser, Debug)]
+pub struct ResetCommand {
+    /// The application whose data to reset. This may be a manifest
+    /// (spin.toml) file, or a directory containing a spin.toml file.
+    /// If omitted, it defaults to "spin.toml".
+    #[clap(
+        name = APP_MANIFEST_FILE_OPT,
+        short = 'f',
+        long = "from",
+        alias = "file",
+        default_value = DEFAULT_MANIFEST_FILE
+    )]
+    pub app_source: PathBuf,
+
+    /// The runtime config file which configures the application's stores,
+    /// as for `spin up`.
+    #[clap(long = "runtime-config-file")]
+    pub runtime_config_file: Option<PathBuf>,
+
+    /// The application state directory, as for `spin up`. Defaults to
+    /// `.spin/` relative to the `spin.toml` file.
+    #[clap(long = "state-dir")]
+    pub state_dir: Option<String>,
+
+    /// Delete the data without asking for confirmation.
+    #[clap(short = 'y', long = "yes")]
+    pub yes: bool,
+}
+
+impl ResetCommand {
+    pub async fn run(self) -> Result<()> {
+        let manifest_file = spin_common::paths::resolve_manifest_file_path(&self.app_source)?;
+        let app_dir = spin_common::paths::parent_dir(&manifest_file)?;
+
+        let mut runtime_config = RuntimeConfig::new(Some(app_dir));
+        if let Some(state_dir) = &self.state_dir {
+            runtime_config.set_state_dir(state_dir);
+        }
+        if let Some(config_file) = &self.runtime_config_file {
+            runtime_config.merge_config_file(config_file)?;
+        }
+
+        // SQLite keeps uncommitted changes beside the database.
+        let files: Vec<_> = runtime_config
+            .local_data_files()?
+            .into_iter()
+            .flat_map(|file| {
+                ["", "-wal", "-shm", "-journal"].map(|suffix| {
+                    let mut path = file.clone().into_os_string();
+                    path.push(suffix);
+                    PathBuf::from(path)
+                })
+            })
+            .filter(|path| path.exists())
+            .collect();
+        if files.is_empty() {
+            println!("There is no local data to reset.");
+            return Ok(());
+        }
+
+        if !self.yes {
+            println!("This will delete:");
+            for file in &files {
+                println!("  {}", file.display());
+            }
+            let confirmed = dialoguer::Confirm::new()
+                .with_prompt("Delete the application's local data?")
+                .default(false)
+                .interact_opt()?
+                .unwrap_or_default();
+            if !confirmed {
+                return Ok(());
+            }
+        }
+
+        for file in &files {
+            std::fs::remove_file(file).with_context(|| format!("Failed to delete {file:?}"))?;
+        }
+        terminal::step!("Reset", "local data for {}", manifest_file.display());
+        Ok(())
+    }
+}
@@ -0,0 +1,319 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    path::Path,
+};
+
+use anyhow::{Context, Result};
+use clap::{Parser, ValueEnum};
+use serde::Serialize;
+use spin_trigger::compat;
+use wasmparser::{Encoding, Parser as WasmParser, Payload, ProducersSectionReader};
+
+/// The import modules provided to (non-component) Wasm modules.
+const MODULE_IMPORTS: &[&str] = &[
+    "wasi_snapshot_preview1",
+    "config",
+    "http",
+    "key-value",
+    "llm",
+    "mysql",
+    "postgres",
+    "redis",
+    "sqlite",
+    "outbound-mysql",
+    "outbound-pg",
+    "outbound-redis",
+    "spin-config",
+    "wasi-outbound-http",
+];
+
+const WASM_PAGE_SIZE: u64 = 64 * 1024;
+
+/// Show the imports, exports and requirements of a Wasm component.
+#[derive(Parser, Debug)]
+#[clap(about = "Show the imports, exports and requirements of a Wasm component")]
+pub struct InspectCommand {
+    /// The Wasm module or component to inspect. This may be a file, or a
+    /// reference to a component in an OCI registry, e.g.
+    /// `ghcr.io/org/component:1.2`.
+    pub source: String,
+
+    /// Ignore server certificate errors when pulling from a registry.
+    #[clap(short = 'k', long = "insecure", takes_value = false)]
+    pub insecure: bool,
+
+    /// The format in which to show the information.
+    #[clap(value_enum, long = "format", default_value = "plain")]
+    pub format: InspectFormat,
+}
+
+#[derive(ValueEnum, Clone, Debug)]
+pub enum InspectFormat {
+    Plain,
+    Json,
+}
+
+impl InspectCommand {
+    pub async fn run(self) -> Result<()> {
+        let wasm = if Path::new(&self.source).exists() {
+            tokio::fs::read(&self.source)
+                .await
+                .with_context(|| format!("Failed to read {}", self.source))?
+        } else {
+            let mut client = spin_oci::Client::new(self.insecure, None).await?;
+            client.pull_component(&self.source).await.with_context(|| {
+                format!(
+                    "'{}' is not a file, and could not be pulled from a registry",
+                    self.source
+                )
+            })?
+        };
+
+        let info = WasmInfo::parse(&wasm)?;
+        match self.format {
+            InspectFormat::Plain => info.print(),
+            InspectFormat::Json => println!("{}", serde_json::to_string_pretty(&info)?),
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct WasmInfo {
+    kind: WasmKind,
+    imports: Vec<Import>,
+    exports: Vec<String>,
+    sdk_version: Option<String>,
+    producers: BTreeMap<String, Vec<String>>,
+    memory: MemoryEstimate,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum WasmKind {
+    Component,
+    Module,
+}
+
+#[derive(Debug, Serialize)]
+struct Import {
+    name: String,
+    provided_by_spin: bool,
+}
+
+/// The linear memory declared by the binary: a lower bound on the memory
+/// used by each instance.
+#[derive(Debug, Default, Serialize)]
+struct MemoryEstimate {
+    initial_bytes: u64,
+    /// None if any memory may grow without limit.
+    maximum_bytes: Option<u64>,
+}
+
+impl WasmInfo {
+    fn parse(wasm: &[u8]) -> Result<Self> {
+        let mut depth = 0;
+        let mut kind = None;
+        let mut imports = vec![];
+        let mut exports = vec![];
+        let mut producers = BTreeMap::<String, Vec<String>>::new();
+        let mut initial_bytes = 0;
+        let mut maximum_bytes = Some(0);
+
+        for payload in WasmParser::new(0).parse_all(wasm) {
+            match payload.context("Not a valid Wasm module or component")? {
+                Payload::Version { encoding, .. } => {
+                    depth += 1;
+                    if depth == 1 {
+                        kind = Some(match encoding {
+                            Encoding::Component => WasmKind::Component,
+                            Encoding::Module => WasmKind::Module,
+                        });
+                    }
+                }
+                Payload::End(_) => depth -= 1,
+                Payload::ImportSection(reader) if depth == 1 => {
+                    for import in reader {
+                        imports.push(import?.module.to_owned());
+                    }
+                }
+                Payload::ComponentImportSection(reader) if depth == 1 => {
+                    for import in reader {
+                        imports.push(import?.name.0.to_owned());
+                    }
+                }
+                Payload::ExportSection(reader) if depth == 1 => {
+                    for export in reader {
+                        exports.push(export?.name.to_owned());
+                    }
+                }
+                Payload::ComponentExportSection(reader) if depth == 1 => {
+                    for export in reader {
+                        exports.push(export?.name.0.to_owned());
+                    }
+                }
+                Payload::MemorySection(reader) => {
+                    for memory in reader {
+                        let memory = memory?;
+                        add_memory(
+                            &mut initial_bytes,
+                            &mut maximum_bytes,
+                            memory.initial,
+                            memory.maximum,
+                        );
+                    }
+                }
+                Payload::CustomSection(reader) if reader.name() == "producers" => {
+                    let section = ProducersSectionReader::new(reader.data(), reader.data_offset())?;
+                    for field in section {
+                        let field = field?;
+                        let values = producers.entry(field.name.to_owned()).or_default();
+                        for value in field.values {
+                            let value = value?;
+                            let value = format!("{} {}", value.name, value.version);
+                            let value = value.trim().to_owned();
+                            if !values.contains(&value) {
+                                values.push(value);
+                            }
+                        }
+                    }
+                }
+                _ => (),
+            }
+        }
+
+        let kind = kind.context("Not a valid Wasm module or component")?;
+        // Modules import many functions from the same module
+        let mut seen = HashSet::new();
+        let imports = imports
+            .into_iter()
+            .filter(|name| seen.insert(name.clone()))
+            .map(|name| Import {
+                provided_by_spin: is_provided_by_spin(&kind, &name),
+                name,
+            })
+            .collect();
+
+        Ok(Self {
+            kind,
+            imports,
+            exports,
+            sdk_version: compat::sdk_version(wasm),
+            producers,
+            memory: MemoryEstimate {
+                initial_bytes,
+                maximum_bytes,
+            },
+        })
+    }
+
+    fn print(&self) {
+        let kind = match self.kind {
+            WasmKind::Component => "component",
+            WasmKind::Module => "module",
+        };
+        println!("Wasm {kind}");
+
+        println!("\nImports:");
+        for import in &self.imports {
+            if import.provided_by_spin {
+                println!("  {}", import.name);
+            } else {
+                println!("  {} (NOT PROVIDED BY SPIN)", import.name);
+            }
+        }
+        println!("\nExports:");
+        for export in &self.exports {
+            println!("  {export}");
+        }
+
+        let missing = self.imports.iter().filter(|i| !i.provided_by_spin).count();
+        if missing > 0 {
+            let wasi_hint = if self.kind == WasmKind::Component {
+                format!(
+                    " This version of Spin provides WASI {}.",
+                    compat::WASI_VERSION
+                )
+            } else {
+                String::new()
+            };
+            println!("\nWarning: {missing} import(s) are not provided by Spin, so the {kind} will fail to instantiate with an 'unknown import' error.{wasi_hint}");
+        }
+
+        println!(
+            "\nSpin SDK: {}",
+            self.sdk_version.as_deref().unwrap_or("unknown")
+        );
+        for (field, values) in &self.producers {
+            println!("{}: {}", capitalize(field), values.join(", "));
+        }
+
+        let maximum = match self.memory.maximum_bytes {
+            Some(max) => format_bytes(max),
+            None => "unbounded".to_owned(),
+        };
+        println!(
+            "\nMemory: {} initial, {maximum} maximum",
+            format_bytes(self.memory.initial_bytes)
+        );
+    }
+}
+
+fn add_memory(
+    initial_bytes: &mut u64,
+    maximum_bytes: &mut Option<u64>,
+    initial: u64,
+    maximum: Option<u64>,
+) {
+    *initial_bytes += initial * WASM_PAGE_SIZE;
+    *maximum_bytes = match (*maximum_bytes, maximum) {
+        (Some(total), Some(max)) => Some(total + max * WASM_PAGE_SIZE),
+        _ => None,
+    };
+}
+
+fn is_provided_by_spin(kind: &WasmKind, import: &str) -> bool {
+    match kind {
+        WasmKind::Module => MODULE_IMPORTS.contains(&import),
+        WasmKind::Component => {
+            let package = import.split(['/', '@']).next().unwrap_or(import);
+            let known = package == "fermyon:spin" || compat::WASI_PACKAGES.contains(&package);
+            known && compat::unsupported_version(import).is_none()
+        }
+    }
+}
+
+fn capitalize(s: &str) -> String {
+    let mut chars = s.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+fn format_bytes(bytes: u64) -> String {
+    const MIB: u64 = 1024 * 1024;
+    if bytes >= MIB {
+        format!("{:.1} MiB", bytes as f64 / MIB as f64)
+    } else {
+        format!("{} KiB", bytes / 1024)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wasi_imports_must_match_the_host_version() {
+        let kind = WasmKind::Component;
+        assert!(is_provided_by_spin(
+            &kind,
+            "wasi:http/outgoing-handler@0.2.0-rc-2023-10-18"
+        ));
+        assert!(is_provided_by_spin(&kind, "fermyon:spin/key-value@2.0.0"));
+        assert!(is_provided_by_spin(&kind, "fermyon:spin/config"));
+        assert!(!is_provided_by_spin(&kind, "wasi:http/types@0.2.0"));
+        assert!(!is_provided_by_spin(&kind, "acme:widgets/frobnicate"));
+    }
+}
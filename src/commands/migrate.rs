@@ -0,0 +1,73 @@
+use std::path::PathBuf;
+
+use anyhow::{anyhow, Context, Result};
+use clap::Parser;
+use spin_app::AppLoader;
+use spin_loader::FilesMountStrategy;
+use spin_trigger::{loader::TriggerLoader, RuntimeConfig};
+use url::Url;
+
+use crate::opts::{APP_MANIFEST_FILE_OPT, DEFAULT_MANIFEST_FILE};
+
+/// Apply the application's database migrations.
+#[derive(Parser, Debug)]
+#[clap(about = "Apply pending database migrations for each component")]
+pub struct MigrateCommand {
+    /// The application to migrate. This may be a manifest (spin.toml) file,
+    /// or a directory containing a spin.toml file.
+    /// If omitted, it defaults to "spin.toml".
+    #[clap(
+        name = APP_MANIFEST_FILE_OPT,
+        short = 'f',
+        long = "from",
+        alias = "file",
+        default_value = DEFAULT_MANIFEST_FILE
+    )]
+    pub app_source: PathBuf,
+
+    /// The runtime config file which configures the application's
+    /// databases, as for `spin up`.
+    #[clap(long = "runtime-config-file")]
+    pub runtime_config_file: Option<PathBuf>,
+
+    /// The application state directory, which holds the default SQLite
+    /// database. Defaults to `.spin/` relative to the `spin.toml` file.
+    #[clap(long = "state-dir")]
+    pub state_dir: Option<String>,
+}
+
+impl MigrateCommand {
+    pub async fn run(self) -> Result<()> {
+        let manifest_file = spin_common::paths::resolve_manifest_file_path(&self.app_source)?;
+        let app_dir = spin_common::paths::parent_dir(&manifest_file)?;
+
+        let working_dir = tempfile::tempdir()?;
+        let locked_app = spin_loader::from_file(
+            &manifest_file,
+            FilesMountStrategy::Copy(working_dir.path().join("assets")),
+        )
+        .await?;
+        let locked_path = working_dir.path().join("spin.lock");
+        let locked_app_contents =
+            serde_json::to_vec_pretty(&locked_app).context("failed to serialize locked app")?;
+        tokio::fs::write(&locked_path, locked_app_contents)
+            .await
+            .with_context(|| format!("failed to write {:?}", locked_path))?;
+        let locked_url = Url::from_file_path(&locked_path)
+            .map_err(|_| anyhow!("cannot convert to file URL: {locked_path:?}"))?
+            .to_string();
+
+        let loader = AppLoader::new(TriggerLoader::new(working_dir.path(), false));
+        let app = loader.load_app(locked_url).await?;
+
+        let mut runtime_config = RuntimeConfig::new(Some(app_dir));
+        if let Some(state_dir) = &self.state_dir {
+            runtime_config.set_state_dir(state_dir);
+        }
+        if let Some(config_file) = &self.runtime_config_file {
+            runtime_config.merge_config_file(config_file)?;
+        }
+
+        spin_trigger::migrate::migrate(&app, &runtime_config).await
+    }
+}
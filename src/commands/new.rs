@@ -1,3 +1,5 @@
+mod from_wasm;
+
 use std::{
     collections::HashMap,
     path::{Path, PathBuf},
@@ -9,8 +11,11 @@ use clap::Parser;
 use path_absolutize::Absolutize;
 use tokio;
 
-use spin_templates::{RunOptions, Template, TemplateManager, TemplateVariantInfo};
+use spin_templates::{
+    RunOptions, SourceTemplates, Template, TemplateManager, TemplateSource, TemplateVariantInfo,
+};
 
+use crate::build_info::*;
 use crate::opts::{APP_MANIFEST_FILE_OPT, DEFAULT_MANIFEST_FILE};
 
 /// Scaffold a new application based on a template.
@@ -38,6 +43,16 @@ pub struct TemplateNewCommandCore {
     )]
     pub tags: Vec<String>,
 
+    /// Use a template from a Git repository, without installing it. The
+    /// repository must contain a `templates` directory. If it contains more
+    /// than one template, use `--template` to choose between them.
+    #[clap(long = "from-git", conflicts_with = "tags")]
+    pub from_git: Option<String>,
+
+    /// The branch or tag of the `--from-git` repository to use.
+    #[clap(long = "branch", requires = "from-git")]
+    pub branch: Option<String>,
+
     /// The directory in which to create the new application or component.
     /// The default is the name argument.
     #[clap(short = 'o', long = "output", group = "location")]
@@ -54,13 +69,23 @@ pub struct TemplateNewCommandCore {
     /// A TOML file which contains parameter values in name = "value" format.
     /// Parameters passed as CLI option overwrite parameters specified in the
     /// file.
-    #[clap(long = "values-file")]
+    #[clap(long = "values-file", alias = "values")]
     pub values_file: Option<PathBuf>,
 
     /// An optional argument that allows to skip prompts for the manifest file
     /// by accepting the defaults if available on the template
     #[clap(short = 'a', long = "accept-defaults", takes_value = false)]
     pub accept_defaults: bool,
+
+    /// Do not run the template's post-generation commands (such as
+    /// `cargo fmt` or `npm install`).
+    #[clap(long = "no-hooks", takes_value = false)]
+    pub no_hooks: bool,
+
+    /// Run the template's post-generation commands without asking for
+    /// confirmation first.
+    #[clap(long = "allow-hooks", takes_value = false, conflicts_with = "no-hooks")]
+    pub allow_hooks: bool,
 }
 
 /// Scaffold a new application based on a template.
@@ -83,6 +108,38 @@ pub struct AddCommand {
         long = "file",
     )]
     pub app: Option<PathBuf>,
+
+    /// Add a component from an existing Wasm module or component, rather
+    /// than from a template. The trigger is chosen to match the binary's
+    /// exports.
+    #[clap(
+        long = "from-wasm",
+        conflicts_with_all = &["template-id", "tags", "from-git", "from-registry"]
+    )]
+    pub from_wasm: Option<PathBuf>,
+
+    /// Add a component published to an OCI registry, e.g.
+    /// `ghcr.io/org/component:1.2`, rather than from a template. The
+    /// component is downloaded into the application's `components` directory.
+    #[clap(
+        long = "from-registry",
+        conflicts_with_all = &["template-id", "tags", "from-git"]
+    )]
+    pub from_registry: Option<String>,
+
+    /// Ignore server certificate errors when pulling from the registry.
+    #[clap(short = 'k', long = "insecure", requires = "from-registry")]
+    pub insecure: bool,
+
+    /// The route for an HTTP component added with `--from-wasm` or
+    /// `--from-registry`. The default is `/...`.
+    #[clap(long = "route")]
+    pub route: Option<String>,
+
+    /// The channel for a Redis component added with `--from-wasm` or
+    /// `--from-registry`.
+    #[clap(long = "channel")]
+    pub channel: Option<String>,
 }
 
 impl NewCommand {
@@ -112,6 +169,29 @@ impl AddCommand {
                 manifest_path.display()
             );
         }
+
+        let wasm_source = match (&self.from_wasm, &self.from_registry) {
+            (Some(path), _) => Some(from_wasm::WasmSource::File(path.clone())),
+            (None, Some(reference)) => Some(from_wasm::WasmSource::Registry {
+                reference: reference.clone(),
+                insecure: self.insecure,
+            }),
+            (None, None) => None,
+        };
+        if let Some(source) = wasm_source {
+            let trigger = from_wasm::TriggerOptions {
+                route: self.route.clone(),
+                channel: self.channel.clone(),
+            };
+            return from_wasm::add_component(
+                &manifest_path,
+                self.options.name.clone(),
+                source,
+                trigger,
+            )
+            .await;
+        }
+
         self.options
             .run(TemplateVariantInfo::AddComponent { manifest_path })
             .await
@@ -125,23 +205,40 @@ impl TemplateNewCommandCore {
 
         let (name, template_id) = self.resolve_name_template_syntax(&template_manager, &variant)?;
 
-        let template = match &template_id {
-            Some(template_id) => match template_manager
-                .get(template_id)
-                .with_context(|| format!("Error retrieving template {}", template_id))?
-            {
-                Some(template) => template,
-                None => match prompt_template(&template_manager, &variant, &[template_id.clone()])
-                    .await?
+        // Must outlive the run, as the template's files are in the source.
+        let mut source_templates = match &self.from_git {
+            Some(url) => Some(
+                TemplateSource::try_from_git(url, &self.branch, SPIN_VERSION)?
+                    .load_templates()
+                    .await
+                    .with_context(|| format!("Failed to read templates from {url}"))?,
+            ),
+            None => None,
+        };
+
+        let template = if let Some(source_templates) = &mut source_templates {
+            template_from_source(source_templates, &template_id)?
+        } else {
+            match &template_id {
+                Some(template_id) => match template_manager
+                    .get(template_id)
+                    .with_context(|| format!("Error retrieving template {}", template_id))?
                 {
+                    Some(template) => template,
+                    None => {
+                        match prompt_template(&template_manager, &variant, &[template_id.clone()])
+                            .await?
+                        {
+                            Some(template) => template,
+                            None => return Ok(()),
+                        }
+                    }
+                },
+                None => match prompt_template(&template_manager, &variant, &self.tags).await? {
                     Some(template) => template,
                     None => return Ok(()),
                 },
-            },
-            None => match prompt_template(&template_manager, &variant, &self.tags).await? {
-                Some(template) => template,
-                None => return Ok(()),
-            },
+            }
         };
 
         if !template.supports_variant(&variant) {
@@ -180,7 +277,15 @@ impl TemplateNewCommandCore {
             accept_defaults: self.accept_defaults,
         };
 
-        template.run(options).interactive().await
+        let run = template.run(options);
+        let run = if self.no_hooks {
+            run.without_hooks()
+        } else if self.allow_hooks {
+            run.allow_hooks()
+        } else {
+            run
+        };
+        run.interactive().await
     }
 
     // Try to guess if the user is using v1 or v2 syntax, and fix things up so
@@ -277,6 +382,33 @@ fn merge_values(from_file: &mut HashMap<String, String>, from_cli: &[ParameterVa
     }
 }
 
+fn template_from_source(
+    source_templates: &mut SourceTemplates,
+    template_id: &Option<String>,
+) -> Result<Template> {
+    let template = match template_id {
+        Some(id) => source_templates.take(id),
+        None => source_templates.take_only(),
+    };
+    match template {
+        Some(template) => Ok(template),
+        None => {
+            let ids = source_templates.ids().collect::<Vec<_>>();
+            match (template_id, ids.is_empty()) {
+                (_, true) => bail!("The repository does not contain any templates"),
+                (Some(id), false) => bail!(
+                    "The repository does not contain a template '{id}'. Available templates: {}",
+                    ids.join(", ")
+                ),
+                (None, false) => bail!(
+                    "The repository contains more than one template. Use --template to choose one of: {}",
+                    ids.join(", ")
+                ),
+            }
+        }
+    }
+}
+
 async fn prompt_template(
     template_manager: &TemplateManager,
     variant: &TemplateVariantInfo,
@@ -0,0 +1,345 @@
+//! Adding a component to an application from an existing Wasm binary, rather
+//! than from a template.
+//!
+//! The binary is inspected to find out which trigger it can serve, so that
+//! the component and trigger written into the manifest match what the
+//! binary actually exports.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, bail, Context, Result};
+use path_absolutize::Absolutize;
+use spin_manifest::{schema::v2::KebabId, ManifestVersion};
+use wasmparser::{Encoding, Parser, Payload};
+
+/// Where the Wasm binary comes from.
+pub(super) enum WasmSource {
+    /// A file on disk.
+    File(PathBuf),
+    /// A component published to an OCI registry.
+    Registry { reference: String, insecure: bool },
+}
+
+/// What a Wasm binary can be run as, based on its exports.
+#[derive(Debug, PartialEq)]
+pub(super) enum ComponentKind {
+    /// A Spin or `wasi:http` HTTP handler.
+    Http,
+    /// A WASI command, run by the Wagi executor.
+    Wagi,
+    /// A Spin Redis message handler.
+    Redis,
+}
+
+impl ComponentKind {
+    fn trigger_type(&self) -> &'static str {
+        match self {
+            Self::Http | Self::Wagi => "http",
+            Self::Redis => "redis",
+        }
+    }
+}
+
+/// How the new component should be triggered.
+pub(super) struct TriggerOptions {
+    pub route: Option<String>,
+    pub channel: Option<String>,
+}
+
+/// Adds a component from `source` to the application at `manifest_path`.
+pub(super) async fn add_component(
+    manifest_path: &Path,
+    id: Option<String>,
+    source: WasmSource,
+    trigger: TriggerOptions,
+) -> Result<()> {
+    let manifest_text = tokio::fs::read_to_string(manifest_path)
+        .await
+        .with_context(|| format!("Failed to read {}", manifest_path.display()))?;
+    if ManifestVersion::detect(&manifest_text)? != ManifestVersion::V2 {
+        bail!("Adding a component from a Wasm binary is only supported for version 2 manifests. Run `spin doctor` to upgrade your manifest.");
+    }
+    let manifest = spin_manifest::manifest_from_str(&manifest_text)?;
+    let app_dir = manifest_path
+        .parent()
+        .context("Manifest path has no parent directory")?;
+
+    let id = match id {
+        Some(id) => id,
+        None => default_id(&source)?,
+    };
+    let id =
+        KebabId::try_from(id.clone()).map_err(|e| anyhow!("Invalid component ID '{id}': {e}"))?;
+    if manifest.components.contains_key(&id) {
+        bail!("The application already contains a component named '{id}'");
+    }
+
+    let (wasm, source_path, provenance) = match source {
+        WasmSource::File(path) => {
+            let wasm = tokio::fs::read(&path)
+                .await
+                .with_context(|| format!("Failed to read {}", path.display()))?;
+            (wasm, relative_source_path(app_dir, &path)?, None)
+        }
+        WasmSource::Registry {
+            reference,
+            insecure,
+        } => {
+            let mut client = spin_oci::Client::new(insecure, None).await?;
+            let wasm = client
+                .pull_component(&reference)
+                .await
+                .with_context(|| format!("Failed to pull {reference}"))?;
+            let dest = app_dir.join("components").join(format!("{id}.wasm"));
+            tokio::fs::create_dir_all(dest.parent().unwrap()).await?;
+            tokio::fs::write(&dest, &wasm)
+                .await
+                .with_context(|| format!("Failed to write {}", dest.display()))?;
+            (wasm, format!("components/{id}.wasm"), Some(reference))
+        }
+    };
+
+    let kind = inspect(&wasm)?;
+    if let Some(existing) = manifest
+        .triggers
+        .keys()
+        .find(|t| t.as_str() != kind.trigger_type())
+    {
+        bail!(
+            "The Wasm binary is a {} component, but the application uses the '{existing}' trigger",
+            kind.trigger_type()
+        );
+    }
+
+    let trigger_config = match kind {
+        ComponentKind::Http | ComponentKind::Wagi => {
+            ("route", trigger.route.unwrap_or_else(|| "/...".to_owned()))
+        }
+        ComponentKind::Redis => (
+            "channel",
+            trigger
+                .channel
+                .context("The Wasm binary is a Redis component: please specify --channel")?,
+        ),
+    };
+
+    let snippet = component_snippet(
+        id.as_ref(),
+        &kind,
+        trigger_config,
+        &source_path,
+        provenance.as_deref(),
+    );
+    let mut new_manifest = manifest_text;
+    if !new_manifest.ends_with('\n') {
+        new_manifest.push('\n');
+    }
+    new_manifest.push('\n');
+    new_manifest.push_str(&snippet);
+    tokio::fs::write(manifest_path, new_manifest)
+        .await
+        .with_context(|| format!("Failed to write {}", manifest_path.display()))?;
+
+    println!(
+        "Added {} component '{id}' to {}",
+        kind.trigger_type(),
+        manifest_path.display()
+    );
+    Ok(())
+}
+
+/// Works out what a Wasm binary can be run as from its top-level exports.
+pub(super) fn inspect(wasm: &[u8]) -> Result<ComponentKind> {
+    let mut depth = 0;
+    let mut encoding = None;
+    let mut exports = vec![];
+
+    for payload in Parser::new(0).parse_all(wasm) {
+        match payload.context("The file is not a valid Wasm module or component")? {
+            Payload::Version { encoding: e, .. } => {
+                depth += 1;
+                if depth == 1 {
+                    encoding = Some(e);
+                }
+            }
+            Payload::End(_) => depth -= 1,
+            Payload::ExportSection(reader) if depth == 1 => {
+                for export in reader {
+                    exports.push(export?.name.to_owned());
+                }
+            }
+            Payload::ComponentExportSection(reader) if depth == 1 => {
+                for export in reader {
+                    exports.push(export?.name.0.to_owned());
+                }
+            }
+            _ => (),
+        }
+    }
+
+    let exports_any = |names: &[&str]| {
+        exports.iter().any(|export| {
+            let unversioned = export.split('@').next().unwrap_or(export);
+            names.iter().any(|name| unversioned.contains(name))
+        })
+    };
+
+    match encoding {
+        Some(Encoding::Component) => {
+            if exports_any(&["fermyon:spin/inbound-http", "wasi:http/incoming-handler"]) {
+                Ok(ComponentKind::Http)
+            } else if exports_any(&["fermyon:spin/inbound-redis"]) {
+                Ok(ComponentKind::Redis)
+            } else {
+                Err(unsupported(&exports))
+            }
+        }
+        Some(Encoding::Module) => {
+            if exports_any(&["inbound-http", "handle-http-request"]) {
+                Ok(ComponentKind::Http)
+            } else if exports_any(&["inbound-redis", "handle-redis-message"]) {
+                Ok(ComponentKind::Redis)
+            } else if exports.iter().any(|e| e == "_start") {
+                Ok(ComponentKind::Wagi)
+            } else {
+                Err(unsupported(&exports))
+            }
+        }
+        None => bail!("The file is not a valid Wasm module or component"),
+    }
+}
+
+fn unsupported(exports: &[String]) -> anyhow::Error {
+    anyhow!(
+        "The Wasm binary does not export a Spin trigger interface (found exports: {})",
+        exports.join(", ")
+    )
+}
+
+fn component_snippet(
+    id: &str,
+    kind: &ComponentKind,
+    (trigger_key, trigger_value): (&str, String),
+    source_path: &str,
+    provenance: Option<&str>,
+) -> String {
+    let mut snippet = format!(
+        "[[trigger.{}]]\n{trigger_key} = {}\ncomponent = {}\n",
+        kind.trigger_type(),
+        toml_string(&trigger_value),
+        toml_string(id),
+    );
+    if *kind == ComponentKind::Wagi {
+        snippet.push_str("executor = \"wagi\"\n");
+    }
+    snippet.push('\n');
+    if let Some(provenance) = provenance {
+        snippet.push_str(&format!("# Pulled from {provenance}\n"));
+    }
+    snippet.push_str(&format!(
+        "[component.{id}]\nsource = {}\nallowed_outbound_hosts = []\n",
+        toml_string(source_path)
+    ));
+    snippet
+}
+
+fn toml_string(s: &str) -> String {
+    toml::Value::String(s.to_owned()).to_string()
+}
+
+/// The path to the Wasm file as written in the manifest: relative to the
+/// application directory if it is inside it, otherwise absolute.
+fn relative_source_path(app_dir: &Path, wasm_path: &Path) -> Result<String> {
+    let app_dir = app_dir.absolutize()?;
+    let wasm_path = wasm_path.absolutize()?;
+    let path = wasm_path
+        .strip_prefix(&app_dir)
+        .unwrap_or(wasm_path.as_ref());
+    Ok(path.to_string_lossy().replace('\\', "/"))
+}
+
+/// Derives a component ID from the file name or repository name.
+fn default_id(source: &WasmSource) -> Result<String> {
+    let name = match source {
+        WasmSource::File(path) => path
+            .file_stem()
+            .map(|s| s.to_string_lossy().into_owned())
+            .context("Cannot derive a component name from the file path: please specify one")?,
+        WasmSource::Registry { reference, .. } => {
+            let last_segment = reference.rsplit('/').next().unwrap_or(reference);
+            last_segment
+                .split(['@', ':'])
+                .next()
+                .unwrap_or(last_segment)
+                .to_owned()
+        }
+    };
+    let id = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_lowercase()
+            } else {
+                '-'
+            }
+        })
+        .collect::<String>();
+    Ok(id
+        .split('-')
+        .filter(|w| !w.is_empty())
+        .collect::<Vec<_>>()
+        .join("-"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // (module (func (export "_start")))
+    const WASI_COMMAND: &[u8] = &[
+        0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, 0x01, 0x04, 0x01, 0x60, 0x00, 0x00, 0x03,
+        0x02, 0x01, 0x00, 0x07, 0x0a, 0x01, 0x06, 0x5f, 0x73, 0x74, 0x61, 0x72, 0x74, 0x00, 0x00,
+        0x0a, 0x04, 0x01, 0x02, 0x00, 0x0b,
+    ];
+
+    #[test]
+    fn wasi_command_is_run_by_wagi() {
+        assert_eq!(ComponentKind::Wagi, inspect(WASI_COMMAND).unwrap());
+    }
+
+    #[test]
+    fn non_wasm_is_rejected() {
+        inspect(b"not wasm").unwrap_err();
+    }
+
+    #[test]
+    fn default_ids_are_kebab_case() {
+        let id = default_id(&WasmSource::File("target/my_Component.wasm".into())).unwrap();
+        assert_eq!("my-component", id);
+        let id = default_id(&WasmSource::Registry {
+            reference: "ghcr.io/org/cool-thing:1.2".to_owned(),
+            insecure: false,
+        })
+        .unwrap();
+        assert_eq!("cool-thing", id);
+    }
+
+    #[test]
+    fn wagi_snippet_sets_executor() {
+        let snippet = component_snippet(
+            "cgi",
+            &ComponentKind::Wagi,
+            ("route", "/cgi/...".to_owned()),
+            "cgi.wasm",
+            None,
+        );
+        let manifest: toml::Value = toml::from_str(&snippet).unwrap();
+        let trigger = &manifest["trigger"]["http"][0];
+        assert_eq!("/cgi/...", trigger["route"].as_str().unwrap());
+        assert_eq!("wagi", trigger["executor"].as_str().unwrap());
+        assert_eq!(
+            "cgi.wasm",
+            manifest["component"]["cgi"]["source"].as_str().unwrap()
+        );
+    }
+}
@@ -2,7 +2,10 @@ use crate::opts::*;
 use anyhow::{Context, Result};
 use clap::{Parser, Subcommand};
 use indicatif::{ProgressBar, ProgressStyle};
-use spin_oci::Client;
+use spin_oci::{
+    docker::{host_architecture, DockerImageOptions},
+    Client,
+};
 use std::{io::Read, path::PathBuf, time::Duration};
 
 /// Commands for working with OCI registries to distribute applications.
@@ -53,6 +56,26 @@ pub struct Push {
     #[clap(long, takes_value = false, env = ALWAYS_BUILD_ENV)]
     pub build: bool,
 
+    /// Publish the application as a container image which runs it with an
+    /// embedded Spin binary, for platforms which only run container images.
+    #[clap(long = "as-docker", takes_value = false)]
+    pub as_docker: bool,
+
+    /// The Spin binary to embed in the container image. The image has no
+    /// base OS, so this must be a statically linked Linux build. Defaults to
+    /// this Spin.
+    #[clap(long = "runtime", requires = "as-docker")]
+    pub runtime: Option<PathBuf>,
+
+    /// The architecture of the container image, e.g. "amd64" or "arm64".
+    /// Defaults to the architecture of this machine.
+    #[clap(long = "image-arch", requires = "as-docker")]
+    pub image_arch: Option<String>,
+
+    /// The address an HTTP application listens on inside the container.
+    #[clap(long = "image-listen", default_value = "0.0.0.0:80")]
+    pub image_listen: String,
+
     /// Reference of the Spin application
     #[clap()]
     pub reference: String,
@@ -69,7 +92,23 @@ impl Push {
 
         let _spinner = create_dotted_spinner(2000, "Pushing app to the Registry".to_owned());
 
-        let digest = client.push(&app_file, &self.reference).await?;
+        let digest = if self.as_docker {
+            let options = DockerImageOptions {
+                runtime: match self.runtime {
+                    Some(runtime) => runtime,
+                    None => std::env::current_exe()?,
+                },
+                architecture: self
+                    .image_arch
+                    .unwrap_or_else(|| host_architecture().to_owned()),
+                listen: self.image_listen,
+            };
+            client
+                .push_docker(&app_file, &self.reference, &options)
+                .await?
+        } else {
+            client.push(&app_file, &self.reference).await?
+        };
         match digest {
             Some(digest) => println!("Pushed with digest {digest}"),
             None => println!("Pushed; the registry did not return the digest"),
@@ -0,0 +1,42 @@
+use std::{ffi::OsString, path::PathBuf};
+
+use anyhow::{Context, Result};
+use clap::Parser;
+
+use super::up::UpCommand;
+
+/// Re-run a failed request from a replay bundle.
+#[derive(Parser, Debug)]
+#[clap(about = "Re-run a failed request recorded with `spin up --record-failures`")]
+pub struct ReplayCommand {
+    /// The replay bundle to run.
+    pub bundle: PathBuf,
+
+    /// The application which recorded the bundle. This may be a manifest
+    /// (spin.toml) file, a directory containing a spin.toml file, or a
+    /// remote registry reference. If omitted, it defaults to "spin.toml".
+    #[clap(short = 'f', long = "from")]
+    pub app_source: Option<String>,
+
+    /// Prepare the component for debugging, as with `spin up --debug`.
+    #[clap(long = "debug")]
+    pub debug: bool,
+}
+
+impl ReplayCommand {
+    pub async fn run(self) -> Result<()> {
+        // The trigger may not share our working directory.
+        let bundle = dunce::canonicalize(&self.bundle)
+            .with_context(|| format!("Couldn't find replay bundle {:?}", self.bundle))?;
+        let mut trigger_args = vec![OsString::from("--replay"), bundle.into()];
+        if self.debug {
+            trigger_args.push("--debug".into());
+        }
+        let up = UpCommand {
+            app_source: self.app_source.into_iter().collect(),
+            trigger_args,
+            ..Default::default()
+        };
+        up.run().await
+    }
+}
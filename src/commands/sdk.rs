@@ -0,0 +1,242 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+use clap::{Parser, Subcommand, ValueEnum};
+
+/// The version of the Spin WIT package embedded in this build of Spin.
+const WIT_PACKAGE: &str = "fermyon:spin@2.0.0";
+
+/// The world which guest bindings are generated for by default. This matches
+/// the Rust SDK: outbound HTTP, Postgres, MySQL, Redis, SQLite, key-value,
+/// LLM and variables, plus the incoming HTTP handler.
+const DEFAULT_WORLD: &str = "http-trigger";
+
+macro_rules! wit_file {
+    ($path:literal) => {
+        ($path, include_str!(concat!("../../wit/preview2/", $path)))
+    };
+}
+
+/// The Spin WIT package and its dependencies, as (relative path, contents).
+const WIT_FILES: &[(&str, &str)] = &[
+    wit_file!("key-value.wit"),
+    wit_file!("llm.wit"),
+    wit_file!("mysql.wit"),
+    wit_file!("postgres.wit"),
+    wit_file!("rdbms-types.wit"),
+    wit_file!("redis.wit"),
+    wit_file!("sqlite.wit"),
+    wit_file!("variables.wit"),
+    wit_file!("world.wit"),
+    wit_file!("deps/http/incoming-handler.wit"),
+    wit_file!("deps/http/outgoing-handler.wit"),
+    wit_file!("deps/http/types.wit"),
+    wit_file!("deps/http/world.wit"),
+    wit_file!("deps/io/poll.wit"),
+    wit_file!("deps/io/streams.wit"),
+    wit_file!("deps/io/world.wit"),
+    wit_file!("deps/spin@3.0.0/app-state.wit"),
+    wit_file!("deps/spin@3.0.0/cache.wit"),
+    wit_file!("deps/spin@3.0.0/context.wit"),
+    wit_file!("deps/spin@3.0.0/crypto.wit"),
+    wit_file!("deps/spin@3.0.0/flags.wit"),
+    wit_file!("deps/spin@3.0.0/html.wit"),
+    wit_file!("deps/spin@3.0.0/image.wit"),
+    wit_file!("deps/spin@3.0.0/inbound-job.wit"),
+    wit_file!("deps/spin@3.0.0/jobs.wit"),
+    wit_file!("deps/spin@3.0.0/jwt.wit"),
+    wit_file!("deps/spin@3.0.0/key-value.wit"),
+    wit_file!("deps/spin@3.0.0/lock.wit"),
+    wit_file!("deps/spin@3.0.0/password.wit"),
+    wit_file!("deps/spin@3.0.0/pdf.wit"),
+    wit_file!("deps/spin@3.0.0/postgres.wit"),
+    wit_file!("deps/spin@3.0.0/random.wit"),
+    wit_file!("deps/spin@3.0.0/rdbms-types.wit"),
+    wit_file!("deps/spin@3.0.0/redis.wit"),
+    wit_file!("deps/spin@3.0.0/template.wit"),
+    wit_file!("deps/spin@3.0.0/timezone.wit"),
+    wit_file!("deps/spin@3.0.0/workflows.wit"),
+    wit_file!("deps/spin@3.0.0/world.wit"),
+    wit_file!("deps/spin@unversioned/config.wit"),
+    wit_file!("deps/spin@unversioned/http-types.wit"),
+    wit_file!("deps/spin@unversioned/http.wit"),
+    wit_file!("deps/spin@unversioned/inbound-http.wit"),
+    wit_file!("deps/spin@unversioned/inbound-redis.wit"),
+    wit_file!("deps/spin@unversioned/key-value.wit"),
+    wit_file!("deps/spin@unversioned/llm.wit"),
+    wit_file!("deps/spin@unversioned/mysql.wit"),
+    wit_file!("deps/spin@unversioned/postgres.wit"),
+    wit_file!("deps/spin@unversioned/rdbms-types.wit"),
+    wit_file!("deps/spin@unversioned/redis-types.wit"),
+    wit_file!("deps/spin@unversioned/redis.wit"),
+    wit_file!("deps/spin@unversioned/sqlite.wit"),
+    wit_file!("deps/spin@unversioned/world.wit"),
+];
+
+/// Commands for working with guest SDKs.
+#[derive(Subcommand, Debug)]
+pub enum SdkCommands {
+    /// Write the Spin WIT package, for use with other bindings generators.
+    Wit(WitCommand),
+    /// Generate guest bindings for the Spin interfaces in another language.
+    Generate(GenerateCommand),
+}
+
+impl SdkCommands {
+    pub async fn run(self) -> Result<()> {
+        match self {
+            Self::Wit(cmd) => cmd.run().await,
+            Self::Generate(cmd) => cmd.run().await,
+        }
+    }
+}
+
+#[derive(Parser, Debug)]
+pub struct WitCommand {
+    /// The directory to write the WIT package to.
+    #[clap(short = 'o', long = "output", default_value = "wit")]
+    pub output: PathBuf,
+}
+
+impl WitCommand {
+    pub async fn run(self) -> Result<()> {
+        write_wit_package(&self.output).await?;
+        println!("Wrote {WIT_PACKAGE} to {}", self.output.display());
+        Ok(())
+    }
+}
+
+/// The languages which bindings can be generated for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Language {
+    /// Go, using TinyGo (requires `wit-bindgen`).
+    Go,
+    /// JavaScript and TypeScript (requires `jco`).
+    Js,
+    /// Python (requires `componentize-py`).
+    Python,
+}
+
+impl Language {
+    /// The bindings generator program and its arguments.
+    fn generator(&self, wit_dir: &Path, world: &str, output: &Path) -> (&'static str, Vec<String>) {
+        let wit_dir = wit_dir.display().to_string();
+        let output = output.display().to_string();
+        match self {
+            Self::Go => (
+                "wit-bindgen",
+                vec![
+                    "tiny-go".into(),
+                    wit_dir,
+                    "--world".into(),
+                    world.into(),
+                    "--out-dir".into(),
+                    output,
+                ],
+            ),
+            Self::Js => (
+                "jco",
+                vec![
+                    "types".into(),
+                    wit_dir,
+                    "--world-name".into(),
+                    world.into(),
+                    "-o".into(),
+                    output,
+                ],
+            ),
+            Self::Python => (
+                "componentize-py",
+                vec![
+                    "-d".into(),
+                    wit_dir,
+                    "-w".into(),
+                    world.into(),
+                    "bindings".into(),
+                    output,
+                ],
+            ),
+        }
+    }
+}
+
+#[derive(Parser, Debug)]
+pub struct GenerateCommand {
+    /// The language to generate bindings for.
+    #[clap(short = 'l', long = "language", value_enum)]
+    pub language: Language,
+
+    /// The WIT world to generate bindings for.
+    #[clap(short = 'w', long = "world", default_value = DEFAULT_WORLD)]
+    pub world: String,
+
+    /// The directory to write the bindings to. The WIT package is written to
+    /// a `wit` subdirectory.
+    #[clap(short = 'o', long = "output", default_value = "spin-sdk")]
+    pub output: PathBuf,
+}
+
+impl GenerateCommand {
+    pub async fn run(self) -> Result<()> {
+        let wit_dir = self.output.join("wit");
+        write_wit_package(&wit_dir).await?;
+
+        let (program, args) = self.language.generator(&wit_dir, &self.world, &self.output);
+        let status = match tokio::process::Command::new(program)
+            .args(&args)
+            .status()
+            .await
+        {
+            Ok(status) => status,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                bail!(
+                    "`{program}` was not found. Install it, then run `{program} {}` to generate the bindings from the WIT package in {}.",
+                    args.join(" "),
+                    wit_dir.display()
+                );
+            }
+            Err(e) => {
+                return Err(e).with_context(|| format!("Failed to run `{program}`"));
+            }
+        };
+        if !status.success() {
+            return Err(crate::subprocess::ExitStatusError::new(status).into());
+        }
+
+        println!(
+            "Generated {WIT_PACKAGE} bindings for world '{}' in {}",
+            self.world,
+            self.output.display()
+        );
+        Ok(())
+    }
+}
+
+async fn write_wit_package(dir: &Path) -> Result<()> {
+    for (path, contents) in WIT_FILES {
+        let path = dir.join(path);
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent)
+                .await
+                .with_context(|| format!("Failed to create {}", parent.display()))?;
+        }
+        tokio::fs::write(&path, contents)
+            .await
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn embedded_wit_package_has_expected_version() {
+        let (_, world) = WIT_FILES
+            .iter()
+            .find(|(path, _)| *path == "world.wit")
+            .unwrap();
+        assert!(world.starts_with(&format!("package {WIT_PACKAGE}")));
+    }
+}
@@ -0,0 +1,445 @@
+use std::{
+    ffi::OsString,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{bail, Context, Result};
+use clap::{Parser, Subcommand, ValueEnum};
+
+use crate::opts::{APP_MANIFEST_FILE_OPT, DEFAULT_MANIFEST_FILE};
+
+/// The prefix of launchd labels for Spin services.
+const LAUNCHD_LABEL_PREFIX: &str = "dev.spin";
+
+/// Commands for running applications as managed services.
+#[derive(Subcommand, Debug)]
+pub enum ServiceCommands {
+    /// Write a service definition which runs an application with `spin up`.
+    Install(InstallCommand),
+}
+
+impl ServiceCommands {
+    pub async fn run(self) -> Result<()> {
+        match self {
+            Self::Install(cmd) => cmd.run().await,
+        }
+    }
+}
+
+/// The service managers which Spin can write definitions for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum ServiceManager {
+    /// A systemd unit.
+    Systemd,
+    /// A launchd property list.
+    Launchd,
+}
+
+#[derive(Parser, Debug)]
+pub struct InstallCommand {
+    /// The application to run. This may be a manifest (spin.toml) file, or a
+    /// directory containing a spin.toml file.
+    #[clap(
+        name = APP_MANIFEST_FILE_OPT,
+        short = 'f',
+        long = "from",
+        alias = "file",
+        default_value = DEFAULT_MANIFEST_FILE
+    )]
+    pub app_source: PathBuf,
+
+    /// The name of the service. Defaults to the name of the directory
+    /// containing the application.
+    #[clap(long = "name")]
+    pub name: Option<String>,
+
+    /// The runtime configuration file to run the application with.
+    #[clap(long = "runtime-config-file")]
+    pub runtime_config_file: Option<PathBuf>,
+
+    /// The user to run the application as. Defaults to the service manager's
+    /// default, which is usually root for system services.
+    #[clap(long = "user")]
+    pub user: Option<String>,
+
+    /// The most memory the service may use, e.g. "512M" or "2G".
+    #[clap(long = "memory-max")]
+    pub memory_max: Option<String>,
+
+    /// The share of a CPU the service may use, e.g. "50%" or "200%".
+    /// Supported only by systemd.
+    #[clap(long = "cpu-quota")]
+    pub cpu_quota: Option<String>,
+
+    /// Set an environment variable for the service, in the form KEY=VALUE.
+    #[clap(long = "env", short = 'e', multiple_occurrences = true)]
+    pub env: Vec<String>,
+
+    /// The service manager to write a definition for. Defaults to launchd on
+    /// macOS and systemd elsewhere.
+    #[clap(value_enum, long = "manager")]
+    pub manager: Option<ServiceManager>,
+
+    /// Where to write the service definition, or "-" to print it. Defaults to
+    /// the service manager's directory for system services.
+    #[clap(short = 'o', long = "output")]
+    pub output: Option<PathBuf>,
+
+    /// Arguments to pass to `spin up`, such as `--listen`.
+    #[clap(last = true)]
+    pub up_args: Vec<OsString>,
+}
+
+impl InstallCommand {
+    pub async fn run(self) -> Result<()> {
+        let manifest = spin_common::paths::resolve_manifest_file_path(&self.app_source)?;
+        let manifest = dunce::canonicalize(&manifest)
+            .with_context(|| format!("Couldn't find manifest {manifest:?}"))?;
+        let app_dir = manifest.parent().unwrap_or(Path::new("/")).to_owned();
+        let name = match &self.name {
+            Some(name) => name.clone(),
+            None => app_dir
+                .file_name()
+                .map(|n| n.to_string_lossy().into_owned())
+                .context("Couldn't infer a service name; use --name to give one")?,
+        };
+        validate_name(&name)?;
+
+        let mut command = vec![
+            std::env::current_exe()?.into_os_string(),
+            "up".into(),
+            "--from".into(),
+            manifest.into_os_string(),
+        ];
+        if let Some(runtime_config_file) = &self.runtime_config_file {
+            let path = dunce::canonicalize(runtime_config_file).with_context(|| {
+                format!("Couldn't find runtime config file {runtime_config_file:?}")
+            })?;
+            command.extend(["--runtime-config-file".into(), path.into_os_string()]);
+        }
+        command.extend(self.up_args.iter().cloned());
+
+        let spec = ServiceSpec {
+            name,
+            command: command
+                .into_iter()
+                .map(|arg| arg.to_string_lossy().into_owned())
+                .collect(),
+            working_dir: app_dir,
+            user: self.user.clone(),
+            env: self
+                .env
+                .iter()
+                .map(|e| parse_env(e))
+                .collect::<Result<_>>()?,
+            memory_max: self.memory_max.clone(),
+            cpu_quota: self.cpu_quota.clone(),
+        };
+
+        let manager = self.manager.unwrap_or(if cfg!(target_os = "macos") {
+            ServiceManager::Launchd
+        } else {
+            ServiceManager::Systemd
+        });
+        let definition = match manager {
+            ServiceManager::Systemd => spec.systemd_unit(),
+            ServiceManager::Launchd => spec.launchd_plist()?,
+        };
+
+        let output = match &self.output {
+            Some(output) if output == Path::new("-") => {
+                print!("{definition}");
+                return Ok(());
+            }
+            Some(output) => output.clone(),
+            None => default_output(manager, &spec)?,
+        };
+        if let Some(parent) = output.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create directory {parent:?}"))?;
+        }
+        std::fs::write(&output, definition)
+            .with_context(|| format!("Failed to write service definition {output:?}"))?;
+
+        println!("Wrote {}", output.display());
+        match manager {
+            ServiceManager::Systemd => println!(
+                "Start the service with:\n    systemctl daemon-reload && systemctl enable --now {}",
+                spec.name
+            ),
+            ServiceManager::Launchd => println!(
+                "Start the service with:\n    launchctl load -w {}",
+                output.display()
+            ),
+        }
+        Ok(())
+    }
+}
+
+/// What a service runs, and how.
+struct ServiceSpec {
+    name: String,
+    command: Vec<String>,
+    working_dir: PathBuf,
+    user: Option<String>,
+    env: Vec<(String, String)>,
+    memory_max: Option<String>,
+    cpu_quota: Option<String>,
+}
+
+impl ServiceSpec {
+    fn systemd_unit(&self) -> String {
+        let mut service = vec![
+            format!(
+                "ExecStart={}",
+                self.command
+                    .iter()
+                    .map(|arg| systemd_quote(arg))
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            ),
+            // Unlike commands, paths are not unquoted, only unescaped.
+            format!(
+                "WorkingDirectory={}",
+                self.working_dir.to_string_lossy().replace('%', "%%")
+            ),
+        ];
+        if let Some(user) = &self.user {
+            service.push(format!("User={user}"));
+        }
+        for (key, value) in &self.env {
+            service.push(format!(
+                "Environment={}",
+                systemd_quote(&format!("{key}={value}"))
+            ));
+        }
+        if let Some(memory_max) = &self.memory_max {
+            service.push(format!("MemoryMax={memory_max}"));
+        }
+        if let Some(cpu_quota) = &self.cpu_quota {
+            service.push(format!("CPUQuota={cpu_quota}"));
+        }
+        // `spin up` stops its trigger on SIGTERM, which is systemd's default.
+        service.push("Restart=on-failure".to_owned());
+
+        format!(
+            "[Unit]\n\
+             Description=Spin application {name}\n\
+             Wants=network-online.target\n\
+             After=network-online.target\n\
+             \n\
+             [Service]\n\
+             {service}\n\
+             \n\
+             [Install]\n\
+             WantedBy=multi-user.target\n",
+            name = self.name,
+            service = service.join("\n"),
+        )
+    }
+
+    fn launchd_plist(&self) -> Result<String> {
+        if self.cpu_quota.is_some() {
+            bail!(
+                "launchd does not support CPU quotas; remove --cpu-quota or use --manager systemd"
+            );
+        }
+
+        let mut entries = vec![
+            plist_key_string("Label", &format!("{LAUNCHD_LABEL_PREFIX}.{}", self.name)),
+            format!(
+                "    <key>ProgramArguments</key>\n    <array>\n{}    </array>",
+                self.command
+                    .iter()
+                    .map(|arg| format!("        <string>{}</string>\n", xml_escape(arg)))
+                    .collect::<String>()
+            ),
+            plist_key_string("WorkingDirectory", &self.working_dir.to_string_lossy()),
+        ];
+        if let Some(user) = &self.user {
+            entries.push(plist_key_string("UserName", user));
+        }
+        if !self.env.is_empty() {
+            entries.push(format!(
+                "    <key>EnvironmentVariables</key>\n    <dict>\n{}    </dict>",
+                self.env
+                    .iter()
+                    .map(|(key, value)| format!(
+                        "        <key>{}</key>\n        <string>{}</string>\n",
+                        xml_escape(key),
+                        xml_escape(value)
+                    ))
+                    .collect::<String>()
+            ));
+        }
+        if let Some(memory_max) = &self.memory_max {
+            let bytes = parse_size(memory_max)?;
+            entries.push(format!(
+                "    <key>HardResourceLimits</key>\n    <dict>\n        <key>ResidentSetSize</key>\n        <integer>{bytes}</integer>\n    </dict>"
+            ));
+        }
+        entries.push("    <key>RunAtLoad</key>\n    <true/>".to_owned());
+        entries.push(
+            "    <key>KeepAlive</key>\n    <dict>\n        <key>SuccessfulExit</key>\n        <false/>\n    </dict>"
+                .to_owned(),
+        );
+
+        Ok(format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+             <!DOCTYPE plist PUBLIC \"-//Apple//DTD PLIST 1.0//EN\" \"http://www.apple.com/DTDs/PropertyList-1.0.dtd\">\n\
+             <plist version=\"1.0\">\n\
+             <dict>\n\
+             {}\n\
+             </dict>\n\
+             </plist>\n",
+            entries.join("\n")
+        ))
+    }
+}
+
+fn default_output(manager: ServiceManager, spec: &ServiceSpec) -> Result<PathBuf> {
+    Ok(match manager {
+        ServiceManager::Systemd => {
+            PathBuf::from(format!("/etc/systemd/system/{}.service", spec.name))
+        }
+        // Only daemons, which run as root until they switch user, can run as
+        // another user.
+        ServiceManager::Launchd if spec.user.is_some() => PathBuf::from(format!(
+            "/Library/LaunchDaemons/{LAUNCHD_LABEL_PREFIX}.{}.plist",
+            spec.name
+        )),
+        ServiceManager::Launchd => dirs::home_dir()
+            .context(
+                "Couldn't find the home directory; use --output to give where to write the service",
+            )?
+            .join("Library/LaunchAgents")
+            .join(format!("{LAUNCHD_LABEL_PREFIX}.{}.plist", spec.name)),
+    })
+}
+
+fn validate_name(name: &str) -> Result<()> {
+    let valid = !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+    if !valid {
+        bail!("Invalid service name '{name}': use only letters, digits, '-', '_' and '.'");
+    }
+    Ok(())
+}
+
+fn parse_env(env: &str) -> Result<(String, String)> {
+    let (key, value) = env
+        .split_once('=')
+        .with_context(|| format!("Environment variable '{env}' must be in the form KEY=VALUE"))?;
+    Ok((key.to_owned(), value.to_owned()))
+}
+
+/// Parses a size such as "512M" into bytes, as systemd's MemoryMax does.
+fn parse_size(size: &str) -> Result<u64> {
+    let (digits, multiplier) = match size.char_indices().last() {
+        Some((i, 'K' | 'k')) => (&size[..i], 1 << 10),
+        Some((i, 'M' | 'm')) => (&size[..i], 1 << 20),
+        Some((i, 'G' | 'g')) => (&size[..i], 1 << 30),
+        Some((i, 'T' | 't')) => (&size[..i], 1 << 40),
+        _ => (size, 1),
+    };
+    let value: u64 = digits
+        .parse()
+        .with_context(|| format!("Invalid size '{size}': expected e.g. 512M"))?;
+    Ok(value * multiplier)
+}
+
+/// Quotes an argument for a systemd unit file, escaping what systemd would
+/// otherwise expand.
+fn systemd_quote(arg: &str) -> String {
+    let escaped = arg
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('%', "%%")
+        .replace('$', "$$");
+    if escaped.contains(char::is_whitespace) || escaped.is_empty() {
+        format!("\"{escaped}\"")
+    } else {
+        escaped
+    }
+}
+
+fn plist_key_string(key: &str, value: &str) -> String {
+    format!(
+        "    <key>{key}</key>\n    <string>{}</string>",
+        xml_escape(value)
+    )
+}
+
+fn xml_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spec() -> ServiceSpec {
+        ServiceSpec {
+            name: "hello".into(),
+            command: vec![
+                "/usr/local/bin/spin".into(),
+                "up".into(),
+                "--from".into(),
+                "/srv/my app/spin.toml".into(),
+            ],
+            working_dir: "/srv/my app".into(),
+            user: Some("spin".into()),
+            env: vec![("RUST_LOG".into(), "info".into())],
+            memory_max: Some("512M".into()),
+            cpu_quota: None,
+        }
+    }
+
+    #[test]
+    fn systemd_units_quote_arguments_and_set_limits() {
+        let unit = spec().systemd_unit();
+        assert!(
+            unit.contains("ExecStart=/usr/local/bin/spin up --from \"/srv/my app/spin.toml\"\n")
+        );
+        assert!(unit.contains("User=spin\n"));
+        assert!(unit.contains("Environment=RUST_LOG=info\n"));
+        assert!(unit.contains("MemoryMax=512M\n"));
+        assert!(!unit.contains("CPUQuota"));
+    }
+
+    #[test]
+    fn launchd_plists_convert_memory_limits() {
+        let plist = spec().launchd_plist().unwrap();
+        assert!(plist.contains("<string>dev.spin.hello</string>"));
+        assert!(plist.contains("<string>/srv/my app/spin.toml</string>"));
+        assert!(plist.contains(&format!("<integer>{}</integer>", 512 << 20)));
+    }
+
+    #[test]
+    fn launchd_rejects_cpu_quotas() {
+        let spec = ServiceSpec {
+            cpu_quota: Some("50%".into()),
+            ..spec()
+        };
+        spec.launchd_plist().unwrap_err();
+    }
+
+    #[test]
+    fn systemd_specifiers_are_escaped() {
+        assert_eq!("100%%", systemd_quote("100%"));
+        assert_eq!("\"a \\\"b\\\"\"", systemd_quote("a \"b\""));
+    }
+
+    #[test]
+    fn sizes_are_parsed() {
+        assert_eq!(1024, parse_size("1K").unwrap());
+        assert_eq!(2 << 30, parse_size("2G").unwrap());
+        assert_eq!(100, parse_size("100").unwrap());
+        parse_size("lots").unwrap_err();
+    }
+}
@@ -0,0 +1,142 @@
+use std::{collections::BTreeMap, path::PathBuf};
+
+use anyhow::{Context, Result};
+use clap::Parser;
+use spin_trigger::admin::{AdminRequest, ComponentUsage, Latency};
+
+use super::ctl::{call, ADMIN_SOCKET_ENV};
+
+/// Show the CPU time and memory used by each component of a running
+/// application, and how busy its executor is.
+#[derive(Parser, Debug)]
+#[clap(about = "Show the resources used by each component of a running application")]
+pub struct StatsCommand {
+    /// The admin socket of the running application, as passed to
+    /// `spin up --admin-socket`.
+    #[clap(short = 's', long = "socket", env = ADMIN_SOCKET_ENV)]
+    pub socket: PathBuf,
+
+    /// Print the statistics as JSON.
+    #[clap(long = "json")]
+    pub json: bool,
+}
+
+impl StatsCommand {
+    pub async fn run(self) -> Result<()> {
+        let metrics = call(&self.socket, &AdminRequest::Metrics)
+            .await?
+            .unwrap_or_default();
+        let usage: BTreeMap<String, ComponentUsage> =
+            serde_json::from_value(metrics["component_usage"].clone())
+                .context("The application did not report component usage")?;
+        // Reported by newer triggers only.
+        let instantiation: BTreeMap<String, Latency> =
+            serde_json::from_value(metrics["instantiation_latency"].clone()).unwrap_or_default();
+
+        if self.json {
+            println!("{}", serde_json::to_string_pretty(&usage)?);
+        } else if usage.is_empty() {
+            println!("No invocations yet");
+        } else {
+            print_executor(&metrics);
+            print_table(&usage, &instantiation);
+        }
+        Ok(())
+    }
+}
+
+fn print_executor(metrics: &serde_json::Value) {
+    let (Some(running), Some(queued)) = (
+        metrics["invocations_running"].as_u64(),
+        metrics["invocations_queued"].as_u64(),
+    ) else {
+        return;
+    };
+    let limit = match metrics["max_concurrent_invocations"].as_u64() {
+        Some(max) => format!(" (limit {max})"),
+        None => String::new(),
+    };
+    let utilization = metrics["executor"]["utilization"].as_f64().unwrap_or(0.0);
+    let threads = metrics["executor"]["worker_threads"].as_u64().unwrap_or(0);
+    println!(
+        "Invocations: {running} running{limit}, {queued} queued; {:.1}% utilization of {threads} worker threads",
+        utilization * 100.0
+    );
+    if let Some(wait) = serde_json::from_value::<Latency>(metrics["queue_wait"].clone())
+        .ok()
+        .filter(|wait| wait.count > 0)
+    {
+        println!(
+            "Queued invocations waited {} on average, and at most {}",
+            format_micros(wait.mean_us()),
+            format_micros(wait.max_us)
+        );
+    }
+    println!();
+}
+
+fn print_table(
+    usage: &BTreeMap<String, ComponentUsage>,
+    instantiation: &BTreeMap<String, Latency>,
+) {
+    let width = usage.keys().map(String::len).max().unwrap_or(0).max(9);
+    println!(
+        "{:width$}  {:>11}  {:>12}  {:>12}  {:>11}  {:>16}  {:>13}",
+        "COMPONENT",
+        "INVOCATIONS",
+        "MEAN CPU",
+        "MAX CPU",
+        "PEAK MEMORY",
+        "MEAN INSTANTIATE",
+        "FORCED YIELDS"
+    );
+    for (component, usage) in usage {
+        let mean_cpu_us = usage.cpu_time_us / usage.invocations.max(1);
+        let mean_instantiate = match instantiation.get(component) {
+            Some(latency) => format_micros(latency.mean_us()),
+            None => "-".to_owned(),
+        };
+        println!(
+            "{component:width$}  {:>11}  {:>12}  {:>12}  {:>11}  {:>16}  {:>13}",
+            usage.invocations,
+            format_micros(mean_cpu_us),
+            format_micros(usage.max_cpu_time_us),
+            format_bytes(usage.peak_memory_bytes),
+            mean_instantiate,
+            usage.forced_yields,
+        );
+    }
+}
+
+fn format_micros(us: u64) -> String {
+    if us >= 1_000_000 {
+        format!("{:.2}s", us as f64 / 1_000_000.0)
+    } else if us >= 1_000 {
+        format!("{:.2}ms", us as f64 / 1_000.0)
+    } else {
+        format!("{us}µs")
+    }
+}
+
+fn format_bytes(bytes: u64) -> String {
+    const MIB: u64 = 1024 * 1024;
+    if bytes >= MIB {
+        format!("{:.1} MiB", bytes as f64 / MIB as f64)
+    } else {
+        format!("{} KiB", bytes / 1024)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_units() {
+        assert_eq!("850µs", format_micros(850));
+        assert_eq!("12.35ms", format_micros(12_346));
+        assert_eq!("2.00s", format_micros(2_000_000));
+        assert_eq!("64 KiB", format_bytes(65536));
+        assert_eq!("1.5 MiB", format_bytes(1536 * 1024));
+    }
+}
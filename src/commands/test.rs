@@ -0,0 +1,417 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use anyhow::{bail, Context, Result};
+use clap::{Parser, Subcommand};
+use regex::Regex;
+use serde::Deserialize;
+
+use super::conformance::{free_local_address, HostProcess};
+
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Headers which differ between runs, and are left out of snapshots.
+const VOLATILE_HEADERS: &[&str] = &[
+    "connection",
+    "content-length",
+    "date",
+    "keep-alive",
+    "server-timing",
+    "transfer-encoding",
+];
+
+/// Commands for testing applications.
+#[derive(Subcommand, Debug)]
+pub enum TestCommands {
+    /// Check an application's responses against recorded snapshots.
+    Snapshot(SnapshotCommand),
+}
+
+impl TestCommands {
+    pub async fn run(self) -> Result<()> {
+        match self {
+            Self::Snapshot(cmd) => cmd.run().await,
+        }
+    }
+}
+
+/// Send the requests listed in a snapshot file to an application, and
+/// compare each response with the one recorded earlier. Responses with no
+/// recording yet are recorded.
+#[derive(Parser, Debug)]
+pub struct SnapshotCommand {
+    /// The requests to send, as `[[request]]` tables in a TOML file.
+    #[clap(long = "requests", default_value = "snapshots.toml")]
+    pub requests: PathBuf,
+
+    /// The directory holding the recorded responses. Defaults to
+    /// `snapshots/` beside the requests file.
+    #[clap(long = "dir")]
+    pub dir: Option<PathBuf>,
+
+    /// The application to run. This may be a manifest (spin.toml) file or a
+    /// directory containing a spin.toml file. If omitted, it defaults to
+    /// "spin.toml".
+    #[clap(short = 'f', long = "from", conflicts_with = "url")]
+    pub app_source: Option<PathBuf>,
+
+    /// Send the requests to an application which is already running at this
+    /// URL, instead of starting one.
+    #[clap(long = "url")]
+    pub url: Option<String>,
+
+    /// Record every response, replacing the existing recordings.
+    #[clap(long = "update")]
+    pub update: bool,
+}
+
+/// The contents of a requests file.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SnapshotConfig {
+    #[serde(default, rename = "request")]
+    requests: Vec<SnapshotRequest>,
+    /// More headers to leave out of snapshots.
+    #[serde(default)]
+    ignore_headers: Vec<String>,
+    /// Regular expressions for text in responses which differs between
+    /// runs, such as generated IDs. Matches are replaced with `<redacted>`;
+    /// if a pattern has a capture group, only the group's text is replaced.
+    #[serde(default)]
+    redact: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SnapshotRequest {
+    /// Names the snapshot file.
+    name: String,
+    path: String,
+    #[serde(default = "default_method")]
+    method: String,
+    #[serde(default)]
+    headers: BTreeMap<String, String>,
+    #[serde(default)]
+    body: Option<String>,
+}
+
+fn default_method() -> String {
+    "GET".to_owned()
+}
+
+impl SnapshotCommand {
+    pub async fn run(self) -> Result<()> {
+        let config: SnapshotConfig = toml::from_str(
+            &std::fs::read_to_string(&self.requests)
+                .with_context(|| format!("Failed to read requests file {:?}", self.requests))?,
+        )
+        .with_context(|| format!("Invalid requests file {:?}", self.requests))?;
+        let normalizer = Normalizer::new(&config)?;
+        let dir = match &self.dir {
+            Some(dir) => dir.clone(),
+            None => self
+                .requests
+                .parent()
+                .unwrap_or(Path::new("."))
+                .join("snapshots"),
+        };
+
+        let mut host = None;
+        let base_url = match &self.url {
+            Some(url) => url.trim_end_matches('/').to_owned(),
+            None => {
+                let manifest = spin_common::paths::resolve_manifest_file_path(
+                    self.app_source.as_deref().unwrap_or(Path::new("spin.toml")),
+                )?;
+                let listen = free_local_address()?;
+                let base_url = format!("http://{listen}");
+                let host = host.insert(HostProcess::start(None, &manifest, listen)?);
+                wait_until_serving(&base_url, host).await?;
+                base_url
+            }
+        };
+
+        let client = reqwest::Client::new();
+        let (mut passed, mut recorded, mut failed) = (0, 0, 0);
+        for request in &config.requests {
+            let path = dir.join(format!("{}.snap", request.name));
+            let actual = match send(&client, &base_url, request, &normalizer).await {
+                Ok(actual) => actual,
+                Err(e) => {
+                    failed += 1;
+                    println!("FAIL {}: {e:#}", request.name);
+                    continue;
+                }
+            };
+            match std::fs::read_to_string(&path) {
+                Ok(expected) if !self.update => {
+                    if expected == actual {
+                        passed += 1;
+                        println!("PASS {}", request.name);
+                    } else {
+                        failed += 1;
+                        println!("FAIL {}: the response differs from {path:?}", request.name);
+                        print_diff(&expected, &actual);
+                    }
+                }
+                _ => {
+                    std::fs::create_dir_all(&dir)
+                        .with_context(|| format!("Failed to create {dir:?}"))?;
+                    std::fs::write(&path, &actual)
+                        .with_context(|| format!("Failed to write snapshot {path:?}"))?;
+                    recorded += 1;
+                    println!("RECORDED {}", request.name);
+                }
+            }
+        }
+
+        println!("\n{passed} passed, {failed} failed, {recorded} recorded");
+        if failed > 0 {
+            bail!(
+                "{failed} snapshot(s) did not match; run with --update to accept the new responses"
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Sends a request, returning its response as snapshot text.
+async fn send(
+    client: &reqwest::Client,
+    base_url: &str,
+    request: &SnapshotRequest,
+    normalizer: &Normalizer,
+) -> Result<String> {
+    let method = reqwest::Method::from_bytes(request.method.to_ascii_uppercase().as_bytes())
+        .with_context(|| format!("invalid method {}", request.method))?;
+    let mut builder = client.request(method.clone(), format!("{base_url}{}", request.path));
+    for (name, value) in &request.headers {
+        builder = builder.header(name, value);
+    }
+    if let Some(body) = &request.body {
+        builder = builder.body(body.clone());
+    }
+    let response = builder.send().await.context("request failed")?;
+
+    let status = response.status();
+    let headers = response
+        .headers()
+        .iter()
+        .map(|(name, value)| {
+            (
+                name.as_str().to_owned(),
+                String::from_utf8_lossy(value.as_bytes()).into_owned(),
+            )
+        })
+        .collect::<Vec<_>>();
+    let body = response.bytes().await.context("failed to read the body")?;
+    Ok(normalizer.snapshot(
+        &format!("{method} {}", request.path),
+        status.as_u16(),
+        headers,
+        &body,
+    ))
+}
+
+/// Makes responses comparable across runs.
+struct Normalizer {
+    ignore_headers: Vec<String>,
+    redactions: Vec<(Regex, &'static str)>,
+}
+
+impl Normalizer {
+    fn new(config: &SnapshotConfig) -> Result<Self> {
+        let mut redactions = vec![
+            // RFC 3339 and similar ISO 8601 timestamps.
+            (
+                Regex::new(
+                    r"\d{4}-\d{2}-\d{2}[T ]\d{2}:\d{2}:\d{2}(?:\.\d+)?(?:Z|[+-]\d{2}:?\d{2})?",
+                )?,
+                "<timestamp>",
+            ),
+            // HTTP dates, e.g. `Tue, 15 Nov 1994 08:12:31 GMT`.
+            (
+                Regex::new(r"[A-Z][a-z]{2}, \d{2} [A-Z][a-z]{2} \d{4} \d{2}:\d{2}:\d{2} GMT")?,
+                "<timestamp>",
+            ),
+        ];
+        for pattern in &config.redact {
+            let regex = Regex::new(pattern)
+                .with_context(|| format!("Invalid redact pattern {pattern:?}"))?;
+            redactions.push((regex, "<redacted>"));
+        }
+        Ok(Self {
+            ignore_headers: config
+                .ignore_headers
+                .iter()
+                .map(|h| h.to_ascii_lowercase())
+                .collect(),
+            redactions,
+        })
+    }
+
+    fn snapshot(
+        &self,
+        request_line: &str,
+        status: u16,
+        mut headers: Vec<(String, String)>,
+        body: &[u8],
+    ) -> String {
+        headers.retain(|(name, _)| {
+            !VOLATILE_HEADERS.contains(&name.as_str()) && !self.ignore_headers.contains(name)
+        });
+        headers.sort();
+
+        let mut snapshot = format!("{request_line}\nstatus: {status}\n");
+        for (name, value) in headers {
+            snapshot += &format!("{name}: {}\n", self.redact(&value));
+        }
+        snapshot.push('\n');
+        snapshot += &self.redact(&format_body(body));
+        if !snapshot.ends_with('\n') {
+            snapshot.push('\n');
+        }
+        snapshot
+    }
+
+    fn redact(&self, text: &str) -> String {
+        self.redactions
+            .iter()
+            .fold(text.to_owned(), |text, (regex, replacement)| {
+                regex
+                    .replace_all(&text, |caps: &regex::Captures| {
+                        let whole = caps.get(0).unwrap();
+                        match caps.get(1) {
+                            Some(group) => format!(
+                                "{}{replacement}{}",
+                                &text[whole.start()..group.start()],
+                                &text[group.end()..whole.end()]
+                            ),
+                            None => replacement.to_string(),
+                        }
+                    })
+                    .into_owned()
+            })
+    }
+}
+
+/// Formats a body for a snapshot: JSON is pretty-printed, so that diffs are
+/// line by line, and binary bodies are summarized.
+fn format_body(body: &[u8]) -> String {
+    if let Ok(json) = serde_json::from_slice::<serde_json::Value>(body) {
+        if let Ok(pretty) = serde_json::to_string_pretty(&json) {
+            return pretty;
+        }
+    }
+    match std::str::from_utf8(body) {
+        Ok(text) => text.to_owned(),
+        Err(_) => format!("<{} bytes of binary data>", body.len()),
+    }
+}
+
+fn print_diff(expected: &str, actual: &str) {
+    let diff = similar::TextDiff::from_lines(expected, actual);
+    print!(
+        "{}",
+        diff.unified_diff()
+            .context_radius(3)
+            .header("snapshot", "response")
+    );
+}
+
+/// Polls the application's health check until it is serving.
+async fn wait_until_serving(base_url: &str, host: &mut HostProcess) -> Result<()> {
+    let client = reqwest::Client::new();
+    let url = format!("{base_url}{}health", spin_http::WELL_KNOWN_PREFIX);
+    let deadline = Instant::now() + STARTUP_TIMEOUT;
+    loop {
+        if let Some(status) = host.0.try_wait()? {
+            bail!("The application exited before serving requests: {status}");
+        }
+        match client.get(&url).send().await {
+            Ok(_) => return Ok(()),
+            Err(_) if Instant::now() < deadline => {
+                tokio::time::sleep(Duration::from_millis(250)).await;
+            }
+            Err(e) => return Err(e).context(format!("{base_url} did not start serving")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn normalizer(toml: &str) -> Normalizer {
+        Normalizer::new(&toml::from_str(toml).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn snapshots_leave_out_what_changes_between_runs() {
+        let normalizer = normalizer(
+            r#"
+            ignore_headers = ["X-Request-Id"]
+            redact = ['"id": "([0-9a-f]+)"']
+            "#,
+        );
+        let headers = vec![
+            (
+                "date".to_owned(),
+                "Tue, 15 Nov 1994 08:12:31 GMT".to_owned(),
+            ),
+            ("x-request-id".to_owned(), "abc".to_owned()),
+            ("content-type".to_owned(), "application/json".to_owned()),
+            (
+                "last-modified".to_owned(),
+                "Wed, 16 Nov 1994 08:12:31 GMT".to_owned(),
+            ),
+        ];
+        let body = br#"{"created": "2023-10-01T12:34:56.789Z", "id": "c0ffee", "ok": true}"#;
+        let snapshot = normalizer.snapshot("GET /api", 200, headers, body);
+        assert_eq!(
+            r#"GET /api
+status: 200
+content-type: application/json
+last-modified: <timestamp>
+
+{
+  "created": "<timestamp>",
+  "id": "<redacted>",
+  "ok": true
+}
+"#,
+            snapshot
+        );
+    }
+
+    #[test]
+    fn bodies_are_formatted() {
+        assert_eq!("plain text", format_body(b"plain text"));
+        assert_eq!("<2 bytes of binary data>", format_body(&[0xff, 0xfe]));
+    }
+
+    #[test]
+    fn requests_files_list_requests() {
+        let config: SnapshotConfig = toml::from_str(
+            r#"
+            [[request]]
+            name = "list-users"
+            path = "/api/users"
+
+            [[request]]
+            name = "create-user"
+            path = "/api/users"
+            method = "POST"
+            headers = { content-type = "application/json" }
+            body = '{"name": "ada"}'
+            "#,
+        )
+        .unwrap();
+        assert_eq!(2, config.requests.len());
+        assert_eq!("GET", config.requests[0].method);
+        assert_eq!("POST", config.requests[1].method);
+    }
+}
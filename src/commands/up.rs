@@ -1,23 +1,33 @@
 mod app_source;
+pub(crate) mod dev_services;
+mod git_source;
+mod multi_app;
+mod permissions;
 
 use std::{
     ffi::OsString,
     fmt::Debug,
     path::{Path, PathBuf},
+    sync::Arc,
 };
 
 use anyhow::{anyhow, bail, Context, Result};
 use clap::{CommandFactory, Parser};
 use reqwest::Url;
 use spin_app::locked::LockedApp;
+use spin_archive::ArchiveLoader;
 use spin_loader::FilesMountStrategy;
 use spin_oci::OciLoader;
 use spin_trigger::cli::{SPIN_LOCAL_APP_DIR, SPIN_LOCKED_URL, SPIN_WORKING_DIR};
 use tempfile::TempDir;
+use tokio::sync::Notify;
 
 use crate::opts::*;
 
 use self::app_source::{AppSource, ResolvedAppSource};
+use self::dev_services::{DevService, DevServices};
+use self::git_source::{GitSource, REDEPLOY_SECRET_ENV};
+use self::permissions::Permission;
 
 const APPLICATION_OPT: &str = "APPLICATION";
 
@@ -35,13 +45,24 @@ pub struct UpCommand {
     /// The application to run. This may be a manifest (spin.toml) file, a
     /// directory containing a spin.toml file, or a remote registry reference.
     /// If omitted, it defaults to "spin.toml".
+    ///
+    /// This may be given more than once to serve several HTTP applications
+    /// in one process. Each application is then served under a path prefix
+    /// derived from its name, e.g. `/myapp/...`, and with `--route-by host`
+    /// also for hosts named after it, e.g. `myapp.example.com`.
     #[clap(
         name = APPLICATION_OPT,
         short = 'f',
         long = "from",
         group = "source",
+        multiple_occurrences = true,
     )]
-    pub app_source: Option<String>,
+    pub app_source: Vec<String>,
+
+    /// How requests are routed when several applications are served
+    /// together: by path prefix only, or by host as well.
+    #[clap(long = "route-by", value_enum, default_value = "path")]
+    pub route_by: multi_app::MultiAppRouting,
 
     /// The application to run. This is the same as `--from` but forces the
     /// application to be interpreted as a file or directory path.
@@ -64,6 +85,38 @@ pub struct UpCommand {
     )]
     pub registry_source: Option<String>,
 
+    /// Run the application from a Git repository, rebuilding and
+    /// redeploying it whenever the branch moves on. The repository must
+    /// have a spin.toml file at its root.
+    #[clap(long = "from-git", group = "source")]
+    pub git_source: Option<String>,
+
+    /// The branch to run with `--from-git`. Defaults to the repository's
+    /// default branch.
+    #[clap(long = "branch", requires = "git-source")]
+    pub branch: Option<String>,
+
+    /// How often to check the `--from-git` branch for changes, e.g. `60s`
+    /// or `5m`.
+    #[clap(
+        long = "poll",
+        default_value = "60s",
+        parse(try_from_str = git_source::parse_poll_interval)
+    )]
+    pub poll: std::time::Duration,
+
+    /// Also check the `--from-git` branch for changes when a webhook, such
+    /// as a GitHub push webhook, is delivered to `/__spin/redeploy`.
+    /// Deliveries must be signed with this secret in the
+    /// `X-Hub-Signature-256` header. HTTP applications only.
+    #[clap(
+        long = "webhook-secret",
+        env = "SPIN_WEBHOOK_SECRET",
+        hide_env_values = true,
+        requires = "git-source"
+    )]
+    pub webhook_secret: Option<String>,
+
     /// Ignore server certificate errors from a registry
     #[clap(
         name = INSECURE_OPT,
@@ -95,6 +148,45 @@ pub struct UpCommand {
     #[clap(long, takes_value = false, env = ALWAYS_BUILD_ENV)]
     pub build: bool,
 
+    /// Let components write to their mounted files. Changes are made to a
+    /// temporary copy of the files and discarded when the application exits.
+    #[clap(long = "allow-transient-write", takes_value = false)]
+    pub allow_transient_write: bool,
+
+    /// Take a permission away from all components, e.g.
+    /// `outbound=https://example.com`, or `key-value` to deny every store.
+    /// The permission may be one of `files`, `outbound`, `key-value` or
+    /// `sqlite`.
+    #[clap(long = "deny", multiple_occurrences = true)]
+    pub deny: Vec<Permission>,
+
+    /// Grant a permission to all components, e.g.
+    /// `outbound=https://example.com` or `sqlite=default`.
+    #[clap(long = "allow", multiple_occurrences = true)]
+    pub allow: Vec<Permission>,
+
+    /// Merge the named overlay into a local application's manifest, e.g.
+    /// `production` for `spin.production.toml` beside `spin.toml`, to
+    /// override variables, allowed hosts, file mounts and other settings for
+    /// an environment. This may be given more than once; later overlays
+    /// take precedence.
+    #[clap(long = "overlay", multiple_occurrences = true)]
+    pub overlays: Vec<String>,
+
+    /// Start throwaway services for the application to use during
+    /// development, e.g. `--with postgres,redis`. Each runs in a Docker or
+    /// Podman container which is removed when Spin exits. Its address is
+    /// given to every component in an environment variable (`DB_URL` for
+    /// Postgres, `REDIS_ADDRESS` for Redis), and as the value of the
+    /// application variable of the same name if there is one.
+    #[clap(
+        long = "with",
+        value_enum,
+        use_value_delimiter = true,
+        multiple_occurrences = true
+    )]
+    pub with: Vec<DevService>,
+
     /// All other args, to be passed through to the trigger
     #[clap(hide = true)]
     pub trigger_args: Vec<OsString>,
@@ -112,6 +204,9 @@ impl UpCommand {
                 .print_help()?;
             println!();
         }
+        if self.git_source.is_some() && !help {
+            return self.run_from_git().await;
+        }
         self.run_inner().await.or_else(|err| {
             if help {
                 tracing::warn!("Error resolving trigger-specific help: {err:?}");
@@ -123,6 +218,10 @@ impl UpCommand {
     }
 
     async fn run_inner(self) -> Result<()> {
+        if self.app_source.len() > 1 {
+            return self.run_multi_app().await;
+        }
+
         let app_source = self.app_source();
 
         if app_source == AppSource::None {
@@ -160,7 +259,11 @@ impl UpCommand {
             .load_resolved_app_source(resolved_app_source, &working_dir)
             .await?;
 
-        self.update_locked_app(&mut locked_app);
+        self.update_locked_app(&mut locked_app)?;
+
+        // Held until the trigger exits, when the services are removed.
+        let dev_services = DevServices::start(&self.with)?;
+        dev_services.update_locked_app(&mut locked_app)?;
 
         let local_app_dir = app_source.local_app_dir().map(Into::into);
 
@@ -173,6 +276,101 @@ impl UpCommand {
         self.run_trigger(trigger_cmd, Some(run_opts)).await
     }
 
+    async fn run_from_git(mut self) -> Result<()> {
+        let source = GitSource {
+            url: self.git_source.take().unwrap_or_default(),
+            branch: self.branch.take(),
+        };
+        // Holds each revision's checkout, and the trigger's upgrade socket.
+        let git_dir = TempDir::with_prefix("spin-git-")?;
+        let checkout = git_dir.path().join("checkout-0");
+        let revision = source
+            .checkout(&checkout)
+            .await
+            .with_context(|| format!("Failed to clone {}", source.url))?;
+        terminal::step!("Cloned", "{} at {revision}", source.url);
+
+        let upgrade_socket = git_dir.path().join("upgrade.sock");
+        let redeploy_requests = Arc::new(Notify::new());
+        if self.webhook_secret.is_some() {
+            let redeploy_socket = git_dir.path().join("redeploy.sock");
+            git_source::listen_for_redeploys(&redeploy_socket, redeploy_requests.clone())?;
+            self.trigger_args
+                .extend(["--redeploy-socket".into(), redeploy_socket.into()]);
+        }
+        tokio::spawn(git_source::watch(
+            source,
+            revision,
+            self.poll,
+            redeploy_requests,
+            git_dir.path().to_owned(),
+            upgrade_socket.clone(),
+        ));
+
+        self.app_source = vec![checkout.to_string_lossy().into_owned()];
+        self.build = true;
+        self.trigger_args
+            .extend(["--upgrade-socket".into(), upgrade_socket.into()]);
+        self.run_inner().await
+    }
+
+    async fn run_multi_app(self) -> Result<()> {
+        let trigger_cmd = trigger_command(multi_app::MULTI_APP_TRIGGER_TYPE);
+
+        if self.help {
+            return self.run_trigger(trigger_cmd, None).await;
+        }
+
+        let app_sources = self
+            .app_source
+            .iter()
+            .map(|source| AppSource::infer_source(source))
+            .collect::<Vec<_>>();
+
+        if self.build {
+            for app_source in &app_sources {
+                app_source.build().await?;
+            }
+        }
+
+        let working_dir_holder = self.get_canonical_working_dir()?;
+        let working_dir = working_dir_holder
+            .path()
+            .canonicalize()
+            .context("Could not canonicalize working directory")?;
+
+        let mut locked_apps = Vec::with_capacity(app_sources.len());
+        for (index, app_source) in app_sources.iter().enumerate() {
+            // Keep each app's assets apart so that identically-named files
+            // in different apps don't collide.
+            let app_working_dir = working_dir.join(format!("app-{index}"));
+            std::fs::create_dir_all(&app_working_dir).with_context(|| {
+                format!("Could not create working directory {app_working_dir:?}")
+            })?;
+            let resolved_app_source = self
+                .resolve_app_source(app_source, &app_working_dir)
+                .await?;
+            let mut locked_app = self
+                .load_resolved_app_source(resolved_app_source, &app_working_dir)
+                .await
+                .with_context(|| format!("Failed to load {app_source}"))?;
+            self.update_locked_app(&mut locked_app)?;
+            locked_apps.push(locked_app);
+        }
+
+        let mut locked_app = multi_app::merge_apps(locked_apps, self.route_by)?;
+        let dev_services = DevServices::start(&self.with)?;
+        dev_services.update_locked_app(&mut locked_app)?;
+
+        let run_opts = RunTriggerOpts {
+            locked_app,
+            working_dir,
+            local_app_dir: None,
+        };
+
+        self.run_trigger(trigger_cmd, Some(run_opts)).await
+    }
+
     fn get_canonical_working_dir(&self) -> Result<WorkingDirectory, anyhow::Error> {
         let working_dir_holder = match &self.tmp {
             None => WorkingDirectory::Temporary(TempDir::with_prefix("spinup-")?),
@@ -205,11 +403,18 @@ impl UpCommand {
             local_app_dir,
         }) = opts
         {
+            permissions::print_summary(&locked_app, self.allow_transient_write);
             let locked_url = self.write_locked_app(&locked_app, &working_dir).await?;
 
             cmd.env(SPIN_LOCKED_URL, locked_url)
                 .env(SPIN_WORKING_DIR, &working_dir)
                 .args(&self.trigger_args);
+            if self.allow_transient_write {
+                cmd.arg("--allow-transient-write");
+            }
+            if let Some(secret) = &self.webhook_secret {
+                cmd.env(REDEPLOY_SECRET_ENV, secret);
+            }
 
             if let Some(local_app_dir) = local_app_dir {
                 cmd.env(SPIN_LOCAL_APP_DIR, local_app_dir);
@@ -243,11 +448,15 @@ impl UpCommand {
     }
 
     fn app_source(&self) -> AppSource {
-        match (&self.app_source, &self.file_source, &self.registry_source) {
-            (None, None, None) => self.default_manifest_or_none(),
-            (Some(source), None, None) => AppSource::infer_source(source),
-            (None, Some(file), None) => AppSource::infer_file_source(file.to_owned()),
-            (None, None, Some(reference)) => AppSource::OciRegistry(reference.to_owned()),
+        match (
+            self.app_source.as_slice(),
+            &self.file_source,
+            &self.registry_source,
+        ) {
+            ([], None, None) => self.default_manifest_or_none(),
+            ([source], None, None) => AppSource::infer_source(source),
+            ([], Some(file), None) => AppSource::infer_file_source(file.to_owned()),
+            ([], None, Some(reference)) => AppSource::OciRegistry(reference.to_owned()),
             _ => AppSource::unresolvable("More than one application source was specified"),
         }
     }
@@ -302,10 +511,16 @@ impl UpCommand {
         Ok(match &app_source {
             AppSource::File(path) => ResolvedAppSource::File {
                 manifest_path: path.clone(),
-                manifest: spin_manifest::manifest_from_file(path)?,
+                manifest: spin_manifest::manifest_from_file_with_overlays(
+                    path,
+                    &self.overlay_paths(path)?,
+                )?,
             },
             // TODO: We could make the `--help` experience a little faster if
             // we could fetch just the locked app JSON at this stage.
+            AppSource::OciRegistry(_) | AppSource::Archive(_) if !self.overlays.is_empty() => {
+                bail!("--overlay can only be used with local applications")
+            }
             AppSource::OciRegistry(reference) => {
                 let mut client = spin_oci::Client::new(self.insecure, None)
                     .await
@@ -316,6 +531,12 @@ impl UpCommand {
                     .await?;
                 ResolvedAppSource::OciRegistry { locked_app }
             }
+            AppSource::Archive(path) => {
+                let locked_app = ArchiveLoader::new(working_dir)
+                    .load_app(path)
+                    .with_context(|| format!("Failed to load archive {path:?}"))?;
+                ResolvedAppSource::Archive { locked_app }
+            }
             AppSource::Unresolvable(err) => bail!("{err}"),
             AppSource::None => bail!("Internal error - should have shown help"),
         })
@@ -334,21 +555,43 @@ impl UpCommand {
                 } else {
                     FilesMountStrategy::Copy(working_dir.join("assets"))
                 };
-                spin_loader::from_file(&manifest_path, files_mount_strategy)
-                    .await
-                    .with_context(|| format!("Failed to load manifest from {manifest_path:?}"))
+                let overlays = self.overlay_paths(&manifest_path)?;
+                spin_loader::from_file_with_overlays(
+                    &manifest_path,
+                    &overlays,
+                    files_mount_strategy,
+                )
+                .await
+                .with_context(|| format!("Failed to load manifest from {manifest_path:?}"))
             }
-            ResolvedAppSource::OciRegistry { locked_app } => Ok(locked_app),
+            ResolvedAppSource::OciRegistry { locked_app }
+            | ResolvedAppSource::Archive { locked_app } => Ok(locked_app),
         }
     }
 
-    fn update_locked_app(&self, locked_app: &mut LockedApp) {
+    // The overlay files named by --overlay for the given manifest.
+    fn overlay_paths(&self, manifest_path: &Path) -> Result<Vec<PathBuf>> {
+        self.overlays
+            .iter()
+            .map(|name| {
+                let path = spin_manifest::overlay::overlay_path(manifest_path, name);
+                if !path.exists() {
+                    bail!("No overlay '{name}': {path:?} does not exist");
+                }
+                Ok(path)
+            })
+            .collect()
+    }
+
+    fn update_locked_app(&self, locked_app: &mut LockedApp) -> Result<()> {
         // Apply --env to component environments
         if !self.env.is_empty() {
             for component in locked_app.components.iter_mut() {
                 component.env.extend(self.env.iter().cloned());
             }
         }
+        // Apply --deny and --allow to component permissions
+        permissions::apply_overrides(locked_app, &self.deny, &self.allow)
     }
 }
 
@@ -447,7 +690,7 @@ mod test {
         let file = repo_path("examples/http-rust/spin.toml");
 
         let source = UpCommand {
-            app_source: Some(file.clone()),
+            app_source: vec![file.clone()],
             ..Default::default()
         }
         .app_source();
@@ -460,7 +703,7 @@ mod test {
         let dir = repo_path("examples/http-rust");
 
         let source = UpCommand {
-            app_source: Some(dir.clone()),
+            app_source: vec![dir.clone()],
             ..Default::default()
         }
         .app_source();
@@ -471,12 +714,27 @@ mod test {
         );
     }
 
+    #[test]
+    fn can_infer_archives() {
+        let dir = tempfile::tempdir().unwrap();
+        let archive = dir.path().join("app.spin");
+        std::fs::write(&archive, "").unwrap();
+
+        let source = UpCommand {
+            app_source: vec![archive.to_string_lossy().into_owned()],
+            ..Default::default()
+        }
+        .app_source();
+
+        assert_eq!(AppSource::Archive(archive), source);
+    }
+
     #[test]
     fn reject_nonexistent_files() {
         let file = repo_path("src/commands/biscuits.toml");
 
         let source = UpCommand {
-            app_source: Some(file),
+            app_source: vec![file],
             ..Default::default()
         }
         .app_source();
@@ -489,7 +747,7 @@ mod test {
         let file = "zoink/honk/biscuits.toml".to_owned(); // NOBODY CREATE THIS OKAY
 
         let source = UpCommand {
-            app_source: Some(file),
+            app_source: vec![file],
             ..Default::default()
         }
         .app_source();
@@ -502,7 +760,7 @@ mod test {
         let dir = repo_path("src/commands");
 
         let source = UpCommand {
-            app_source: Some(dir),
+            app_source: vec![dir],
             ..Default::default()
         }
         .app_source();
@@ -515,7 +773,7 @@ mod test {
         let reference = "ghcr.io/fermyon/noodles:v1".to_owned();
 
         let source = UpCommand {
-            app_source: Some(reference.clone()),
+            app_source: vec![reference.clone()],
             ..Default::default()
         }
         .app_source();
@@ -529,7 +787,7 @@ mod test {
         let reference = "docker.io/fermyon/noodles".to_owned();
 
         let source = UpCommand {
-            app_source: Some(reference.clone()),
+            app_source: vec![reference.clone()],
             ..Default::default()
         }
         .app_source();
@@ -542,7 +800,7 @@ mod test {
         let garbage = repo_path("ftp://🤡***🤡 HELLO MR CLOWN?!");
 
         let source = UpCommand {
-            app_source: Some(garbage),
+            app_source: vec![garbage],
             ..Default::default()
         }
         .app_source();
@@ -567,6 +825,13 @@ mod test {
         .expect("Failed to parse --from with trigger option");
     }
 
+    #[test]
+    fn parses_multiple_sources() {
+        let up = UpCommand::try_parse_from(["up", "-f", "app1/", "--from", "app2/"])
+            .expect("Failed to parse multiple --from");
+        assert_eq!(vec!["app1/", "app2/"], up.app_source);
+    }
+
     #[test]
     fn parses_typed_source() {
         UpCommand::try_parse_from(["up", "--from-registry", "ghcr.io/example/test:v1"])
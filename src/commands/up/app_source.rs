@@ -11,6 +11,7 @@ use spin_manifest::schema::v2::AppManifest;
 #[derive(Debug, PartialEq, Eq)]
 pub enum AppSource {
     File(PathBuf),
+    Archive(PathBuf),
     OciRegistry(String),
     Unresolvable(String),
     None,
@@ -29,7 +30,11 @@ impl AppSource {
     }
 
     pub fn infer_file_source(path: impl Into<PathBuf>) -> Self {
-        match spin_common::paths::resolve_manifest_file_path(path.into()) {
+        let path = path.into();
+        if spin_archive::is_archive(&path) {
+            return Self::Archive(path);
+        }
+        match spin_common::paths::resolve_manifest_file_path(path) {
             Ok(file) => Self::File(file),
             Err(e) => Self::Unresolvable(e.to_string()),
         }
@@ -61,6 +66,7 @@ impl std::fmt::Display for AppSource {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Self::File(path) => write!(f, "local app {path:?}"),
+            Self::Archive(path) => write!(f, "archived app {path:?}"),
             Self::OciRegistry(reference) => write!(f, "remote app {reference:?}"),
             Self::Unresolvable(s) => write!(f, "unknown app source: {s:?}"),
             Self::None => write!(f, "<no source>"),
@@ -79,6 +85,9 @@ pub enum ResolvedAppSource {
     OciRegistry {
         locked_app: LockedApp,
     },
+    Archive {
+        locked_app: LockedApp,
+    },
 }
 
 impl ResolvedAppSource {
@@ -87,7 +96,8 @@ impl ResolvedAppSource {
             ResolvedAppSource::File { manifest, .. } => {
                 manifest.triggers.keys().collect::<HashSet<_>>()
             }
-            ResolvedAppSource::OciRegistry { locked_app } => locked_app
+            ResolvedAppSource::OciRegistry { locked_app }
+            | ResolvedAppSource::Archive { locked_app } => locked_app
                 .triggers
                 .iter()
                 .map(|t| &t.trigger_type)
@@ -0,0 +1,223 @@
+//! Running an application from a Git repository, as `spin up --from-git`
+//! does, and redeploying it whenever its branch moves on.
+//!
+//! Each revision is cloned into a directory of its own and built there. The
+//! first is run as usual; later ones are handed to the running trigger
+//! through its upgrade socket, so that a revision which fails to build or
+//! load leaves the previous one running.
+//!
+//! With `--webhook-secret`, the HTTP trigger also accepts signed requests to
+//! `/__spin/redeploy`, which it passes on over a socket of our own so that
+//! the branch is checked at once rather than at the next poll.
+
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+use anyhow::{bail, Context, Result};
+use tokio::{process::Command, sync::Notify};
+
+/// The environment variable the HTTP trigger reads the redeploy webhook's
+/// secret from.
+pub const REDEPLOY_SECRET_ENV: &str = "SPIN_REDEPLOY_SECRET";
+
+/// A branch of a Git repository.
+#[derive(Clone, Debug)]
+pub struct GitSource {
+    pub url: String,
+    /// The branch, or the remote's default branch if `None`.
+    pub branch: Option<String>,
+}
+
+impl GitSource {
+    /// Returns the commit the branch currently points at.
+    pub async fn remote_revision(&self) -> Result<String> {
+        let reference = match &self.branch {
+            Some(branch) => format!("refs/heads/{branch}"),
+            None => "HEAD".to_owned(),
+        };
+        let output = git(["ls-remote", self.url.as_str(), reference.as_str()]).await?;
+        output
+            .split_whitespace()
+            .next()
+            .map(str::to_owned)
+            .with_context(|| format!("{} has no {reference}", self.url))
+    }
+
+    /// Clones the branch into `dir`, returning the commit it holds.
+    pub async fn checkout(&self, dir: &Path) -> Result<String> {
+        let dir = dir.to_string_lossy();
+        let mut args = vec!["clone", "--quiet", "--depth", "1"];
+        if let Some(branch) = &self.branch {
+            args.extend(["--branch", branch.as_str()]);
+        }
+        args.extend([self.url.as_str(), &*dir]);
+        git(args).await?;
+        let revision = git(["-C", &*dir, "rev-parse", "HEAD"]).await?;
+        Ok(revision.trim().to_owned())
+    }
+}
+
+/// Polls `source` every `interval`, and whenever a redeploy is requested,
+/// and redeploys the application through the trigger's `upgrade_socket`
+/// when the branch moves on from `revision`.
+pub async fn watch(
+    source: GitSource,
+    mut revision: String,
+    interval: Duration,
+    redeploy_requests: Arc<Notify>,
+    checkouts_dir: PathBuf,
+    upgrade_socket: PathBuf,
+) {
+    for generation in 1.. {
+        tokio::select! {
+            _ = tokio::time::sleep(interval) => {}
+            _ = redeploy_requests.notified() => {}
+        }
+        let latest = match source.remote_revision().await {
+            Ok(latest) => latest,
+            Err(e) => {
+                terminal::warn!("Failed to check {} for changes: {e:#}", source.url);
+                continue;
+            }
+        };
+        if latest == revision {
+            continue;
+        }
+
+        let checkout = checkouts_dir.join(format!("checkout-{generation}"));
+        match redeploy(&source, &checkout, &upgrade_socket).await {
+            Ok(deployed) => terminal::step!("Redeployed", "{} at {}", source.url, short(&deployed)),
+            Err(e) => terminal::error!(
+                "Failed to redeploy {} at {}; the previous revision is still running: {e:#}",
+                source.url,
+                short(&latest)
+            ),
+        }
+        // A revision which failed isn't retried until the branch moves on.
+        revision = latest;
+    }
+}
+
+/// Listens on `socket` for the redeploy requests the HTTP trigger passes
+/// on, notifying `redeploy_requests` of each. Requests which arrive while a
+/// redeploy is under way are coalesced into one more check.
+#[cfg(unix)]
+pub fn listen_for_redeploys(socket: &Path, redeploy_requests: Arc<Notify>) -> Result<()> {
+    let listener = tokio::net::UnixListener::bind(socket)
+        .with_context(|| format!("Couldn't listen on redeploy socket {socket:?}"))?;
+    tokio::spawn(async move {
+        loop {
+            match listener.accept().await {
+                Ok(_) => {
+                    terminal::step!("Checking", "for changes: a redeploy was requested");
+                    redeploy_requests.notify_one();
+                }
+                Err(e) => tracing::warn!("Failed to accept redeploy request: {e:?}"),
+            }
+        }
+    });
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn listen_for_redeploys(_socket: &Path, _redeploy_requests: Arc<Notify>) -> Result<()> {
+    bail!("--webhook-secret is only supported on Unix platforms")
+}
+
+async fn redeploy(source: &GitSource, checkout: &Path, upgrade_socket: &Path) -> Result<String> {
+    let revision = source.checkout(checkout).await?;
+    let manifest = spin_common::paths::resolve_manifest_file_path(checkout)?;
+    spin_build::build(&manifest, &[]).await?;
+    upgrade(upgrade_socket, &manifest).await?;
+    Ok(revision)
+}
+
+/// Asks the trigger listening on `socket` to upgrade to the application at
+/// `manifest`.
+#[cfg(unix)]
+async fn upgrade(socket: &Path, manifest: &Path) -> Result<()> {
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+    let stream = tokio::net::UnixStream::connect(socket)
+        .await
+        .with_context(|| format!("Couldn't connect to upgrade socket {socket:?}"))?;
+    let (reader, mut writer) = stream.into_split();
+    writer
+        .write_all(format!("{}\n", manifest.display()).as_bytes())
+        .await?;
+    let mut response = String::new();
+    BufReader::new(reader).read_line(&mut response).await?;
+    match response.trim() {
+        "OK" => Ok(()),
+        response => bail!("{}", response.trim_start_matches("ERROR: ")),
+    }
+}
+
+#[cfg(not(unix))]
+async fn upgrade(_socket: &Path, _manifest: &Path) -> Result<()> {
+    bail!("--from-git is only supported on Unix platforms")
+}
+
+async fn git<'a>(args: impl IntoIterator<Item = &'a str>) -> Result<String> {
+    let mut command = Command::new("git");
+    command.args(args);
+    let output = command
+        .output()
+        .await
+        .with_context(|| format!("Failed to run {command:?}; is Git installed?"))?;
+    if !output.status.success() {
+        bail!(
+            "{command:?} failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+fn short(revision: &str) -> &str {
+    &revision[..revision.len().min(12)]
+}
+
+/// Parses a poll interval: a number of seconds, optionally suffixed with
+/// `s`, `m` or `h`, e.g. `60s` or `5m`.
+pub fn parse_poll_interval(s: &str) -> Result<Duration> {
+    let (number, unit) = match s.find(|c: char| !c.is_ascii_digit()) {
+        Some(index) => s.split_at(index),
+        None => (s, "s"),
+    };
+    let number: u64 = number
+        .parse()
+        .with_context(|| format!("Invalid poll interval {s:?}"))?;
+    let seconds = match unit {
+        "s" => number,
+        "m" => number * 60,
+        "h" => number * 60 * 60,
+        _ => bail!("Invalid poll interval {s:?}: expected e.g. 60s, 5m or 1h"),
+    };
+    if seconds == 0 {
+        bail!("The poll interval must be at least one second");
+    }
+    Ok(Duration::from_secs(seconds))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn poll_intervals_have_units() {
+        assert_eq!(Duration::from_secs(60), parse_poll_interval("60s").unwrap());
+        assert_eq!(Duration::from_secs(90), parse_poll_interval("90").unwrap());
+        assert_eq!(Duration::from_secs(300), parse_poll_interval("5m").unwrap());
+        assert_eq!(
+            Duration::from_secs(3600),
+            parse_poll_interval("1h").unwrap()
+        );
+        assert!(parse_poll_interval("0s").is_err());
+        assert!(parse_poll_interval("1d").is_err());
+        assert!(parse_poll_interval("s").is_err());
+    }
+}
@@ -0,0 +1,287 @@
+//! Support for serving several applications from a single `spin up`.
+//!
+//! Each application is mounted under a path prefix derived from its name
+//! (e.g. an app named `hello` is served at `/hello/...`), and optionally also
+//! for hosts whose first label is that name (e.g. `hello.example.com/...`).
+//! The applications are merged into a single locked app so that they share
+//! one engine, one component cache and one listener, while each component
+//! keeps its own environment, files and instance state.
+
+use std::collections::HashSet;
+
+use anyhow::{bail, ensure, Context, Result};
+use clap::ValueEnum;
+use serde_json::Value;
+use spin_app::locked::LockedApp;
+use spin_http::config::HttpTriggerConfig;
+use spin_locked_app::values::ValuesMapBuilder;
+
+/// The only trigger type that can currently be served in multi-app mode.
+pub const MULTI_APP_TRIGGER_TYPE: &str = "http";
+
+/// How requests are routed to the applications served together.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum MultiAppRouting {
+    /// By path prefix: an app named `hello` is served at `/hello/...`.
+    #[default]
+    Path,
+    /// By host as well as path prefix: an app named `hello` is also served
+    /// at `hello.example.com/...`, or any other host whose first label is
+    /// the app's name.
+    Host,
+}
+
+/// Merges the given apps into a single HTTP app, with each app mounted under
+/// a path prefix derived from its name.
+pub fn merge_apps(apps: Vec<LockedApp>, routing: MultiAppRouting) -> Result<LockedApp> {
+    ensure!(!apps.is_empty(), "no applications to serve");
+
+    let mut prefixes = HashSet::new();
+    let mut trigger_ids = HashSet::new();
+    let mut component_ids = HashSet::new();
+    let mut merged = LockedApp {
+        spin_lock_version: Default::default(),
+        metadata: Default::default(),
+        variables: Default::default(),
+        triggers: vec![],
+        components: vec![],
+    };
+
+    for app in apps {
+        let name = app_name(&app)?;
+        let prefix = mount_prefix(&name);
+        if !prefixes.insert(prefix.clone()) {
+            bail!("More than one application would be served at '/{prefix}'. Applications served together must have distinct names.");
+        }
+        let base = app_base(&app)?;
+
+        for (var_name, variable) in app.variables {
+            match merged.variables.get(&var_name) {
+                Some(existing)
+                    if existing.default != variable.default
+                        || existing.secret != variable.secret =>
+                {
+                    bail!("Application '{name}' defines variable '{var_name}' differently from another application. Applications served together must agree on shared variables.");
+                }
+                Some(_) => (),
+                None => {
+                    merged.variables.insert(var_name, variable);
+                }
+            }
+        }
+
+        for mut trigger in app.triggers {
+            ensure!(
+                trigger.trigger_type == MULTI_APP_TRIGGER_TYPE,
+                "Application '{name}' uses trigger type '{}'; only '{MULTI_APP_TRIGGER_TYPE}' applications can be served together",
+                trigger.trigger_type
+            );
+            let mut config: HttpTriggerConfig =
+                serde_json::from_value(trigger.trigger_config.clone()).with_context(|| {
+                    format!(
+                        "Application '{name}' has invalid HTTP trigger config for '{}'",
+                        trigger.id
+                    )
+                })?;
+            config.component = prefixed_id(&prefix, &config.component);
+            config.route = prefixed_route(&prefix, &base, &config.route);
+            trigger.id = prefixed_id(&prefix, &trigger.id);
+            if !trigger_ids.insert(trigger.id.clone()) {
+                bail!("Application '{name}' has a trigger which would be served as '{}', as would a trigger of another application. Rename one of the applications or its components.", trigger.id);
+            }
+            trigger.trigger_config = serde_json::to_value(config)?;
+            merged.triggers.push(trigger);
+        }
+
+        for mut component in app.components {
+            let id = prefixed_id(&prefix, &component.id);
+            if !component_ids.insert(id.clone()) {
+                bail!("Component '{}' of application '{name}' would be served as '{id}', as would a component of another application. Rename one of the applications or components.", component.id);
+            }
+            component.id = id;
+            merged.components.push(component);
+        }
+    }
+
+    let hosts = match routing {
+        MultiAppRouting::Path => vec![],
+        MultiAppRouting::Host => {
+            let mut hosts = prefixes.into_iter().collect::<Vec<_>>();
+            hosts.sort();
+            hosts
+        }
+    };
+
+    let mut builder = ValuesMapBuilder::new();
+    builder
+        .string("name", "multi-app")
+        .serializable(
+            "trigger",
+            serde_json::json!({ "type": MULTI_APP_TRIGGER_TYPE, "base": "/", "hosts": hosts }),
+        )?
+        .serializable("triggers", serde_json::json!({ "http": { "base": "/" } }))?;
+    merged.metadata = builder.build();
+
+    Ok(merged)
+}
+
+fn app_name(app: &LockedApp) -> Result<String> {
+    match app.metadata.get("name") {
+        Some(Value::String(name)) if !name.is_empty() => Ok(name.clone()),
+        _ => bail!("Applications served together must each have a name"),
+    }
+}
+
+fn app_base(app: &LockedApp) -> Result<String> {
+    Ok(app
+        .get_metadata(spin_http::trigger::METADATA_KEY)?
+        .map(|m| m.base)
+        .unwrap_or_else(spin_http::trigger::default_base))
+}
+
+/// Converts an application name into a single URL path segment.
+fn mount_prefix(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c.to_ascii_lowercase()
+            } else {
+                '-'
+            }
+        })
+        .collect()
+}
+
+fn prefixed_id(prefix: &str, id: &str) -> String {
+    format!("{prefix}-{id}")
+}
+
+fn prefixed_route(prefix: &str, base: &str, route: &str) -> String {
+    let base = base.trim_matches('/');
+    let route = route.trim_start_matches('/');
+    [prefix, base, route]
+        .into_iter()
+        .filter(|s| !s.is_empty())
+        .fold(String::new(), |acc, s| format!("{acc}/{s}"))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn app(name: &str, base: &str, routes: &[(&str, &str)]) -> LockedApp {
+        let triggers = routes
+            .iter()
+            .map(|(component, route)| {
+                serde_json::json!({
+                    "id": format!("trigger--{component}"),
+                    "trigger_type": "http",
+                    "trigger_config": { "component": component, "route": route },
+                })
+            })
+            .collect::<Vec<_>>();
+        let components = routes
+            .iter()
+            .map(|(component, _)| {
+                serde_json::json!({
+                    "id": component,
+                    "source": { "content_type": "application/wasm" },
+                })
+            })
+            .collect::<Vec<_>>();
+        serde_json::from_value(serde_json::json!({
+            "spin_lock_version": 0,
+            "metadata": { "name": name, "trigger": { "type": "http", "base": base } },
+            "triggers": triggers,
+            "components": components,
+        }))
+        .unwrap()
+    }
+
+    fn routes(app: &LockedApp) -> Vec<(String, String)> {
+        app.triggers
+            .iter()
+            .map(|t| {
+                let config: HttpTriggerConfig =
+                    serde_json::from_value(t.trigger_config.clone()).unwrap();
+                (config.component, config.route)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn merged_routes_are_prefixed_by_app_name() {
+        let merged = merge_apps(
+            vec![
+                app("first", "/", &[("hello", "/hello"), ("all", "/...")]),
+                app("Second App", "/base", &[("hello", "/")]),
+            ],
+            MultiAppRouting::Path,
+        )
+        .unwrap();
+
+        assert_eq!(
+            vec![
+                ("first-hello".to_owned(), "/first/hello".to_owned()),
+                ("first-all".to_owned(), "/first/...".to_owned()),
+                ("second-app-hello".to_owned(), "/second-app/base".to_owned()),
+            ],
+            routes(&merged)
+        );
+        assert!(merged.components.iter().any(|c| c.id == "second-app-hello"));
+    }
+
+    #[test]
+    fn duplicate_app_names_are_rejected() {
+        merge_apps(
+            vec![
+                app("same", "/", &[("a", "/")]),
+                app("same", "/", &[("b", "/")]),
+            ],
+            MultiAppRouting::Path,
+        )
+        .unwrap_err();
+    }
+
+    #[test]
+    fn colliding_component_ids_are_rejected() {
+        // `a` + `b-c` and `a-b` + `c` would both be served as `a-b-c`.
+        let err = merge_apps(
+            vec![
+                app("a", "/", &[("b-c", "/one")]),
+                app("a-b", "/", &[("c", "/two")]),
+            ],
+            MultiAppRouting::Path,
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("a-b-c"), "{err}");
+    }
+
+    #[test]
+    fn host_routing_lists_app_hosts() {
+        let merged = merge_apps(
+            vec![
+                app("second", "/", &[("hello", "/")]),
+                app("first", "/", &[("hello", "/")]),
+            ],
+            MultiAppRouting::Host,
+        )
+        .unwrap();
+        let metadata = merged
+            .get_metadata(spin_http::trigger::METADATA_KEY)
+            .unwrap()
+            .unwrap();
+        assert_eq!(vec!["first", "second"], metadata.hosts);
+
+        let merged = merge_apps(
+            vec![app("first", "/", &[("hello", "/")])],
+            MultiAppRouting::Path,
+        )
+        .unwrap();
+        let metadata = merged
+            .get_metadata(spin_http::trigger::METADATA_KEY)
+            .unwrap()
+            .unwrap();
+        assert!(metadata.hosts.is_empty());
+    }
+}
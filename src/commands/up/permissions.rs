@@ -0,0 +1,284 @@
+//! The permissions an application asks for, and overriding them from the
+//! `spin up` command line.
+//!
+//! A component can only touch what its manifest grants it: the files mounted
+//! into it, the hosts it may connect to, and the key-value stores and SQLite
+//! databases it may open. `spin up` prints these before starting the app, so
+//! that it is obvious what a third-party app can do, and `--deny`/`--allow`
+//! narrow or widen them without editing the manifest.
+
+use std::{fmt::Display, str::FromStr};
+
+use anyhow::{bail, Result};
+use serde_json::Value;
+use spin_app::{
+    locked::{LockedApp, LockedComponent},
+    MetadataKey,
+};
+use spin_key_value::KEY_VALUE_STORES_KEY;
+use spin_outbound_networking::ALLOWED_HOSTS_KEY;
+use spin_sqlite::DATABASES_KEY;
+
+/// A kind of permission a component may be granted.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PermissionKind {
+    /// Files mounted into the component, by guest path.
+    Files,
+    /// Hosts the component may make outbound connections to.
+    Outbound,
+    /// Key-value stores the component may open.
+    KeyValue,
+    /// SQLite databases the component may open.
+    Sqlite,
+}
+
+impl PermissionKind {
+    fn metadata_key(&self) -> Option<&'static str> {
+        match self {
+            Self::Files => None,
+            Self::Outbound => Some(key_name(&ALLOWED_HOSTS_KEY)),
+            Self::KeyValue => Some(key_name(&KEY_VALUE_STORES_KEY)),
+            Self::Sqlite => Some(key_name(&DATABASES_KEY)),
+        }
+    }
+}
+
+fn key_name<T>(key: &'static MetadataKey<T>) -> &'static str {
+    key.as_ref()
+}
+
+impl Display for PermissionKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Files => "files",
+            Self::Outbound => "outbound",
+            Self::KeyValue => "key-value",
+            Self::Sqlite => "sqlite",
+        })
+    }
+}
+
+/// A permission given to `--allow` or `--deny`: a kind, and optionally the
+/// single value (path, host, store or database) it applies to, e.g.
+/// `outbound=https://example.com` or `key-value`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Permission {
+    kind: PermissionKind,
+    value: Option<String>,
+}
+
+impl FromStr for Permission {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (kind, value) = match s.split_once('=') {
+            Some((kind, value)) => (kind, Some(value.to_owned())),
+            None => (s, None),
+        };
+        let kind = match kind {
+            "files" => PermissionKind::Files,
+            "outbound" => PermissionKind::Outbound,
+            "key-value" => PermissionKind::KeyValue,
+            "sqlite" => PermissionKind::Sqlite,
+            _ => bail!(
+                "Unknown permission '{kind}': expected one of files, outbound, key-value or sqlite"
+            ),
+        };
+        if value.as_deref() == Some("") {
+            bail!("Permission '{kind}=' is missing a value");
+        }
+        Ok(Self { kind, value })
+    }
+}
+
+/// Removes the denied permissions from every component, then adds the
+/// allowed ones.
+pub fn apply_overrides(
+    app: &mut LockedApp,
+    deny: &[Permission],
+    allow: &[Permission],
+) -> Result<()> {
+    for permission in allow {
+        if permission.kind == PermissionKind::Files {
+            bail!(
+                "Files can't be granted with --allow; add a `files` entry to the manifest instead"
+            );
+        }
+        if permission.value.is_none() {
+            bail!(
+                "--allow needs a value, e.g. `--allow {}=<VALUE>`",
+                permission.kind
+            );
+        }
+    }
+
+    for component in app.components.iter_mut() {
+        for permission in deny {
+            deny_permission(component, permission);
+        }
+        for permission in allow {
+            allow_permission(component, permission);
+        }
+    }
+    Ok(())
+}
+
+fn deny_permission(component: &mut LockedComponent, permission: &Permission) {
+    let denied = |v: &str| permission.value.as_deref().map_or(true, |p| p == v);
+    match permission.kind.metadata_key() {
+        None => component
+            .files
+            .retain(|f| !denied(&f.path.to_string_lossy())),
+        Some(key) => {
+            if let Some(Value::Array(values)) = component.metadata.get_mut(key) {
+                values.retain(|v| !v.as_str().is_some_and(denied));
+            }
+        }
+    }
+}
+
+fn allow_permission(component: &mut LockedComponent, permission: &Permission) {
+    let (Some(key), Some(value)) = (permission.kind.metadata_key(), &permission.value) else {
+        return;
+    };
+    let entry = component
+        .metadata
+        .entry(key)
+        .or_insert_with(|| Value::Array(vec![]));
+    if let Value::Array(values) = entry {
+        if !values.iter().any(|v| v.as_str() == Some(value)) {
+            values.push(Value::String(value.clone()));
+        }
+    }
+}
+
+/// Prints what each component of the app is permitted to do.
+pub fn print_summary(app: &LockedApp, transient_write: bool) {
+    println!("Permissions:");
+    for component in &app.components {
+        println!("  {}", component.id);
+        let access = if transient_write {
+            "read-write, changes discarded on exit"
+        } else {
+            "read-only"
+        };
+        let files = component
+            .files
+            .iter()
+            .map(|f| format!("{} ({access})", f.path.display()))
+            .collect::<Vec<_>>();
+        print_permission(PermissionKind::Files, &files);
+        for kind in [
+            PermissionKind::Outbound,
+            PermissionKind::KeyValue,
+            PermissionKind::Sqlite,
+        ] {
+            print_permission(kind, &metadata_strings(component, kind));
+        }
+    }
+}
+
+fn print_permission(kind: PermissionKind, values: &[String]) {
+    if values.is_empty() {
+        println!("    {kind}: none");
+    } else {
+        println!("    {kind}: {}", values.join(", "));
+    }
+}
+
+fn metadata_strings(component: &LockedComponent, kind: PermissionKind) -> Vec<String> {
+    let Some(Value::Array(values)) = kind.metadata_key().and_then(|k| component.metadata.get(k))
+    else {
+        return vec![];
+    };
+    values
+        .iter()
+        .filter_map(|v| v.as_str().map(str::to_owned))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn component(metadata: Value) -> LockedComponent {
+        serde_json::from_value(serde_json::json!({
+            "id": "test",
+            "metadata": metadata,
+            "source": { "content_type": "application/wasm", "source": "file:///test.wasm" },
+            "files": [
+                { "source": "file:///assets", "path": "/assets" },
+                { "source": "file:///data", "path": "/data" },
+            ],
+        }))
+        .unwrap()
+    }
+
+    fn app(component: LockedComponent) -> LockedApp {
+        LockedApp {
+            spin_lock_version: Default::default(),
+            metadata: Default::default(),
+            variables: Default::default(),
+            triggers: vec![],
+            components: vec![component],
+        }
+    }
+
+    #[test]
+    fn parses_permissions() {
+        let permission: Permission = "outbound=https://example.com".parse().unwrap();
+        assert_eq!(PermissionKind::Outbound, permission.kind);
+        assert_eq!(Some("https://example.com"), permission.value.as_deref());
+
+        let permission: Permission = "key-value".parse().unwrap();
+        assert_eq!(PermissionKind::KeyValue, permission.kind);
+        assert_eq!(None, permission.value);
+
+        "network".parse::<Permission>().unwrap_err();
+        "sqlite=".parse::<Permission>().unwrap_err();
+    }
+
+    #[test]
+    fn deny_removes_one_or_all_values() {
+        let mut app = app(component(serde_json::json!({
+            "allowed_outbound_hosts": ["https://a.example", "https://b.example"],
+            "key_value_stores": ["default"],
+        })));
+        let deny = [
+            "outbound=https://a.example".parse().unwrap(),
+            "key-value".parse().unwrap(),
+            "files=/data".parse().unwrap(),
+        ];
+        apply_overrides(&mut app, &deny, &[]).unwrap();
+
+        let component = &app.components[0];
+        assert_eq!(
+            vec!["https://b.example"],
+            metadata_strings(component, PermissionKind::Outbound)
+        );
+        assert!(metadata_strings(component, PermissionKind::KeyValue).is_empty());
+        assert_eq!(1, component.files.len());
+        assert_eq!("/assets", component.files[0].path.to_string_lossy());
+    }
+
+    #[test]
+    fn allow_adds_values_once() {
+        let mut app = app(component(serde_json::json!({})));
+        let allow = [
+            "sqlite=default".parse().unwrap(),
+            "sqlite=default".parse().unwrap(),
+        ];
+        apply_overrides(&mut app, &[], &allow).unwrap();
+        assert_eq!(
+            vec!["default"],
+            metadata_strings(&app.components[0], PermissionKind::Sqlite)
+        );
+    }
+
+    #[test]
+    fn allow_needs_a_value_and_cannot_grant_files() {
+        let mut app = app(component(serde_json::json!({})));
+        apply_overrides(&mut app, &[], &["outbound".parse().unwrap()]).unwrap_err();
+        apply_overrides(&mut app, &[], &["files=/etc".parse().unwrap()]).unwrap_err();
+    }
+}
@@ -0,0 +1,7 @@
+export async function handleRequest(request) {
+    return {
+        status: 200,
+        headers: { "content-type": "text/plain" },
+        body: "Hello from JS-SDK"
+    }
+}
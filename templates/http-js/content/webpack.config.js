@@ -0,0 +1,22 @@
+const path = require('path');
+
+module.exports = {
+    entry: './src/index.js',
+    resolve: {
+        extensions: ['.js'],
+    },
+    output: {
+        path: path.resolve(__dirname, './'),
+        filename: 'dist/spin.js',
+        module: true,
+        library: {
+            type: "module",
+        }
+    },
+    experiments: {
+        outputModule: true,
+    },
+    optimization: {
+        minimize: false
+    },
+};
@@ -0,0 +1,9 @@
+from spin_sdk import http
+from spin_sdk.http import Request, Response
+
+from spin_helpers import text
+
+
+class IncomingHandler(http.IncomingHandler):
+    def handle_request(self, request: Request) -> Response:
+        return text("Hello from Python!")
@@ -0,0 +1,47 @@
+"""Shortcuts over the Spin SDK for the calls most handlers make.
+
+The full SDK is available as `spin_sdk`; these cover responding to a
+request, using the default key-value store and making outbound requests.
+"""
+
+import json
+from typing import Any, Dict, Optional
+
+from spin_sdk import http, key_value
+from spin_sdk.http import Request, Response
+
+
+def text(body: str, status: int = 200) -> Response:
+    """A plain text response."""
+    return Response(status, {"content-type": "text/plain"}, bytes(body, "utf-8"))
+
+
+def json_response(value: Any, status: int = 200) -> Response:
+    """A JSON response."""
+    return Response(
+        status,
+        {"content-type": "application/json"},
+        bytes(json.dumps(value), "utf-8"),
+    )
+
+
+def fetch(
+    url: str,
+    method: str = "GET",
+    headers: Optional[Dict[str, str]] = None,
+    body: Optional[bytes] = None,
+) -> Response:
+    """Send an outbound HTTP request. The host must be listed in the
+    component's `allowed_outbound_hosts`."""
+    return http.send(Request(method, url, headers or {}, body))
+
+
+def kv_get(key: str) -> Optional[bytes]:
+    """Get a value from the default key-value store, or None if it isn't set.
+    The component's `key_value_stores` must include "default"."""
+    return key_value.open_default().get(key)
+
+
+def kv_set(key: str, value: bytes) -> None:
+    """Set a value in the default key-value store."""
+    key_value.open_default().set(key, value)
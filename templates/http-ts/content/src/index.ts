@@ -0,0 +1,9 @@
+import { HandleRequest, HttpRequest, HttpResponse } from "@fermyon/spin-sdk"
+
+export const handleRequest: HandleRequest = async function (request: HttpRequest): Promise<HttpResponse> {
+    return {
+        status: 200,
+        headers: { "content-type": "text/plain" },
+        body: "Hello from TS-SDK"
+    }
+}
@@ -0,0 +1,31 @@
+const path = require('path');
+
+module.exports = {
+    entry: './src/index.ts',
+    module: {
+        rules: [
+            {
+                test: /\.tsx?$/,
+                use: 'ts-loader',
+                exclude: /node_modules/,
+            },
+        ],
+    },
+    resolve: {
+        extensions: ['.tsx', '.ts', '.js'],
+    },
+    output: {
+        path: path.resolve(__dirname, './'),
+        filename: 'dist/spin.js',
+        module: true,
+        library: {
+            type: "module",
+        }
+    },
+    experiments: {
+        outputModule: true,
+    },
+    optimization: {
+        minimize: false
+    },
+};
@@ -1,6 +1,7 @@
 #![allow(dead_code)]
 use anyhow::Result;
 use spin_sdk::{
+    http::{extract, Request, Response, Router},
     http_component,
     pg::{self, Decode},
 };
@@ -40,19 +41,16 @@ struct GeneralRow<'a> {
 }
 
 #[http_component]
-fn process(req: http::Request<()>) -> Result<http::Response<String>> {
-    match req.uri().path() {
-        "/test_character_types" => test_character_types(req),
-        "/test_numeric_types" => test_numeric_types(req),
-        "/test_general_types" => test_general_types(req),
-        "/pg_backend_pid" => pg_backend_pid(req),
-        _ => Ok(http::Response::builder()
-            .status(404)
-            .body("Not found".into())?),
-    }
+fn process(req: Request) -> Response {
+    let mut router = Router::new();
+    router.get("/test_character_types", extract(test_character_types));
+    router.get("/test_numeric_types", extract(test_numeric_types));
+    router.get("/test_general_types", extract(test_general_types));
+    router.get("/pg_backend_pid", extract(pg_backend_pid));
+    router.handle(req)
 }
 
-fn test_numeric_types(_req: http::Request<()>) -> Result<http::Response<String>> {
+fn test_numeric_types() -> Result<http::Response<String>> {
     let address = std::env::var(DB_URL_ENV)?;
     let conn = pg::Connection::open(&address)?;
 
@@ -154,7 +152,7 @@ fn test_numeric_types(_req: http::Request<()>) -> Result<http::Response<String>>
     Ok(http::Response::builder().status(200).body(response)?)
 }
 
-fn test_character_types(_req: http::Request<()>) -> Result<http::Response<String>> {
+fn test_character_types() -> Result<http::Response<String>> {
     let address = std::env::var(DB_URL_ENV)?;
     let conn = pg::Connection::open(&address)?;
 
@@ -218,7 +216,7 @@ fn test_character_types(_req: http::Request<()>) -> Result<http::Response<String
     Ok(http::Response::builder().status(200).body(response)?)
 }
 
-fn test_general_types(_req: http::Request<()>) -> Result<http::Response<String>> {
+fn test_general_types() -> Result<http::Response<String>> {
     let address = std::env::var(DB_URL_ENV)?;
     let conn = pg::Connection::open(&address)?;
 
@@ -282,7 +280,7 @@ fn test_general_types(_req: http::Request<()>) -> Result<http::Response<String>>
     Ok(http::Response::builder().status(200).body(response)?)
 }
 
-fn pg_backend_pid(_req: http::Request<()>) -> Result<http::Response<String>> {
+fn pg_backend_pid() -> Result<http::Response<String>> {
     let address = std::env::var(DB_URL_ENV)?;
     let conn = pg::Connection::open(&address)?;
     let sql = "SELECT pg_backend_pid()";